@@ -1,11 +1,22 @@
 use crate::async_dedup_index;
 use crate::canister::utils::deleted_canister::WrappedContextCanisters;
-use crate::config::AppConfig;
+use crate::config::{AppConfig, NsfwConfig};
 use crate::consts::{NSFW_SERVER_URL, YRAL_METADATA_URL};
+#[cfg(not(feature = "local-bin"))]
+use crate::duplicate_video::redis_hash_index::RedisBackedVideoHashIndex;
+use crate::duplicate_video::video_hash_index::VideoHashIndex;
+use crate::events::subscribe::EventSubscribers;
 use crate::metrics::{init_metrics, CfMetricTx};
 use crate::qstash::client::QStashClient;
 use crate::qstash::QStashState;
-use crate::types::RedisPool;
+use crate::types::{DelegatedIdentityWire, RedisPool};
+use crate::user::utils::get_agent_from_delegated_identity_wire;
+use crate::utils::agent_pool::DelegatedIdentityPool;
+use crate::utils::geoip::GeoIpResolver;
+use crate::utils::notifications::{init_notification_backends, NotificationBackends};
+#[cfg(not(feature = "local-bin"))]
+use crate::utils::token_cache::token_expires_at;
+use crate::utils::token_cache::GoogleTokenCache;
 use anyhow::{anyhow, Context, Result};
 use candid::Principal;
 use firestore::{FirestoreDb, FirestoreDbOptions};
@@ -14,6 +25,7 @@ use google_cloud_auth::credentials::service_account::Builder as CredBuilder;
 use google_cloud_bigquery::client::{Client, ClientConfig};
 use hyper_util::client::legacy::connect::HttpConnector;
 use ic_agent::Agent;
+use std::collections::HashMap;
 use std::env;
 use std::sync::Arc;
 use tonic::transport::{Channel, ClientTlsConfig};
@@ -45,16 +57,26 @@ pub struct AppState {
     #[cfg(not(feature = "local-bin"))]
     pub alloydb_client: AlloyDbInstance,
     #[cfg(not(feature = "local-bin"))]
-    pub dedup_index_ctx: async_dedup_index::WrappedContext,
+    pub dedup_index_ctx: async_dedup_index::AsyncDedupIndex,
     #[cfg(not(feature = "local-bin"))]
     pub canister_backup_redis_pool: RedisPool,
     #[cfg(not(feature = "local-bin"))]
     pub canisters_ctx: WrappedContextCanisters,
+    pub test_principals: Vec<Principal>,
+    pub video_hash_index: Arc<tokio::sync::RwLock<VideoHashIndex>>,
+    #[cfg(not(feature = "local-bin"))]
+    pub google_token_cache: Arc<GoogleTokenCache>,
+    pub event_subscribers: Arc<EventSubscribers>,
+    pub agent_pool: Arc<DelegatedIdentityPool>,
+    pub notification_backends: Arc<NotificationBackends>,
+    pub ga_event_mapping: HashMap<String, String>,
+    pub nsfw_config: NsfwConfig,
+    pub geoip: GeoIpResolver,
 }
 
 impl AppState {
     pub async fn new(app_config: AppConfig) -> Self {
-        AppState {
+        let state = AppState {
             yral_metadata_client: init_yral_metadata_client(&app_config),
             agent: init_agent().await,
             #[cfg(not(feature = "local-bin"))]
@@ -80,27 +102,64 @@ impl AppState {
             canister_backup_redis_pool: init_canister_backup_redis_pool().await,
             #[cfg(not(feature = "local-bin"))]
             canisters_ctx: init_canisters_ctx().await,
-        }
+            test_principals: init_test_principals(&app_config),
+            video_hash_index: Arc::new(tokio::sync::RwLock::new(VideoHashIndex::new())),
+            #[cfg(not(feature = "local-bin"))]
+            google_token_cache: Arc::new(GoogleTokenCache::new()),
+            event_subscribers: Arc::new(EventSubscribers::new()),
+            agent_pool: Arc::new(DelegatedIdentityPool::new()),
+            notification_backends: Arc::new(init_notification_backends()),
+            nsfw_config: app_config.nsfw_config(),
+            ga_event_mapping: app_config.ga_event_mapping,
+            geoip: GeoIpResolver::new(),
+        };
+
+        // loaded in the background, serving doesn't wait on redis
+        #[cfg(not(feature = "local-bin"))]
+        tokio::spawn(state.redis_video_hash_index().keep_in_sync());
+
+        state
     }
 
-    pub async fn get_access_token(&self, scopes: &[&str]) -> String {
+    pub async fn get_access_token(&self, scopes: &[&str]) -> Result<String> {
         #[cfg(feature = "local-bin")]
         {
-            "localtoken".into()
+            let _ = scopes;
+            Ok("localtoken".into())
         }
 
         #[cfg(not(feature = "local-bin"))]
         {
             let auth = &self.auth;
-            let token = auth.token(scopes).await.unwrap();
-
-            match token.token() {
-                Some(t) => t.to_string(),
-                _ => panic!("No access token found"),
-            }
+            self.google_token_cache
+                .get_or_refresh(scopes, || async move {
+                    let token = auth.token(scopes).await?;
+                    let expires_at = token_expires_at(
+                        token.expiration_time().map(|t| t.unix_timestamp()),
+                        chrono::Utc::now().timestamp(),
+                        std::time::Instant::now(),
+                    );
+
+                    match token.token() {
+                        Some(t) => Ok::<_, anyhow::Error>((t.to_string(), expires_at)),
+                        None => Err(anyhow!("No access token found for scopes {:?}", scopes)),
+                    }
+                })
+                .await
         }
     }
 
+    /// Agent acting as the delegated identity, reused while the same identity keeps calling
+    pub fn get_pooled_agent(
+        &self,
+        identity_wire: &DelegatedIdentityWire,
+    ) -> Result<Agent, anyhow::Error> {
+        let key = DelegatedIdentityPool::pool_key(identity_wire)?;
+        self.agent_pool.get_or_insert_with(key, || {
+            get_agent_from_delegated_identity_wire(identity_wire)
+        })
+    }
+
     pub async fn get_individual_canister_by_user_principal(
         &self,
         user_principal: Principal,
@@ -122,6 +181,14 @@ impl AppState {
     pub fn individual_user(&self, user_canister: Principal) -> IndividualUserTemplate<'_> {
         IndividualUserTemplate(user_canister, &self.agent)
     }
+
+    #[cfg(not(feature = "local-bin"))]
+    pub fn redis_video_hash_index(&self) -> RedisBackedVideoHashIndex {
+        RedisBackedVideoHashIndex::new(
+            self.canister_backup_redis_pool.clone(),
+            self.video_hash_index.clone(),
+        )
+    }
 }
 
 pub fn init_yral_metadata_client(conf: &AppConfig) -> MetadataClient<true> {
@@ -191,6 +258,14 @@ pub async fn init_firestoredb() -> FirestoreDb {
         .expect("failed to create firestore db")
 }
 
+pub fn init_test_principals(app_config: &AppConfig) -> Vec<Principal> {
+    app_config
+        .test_principals
+        .iter()
+        .map(|p| Principal::from_text(p.trim()).expect("test_principals to be valid principals"))
+        .collect()
+}
+
 pub fn init_qstash() -> QStashState {
     let qstash_key =
         env::var("QSTASH_CURRENT_SIGNING_KEY").expect("QSTASH_CURRENT_SIGNING_KEY is required");
@@ -218,8 +293,8 @@ pub async fn init_qstash_client() -> QStashClient {
     QStashClient::new(auth_token.as_str())
 }
 
-pub async fn init_dedup_index_ctx() -> async_dedup_index::WrappedContext {
-    async_dedup_index::WrappedContext::new().expect("Stdb dedup index to be connected")
+pub async fn init_dedup_index_ctx() -> async_dedup_index::AsyncDedupIndex {
+    async_dedup_index::AsyncDedupIndex::new().expect("Stdb dedup index to be connected")
 }
 
 async fn init_alloydb_client() -> AlloyDbInstance {
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use serde_json::json;
+
+use super::{is_duplicate_query, reconnect_delay, retry_with_backoff, sql_response_has_rows};
+
+#[test]
+fn test_reconnect_delay_doubles_up_to_a_minute() {
+    assert_eq!(reconnect_delay(0), Duration::from_secs(1));
+    assert_eq!(reconnect_delay(1), Duration::from_secs(2));
+    assert_eq!(reconnect_delay(5), Duration::from_secs(32));
+    assert_eq!(reconnect_delay(6), Duration::from_secs(60));
+    assert_eq!(reconnect_delay(u32::MAX), Duration::from_secs(60));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_retry_with_backoff_waits_between_attempts() {
+    let start = tokio::time::Instant::now();
+    let mut attempts = 0;
+
+    let conn = retry_with_backoff(|| {
+        attempts += 1;
+        if attempts < 4 {
+            Err(anyhow::anyhow!("connection refused"))
+        } else {
+            Ok("conn")
+        }
+    })
+    .await;
+
+    assert_eq!(conn, "conn");
+    assert_eq!(attempts, 4);
+    // 1s + 2s + 4s of backoff before the fourth attempt
+    assert_eq!(start.elapsed(), Duration::from_secs(7));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_retry_with_backoff_connects_immediately() {
+    let start = tokio::time::Instant::now();
+
+    let conn = retry_with_backoff(|| Ok::<_, anyhow::Error>(1)).await;
+
+    assert_eq!(conn, 1);
+    assert_eq!(start.elapsed(), Duration::ZERO);
+}
+
+#[test]
+fn test_is_duplicate_query() {
+    let query = is_duplicate_query("0110").unwrap();
+    assert!(query.contains("WHERE hash = '0110'"));
+
+    assert!(is_duplicate_query("").is_err());
+    assert!(is_duplicate_query("01' OR '1'='1").is_err());
+}
+
+#[test]
+fn test_sql_response_has_rows() {
+    let found = json!([{ "schema": {}, "rows": [["video-1"]] }]);
+    let empty = json!([{ "schema": {}, "rows": [] }]);
+
+    assert!(sql_response_has_rows(&found));
+    assert!(!sql_response_has_rows(&empty));
+    assert!(!sql_response_has_rows(&json!([])));
+    assert!(!sql_response_has_rows(&json!({ "error": "no such table" })));
+}
@@ -0,0 +1,288 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+#[cfg(feature = "prod-bin")]
+use fasthash::{BufHasher, HasherExt, MetroHasherExt};
+use serde_json::Value;
+use spacetimedb_sdk::{DbContext, Status, Timestamp};
+use tokio::sync::broadcast;
+use yral_spacetime_bindings::autogenerated::dedup_index::{self, add};
+
+use crate::consts::{DEDUP_INDEX_MODULE_IDENTITY, STDB_ACCESS_TOKEN, STDB_URL};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+/// Table of the dedup index module the `add` reducer writes to
+const DEDUP_INDEX_TABLE: &str = "video_hash";
+
+pub type ReducerResult = Result<(), String>;
+
+/// (input hash, reducer result)
+type BusMessage = (u128, ReducerResult);
+
+#[cfg(feature = "prod-bin")]
+fn fast_hash<H: std::hash::Hash>(data: H) -> u128 {
+    // set constant seed to get consistent result
+    let mut hasher = MetroHasherExt::with_capacity_and_seed(0, Some(0));
+
+    data.hash(&mut hasher);
+
+    hasher.finish_ext()
+}
+
+#[cfg(not(feature = "prod-bin"))]
+fn fast_hash<H: std::hash::Hash>(data: H) -> u128 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish() as u128
+}
+
+/// Connects to the dedup index, forwarding results of `add` reducer calls to the bus
+fn connect(tx: broadcast::Sender<BusMessage>) -> anyhow::Result<dedup_index::DbConnection> {
+    let conn = dedup_index::DbConnection::builder()
+        .with_uri(STDB_URL)
+        .with_module_name(DEDUP_INDEX_MODULE_IDENTITY)
+        .with_token(Some(STDB_ACCESS_TOKEN.as_str()))
+        .build()
+        .context("Couldn't connect to the db")?;
+
+    conn.reducers
+        .on_add(move |event, hash, video_id, timestamp| {
+            let search_hash = fast_hash(HashData {
+                video_id: video_id.clone(),
+                hash: hash.clone(),
+                timestamp: *timestamp,
+            });
+
+            let res = match event.event.status {
+                Status::Committed => Ok(()),
+                Status::Failed(ref msg) => Err(msg.to_string()),
+                Status::OutOfEnergy => Err("Out of energy".into()),
+            };
+
+            // no receiver means nobody is waiting on this result
+            let _ = tx.send((search_hash, res));
+        });
+
+    Ok(conn)
+}
+
+/// Delay before the given reconnection attempt, doubling from 1s up to a minute
+pub fn reconnect_delay(attempt: u32) -> Duration {
+    RECONNECT_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(RECONNECT_MAX_DELAY)
+}
+
+/// Calls `connect` until it succeeds, sleeping [`reconnect_delay`] between attempts
+pub async fn retry_with_backoff<T, F>(mut connect: F) -> T
+where
+    F: FnMut() -> anyhow::Result<T>,
+{
+    let mut attempt = 0;
+    loop {
+        match connect() {
+            Ok(conn) => return conn,
+            Err(err) => {
+                let delay = reconnect_delay(attempt);
+                log::error!(
+                    "Couldn't reconnect to dedup index (attempt {}), retrying in {:?}: {err:#?}",
+                    attempt + 1,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt = attempt.saturating_add(1);
+            }
+        }
+    }
+}
+
+/// Query listing the index entries of a hash, hashes are plain bit strings so anything else is rejected
+pub fn is_duplicate_query(hash: &str) -> anyhow::Result<String> {
+    anyhow::ensure!(
+        !hash.is_empty() && hash.chars().all(|c| c.is_ascii_alphanumeric()),
+        "invalid video hash {hash:?}"
+    );
+
+    Ok(format!(
+        "SELECT video_id FROM {DEDUP_INDEX_TABLE} WHERE hash = '{hash}'"
+    ))
+}
+
+/// The sql api answers with one result per statement, each with its `rows`
+pub fn sql_response_has_rows(response: &Value) -> bool {
+    response.as_array().is_some_and(|statements| {
+        statements.iter().any(|statement| {
+            statement["rows"]
+                .as_array()
+                .is_some_and(|rows| !rows.is_empty())
+        })
+    })
+}
+
+/// A wrapper around the [`dedup_index::DbConnection`] with an internal message bus that allows for async operations.
+/// The connection is replaced with a new one whenever it drops.
+#[derive(Clone)]
+pub struct AsyncDedupIndex {
+    conn: Arc<RwLock<Arc<dedup_index::DbConnection>>>,
+    tx: broadcast::Sender<BusMessage>,
+    http: reqwest::Client,
+}
+
+impl AsyncDedupIndex {
+    pub fn new() -> anyhow::Result<Self> {
+        // this limit is for lagging mechanism of broadcast channel
+        //
+        // in our case, the receivers don't any slow work after receiving
+        // messages, so we wont run into "slow receiver" problem.
+        //
+        // however, in case we end up with more than this limit number of
+        // requests at the same time, the receiver would fail with error and
+        // will most likely be retried by qstash
+        let (tx, _) = broadcast::channel(65536);
+        let conn = connect(tx.clone())?;
+
+        let index = Self {
+            conn: Arc::new(RwLock::new(Arc::new(conn))),
+            tx,
+            http: reqwest::Client::new(),
+        };
+        index.spawn_connection_loop();
+        index.spawn_heartbeat();
+
+        Ok(index)
+    }
+
+    fn conn(&self) -> Arc<dedup_index::DbConnection> {
+        self.conn
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Runs the current connection, reconnecting with backoff once it ends
+    fn spawn_connection_loop(&self) {
+        let index = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let conn = index.conn();
+                match conn.run_async().await {
+                    Ok(()) => log::warn!("connection to dedup index closed"),
+                    Err(err) => {
+                        log::error!("connection to dedup index broke with an error: {err:#?}")
+                    }
+                }
+
+                let tx = index.tx.clone();
+                let conn = retry_with_backoff(|| connect(tx.clone())).await;
+                *index
+                    .conn
+                    .write()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(conn);
+                log::info!("reconnected to dedup index");
+            }
+        });
+    }
+
+    /// Checks the connection every 30 seconds, disconnecting one that went inactive without
+    /// its run loop noticing so the connection loop replaces it
+    fn spawn_heartbeat(&self) {
+        let index = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                interval.tick().await;
+
+                let conn = index.conn();
+                if !conn.is_active() {
+                    log::warn!("dedup index connection inactive on heartbeat, reconnecting");
+                    if let Err(err) = conn.disconnect() {
+                        log::error!(
+                            "Couldn't disconnect inactive dedup index connection: {err:#?}"
+                        );
+                    }
+                }
+            }
+        });
+    }
+
+    /// Adds a video hash to dedup index
+    ///
+    /// Outer error is any error when sending request, for example, network error.
+    /// The inner error is the result of the operation itself
+    pub async fn add(
+        &self,
+        video_id: &str,
+        hash: &str,
+        timestamp: SystemTime,
+    ) -> anyhow::Result<ReducerResult> {
+        let search_hash = fast_hash(HashData {
+            video_id: video_id.to_string(),
+            hash: hash.to_string(),
+            timestamp: timestamp.into(),
+        });
+        let mut rx = self.tx.subscribe();
+        self.conn()
+            .reducers
+            .add(hash.to_string(), video_id.to_string(), timestamp.into())
+            .context("Couldn't send request to add")?;
+
+        let res = loop {
+            let (recv_hash, data) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .context("timeout reached before receiving result")??;
+            if recv_hash == search_hash {
+                break data;
+            }
+        };
+
+        Ok(res)
+    }
+
+    /// Adds a video hash to dedup index, failing on both request and module errors
+    pub async fn add_hash(&self, hash: &str, video_id: &str) -> anyhow::Result<()> {
+        self.add(video_id, hash, SystemTime::now())
+            .await
+            .context("Couldn't send request to stdb")?
+            .map_err(|err| anyhow::anyhow!("{err}"))
+            .context("Module returned error")
+    }
+
+    /// Whether any video is indexed with this exact hash, read through the sql api
+    pub async fn is_duplicate(&self, hash: &str) -> anyhow::Result<bool> {
+        let query = is_duplicate_query(hash)?;
+        let response: Value = self
+            .http
+            .post(format!(
+                "{STDB_URL}/v1/database/{DEDUP_INDEX_MODULE_IDENTITY}/sql"
+            ))
+            .bearer_auth(STDB_ACCESS_TOKEN.as_str())
+            .body(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(sql_response_has_rows(&response))
+    }
+}
+
+#[derive(Debug, Hash)]
+struct HashData {
+    video_id: String,
+    hash: String,
+    timestamp: Timestamp,
+}
+
+#[cfg(test)]
+mod async_dedup_index_tests;
@@ -12,6 +12,11 @@ use serde_json::json;
 use tonic::metadata::MetadataValue;
 use tonic::{Request, Status};
 
+pub mod nonce;
+
+#[cfg(test)]
+mod nonce_tests;
+
 #[derive(Debug)]
 pub enum AuthError {
     WrongCredentials,
@@ -135,3 +140,14 @@ pub fn check_auth_events(req_token: Option<String>) -> Result<(), anyhow::Error>
         _ => Err(anyhow::anyhow!("No valid auth token")),
     }
 }
+
+pub fn check_auth_admin(req_token: &str) -> Result<(), AuthError> {
+    let mut token = env::var("ADMIN_AUTH_TOKEN").expect("ADMIN_AUTH_TOKEN is required");
+    token.retain(|c| !c.is_whitespace());
+
+    if req_token.trim() == token {
+        Ok(())
+    } else {
+        Err(AuthError::WrongCredentials)
+    }
+}
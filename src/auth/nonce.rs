@@ -0,0 +1,126 @@
+use std::env;
+
+use axum::http::{HeaderMap, StatusCode};
+use candid::Principal;
+use chrono::NaiveDate;
+use thiserror::Error;
+
+use crate::utils::claim_store::ClaimStore;
+
+pub const NONCE_HEADER: &str = "x-nonce";
+/// How long a used nonce is remembered, as long as the longest ingress expiry of an IC call
+pub const NONCE_TTL_SECS: u64 = 5 * 60;
+pub const MAX_NONCE_LEN: usize = 128;
+/// Rollout opt out, a `YYYY-MM-DD` date up to which requests without a nonce still pass. Unset
+/// or past, a missing nonce is rejected.
+pub const ALLOW_MISSING_NONCE_UNTIL_ENV: &str = "ALLOW_MISSING_X_NONCE_UNTIL";
+
+#[derive(Error, Debug)]
+pub enum NonceError {
+    #[error("missing x-nonce header")]
+    Missing,
+    #[error("invalid x-nonce header")]
+    Invalid,
+    #[error("nonce already used")]
+    Replayed,
+    #[error("nonce store unavailable: {0}")]
+    Store(#[from] anyhow::Error),
+}
+
+impl From<NonceError> for StatusCode {
+    fn from(e: NonceError) -> Self {
+        match e {
+            NonceError::Missing | NonceError::Invalid => StatusCode::BAD_REQUEST,
+            NonceError::Replayed => StatusCode::CONFLICT,
+            NonceError::Store(e) => {
+                log::error!("Failed to check nonce: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+pub fn nonce_key(principal: Principal, nonce: &str) -> String {
+    format!("nonce:{}:{}", principal, nonce)
+}
+
+/// The client's nonce, up to [`MAX_NONCE_LEN`] visible ASCII characters
+pub fn nonce_from_headers(headers: &HeaderMap) -> Result<String, NonceError> {
+    let nonce = headers
+        .get(NONCE_HEADER)
+        .ok_or(NonceError::Missing)?
+        .to_str()
+        .map_err(|_| NonceError::Invalid)?
+        .trim();
+
+    if nonce.is_empty()
+        || nonce.len() > MAX_NONCE_LEN
+        || !nonce.bytes().all(|b| b.is_ascii_graphic())
+    {
+        return Err(NonceError::Invalid);
+    }
+
+    Ok(nonce.to_string())
+}
+
+/// Whether a nonce is required on `today` given the [`ALLOW_MISSING_NONCE_UNTIL_ENV`] value, an
+/// unreadable date requires it
+pub fn nonce_required_on(allow_missing_until: Option<&str>, today: NaiveDate) -> bool {
+    allow_missing_until
+        .and_then(|until| NaiveDate::parse_from_str(until.trim(), "%Y-%m-%d").ok())
+        .is_none_or(|until| today > until)
+}
+
+pub fn nonce_required() -> bool {
+    nonce_required_on(
+        env::var(ALLOW_MISSING_NONCE_UNTIL_ENV).ok().as_deref(),
+        chrono::Utc::now().date_naive(),
+    )
+}
+
+/// Like [`nonce_from_headers`], but a missing nonce is `None` unless `required`, see
+/// [`nonce_required`]. A nonce that is sent is always checked.
+pub fn optional_nonce_from_headers(
+    headers: &HeaderMap,
+    required: bool,
+) -> Result<Option<String>, NonceError> {
+    match nonce_from_headers(headers) {
+        Ok(nonce) => Ok(Some(nonce)),
+        Err(NonceError::Missing) if !required => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// `(principal, nonce)` pairs used in the last [`NONCE_TTL_SECS`], so a captured request of a
+/// sensitive operation can't be replayed
+pub struct NonceCache<'a, S> {
+    store: &'a S,
+}
+
+impl<'a, S: ClaimStore> NonceCache<'a, S> {
+    pub fn new(store: &'a S) -> Self {
+        Self { store }
+    }
+
+    /// Marks the nonce as used by the principal, fails if it already was. A failed store fails
+    /// the check too.
+    pub async fn claim(&self, principal: Principal, nonce: &str) -> Result<(), NonceError> {
+        if self
+            .store
+            .set_if_absent(&nonce_key(principal, nonce), NONCE_TTL_SECS)
+            .await?
+        {
+            Ok(())
+        } else {
+            Err(NonceError::Replayed)
+        }
+    }
+
+    /// Frees the nonce of an operation that failed, so QStash's retry of the same request
+    /// is not taken for a replay
+    pub async fn release(&self, principal: Principal, nonce: &str) {
+        if let Err(e) = self.store.remove(&nonce_key(principal, nonce)).await {
+            log::warn!("Failed to release nonce of {}: {}", principal, e);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use candid::Principal;
+use chrono::NaiveDate;
+
+use crate::utils::claim_store::ClaimStore;
+
+use super::nonce::{
+    nonce_from_headers, nonce_key, nonce_required_on, optional_nonce_from_headers, NonceCache,
+    NonceError, MAX_NONCE_LEN, NONCE_HEADER, NONCE_TTL_SECS,
+};
+
+/// Keys with the time they expire at, on a clock the tests advance
+#[derive(Default)]
+struct MockRedis {
+    now: Mutex<u64>,
+    keys: Mutex<HashMap<String, u64>>,
+    unavailable: bool,
+}
+
+impl MockRedis {
+    fn advance(&self, secs: u64) {
+        *self.now.lock().unwrap() += secs;
+    }
+}
+
+impl ClaimStore for MockRedis {
+    async fn set_if_absent(&self, key: &str, ttl_secs: u64) -> Result<bool, anyhow::Error> {
+        if self.unavailable {
+            return Err(anyhow::anyhow!("connection refused"));
+        }
+
+        let now = *self.now.lock().unwrap();
+        let mut keys = self.keys.lock().unwrap();
+        if keys.get(key).is_some_and(|expires_at| *expires_at > now) {
+            return Ok(false);
+        }
+        keys.insert(key.to_string(), now + ttl_secs);
+        Ok(true)
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), anyhow::Error> {
+        self.keys.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+fn user(i: u8) -> Principal {
+    Principal::from_slice(&[i])
+}
+
+fn headers(nonce: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(NONCE_HEADER, HeaderValue::from_str(nonce).unwrap());
+    headers
+}
+
+#[test]
+fn test_nonce_key() {
+    assert_eq!(
+        nonce_key(Principal::anonymous(), "abc"),
+        "nonce:2vxsx-fae:abc"
+    );
+}
+
+#[test]
+fn test_nonce_from_headers() {
+    assert_eq!(
+        nonce_from_headers(&headers(" abc-123 ")).unwrap(),
+        "abc-123"
+    );
+    assert!(matches!(
+        nonce_from_headers(&HeaderMap::new()),
+        Err(NonceError::Missing)
+    ));
+    for invalid in ["", "a b", "a".repeat(MAX_NONCE_LEN + 1).as_str()] {
+        assert!(matches!(
+            nonce_from_headers(&headers(invalid)),
+            Err(NonceError::Invalid)
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_used_nonce_is_rejected() {
+    let redis = MockRedis::default();
+    let nonces = NonceCache::new(&redis);
+
+    nonces.claim(user(1), "n1").await.unwrap();
+    let err = nonces.claim(user(1), "n1").await.unwrap_err();
+
+    assert!(matches!(err, NonceError::Replayed));
+    assert_eq!(StatusCode::from(err), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_nonces_are_scoped_to_the_principal() {
+    let redis = MockRedis::default();
+    let nonces = NonceCache::new(&redis);
+
+    nonces.claim(user(1), "n1").await.unwrap();
+    nonces.claim(user(2), "n1").await.unwrap();
+    nonces.claim(user(1), "n2").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_nonce_can_be_reused_after_ttl() {
+    let redis = MockRedis::default();
+    let nonces = NonceCache::new(&redis);
+    nonces.claim(user(1), "n1").await.unwrap();
+
+    redis.advance(NONCE_TTL_SECS - 1);
+    assert!(nonces.claim(user(1), "n1").await.is_err());
+
+    redis.advance(1);
+    nonces.claim(user(1), "n1").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_released_nonce_can_be_retried() {
+    let redis = MockRedis::default();
+    let nonces = NonceCache::new(&redis);
+    nonces.claim(user(1), "n1").await.unwrap();
+
+    nonces.release(user(1), "n1").await;
+
+    nonces.claim(user(1), "n1").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unavailable_store_fails_closed() {
+    let redis = MockRedis {
+        unavailable: true,
+        ..Default::default()
+    };
+
+    let err = NonceCache::new(&redis)
+        .claim(user(1), "n1")
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, NonceError::Store(_)));
+    assert_eq!(StatusCode::from(err), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn test_nonce_is_required_unless_opted_out_until_a_later_date() {
+    let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+
+    assert!(nonce_required_on(None, today));
+    assert!(nonce_required_on(Some("true"), today));
+    assert!(nonce_required_on(Some("2025-03-09"), today));
+    assert!(!nonce_required_on(Some("2025-03-10"), today));
+    assert!(!nonce_required_on(Some(" 2025-04-01 "), today));
+}
+
+#[test]
+fn test_missing_nonce_passes_only_when_not_required() {
+    assert_eq!(
+        optional_nonce_from_headers(&HeaderMap::new(), false).unwrap(),
+        None
+    );
+    assert!(matches!(
+        optional_nonce_from_headers(&HeaderMap::new(), true),
+        Err(NonceError::Missing)
+    ));
+    assert_eq!(
+        optional_nonce_from_headers(&headers("abc"), false).unwrap(),
+        Some("abc".to_string())
+    );
+    assert!(matches!(
+        optional_nonce_from_headers(&headers("a b"), false),
+        Err(NonceError::Invalid)
+    ));
+}
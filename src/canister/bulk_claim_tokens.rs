@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use candid::Principal;
+use futures::StreamExt;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+};
+
+const METADATA_LOOKUP_CONCURRENCY: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct BulkClaimTokensRequest {
+    pub user_principals: Vec<Principal>,
+}
+
+/// Body of the `claim_tokens_admin` QStash job
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AdminClaimTokensRequest {
+    pub user_principal: Principal,
+    pub user_canister: Principal,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct BulkClaimTokensResponse {
+    pub enqueued: Vec<Principal>,
+    pub failed: Vec<Principal>,
+}
+
+/// Splits metadata lookups into claim jobs and principals whose canister could not be found
+pub fn partition_lookups(
+    lookups: Vec<(Principal, Option<Principal>)>,
+) -> (Vec<AdminClaimTokensRequest>, Vec<Principal>) {
+    let mut requests = Vec::new();
+    let mut failed = Vec::new();
+    for (user_principal, user_canister) in lookups {
+        match user_canister {
+            Some(user_canister) => requests.push(AdminClaimTokensRequest {
+                user_principal,
+                user_canister,
+            }),
+            None => failed.push(user_principal),
+        }
+    }
+
+    (requests, failed)
+}
+
+/// Enqueues a token claim for every user, claims run with the admin identity
+#[instrument(skip(state, token, req))]
+pub async fn bulk_claim_tokens_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Json(req): Json<BulkClaimTokensRequest>,
+) -> Result<Json<BulkClaimTokensResponse>, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let lookups = futures::stream::iter(req.user_principals)
+        .map(|user_principal| {
+            let metadata = &state.yral_metadata_client;
+            async move {
+                let user_canister = match metadata.get_user_metadata(user_principal).await {
+                    Ok(meta) => meta.map(|meta| meta.user_canister_id),
+                    Err(e) => {
+                        log::error!("Failed to get metadata of {}: {}", user_principal, e);
+                        None
+                    }
+                };
+                (user_principal, user_canister)
+            }
+        })
+        .buffer_unordered(METADATA_LOOKUP_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let (requests, failed) = partition_lookups(lookups);
+    if !requests.is_empty() {
+        state
+            .qstash_client
+            .claim_tokens_batch(&requests)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to enqueue bulk token claims: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    }
+
+    log::info!(
+        "Enqueued token claims for {} users, {} failed",
+        requests.len(),
+        failed.len()
+    );
+
+    Ok(Json(BulkClaimTokensResponse {
+        enqueued: requests.into_iter().map(|req| req.user_principal).collect(),
+        failed,
+    }))
+}
@@ -0,0 +1,104 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, routing::post, Json, Router};
+use candid::Principal;
+use reqwest::Url;
+use serde_json::Value;
+
+use super::bulk_claim_tokens::{partition_lookups, AdminClaimTokensRequest};
+use crate::qstash::client::{claim_tokens_batch_body, QStashClient};
+
+fn principal(text: &str) -> Principal {
+    Principal::from_text(text).unwrap()
+}
+
+#[test]
+fn test_partition_lookups() {
+    let user = principal("rrkah-fqaaa-aaaaa-aaaaq-cai");
+    let canister = principal("ryjl3-tyaaa-aaaaa-aaaba-cai");
+    let unknown = Principal::anonymous();
+
+    let (requests, failed) = partition_lookups(vec![(user, Some(canister)), (unknown, None)]);
+
+    assert_eq!(
+        requests,
+        vec![AdminClaimTokensRequest {
+            user_principal: user,
+            user_canister: canister,
+        }]
+    );
+    assert_eq!(failed, vec![unknown]);
+}
+
+#[test]
+fn test_batch_body_has_one_message_per_user() {
+    let requests = vec![
+        AdminClaimTokensRequest {
+            user_principal: principal("rrkah-fqaaa-aaaaa-aaaaq-cai"),
+            user_canister: principal("ryjl3-tyaaa-aaaaa-aaaba-cai"),
+        },
+        AdminClaimTokensRequest {
+            user_principal: Principal::anonymous(),
+            user_canister: principal("ryjl3-tyaaa-aaaaa-aaaba-cai"),
+        },
+    ];
+
+    let body = claim_tokens_batch_body("https://example.com/qstash/claim_tokens_admin", &requests);
+
+    assert_eq!(body.len(), 2);
+    assert_eq!(
+        body[0]["destination"],
+        "https://example.com/qstash/claim_tokens_admin"
+    );
+    assert_eq!(body[0]["headers"]["Upstash-Forward-Method"], "POST");
+    let nonces: Vec<&str> = body
+        .iter()
+        .map(|entry| {
+            entry["headers"]["Upstash-Forward-X-Nonce"]
+                .as_str()
+                .unwrap()
+        })
+        .collect();
+    assert_ne!(nonces[0], nonces[1]);
+    let decoded: AdminClaimTokensRequest =
+        serde_json::from_str(body[1]["body"].as_str().unwrap()).unwrap();
+    assert_eq!(decoded, requests[1]);
+}
+
+#[tokio::test]
+async fn test_claims_are_sent_in_batches_qstash_accepts() {
+    // sizes of the batches QStash received
+    let batches = Arc::new(Mutex::new(Vec::new()));
+    let app =
+        Router::new()
+            .route(
+                "/v2/batch",
+                post(
+                    |State(batches): State<Arc<Mutex<Vec<usize>>>>,
+                     Json(body): Json<Vec<Value>>| async move {
+                        batches.lock().unwrap().push(body.len());
+                        Json(Vec::<Value>::new())
+                    },
+                ),
+            )
+            .with_state(batches.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let client = QStashClient {
+        client: reqwest::Client::new(),
+        base_url: Arc::new(Url::parse(&format!("http://{addr}/v2/")).unwrap()),
+    };
+    let requests = vec![
+        AdminClaimTokensRequest {
+            user_principal: Principal::anonymous(),
+            user_canister: principal("ryjl3-tyaaa-aaaaa-aaaba-cai"),
+        };
+        250
+    ];
+
+    client.claim_tokens_batch(&requests).await.unwrap();
+
+    assert_eq!(*batches.lock().unwrap(), vec![100, 100, 50]);
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use axum::{extract::State, response::IntoResponse};
+use candid::{Nat, Principal};
+use futures::StreamExt;
+use google_cloud_bigquery::http::tabledata::insert_all::{InsertAllRequest, Row};
+use http::StatusCode;
+use ic_agent::Agent;
+use serde::Serialize;
+use tracing::instrument;
+use yral_canisters_client::{
+    individual_user_template::IndividualUserTemplate,
+    sns_root::{CanisterSummary, GetSnsCanistersSummaryRequest, SnsRoot},
+};
+
+use crate::{app_state::AppState, canister::utils::get_user_canisters_list_v2};
+
+/// Rows per insertAll request
+pub const CANISTER_METRICS_INSERT_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct CanisterMetricsRow {
+    pub canister_id: String,
+    pub cycles: u64,
+    pub memory_bytes: u64,
+    pub timestamp: String,
+}
+
+fn nat_to_u64(n: &Nat) -> u64 {
+    u64::try_from(n.0.clone()).unwrap_or(u64::MAX)
+}
+
+pub fn canister_metrics_row(
+    canister_id: Principal,
+    cycles: &Nat,
+    memory_size: &Nat,
+    timestamp: &str,
+) -> CanisterMetricsRow {
+    CanisterMetricsRow {
+        canister_id: canister_id.to_text(),
+        cycles: nat_to_u64(cycles),
+        memory_bytes: nat_to_u64(memory_size),
+        timestamp: timestamp.to_string(),
+    }
+}
+
+/// Rows of the canisters in SNS root summaries, skipping canisters the root reported no status for
+fn summary_metrics_rows(
+    summaries: impl IntoIterator<Item = CanisterSummary>,
+    timestamp: &str,
+) -> Vec<CanisterMetricsRow> {
+    summaries
+        .into_iter()
+        .filter_map(|summary| {
+            let status = summary.status?;
+            Some(canister_metrics_row(
+                summary.canister_id?,
+                &status.cycles,
+                &status.memory_size,
+                timestamp,
+            ))
+        })
+        .collect()
+}
+
+/// Cycles and memory of the SNS canisters of the user's tokens. The statuses come from the SNS
+/// root, `canister_status` would need the agent to control the canisters.
+async fn get_canister_metrics(
+    agent: &Agent,
+    user_canister: Principal,
+    timestamp: &str,
+) -> Result<Vec<CanisterMetricsRow>, anyhow::Error> {
+    let deployed = IndividualUserTemplate(user_canister, agent)
+        .deployed_cdao_canisters()
+        .await?;
+
+    let mut rows = vec![];
+    for token in deployed {
+        let summary = SnsRoot(token.root, agent)
+            .get_sns_canisters_summary(GetSnsCanistersSummaryRequest {
+                update_canister_list: None,
+            })
+            .await?;
+
+        let summaries = [
+            summary.root,
+            summary.governance,
+            summary.ledger,
+            summary.index,
+            summary.swap,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(summary.dapps)
+        .chain(summary.archives);
+        rows.extend(summary_metrics_rows(summaries, timestamp));
+    }
+
+    Ok(rows)
+}
+
+/// Records cycles and memory of the SNS canisters of every user canister's tokens to
+/// `yral_ds.canister_metrics`.
+/// Scheduled daily from QStash.
+#[instrument(skip(state))]
+pub async fn export_canister_metrics(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let agent = state.agent.clone();
+    let canister_ids = get_user_canisters_list_v2(&agent)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tokio::spawn(async move {
+        let timestamp = chrono::Utc::now().to_rfc3339();
+        let total = canister_ids.len();
+
+        let mut rows = futures::stream::iter(canister_ids)
+            .map(|canister_id| {
+                let agent = &agent;
+                let timestamp = &timestamp;
+                async move {
+                    get_canister_metrics(agent, canister_id, timestamp)
+                        .await
+                        .unwrap_or_else(|e| {
+                            log::warn!("Failed to get statuses of {}: {}", canister_id, e);
+                            vec![]
+                        })
+                }
+            })
+            .buffer_unordered(100)
+            .flat_map(futures::stream::iter)
+            .chunks(CANISTER_METRICS_INSERT_BATCH_SIZE);
+
+        let mut exported = 0;
+        while let Some(batch) = rows.next().await {
+            let batch_len = batch.len();
+            let request = InsertAllRequest {
+                rows: batch
+                    .into_iter()
+                    .map(|json| Row {
+                        insert_id: None,
+                        json,
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+
+            match state
+                .bigquery_client
+                .tabledata()
+                .insert(
+                    "hot-or-not-feed-intelligence",
+                    "yral_ds",
+                    "canister_metrics",
+                    &request,
+                )
+                .await
+            {
+                Ok(_) => exported += batch_len,
+                Err(e) => log::error!("Failed to insert canister metrics: {}", e),
+            }
+        }
+
+        log::info!(
+            "Exported metrics of {} SNS canisters of {} user canisters",
+            exported,
+            total
+        );
+    });
+
+    Ok((
+        StatusCode::OK,
+        "Canister metrics export started".to_string(),
+    ))
+}
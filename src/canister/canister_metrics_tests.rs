@@ -0,0 +1,35 @@
+use candid::{Nat, Principal};
+
+use super::canister_metrics::{canister_metrics_row, CanisterMetricsRow};
+
+#[test]
+fn test_row_from_canister_status() {
+    let canister_id = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+
+    let row = canister_metrics_row(
+        canister_id,
+        &Nat::from(2_500_000_000_000_u64),
+        &Nat::from(104_857_600_u64),
+        "2025-01-31T00:00:00+00:00",
+    );
+
+    assert_eq!(
+        row,
+        CanisterMetricsRow {
+            canister_id: "rrkah-fqaaa-aaaaa-aaaaq-cai".to_string(),
+            cycles: 2_500_000_000_000,
+            memory_bytes: 104_857_600,
+            timestamp: "2025-01-31T00:00:00+00:00".to_string(),
+        }
+    );
+}
+
+#[test]
+fn test_row_saturates_oversized_values() {
+    let huge = Nat::from(u128::MAX);
+
+    let row = canister_metrics_row(Principal::anonymous(), &huge, &Nat::from(0_u64), "t");
+
+    assert_eq!(row.cycles, u64::MAX);
+    assert_eq!(row.memory_bytes, 0);
+}
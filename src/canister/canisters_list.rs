@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use candid::Principal;
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::instrument;
+use yral_canisters_client::user_index::UserIndex;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+};
+
+use super::utils::{get_subnet_orch_ids, get_user_canisters_list_v2};
+
+const DEFAULT_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CanistersListFormat {
+    #[default]
+    Ndjson,
+    Prometheus,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CanistersListQuery {
+    pub page_size: Option<usize>,
+    #[serde(default)]
+    pub format: CanistersListFormat,
+}
+
+/// Lists all user canisters across subnet orchestrators.
+///
+/// The default mode streams newline delimited JSON arrays of at most `page_size` canister ids,
+/// one subnet orchestrator at a time, so the full list is never held in memory.
+/// `?format=prometheus` buffers the full list and returns it as a Prometheus http_sd target list.
+#[instrument(skip(state, token))]
+pub async fn canisters_list_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Query(params): Query<CanistersListQuery>,
+) -> Result<Response, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    if params.format == CanistersListFormat::Prometheus {
+        let canister_ids = get_user_canisters_list_v2(&state.agent)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to get user canisters list: {e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        return Ok(Json(prometheus_sd_targets(&canister_ids)).into_response());
+    }
+
+    let page_size = params.page_size.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+
+    let subnet_orch_ids = get_subnet_orch_ids(&state.agent).await.map_err(|e| {
+        log::error!("Failed to get subnet orchestrator ids: {e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let body_stream = stream::iter(subnet_orch_ids)
+        .then(move |subnet_orch_id| {
+            let state = state.clone();
+            async move {
+                let subnet_orch = UserIndex(subnet_orch_id, &state.agent);
+                subnet_orch.get_user_canister_list().await.map_err(|e| {
+                    log::error!(
+                        "Failed to get user canister list for subnet orchestrator {subnet_orch_id}: {e}"
+                    );
+                    std::io::Error::other(e.to_string())
+                })
+            }
+        })
+        .flat_map(move |res| match res {
+            Ok(canister_ids) => stream::iter(
+                canister_ids
+                    .chunks(page_size)
+                    .map(|page| Ok(encode_ndjson_page(page)))
+                    .collect::<Vec<_>>(),
+            )
+            .left_stream(),
+            Err(e) => stream::once(async move { Err(e) }).right_stream(),
+        });
+
+    Ok((
+        [(CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(body_stream),
+    )
+        .into_response())
+}
+
+pub(crate) fn encode_ndjson_page(page: &[Principal]) -> Bytes {
+    let ids = page.iter().map(|id| id.to_text()).collect::<Vec<_>>();
+    let mut line = serde_json::to_vec(&ids).unwrap_or_default();
+    line.push(b'\n');
+
+    Bytes::from(line)
+}
+
+pub(crate) fn prometheus_sd_targets(canister_ids: &[Principal]) -> serde_json::Value {
+    let targets = canister_ids
+        .iter()
+        .map(|id| format!("{}.raw.icp0.io", id.to_text()))
+        .collect::<Vec<_>>();
+
+    json!([{
+        "targets": targets,
+        "labels": {
+            "__metrics_path__": "/metrics",
+        },
+    }])
+}
@@ -0,0 +1,40 @@
+use super::canisters_list::{encode_ndjson_page, prometheus_sd_targets};
+use candid::Principal;
+
+#[test]
+fn test_ndjson_page_is_single_line_array() {
+    let ids = vec![Principal::anonymous(), Principal::management_canister()];
+    let line = encode_ndjson_page(&ids);
+
+    assert!(line.ends_with(b"\n"));
+    assert_eq!(line.iter().filter(|b| **b == b'\n').count(), 1);
+
+    let decoded: Vec<String> = serde_json::from_slice(&line).unwrap();
+    assert_eq!(
+        decoded,
+        vec!["2vxsx-fae".to_string(), "aaaaa-aa".to_string()]
+    );
+}
+
+#[test]
+fn test_ndjson_pages_cover_all_ids() {
+    let ids = vec![Principal::anonymous(); 25];
+    let total: usize = ids
+        .chunks(10)
+        .map(|page| {
+            serde_json::from_slice::<Vec<String>>(&encode_ndjson_page(page))
+                .unwrap()
+                .len()
+        })
+        .sum();
+
+    assert_eq!(total, 25);
+}
+
+#[test]
+fn test_prometheus_sd_targets() {
+    let targets = prometheus_sd_targets(&[Principal::management_canister()]);
+
+    assert_eq!(targets[0]["targets"][0], "aaaaa-aa.raw.icp0.io");
+    assert_eq!(targets[0]["labels"]["__metrics_path__"], "/metrics");
+}
@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+
+use candid::Principal;
+use serde_json::json;
+#[cfg(not(feature = "local-bin"))]
+use tracing::instrument;
+#[cfg(not(feature = "local-bin"))]
+use yral_canisters_client::sns_governance::{ListNeurons, Neuron, SnsGovernance};
+
+use crate::events::warehouse_events::WarehouseEvent;
+#[cfg(not(feature = "local-bin"))]
+use crate::{
+    app_state::AppState,
+    events::{event::Event, subscribe},
+    types::RedisPool,
+};
+
+pub const FIRST_UPGRADE_MILESTONE: &str = "first_upgrade";
+/// Holders told about a milestone, larger DAOs only reach their first holders
+pub const CDAO_MILESTONE_NOTIFY_CAP: usize = 1000;
+#[cfg(not(feature = "local-bin"))]
+const LIST_NEURONS_PAGE_SIZE: u32 = 100;
+
+pub fn cdao_milestone_key(governance: Principal, milestone: &str) -> String {
+    format!("cdao_milestone:{}:{}", governance, milestone)
+}
+
+/// `creator_dao_milestone` warehouse event
+pub fn creator_dao_milestone_event(
+    governance: Principal,
+    proposal_id: u64,
+    milestone: &str,
+    timestamp: &str,
+) -> WarehouseEvent {
+    WarehouseEvent {
+        event: "creator_dao_milestone".into(),
+        params: json!({
+            "governance_canister": governance,
+            "proposal_id": proposal_id,
+            "milestone": milestone,
+            "timestamp": timestamp,
+        })
+        .to_string(),
+    }
+}
+
+/// `creator_dao_milestone_reached` event for a holder, its `user_id` routes it to their open
+/// SSE connections
+pub fn holder_notification_event(
+    holder: Principal,
+    governance: Principal,
+    milestone: &str,
+) -> WarehouseEvent {
+    WarehouseEvent {
+        event: "creator_dao_milestone_reached".into(),
+        params: json!({
+            "user_id": holder,
+            "governance_canister": governance,
+            "milestone": milestone,
+            "message": "A token you hold was upgraded by its DAO for the first time!",
+        })
+        .to_string(),
+    }
+}
+
+/// Distinct principals of the neurons' permissions, in the order first seen
+pub fn neuron_holders(
+    permission_principals: impl IntoIterator<Item = Option<Principal>>,
+    cap: usize,
+) -> Vec<Principal> {
+    let mut seen = HashSet::new();
+    permission_principals
+        .into_iter()
+        .flatten()
+        .filter(|principal| seen.insert(*principal))
+        .take(cap)
+        .collect()
+}
+
+#[cfg(not(feature = "local-bin"))]
+fn permission_principals(neurons: &[Neuron]) -> impl Iterator<Item = Option<Principal>> + '_ {
+    neurons
+        .iter()
+        .flat_map(|neuron| &neuron.permissions)
+        .map(|permission| permission.principal)
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn claim_milestone(redis_pool: &RedisPool, key: &str) -> Result<bool, anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    let res: Option<String> = redis::cmd("SET")
+        .arg(key)
+        .arg(1)
+        .arg("NX")
+        .query_async(&mut *conn)
+        .await?;
+
+    Ok(res.is_some())
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn release_milestone(redis_pool: &RedisPool, key: &str) -> Result<(), anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    redis::cmd("DEL")
+        .arg(key)
+        .query_async::<()>(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Holders of the DAO's neurons, up to [`CDAO_MILESTONE_NOTIFY_CAP`]
+#[cfg(not(feature = "local-bin"))]
+async fn dao_holders(governance: &SnsGovernance<'_>) -> Result<Vec<Principal>, anyhow::Error> {
+    let mut neurons: Vec<Neuron> = Vec::new();
+    let mut start_page_at = None;
+    loop {
+        let page = governance
+            .list_neurons(ListNeurons {
+                of_principal: None,
+                limit: LIST_NEURONS_PAGE_SIZE,
+                start_page_at,
+            })
+            .await?
+            .neurons;
+        let page_len = page.len();
+        start_page_at = page.last().and_then(|neuron| neuron.id.clone());
+        neurons.extend(page);
+
+        if page_len < LIST_NEURONS_PAGE_SIZE as usize
+            || start_page_at.is_none()
+            || neuron_holders(permission_principals(&neurons), CDAO_MILESTONE_NOTIFY_CAP).len()
+                >= CDAO_MILESTONE_NOTIFY_CAP
+        {
+            break;
+        }
+    }
+
+    Ok(neuron_holders(
+        permission_principals(&neurons),
+        CDAO_MILESTONE_NOTIFY_CAP,
+    ))
+}
+
+/// Records the DAO's first executed upgrade proposal and tells its holders, only once per DAO
+#[cfg(not(feature = "local-bin"))]
+#[instrument(skip(app_state))]
+pub async fn record_first_upgrade_milestone(
+    app_state: &AppState,
+    governance: Principal,
+    proposal_id: u64,
+) -> Result<(), anyhow::Error> {
+    let key = cdao_milestone_key(governance, FIRST_UPGRADE_MILESTONE);
+    if !claim_milestone(&app_state.canister_backup_redis_pool, &key).await? {
+        return Ok(());
+    }
+
+    // the holders are listed before anything is recorded, a failed listing frees the milestone
+    // so the DAO's next executed upgrade records it
+    let holders = match dao_holders(&SnsGovernance(governance, &app_state.agent)).await {
+        Ok(holders) => holders,
+        Err(e) => {
+            if let Err(release_err) =
+                release_milestone(&app_state.canister_backup_redis_pool, &key).await
+            {
+                log::error!("Failed to release milestone claim {}: {}", key, release_err);
+            }
+            return Err(e);
+        }
+    };
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    Event::new(creator_dao_milestone_event(
+        governance,
+        proposal_id,
+        FIRST_UPGRADE_MILESTONE,
+        &timestamp,
+    ))
+    .stream_to_bigquery(app_state);
+
+    for holder in &holders {
+        let event = holder_notification_event(*holder, governance, FIRST_UPGRADE_MILESTONE);
+        subscribe::publish_event(&app_state.event_subscribers, &event.event, &event.params);
+    }
+
+    log::info!(
+        "DAO {} reached its first upgrade with proposal {}, told {} holders",
+        governance,
+        proposal_id,
+        holders.len()
+    );
+
+    Ok(())
+}
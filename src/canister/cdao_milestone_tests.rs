@@ -0,0 +1,79 @@
+use candid::Principal;
+use serde_json::{json, Value};
+
+use super::cdao_milestone::{
+    cdao_milestone_key, creator_dao_milestone_event, holder_notification_event, neuron_holders,
+    CDAO_MILESTONE_NOTIFY_CAP, FIRST_UPGRADE_MILESTONE,
+};
+
+fn principal(i: u16) -> Principal {
+    Principal::from_slice(&i.to_be_bytes())
+}
+
+#[test]
+fn test_cdao_milestone_key() {
+    let governance = Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap();
+
+    assert_eq!(
+        cdao_milestone_key(governance, FIRST_UPGRADE_MILESTONE),
+        "cdao_milestone:ryjl3-tyaaa-aaaaa-aaaba-cai:first_upgrade"
+    );
+}
+
+#[test]
+fn test_creator_dao_milestone_event() {
+    let governance = principal(1);
+
+    let event = creator_dao_milestone_event(
+        governance,
+        42,
+        FIRST_UPGRADE_MILESTONE,
+        "2026-10-15T08:00:00+00:00",
+    );
+    let params: Value = serde_json::from_str(&event.params).unwrap();
+
+    assert_eq!(event.event, "creator_dao_milestone");
+    assert_eq!(
+        params,
+        json!({
+            "governance_canister": governance.to_text(),
+            "proposal_id": 42,
+            "milestone": "first_upgrade",
+            "timestamp": "2026-10-15T08:00:00+00:00",
+        })
+    );
+}
+
+#[test]
+fn test_holder_notification_targets_holder() {
+    let event = holder_notification_event(principal(7), principal(1), FIRST_UPGRADE_MILESTONE);
+    let params: Value = serde_json::from_str(&event.params).unwrap();
+
+    assert_eq!(params["user_id"], principal(7).to_text());
+    assert_eq!(params["milestone"], "first_upgrade");
+}
+
+#[test]
+fn test_neuron_holders_are_distinct() {
+    let holders = neuron_holders(
+        vec![
+            Some(principal(1)),
+            None,
+            Some(principal(2)),
+            Some(principal(1)),
+        ],
+        CDAO_MILESTONE_NOTIFY_CAP,
+    );
+
+    assert_eq!(holders, vec![principal(1), principal(2)]);
+}
+
+#[test]
+fn test_neuron_holders_are_capped() {
+    let principals = (0..3000).map(|i| Some(principal(i)));
+
+    let holders = neuron_holders(principals, CDAO_MILESTONE_NOTIFY_CAP);
+
+    assert_eq!(holders.len(), 1000);
+    assert_eq!(holders[999], principal(999));
+}
@@ -0,0 +1,244 @@
+use std::{fmt, sync::Arc};
+
+use axum::{extract::State, Json};
+use candid::Principal;
+use futures::StreamExt;
+use ic_agent::Agent;
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::Serialize;
+use tracing::instrument;
+use yral_canisters_client::sns_governance::{ListNeurons, SnsGovernance};
+
+use crate::{app_state::AppState, canister::neuron_health::remaining_dissolve_delay, AppError};
+#[cfg(not(feature = "local-bin"))]
+use crate::{canister::neuron_health::GOVERNED_CANISTERS_KEY, utils::notifications::notify_all};
+
+pub const SNS_GOVERNANCE_HEALTH_CHECK_CRON: &str = "0 6 * * *";
+pub const SNS_GOVERNANCE_HEALTH_CHECK_SCHEDULE_ID: &str = "sns-governance-health-check";
+/// Shortest dissolve delay letting a neuron vote, the upgrade job locks admin neurons for this long
+pub const MIN_VOTING_DISSOLVE_DELAY_SECS: u64 = 172800;
+const HEALTH_CHECK_CONCURRENCY: usize = 20;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum GovernanceIssue {
+    /// `list_nervous_system_functions` failed, the canister can't take proposals
+    Unreachable {
+        error: String,
+    },
+    /// `list_neurons` failed
+    NeuronsUnavailable {
+        error: String,
+    },
+    NoAdminNeurons,
+    /// Every admin neuron dissolves too soon to vote
+    NoVotingNeuron {
+        longest_dissolve_delay_seconds: u64,
+    },
+}
+
+impl fmt::Display for GovernanceIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreachable { error } => {
+                write!(f, "list_nervous_system_functions failed: {}", error)
+            }
+            Self::NeuronsUnavailable { error } => write!(f, "list_neurons failed: {}", error),
+            Self::NoAdminNeurons => write!(f, "admin principal has no neurons"),
+            Self::NoVotingNeuron {
+                longest_dissolve_delay_seconds,
+            } => write!(
+                f,
+                "no admin neuron can vote, longest dissolve delay is {}s (needs {}s)",
+                longest_dissolve_delay_seconds, MIN_VOTING_DISSOLVE_DELAY_SECS
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct UnhealthyGovernance {
+    pub governance: Principal,
+    pub issue: GovernanceIssue,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct SnsGovernanceHealthCheckResponse {
+    pub healthy: usize,
+    pub unhealthy: Vec<UnhealthyGovernance>,
+}
+
+pub(crate) trait GovernanceProbe {
+    async fn list_functions(&self, governance: Principal) -> Result<(), anyhow::Error>;
+
+    /// Remaining dissolve delay of each of the admin principal's neurons
+    async fn admin_dissolve_delays(
+        &self,
+        governance: Principal,
+        now_secs: u64,
+    ) -> Result<Vec<u64>, anyhow::Error>;
+}
+
+impl GovernanceProbe for Agent {
+    async fn list_functions(&self, governance: Principal) -> Result<(), anyhow::Error> {
+        SnsGovernance(governance, self)
+            .list_nervous_system_functions()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn admin_dissolve_delays(
+        &self,
+        governance: Principal,
+        now_secs: u64,
+    ) -> Result<Vec<u64>, anyhow::Error> {
+        let neurons = SnsGovernance(governance, self)
+            .list_neurons(ListNeurons {
+                of_principal: Some(self.get_principal().map_err(|e| anyhow::anyhow!(e))?),
+                limit: 10,
+                start_page_at: None,
+            })
+            .await?
+            .neurons;
+
+        Ok(neurons
+            .iter()
+            .map(|n| remaining_dissolve_delay(n.dissolve_state.as_ref(), now_secs))
+            .collect())
+    }
+}
+
+/// First problem keeping the governance canister from proposing or voting, None when healthy
+pub async fn check_governance_health(
+    probe: &impl GovernanceProbe,
+    governance: Principal,
+    now_secs: u64,
+) -> Option<GovernanceIssue> {
+    if let Err(e) = probe.list_functions(governance).await {
+        return Some(GovernanceIssue::Unreachable {
+            error: e.to_string(),
+        });
+    }
+
+    let delays = match probe.admin_dissolve_delays(governance, now_secs).await {
+        Ok(delays) => delays,
+        Err(e) => {
+            return Some(GovernanceIssue::NeuronsUnavailable {
+                error: e.to_string(),
+            })
+        }
+    };
+
+    match delays.into_iter().max() {
+        None => Some(GovernanceIssue::NoAdminNeurons),
+        Some(longest) if longest < MIN_VOTING_DISSOLVE_DELAY_SECS => {
+            Some(GovernanceIssue::NoVotingNeuron {
+                longest_dissolve_delay_seconds: longest,
+            })
+        }
+        Some(_) => None,
+    }
+}
+
+/// Checks every governance canister, unhealthy ones ordered by canister id
+pub async fn check_all_governance_health(
+    probe: &impl GovernanceProbe,
+    governed: impl IntoIterator<Item = Principal>,
+    now_secs: u64,
+) -> SnsGovernanceHealthCheckResponse {
+    let results = futures::stream::iter(governed)
+        .map(|governance| async move {
+            (
+                governance,
+                check_governance_health(probe, governance, now_secs).await,
+            )
+        })
+        .buffer_unordered(HEALTH_CHECK_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut res = SnsGovernanceHealthCheckResponse::default();
+    for (governance, issue) in results {
+        match issue {
+            None => res.healthy += 1,
+            Some(issue) => res
+                .unhealthy
+                .push(UnhealthyGovernance { governance, issue }),
+        }
+    }
+    res.unhealthy.sort_by_key(|unhealthy| unhealthy.governance);
+
+    res
+}
+
+pub fn unhealthy_alert_message(unhealthy: &[UnhealthyGovernance]) -> String {
+    let mut msg = format!(
+        "{} SNS governance canisters are unhealthy:\n",
+        unhealthy.len()
+    );
+    for unhealthy in unhealthy {
+        msg.push_str(&format!(
+            "- {}: {}\n",
+            unhealthy.governance, unhealthy.issue
+        ));
+    }
+    msg
+}
+
+/// Checks that every governance canister the upgrade job touched can still take proposals and
+/// be voted on by the admin, alerting on the ones that can't. Scheduled daily.
+#[instrument(skip(state))]
+pub async fn sns_governance_health_check(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SnsGovernanceHealthCheckResponse>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let mut conn = state
+            .canister_backup_redis_pool
+            .get()
+            .await
+            .map_err(anyhow::Error::from)?;
+        let governed: Vec<String> = conn
+            .smembers(GOVERNED_CANISTERS_KEY)
+            .await
+            .map_err(anyhow::Error::from)?;
+        drop(conn);
+
+        let governed = governed
+            .iter()
+            .filter_map(|id| match Principal::from_text(id) {
+                Ok(governance) => Some(governance),
+                Err(e) => {
+                    log::warn!("Skipping invalid governance canister id {}: {}", id, e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let now_secs = chrono::Utc::now().timestamp() as u64;
+        let res = check_all_governance_health(&state.agent, governed, now_secs).await;
+
+        if !res.unhealthy.is_empty() {
+            notify_all(
+                &state.notification_backends,
+                &unhealthy_alert_message(&res.unhealthy),
+            )
+            .await?;
+        }
+        log::info!(
+            "Checked sns governance health: {} healthy, {} unhealthy",
+            res.healthy,
+            res.unhealthy.len()
+        );
+
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Ok(Json(SnsGovernanceHealthCheckResponse::default()))
+    }
+}
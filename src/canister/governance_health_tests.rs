@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use candid::Principal;
+
+use super::governance_health::{
+    check_all_governance_health, check_governance_health, unhealthy_alert_message, GovernanceIssue,
+    GovernanceProbe, UnhealthyGovernance, MIN_VOTING_DISSOLVE_DELAY_SECS,
+};
+
+const NOW: u64 = 1_700_000_000;
+
+fn canister(i: u8) -> Principal {
+    Principal::from_slice(&[i])
+}
+
+/// Governance canisters by id, missing ones are unreachable
+#[derive(Default)]
+struct MockProbe {
+    /// None when `list_neurons` fails
+    neurons: HashMap<Principal, Option<Vec<u64>>>,
+}
+
+impl MockProbe {
+    fn with(governance: &[(u8, Option<Vec<u64>>)]) -> Self {
+        Self {
+            neurons: governance
+                .iter()
+                .map(|(i, delays)| (canister(*i), delays.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl GovernanceProbe for MockProbe {
+    async fn list_functions(&self, governance: Principal) -> Result<(), anyhow::Error> {
+        if self.neurons.contains_key(&governance) {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("canister trapped"))
+        }
+    }
+
+    async fn admin_dissolve_delays(
+        &self,
+        governance: Principal,
+        now_secs: u64,
+    ) -> Result<Vec<u64>, anyhow::Error> {
+        assert_eq!(now_secs, NOW);
+        self.neurons[&governance]
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("list_neurons rejected"))
+    }
+}
+
+#[tokio::test]
+async fn test_one_voting_neuron_is_healthy() {
+    let probe = MockProbe::with(&[(1, Some(vec![0, 3600, MIN_VOTING_DISSOLVE_DELAY_SECS]))]);
+
+    assert_eq!(
+        check_governance_health(&probe, canister(1), NOW).await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_unreachable_canister() {
+    let probe = MockProbe::default();
+
+    assert_eq!(
+        check_governance_health(&probe, canister(1), NOW).await,
+        Some(GovernanceIssue::Unreachable {
+            error: "canister trapped".into()
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_failed_list_neurons() {
+    let probe = MockProbe::with(&[(1, None)]);
+
+    assert_eq!(
+        check_governance_health(&probe, canister(1), NOW).await,
+        Some(GovernanceIssue::NeuronsUnavailable {
+            error: "list_neurons rejected".into()
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_no_admin_neurons() {
+    let probe = MockProbe::with(&[(1, Some(vec![]))]);
+
+    assert_eq!(
+        check_governance_health(&probe, canister(1), NOW).await,
+        Some(GovernanceIssue::NoAdminNeurons)
+    );
+}
+
+#[tokio::test]
+async fn test_neurons_dissolving_too_soon_cannot_vote() {
+    let probe = MockProbe::with(&[(1, Some(vec![0, MIN_VOTING_DISSOLVE_DELAY_SECS - 1]))]);
+
+    assert_eq!(
+        check_governance_health(&probe, canister(1), NOW).await,
+        Some(GovernanceIssue::NoVotingNeuron {
+            longest_dissolve_delay_seconds: MIN_VOTING_DISSOLVE_DELAY_SECS - 1
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_check_all_counts_healthy_and_orders_unhealthy() {
+    let probe = MockProbe::with(&[
+        (1, Some(vec![MIN_VOTING_DISSOLVE_DELAY_SECS])),
+        (2, Some(vec![])),
+        (4, Some(vec![MIN_VOTING_DISSOLVE_DELAY_SECS * 2])),
+    ]);
+
+    let res = check_all_governance_health(
+        &probe,
+        [canister(4), canister(3), canister(2), canister(1)],
+        NOW,
+    )
+    .await;
+
+    assert_eq!(res.healthy, 2);
+    assert_eq!(
+        res.unhealthy,
+        vec![
+            UnhealthyGovernance {
+                governance: canister(2),
+                issue: GovernanceIssue::NoAdminNeurons,
+            },
+            UnhealthyGovernance {
+                governance: canister(3),
+                issue: GovernanceIssue::Unreachable {
+                    error: "canister trapped".into()
+                },
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_alert_message_lists_every_canister() {
+    let msg = unhealthy_alert_message(&[
+        UnhealthyGovernance {
+            governance: canister(2),
+            issue: GovernanceIssue::NoAdminNeurons,
+        },
+        UnhealthyGovernance {
+            governance: canister(3),
+            issue: GovernanceIssue::NoVotingNeuron {
+                longest_dissolve_delay_seconds: 60,
+            },
+        },
+    ]);
+
+    assert!(msg.starts_with("2 SNS governance canisters are unhealthy"));
+    assert!(msg.contains(&format!(
+        "- {}: admin principal has no neurons",
+        canister(2)
+    )));
+    assert!(msg.contains("longest dissolve delay is 60s (needs 172800s)"));
+}
@@ -1,6 +1,35 @@
+pub mod bulk_claim_tokens;
+pub mod canister_metrics;
+pub mod canisters_list;
+pub mod cdao_milestone;
+pub mod governance_health;
+pub mod neuron_followees;
+pub mod neuron_health;
 pub mod queries;
 // pub mod snapshot;
 pub mod snapshot;
+pub mod sns_wasm_hashes;
 pub mod upgrade_user_token_sns_canister;
 pub mod upload_user_video;
 pub mod utils;
+
+#[cfg(test)]
+mod bulk_claim_tokens_tests;
+#[cfg(test)]
+mod canister_metrics_tests;
+#[cfg(test)]
+mod canisters_list_tests;
+#[cfg(test)]
+mod cdao_milestone_tests;
+#[cfg(test)]
+mod governance_health_tests;
+#[cfg(test)]
+mod neuron_followees_tests;
+#[cfg(test)]
+mod neuron_health_tests;
+#[cfg(test)]
+mod sns_wasm_hashes_tests;
+#[cfg(test)]
+mod upgrade_user_token_sns_canister_tests;
+#[cfg(test)]
+mod upload_user_video_tests;
@@ -0,0 +1,273 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use candid::Principal;
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+#[cfg(not(feature = "local-bin"))]
+use yral_canisters_client::sns_governance::{
+    Command, Command1, Follow, ListNeurons, ManageNeuron, NeuronId, SnsGovernance,
+};
+
+#[cfg(not(feature = "local-bin"))]
+use super::neuron_health::GOVERNED_CANISTERS_KEY;
+use crate::{app_state::AppState, AppError};
+
+/// Governance canisters checked per invocation, the job re-enqueues itself for the rest
+pub const SYNC_FOLLOWEES_BATCH_SIZE: usize = 50;
+pub const SYNC_NEURON_FOLLOWEES_CRON: &str = "0 3 * * 1";
+pub const SYNC_NEURON_FOLLOWEES_SCHEDULE_ID: &str = "sync-sns-neuron-followees";
+/// Function the admin's second neuron follows the first on, see `setup_neurons_for_admin_principal`
+pub const UPGRADE_SNS_FUNCTION_NAME: &str = "Upgrade SNS to next version";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SyncNeuronFolloweesRequest {
+    /// Last governance canister of the previous invocation, checking resumes after it
+    #[serde(default)]
+    pub after: Option<Principal>,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct SyncNeuronFolloweesResponse {
+    pub checked: usize,
+    pub repaired: Vec<Principal>,
+    pub failed: Vec<Principal>,
+    /// Set when the job was re-enqueued from there
+    pub next: Option<Principal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminNeuron {
+    pub id: Vec<u8>,
+    /// Followed neurons per function id
+    pub followees: Vec<(u64, Vec<Vec<u8>>)>,
+}
+
+impl AdminNeuron {
+    pub fn followees_of(&self, function_id: u64) -> &[Vec<u8>] {
+        self.followees
+            .iter()
+            .find(|(id, _)| *id == function_id)
+            .map(|(_, followees)| followees.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+pub(crate) trait FolloweeGovernance {
+    /// Id of the [`UPGRADE_SNS_FUNCTION_NAME`] function
+    async fn upgrade_function_id(&self, governance: Principal) -> Result<u64, anyhow::Error>;
+
+    /// Neurons of the agent's principal, in the order `list_neurons` returns them
+    async fn admin_neurons(&self, governance: Principal)
+        -> Result<Vec<AdminNeuron>, anyhow::Error>;
+
+    async fn follow(
+        &self,
+        governance: Principal,
+        neuron_id: &[u8],
+        function_id: u64,
+        followee: &[u8],
+    ) -> Result<(), anyhow::Error>;
+}
+
+/// Makes the second admin neuron follow only the first one on SNS upgrades, returns whether it
+/// had to be repaired
+pub async fn sync_neuron_followees(
+    governance_api: &impl FolloweeGovernance,
+    governance: Principal,
+) -> Result<bool, anyhow::Error> {
+    let neurons = governance_api.admin_neurons(governance).await?;
+    let [first, second, ..] = neurons.as_slice() else {
+        return Err(anyhow::anyhow!(
+            "expected 2 admin neurons, found {}",
+            neurons.len()
+        ));
+    };
+
+    let function_id = governance_api.upgrade_function_id(governance).await?;
+    if second.followees_of(function_id) == std::slice::from_ref(&first.id) {
+        return Ok(false);
+    }
+
+    governance_api
+        .follow(governance, &second.id, function_id, &first.id)
+        .await?;
+
+    Ok(true)
+}
+
+/// Checks up to `batch_size` governance canisters after `after`, in principal order
+pub async fn sync_neuron_followees_batch(
+    governance_api: &impl FolloweeGovernance,
+    mut governed: Vec<Principal>,
+    after: Option<Principal>,
+    batch_size: usize,
+) -> SyncNeuronFolloweesResponse {
+    governed.sort();
+    governed.dedup();
+    let remaining: Vec<Principal> = governed
+        .into_iter()
+        .filter(|governance| after.is_none_or(|after| *governance > after))
+        .collect();
+
+    let mut res = SyncNeuronFolloweesResponse::default();
+    for governance in remaining.iter().take(batch_size) {
+        res.checked += 1;
+        match sync_neuron_followees(governance_api, *governance).await {
+            Ok(true) => {
+                log::info!("Repaired upgrade followees of governance {}", governance);
+                res.repaired.push(*governance);
+            }
+            Ok(false) => {}
+            Err(e) => {
+                log::warn!(
+                    "Failed to sync followees of governance {}: {}",
+                    governance,
+                    e
+                );
+                res.failed.push(*governance);
+            }
+        }
+    }
+
+    if remaining.len() > batch_size {
+        res.next = Some(remaining[batch_size - 1]);
+    }
+
+    res
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl FolloweeGovernance for ic_agent::Agent {
+    async fn upgrade_function_id(&self, governance: Principal) -> Result<u64, anyhow::Error> {
+        SnsGovernance(governance, self)
+            .list_nervous_system_functions()
+            .await?
+            .functions
+            .iter()
+            .find(|function| function.name.contains(UPGRADE_SNS_FUNCTION_NAME))
+            .map(|function| function.id)
+            .ok_or_else(|| anyhow::anyhow!("no {} function", UPGRADE_SNS_FUNCTION_NAME))
+    }
+
+    async fn admin_neurons(
+        &self,
+        governance: Principal,
+    ) -> Result<Vec<AdminNeuron>, anyhow::Error> {
+        let neurons = SnsGovernance(governance, self)
+            .list_neurons(ListNeurons {
+                of_principal: Some(self.get_principal().map_err(|e| anyhow::anyhow!(e))?),
+                limit: 10,
+                start_page_at: None,
+            })
+            .await?
+            .neurons;
+
+        Ok(neurons
+            .into_iter()
+            .filter_map(|neuron| {
+                Some(AdminNeuron {
+                    id: neuron.id?.id.to_vec(),
+                    followees: neuron
+                        .followees
+                        .into_iter()
+                        .map(|(function_id, followees)| {
+                            (
+                                function_id,
+                                followees
+                                    .followees
+                                    .into_iter()
+                                    .map(|followee| followee.id.to_vec())
+                                    .collect(),
+                            )
+                        })
+                        .collect(),
+                })
+            })
+            .collect())
+    }
+
+    async fn follow(
+        &self,
+        governance: Principal,
+        neuron_id: &[u8],
+        function_id: u64,
+        followee: &[u8],
+    ) -> Result<(), anyhow::Error> {
+        let res = SnsGovernance(governance, self)
+            .manage_neuron(ManageNeuron {
+                subaccount: neuron_id.to_vec().into(),
+                command: Some(Command::Follow(Follow {
+                    function_id,
+                    followees: vec![NeuronId {
+                        id: followee.to_vec().into(),
+                    }],
+                })),
+            })
+            .await?;
+
+        match res.command {
+            Some(Command1::Follow(_)) => Ok(()),
+            Some(Command1::Error(e)) => Err(anyhow::anyhow!(e.error_message)),
+            _ => Err(anyhow::anyhow!("unexpected manage_neuron response")),
+        }
+    }
+}
+
+/// Repairs the upgrade followees of the admin neurons in `governed_canisters`,
+/// [`SYNC_FOLLOWEES_BATCH_SIZE`] canisters at a time. Scheduled weekly, the schedule posts
+/// without a body, which starts from the first canister.
+#[instrument(skip(state))]
+pub async fn sync_sns_neuron_followees(
+    State(state): State<Arc<AppState>>,
+    req: Option<Json<SyncNeuronFolloweesRequest>>,
+) -> Result<Json<SyncNeuronFolloweesResponse>, AppError> {
+    let after = req.and_then(|Json(req)| req.after);
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let mut conn = state
+            .canister_backup_redis_pool
+            .get()
+            .await
+            .map_err(|e| AppError::Internal(e.into()))?;
+        let governed: Vec<String> = conn
+            .smembers(GOVERNED_CANISTERS_KEY)
+            .await
+            .map_err(|e| AppError::Internal(e.into()))?;
+        drop(conn);
+        let governed = governed
+            .iter()
+            .filter_map(|id| Principal::from_text(id).ok())
+            .collect();
+
+        let res =
+            sync_neuron_followees_batch(&state.agent, governed, after, SYNC_FOLLOWEES_BATCH_SIZE)
+                .await;
+
+        if let Some(next) = res.next {
+            state
+                .qstash_client
+                .publish_sync_sns_neuron_followees(&SyncNeuronFolloweesRequest {
+                    after: Some(next),
+                })
+                .await?;
+        }
+        log::info!(
+            "Synced neuron followees of {} governance canisters: {} repaired, {} failed",
+            res.checked,
+            res.repaired.len(),
+            res.failed.len()
+        );
+
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, after);
+        Ok(Json(SyncNeuronFolloweesResponse::default()))
+    }
+}
@@ -0,0 +1,151 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use candid::Principal;
+
+use super::neuron_followees::{
+    sync_neuron_followees, sync_neuron_followees_batch, AdminNeuron, FolloweeGovernance,
+};
+
+const UPGRADE_FUNCTION_ID: u64 = 7;
+
+fn canister(i: u8) -> Principal {
+    Principal::from_slice(&[i])
+}
+
+fn neuron(id: u8, followees: Vec<(u64, Vec<Vec<u8>>)>) -> AdminNeuron {
+    AdminNeuron {
+        id: vec![id],
+        followees,
+    }
+}
+
+/// The admin neurons of every governance canister, records each `Follow` sent
+#[derive(Default)]
+struct MockGovernance {
+    neurons: HashMap<Principal, Vec<AdminNeuron>>,
+    follows: Mutex<Vec<(Principal, Vec<u8>, u64, Vec<u8>)>>,
+}
+
+impl MockGovernance {
+    fn with(neurons: Vec<(u8, Vec<AdminNeuron>)>) -> Self {
+        Self {
+            neurons: neurons
+                .into_iter()
+                .map(|(i, neurons)| (canister(i), neurons))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn follows(&self) -> Vec<(Principal, Vec<u8>, u64, Vec<u8>)> {
+        self.follows.lock().unwrap().clone()
+    }
+}
+
+impl FolloweeGovernance for MockGovernance {
+    async fn upgrade_function_id(&self, _governance: Principal) -> Result<u64, anyhow::Error> {
+        Ok(UPGRADE_FUNCTION_ID)
+    }
+
+    async fn admin_neurons(
+        &self,
+        governance: Principal,
+    ) -> Result<Vec<AdminNeuron>, anyhow::Error> {
+        self.neurons
+            .get(&governance)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("canister unreachable"))
+    }
+
+    async fn follow(
+        &self,
+        governance: Principal,
+        neuron_id: &[u8],
+        function_id: u64,
+        followee: &[u8],
+    ) -> Result<(), anyhow::Error> {
+        self.follows.lock().unwrap().push((
+            governance,
+            neuron_id.to_vec(),
+            function_id,
+            followee.to_vec(),
+        ));
+        Ok(())
+    }
+}
+
+/// Admin neurons as `setup_neurons_for_admin_principal` leaves them
+fn synced_neurons() -> Vec<AdminNeuron> {
+    vec![
+        neuron(1, vec![]),
+        neuron(2, vec![(UPGRADE_FUNCTION_ID, vec![vec![1]])]),
+    ]
+}
+
+#[tokio::test]
+async fn test_synced_followees_are_left_alone() {
+    let governance = MockGovernance::with(vec![(1, synced_neurons())]);
+
+    assert!(!sync_neuron_followees(&governance, canister(1))
+        .await
+        .unwrap());
+    assert!(governance.follows().is_empty());
+}
+
+#[tokio::test]
+async fn test_drifted_followees_are_repaired() {
+    for followees in [
+        vec![],
+        vec![(UPGRADE_FUNCTION_ID, vec![])],
+        vec![(UPGRADE_FUNCTION_ID, vec![vec![9]])],
+        vec![(UPGRADE_FUNCTION_ID, vec![vec![1], vec![9]])],
+        vec![(UPGRADE_FUNCTION_ID + 1, vec![vec![1]])],
+    ] {
+        let governance =
+            MockGovernance::with(vec![(1, vec![neuron(1, vec![]), neuron(2, followees)])]);
+
+        assert!(sync_neuron_followees(&governance, canister(1))
+            .await
+            .unwrap());
+        assert_eq!(
+            governance.follows(),
+            vec![(canister(1), vec![2], UPGRADE_FUNCTION_ID, vec![1])]
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_missing_second_neuron_fails() {
+    let governance = MockGovernance::with(vec![(1, vec![neuron(1, vec![])])]);
+
+    let err = sync_neuron_followees(&governance, canister(1))
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "expected 2 admin neurons, found 1");
+    assert!(governance.follows().is_empty());
+}
+
+#[tokio::test]
+async fn test_batch_resumes_after_the_last_checked_canister() {
+    let drifted = vec![neuron(1, vec![]), neuron(2, vec![])];
+    let governance = MockGovernance::with(vec![
+        (1, synced_neurons()),
+        (2, drifted.clone()),
+        (4, synced_neurons()),
+        (5, drifted),
+    ]);
+    let governed: Vec<Principal> = (1..=5).rev().map(canister).collect();
+
+    let first = sync_neuron_followees_batch(&governance, governed.clone(), None, 3).await;
+    assert_eq!(first.checked, 3);
+    assert_eq!(first.repaired, vec![canister(2)]);
+    assert_eq!(first.failed, vec![canister(3)]);
+    assert_eq!(first.next, Some(canister(3)));
+
+    let last = sync_neuron_followees_batch(&governance, governed, first.next, 3).await;
+    assert_eq!(last.checked, 2);
+    assert_eq!(last.repaired, vec![canister(5)]);
+    assert!(last.failed.is_empty());
+    assert_eq!(last.next, None);
+}
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use candid::Principal;
+use futures::StreamExt;
+use http::StatusCode;
+use ic_agent::Agent;
+use redis::AsyncCommands;
+use serde::Serialize;
+use tracing::instrument;
+use yral_canisters_client::sns_governance::{DissolveState, ListNeurons, SnsGovernance};
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    types::RedisPool,
+};
+
+/// Redis set of governance canisters the upgrade job has touched
+pub const GOVERNED_CANISTERS_KEY: &str = "governed_canisters";
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct NeuronHealth {
+    pub canister_id: Principal,
+    pub neuron_stake_e8s: u64,
+    pub dissolve_delay_seconds: u64,
+    pub is_dissolved: bool,
+}
+
+/// Remaining dissolve delay of a neuron, 0 once it has dissolved
+pub fn remaining_dissolve_delay(dissolve_state: Option<&DissolveState>, now_secs: u64) -> u64 {
+    match dissolve_state {
+        Some(DissolveState::DissolveDelaySeconds(delay)) => *delay,
+        Some(DissolveState::WhenDissolvedTimestampSeconds(ts)) => ts.saturating_sub(now_secs),
+        None => 0,
+    }
+}
+
+/// Aggregates the admin neurons of one governance canister: total stake and the
+/// shortest remaining dissolve delay. Returns None if the admin has no neurons there.
+pub fn aggregate_neuron_health<'a>(
+    canister_id: Principal,
+    neurons: impl IntoIterator<Item = (u64, Option<&'a DissolveState>)>,
+    now_secs: u64,
+) -> Option<NeuronHealth> {
+    neurons
+        .into_iter()
+        .map(|(stake, dissolve_state)| (stake, remaining_dissolve_delay(dissolve_state, now_secs)))
+        .fold(None, |acc: Option<NeuronHealth>, (stake, delay)| {
+            let mut health = acc.unwrap_or(NeuronHealth {
+                canister_id,
+                neuron_stake_e8s: 0,
+                dissolve_delay_seconds: u64::MAX,
+                is_dissolved: false,
+            });
+            health.neuron_stake_e8s += stake;
+            health.dissolve_delay_seconds = health.dissolve_delay_seconds.min(delay);
+            health.is_dissolved |= delay == 0;
+            Some(health)
+        })
+}
+
+/// Nearly dissolved neurons first
+pub fn sort_by_dissolve_delay(health: &mut [NeuronHealth]) {
+    health.sort_by_key(|h| (h.dissolve_delay_seconds, h.canister_id));
+}
+
+pub async fn track_governed_canister(
+    redis_pool: &RedisPool,
+    governance: Principal,
+) -> Result<(), anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    conn.sadd::<_, _, ()>(GOVERNED_CANISTERS_KEY, governance.to_text())
+        .await?;
+
+    Ok(())
+}
+
+async fn get_admin_neuron_health(
+    agent: &Agent,
+    governance: Principal,
+    now_secs: u64,
+) -> Result<Option<NeuronHealth>, anyhow::Error> {
+    let sns_governance = SnsGovernance(governance, agent);
+    let neurons = sns_governance
+        .list_neurons(ListNeurons {
+            of_principal: Some(agent.get_principal().map_err(|e| anyhow::anyhow!(e))?),
+            limit: 10,
+            start_page_at: None,
+        })
+        .await?
+        .neurons;
+
+    Ok(aggregate_neuron_health(
+        governance,
+        neurons
+            .iter()
+            .map(|n| (n.cached_neuron_stake_e8s, n.dissolve_state.as_ref())),
+        now_secs,
+    ))
+}
+
+#[instrument(skip(state, token))]
+pub async fn neuron_health_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<NeuronHealth>>, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let mut conn = state.canister_backup_redis_pool.get().await.map_err(|e| {
+            log::error!("Failed to get redis connection: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        let governed: Vec<String> = conn.smembers(GOVERNED_CANISTERS_KEY).await.map_err(|e| {
+            log::error!("Failed to read governed canisters: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        drop(conn);
+
+        let now_secs = chrono::Utc::now().timestamp() as u64;
+        let agent = &state.agent;
+
+        let mut health: Vec<NeuronHealth> = futures::stream::iter(
+            governed
+                .iter()
+                .filter_map(|id| Principal::from_text(id).ok()),
+        )
+        .map(|governance| async move {
+            match get_admin_neuron_health(agent, governance, now_secs).await {
+                Ok(health) => health,
+                Err(e) => {
+                    log::warn!("Failed to list neurons of {}: {}", governance, e);
+                    None
+                }
+            }
+        })
+        .buffer_unordered(20)
+        .filter_map(|health| async move { health })
+        .collect()
+        .await;
+
+        sort_by_dissolve_delay(&mut health);
+
+        Ok(Json(health))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Ok(Json(vec![]))
+    }
+}
@@ -0,0 +1,66 @@
+use candid::Principal;
+use yral_canisters_client::sns_governance::DissolveState;
+
+use super::neuron_health::{aggregate_neuron_health, sort_by_dissolve_delay, NeuronHealth};
+
+const NOW: u64 = 1_700_000_000;
+
+fn canister(i: u8) -> Principal {
+    Principal::from_slice(&[i])
+}
+
+#[test]
+fn test_no_neurons_reports_nothing() {
+    assert_eq!(aggregate_neuron_health(canister(1), vec![], NOW), None);
+}
+
+#[test]
+fn test_aggregate_sums_stake_and_takes_shortest_delay() {
+    let locked = DissolveState::DissolveDelaySeconds(172800);
+    let dissolving = DissolveState::WhenDissolvedTimestampSeconds(NOW + 3600);
+
+    let health = aggregate_neuron_health(
+        canister(1),
+        vec![(100, Some(&locked)), (50, Some(&dissolving))],
+        NOW,
+    )
+    .unwrap();
+
+    assert_eq!(
+        health,
+        NeuronHealth {
+            canister_id: canister(1),
+            neuron_stake_e8s: 150,
+            dissolve_delay_seconds: 3600,
+            is_dissolved: false,
+        }
+    );
+}
+
+#[test]
+fn test_dissolved_neuron_is_flagged() {
+    let dissolved = DissolveState::WhenDissolvedTimestampSeconds(NOW - 1);
+
+    let health = aggregate_neuron_health(canister(1), vec![(100, Some(&dissolved))], NOW).unwrap();
+    assert!(health.is_dissolved);
+    assert_eq!(health.dissolve_delay_seconds, 0);
+
+    let health = aggregate_neuron_health(canister(1), vec![(100, None)], NOW).unwrap();
+    assert!(health.is_dissolved);
+}
+
+#[test]
+fn test_sort_puts_nearly_dissolved_first() {
+    let health = |i, delay| NeuronHealth {
+        canister_id: canister(i),
+        neuron_stake_e8s: 0,
+        dissolve_delay_seconds: delay,
+        is_dissolved: delay == 0,
+    };
+    let mut all = vec![health(1, 172800), health(2, 0), health(3, 3600)];
+
+    sort_by_dissolve_delay(&mut all);
+
+    let order: Vec<u64> = all.iter().map(|h| h.dissolve_delay_seconds).collect();
+    assert_eq!(order, vec![0, 3600, 172800]);
+}
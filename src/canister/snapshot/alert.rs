@@ -4,8 +4,7 @@ use http::StatusCode;
 use ic_agent::Agent;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::{collections::HashMap, env, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 use tracing::instrument;
 
 use crate::{
@@ -15,6 +14,7 @@ use crate::{
         get_user_canister_list_for_backup,
     },
     types::RedisPool,
+    utils::notifications::{notify_all, NotificationBackends},
 };
 
 use super::{snapshot_v2::backup_canister_impl, CanisterData, CanisterType};
@@ -31,20 +31,27 @@ pub async fn snapshot_alert_job(
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     let agent = state.agent.clone();
     let canister_backup_redis_pool = state.canister_backup_redis_pool.clone();
+    let notification_backends = state.notification_backends.clone();
 
     let _ = tokio::spawn(async move {
-        snapshot_alert_job_impl(&agent, &canister_backup_redis_pool, payload.date_str)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        snapshot_alert_job_impl(
+            &agent,
+            &canister_backup_redis_pool,
+            &notification_backends,
+            payload.date_str,
+        )
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
     });
 
     Ok(StatusCode::OK)
 }
 
-#[instrument(skip(agent))]
+#[instrument(skip(agent, notification_backends))]
 pub async fn snapshot_alert_job_impl(
     agent: &Agent,
     redis_pool: &RedisPool,
+    notification_backends: &NotificationBackends,
     date_str: String,
 ) -> Result<(), anyhow::Error> {
     log::info!("Starting snapshot alert job");
@@ -96,7 +103,7 @@ pub async fn snapshot_alert_job_impl(
     let canisters_retry_backup_results =
         retry_backup_canisters(agent, redis_pool, canisters_backups, date_str).await?;
 
-    send_google_chat_alert(canisters_retry_backup_results).await?;
+    send_snapshot_alert(notification_backends, canisters_retry_backup_results).await?;
 
     Ok(())
 }
@@ -162,12 +169,10 @@ pub async fn retry_backup_canisters(
     Ok(results)
 }
 
-async fn send_google_chat_alert(
+async fn send_snapshot_alert(
+    notification_backends: &NotificationBackends,
     canisters_retry_backup_results: HashMap<String, Vec<(String, String)>>,
 ) -> Result<(), anyhow::Error> {
-    let google_webhook_url = env::var("CANISTER_BACKUP_ALERT_GOOGLE_CHAT_WEBHOOK_URL")
-        .map_err(|_| anyhow::anyhow!("CANISTER_BACKUP_ALERT_GOOGLE_CHAT_WEBHOOK_URL not set"))?;
-    let client = reqwest::Client::new();
     // Calculate total count from the new structure
     let total_attention_count: usize = canisters_retry_backup_results
         .values()
@@ -175,17 +180,15 @@ async fn send_google_chat_alert(
         .sum();
 
     if total_attention_count == 0 {
-        let body = json!({
-            "text": "✅ Snapshot Retry Job Finished: All previously failing canisters backed up successfully or no canisters needed retries."
-        });
-        let res = client.post(&google_webhook_url).json(&body).send().await?;
-        if res.status().is_success() {
-            log::info!("Snapshot Retry Job Finished: 'All clear' status sent to Google Chat");
-        } else {
-            log::error!(
-                "Snapshot Retry Job Finished: Failed to send 'All clear' status to Google Chat: {}",
-                res.status()
-            );
+        let msg = "✅ Snapshot Retry Job Finished: All previously failing canisters backed up successfully or no canisters needed retries.";
+        match notify_all(notification_backends, msg).await {
+            Ok(()) => {
+                log::info!("Snapshot Retry Job Finished: 'All clear' status sent")
+            }
+            Err(e) => log::error!(
+                "Snapshot Retry Job Finished: Failed to send 'All clear' status: {}",
+                e
+            ),
         }
         return Ok(());
     }
@@ -266,7 +269,7 @@ async fn send_google_chat_alert(
 
     // Send all the messages
     log::info!(
-        "Sending {} alert message chunk(s) to Google Chat...",
+        "Sending {} alert message chunk(s)...",
         messages_to_send.len()
     );
     for (i, msg) in messages_to_send.iter().enumerate() {
@@ -281,40 +284,18 @@ async fn send_google_chat_alert(
             continue;
         }
 
-        let body = json!({ "text": msg });
-        match client.post(&google_webhook_url).json(&body).send().await {
-            Ok(res) => {
-                if res.status().is_success() {
-                    log::info!(
-                        "Successfully sent message chunk {}/{} to Google Chat",
-                        i + 1,
-                        messages_to_send.len()
-                    );
-                } else {
-                    log::error!(
-                        "Failed to send message chunk {}/{} to Google Chat: Status {} Body: {:?}",
-                        i + 1,
-                        messages_to_send.len(),
-                        res.status(),
-                        res.text()
-                            .await
-                            .unwrap_or_else(|_| "<Failed to read body>".to_string())
-                    );
-                    // Consider adding error handling strategy here (e.g., stop sending?)
-                }
-            }
-            Err(e) => {
-                log::error!(
-                    "Error sending message chunk {}/{} to Google Chat: {} ; body: {}, msg: {}, google_webhook_url: {}",
-                    i + 1,
-                    messages_to_send.len(),
-                    e,
-                    body,
-                    msg,
-                    google_webhook_url
-                );
-                // Consider adding error handling strategy here
-            }
+        match notify_all(notification_backends, msg).await {
+            Ok(()) => log::info!(
+                "Successfully sent message chunk {}/{}",
+                i + 1,
+                messages_to_send.len()
+            ),
+            Err(e) => log::error!(
+                "Failed to send message chunk {}/{}: {}",
+                i + 1,
+                messages_to_send.len(),
+                e
+            ),
         }
 
         // Add a small delay between messages if needed to avoid rate limiting
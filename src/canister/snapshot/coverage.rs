@@ -0,0 +1,177 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use candid::Principal;
+use chrono::NaiveDate;
+use once_cell::sync::Lazy;
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::instrument;
+#[cfg(not(feature = "local-bin"))]
+use yral_canisters_client::ic::PLATFORM_ORCHESTRATOR_ID;
+
+#[cfg(not(feature = "local-bin"))]
+use super::utils::backup_done_key;
+use super::CanisterType;
+#[cfg(not(feature = "local-bin"))]
+use crate::canister::utils::{get_subnet_orch_ids, get_user_canisters_list_v2};
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    AppError,
+};
+
+const BACKUP_COVERAGE_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Coverage per date, the network listing behind it is too slow to run on every request
+static BACKUP_COVERAGE_CACHE: Lazy<RwLock<HashMap<String, (Instant, BackupCoverage)>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TypeCoverage {
+    pub total_canisters: usize,
+    pub backed_up: usize,
+    pub coverage_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BackupCoverage {
+    pub date: String,
+    pub total_canisters: usize,
+    pub backed_up: usize,
+    pub coverage_pct: f64,
+    pub last_updated: String,
+    /// Keyed by `User`, `SubnetOrch` and `PlatformOrch`
+    pub by_type: BTreeMap<String, TypeCoverage>,
+}
+
+/// Percentage of `total` backed up, 0 for an empty network
+pub fn coverage_pct(backed_up: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+
+    backed_up as f64 / total as f64 * 100.0
+}
+
+/// Only canisters still in the network count as backed up, the done set can hold deleted ones
+pub fn backup_coverage(
+    date: &str,
+    done: &HashSet<String>,
+    canisters: &[(CanisterType, Vec<Principal>)],
+    last_updated: String,
+) -> BackupCoverage {
+    let by_type: BTreeMap<String, TypeCoverage> = canisters
+        .iter()
+        .map(|(canister_type, ids)| {
+            let backed_up = ids.iter().filter(|id| done.contains(&id.to_text())).count();
+            (
+                format!("{:?}", canister_type),
+                TypeCoverage {
+                    total_canisters: ids.len(),
+                    backed_up,
+                    coverage_pct: coverage_pct(backed_up, ids.len()),
+                },
+            )
+        })
+        .collect();
+
+    let total_canisters = by_type.values().map(|c| c.total_canisters).sum();
+    let backed_up = by_type.values().map(|c| c.backed_up).sum();
+
+    BackupCoverage {
+        date: date.to_string(),
+        total_canisters,
+        backed_up,
+        coverage_pct: coverage_pct(backed_up, total_canisters),
+        last_updated,
+        by_type,
+    }
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn fetch_backup_coverage(
+    state: &AppState,
+    date_str: &str,
+) -> Result<BackupCoverage, anyhow::Error> {
+    let mut conn = state.canister_backup_redis_pool.get().await?;
+    let done: HashSet<String> = conn.smembers(backup_done_key(date_str)).await?;
+    drop(conn);
+
+    let canisters = vec![
+        (
+            CanisterType::User,
+            get_user_canisters_list_v2(&state.agent).await?,
+        ),
+        (
+            CanisterType::SubnetOrch,
+            get_subnet_orch_ids(&state.agent).await?,
+        ),
+        (CanisterType::PlatformOrch, vec![PLATFORM_ORCHESTRATOR_ID]),
+    ];
+
+    Ok(backup_coverage(
+        date_str,
+        &done,
+        &canisters,
+        chrono::Utc::now().to_rfc3339(),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackupCoverageParams {
+    /// `%Y-%m-%d`, today when missing
+    pub date_str: Option<String>,
+}
+
+/// Share of the network backed up on a day
+#[instrument(skip(state, token))]
+pub async fn backup_coverage_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Query(params): Query<BackupCoverageParams>,
+) -> Result<Json<BackupCoverage>, AppError> {
+    check_auth_admin(&token).map_err(|_| AppError::Unauthorized)?;
+
+    let date_str = params
+        .date_str
+        .unwrap_or_else(|| chrono::Utc::now().format("%Y-%m-%d").to_string());
+    NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
+        .map_err(|_| AppError::InvalidInput(format!("invalid date {}", date_str)))?;
+
+    if let Some((fetched_at, coverage)) = BACKUP_COVERAGE_CACHE.read().await.get(&date_str) {
+        if fetched_at.elapsed() < BACKUP_COVERAGE_CACHE_TTL {
+            return Ok(Json(coverage.clone()));
+        }
+    }
+
+    #[cfg(not(feature = "local-bin"))]
+    let coverage = fetch_backup_coverage(&state, &date_str).await?;
+
+    #[cfg(feature = "local-bin")]
+    let coverage = {
+        let _ = state;
+        backup_coverage(
+            &date_str,
+            &HashSet::new(),
+            &[],
+            chrono::Utc::now().to_rfc3339(),
+        )
+    };
+
+    BACKUP_COVERAGE_CACHE
+        .write()
+        .await
+        .insert(date_str, (Instant::now(), coverage.clone()));
+
+    Ok(Json(coverage))
+}
@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+
+use candid::Principal;
+
+use super::{
+    coverage::{backup_coverage, coverage_pct},
+    CanisterType,
+};
+
+fn canister(n: u8) -> Principal {
+    Principal::from_slice(&[n])
+}
+
+fn done(ids: &[Principal]) -> HashSet<String> {
+    ids.iter().map(|id| id.to_text()).collect()
+}
+
+#[test]
+fn test_coverage_pct() {
+    assert_eq!(coverage_pct(0, 0), 0.0);
+    assert_eq!(coverage_pct(0, 10), 0.0);
+    assert_eq!(coverage_pct(5, 10), 50.0);
+    assert_eq!(coverage_pct(10, 10), 100.0);
+}
+
+#[test]
+fn test_coverage_of_empty_network() {
+    let coverage = backup_coverage(
+        "2025-01-01",
+        &done(&[canister(1)]),
+        &[
+            (CanisterType::User, vec![]),
+            (CanisterType::SubnetOrch, vec![]),
+        ],
+        "now".into(),
+    );
+
+    assert_eq!(coverage.total_canisters, 0);
+    assert_eq!(coverage.backed_up, 0);
+    assert_eq!(coverage.coverage_pct, 0.0);
+    assert_eq!(coverage.by_type["User"].coverage_pct, 0.0);
+}
+
+#[test]
+fn test_full_coverage() {
+    let users = vec![canister(1), canister(2)];
+    let coverage = backup_coverage(
+        "2025-01-01",
+        &done(&[canister(1), canister(2), canister(9)]),
+        &[
+            (CanisterType::User, users),
+            (CanisterType::PlatformOrch, vec![canister(9)]),
+        ],
+        "now".into(),
+    );
+
+    assert_eq!(coverage.total_canisters, 3);
+    assert_eq!(coverage.backed_up, 3);
+    assert_eq!(coverage.coverage_pct, 100.0);
+}
+
+#[test]
+fn test_breakdown_by_type_ignores_deleted_canisters() {
+    let coverage = backup_coverage(
+        "2025-01-01",
+        &done(&[canister(1), canister(5), canister(42)]),
+        &[
+            (
+                CanisterType::User,
+                vec![canister(1), canister(2), canister(3), canister(4)],
+            ),
+            (CanisterType::SubnetOrch, vec![canister(5)]),
+            (CanisterType::PlatformOrch, vec![canister(9)]),
+        ],
+        "2025-01-01T12:00:00Z".into(),
+    );
+
+    assert_eq!(coverage.date, "2025-01-01");
+    assert_eq!(coverage.total_canisters, 6);
+    assert_eq!(coverage.backed_up, 2);
+    assert_eq!(coverage.by_type["User"].backed_up, 1);
+    assert_eq!(coverage.by_type["User"].coverage_pct, 25.0);
+    assert_eq!(coverage.by_type["SubnetOrch"].coverage_pct, 100.0);
+    assert_eq!(coverage.by_type["PlatformOrch"].coverage_pct, 0.0);
+    assert_eq!(coverage.last_updated, "2025-01-01T12:00:00Z");
+}
@@ -1,4 +1,4 @@
-use candid::Principal;
+use candid::{Decode, Encode, Principal};
 use ic_agent::Agent;
 use tracing::instrument;
 use yral_canisters_client::{
@@ -24,6 +24,22 @@ pub async fn get_canister_snapshot(
     }
 }
 
+/// Returns the size of the saved snapshot
+#[instrument(skip(agent))]
+pub async fn save_canister_snapshot(
+    canister_id: Principal,
+    canister_type: &CanisterType,
+    agent: &Agent,
+) -> Result<u32, anyhow::Error> {
+    let res = agent
+        .update(&canister_id, canister_type.snapshot_method_name())
+        .with_arg(Encode!()?)
+        .call_and_wait()
+        .await?;
+
+    Ok(Decode!(&res, u32)?)
+}
+
 #[instrument(skip(agent))]
 pub async fn get_user_canister_snapshot(
     canister_id: Principal,
@@ -31,10 +47,12 @@ pub async fn get_user_canister_snapshot(
 ) -> Result<Vec<u8>, anyhow::Error> {
     let user_canister = IndividualUserTemplate(canister_id, agent);
 
-    let snapshot_size = user_canister.save_snapshot_json_v_2().await.map_err(|e| {
-        log::error!("Failed to save user canister snapshot: {}", e);
-        anyhow::anyhow!("Failed to save user canister snapshot: {}", e)
-    })?;
+    let snapshot_size = save_canister_snapshot(canister_id, &CanisterType::User, agent)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to save user canister snapshot: {}", e);
+            anyhow::anyhow!("Failed to save user canister snapshot: {}", e)
+        })?;
 
     // delay 2-3 seconds with jitter
     let base_delay = 2000; // 2 second base in milliseconds
@@ -81,10 +99,12 @@ pub async fn get_subnet_orchestrator_snapshot(
 ) -> Result<Vec<u8>, anyhow::Error> {
     let subnet_orch = UserIndex(canister_id, agent);
 
-    let snapshot_size = subnet_orch.save_snapshot_json().await.map_err(|e| {
-        log::error!("Failed to save subnet orchestrator snapshot: {}", e);
-        anyhow::anyhow!("Failed to save subnet orchestrator snapshot: {}", e)
-    })?;
+    let snapshot_size = save_canister_snapshot(canister_id, &CanisterType::SubnetOrch, agent)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to save subnet orchestrator snapshot: {}", e);
+            anyhow::anyhow!("Failed to save subnet orchestrator snapshot: {}", e)
+        })?;
 
     tokio::time::sleep(std::time::Duration::from_secs(10)).await;
 
@@ -127,8 +147,7 @@ pub async fn get_platform_orchestrator_snapshot(
 ) -> Result<Vec<u8>, anyhow::Error> {
     let platform_orchestrator = PlatformOrchestrator(canister_id, agent);
 
-    let snapshot_size = platform_orchestrator
-        .save_snapshot_json()
+    let snapshot_size = save_canister_snapshot(canister_id, &CanisterType::PlatformOrch, agent)
         .await
         .map_err(|e| {
             log::error!("Failed to save platform orchestrator snapshot: {}", e);
@@ -0,0 +1,219 @@
+use std::{process::Stdio, sync::Arc};
+
+use axum::{extract::State, Json};
+use candid::Principal;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    consts::{CANISTER_BACKUPS_BUCKET, STORJ_BACKUP_CANISTER_ACCESS_GRANT},
+    AppError,
+};
+
+use super::{snapshot_v2::BackupUserCanisterPayload, utils::unmark_canister_backup_done};
+
+/// Frame magic number of zstd compressed objects
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+/// Prefix corrupted backups are moved under, out of the way of the next backup of the date
+pub const FAILED_BACKUPS_PREFIX: &str = "failed-backups";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VerifyBackupIntegrityRequest {
+    pub canister_id: Principal,
+    pub date_str: String,
+}
+
+/// Suffix of backup objects, compressed backups are stored next to the plain ones of earlier days
+#[cfg(feature = "compressed-snapshots")]
+pub const BACKUP_OBJECT_SUFFIX: &str = ".json.zst";
+#[cfg(not(feature = "compressed-snapshots"))]
+pub const BACKUP_OBJECT_SUFFIX: &str = "";
+
+/// Object the backup of the date is uploaded to, see `upload_snapshot_to_storj_v2`
+pub fn backup_object_key(canister_id: Principal, date_str: &str) -> String {
+    format!("{}/{}{}", canister_id, date_str, BACKUP_OBJECT_SUFFIX)
+}
+
+pub fn failed_backup_object_key(canister_id: Principal, date_str: &str) -> String {
+    format!(
+        "{}/{}",
+        FAILED_BACKUPS_PREFIX,
+        backup_object_key(canister_id, date_str)
+    )
+}
+
+/// The snapshot JSON of a downloaded backup, decompressed when it is zstd
+pub fn decode_backup(bytes: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    if !bytes.starts_with(&ZSTD_MAGIC) {
+        return Ok(bytes.to_vec());
+    }
+
+    zstd::stream::decode_all(bytes).map_err(|e| anyhow::anyhow!("backup is not valid zstd: {}", e))
+}
+
+/// A backup is intact when it is JSON, zstd compressed or not
+pub fn validate_backup(bytes: &[u8]) -> Result<(), anyhow::Error> {
+    if bytes.is_empty() {
+        return Err(anyhow::anyhow!("backup is empty"));
+    }
+
+    let json = decode_backup(bytes)?;
+
+    serde_json::from_slice::<serde_json::Value>(&json)
+        .map_err(|e| anyhow::anyhow!("backup is not valid JSON: {}", e))?;
+
+    Ok(())
+}
+
+pub(crate) trait BackupRepair {
+    async fn download_backup(&self, key: &str) -> Result<Vec<u8>, anyhow::Error>;
+
+    async fn move_backup(&self, from: &str, to: &str) -> Result<(), anyhow::Error>;
+
+    /// Removes the canister from the date's `backup:done` set so it can be backed up again
+    async fn unmark_backup_done(
+        &self,
+        canister_id: Principal,
+        date_str: &str,
+    ) -> Result<(), anyhow::Error>;
+
+    async fn enqueue_backup(
+        &self,
+        payload: &BackupUserCanisterPayload,
+    ) -> Result<(), anyhow::Error>;
+}
+
+fn storj_url(key: &str) -> String {
+    format!("sj://{}/{}", CANISTER_BACKUPS_BUCKET, key)
+}
+
+async fn run_uplink(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    let output = Command::new("uplink")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "uplink {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl BackupRepair for AppState {
+    async fn download_backup(&self, key: &str) -> Result<Vec<u8>, anyhow::Error> {
+        run_uplink(&[
+            "cp",
+            "--analytics=false",
+            "--progress=false",
+            "--access",
+            &STORJ_BACKUP_CANISTER_ACCESS_GRANT,
+            &storj_url(key),
+            "-",
+        ])
+        .await
+    }
+
+    async fn move_backup(&self, from: &str, to: &str) -> Result<(), anyhow::Error> {
+        run_uplink(&[
+            "mv",
+            "--access",
+            &STORJ_BACKUP_CANISTER_ACCESS_GRANT,
+            &storj_url(from),
+            &storj_url(to),
+        ])
+        .await?;
+        Ok(())
+    }
+
+    async fn unmark_backup_done(
+        &self,
+        canister_id: Principal,
+        date_str: &str,
+    ) -> Result<(), anyhow::Error> {
+        unmark_canister_backup_done(&self.canister_backup_redis_pool, date_str, canister_id).await
+    }
+
+    async fn enqueue_backup(
+        &self,
+        payload: &BackupUserCanisterPayload,
+    ) -> Result<(), anyhow::Error> {
+        self.qstash_client
+            .publish_backup_user_canister(payload)
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct VerifyBackupIntegrityResponse {
+    pub valid: bool,
+    /// Why the backup was found corrupted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Validates the date's backup of the canister. A corrupted backup is moved under
+/// [`FAILED_BACKUPS_PREFIX`], unmarked as done and backed up again.
+pub async fn verify_backup_integrity_impl(
+    repair: &impl BackupRepair,
+    req: &VerifyBackupIntegrityRequest,
+) -> Result<VerifyBackupIntegrityResponse, anyhow::Error> {
+    let key = backup_object_key(req.canister_id, &req.date_str);
+    let bytes = repair.download_backup(&key).await?;
+
+    let Err(e) = validate_backup(&bytes) else {
+        return Ok(VerifyBackupIntegrityResponse {
+            valid: true,
+            error: None,
+        });
+    };
+    log::error!("Backup {} is corrupted: {}", key, e);
+
+    repair
+        .move_backup(
+            &key,
+            &failed_backup_object_key(req.canister_id, &req.date_str),
+        )
+        .await?;
+    repair
+        .unmark_backup_done(req.canister_id, &req.date_str)
+        .await?;
+    repair
+        .enqueue_backup(&BackupUserCanisterPayload {
+            canister_id: req.canister_id,
+            date_str: req.date_str.clone(),
+        })
+        .await?;
+
+    Ok(VerifyBackupIntegrityResponse {
+        valid: false,
+        error: Some(e.to_string()),
+    })
+}
+
+#[instrument(skip(state))]
+pub async fn verify_backup_integrity(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<VerifyBackupIntegrityRequest>,
+) -> Result<Json<VerifyBackupIntegrityResponse>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        Ok(Json(
+            verify_backup_integrity_impl(state.as_ref(), &req).await?,
+        ))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, req);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
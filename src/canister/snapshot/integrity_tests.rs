@@ -0,0 +1,173 @@
+use std::sync::Mutex;
+
+use candid::Principal;
+
+use super::integrity::{
+    backup_object_key, failed_backup_object_key, validate_backup, verify_backup_integrity_impl,
+    BackupRepair, VerifyBackupIntegrityRequest, BACKUP_OBJECT_SUFFIX, ZSTD_MAGIC,
+};
+use super::snapshot_v2::BackupUserCanisterPayload;
+
+/// Storj holding one object, records every repair step taken
+struct MockRepair {
+    object: Vec<u8>,
+    steps: Mutex<Vec<String>>,
+}
+
+impl MockRepair {
+    fn with_object(object: &[u8]) -> Self {
+        Self {
+            object: object.to_vec(),
+            steps: Mutex::new(vec![]),
+        }
+    }
+
+    fn steps(&self) -> Vec<String> {
+        self.steps.lock().unwrap().clone()
+    }
+}
+
+impl BackupRepair for MockRepair {
+    async fn download_backup(&self, _key: &str) -> Result<Vec<u8>, anyhow::Error> {
+        Ok(self.object.clone())
+    }
+
+    async fn move_backup(&self, from: &str, to: &str) -> Result<(), anyhow::Error> {
+        self.steps
+            .lock()
+            .unwrap()
+            .push(format!("move {} {}", from, to));
+        Ok(())
+    }
+
+    async fn unmark_backup_done(
+        &self,
+        canister_id: Principal,
+        date_str: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.steps
+            .lock()
+            .unwrap()
+            .push(format!("unmark {} {}", canister_id, date_str));
+        Ok(())
+    }
+
+    async fn enqueue_backup(
+        &self,
+        payload: &BackupUserCanisterPayload,
+    ) -> Result<(), anyhow::Error> {
+        self.steps.lock().unwrap().push(format!(
+            "enqueue {} {}",
+            payload.canister_id, payload.date_str
+        ));
+        Ok(())
+    }
+}
+
+fn canister() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+fn request() -> VerifyBackupIntegrityRequest {
+    VerifyBackupIntegrityRequest {
+        canister_id: canister(),
+        date_str: "2025-01-31".into(),
+    }
+}
+
+#[test]
+fn test_backup_object_keys() {
+    assert_eq!(
+        backup_object_key(canister(), "2025-01-31"),
+        format!(
+            "rrkah-fqaaa-aaaaa-aaaaq-cai/2025-01-31{}",
+            BACKUP_OBJECT_SUFFIX
+        )
+    );
+    assert_eq!(
+        failed_backup_object_key(canister(), "2025-01-31"),
+        format!(
+            "failed-backups/rrkah-fqaaa-aaaaa-aaaaq-cai/2025-01-31{}",
+            BACKUP_OBJECT_SUFFIX
+        )
+    );
+}
+
+#[test]
+fn test_plain_json_backup_is_valid() {
+    assert!(validate_backup(br#"{"posts": [], "profile": {"name": "a"}}"#).is_ok());
+}
+
+#[test]
+fn test_compressed_json_backup_is_valid() {
+    let compressed = zstd::stream::encode_all(&br#"{"posts": []}"#[..], 3).unwrap();
+    assert!(compressed.starts_with(&ZSTD_MAGIC));
+
+    assert!(validate_backup(&compressed).is_ok());
+}
+
+#[test]
+fn test_truncated_json_backup_is_invalid() {
+    let err = validate_backup(br#"{"posts": [1, 2"#).unwrap_err();
+
+    assert!(err.to_string().starts_with("backup is not valid JSON"));
+}
+
+#[test]
+fn test_empty_backup_is_invalid() {
+    assert_eq!(
+        validate_backup(&[]).unwrap_err().to_string(),
+        "backup is empty"
+    );
+}
+
+#[test]
+fn test_truncated_zstd_backup_is_invalid() {
+    let compressed = zstd::stream::encode_all(&br#"{"posts": []}"#[..], 3).unwrap();
+
+    let err = validate_backup(&compressed[..compressed.len() - 4]).unwrap_err();
+
+    assert!(err.to_string().starts_with("backup is not valid zstd"));
+}
+
+#[test]
+fn test_compressed_garbage_is_invalid() {
+    let compressed = zstd::stream::encode_all(&b"not json"[..], 3).unwrap();
+
+    let err = validate_backup(&compressed).unwrap_err();
+
+    assert!(err.to_string().starts_with("backup is not valid JSON"));
+}
+
+#[tokio::test]
+async fn test_valid_backup_is_left_alone() {
+    let repair = MockRepair::with_object(b"{}");
+
+    let res = verify_backup_integrity_impl(&repair, &request())
+        .await
+        .unwrap();
+
+    assert!(res.valid);
+    assert!(res.error.is_none());
+    assert!(repair.steps().is_empty());
+}
+
+#[tokio::test]
+async fn test_corrupted_backup_is_moved_and_backed_up_again() {
+    let repair = MockRepair::with_object(b"\x00\x01garbage");
+
+    let res = verify_backup_integrity_impl(&repair, &request())
+        .await
+        .unwrap();
+
+    assert!(!res.valid);
+    assert!(res.error.unwrap().starts_with("backup is not valid JSON"));
+    assert_eq!(
+        repair.steps(),
+        vec![
+            "move rrkah-fqaaa-aaaaa-aaaaq-cai/2025-01-31 failed-backups/rrkah-fqaaa-aaaaa-aaaaq-cai/2025-01-31",
+            "unmark rrkah-fqaaa-aaaaa-aaaaq-cai 2025-01-31",
+            "enqueue rrkah-fqaaa-aaaaa-aaaaq-cai 2025-01-31",
+        ]
+    );
+}
@@ -2,11 +2,29 @@ use candid::Principal;
 use serde::{Deserialize, Serialize};
 
 pub mod alert;
+pub mod coverage;
 pub mod download;
+pub mod integrity;
+pub mod sample_verify;
 pub mod snapshot_v2;
 pub mod upload;
 pub mod utils;
 
+#[cfg(test)]
+mod coverage_tests;
+#[cfg(test)]
+mod integrity_tests;
+#[cfg(test)]
+mod sample_verify_tests;
+#[cfg(test)]
+mod snapshot_tests;
+#[cfg(test)]
+mod snapshot_v2_tests;
+#[cfg(test)]
+mod upload_tests;
+#[cfg(test)]
+mod utils_tests;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum CanisterType {
     User,
@@ -14,6 +32,17 @@ pub enum CanisterType {
     PlatformOrch,
 }
 
+impl CanisterType {
+    /// Update method that serializes the canister state and returns the snapshot size
+    pub fn snapshot_method_name(&self) -> &'static str {
+        match self {
+            CanisterType::User => "save_snapshot_json_v_2",
+            CanisterType::SubnetOrch => "save_snapshot_json",
+            CanisterType::PlatformOrch => "save_snapshot_json",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CanisterData {
     pub canister_id: Principal,
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use candid::Principal;
+use futures::StreamExt;
+use rand::Rng;
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+#[cfg(not(feature = "local-bin"))]
+use crate::utils::notifications::notify_all;
+use crate::{app_state::AppState, AppError};
+
+use super::integrity::{backup_object_key, validate_backup, BackupRepair};
+#[cfg(not(feature = "local-bin"))]
+use super::utils::backup_done_key;
+
+/// Share of the day's backups verified, in percent
+pub const BACKUP_SAMPLE_PERCENT: usize = 5;
+/// Time the backup job gets to finish before its backups are sampled
+pub const VERIFY_BACKUP_SAMPLE_DELAY_SECS: u64 = 2 * 60 * 60;
+const SAMPLE_VERIFY_CONCURRENCY: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VerifyBackupSampleRequest {
+    pub date_str: String,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct BackupSampleReport {
+    /// Backups marked done for the date
+    pub total: usize,
+    pub sampled: usize,
+    pub passed: usize,
+    /// Canisters whose backup failed to download or validate, ordered by id
+    pub failed: Vec<Principal>,
+}
+
+/// [`BACKUP_SAMPLE_PERCENT`] of `total` rounded up, at least one backup out of a non empty day
+pub fn sample_size(total: usize) -> usize {
+    total.saturating_mul(BACKUP_SAMPLE_PERCENT).div_ceil(100)
+}
+
+/// Uniform sample of up to `k` items in one pass (Algorithm R)
+pub fn reservoir_sample<T>(
+    items: impl IntoIterator<Item = T>,
+    k: usize,
+    rng: &mut impl Rng,
+) -> Vec<T> {
+    let mut reservoir = Vec::with_capacity(k);
+    if k == 0 {
+        return reservoir;
+    }
+
+    for (i, item) in items.into_iter().enumerate() {
+        if i < k {
+            reservoir.push(item);
+            continue;
+        }
+
+        let j = rng.random_range(0..=i);
+        if j < k {
+            reservoir[j] = item;
+        }
+    }
+
+    reservoir
+}
+
+/// Downloads and validates every sampled backup of the date, without repairing failed ones
+pub async fn verify_backup_sample_impl(
+    store: &impl BackupRepair,
+    date_str: &str,
+    total: usize,
+    sample: Vec<Principal>,
+) -> BackupSampleReport {
+    let results = futures::stream::iter(sample)
+        .map(|canister_id| async move {
+            let key = backup_object_key(canister_id, date_str);
+            let res = match store.download_backup(&key).await {
+                Ok(bytes) => validate_backup(&bytes),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = &res {
+                log::warn!("Sampled backup {} failed verification: {}", key, e);
+            }
+            (canister_id, res.is_ok())
+        })
+        .buffer_unordered(SAMPLE_VERIFY_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut report = BackupSampleReport {
+        total,
+        sampled: results.len(),
+        ..Default::default()
+    };
+    for (canister_id, passed) in results {
+        if passed {
+            report.passed += 1;
+        } else {
+            report.failed.push(canister_id);
+        }
+    }
+    report.failed.sort();
+
+    report
+}
+
+pub fn backup_sample_message(date_str: &str, report: &BackupSampleReport) -> String {
+    let mut msg = format!(
+        "Backup sample for {}: {} of {} backups sampled, {} passed, {} failed\n",
+        date_str,
+        report.sampled,
+        report.total,
+        report.passed,
+        report.failed.len()
+    );
+    for canister_id in &report.failed {
+        msg.push_str(&format!("- {}\n", canister_id));
+    }
+    msg
+}
+
+/// Verifies a random sample of the date's backups and reports the result. Enqueued by the
+/// backup job with a delay of [`VERIFY_BACKUP_SAMPLE_DELAY_SECS`].
+#[instrument(skip(state))]
+pub async fn verify_backup_sample(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<VerifyBackupSampleRequest>,
+) -> Result<Json<BackupSampleReport>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let mut conn = state
+            .canister_backup_redis_pool
+            .get()
+            .await
+            .map_err(anyhow::Error::from)?;
+        let done: Vec<String> = conn
+            .smembers(backup_done_key(&req.date_str))
+            .await
+            .map_err(anyhow::Error::from)?;
+        drop(conn);
+
+        let total = done.len();
+        let sample = reservoir_sample(
+            done.iter().filter_map(|id| Principal::from_text(id).ok()),
+            sample_size(total),
+            &mut rand::rng(),
+        );
+
+        let report = verify_backup_sample_impl(state.as_ref(), &req.date_str, total, sample).await;
+        notify_all(
+            &state.notification_backends,
+            &backup_sample_message(&req.date_str, &report),
+        )
+        .await?;
+
+        Ok(Json(report))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, req);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::{HashMap, HashSet};
+
+use candid::Principal;
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::integrity::{backup_object_key, BackupRepair, ZSTD_MAGIC};
+use super::sample_verify::{
+    backup_sample_message, reservoir_sample, sample_size, verify_backup_sample_impl,
+    BackupSampleReport,
+};
+use super::snapshot_v2::BackupUserCanisterPayload;
+
+const DATE: &str = "2025-01-31";
+
+fn canister(i: u8) -> Principal {
+    Principal::from_slice(&[i])
+}
+
+/// Storj holding the given objects, sampling never repairs
+struct MockStore {
+    objects: HashMap<String, Vec<u8>>,
+}
+
+impl MockStore {
+    fn with_backups(backups: &[(u8, &[u8])]) -> Self {
+        Self {
+            objects: backups
+                .iter()
+                .map(|(i, bytes)| (backup_object_key(canister(*i), DATE), bytes.to_vec()))
+                .collect(),
+        }
+    }
+}
+
+impl BackupRepair for MockStore {
+    async fn download_backup(&self, key: &str) -> Result<Vec<u8>, anyhow::Error> {
+        self.objects
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("object not found"))
+    }
+
+    async fn move_backup(&self, _from: &str, _to: &str) -> Result<(), anyhow::Error> {
+        unreachable!("sampling never moves backups")
+    }
+
+    async fn unmark_backup_done(
+        &self,
+        _canister_id: Principal,
+        _date_str: &str,
+    ) -> Result<(), anyhow::Error> {
+        unreachable!("sampling never unmarks backups")
+    }
+
+    async fn enqueue_backup(
+        &self,
+        _payload: &BackupUserCanisterPayload,
+    ) -> Result<(), anyhow::Error> {
+        unreachable!("sampling never enqueues backups")
+    }
+}
+
+#[test]
+fn test_sample_size_rounds_up() {
+    assert_eq!(sample_size(0), 0);
+    assert_eq!(sample_size(1), 1);
+    assert_eq!(sample_size(20), 1);
+    assert_eq!(sample_size(21), 2);
+    assert_eq!(sample_size(100_000), 5_000);
+}
+
+#[test]
+fn test_reservoir_keeps_everything_when_small() {
+    let mut rng = StdRng::seed_from_u64(7);
+
+    assert_eq!(reservoir_sample(0..3, 5, &mut rng), vec![0, 1, 2]);
+    assert!(reservoir_sample(0..3, 0, &mut rng).is_empty());
+}
+
+#[test]
+fn test_reservoir_samples_distinct_items() {
+    let mut rng = StdRng::seed_from_u64(7);
+
+    let sample = reservoir_sample(0..1000, 50, &mut rng);
+
+    assert_eq!(sample.len(), 50);
+    assert_eq!(sample.iter().collect::<HashSet<_>>().len(), 50);
+    assert!(sample.iter().all(|item| (0..1000).contains(item)));
+}
+
+#[test]
+fn test_reservoir_reaches_every_item() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut counts = [0u32; 10];
+
+    for _ in 0..10_000 {
+        for item in reservoir_sample(0..10, 2, &mut rng) {
+            counts[item] += 1;
+        }
+    }
+
+    // each item is expected 2000 times
+    assert!(counts.iter().all(|count| (1700..2300).contains(count)));
+}
+
+#[tokio::test]
+async fn test_verification_reports_invalid_and_missing_backups() {
+    let compressed = zstd::stream::encode_all(&br#"{"ok":true}"#[..], 3).unwrap();
+    let mut truncated = ZSTD_MAGIC.to_vec();
+    truncated.push(0);
+    let store = MockStore::with_backups(&[
+        (1, br#"{"ok":true}"#),
+        (2, &compressed),
+        (3, b"{\"ok\":"),
+        (4, &truncated),
+    ]);
+
+    let report = verify_backup_sample_impl(
+        &store,
+        DATE,
+        100,
+        vec![
+            canister(5),
+            canister(4),
+            canister(3),
+            canister(2),
+            canister(1),
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        report,
+        BackupSampleReport {
+            total: 100,
+            sampled: 5,
+            passed: 2,
+            failed: vec![canister(3), canister(4), canister(5)],
+        }
+    );
+}
+
+#[test]
+fn test_message_lists_failed_canisters() {
+    let report = BackupSampleReport {
+        total: 100,
+        sampled: 5,
+        passed: 4,
+        failed: vec![canister(3)],
+    };
+
+    let msg = backup_sample_message(DATE, &report);
+
+    assert!(msg
+        .starts_with("Backup sample for 2025-01-31: 5 of 100 backups sampled, 4 passed, 1 failed"));
+    assert!(msg.contains(&format!("- {}", canister(3))));
+}
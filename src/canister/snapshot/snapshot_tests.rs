@@ -0,0 +1,21 @@
+use super::CanisterType;
+
+#[test]
+fn test_user_canister_snapshot_method() {
+    assert_eq!(
+        CanisterType::User.snapshot_method_name(),
+        "save_snapshot_json_v_2"
+    );
+}
+
+#[test]
+fn test_orchestrator_snapshot_methods() {
+    assert_eq!(
+        CanisterType::SubnetOrch.snapshot_method_name(),
+        "save_snapshot_json"
+    );
+    assert_eq!(
+        CanisterType::PlatformOrch.snapshot_method_name(),
+        "save_snapshot_json"
+    );
+}
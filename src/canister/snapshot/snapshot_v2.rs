@@ -1,6 +1,9 @@
-use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use axum::{extract::State, response::IntoResponse, Json};
@@ -20,13 +23,23 @@ use crate::{
         alert::snapshot_alert_job_impl,
         download::get_canister_snapshot,
         upload::upload_snapshot_to_storj_v2,
-        utils::{get_user_canister_list_for_backup, insert_canister_backup_date_into_redis},
+        utils::{
+            get_user_canister_list_for_backup, insert_canister_backup_date_into_redis,
+            BackupDoneSet,
+        },
     },
     types::RedisPool,
 };
 
 use super::{utils::get_subnet_orch_ids_list_for_backup, CanisterData, CanisterType};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupStatus {
+    Completed,
+    /// Already backed up for the date, e.g. during a retry run of a failed batch
+    Skipped,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BackupCanistersJobPayload {
     pub num_canisters: u32,
@@ -48,6 +61,7 @@ pub async fn backup_canisters_job_v2(
 
     let agent = state.agent.clone();
     let canister_backup_redis_pool = state.canister_backup_redis_pool.clone();
+    let notification_backends = state.notification_backends.clone();
 
     let mut user_canister_list =
         get_user_canister_list_for_backup(&agent, &canister_backup_redis_pool, date_str.clone())
@@ -61,6 +75,14 @@ pub async fn backup_canisters_job_v2(
             .collect();
     }
 
+    if let Err(e) = state
+        .qstash_client
+        .publish_verify_backup_sample(date_str.clone())
+        .await
+    {
+        log::error!("Failed to enqueue backup sample verification: {}", e);
+    }
+
     tokio::spawn(async move {
         let _failed_canisters_ids = backup_user_canisters_bulk(
             &agent,
@@ -79,8 +101,13 @@ pub async fn backup_canisters_job_v2(
 
         log::info!("Successfully backed up PF and subnet orchs. Starting snapshot alert job");
 
-        if let Err(e) =
-            snapshot_alert_job_impl(&agent, &canister_backup_redis_pool, date_str.clone()).await
+        if let Err(e) = snapshot_alert_job_impl(
+            &agent,
+            &canister_backup_redis_pool,
+            &notification_backends,
+            date_str.clone(),
+        )
+        .await
         {
             log::error!("Failed to run snapshot alert job: {}", e);
         }
@@ -100,6 +127,7 @@ pub async fn backup_user_canisters_bulk(
     let total_canisters = user_canister_list.len();
     let completed_counter = Arc::new(AtomicUsize::new(0));
     let failed_counter = Arc::new(AtomicUsize::new(0));
+    let skipped_counter = Arc::new(AtomicUsize::new(0));
 
     log::info!("Starting backup for {} canisters", total_canisters);
 
@@ -108,6 +136,7 @@ pub async fn backup_user_canisters_bulk(
         let date_str = date_str.clone();
         let completed_counter = completed_counter.clone();
         let failed_counter = failed_counter.clone();
+        let skipped_counter = skipped_counter.clone();
         let canister_data = CanisterData {
             canister_id,
             canister_type: CanisterType::User,
@@ -123,6 +152,10 @@ pub async fn backup_user_canisters_bulk(
             )
             .await;
 
+            if let Ok(BackupStatus::Skipped) = result {
+                skipped_counter.fetch_add(1, Ordering::Relaxed);
+            }
+
             let current_completed = if result.is_ok() {
                 completed_counter.fetch_add(1, Ordering::Relaxed) + 1
             } else {
@@ -144,7 +177,9 @@ pub async fn backup_user_canisters_bulk(
 
             Some((
                 canister_data.canister_id,
-                result.map_err(|e| anyhow::anyhow!("Failed to backup user canister: {}", e)),
+                result
+                    .map(|_| ())
+                    .map_err(|e| anyhow::anyhow!("Failed to backup user canister: {}", e)),
             ))
         }
     });
@@ -165,6 +200,14 @@ pub async fn backup_user_canisters_bulk(
         results.len()
     );
 
+    let skipped_count = skipped_counter.load(Ordering::Relaxed);
+    log::info!(
+        "Skipped {} already backed up canisters, backed up {} ({:.2} skipped per backup)",
+        skipped_count,
+        total_canisters - skipped_count,
+        skipped_count as f64 / (total_canisters - skipped_count).max(1) as f64
+    );
+
     Ok(failed_canisters_ids)
 }
 
@@ -252,6 +295,55 @@ pub async fn backup_canister_impl(
     canister_backup_redis_pool: &RedisPool,
     canister_data: CanisterData,
     date_str: String,
+) -> Result<BackupStatus, anyhow::Error> {
+    let canister_id = canister_data.canister_id;
+    let backup = backup_canister_snapshot(
+        agent,
+        canister_backup_redis_pool,
+        canister_data,
+        date_str.clone(),
+    );
+
+    backup_unless_done(canister_backup_redis_pool, &date_str, canister_id, backup).await
+}
+
+/// Runs the backup unless the canister is in the day's done set, and adds it to the set once the
+/// backup succeeded. A failed or interrupted backup leaves the canister out of the set so a retry
+/// run picks it up again.
+pub async fn backup_unless_done(
+    done_set: &impl BackupDoneSet,
+    date_str: &str,
+    canister_id: Principal,
+    backup: impl Future<Output = Result<(), anyhow::Error>>,
+) -> Result<BackupStatus, anyhow::Error> {
+    match done_set.is_backup_done(date_str, canister_id).await {
+        Ok(true) => {
+            log::info!(
+                "Canister {} already backed up for {}",
+                canister_id,
+                date_str
+            );
+            return Ok(BackupStatus::Skipped);
+        }
+        Ok(false) => {}
+        // redis being down should not block backups
+        Err(e) => log::error!("Failed to check backup of {}: {}", canister_id, e),
+    }
+
+    backup.await?;
+
+    if let Err(e) = done_set.mark_backup_done(date_str, canister_id).await {
+        log::error!("Failed to mark backup of {} as done: {}", canister_id, e);
+    }
+
+    Ok(BackupStatus::Completed)
+}
+
+async fn backup_canister_snapshot(
+    agent: &Agent,
+    canister_backup_redis_pool: &RedisPool,
+    canister_data: CanisterData,
+    date_str: String,
 ) -> Result<(), anyhow::Error> {
     let canister_id = canister_data.canister_id.to_string();
 
@@ -0,0 +1,156 @@
+use std::{
+    collections::HashSet,
+    future::{pending, ready},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use candid::Principal;
+
+use super::{
+    snapshot_v2::{backup_unless_done, BackupStatus},
+    utils::BackupDoneSet,
+};
+
+const DATE: &str = "2025-01-31";
+
+fn canister() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+#[derive(Default)]
+struct MockDoneSet {
+    done: Mutex<HashSet<(String, Principal)>>,
+    unavailable: bool,
+}
+
+impl MockDoneSet {
+    fn contains(&self, date_str: &str, canister_id: Principal) -> bool {
+        self.done
+            .lock()
+            .unwrap()
+            .contains(&(date_str.to_string(), canister_id))
+    }
+}
+
+impl BackupDoneSet for MockDoneSet {
+    async fn is_backup_done(
+        &self,
+        date_str: &str,
+        canister_id: Principal,
+    ) -> Result<bool, anyhow::Error> {
+        if self.unavailable {
+            return Err(anyhow::anyhow!("connection refused"));
+        }
+        Ok(self.contains(date_str, canister_id))
+    }
+
+    async fn mark_backup_done(
+        &self,
+        date_str: &str,
+        canister_id: Principal,
+    ) -> Result<(), anyhow::Error> {
+        if self.unavailable {
+            return Err(anyhow::anyhow!("connection refused"));
+        }
+        self.done
+            .lock()
+            .unwrap()
+            .insert((date_str.to_string(), canister_id));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_successful_backup_is_marked_done() {
+    let done_set = MockDoneSet::default();
+
+    let status = backup_unless_done(&done_set, DATE, canister(), ready(Ok(())))
+        .await
+        .unwrap();
+
+    assert_eq!(status, BackupStatus::Completed);
+    assert!(done_set.contains(DATE, canister()));
+}
+
+#[tokio::test]
+async fn test_done_canister_is_skipped() {
+    let done_set = MockDoneSet::default();
+    done_set.mark_backup_done(DATE, canister()).await.unwrap();
+
+    let backed_up = AtomicBool::new(false);
+    let backup = async {
+        backed_up.store(true, Ordering::SeqCst);
+        anyhow::Ok(())
+    };
+
+    let status = backup_unless_done(&done_set, DATE, canister(), backup)
+        .await
+        .unwrap();
+
+    assert_eq!(status, BackupStatus::Skipped);
+    assert!(!backed_up.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn test_done_on_another_day_is_backed_up() {
+    let done_set = MockDoneSet::default();
+    done_set
+        .mark_backup_done("2025-01-30", canister())
+        .await
+        .unwrap();
+
+    let status = backup_unless_done(&done_set, DATE, canister(), ready(Ok(())))
+        .await
+        .unwrap();
+
+    assert_eq!(status, BackupStatus::Completed);
+}
+
+#[tokio::test]
+async fn test_failed_backup_is_not_marked_done() {
+    let done_set = MockDoneSet::default();
+
+    let res = backup_unless_done(
+        &done_set,
+        DATE,
+        canister(),
+        ready(Err(anyhow::anyhow!("snapshot timed out"))),
+    )
+    .await;
+
+    assert!(res.is_err());
+    assert!(!done_set.contains(DATE, canister()));
+}
+
+#[tokio::test]
+async fn test_interrupted_backup_is_not_marked_done() {
+    let done_set = MockDoneSet::default();
+
+    // the job dies while the backup is in flight
+    let interrupted = tokio::time::timeout(
+        Duration::from_millis(10),
+        backup_unless_done(&done_set, DATE, canister(), pending()),
+    )
+    .await;
+
+    assert!(interrupted.is_err());
+    assert!(!done_set.contains(DATE, canister()));
+}
+
+#[tokio::test]
+async fn test_unavailable_done_set_does_not_block_backups() {
+    let done_set = MockDoneSet {
+        unavailable: true,
+        ..Default::default()
+    };
+
+    let status = backup_unless_done(&done_set, DATE, canister(), ready(Ok(())))
+        .await
+        .unwrap();
+
+    assert_eq!(status, BackupStatus::Completed);
+}
@@ -6,6 +6,43 @@ use tokio::{io::AsyncWriteExt, process::Command};
 
 use crate::consts::{CANISTER_BACKUPS_BUCKET, STORJ_BACKUP_CANISTER_ACCESS_GRANT};
 
+use super::integrity::backup_object_key;
+
+/// zstd level of compressed snapshots
+pub const SNAPSHOT_ZSTD_LEVEL: i32 = 3;
+
+/// Uncompressed size over compressed size
+pub fn compression_ratio(raw_len: usize, compressed_len: usize) -> f64 {
+    raw_len as f64 / compressed_len.max(1) as f64
+}
+
+/// The object stored for a snapshot, zstd compressed with the `compressed-snapshots` feature.
+/// `decode_backup` reverses it.
+pub fn encode_snapshot(
+    canister_id: Principal,
+    snapshot_bytes: Vec<u8>,
+) -> Result<Vec<u8>, anyhow::Error> {
+    #[cfg(feature = "compressed-snapshots")]
+    {
+        let compressed = zstd::stream::encode_all(&snapshot_bytes[..], SNAPSHOT_ZSTD_LEVEL)?;
+        log::info!(
+            "Compressed snapshot of {} from {} to {} bytes, ratio {:.2}",
+            canister_id,
+            snapshot_bytes.len(),
+            compressed.len(),
+            compression_ratio(snapshot_bytes.len(), compressed.len())
+        );
+
+        Ok(compressed)
+    }
+
+    #[cfg(not(feature = "compressed-snapshots"))]
+    {
+        let _ = canister_id;
+        Ok(snapshot_bytes)
+    }
+}
+
 pub async fn upload_snapshot_to_storj_v2(
     canister_id: Principal,
     object_id: String,
@@ -13,37 +50,50 @@ pub async fn upload_snapshot_to_storj_v2(
 ) -> Result<(), anyhow::Error> {
     let access_grant = &STORJ_BACKUP_CANISTER_ACCESS_GRANT.to_string();
     let bucket_name = CANISTER_BACKUPS_BUCKET;
-    let dest = format!("sj://{bucket_name}/{canister_id}/{object_id}");
+    let dest = format!(
+        "sj://{bucket_name}/{}",
+        backup_object_key(canister_id, &object_id)
+    );
+    let object = encode_snapshot(canister_id, snapshot_bytes)?;
+
+    let mut args = vec![
+        "cp",
+        "--interactive=false",
+        "--analytics=false",
+        "--progress=false",
+        "--access",
+        access_grant,
+    ];
+    #[cfg(feature = "compressed-snapshots")]
+    args.extend(["--metadata", r#"{"Content-Encoding":"zstd"}"#]);
+    args.extend(["-", dest.as_str()]); // from stdin to dest
 
     let mut child = Command::new("uplink")
-        .args([
-            "cp",
-            "--interactive=false",
-            "--analytics=false",
-            "--progress=false",
-            "--access",
-            access_grant,
-            "-",
-            dest.as_str(), // from stdin to dest
-        ])
+        .args(args)
         .stdin(Stdio::piped())
         .stdout(Stdio::null())
         .spawn()?;
 
     let mut pipe = child.stdin.take().expect("Stdin pipe to be opened for us");
 
-    pipe.write_all(&snapshot_bytes).await?;
+    pipe.write_all(&object).await?;
 
     let ninety_days_ago = Utc::now() - Duration::days(90);
     let date_str_ninety_days_ago = ninety_days_ago.format("%Y-%m-%d").to_string();
 
-    let to_delete_dest = format!("sj://{bucket_name}/{canister_id}/{date_str_ninety_days_ago}");
+    // backups from before the switch to compression are plain objects
+    let mut to_delete = vec![format!("{canister_id}/{date_str_ninety_days_ago}")];
+    to_delete.push(backup_object_key(canister_id, &date_str_ninety_days_ago));
+    to_delete.dedup();
+    for key in to_delete {
+        let to_delete_dest = format!("sj://{bucket_name}/{key}");
 
-    let mut child = Command::new("uplink")
-        .args(["rm", "--access", access_grant, to_delete_dest.as_str()])
-        .spawn()?;
+        let mut child = Command::new("uplink")
+            .args(["rm", "--access", access_grant, to_delete_dest.as_str()])
+            .spawn()?;
 
-    child.wait().await?;
+        child.wait().await?;
+    }
 
     Ok(())
 }
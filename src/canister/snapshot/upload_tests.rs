@@ -0,0 +1,60 @@
+use candid::Principal;
+
+use super::{
+    integrity::{decode_backup, validate_backup},
+    upload::{compression_ratio, encode_snapshot, SNAPSHOT_ZSTD_LEVEL},
+};
+
+/// About 10 KB of snapshot JSON
+fn snapshot_json() -> Vec<u8> {
+    let posts: Vec<serde_json::Value> = (0..100)
+        .map(|i| {
+            serde_json::json!({
+                "id": i,
+                "video_uid": format!("{:032x}", i),
+                "description": "a cooking video",
+            })
+        })
+        .collect();
+    let bytes = serde_json::to_vec(&serde_json::json!({ "posts": posts })).unwrap();
+    assert!(bytes.len() >= 10 * 1000);
+    bytes
+}
+
+#[test]
+fn test_snapshot_round_trips_through_the_backup_codec() {
+    let snapshot = snapshot_json();
+
+    let object = encode_snapshot(Principal::anonymous(), snapshot.clone()).unwrap();
+
+    assert!(validate_backup(&object).is_ok());
+    assert_eq!(decode_backup(&object).unwrap(), snapshot);
+}
+
+#[cfg(feature = "compressed-snapshots")]
+#[test]
+fn test_compressed_snapshot_is_zstd() {
+    use super::integrity::ZSTD_MAGIC;
+
+    let snapshot = snapshot_json();
+
+    let object = encode_snapshot(Principal::anonymous(), snapshot.clone()).unwrap();
+
+    assert!(object.starts_with(&ZSTD_MAGIC));
+    assert!(object.len() < snapshot.len());
+}
+
+#[test]
+fn test_plain_backups_decode_unchanged() {
+    let snapshot = snapshot_json();
+
+    assert_eq!(decode_backup(&snapshot).unwrap(), snapshot);
+    let compressed = zstd::stream::encode_all(&snapshot[..], SNAPSHOT_ZSTD_LEVEL).unwrap();
+    assert_eq!(decode_backup(&compressed).unwrap(), snapshot);
+}
+
+#[test]
+fn test_compression_ratio() {
+    assert_eq!(compression_ratio(1000, 250), 4.0);
+    assert_eq!(compression_ratio(10, 0), 10.0);
+}
@@ -15,6 +15,110 @@ use crate::{
 
 use super::CanisterData;
 
+/// A little over a day so retry runs just after midnight still see the previous day's set
+pub const BACKUP_DONE_TTL_SECS: i64 = 25 * 60 * 60;
+
+pub fn backup_done_key(date_str: &str) -> String {
+    format!("backup:done:{}", date_str)
+}
+
+fn backup_candidates_key(date_str: &str) -> String {
+    format!("backup:candidates:{}", date_str)
+}
+
+/// The day's set of canisters backed up successfully
+pub(crate) trait BackupDoneSet {
+    async fn is_backup_done(
+        &self,
+        date_str: &str,
+        canister_id: Principal,
+    ) -> Result<bool, anyhow::Error>;
+
+    async fn mark_backup_done(
+        &self,
+        date_str: &str,
+        canister_id: Principal,
+    ) -> Result<(), anyhow::Error>;
+}
+
+impl BackupDoneSet for RedisPool {
+    async fn is_backup_done(
+        &self,
+        date_str: &str,
+        canister_id: Principal,
+    ) -> Result<bool, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let done: bool = conn
+            .sismember(backup_done_key(date_str), canister_id.to_string())
+            .await?;
+
+        Ok(done)
+    }
+
+    async fn mark_backup_done(
+        &self,
+        date_str: &str,
+        canister_id: Principal,
+    ) -> Result<(), anyhow::Error> {
+        let key = backup_done_key(date_str);
+        let mut conn = self.get().await?;
+
+        redis::pipe()
+            .atomic()
+            .sadd(&key, canister_id.to_string())
+            .ignore()
+            .expire(&key, BACKUP_DONE_TTL_SECS)
+            .ignore()
+            .query_async::<()>(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Removes the canister from the day's done set so a retry run backs it up again
+pub async fn unmark_canister_backup_done(
+    canister_backup_redis_pool: &RedisPool,
+    date_str: &str,
+    canister_id: Principal,
+) -> Result<(), anyhow::Error> {
+    let mut conn = canister_backup_redis_pool.get().await?;
+    conn.srem::<_, _, ()>(backup_done_key(date_str), canister_id.to_string())
+        .await?;
+
+    Ok(())
+}
+
+/// Removes canisters already in the day's done set using SDIFF against a temporary set
+pub async fn filter_backup_done_canisters(
+    canister_backup_redis_pool: &RedisPool,
+    date_str: &str,
+    canister_ids: Vec<Principal>,
+) -> Result<Vec<Principal>, anyhow::Error> {
+    if canister_ids.is_empty() {
+        return Ok(canister_ids);
+    }
+
+    let candidates_key = backup_candidates_key(date_str);
+    let mut conn = canister_backup_redis_pool.get().await?;
+
+    conn.del::<_, ()>(&candidates_key).await?;
+    for chunk in canister_ids.chunks(10000) {
+        let chunk = chunk.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        conn.sadd::<_, _, ()>(&candidates_key, chunk).await?;
+    }
+
+    let remaining: Vec<String> = conn
+        .sdiff(&[candidates_key.clone(), backup_done_key(date_str)])
+        .await?;
+    conn.del::<_, ()>(&candidates_key).await?;
+
+    Ok(remaining
+        .iter()
+        .filter_map(|canister_id| Principal::from_text(canister_id).ok())
+        .collect())
+}
+
 pub async fn insert_canister_backup_date_into_redis(
     canister_backup_redis_pool: &RedisPool,
     date_str: String,
@@ -71,18 +175,9 @@ pub async fn get_user_canister_list_for_backup(
 
     log::info!("User canister list length: {:?}", user_canister_list.len());
 
-    let canister_backup_date_list =
-        get_canister_backup_date_list(canister_backup_redis_pool, CanisterType::User, date_str)
+    let user_canister_list =
+        filter_backup_done_canisters(canister_backup_redis_pool, &date_str, user_canister_list)
             .await?;
-    let canister_backup_date_set = canister_backup_date_list
-        .iter()
-        .map(|canister_id| Principal::from_text(canister_id).unwrap())
-        .collect::<HashSet<_>>();
-
-    let user_canister_list = user_canister_list
-        .into_iter()
-        .filter(|canister_id| !canister_backup_date_set.contains(canister_id))
-        .collect::<Vec<_>>();
 
     log::info!(
         "User canister list length after filtering: {:?}",
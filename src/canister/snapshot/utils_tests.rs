@@ -0,0 +1,12 @@
+use super::utils::{backup_done_key, BACKUP_DONE_TTL_SECS};
+
+#[test]
+fn test_backup_done_key_is_per_date() {
+    assert_eq!(backup_done_key("2025-01-31"), "backup:done:2025-01-31");
+    assert_ne!(backup_done_key("2025-01-31"), backup_done_key("2025-02-01"));
+}
+
+#[test]
+fn test_backup_done_set_outlives_the_day() {
+    assert!(BACKUP_DONE_TTL_SECS > 24 * 60 * 60);
+}
@@ -0,0 +1,224 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use candid::Principal;
+use futures::StreamExt;
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
+use hex::ToHex;
+use ic_agent::Agent;
+use serde::Serialize;
+use tracing::instrument;
+use yral_canisters_client::{
+    individual_user_template::IndividualUserTemplate,
+    sns_governance::{GetRunningSnsVersionArg, SnsGovernance, Version},
+};
+
+use crate::{
+    app_state::AppState,
+    canister::upgrade_user_token_sns_canister::{
+        SNS_TOKEN_ARCHIVE_MODULE_HASH, SNS_TOKEN_GOVERNANCE_MODULE_HASH,
+        SNS_TOKEN_INDEX_MODULE_HASH, SNS_TOKEN_LEDGER_MODULE_HASH, SNS_TOKEN_ROOT_MODULE_HASH,
+        SNS_TOKEN_SWAP_MODULE_HASH,
+    },
+    utils::notifications::notify_all,
+    AppError,
+};
+
+/// User canisters sampled, by number of uploaded videos
+pub const SNS_WASM_SAMPLE_SIZE: u32 = 100;
+pub const VERIFY_SNS_WASM_HASHES_CRON: &str = "0 4 * * 0";
+pub const VERIFY_SNS_WASM_HASHES_SCHEDULE_ID: &str = "verify-sns-wasm-hashes";
+const VERIFY_CONCURRENCY: usize = 10;
+
+/// Expected module hash of every SNS canister, as hex
+pub const EXPECTED_SNS_MODULE_HASHES: [(&str, &str); 6] = [
+    ("governance", SNS_TOKEN_GOVERNANCE_MODULE_HASH),
+    ("index", SNS_TOKEN_INDEX_MODULE_HASH),
+    ("swap", SNS_TOKEN_SWAP_MODULE_HASH),
+    ("ledger", SNS_TOKEN_LEDGER_MODULE_HASH),
+    ("root", SNS_TOKEN_ROOT_MODULE_HASH),
+    ("archive", SNS_TOKEN_ARCHIVE_MODULE_HASH),
+];
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct WasmHashMismatch {
+    pub governance: Principal,
+    /// Modules running another wasm than the expected one
+    pub modules: Vec<&'static str>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct VerifySnsWasmHashesResponse {
+    pub verified: usize,
+    pub mismatched: Vec<WasmHashMismatch>,
+    pub failed: Vec<Principal>,
+}
+
+/// Running module hashes of an SNS as hex, in the order of [`EXPECTED_SNS_MODULE_HASHES`]
+pub fn deployed_module_hashes(version: &Version) -> [String; 6] {
+    [
+        version.governance_wasm_hash.to_vec().encode_hex(),
+        version.index_wasm_hash.to_vec().encode_hex(),
+        version.swap_wasm_hash.to_vec().encode_hex(),
+        version.ledger_wasm_hash.to_vec().encode_hex(),
+        version.root_wasm_hash.to_vec().encode_hex(),
+        version.archive_wasm_hash.to_vec().encode_hex(),
+    ]
+}
+
+/// Modules whose hash differs from the expected one, compared case insensitively
+pub fn mismatched_modules(deployed: &[String; 6]) -> Vec<&'static str> {
+    EXPECTED_SNS_MODULE_HASHES
+        .iter()
+        .zip(deployed)
+        .filter(|((_, expected), deployed)| !expected.eq_ignore_ascii_case(deployed))
+        .map(|((module, _), _)| *module)
+        .collect()
+}
+
+pub fn mismatch_alert_message(mismatched: &[WasmHashMismatch]) -> String {
+    let mut msg = format!(
+        "SNS wasm hash mismatch in {} canisters:\n",
+        mismatched.len()
+    );
+    for mismatch in mismatched {
+        msg.push_str(&format!(
+            "- {}: {}\n",
+            mismatch.governance,
+            mismatch.modules.join(", ")
+        ));
+    }
+    msg
+}
+
+pub fn top_uploaders_query() -> String {
+    format!(
+        "SELECT JSON_EXTRACT_SCALAR(params, '$.canister_id') AS canister_id, COUNT(*) AS uploads
+        FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+        WHERE event = 'video_upload_successful'
+            AND JSON_EXTRACT_SCALAR(params, '$.canister_id') IS NOT NULL
+        GROUP BY canister_id
+        ORDER BY uploads DESC
+        LIMIT {}",
+        SNS_WASM_SAMPLE_SIZE
+    )
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn fetch_top_uploaders(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+) -> Result<Vec<Principal>, anyhow::Error> {
+    let request = QueryRequest {
+        query: top_uploaders_query(),
+        ..Default::default()
+    };
+    let mut response = bigquery_client
+        .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+        .await?;
+
+    let mut canisters = Vec::new();
+    while let Some(row) = response.next().await? {
+        match Principal::from_text(row.column::<String>(0)?) {
+            Ok(canister) => canisters.push(canister),
+            Err(e) => log::warn!("Skipping invalid user canister id: {}", e),
+        }
+    }
+
+    Ok(canisters)
+}
+
+async fn governance_canisters(agent: &Agent, user_canister: Principal) -> Vec<Principal> {
+    match IndividualUserTemplate(user_canister, agent)
+        .deployed_cdao_canisters()
+        .await
+    {
+        Ok(deployed) => deployed.into_iter().map(|d| d.governance).collect(),
+        Err(e) => {
+            log::warn!("Failed to list cdao canisters of {}: {}", user_canister, e);
+            vec![]
+        }
+    }
+}
+
+async fn verify_governance(
+    agent: &Agent,
+    governance: Principal,
+) -> Result<Vec<&'static str>, anyhow::Error> {
+    let deployed_version = SnsGovernance(governance, agent)
+        .get_running_sns_version(GetRunningSnsVersionArg {})
+        .await?
+        .deployed_version
+        .ok_or_else(|| anyhow::anyhow!("deployed version not found"))?;
+
+    Ok(mismatched_modules(&deployed_module_hashes(
+        &deployed_version,
+    )))
+}
+
+/// Checks the running SNS wasms of the top uploaders' token canisters against the expected
+/// hashes, alerting on mismatches. Scheduled weekly.
+#[instrument(skip(state))]
+pub async fn verify_sns_wasm_hashes(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<VerifySnsWasmHashesResponse>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let agent = &state.agent;
+        let user_canisters = fetch_top_uploaders(&state.bigquery_client)
+            .await
+            .map_err(AppError::BigQueryError)?;
+
+        let governance: Vec<Principal> = futures::stream::iter(user_canisters)
+            .map(|user_canister| governance_canisters(agent, user_canister))
+            .buffer_unordered(VERIFY_CONCURRENCY)
+            .flat_map(futures::stream::iter)
+            .collect()
+            .await;
+
+        let results =
+            futures::stream::iter(governance)
+                .map(|governance| async move {
+                    (governance, verify_governance(agent, governance).await)
+                })
+                .buffer_unordered(VERIFY_CONCURRENCY)
+                .collect::<Vec<_>>()
+                .await;
+
+        let mut res = VerifySnsWasmHashesResponse::default();
+        for (governance, result) in results {
+            match result {
+                Ok(modules) if modules.is_empty() => res.verified += 1,
+                Ok(modules) => res.mismatched.push(WasmHashMismatch {
+                    governance,
+                    modules,
+                }),
+                Err(e) => {
+                    log::warn!("Failed to get running sns version of {}: {}", governance, e);
+                    res.failed.push(governance);
+                }
+            }
+        }
+
+        if !res.mismatched.is_empty() {
+            notify_all(
+                &state.notification_backends,
+                &mismatch_alert_message(&res.mismatched),
+            )
+            .await?;
+        }
+        log::info!(
+            "Verified sns wasm hashes: {} ok, {} mismatched, {} failed",
+            res.verified,
+            res.mismatched.len(),
+            res.failed.len()
+        );
+
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Ok(Json(VerifySnsWasmHashesResponse::default()))
+    }
+}
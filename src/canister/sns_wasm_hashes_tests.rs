@@ -0,0 +1,59 @@
+use candid::Principal;
+
+use super::sns_wasm_hashes::{
+    mismatch_alert_message, mismatched_modules, WasmHashMismatch, EXPECTED_SNS_MODULE_HASHES,
+};
+
+fn expected() -> [String; 6] {
+    EXPECTED_SNS_MODULE_HASHES.map(|(_, hash)| hash.to_string())
+}
+
+#[test]
+fn test_expected_hashes_match() {
+    assert!(mismatched_modules(&expected()).is_empty());
+}
+
+#[test]
+fn test_hash_comparison_ignores_case() {
+    let upper = expected().map(|hash| hash.to_ascii_uppercase());
+    assert!(mismatched_modules(&upper).is_empty());
+}
+
+#[test]
+fn test_mismatched_modules_are_named() {
+    let mut deployed = expected();
+    deployed[0] = "00".repeat(32);
+    deployed[5] = String::new();
+
+    assert_eq!(mismatched_modules(&deployed), vec!["governance", "archive"]);
+}
+
+#[test]
+fn test_hash_of_another_module_is_a_mismatch() {
+    // the upgrade check accepts any known hash for any module, this one must not
+    let mut deployed = expected();
+    deployed.swap(1, 2);
+
+    assert_eq!(mismatched_modules(&deployed), vec!["index", "swap"]);
+}
+
+#[test]
+fn test_mismatch_alert_lists_canisters() {
+    let msg = mismatch_alert_message(&[
+        WasmHashMismatch {
+            governance: Principal::anonymous(),
+            modules: vec!["ledger"],
+        },
+        WasmHashMismatch {
+            governance: Principal::management_canister(),
+            modules: vec!["root", "swap"],
+        },
+    ]);
+
+    assert!(msg.starts_with("SNS wasm hash mismatch in 2 canisters"));
+    assert!(msg.contains(&format!("- {}: ledger", Principal::anonymous())));
+    assert!(msg.contains(&format!(
+        "- {}: root, swap",
+        Principal::management_canister()
+    )));
+}
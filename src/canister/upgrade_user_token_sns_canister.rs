@@ -8,16 +8,16 @@ use hex::ToHex;
 use ic_agent::Agent;
 use ic_sns_governance::init::GovernanceCanisterInitPayloadBuilder;
 use serde::{Deserialize, Serialize};
-use std::{error::Error, sync::Arc, time::Duration, vec};
+use std::{error::Error, future::Future, sync::Arc, time::Duration, vec};
 use yral_canisters_client::{
     individual_user_template::{DeployedCdaoCanisters, IndividualUserTemplate},
     platform_orchestrator::{self, PlatformOrchestrator},
     sns_governance::{
-        self, Action, Command1, Configure, Follow, GetProposal, GetRunningSnsVersionArg,
-        IncreaseDissolveDelay, ListNeurons, ManageNeuron, NeuronId, Operation, Proposal,
-        ProposalId, SnsGovernance, Version,
+        self, Action, Command1, Configure, DissolveState, Follow, GetProposal,
+        GetRunningSnsVersionArg, IncreaseDissolveDelay, ListNeurons, ManageNeuron, Neuron,
+        NeuronId, Operation, Proposal, ProposalId, SnsGovernance, Version,
     },
-    sns_root::{GetSnsCanistersSummaryRequest, SnsRoot},
+    sns_root::{CanisterSummary, GetSnsCanistersSummaryRequest, SnsRoot},
     user_index::UserIndex,
 };
 
@@ -29,7 +29,10 @@ use ic_utils::{
     Canister,
 };
 
-use crate::{consts::PLATFORM_ORCHESTRATOR_ID, qstash::client::QStashClient};
+use crate::{
+    consts::{PLATFORM_ORCHESTRATOR_ID, SNS_MINIMUM_CYCLES_FOR_UPGRADE},
+    qstash::client::QStashClient,
+};
 
 use crate::app_state::AppState;
 use crate::utils::api_response::ApiResponse;
@@ -65,9 +68,6 @@ pub const SNS_TOKEN_INDEX_MODULE_HASH: &'static str =
 pub const SNS_TOKEN_ARCHIVE_MODULE_HASH: &'static str =
     "317771544f0e828a60ad6efc97694c425c169c4d75d911ba592546912dba3116";
 
-const MINIMUM_RECHARGE_AMOUNT_TO_RUN_SNS_UPGRADE: u128 = 1_000_000_000_000; //1T
-const INITIAL_RECHARGE_AMOUNT: u128 = 300_000_000_000; //0.3T
-
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct VerifyUpgradeProposalRequest {
     pub sns_canisters: SnsCanisters,
@@ -123,41 +123,83 @@ pub async fn upgrade_user_token_sns_canister_for_entire_network_impl(
         individual_canister_ids.extend(subnet_orchestrator.get_user_canister_list().await?);
     }
 
-    let upgrade_governance_canister_tasks =
-        individual_canister_ids
-            .into_iter()
-            .map(|individual_canister| async move {
-                let individual_canister_template =
-                    IndividualUserTemplate(individual_canister, agent);
-
-                let deployed_cdao_canisters_res =
-                    individual_canister_template.deployed_cdao_canisters().await;
-
-                let deployed_cdao_canisters_len = deployed_cdao_canisters_res
-                    .map(|res| res.len())
-                    .unwrap_or(0);
-
-                if deployed_cdao_canisters_len > 0 {
-                    qstash_client
-                        .upgrade_all_sns_canisters_for_a_user_canister(
-                            individual_canister.to_text(),
-                        )
-                        .await
-                } else {
-                    Ok(())
-                }
-            });
-
-    let stream = futures::stream::iter(upgrade_governance_canister_tasks)
-        .boxed()
-        .buffer_unordered(100);
+    let summary = parallel_canister_upgrade_with_backpressure(
+        individual_canister_ids,
+        UPGRADE_FANOUT_CONCURRENCY,
+        |individual_canister| async move {
+            let individual_canister_template = IndividualUserTemplate(individual_canister, agent);
+
+            let deployed_cdao_canisters_res =
+                individual_canister_template.deployed_cdao_canisters().await;
+
+            let deployed_cdao_canisters_len = deployed_cdao_canisters_res
+                .map(|res| res.len())
+                .unwrap_or(0);
+
+            if deployed_cdao_canisters_len > 0 {
+                qstash_client
+                    .upgrade_all_sns_canisters_for_a_user_canister(individual_canister.to_text())
+                    .await
+            } else {
+                Ok(())
+            }
+        },
+    )
+    .await;
 
-    let _upgrade_creator_dao_governance_canister_tasks =
-        stream.collect::<Vec<Result<(), anyhow::Error>>>().await;
+    log::info!(
+        "Enqueued sns upgrades for {} user canisters, {} failed",
+        summary.succeeded,
+        summary.failed
+    );
 
     Ok(())
 }
 
+/// User canisters handled at once by the network wide upgrade
+pub const UPGRADE_FANOUT_CONCURRENCY: usize = 100;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FanoutSummary {
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// Runs `task` for every canister with at most `concurrency` of them in flight. Futures are
+/// created as slots free up and results are counted as they complete, so memory stays bounded
+/// by `concurrency` rather than the number of canisters.
+pub async fn parallel_canister_upgrade_with_backpressure<I, F, Fut>(
+    canister_ids: I,
+    concurrency: usize,
+    task: F,
+) -> FanoutSummary
+where
+    I: IntoIterator<Item = Principal>,
+    F: Fn(Principal) -> Fut,
+    Fut: Future<Output = Result<(), anyhow::Error>>,
+{
+    futures::stream::iter(canister_ids)
+        .map(|canister_id| {
+            let fut = task(canister_id);
+            async move { (canister_id, fut.await) }
+        })
+        .buffer_unordered(concurrency)
+        .fold(
+            FanoutSummary::default(),
+            |mut summary, (canister_id, res)| async move {
+                match res {
+                    Ok(()) => summary.succeeded += 1,
+                    Err(e) => {
+                        log::warn!("Upgrade fanout failed for {}: {}", canister_id, e);
+                        summary.failed += 1;
+                    }
+                }
+                summary
+            },
+        )
+        .await
+}
+
 pub async fn upgrade_user_token_sns_canister_handler(
     Path(user_canister_id): Path<String>,
     State(state): State<Arc<AppState>>,
@@ -195,7 +237,7 @@ pub async fn setup_sns_canisters_of_a_user_canister_for_upgrade(
     sns_canisters
         .into_iter()
         .map(|sns_canisters| async move {
-            recharge_canisters(agent, sns_canisters).await?;
+            recharge_canisters(agent, sns_canisters).await;
             setup_neurons_for_admin_principal(agent, sns_canisters).await?;
             qstash_client
                 .upgrade_sns_creator_dao_canister(sns_canisters)
@@ -525,154 +567,130 @@ pub async fn check_if_the_proposal_executed_successfully(
     }
 }
 
-pub async fn recharge_canisters(
-    agent: &Agent,
-    deployed_canisters: SnsCanisters,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut recharge_canister_tasks = vec![];
-
-    recharge_canister_tasks.push(recharge_canister_using_platform_orchestrator(
-        agent,
-        deployed_canisters.governance,
-        INITIAL_RECHARGE_AMOUNT,
-    ));
-
-    recharge_canister_tasks.push(recharge_canister_using_platform_orchestrator(
-        agent,
-        deployed_canisters.index,
-        INITIAL_RECHARGE_AMOUNT,
-    ));
-    recharge_canister_tasks.push(recharge_canister_using_platform_orchestrator(
-        agent,
-        deployed_canisters.ledger,
-        INITIAL_RECHARGE_AMOUNT,
-    ));
-    recharge_canister_tasks.push(recharge_canister_using_platform_orchestrator(
-        agent,
-        deployed_canisters.root,
-        INITIAL_RECHARGE_AMOUNT,
-    ));
-    recharge_canister_tasks.push(recharge_canister_using_platform_orchestrator(
-        agent,
-        deployed_canisters.swap,
-        INITIAL_RECHARGE_AMOUNT,
-    ));
-
-    recharge_canister_tasks
-        .into_iter()
-        .collect::<FuturesUnordered<_>>()
-        .try_collect::<()>()
-        .await?;
-
-    Ok(())
+/// Top up bringing a canister holding `current_cycles` to `minimum_required`, None if it has
+/// enough
+pub fn cycles_top_up_needed(current_cycles: u128, minimum_required: u128) -> Option<u128> {
+    (current_cycles < minimum_required).then(|| minimum_required - current_cycles)
 }
 
-async fn recharge_if_sns_canister_threshold(
-    agent: &Agent,
-    canister_id: Principal,
-    cycle_balance: u128,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if cycle_balance < MINIMUM_RECHARGE_AMOUNT_TO_RUN_SNS_UPGRADE {
-        let amount = MINIMUM_RECHARGE_AMOUNT_TO_RUN_SNS_UPGRADE.saturating_sub(cycle_balance);
-        recharge_canister_using_platform_orchestrator(agent, canister_id, amount).await?;
-    }
-
-    Ok(())
+fn summary_cycles(summary: Option<CanisterSummary>) -> Option<u128> {
+    summary
+        .and_then(|summary| summary.status)
+        .and_then(|status| u128::try_from(status.cycles.0).ok())
 }
 
-async fn check_and_recharge_canisters_for_sns_upgrade(
-    agent: &Agent,
-    sns_canisters: SnsCanisters,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let root_canister = SnsRoot(sns_canisters.root, agent);
-    let sns_canister_summary = root_canister
+/// Tops up the SNS canisters below [`SNS_MINIMUM_CYCLES_FOR_UPGRADE`] to it. The balances come
+/// from the SNS root, which controls the canisters. Failures are only logged, a canister still
+/// short of cycles fails its upgrade later on.
+pub async fn recharge_canisters(agent: &Agent, sns_canisters: SnsCanisters) {
+    let root = SnsRoot(sns_canisters.root, agent);
+    let summary = match root
         .get_sns_canisters_summary(GetSnsCanistersSummaryRequest {
             update_canister_list: None,
         })
-        .await?;
+        .await
+    {
+        Ok(summary) => summary,
+        Err(e) => {
+            log::error!(
+                "Failed to read cycle balances of the SNS of root {}: {}",
+                sns_canisters.root,
+                e
+            );
+            return;
+        }
+    };
+
+    let balances = [
+        (sns_canisters.root, summary_cycles(summary.root)),
+        (sns_canisters.swap, summary_cycles(summary.swap)),
+        (sns_canisters.ledger, summary_cycles(summary.ledger)),
+        (sns_canisters.index, summary_cycles(summary.index)),
+        (sns_canisters.governance, summary_cycles(summary.governance)),
+    ];
 
-    let root_canister_cycle_balance = sns_canister_summary
-        .root
-        .map(|canister_res| {
-            canister_res
-                .status
-                .map(|canister_status| u128::try_from(canister_status.cycles.0).unwrap())
-        })
-        .flatten();
+    for (canister_id, balance) in balances {
+        let Some(balance) = balance else {
+            log::warn!("No cycle balance reported for SNS canister {}", canister_id);
+            continue;
+        };
+        let Some(amount) = cycles_top_up_needed(balance, *SNS_MINIMUM_CYCLES_FOR_UPGRADE) else {
+            continue;
+        };
 
-    if let Some(root_canister_balance) = root_canister_cycle_balance {
-        let _ =
-            recharge_if_sns_canister_threshold(agent, sns_canisters.root, root_canister_balance)
-                .await;
+        if let Err(e) =
+            recharge_canister_using_platform_orchestrator(agent, canister_id, amount).await
+        {
+            log::error!(
+                "Failed to recharge SNS canister {} with {} cycles: {}",
+                canister_id,
+                amount,
+                e
+            );
+        }
     }
+}
 
-    let swap_canister_cycle_balance = sns_canister_summary
-        .swap
-        .map(|canister_res| {
-            canister_res
-                .status
-                .map(|canister_status| u128::try_from(canister_status.cycles.0).unwrap())
-        })
-        .flatten();
-
-    if let Some(swap_canister_balance) = swap_canister_cycle_balance {
-        let _ =
-            recharge_if_sns_canister_threshold(agent, sns_canisters.swap, swap_canister_balance)
-                .await;
+/// Minimum dissolve delay for the proposing neuron to be allowed to vote
+pub const MIN_PROPOSER_DISSOLVE_DELAY_SECONDS: u64 = 86400;
+
+pub fn check_neuron_voting_eligibility(
+    cached_neuron_stake_e8s: u64,
+    neuron_minimum_stake_e8s: u64,
+    dissolve_state: Option<&DissolveState>,
+    now_secs: u64,
+) -> Result<(), String> {
+    if cached_neuron_stake_e8s < neuron_minimum_stake_e8s {
+        return Err(format!(
+            "neuron stake {} e8s is below the minimum stake of {} e8s",
+            cached_neuron_stake_e8s, neuron_minimum_stake_e8s
+        ));
     }
 
-    let ledger_canister_cycle_balance = sns_canister_summary
-        .ledger
-        .map(|canister_res| {
-            canister_res
-                .status
-                .map(|canister_status| u128::try_from(canister_status.cycles.0).unwrap())
-        })
-        .flatten();
-
-    if let Some(ledger_canister_balance) = ledger_canister_cycle_balance {
-        let _ = recharge_if_sns_canister_threshold(
-            agent,
-            sns_canisters.ledger,
-            ledger_canister_balance,
-        )
-        .await;
+    let dissolve_delay_seconds = match dissolve_state {
+        Some(DissolveState::DissolveDelaySeconds(delay)) => *delay,
+        Some(DissolveState::WhenDissolvedTimestampSeconds(ts)) => ts.saturating_sub(now_secs),
+        None => 0,
+    };
+    if dissolve_delay_seconds < MIN_PROPOSER_DISSOLVE_DELAY_SECONDS {
+        return Err(format!(
+            "neuron dissolve delay {}s is below the required {}s",
+            dissolve_delay_seconds, MIN_PROPOSER_DISSOLVE_DELAY_SECONDS
+        ));
     }
 
-    let index_cansiter_cycle_balance = sns_canister_summary
-        .index
-        .map(|canister_res| {
-            canister_res
-                .status
-                .map(|canister_status| u128::try_from(canister_status.cycles.0).unwrap())
-        })
-        .flatten();
-
-    if let Some(index_canister_balance) = index_cansiter_cycle_balance {
-        let _ =
-            recharge_if_sns_canister_threshold(agent, sns_canisters.index, index_canister_balance)
-                .await;
-    }
-
-    let governance_canister_cycle_balance = sns_canister_summary
-        .governance
-        .map(|canister_res| {
-            canister_res
-                .status
-                .map(|canister_status| u128::try_from(canister_status.cycles.0).unwrap())
-        })
-        .flatten();
+    Ok(())
+}
 
-    if let Some(governance_canister_balance) = governance_canister_cycle_balance {
-        let _ = recharge_if_sns_canister_threshold(
-            agent,
-            sns_canisters.governance,
-            governance_canister_balance,
+/// Verifies the proposing neuron can vote the proposal through before it is submitted
+pub async fn verify_neuron_has_majority_voting_power(
+    sns_governance: &SnsGovernance<'_>,
+    neuron: &Neuron,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let neuron_minimum_stake_e8s = sns_governance
+        .get_nervous_system_parameters(())
+        .await?
+        .neuron_minimum_stake_e8s
+        .unwrap_or_default();
+
+    let now_secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+
+    check_neuron_voting_eligibility(
+        neuron.cached_neuron_stake_e8s,
+        neuron_minimum_stake_e8s,
+        neuron.dissolve_state.as_ref(),
+        now_secs,
+    )
+    .map_err(|e| {
+        format!(
+            "neuron in governance {} cannot pass the upgrade proposal: {}",
+            sns_governance.0, e
         )
-        .await;
-    }
+    })?;
 
-    Ok(())
+    Ok(true)
 }
 
 pub async fn upgrade_user_token_sns_canister_impl(
@@ -688,7 +706,7 @@ pub async fn upgrade_user_token_sns_canister_impl(
         return Ok(());
     }
 
-    check_and_recharge_canisters_for_sns_upgrade(agent, sns_canisters).await?;
+    recharge_canisters(agent, sns_canisters).await;
 
     let neuron_list = sns_governance
         .list_neurons(ListNeurons {
@@ -700,12 +718,11 @@ pub async fn upgrade_user_token_sns_canister_impl(
         .map_err(|e| e.to_string())?
         .neurons;
 
-    let first_neuron = neuron_list
-        .get(0)
-        .ok_or("first neuron not found")?
-        .id
-        .as_ref()
-        .ok_or("first neuronId not found")?;
+    let first_neuron = neuron_list.get(0).ok_or("first neuron not found")?;
+
+    verify_neuron_has_majority_voting_power(&sns_governance, first_neuron).await?;
+
+    let first_neuron = first_neuron.id.as_ref().ok_or("first neuronId not found")?;
 
     let proposal_id = sns_governance
         .manage_neuron(ManageNeuron {
@@ -0,0 +1,136 @@
+use yral_canisters_client::sns_governance::DissolveState;
+
+use super::upgrade_user_token_sns_canister::{
+    check_neuron_voting_eligibility, cycles_top_up_needed,
+    parallel_canister_upgrade_with_backpressure, FanoutSummary,
+    MIN_PROPOSER_DISSOLVE_DELAY_SECONDS, UPGRADE_FANOUT_CONCURRENCY,
+};
+
+const NOW: u64 = 1_700_000_000;
+
+#[test]
+fn test_neuron_with_stake_and_delay_is_eligible() {
+    let res = check_neuron_voting_eligibility(
+        1_000,
+        1_000,
+        Some(&DissolveState::DissolveDelaySeconds(172800)),
+        NOW,
+    );
+    assert!(res.is_ok());
+}
+
+#[test]
+fn test_neuron_below_minimum_stake_is_rejected() {
+    let res = check_neuron_voting_eligibility(
+        999,
+        1_000,
+        Some(&DissolveState::DissolveDelaySeconds(172800)),
+        NOW,
+    );
+    assert!(res.unwrap_err().contains("minimum stake"));
+}
+
+#[test]
+fn test_neuron_with_short_dissolve_delay_is_rejected() {
+    let res = check_neuron_voting_eligibility(
+        1_000,
+        1_000,
+        Some(&DissolveState::DissolveDelaySeconds(
+            MIN_PROPOSER_DISSOLVE_DELAY_SECONDS - 1,
+        )),
+        NOW,
+    );
+    assert!(res.unwrap_err().contains("dissolve delay"));
+
+    let res = check_neuron_voting_eligibility(1_000, 1_000, None, NOW);
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_dissolving_neuron_uses_remaining_delay() {
+    let dissolving_soon = DissolveState::WhenDissolvedTimestampSeconds(NOW + 3600);
+    assert!(check_neuron_voting_eligibility(1_000, 0, Some(&dissolving_soon), NOW).is_err());
+
+    let dissolving_later =
+        DissolveState::WhenDissolvedTimestampSeconds(NOW + MIN_PROPOSER_DISSOLVE_DELAY_SECONDS);
+    assert!(check_neuron_voting_eligibility(1_000, 0, Some(&dissolving_later), NOW).is_ok());
+}
+
+#[test]
+fn test_canister_below_minimum_is_topped_up() {
+    assert_eq!(
+        cycles_top_up_needed(100_000_000_000, 1_000_000_000_000),
+        Some(900_000_000_000)
+    );
+}
+
+#[test]
+fn test_canister_with_enough_cycles_is_not_topped_up() {
+    assert_eq!(
+        cycles_top_up_needed(1_000_000_000_000, 1_000_000_000_000),
+        None
+    );
+    assert_eq!(
+        cycles_top_up_needed(5_000_000_000_000, 1_000_000_000_000),
+        None
+    );
+}
+
+fn canister_ids(count: u64) -> impl Iterator<Item = candid::Principal> {
+    (0..count).map(|i| candid::Principal::from_slice(&i.to_be_bytes()))
+}
+
+#[tokio::test]
+async fn test_fanout_bounds_in_flight_tasks() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let in_flight = AtomicUsize::new(0);
+    let max_in_flight = AtomicUsize::new(0);
+
+    let summary = parallel_canister_upgrade_with_backpressure(
+        canister_ids(10_000),
+        UPGRADE_FANOUT_CONCURRENCY,
+        |_| async {
+            let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            max_in_flight.fetch_max(current, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        },
+    )
+    .await;
+
+    assert_eq!(
+        summary,
+        FanoutSummary {
+            succeeded: 10_000,
+            failed: 0
+        }
+    );
+    assert_eq!(
+        max_in_flight.load(Ordering::SeqCst),
+        UPGRADE_FANOUT_CONCURRENCY
+    );
+}
+
+#[tokio::test]
+async fn test_fanout_counts_failures_and_keeps_going() {
+    let fails = |id: &candid::Principal| id.as_slice().last().is_some_and(|b| b % 10 == 0);
+    let expected_failures = canister_ids(10_000).filter(fails).count();
+
+    let summary = parallel_canister_upgrade_with_backpressure(canister_ids(10_000), 100, |id| {
+        let fail = fails(&id);
+        async move {
+            if fail {
+                Err(anyhow::anyhow!("qstash unavailable"))
+            } else {
+                Ok(())
+            }
+        }
+    })
+    .await;
+
+    assert!(expected_failures > 0);
+    assert_eq!(summary.failed, expected_failures);
+    assert_eq!(summary.succeeded, 10_000 - expected_failures);
+}
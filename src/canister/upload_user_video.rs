@@ -1,18 +1,27 @@
-use std::{error::Error, sync::Arc};
+use std::{env, error::Error, sync::Arc};
 
-use axum::{extract::State, Json};
-use ic_agent::{
-    identity::{DelegatedIdentity, Secp256k1Identity, SignedDelegation},
-    Agent, Identity,
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+    Json,
 };
+#[cfg(not(feature = "local-bin"))]
+use axum::{http::StatusCode, response::IntoResponse};
+use ic_agent::identity::{DelegatedIdentity, Secp256k1Identity, SignedDelegation};
 use k256::{elliptic_curve::JwkEcKey, SecretKey};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use crate::{
-    app_state::AppState, events::VideoUploadSuccessful, types::DelegatedIdentityWire,
+    app_state::AppState,
+    events::VideoUploadSuccessful,
+    types::{DelegatedIdentityWire, RedisPool},
     utils::api_response::ApiResponse,
 };
+#[cfg(not(feature = "local-bin"))]
+use crate::{qstash::gcs_gc::VIDEOS_BUCKET, utils::notifications::notify_all};
 
 use yral_canisters_client::individual_user_template::{
     IndividualUserTemplate, PostDetailsFromFrontend, Result1,
@@ -76,13 +85,8 @@ pub async fn upload_user_video_impl(
     payload: UploadUserVideoRequestBody,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
     let yral_metadata_client = &app_state.yral_metadata_client;
-    let identity: DelegatedIdentity = DelegatedIdentity::try_from(payload.delegated_identity_wire)?;
-    let user_principal = identity.sender()?;
-
-    let agent = Agent::builder()
-        .with_identity(identity)
-        .with_url("https://ic0.app")
-        .build()?;
+    let agent = app_state.get_pooled_agent(&payload.delegated_identity_wire)?;
+    let user_principal = agent.get_principal()?;
     let user_meta_data = yral_metadata_client
         .get_user_metadata(user_principal)
         .await?
@@ -123,3 +127,274 @@ pub async fn upload_user_video_impl(
         Result1::Err(e) => Err(e.into()),
     }
 }
+
+pub const GCS_QUOTA_WARNING_BYTES_ENV: &str = "GCS_QUOTA_WARNING_BYTES";
+pub const GCS_QUOTA_HARD_LIMIT_BYTES_ENV: &str = "GCS_QUOTA_HARD_LIMIT_BYTES";
+/// How long a measured bucket usage is reused, so GCS is asked and alerts go out at most hourly
+pub const STORAGE_USAGE_CACHE_TTL_SECS: u64 = 60 * 60;
+const STORAGE_USAGE_KEY_PREFIX: &str = "gcs_usage_bytes:";
+#[cfg(not(feature = "local-bin"))]
+const MONITORING_READ_SCOPE: &str = "https://www.googleapis.com/auth/monitoring.read";
+#[cfg(not(feature = "local-bin"))]
+const TOTAL_BYTES_METRIC: &str = "storage.googleapis.com/storage/total_bytes";
+/// `total_bytes` is sampled once a day, two days back always holds a sample
+#[cfg(not(feature = "local-bin"))]
+const TOTAL_BYTES_LOOKBACK_SECS: i64 = 2 * 24 * 60 * 60;
+
+pub static STORAGE_QUOTA: Lazy<StorageQuota> = Lazy::new(StorageQuota::from_env);
+
+/// Usage thresholds of the videos bucket, each one is off while its variable is unset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageQuota {
+    pub warning_bytes: Option<u64>,
+    pub hard_limit_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaStatus {
+    Ok,
+    Warning,
+    Exceeded,
+}
+
+impl StorageQuota {
+    pub fn from_env() -> Self {
+        let bytes = |var| env::var(var).ok().and_then(|v| v.trim().parse().ok());
+        Self {
+            warning_bytes: bytes(GCS_QUOTA_WARNING_BYTES_ENV),
+            hard_limit_bytes: bytes(GCS_QUOTA_HARD_LIMIT_BYTES_ENV),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.warning_bytes.is_some() || self.hard_limit_bytes.is_some()
+    }
+
+    pub fn status(&self, used_bytes: u64) -> QuotaStatus {
+        if self
+            .hard_limit_bytes
+            .is_some_and(|limit| used_bytes >= limit)
+        {
+            QuotaStatus::Exceeded
+        } else if self.warning_bytes.is_some_and(|limit| used_bytes >= limit) {
+            QuotaStatus::Warning
+        } else {
+            QuotaStatus::Ok
+        }
+    }
+}
+
+pub fn storage_usage_key(bucket: &str) -> String {
+    format!("{}{}", STORAGE_USAGE_KEY_PREFIX, bucket)
+}
+
+pub fn quota_alert_message(
+    bucket: &str,
+    used_bytes: u64,
+    quota: &StorageQuota,
+    status: QuotaStatus,
+) -> String {
+    let gib = |bytes: u64| bytes as f64 / (1u64 << 30) as f64;
+    let limit = |bytes: Option<u64>| {
+        bytes.map_or("unset".to_string(), |bytes| {
+            format!("{:.1} GiB", gib(bytes))
+        })
+    };
+    let action = match status {
+        QuotaStatus::Exceeded => "video uploads are rejected until space is freed",
+        _ => "video uploads are still accepted",
+    };
+
+    format!(
+        "GCS bucket {} holds {:.1} GiB (warning at {}, hard limit at {}), {}",
+        bucket,
+        gib(used_bytes),
+        limit(quota.warning_bytes),
+        limit(quota.hard_limit_bytes),
+        action
+    )
+}
+
+/// Response of Cloud Monitoring's `projects.timeSeries.list`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeSeriesList {
+    #[serde(default)]
+    pub time_series: Vec<TimeSeries>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimeSeries {
+    /// Newest first
+    #[serde(default)]
+    pub points: Vec<TimeSeriesPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimeSeriesPoint {
+    pub value: TypedValue,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedValue {
+    pub double_value: Option<f64>,
+    /// int64 values are sent as strings
+    pub int64_value: Option<String>,
+}
+
+/// Sum of the latest sample of every series, there is one per storage class. `None` without
+/// samples.
+pub fn total_bytes_from_time_series(list: &TimeSeriesList) -> Option<u64> {
+    let latest: Vec<u64> = list
+        .time_series
+        .iter()
+        .filter_map(|series| {
+            let value = &series.points.first()?.value;
+            value
+                .double_value
+                .map(|bytes| bytes as u64)
+                .or_else(|| value.int64_value.as_ref()?.parse().ok())
+        })
+        .collect();
+
+    (!latest.is_empty()).then(|| latest.iter().sum())
+}
+
+pub(crate) trait StorageUsageCache {
+    async fn cached_usage(&self, key: &str) -> Result<Option<u64>, anyhow::Error>;
+
+    async fn cache_usage(&self, key: &str, bytes: u64, ttl_secs: u64) -> Result<(), anyhow::Error>;
+}
+
+impl StorageUsageCache for RedisPool {
+    async fn cached_usage(&self, key: &str) -> Result<Option<u64>, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let bytes = redis::cmd("GET").arg(key).query_async(&mut *conn).await?;
+
+        Ok(bytes)
+    }
+
+    async fn cache_usage(&self, key: &str, bytes: u64, ttl_secs: u64) -> Result<(), anyhow::Error> {
+        let mut conn = self.get().await?;
+        redis::cmd("SET")
+            .arg(key)
+            .arg(bytes)
+            .arg("EX")
+            .arg(ttl_secs)
+            .query_async::<()>(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub(crate) trait BucketUsageSource {
+    /// Bytes stored in the bucket
+    async fn bucket_usage(&self, bucket: &str) -> Result<u64, anyhow::Error>;
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl BucketUsageSource for AppState {
+    async fn bucket_usage(&self, bucket: &str) -> Result<u64, anyhow::Error> {
+        // the metric lives in the project of the bucket
+        let bucket = self.gcs_client.bucket().read(bucket).await?;
+
+        let token = self.get_access_token(&[MONITORING_READ_SCOPE]).await?;
+        let end = chrono::Utc::now();
+        let start = end - chrono::Duration::seconds(TOTAL_BYTES_LOOKBACK_SECS);
+        let filter = format!(
+            r#"metric.type="{}" AND resource.labels.bucket_name="{}""#,
+            TOTAL_BYTES_METRIC, bucket.name
+        );
+        let series: TimeSeriesList = reqwest::Client::new()
+            .get(format!(
+                "https://monitoring.googleapis.com/v3/projects/{}/timeSeries",
+                bucket.project_number
+            ))
+            .bearer_auth(token)
+            .query(&[
+                ("filter", filter),
+                ("interval.startTime", start.to_rfc3339()),
+                ("interval.endTime", end.to_rfc3339()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        total_bytes_from_time_series(&series).ok_or_else(|| {
+            anyhow::anyhow!(
+                "no {} samples for bucket {} in {:?}",
+                TOTAL_BYTES_METRIC,
+                bucket.name,
+                bucket.location
+            )
+        })
+    }
+}
+
+/// Usage of the bucket, measured at most once per [`STORAGE_USAGE_CACHE_TTL_SECS`]. The flag is
+/// set when it was just measured.
+pub async fn bucket_usage(
+    cache: &impl StorageUsageCache,
+    source: &impl BucketUsageSource,
+    bucket: &str,
+) -> Result<(u64, bool), anyhow::Error> {
+    let key = storage_usage_key(bucket);
+    if let Some(bytes) = cache.cached_usage(&key).await? {
+        return Ok((bytes, false));
+    }
+
+    let bytes = source.bucket_usage(bucket).await?;
+    cache
+        .cache_usage(&key, bytes, STORAGE_USAGE_CACHE_TTL_SECS)
+        .await?;
+
+    Ok((bytes, true))
+}
+
+/// Answers `503` to uploads while the videos bucket is over [`GCS_QUOTA_HARD_LIMIT_BYTES_ENV`] and
+/// alerts when it goes over [`GCS_QUOTA_WARNING_BYTES_ENV`]. Uploads go through when the usage
+/// can't be measured.
+pub async fn storage_quota_check(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    #[cfg(not(feature = "local-bin"))]
+    if STORAGE_QUOTA.is_enabled() {
+        let quota = *STORAGE_QUOTA;
+        match bucket_usage(
+            &state.ml_feed_cache.redis_pool,
+            state.as_ref(),
+            VIDEOS_BUCKET,
+        )
+        .await
+        {
+            Ok((used_bytes, measured)) => {
+                let status = quota.status(used_bytes);
+                if measured && status != QuotaStatus::Ok {
+                    let message = quota_alert_message(VIDEOS_BUCKET, used_bytes, &quota, status);
+                    if let Err(e) = notify_all(&state.notification_backends, &message).await {
+                        log::error!("Failed to send GCS quota alert: {}", e);
+                    }
+                }
+                if status == QuotaStatus::Exceeded {
+                    return (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        "Video storage is full, try again later",
+                    )
+                        .into_response();
+                }
+            }
+            Err(e) => log::warn!("Failed to check GCS usage of {}: {}", VIDEOS_BUCKET, e),
+        }
+    }
+
+    #[cfg(feature = "local-bin")]
+    let _ = &state;
+
+    next.run(request).await
+}
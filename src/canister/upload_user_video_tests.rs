@@ -0,0 +1,155 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use super::upload_user_video::{
+    bucket_usage, quota_alert_message, storage_usage_key, total_bytes_from_time_series,
+    BucketUsageSource, QuotaStatus, StorageQuota, StorageUsageCache, TimeSeriesList,
+    STORAGE_USAGE_CACHE_TTL_SECS,
+};
+
+const GIB: u64 = 1 << 30;
+
+/// Cached usages with the TTL they were stored with
+#[derive(Default)]
+struct MockCache {
+    usages: Mutex<HashMap<String, (u64, u64)>>,
+}
+
+impl StorageUsageCache for MockCache {
+    async fn cached_usage(&self, key: &str) -> Result<Option<u64>, anyhow::Error> {
+        Ok(self
+            .usages
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|(bytes, _)| *bytes))
+    }
+
+    async fn cache_usage(&self, key: &str, bytes: u64, ttl_secs: u64) -> Result<(), anyhow::Error> {
+        self.usages
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), (bytes, ttl_secs));
+        Ok(())
+    }
+}
+
+/// Answers the bucket usage, counts the measurements
+#[derive(Default)]
+struct MockSource {
+    usage: Option<u64>,
+    calls: Mutex<usize>,
+}
+
+impl BucketUsageSource for MockSource {
+    async fn bucket_usage(&self, _bucket: &str) -> Result<u64, anyhow::Error> {
+        *self.calls.lock().unwrap() += 1;
+        self.usage
+            .ok_or_else(|| anyhow::anyhow!("monitoring unavailable"))
+    }
+}
+
+fn quota() -> StorageQuota {
+    StorageQuota {
+        warning_bytes: Some(80 * GIB),
+        hard_limit_bytes: Some(100 * GIB),
+    }
+}
+
+#[test]
+fn test_quota_status() {
+    let quota = quota();
+
+    assert_eq!(quota.status(80 * GIB - 1), QuotaStatus::Ok);
+    assert_eq!(quota.status(80 * GIB), QuotaStatus::Warning);
+    assert_eq!(quota.status(100 * GIB - 1), QuotaStatus::Warning);
+    assert_eq!(quota.status(100 * GIB), QuotaStatus::Exceeded);
+}
+
+#[test]
+fn test_unset_thresholds_are_off() {
+    assert!(!StorageQuota::default().is_enabled());
+    assert_eq!(StorageQuota::default().status(u64::MAX), QuotaStatus::Ok);
+
+    let hard_limit_only = StorageQuota {
+        warning_bytes: None,
+        hard_limit_bytes: Some(100 * GIB),
+    };
+    assert!(hard_limit_only.is_enabled());
+    assert_eq!(hard_limit_only.status(99 * GIB), QuotaStatus::Ok);
+    assert_eq!(hard_limit_only.status(100 * GIB), QuotaStatus::Exceeded);
+}
+
+#[test]
+fn test_quota_alert_message() {
+    assert_eq!(
+        quota_alert_message("yral-videos", 90 * GIB, &quota(), QuotaStatus::Warning),
+        "GCS bucket yral-videos holds 90.0 GiB (warning at 80.0 GiB, hard limit at 100.0 GiB), \
+         video uploads are still accepted"
+    );
+    assert!(
+        quota_alert_message("yral-videos", 100 * GIB, &quota(), QuotaStatus::Exceeded)
+            .ends_with("video uploads are rejected until space is freed")
+    );
+}
+
+#[test]
+fn test_total_bytes_sums_latest_sample_of_each_storage_class() {
+    let series: TimeSeriesList = serde_json::from_value(serde_json::json!({
+        "timeSeries": [
+            {
+                "metric": { "labels": { "storage_class": "STANDARD" } },
+                "points": [
+                    { "interval": {}, "value": { "doubleValue": 3000.0 } },
+                    { "interval": {}, "value": { "doubleValue": 1000.0 } }
+                ]
+            },
+            {
+                "metric": { "labels": { "storage_class": "NEARLINE" } },
+                "points": [{ "interval": {}, "value": { "int64Value": "500" } }]
+            }
+        ]
+    }))
+    .unwrap();
+
+    assert_eq!(total_bytes_from_time_series(&series), Some(3500));
+}
+
+#[test]
+fn test_total_bytes_without_samples() {
+    let series: TimeSeriesList = serde_json::from_value(serde_json::json!({})).unwrap();
+
+    assert_eq!(total_bytes_from_time_series(&series), None);
+}
+
+#[tokio::test]
+async fn test_usage_is_measured_and_cached_for_an_hour() {
+    let cache = MockCache::default();
+    let source = MockSource {
+        usage: Some(42 * GIB),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        bucket_usage(&cache, &source, "yral-videos").await.unwrap(),
+        (42 * GIB, true)
+    );
+    assert_eq!(
+        cache.usages.lock().unwrap()[&storage_usage_key("yral-videos")],
+        (42 * GIB, STORAGE_USAGE_CACHE_TTL_SECS)
+    );
+
+    assert_eq!(
+        bucket_usage(&cache, &source, "yral-videos").await.unwrap(),
+        (42 * GIB, false)
+    );
+    assert_eq!(*source.calls.lock().unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_failed_measurement_is_not_cached() {
+    let cache = MockCache::default();
+    let source = MockSource::default();
+
+    assert!(bucket_usage(&cache, &source, "yral-videos").await.is_err());
+    assert!(cache.usages.lock().unwrap().is_empty());
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     env,
     fs::OpenOptions,
     io::{BufWriter, Write},
@@ -9,13 +10,47 @@ use once_cell::sync::Lazy;
 use serde::Deserialize;
 use serde_with::serde_as;
 
-use crate::consts::{STORJ_BACKUP_CANISTER_ACCESS_GRANT, STORJ_INTERFACE_TOKEN};
+use crate::{
+    consts::{NSFW_THRESHOLD, STORJ_BACKUP_CANISTER_ACCESS_GRANT, STORJ_INTERFACE_TOKEN},
+    events::legacy_ga::default_ga_event_mapping,
+};
+
+pub static CLOUDFLARE_CONFIG: Lazy<CloudflareConfig> = Lazy::new(CloudflareConfig::from_env);
 
 #[serde_as]
 #[derive(Deserialize, Clone)]
 pub struct AppConfig {
     pub yral_metadata_token: String,
     pub google_sa_key: String,
+    /// Principals of internal test accounts, comma separated in the environment
+    #[serde(default)]
+    pub test_principals: Vec<String>,
+    /// GA event name to warehouse event name, for `/api/v1/events/ingest-legacy-ga`
+    #[serde(default = "default_ga_event_mapping")]
+    pub ga_event_mapping: HashMap<String, String>,
+    /// Embedding NSFW probability from which a video is NSFW, defaults to 0.4
+    #[serde(default = "default_nsfw_probability_threshold")]
+    pub nsfw_probability_threshold: f32,
+    /// Explicit content labels counted as NSFW, comma separated in the environment.
+    /// Defaults to nudity, provocative and explicit.
+    #[serde(default = "default_nsfw_ec_categories")]
+    pub nsfw_ec_categories: Vec<String>,
+    /// Gore likelihoods counted as NSFW, comma separated in the environment.
+    /// Defaults to POSSIBLE, LIKELY and VERY_LIKELY.
+    #[serde(default = "default_nsfw_gore_categories")]
+    pub nsfw_gore_categories: Vec<String>,
+}
+
+fn default_nsfw_probability_threshold() -> f32 {
+    NsfwConfig::default().probability_threshold
+}
+
+fn default_nsfw_ec_categories() -> Vec<String> {
+    NsfwConfig::default().ec_categories
+}
+
+fn default_nsfw_gore_categories() -> Vec<String> {
+    NsfwConfig::default().gore_categories
 }
 
 impl AppConfig {
@@ -44,9 +79,120 @@ impl AppConfig {
         let conf = Config::builder()
             .add_source(File::with_name("config.toml").required(false))
             .add_source(File::with_name(".env").required(false))
-            .add_source(Environment::default())
+            .add_source(
+                Environment::default()
+                    .try_parsing(true)
+                    .list_separator(",")
+                    .with_list_parse_key("test_principals")
+                    .with_list_parse_key("nsfw_ec_categories")
+                    .with_list_parse_key("nsfw_gore_categories"),
+            )
             .build()?;
 
         conf.try_deserialize()
     }
+
+    pub fn nsfw_config(&self) -> NsfwConfig {
+        NsfwConfig {
+            probability_threshold: self.nsfw_probability_threshold,
+            ec_categories: self.nsfw_ec_categories.clone(),
+            gore_categories: self.nsfw_gore_categories.clone(),
+        }
+    }
+}
+
+/// When detector results count as NSFW
+#[derive(Debug, Clone, PartialEq)]
+pub struct NsfwConfig {
+    pub probability_threshold: f32,
+    pub ec_categories: Vec<String>,
+    pub gore_categories: Vec<String>,
+}
+
+impl Default for NsfwConfig {
+    fn default() -> Self {
+        Self {
+            probability_threshold: NSFW_THRESHOLD,
+            ec_categories: ["nudity", "provocative", "explicit"]
+                .map(String::from)
+                .to_vec(),
+            gore_categories: ["POSSIBLE", "LIKELY", "VERY_LIKELY"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+impl NsfwConfig {
+    /// Embedding based classification
+    pub fn is_nsfw_probability(&self, probability: f32) -> bool {
+        probability >= self.probability_threshold
+    }
+
+    /// Label based classification of the v1 detector
+    pub fn is_nsfw(&self, nsfw_ec: &str, nsfw_gore: &str, csam_detected: bool) -> bool {
+        csam_detected
+            || self
+                .ec_categories
+                .iter()
+                .any(|category| category == nsfw_ec)
+            || self
+                .gore_categories
+                .iter()
+                .any(|category| category == nsfw_gore)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoQuality {
+    /// The original upload quality served by Cloudflare Stream
+    Default,
+    P480,
+    P360,
+}
+
+impl VideoQuality {
+    /// Bound of the longer side, so portrait and landscape videos both get the short side
+    fn max_dimension(self) -> Option<u32> {
+        match self {
+            Self::Default => None,
+            Self::P480 => Some(854),
+            Self::P360 => Some(640),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CloudflareConfig {
+    pub stream_customer_domain: String,
+    /// Zone with media transformations enabled, e.g. `https://yral.com`.
+    /// Without it every quality falls back to the default download.
+    pub media_transform_zone: Option<String>,
+}
+
+impl CloudflareConfig {
+    pub fn from_env() -> Self {
+        Self {
+            stream_customer_domain: env::var("CF_STREAM_CUSTOMER_DOMAIN")
+                .unwrap_or_else(|_| "customer-2p3jflss4r4hmpnz.cloudflarestream.com".to_string()),
+            media_transform_zone: env::var("CF_MEDIA_TRANSFORM_ZONE")
+                .ok()
+                .map(|zone| zone.trim_end_matches('/').to_string()),
+        }
+    }
+
+    pub fn video_download_url(&self, video_id: &str, quality: VideoQuality) -> String {
+        let default_url = format!(
+            "https://{}/{}/downloads/default.mp4",
+            self.stream_customer_domain, video_id
+        );
+
+        match (quality.max_dimension(), &self.media_transform_zone) {
+            (Some(size), Some(zone)) => format!(
+                "{}/cdn-cgi/media/mode=video,width={size},height={size},fit=scale-down/{}",
+                zone, default_url
+            ),
+            _ => default_url,
+        }
+    }
 }
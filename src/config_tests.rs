@@ -0,0 +1,37 @@
+use crate::config::{CloudflareConfig, VideoQuality};
+
+fn config(media_transform_zone: Option<&str>) -> CloudflareConfig {
+    CloudflareConfig {
+        stream_customer_domain: "customer-2p3jflss4r4hmpnz.cloudflarestream.com".to_string(),
+        media_transform_zone: media_transform_zone.map(|zone| zone.to_string()),
+    }
+}
+
+#[test]
+fn test_default_quality_is_the_stream_download() {
+    assert_eq!(
+        config(Some("https://yral.com")).video_download_url("abc123", VideoQuality::Default),
+        "https://customer-2p3jflss4r4hmpnz.cloudflarestream.com/abc123/downloads/default.mp4"
+    );
+}
+
+#[test]
+fn test_lower_qualities_are_transformed_downloads() {
+    let config = config(Some("https://yral.com"));
+
+    assert_eq!(
+        config.video_download_url("abc123", VideoQuality::P480),
+        "https://yral.com/cdn-cgi/media/mode=video,width=854,height=854,fit=scale-down/https://customer-2p3jflss4r4hmpnz.cloudflarestream.com/abc123/downloads/default.mp4"
+    );
+    assert!(config
+        .video_download_url("abc123", VideoQuality::P360)
+        .starts_with("https://yral.com/cdn-cgi/media/mode=video,width=640,height=640,"));
+}
+
+#[test]
+fn test_lower_qualities_fall_back_without_transform_zone() {
+    assert_eq!(
+        config(None).video_download_url("abc123", VideoQuality::P480),
+        "https://customer-2p3jflss4r4hmpnz.cloudflarestream.com/abc123/downloads/default.mp4"
+    );
+}
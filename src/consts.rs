@@ -10,13 +10,19 @@ pub static STDB_ACCESS_TOKEN: Lazy<String> = Lazy::new(|| {
     std::env::var("DEDUP_INDEX_ACCESS_TOKEN").expect("DEDUP_INDEX_ACCESS_TOKEN to be set")
 });
 
-/// with nsfw detection v2, nsfw probablity greater or equal to this is considered nsfw
+/// with nsfw detection v2, nsfw probablity greater or equal to this is considered nsfw.
+/// Default of `NSFW_PROBABILITY_THRESHOLD`, see [`crate::config::NsfwConfig`]
 pub const NSFW_THRESHOLD: f32 = 0.4;
 
 pub static BIGQUERY_INGESTION_URL: Lazy<Url> = Lazy::new(|| {
     Url::parse("https://bigquery.googleapis.com/bigquery/v2/projects/hot-or-not-feed-intelligence/datasets/analytics_335143420/tables/test_events_analytics/insertAll").unwrap()
 });
 
+/// Rows the events table rejected, with `failed_at` and `error_message` so they can be replayed
+pub static BIGQUERY_DEAD_LETTER_INGESTION_URL: Lazy<Url> = Lazy::new(|| {
+    Url::parse("https://bigquery.googleapis.com/bigquery/v2/projects/hot-or-not-feed-intelligence/datasets/analytics_335143420/tables/yral_raw_events_dead_letter/insertAll").unwrap()
+});
+
 pub const PLATFORM_ORCHESTRATOR_ID: &str = "74zq4-iqaaa-aaaam-ab53a-cai";
 
 pub static YRAL_METADATA_URL: Lazy<Url> =
@@ -55,3 +61,24 @@ pub static STORJ_BACKUP_CANISTER_ACCESS_GRANT: Lazy<String> = Lazy::new(|| {
 });
 
 pub const CANISTER_BACKUPS_BUCKET: &str = "canister-backups";
+
+pub static CCAT_LEDGER_CANISTER_ID: Lazy<candid::Principal> = Lazy::new(|| {
+    let id = std::env::var("CCAT_LEDGER_CANISTER_ID").expect("CCAT_LEDGER_CANISTER_ID to be set");
+    candid::Principal::from_text(id).expect("CCAT_LEDGER_CANISTER_ID to be a valid principal")
+});
+
+/// SNS canisters below this balance are topped up before an upgrade
+pub static SNS_MINIMUM_CYCLES_FOR_UPGRADE: Lazy<u128> = Lazy::new(|| {
+    std::env::var("SNS_MINIMUM_CYCLES_FOR_UPGRADE")
+        .ok()
+        .and_then(|cycles| cycles.parse().ok())
+        .unwrap_or(1_000_000_000_000) // 1T
+});
+
+/// CCAT paid for a completed watch, see `events::event::watch_reward`
+pub static WATCH_REWARD_E8S: Lazy<u64> = Lazy::new(|| {
+    std::env::var("WATCH_REWARD_E8S")
+        .ok()
+        .and_then(|reward| reward.parse().ok())
+        .unwrap_or(1_000)
+});
@@ -0,0 +1,20 @@
+pub mod score;
+pub mod token_holders;
+
+#[cfg(test)]
+mod score_tests;
+#[cfg(test)]
+mod token_holders_tests;
+
+use std::sync::Arc;
+
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::app_state::AppState;
+
+pub fn creators_router(state: Arc<AppState>) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(score::handle_creator_score))
+        .routes(routes!(token_holders::handle_token_holders))
+        .with_state(state)
+}
@@ -0,0 +1,262 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use candid::Principal;
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::{app_state::AppState, AppError};
+
+pub const CREATOR_SCORE_WINDOW_DAYS: u32 = 30;
+/// Outlives the weekly schedule by a day so a score is always available between runs
+pub const CREATOR_SCORE_TTL_SECS: u64 = 8 * 24 * 60 * 60;
+pub const COMPUTE_CREATOR_SCORES_CRON: &str = "0 2 * * 1";
+pub const COMPUTE_CREATOR_SCORES_SCHEDULE_ID: &str = "compute-creator-scores";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ComputeCreatorScoreRequest {
+    pub creator_canister: Principal,
+}
+
+/// Engagement of a creator's videos over the score window
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CreatorEngagement {
+    /// Average `percentage_watched`, 0 to 100
+    pub avg_percent_watched: f64,
+    pub views: u64,
+    pub likes: u64,
+    pub unique_viewers: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, ToSchema)]
+pub struct CreatorScore {
+    pub score: f64,
+}
+
+pub fn creator_score_key(creator_canister: Principal) -> String {
+    format!("creator_score:{}", creator_canister)
+}
+
+/// `avg_watch * 0.4 + like_rate * 0.4 + unique_viewers_log * 0.2`, with watch and like rate as fractions
+pub fn creator_score(engagement: &CreatorEngagement) -> f64 {
+    let avg_watch = (engagement.avg_percent_watched / 100.0).clamp(0.0, 1.0);
+    let like_rate = if engagement.views == 0 {
+        0.0
+    } else {
+        (engagement.likes as f64 / engagement.views as f64).min(1.0)
+    };
+    let unique_viewers_log = (1.0 + engagement.unique_viewers as f64).log10();
+
+    avg_watch * 0.4 + like_rate * 0.4 + unique_viewers_log * 0.2
+}
+
+pub fn creator_engagement_query(creator_canister: Principal) -> String {
+    format!(
+        "SELECT
+            IFNULL(AVG(IF(event = 'video_duration_watched', CAST(JSON_EXTRACT_SCALAR(params, '$.percentage_watched') AS FLOAT64), NULL)), 0) AS avg_percent_watched,
+            COUNTIF(event = 'video_duration_watched') AS views,
+            COUNTIF(event = 'like_video') AS likes,
+            COUNT(DISTINCT IF(event = 'video_duration_watched', JSON_EXTRACT_SCALAR(params, '$.user_id'), NULL)) AS unique_viewers
+        FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+        WHERE event IN ('video_duration_watched', 'like_video')
+            AND JSON_EXTRACT_SCALAR(params, '$.publisher_canister_id') = '{}'
+            AND timestamp >= TIMESTAMP_SUB(CURRENT_TIMESTAMP(), INTERVAL {} DAY)",
+        creator_canister.to_text(),
+        CREATOR_SCORE_WINDOW_DAYS
+    )
+}
+
+pub fn active_creators_query() -> String {
+    format!(
+        "SELECT DISTINCT JSON_EXTRACT_SCALAR(params, '$.publisher_canister_id')
+        FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+        WHERE event = 'video_duration_watched'
+            AND JSON_EXTRACT_SCALAR(params, '$.publisher_canister_id') IS NOT NULL
+            AND timestamp >= TIMESTAMP_SUB(CURRENT_TIMESTAMP(), INTERVAL {} DAY)",
+        CREATOR_SCORE_WINDOW_DAYS
+    )
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn query_creator_engagement(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+    creator_canister: Principal,
+) -> Result<CreatorEngagement, anyhow::Error> {
+    let request = QueryRequest {
+        query: creator_engagement_query(creator_canister),
+        ..Default::default()
+    };
+    let mut response = bigquery_client
+        .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+        .await?;
+
+    let Some(row) = response.next().await? else {
+        return Ok(CreatorEngagement::default());
+    };
+
+    Ok(CreatorEngagement {
+        avg_percent_watched: row.column::<f64>(0)?,
+        views: row.column::<i64>(1)? as u64,
+        likes: row.column::<i64>(2)? as u64,
+        unique_viewers: row.column::<i64>(3)? as u64,
+    })
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn query_active_creators(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+) -> Result<Vec<Principal>, anyhow::Error> {
+    let request = QueryRequest {
+        query: active_creators_query(),
+        ..Default::default()
+    };
+    let mut response = bigquery_client
+        .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+        .await?;
+
+    let mut creators = Vec::new();
+    while let Some(row) = response.next().await? {
+        match Principal::from_text(row.column::<String>(0)?) {
+            Ok(creator) => creators.push(creator),
+            Err(e) => log::warn!("Skipping invalid publisher canister id: {}", e),
+        }
+    }
+
+    Ok(creators)
+}
+
+/// Computes a creator's engagement score from the last 30 days and caches it in Redis
+#[instrument(skip(state))]
+pub async fn compute_creator_score(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ComputeCreatorScoreRequest>,
+) -> Result<Json<CreatorScore>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let engagement = query_creator_engagement(&state.bigquery_client, req.creator_canister)
+            .await
+            .map_err(AppError::BigQueryError)?;
+        let score = creator_score(&engagement);
+
+        let mut conn = state
+            .canister_backup_redis_pool
+            .get()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get redis connection: {}", e))?;
+        conn.set_ex::<_, _, ()>(
+            creator_score_key(req.creator_canister),
+            score,
+            CREATOR_SCORE_TTL_SECS,
+        )
+        .await
+        .map_err(anyhow::Error::from)?;
+
+        log::info!(
+            "Creator score of {}: {} from {:?}",
+            req.creator_canister,
+            score,
+            engagement
+        );
+
+        Ok(Json(CreatorScore { score }))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, req);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
+
+/// Enqueues a score computation for every creator with views in the last 30 days, scheduled weekly
+#[instrument(skip(state))]
+pub async fn compute_creator_scores(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<usize>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let creators = query_active_creators(&state.bigquery_client)
+            .await
+            .map_err(AppError::BigQueryError)?;
+        let requests = creators
+            .into_iter()
+            .map(|creator_canister| ComputeCreatorScoreRequest { creator_canister })
+            .collect::<Vec<_>>();
+
+        state
+            .qstash_client
+            .publish_compute_creator_scores(&requests)
+            .await?;
+        log::info!("Enqueued score computation for {} creators", requests.len());
+
+        Ok(Json(requests.len()))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Ok(Json(0))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{canister_id}/score",
+    params(
+        ("canister_id" = String, Path, description = "Canister id of the creator"),
+    ),
+    tag = "creators",
+    responses(
+        (status = 200, description = "Cached engagement score of the creator", body = CreatorScore),
+        (status = 400, description = "Invalid canister id"),
+        (status = 404, description = "No score computed for the creator"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn handle_creator_score(
+    State(state): State<Arc<AppState>>,
+    Path(canister_id): Path<String>,
+) -> Result<Json<CreatorScore>, (StatusCode, String)> {
+    let canister_id = Principal::from_text(&canister_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid canister id".to_string()))?;
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let internal_error = |e: String| {
+            log::error!("Failed to read creator score of {}: {}", canister_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read creator score".to_string(),
+            )
+        };
+
+        let mut conn = state
+            .canister_backup_redis_pool
+            .get()
+            .await
+            .map_err(|e| internal_error(e.to_string()))?;
+        let score: Option<f64> = conn
+            .get(creator_score_key(canister_id))
+            .await
+            .map_err(|e| internal_error(e.to_string()))?;
+
+        score
+            .map(|score| Json(CreatorScore { score }))
+            .ok_or((StatusCode::NOT_FOUND, "No score for creator".to_string()))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, canister_id);
+        Err((StatusCode::NOT_FOUND, "No score for creator".to_string()))
+    }
+}
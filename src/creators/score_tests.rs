@@ -0,0 +1,78 @@
+use candid::Principal;
+
+use super::score::{
+    active_creators_query, creator_engagement_query, creator_score, creator_score_key,
+    CreatorEngagement,
+};
+
+fn creator() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+}
+
+#[test]
+fn test_score_weights() {
+    let engagement = CreatorEngagement {
+        avg_percent_watched: 50.0,
+        views: 200,
+        likes: 50,
+        unique_viewers: 99,
+    };
+
+    // 0.5 * 0.4 + 0.25 * 0.4 + log10(100) * 0.2
+    assert_close(creator_score(&engagement), 0.2 + 0.1 + 0.4);
+}
+
+#[test]
+fn test_score_without_views_is_zero() {
+    assert_close(creator_score(&CreatorEngagement::default()), 0.0);
+}
+
+#[test]
+fn test_score_rates_are_bounded() {
+    let engagement = CreatorEngagement {
+        avg_percent_watched: 250.0,
+        views: 1,
+        likes: 5,
+        unique_viewers: 0,
+    };
+
+    assert_close(creator_score(&engagement), 0.4 + 0.4);
+}
+
+#[test]
+fn test_score_grows_with_unique_viewers() {
+    let few = CreatorEngagement {
+        unique_viewers: 10,
+        ..Default::default()
+    };
+    let many = CreatorEngagement {
+        unique_viewers: 10_000,
+        ..Default::default()
+    };
+
+    assert!(creator_score(&many) > creator_score(&few));
+}
+
+#[test]
+fn test_creator_score_key() {
+    assert_eq!(
+        creator_score_key(creator()),
+        "creator_score:rrkah-fqaaa-aaaaa-aaaaq-cai"
+    );
+}
+
+#[test]
+fn test_engagement_query_filters_creator_and_window() {
+    let query = creator_engagement_query(creator());
+
+    assert!(query.contains(
+        "JSON_EXTRACT_SCALAR(params, '$.publisher_canister_id') = 'rrkah-fqaaa-aaaaa-aaaaq-cai'"
+    ));
+    assert!(query.contains("INTERVAL 30 DAY"));
+    assert!(query.contains("COUNT(DISTINCT"));
+    assert!(active_creators_query().contains("INTERVAL 30 DAY"));
+}
@@ -0,0 +1,261 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use candid::Principal;
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+#[cfg(not(feature = "local-bin"))]
+use yral_canisters_client::{
+    individual_user_template::IndividualUserTemplate,
+    sns_governance::{ListNeurons, NeuronId, SnsGovernance},
+};
+
+use crate::{
+    app_state::AppState, posts::watch_history::DELEGATED_IDENTITY_HEADER,
+    types::DelegatedIdentityWire,
+    utils::delegated_identity::get_user_info_from_delegated_identity_wire,
+};
+
+pub const TOKEN_HOLDERS_CACHE_TTL_SECS: u64 = 5 * 60;
+pub const LIST_NEURONS_PAGE_SIZE: u32 = 100;
+const E8S_PER_TOKEN: u64 = 100_000_000;
+
+/// Upper bound of each bucket in whole tokens, the last bucket is unbounded
+pub const HOLDER_BUCKETS: [(&str, Option<u64>); 4] = [
+    ("0-1K", Some(1_000)),
+    ("1K-10K", Some(10_000)),
+    ("10K-100K", Some(100_000)),
+    ("100K+", None),
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct HolderBucket {
+    pub bucket: String,
+    pub count: u64,
+    /// Share of all holders, 0 to 100
+    pub percentage: f64,
+}
+
+/// A DAO neuron, its stake attributed to the first principal of its permissions
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeuronStake {
+    pub id: Vec<u8>,
+    pub holder: Option<Principal>,
+    pub stake_e8s: u64,
+}
+
+pub fn token_holders_key(governance: Principal) -> String {
+    format!("token_holders:{}", governance)
+}
+
+pub(crate) trait DaoNeurons {
+    /// Up to [`LIST_NEURONS_PAGE_SIZE`] neurons after `start_page_at`, ordered by id
+    async fn neurons_page(
+        &self,
+        governance: Principal,
+        start_page_at: Option<Vec<u8>>,
+    ) -> Result<Vec<NeuronStake>, anyhow::Error>;
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl DaoNeurons for ic_agent::Agent {
+    async fn neurons_page(
+        &self,
+        governance: Principal,
+        start_page_at: Option<Vec<u8>>,
+    ) -> Result<Vec<NeuronStake>, anyhow::Error> {
+        let neurons = SnsGovernance(governance, self)
+            .list_neurons(ListNeurons {
+                of_principal: None,
+                limit: LIST_NEURONS_PAGE_SIZE,
+                start_page_at: start_page_at.map(|id| NeuronId { id: id.into() }),
+            })
+            .await?
+            .neurons;
+
+        Ok(neurons
+            .into_iter()
+            .filter_map(|neuron| {
+                Some(NeuronStake {
+                    id: neuron.id?.id.to_vec(),
+                    holder: neuron
+                        .permissions
+                        .first()
+                        .and_then(|permission| permission.principal),
+                    stake_e8s: neuron.cached_neuron_stake_e8s,
+                })
+            })
+            .collect())
+    }
+}
+
+/// Staked e8s of every holder with a non zero stake, across all pages of the DAO's neurons
+pub async fn holder_balances(
+    dao: &impl DaoNeurons,
+    governance: Principal,
+) -> Result<HashMap<Principal, u64>, anyhow::Error> {
+    let mut balances: HashMap<Principal, u64> = HashMap::new();
+    let mut start_page_at = None;
+    loop {
+        let page = dao.neurons_page(governance, start_page_at).await?;
+        let page_len = page.len();
+        start_page_at = page.last().map(|neuron| neuron.id.clone());
+
+        for neuron in page {
+            if let (Some(holder), true) = (neuron.holder, neuron.stake_e8s > 0) {
+                *balances.entry(holder).or_default() += neuron.stake_e8s;
+            }
+        }
+
+        if page_len < LIST_NEURONS_PAGE_SIZE as usize {
+            break;
+        }
+    }
+
+    Ok(balances)
+}
+
+/// Holders per bucket, every bucket is listed even when empty
+pub fn holder_distribution(balances_e8s: impl IntoIterator<Item = u64>) -> Vec<HolderBucket> {
+    let mut counts = [0u64; HOLDER_BUCKETS.len()];
+    for balance_e8s in balances_e8s {
+        let tokens = balance_e8s / E8S_PER_TOKEN;
+        let bucket = HOLDER_BUCKETS
+            .iter()
+            .position(|(_, upper)| upper.is_none_or(|upper| tokens < upper))
+            .unwrap_or(HOLDER_BUCKETS.len() - 1);
+        counts[bucket] += 1;
+    }
+
+    let total: u64 = counts.iter().sum();
+    HOLDER_BUCKETS
+        .iter()
+        .zip(counts)
+        .map(|((bucket, _), count)| HolderBucket {
+            bucket: bucket.to_string(),
+            count,
+            percentage: if total == 0 {
+                0.0
+            } else {
+                count as f64 / total as f64 * 100.0
+            },
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn cached_distribution(
+    state: &AppState,
+    governance: Principal,
+) -> Result<Vec<HolderBucket>, anyhow::Error> {
+    let key = token_holders_key(governance);
+    let mut conn = state.canister_backup_redis_pool.get().await?;
+    let cached: Option<String> = conn.get(&key).await?;
+    if let Some(distribution) = cached.and_then(|cached| serde_json::from_str(&cached).ok()) {
+        return Ok(distribution);
+    }
+    drop(conn);
+
+    let balances = holder_balances(&state.agent, governance).await?;
+    let distribution = holder_distribution(balances.into_values());
+
+    let mut conn = state.canister_backup_redis_pool.get().await?;
+    let _: () = conn
+        .set_ex(
+            &key,
+            serde_json::to_string(&distribution)?,
+            TOKEN_HOLDERS_CACHE_TTL_SECS,
+        )
+        .await?;
+
+    Ok(distribution)
+}
+
+#[utoipa::path(
+    get,
+    path = "/{canister_id}/token-holders",
+    params(
+        ("canister_id" = String, Path, description = "Canister id of the creator"),
+        ("x-delegated-identity" = String, Header, description = "JSON encoded DelegatedIdentityWire of the creator"),
+    ),
+    tag = "creators",
+    responses(
+        (status = 200, description = "Holders of the creator's token by staked balance", body = Vec<HolderBucket>),
+        (status = 400, description = "Invalid canister id"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Creator has no token"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state, headers))]
+pub async fn handle_token_holders(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(canister_id): Path<String>,
+) -> Result<Json<Vec<HolderBucket>>, (StatusCode, String)> {
+    let canister_id = Principal::from_text(&canister_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid canister id".to_string()))?;
+
+    let delegated_identity_wire: DelegatedIdentityWire = headers
+        .get(DELEGATED_IDENTITY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| serde_json::from_str(value).ok())
+        .ok_or((
+            StatusCode::UNAUTHORIZED,
+            "Missing delegated identity".to_string(),
+        ))?;
+
+    let user_info = get_user_info_from_delegated_identity_wire(&state, delegated_identity_wire)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::UNAUTHORIZED,
+                format!("Failed to get user info: {}", e),
+            )
+        })?;
+    if user_info.user_canister != canister_id {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Canister does not belong to user".to_string(),
+        ));
+    }
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let internal_error = |e: anyhow::Error| {
+            log::error!("Failed to read token holders of {}: {}", canister_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read token holders".to_string(),
+            )
+        };
+
+        // creators hold one token, the latest one when older deployments exist
+        let governance = IndividualUserTemplate(canister_id, &state.agent)
+            .deployed_cdao_canisters()
+            .await
+            .map_err(|e| internal_error(e.into()))?
+            .last()
+            .map(|cdao| cdao.governance)
+            .ok_or((StatusCode::NOT_FOUND, "Creator has no token".to_string()))?;
+
+        Ok(Json(
+            cached_distribution(&state, governance)
+                .await
+                .map_err(internal_error)?,
+        ))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Err((StatusCode::NOT_FOUND, "Creator has no token".to_string()))
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use candid::Principal;
+
+use super::token_holders::{
+    holder_balances, holder_distribution, token_holders_key, DaoNeurons, NeuronStake,
+    LIST_NEURONS_PAGE_SIZE,
+};
+
+const TOKEN: u64 = 100_000_000;
+
+/// Governance canister answering `list_neurons` from its neurons sorted by id
+struct MockGovernance(Vec<NeuronStake>);
+
+impl DaoNeurons for MockGovernance {
+    async fn neurons_page(
+        &self,
+        _governance: Principal,
+        start_page_at: Option<Vec<u8>>,
+    ) -> Result<Vec<NeuronStake>, anyhow::Error> {
+        Ok(self
+            .0
+            .iter()
+            .filter(|neuron| {
+                start_page_at
+                    .as_ref()
+                    .is_none_or(|start| neuron.id > *start)
+            })
+            .take(LIST_NEURONS_PAGE_SIZE as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+fn principal(n: u8) -> Principal {
+    Principal::from_slice(&[n])
+}
+
+fn neuron(id: u16, holder: Option<Principal>, stake_e8s: u64) -> NeuronStake {
+    NeuronStake {
+        id: id.to_be_bytes().to_vec(),
+        holder,
+        stake_e8s,
+    }
+}
+
+#[test]
+fn test_token_holders_key() {
+    assert_eq!(
+        token_holders_key(Principal::anonymous()),
+        "token_holders:2vxsx-fae"
+    );
+}
+
+#[tokio::test]
+async fn test_balances_sum_neurons_of_a_holder_across_pages() {
+    let mut neurons: Vec<NeuronStake> = (0..LIST_NEURONS_PAGE_SIZE as u16 + 5)
+        .map(|id| neuron(id, Some(principal(1)), TOKEN))
+        .collect();
+    neurons.push(neuron(500, Some(principal(2)), 3 * TOKEN));
+    neurons.push(neuron(501, None, 7 * TOKEN));
+    neurons.push(neuron(502, Some(principal(3)), 0));
+
+    let balances = holder_balances(&MockGovernance(neurons), principal(9))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        balances,
+        HashMap::from([
+            (principal(1), (LIST_NEURONS_PAGE_SIZE as u64 + 5) * TOKEN),
+            (principal(2), 3 * TOKEN),
+        ])
+    );
+}
+
+#[test]
+fn test_distribution_buckets_by_whole_tokens() {
+    let distribution = holder_distribution([
+        0,
+        999 * TOKEN,
+        1_000 * TOKEN,
+        9_999 * TOKEN,
+        50_000 * TOKEN,
+        100_000 * TOKEN,
+        2_000_000 * TOKEN,
+        u64::MAX,
+    ]);
+
+    let counts: Vec<(&str, u64)> = distribution
+        .iter()
+        .map(|bucket| (bucket.bucket.as_str(), bucket.count))
+        .collect();
+    assert_eq!(
+        counts,
+        vec![("0-1K", 2), ("1K-10K", 2), ("10K-100K", 1), ("100K+", 3)]
+    );
+    assert_eq!(distribution[0].percentage, 25.0);
+    assert_eq!(distribution[3].percentage, 37.5);
+}
+
+#[test]
+fn test_distribution_without_holders_lists_empty_buckets() {
+    let distribution = holder_distribution([]);
+
+    assert_eq!(distribution.len(), 4);
+    assert!(distribution
+        .iter()
+        .all(|bucket| bucket.count == 0 && bucket.percentage == 0.0));
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_events, AuthBearer},
+};
+
+use super::video_hash_index::{similarity_pct, VideoHashIndex, MIH_MAX_RADIUS};
+
+/// About 86% similarity, the distance uploads are treated as duplicates at
+pub const DEFAULT_SIMILAR_MAX_DISTANCE: u32 = 9;
+/// Larger distances would need a linear scan of the whole index
+pub const SIMILAR_MAX_DISTANCE_LIMIT: u32 = MIH_MAX_RADIUS;
+pub const DEFAULT_SIMILAR_LIMIT: usize = 20;
+pub const SIMILAR_LIMIT_MAX: usize = 100;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SimilarVideosParams {
+    /// Largest Hamming distance between the hashes, at most 15, defaults to 9
+    pub max_distance: Option<u32>,
+    /// Number of videos returned, at most 100, defaults to 20
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct SimilarVideo {
+    pub uuid: Uuid,
+    /// Share of matching hash bits, 0 to 100
+    pub similarity_pct: f64,
+}
+
+/// The `limit` videos within `max_distance` bits of `bits`, most similar first, `video_id`
+/// itself excluded
+pub fn similar_videos(
+    index: &VideoHashIndex,
+    video_id: Uuid,
+    bits: u64,
+    max_distance: u32,
+    limit: usize,
+) -> Vec<SimilarVideo> {
+    index
+        .find_within_distance(bits, max_distance)
+        .into_iter()
+        .filter(|(id, _)| *id != video_id)
+        .take(limit)
+        .map(|(uuid, distance)| SimilarVideo {
+            uuid,
+            similarity_pct: similarity_pct(distance),
+        })
+        .collect()
+}
+
+/// Distance and limit of the query, defaulted and checked against their maximums
+pub fn similar_videos_bounds(params: &SimilarVideosParams) -> Result<(u32, usize), String> {
+    let max_distance = params.max_distance.unwrap_or(DEFAULT_SIMILAR_MAX_DISTANCE);
+    if max_distance > SIMILAR_MAX_DISTANCE_LIMIT {
+        return Err(format!(
+            "max_distance is at most {}",
+            SIMILAR_MAX_DISTANCE_LIMIT
+        ));
+    }
+
+    let limit = params.limit.unwrap_or(DEFAULT_SIMILAR_LIMIT);
+    if limit == 0 || limit > SIMILAR_LIMIT_MAX {
+        return Err(format!("limit is between 1 and {}", SIMILAR_LIMIT_MAX));
+    }
+
+    Ok((max_distance, limit))
+}
+
+/// Hash of the video from the local index, or from Redis when another replica added it since
+/// the last load
+async fn stored_hash(state: &AppState, video_id: Uuid) -> Result<Option<u64>, anyhow::Error> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        state.redis_video_hash_index().get(&video_id).await
+    }
+
+    #[cfg(feature = "local-bin")]
+    Ok(state.video_hash_index.read().await.get(&video_id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{video_id}/similar",
+    params(
+        ("video_id" = String, Path, description = "Cloudflare uid of the video"),
+        SimilarVideosParams,
+    ),
+    tag = "videos",
+    responses(
+        (status = 200, description = "Indexed videos with a similar perceptual hash, most similar first", body = Vec<SimilarVideo>),
+        (status = 400, description = "Invalid video id, distance or limit"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Video is not indexed"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state, token))]
+pub async fn handle_similar_videos(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Path(video_id): Path<String>,
+    Query(params): Query<SimilarVideosParams>,
+) -> Result<Json<Vec<SimilarVideo>>, (StatusCode, String)> {
+    check_auth_events(Some(token))
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()))?;
+
+    let video_id = Uuid::parse_str(&video_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid video id".to_string()))?;
+    let (max_distance, limit) =
+        similar_videos_bounds(&params).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let bits = stored_hash(&state, video_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to read the hash of video {}: {}", video_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read video hash".to_string(),
+            )
+        })?
+        .ok_or((StatusCode::NOT_FOUND, "Video is not indexed".to_string()))?;
+
+    let index = state.video_hash_index.read().await;
+    Ok(Json(similar_videos(
+        &index,
+        video_id,
+        bits,
+        max_distance,
+        limit,
+    )))
+}
@@ -0,0 +1,150 @@
+use uuid::Uuid;
+
+use super::{
+    api::{
+        similar_videos, similar_videos_bounds, SimilarVideo, SimilarVideosParams,
+        DEFAULT_SIMILAR_LIMIT, DEFAULT_SIMILAR_MAX_DISTANCE, SIMILAR_LIMIT_MAX,
+        SIMILAR_MAX_DISTANCE_LIMIT,
+    },
+    video_hash_index::VideoHashIndex,
+};
+
+const BITS: u64 = 0x0123_4567_89ab_cdef;
+
+fn id(n: u128) -> Uuid {
+    Uuid::from_u128(n)
+}
+
+#[test]
+fn test_empty_index_has_no_similar_videos() {
+    assert!(similar_videos(
+        &VideoHashIndex::new(),
+        id(1),
+        BITS,
+        DEFAULT_SIMILAR_MAX_DISTANCE,
+        DEFAULT_SIMILAR_LIMIT
+    )
+    .is_empty());
+}
+
+#[test]
+fn test_exact_match_is_fully_similar() {
+    let mut index = VideoHashIndex::new();
+    index.add(id(1), BITS);
+    index.add(id(2), BITS);
+
+    assert_eq!(
+        similar_videos(
+            &index,
+            id(1),
+            BITS,
+            DEFAULT_SIMILAR_MAX_DISTANCE,
+            DEFAULT_SIMILAR_LIMIT
+        ),
+        vec![SimilarVideo {
+            uuid: id(2),
+            similarity_pct: 100.0,
+        }]
+    );
+}
+
+#[test]
+fn test_similarity_of_a_hash_four_bits_away() {
+    let mut index = VideoHashIndex::new();
+    index.add(id(1), BITS);
+    index.add(id(2), BITS ^ 0b1111);
+
+    assert_eq!(
+        similar_videos(
+            &index,
+            id(1),
+            BITS,
+            DEFAULT_SIMILAR_MAX_DISTANCE,
+            DEFAULT_SIMILAR_LIMIT
+        ),
+        vec![SimilarVideo {
+            uuid: id(2),
+            similarity_pct: 93.75,
+        }]
+    );
+}
+
+#[test]
+fn test_most_similar_first_within_max_distance() {
+    let mut index = VideoHashIndex::new();
+    index.add(id(1), BITS);
+    index.add(id(2), BITS ^ 0b11_1111);
+    index.add(id(3), BITS ^ 0b1);
+    index.add(id(4), BITS ^ 0x3ff);
+
+    let similar: Vec<Uuid> = similar_videos(
+        &index,
+        id(1),
+        BITS,
+        DEFAULT_SIMILAR_MAX_DISTANCE,
+        DEFAULT_SIMILAR_LIMIT,
+    )
+    .into_iter()
+    .map(|video| video.uuid)
+    .collect();
+    assert_eq!(similar, vec![id(3), id(2)]);
+
+    assert_eq!(
+        similar_videos(&index, id(1), BITS, 10, DEFAULT_SIMILAR_LIMIT).len(),
+        3
+    );
+}
+
+#[test]
+fn test_results_are_cut_at_the_limit() {
+    let mut index = VideoHashIndex::new();
+    index.add(id(1), BITS);
+    index.add(id(2), BITS ^ 0b111);
+    index.add(id(3), BITS ^ 0b1);
+    index.add(id(4), BITS ^ 0b11);
+
+    let similar: Vec<Uuid> = similar_videos(&index, id(1), BITS, DEFAULT_SIMILAR_MAX_DISTANCE, 2)
+        .into_iter()
+        .map(|video| video.uuid)
+        .collect();
+    assert_eq!(similar, vec![id(3), id(4)]);
+}
+
+#[test]
+fn test_bounds_default_when_missing() {
+    let params = SimilarVideosParams {
+        max_distance: None,
+        limit: None,
+    };
+
+    assert_eq!(
+        similar_videos_bounds(&params),
+        Ok((DEFAULT_SIMILAR_MAX_DISTANCE, DEFAULT_SIMILAR_LIMIT))
+    );
+}
+
+#[test]
+fn test_bounds_reject_distances_past_the_mih_radius() {
+    let params = SimilarVideosParams {
+        max_distance: Some(SIMILAR_MAX_DISTANCE_LIMIT + 1),
+        limit: None,
+    };
+    assert!(similar_videos_bounds(&params).is_err());
+
+    let params = SimilarVideosParams {
+        max_distance: Some(SIMILAR_MAX_DISTANCE_LIMIT),
+        limit: None,
+    };
+    assert!(similar_videos_bounds(&params).is_ok());
+}
+
+#[test]
+fn test_bounds_reject_out_of_range_limits() {
+    for limit in [0, SIMILAR_LIMIT_MAX + 1] {
+        let params = SimilarVideosParams {
+            max_distance: None,
+            limit: Some(limit),
+        };
+        assert!(similar_videos_bounds(&params).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::AppState;
 use crate::{
     app_state,
+    config::{VideoQuality, CLOUDFLARE_CONFIG},
     qstash::duplicate::{VideoHashDuplication, VideoPublisherData},
 };
 use axum::{extract::Query, extract::State, http::HeaderMap, Json};
@@ -207,10 +208,7 @@ async fn queue_video_to_qstash(
     use http::header::CONTENT_TYPE;
 
     // Prepare the video URL
-    let video_url = format!(
-        "https://customer-2p3jflss4r4hmpnz.cloudflarestream.com/{}/downloads/default.mp4",
-        video_id
-    );
+    let video_url = CLOUDFLARE_CONFIG.video_download_url(&video_id, VideoQuality::Default);
 
     // Create request payload - this is specifically for backfill
     let request_data = serde_json::json!({
@@ -0,0 +1,114 @@
+use std::{
+    error::Error,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use image::{DynamicImage, Rgb, RgbImage};
+
+use super::videohash::VideoHash;
+
+/// Frames with a known hash, a change to the hashing algorithm shows up as a changed hash
+pub struct FrameVector {
+    pub name: &'static str,
+    pub frames: Vec<DynamicImage>,
+    pub expected_hash: String,
+}
+
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+const RED: Rgb<u8> = Rgb([255, 0, 0]);
+const DARK_RED: Rgb<u8> = Rgb([100, 0, 0]);
+const BLUE: Rgb<u8> = Rgb([0, 0, 255]);
+
+fn solid(color: Rgb<u8>) -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_pixel(64, 64, color))
+}
+
+/// `left` on the left half, `right` on the right half
+fn halves(left: Rgb<u8>, right: Rgb<u8>) -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(
+        64,
+        64,
+        |x, _| {
+            if x < 32 {
+                left
+            } else {
+                right
+            }
+        },
+    ))
+}
+
+/// Vectors made of generated frames, so they need neither ffmpeg nor downloaded videos.
+///
+/// A uniform frame has every grayscale cell at the median, so its wavelet bits are all set and
+/// the hash is the negated color bits. A color cell's bit is set when its dominant channel, or
+/// its brightness if no channel dominates, is above 128.
+pub fn frame_vectors() -> Vec<FrameVector> {
+    vec![
+        FrameVector {
+            name: "black frame",
+            frames: vec![solid(BLACK)],
+            expected_hash: "1".repeat(64),
+        },
+        FrameVector {
+            name: "white frame",
+            frames: vec![solid(WHITE)],
+            expected_hash: "0".repeat(64),
+        },
+        FrameVector {
+            name: "red frame",
+            frames: vec![solid(RED)],
+            expected_hash: "0".repeat(64),
+        },
+        FrameVector {
+            name: "dark red frame",
+            frames: vec![solid(DARK_RED)],
+            expected_hash: "1".repeat(64),
+        },
+        // the brighter red half is above the grayscale median, both halves are bright colors
+        FrameVector {
+            name: "red and blue halves",
+            frames: vec![halves(RED, BLUE)],
+            expected_hash: "00001111".repeat(8),
+        },
+        // most of the collage is the empty black of its second row, so the grayscale median is
+        // black, and the stitched frames leave the left color cells dark
+        FrameVector {
+            name: "black then white frame",
+            frames: vec![solid(BLACK), solid(WHITE)],
+            expected_hash: "11110000".repeat(8),
+        },
+    ]
+}
+
+#[derive(Debug)]
+pub struct BenchmarkTimings {
+    pub min: Duration,
+    pub median: Duration,
+    pub max: Duration,
+}
+
+/// Times repeated [`VideoHash::fast_hash`] runs over one video
+pub struct VideoHashBenchmark {
+    pub runs: usize,
+}
+
+impl VideoHashBenchmark {
+    pub fn run(&self, video_path: &Path) -> Result<BenchmarkTimings, Box<dyn Error + Send + Sync>> {
+        let mut timings = Vec::with_capacity(self.runs);
+        for _ in 0..self.runs.max(1) {
+            let start = Instant::now();
+            VideoHash::fast_hash(video_path)?;
+            timings.push(start.elapsed());
+        }
+        timings.sort();
+
+        Ok(BenchmarkTimings {
+            min: timings[0],
+            median: timings[timings.len() / 2],
+            max: timings[timings.len() - 1],
+        })
+    }
+}
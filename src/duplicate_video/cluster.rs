@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
+use log::{error, info, warn};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    duplicate_video::videohash::VideoHash,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct VideoHashClusterRequest {
+    video_ids: Vec<String>,
+    threshold: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimilarVideoPair {
+    video_id_a: String,
+    video_id_b: String,
+    similarity: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VideoHashClusterResponse {
+    videos_compared: usize,
+    pairs: Vec<SimilarVideoPair>,
+}
+
+pub async fn videohash_cluster_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Json(req): Json<VideoHashClusterRequest>,
+) -> Result<Json<VideoHashClusterResponse>, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let video_ids: Vec<Uuid> = req
+        .video_ids
+        .iter()
+        .filter_map(|id| match Uuid::parse_str(id) {
+            Ok(uuid) => Some(uuid),
+            Err(_) => {
+                warn!("Skipping invalid video id: {}", id);
+                None
+            }
+        })
+        .collect();
+
+    if video_ids.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let hashes = fetch_videohashes(&state.bigquery_client, &video_ids)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch videohashes: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    info!(
+        "Computing similarity matrix for {} of {} requested videos",
+        hashes.len(),
+        video_ids.len()
+    );
+
+    let matrix = VideoHash::similarity_matrix(&hashes, req.threshold);
+
+    let mut pairs: Vec<SimilarVideoPair> = matrix
+        .into_iter()
+        .map(|((a, b), similarity)| SimilarVideoPair {
+            video_id_a: a.as_simple().to_string(),
+            video_id_b: b.as_simple().to_string(),
+            similarity,
+        })
+        .collect();
+    pairs.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+
+    Ok(Json(VideoHashClusterResponse {
+        videos_compared: hashes.len(),
+        pairs,
+    }))
+}
+
+async fn fetch_videohashes(
+    bq_client: &google_cloud_bigquery::client::Client,
+    video_ids: &[Uuid],
+) -> anyhow::Result<Vec<(Uuid, VideoHash)>> {
+    let id_list = video_ids
+        .iter()
+        .map(|id| format!("'{}'", id.as_simple()))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let request = QueryRequest {
+        query: format!(
+            "SELECT video_id, ANY_VALUE(videohash) AS videohash
+             FROM `hot-or-not-feed-intelligence.yral_ds.videohash_original`
+             WHERE video_id IN ({})
+             GROUP BY video_id",
+            id_list
+        ),
+        ..Default::default()
+    };
+
+    let mut response = bq_client
+        .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to query videohash_original: {}", e))?;
+
+    let mut hashes = Vec::new();
+    while let Some(row) = response.next().await? {
+        let video_id: String = row.column(0)?;
+        let hash: String = row.column(1)?;
+        let Ok(uuid) = Uuid::parse_str(&video_id) else {
+            continue;
+        };
+        hashes.push((uuid, VideoHash { hash }));
+    }
+
+    Ok(hashes)
+}
@@ -0,0 +1,187 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    response::Response,
+    Json,
+};
+use http::{header, StatusCode};
+use serde::Serialize;
+use tokio::{sync::RwLock, task::JoinHandle};
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+};
+
+use super::video_hash_index::{VideoHashIndex, VideoHashIndexStats};
+
+#[derive(Debug, Serialize)]
+pub struct VideoHashImportResponse {
+    pub imported: usize,
+    pub total: usize,
+}
+
+#[instrument(skip(state, token))]
+pub async fn videohash_export_csv_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Response, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let mut csv = Vec::new();
+    state
+        .video_hash_index
+        .read()
+        .await
+        .export_csv(&mut csv)
+        .map_err(|e| {
+            log::error!("Failed to export video hash index: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/csv")
+        .header(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"videohash_index.csv\"",
+        )
+        .body(Body::from(csv))
+        .unwrap())
+}
+
+/// Merges the uploaded CSV into the in-memory index and shares it with the other replicas
+/// through Redis, uploaded rows win on conflicts
+#[instrument(skip(state, token, body))]
+pub async fn videohash_import_csv_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    body: Bytes,
+) -> Result<Json<VideoHashImportResponse>, (StatusCode, String)> {
+    check_auth_admin(&token).map_err(|_| (StatusCode::UNAUTHORIZED, "Unauthorized".into()))?;
+
+    let imported = VideoHashIndex::import_csv(&body[..])
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid CSV: {}", e)))?;
+    let imported_len = imported.len();
+
+    let total = {
+        let mut index = state.video_hash_index.write().await;
+        let current = std::mem::take(&mut *index);
+        *index = current.merge(imported);
+        index.len()
+    };
+
+    // the next load from redis would drop hashes only held locally
+    #[cfg(not(feature = "local-bin"))]
+    state
+        .redis_video_hash_index()
+        .flush_to_redis()
+        .await
+        .map_err(|e| {
+            log::error!("Failed to store imported video hashes: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to store video hashes".into(),
+            )
+        })?;
+
+    log::info!(
+        "Imported {} video hashes, index now holds {}",
+        imported_len,
+        total
+    );
+
+    Ok(Json(VideoHashImportResponse {
+        imported: imported_len,
+        total,
+    }))
+}
+
+#[instrument(skip(state, token))]
+pub async fn videohash_index_stats_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<VideoHashIndexStats>, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    Ok(Json(state.video_hash_index.read().await.get_stats()))
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct MihRebuildStarted {
+    pub total_hashes: usize,
+    pub mih_allocated_slots: usize,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct MihRebuildReport {
+    pub total_hashes: usize,
+    pub slots_before: usize,
+    pub slots_after: usize,
+    pub duration_ms: u128,
+}
+
+/// Rebuilds the MIH tables off the async workers from a snapshot of the hashes, searches only
+/// wait for the tables to be swapped in
+pub async fn rebuild_mih_index(
+    index: &RwLock<VideoHashIndex>,
+) -> Result<MihRebuildReport, anyhow::Error> {
+    let start = tokio::time::Instant::now();
+    let (snapshot, slots_before) = {
+        let index = index.read().await;
+        (index.mih_snapshot(), index.mih_allocated_slots())
+    };
+
+    let rebuilt = tokio::task::spawn_blocking(move || snapshot.build()).await?;
+
+    let mut index = index.write().await;
+    index.install_mih(rebuilt);
+
+    Ok(MihRebuildReport {
+        total_hashes: index.len(),
+        slots_before,
+        slots_after: index.mih_allocated_slots(),
+        duration_ms: start.elapsed().as_millis(),
+    })
+}
+
+pub fn spawn_mih_rebuild(
+    index: Arc<RwLock<VideoHashIndex>>,
+) -> JoinHandle<Result<MihRebuildReport, anyhow::Error>> {
+    tokio::spawn(async move {
+        let report = rebuild_mih_index(&index)
+            .await
+            .inspect_err(|e| log::error!("Failed to rebuild MIH index: {}", e))?;
+        log::info!(
+            "Rebuilt MIH index of {} video hashes in {}ms, slots {} -> {}",
+            report.total_hashes,
+            report.duration_ms,
+            report.slots_before,
+            report.slots_after
+        );
+        Ok(report)
+    })
+}
+
+/// Starts a full MIH rebuild in the background and returns the index size before it
+#[instrument(skip(state, token))]
+pub async fn videohash_rebuild_mih_index_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+) -> Result<(StatusCode, Json<MihRebuildStarted>), StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let started = {
+        let index = state.video_hash_index.read().await;
+        MihRebuildStarted {
+            total_hashes: index.len(),
+            mih_allocated_slots: index.mih_allocated_slots(),
+        }
+    };
+    spawn_mih_rebuild(state.video_hash_index.clone());
+
+    Ok((StatusCode::ACCEPTED, Json(started)))
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::{
+    index_csv::{rebuild_mih_index, spawn_mih_rebuild},
+    video_hash_index::VideoHashIndex,
+};
+
+fn churned_index() -> VideoHashIndex {
+    let mut index = VideoHashIndex::new();
+    for n in 0..100u128 {
+        index.add(Uuid::from_u128(n), (n as u64) << 8);
+    }
+    for n in 0..90u128 {
+        index.remove(&Uuid::from_u128(n));
+    }
+    index
+}
+
+#[tokio::test]
+async fn test_rebuild_reclaims_removed_slots() {
+    let index = RwLock::new(churned_index());
+
+    let report = rebuild_mih_index(&index).await.unwrap();
+
+    assert_eq!(report.total_hashes, 10);
+    assert!(report.slots_after < report.slots_before);
+    assert_eq!(report.slots_after, index.read().await.mih_allocated_slots());
+}
+
+#[tokio::test]
+async fn test_background_rebuild_keeps_index_searchable() {
+    let index = Arc::new(RwLock::new(churned_index()));
+    assert!(index
+        .read()
+        .await
+        .get_stats()
+        .last_explicit_rebuild
+        .is_none());
+
+    let report = spawn_mih_rebuild(index.clone()).await.unwrap().unwrap();
+
+    let index = index.read().await;
+    assert_eq!(report.total_hashes, index.len());
+    assert!(index.get_stats().last_explicit_rebuild.is_some());
+    assert!(index.get_stats().index_built);
+    assert_eq!(
+        index.find_within_distance(95 << 8, 0),
+        vec![(Uuid::from_u128(95), 0)]
+    );
+    assert_eq!(index.find_within_distance(5 << 8, 0), vec![]);
+}
+
+#[test]
+fn test_changes_during_rebuild_are_kept() {
+    let mut index = churned_index();
+    let snapshot = index.mih_snapshot();
+
+    // the index keeps taking writes while the tables are rebuilt off the lock
+    index.add(Uuid::from_u128(200), 200 << 8);
+    index.remove(&Uuid::from_u128(95));
+    index.add(Uuid::from_u128(96), 300 << 8);
+
+    index.install_mih(snapshot.build());
+
+    assert_eq!(
+        index.find_within_distance(200 << 8, 0),
+        vec![(Uuid::from_u128(200), 0)]
+    );
+    assert_eq!(index.find_within_distance(95 << 8, 0), vec![]);
+    assert_eq!(index.find_within_distance(96 << 8, 0), vec![]);
+    assert_eq!(
+        index.find_within_distance(300 << 8, 0),
+        vec![(Uuid::from_u128(96), 0)]
+    );
+    assert_eq!(
+        index.find_within_distance(97 << 8, 0),
+        vec![(Uuid::from_u128(97), 0)]
+    );
+}
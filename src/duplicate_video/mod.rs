@@ -1,5 +1,32 @@
+pub mod api;
 pub mod backfill;
+pub mod cluster;
+pub mod index_csv;
+pub mod redis_hash_index;
+pub mod video_hash_index;
 pub mod videohash;
 
+#[cfg(test)]
+mod api_tests;
+#[cfg(test)]
+mod benchmarks;
+#[cfg(test)]
+mod index_csv_tests;
+#[cfg(test)]
+mod redis_hash_index_tests;
+#[cfg(test)]
+mod video_hash_index_tests;
 #[cfg(test)]
 mod videohash_tests;
+
+use std::sync::Arc;
+
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::app_state::AppState;
+
+pub fn videos_router(state: Arc<AppState>) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(api::handle_similar_videos))
+        .with_state(state)
+}
@@ -0,0 +1,224 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::types::RedisPool;
+
+use super::{video_hash_index::VideoHashIndex, videohash::VideoHash};
+
+/// Redis hash of every indexed video, `<uuid> <hex-encoded-u64>`
+pub const VIDEOHASH_INDEX_KEY: &str = "videohash_index";
+const REDIS_BATCH_SIZE: usize = 1000;
+/// How often replicas pick up the hashes added and removed by the others
+pub const REBUILD_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+pub fn encode_hash(bits: u64) -> String {
+    format!("{:016x}", bits)
+}
+
+/// `<uuid>` field and its `<hex-encoded-u64>` value, `None` for entries of another shape
+pub fn parse_hash_entry(field: &str, hash_hex: &str) -> Option<(Uuid, u64)> {
+    let id = Uuid::parse_str(field).ok()?;
+    let bits = u64::from_str_radix(hash_hex, 16).ok()?;
+    Some((id, bits))
+}
+
+/// The `videohash_index` hash shared by every replica
+pub(crate) trait HashStore {
+    async fn set_hashes(&self, entries: &[(String, String)]) -> Result<(), anyhow::Error>;
+
+    /// Every field of the hash with its value
+    async fn hashes(&self) -> Result<Vec<(String, String)>, anyhow::Error>;
+
+    async fn hash(&self, field: &str) -> Result<Option<String>, anyhow::Error>;
+
+    /// Returns whether the field existed
+    async fn remove_hash(&self, field: &str) -> Result<bool, anyhow::Error>;
+}
+
+impl HashStore for RedisPool {
+    async fn set_hashes(&self, entries: &[(String, String)]) -> Result<(), anyhow::Error> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut conn = self.get().await?;
+        let mut cmd = redis::cmd("HSET");
+        cmd.arg(VIDEOHASH_INDEX_KEY);
+        for (field, value) in entries {
+            cmd.arg(field).arg(value);
+        }
+        cmd.query_async::<()>(&mut *conn).await?;
+
+        Ok(())
+    }
+
+    async fn hashes(&self) -> Result<Vec<(String, String)>, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let mut entries = Vec::new();
+        let mut cursor: u64 = 0;
+        // HSCAN in batches so a large index doesn't block redis like HGETALL would
+        loop {
+            let (next, batch): (u64, Vec<(String, String)>) = redis::cmd("HSCAN")
+                .arg(VIDEOHASH_INDEX_KEY)
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(REDIS_BATCH_SIZE)
+                .query_async(&mut *conn)
+                .await?;
+            entries.extend(batch);
+
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        Ok(entries)
+    }
+
+    async fn hash(&self, field: &str) -> Result<Option<String>, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let hash_hex: Option<String> = redis::cmd("HGET")
+            .arg(VIDEOHASH_INDEX_KEY)
+            .arg(field)
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(hash_hex)
+    }
+
+    async fn remove_hash(&self, field: &str) -> Result<bool, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let removed: u64 = redis::cmd("HDEL")
+            .arg(VIDEOHASH_INDEX_KEY)
+            .arg(field)
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(removed > 0)
+    }
+}
+
+/// [`VideoHashIndex`] persisted in the `videohash_index` Redis hash. Every hash is written to
+/// Redis before the local index, and the local index is replaced by the Redis copy by
+/// [`RedisBackedVideoHashIndex::keep_in_sync`] to pick up the adds and removes of other
+/// replicas.
+pub struct RedisBackedVideoHashIndex<S = RedisPool> {
+    store: S,
+    index: Arc<RwLock<VideoHashIndex>>,
+}
+
+impl<S: HashStore> RedisBackedVideoHashIndex<S> {
+    pub fn new(store: S, index: Arc<RwLock<VideoHashIndex>>) -> Self {
+        Self { store, index }
+    }
+
+    /// Replaces the local index with every hash in Redis, returns the number restored. Hashes
+    /// added locally while Redis is read come back at the next load.
+    pub async fn load_from_redis(&self) -> Result<usize, anyhow::Error> {
+        let mut loaded = VideoHashIndex::new();
+        loaded.batch_add(
+            self.store
+                .hashes()
+                .await?
+                .into_iter()
+                .filter_map(|(field, hash_hex)| {
+                    let entry = parse_hash_entry(&field, &hash_hex);
+                    if entry.is_none() {
+                        log::warn!("Skipping invalid video hash entry {}", field);
+                    }
+                    entry
+                }),
+        );
+
+        let restored = loaded.len();
+        *self.index.write().await = loaded;
+
+        Ok(restored)
+    }
+
+    /// Loads right away, then every [`REBUILD_INTERVAL`]. Meant to be spawned, so serving
+    /// doesn't wait on the first read of Redis.
+    pub async fn keep_in_sync(self) {
+        let mut interval = tokio::time::interval(REBUILD_INTERVAL);
+        let mut restored = false;
+        loop {
+            interval.tick().await;
+            match self.load_from_redis().await {
+                Ok(len) if !restored => {
+                    restored = true;
+                    log::info!("Restored {} video hashes from redis", len);
+                }
+                Ok(len) => log::info!("Loaded {} video hashes from redis", len),
+                Err(e) => log::error!("Failed to load video hashes from redis: {}", e),
+            }
+        }
+    }
+
+    /// Writes every hash of the local index to Redis
+    pub async fn flush_to_redis(&self) -> Result<usize, anyhow::Error> {
+        let entries: Vec<(String, String)> = self
+            .index
+            .read()
+            .await
+            .iter()
+            .map(|(id, bits)| (id.to_string(), encode_hash(*bits)))
+            .collect();
+        for chunk in entries.chunks(REDIS_BATCH_SIZE) {
+            self.store.set_hashes(chunk).await?;
+        }
+
+        Ok(entries.len())
+    }
+
+    /// Stores the hash in Redis, then adds it to the local index
+    pub async fn add(&self, id: Uuid, bits: u64) -> Result<(), anyhow::Error> {
+        self.store
+            .set_hashes(&[(id.to_string(), encode_hash(bits))])
+            .await?;
+        self.index.write().await.add(id, bits);
+
+        Ok(())
+    }
+
+    /// Adds the hash of an uploaded video, returns false if the video id is not a uuid or the
+    /// hash is malformed
+    pub async fn add_video_hash(
+        &self,
+        video_id: &str,
+        hash: &VideoHash,
+    ) -> Result<bool, anyhow::Error> {
+        let (Ok(id), Some(bits)) = (Uuid::parse_str(video_id), hash.as_u64()) else {
+            return Ok(false);
+        };
+        self.add(id, bits).await?;
+
+        Ok(true)
+    }
+
+    /// Hash of the video from the local index, or from Redis when another replica added it
+    /// since the last load
+    pub async fn get(&self, id: &Uuid) -> Result<Option<u64>, anyhow::Error> {
+        if let Some(bits) = self.index.read().await.get(id) {
+            return Ok(Some(bits));
+        }
+
+        let field = id.to_string();
+        Ok(self
+            .store
+            .hash(&field)
+            .await?
+            .and_then(|hash_hex| parse_hash_entry(&field, &hash_hex))
+            .map(|(_, bits)| bits))
+    }
+
+    /// Removes the hash from Redis, then from the local index. Returns whether either had it.
+    pub async fn remove(&self, id: &Uuid) -> Result<bool, anyhow::Error> {
+        let stored = self.store.remove_hash(&id.to_string()).await?;
+        let local = self.index.write().await.remove(id).is_some();
+
+        Ok(stored || local)
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::redis_hash_index::{
+    encode_hash, parse_hash_entry, HashStore, RedisBackedVideoHashIndex, REBUILD_INTERVAL,
+};
+use super::video_hash_index::VideoHashIndex;
+use super::videohash::VideoHash;
+
+/// `videohash_index` hash shared by the replicas of a test
+#[derive(Clone, Default)]
+struct MockRedis(Arc<Mutex<HashMap<String, String>>>);
+
+impl HashStore for MockRedis {
+    async fn set_hashes(&self, entries: &[(String, String)]) -> Result<(), anyhow::Error> {
+        self.0.lock().unwrap().extend(entries.iter().cloned());
+        Ok(())
+    }
+
+    async fn hashes(&self) -> Result<Vec<(String, String)>, anyhow::Error> {
+        Ok(self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect())
+    }
+
+    async fn hash(&self, field: &str) -> Result<Option<String>, anyhow::Error> {
+        Ok(self.0.lock().unwrap().get(field).cloned())
+    }
+
+    async fn remove_hash(&self, field: &str) -> Result<bool, anyhow::Error> {
+        Ok(self.0.lock().unwrap().remove(field).is_some())
+    }
+}
+
+fn id(n: u128) -> Uuid {
+    Uuid::from_u128(n)
+}
+
+/// A replica and its local index
+fn replica(
+    redis: &MockRedis,
+) -> (
+    RedisBackedVideoHashIndex<MockRedis>,
+    Arc<RwLock<VideoHashIndex>>,
+) {
+    let index = Arc::new(RwLock::new(VideoHashIndex::new()));
+    (
+        RedisBackedVideoHashIndex::new(redis.clone(), index.clone()),
+        index,
+    )
+}
+
+#[test]
+fn test_hash_entry_round_trip() {
+    assert_eq!(encode_hash(0xff), "00000000000000ff");
+    assert_eq!(
+        parse_hash_entry(&id(1).to_string(), &encode_hash(0xff)),
+        Some((id(1), 0xff))
+    );
+    assert_eq!(parse_hash_entry("not-a-uuid", "00000000000000ff"), None);
+    assert_eq!(parse_hash_entry(&id(1).to_string(), "not hex"), None);
+}
+
+#[tokio::test]
+async fn test_add_is_stored_as_hex() {
+    let redis = MockRedis::default();
+
+    let (replica, index) = replica(&redis);
+    replica.add(id(1), 0xabc).await.unwrap();
+
+    assert_eq!(
+        redis.0.lock().unwrap()["00000000-0000-0000-0000-000000000001"],
+        "0000000000000abc"
+    );
+    assert_eq!(index.read().await.get(&id(1)), Some(0xabc));
+}
+
+#[tokio::test]
+async fn test_new_replica_sees_hashes_of_others() {
+    let redis = MockRedis::default();
+    let (first, _) = replica(&redis);
+    first.add(id(1), 0).await.unwrap();
+    first.add(id(2), u64::MAX).await.unwrap();
+
+    let (second, index) = replica(&redis);
+    assert_eq!(index.read().await.find_nearest_neighbor(1), None);
+    assert_eq!(second.load_from_redis().await.unwrap(), 2);
+
+    assert_eq!(
+        index.read().await.find_nearest_neighbor(1),
+        Some((id(1), 1))
+    );
+    assert_eq!(
+        index.read().await.find_within_distance(u64::MAX, 0),
+        vec![(id(2), 0)]
+    );
+}
+
+#[tokio::test]
+async fn test_load_replaces_the_local_index() {
+    let redis = MockRedis::default();
+    let (first, _) = replica(&redis);
+    let (second, second_index) = replica(&redis);
+
+    first.add(id(1), 0).await.unwrap();
+    first.add(id(2), 0b11).await.unwrap();
+    second.load_from_redis().await.unwrap();
+    assert_eq!(second_index.read().await.len(), 2);
+
+    assert!(first.remove(&id(1)).await.unwrap());
+    assert_eq!(second.load_from_redis().await.unwrap(), 1);
+
+    assert_eq!(second_index.read().await.get(&id(1)), None);
+    assert_eq!(
+        second_index.read().await.find_within_distance(0, 2),
+        vec![(id(2), 2)]
+    );
+}
+
+#[tokio::test]
+async fn test_remove_drops_the_hash_everywhere() {
+    let redis = MockRedis::default();
+    let (replica, index) = replica(&redis);
+    replica.add(id(1), 1).await.unwrap();
+
+    assert!(replica.remove(&id(1)).await.unwrap());
+
+    assert!(redis.0.lock().unwrap().is_empty());
+    assert_eq!(index.read().await.get(&id(1)), None);
+    assert!(!replica.remove(&id(1)).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_get_falls_back_to_redis() {
+    let redis = MockRedis::default();
+    let (first, _) = replica(&redis);
+    let (second, _) = replica(&redis);
+    first.add(id(1), 0xf0).await.unwrap();
+
+    assert_eq!(second.get(&id(1)).await.unwrap(), Some(0xf0));
+    assert_eq!(second.get(&id(2)).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_flush_to_redis_snapshots_the_local_index() {
+    let redis = MockRedis::default();
+    let (first, first_index) = replica(&redis);
+    first_index
+        .write()
+        .await
+        .batch_add((0..2500).map(|n| (id(n), n as u64)));
+
+    assert_eq!(first.flush_to_redis().await.unwrap(), 2500);
+
+    let (other, index) = replica(&redis);
+    assert_eq!(other.load_from_redis().await.unwrap(), 2500);
+    assert_eq!(
+        index.read().await.find_nearest_neighbor(2499),
+        Some((id(2499), 0))
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_entries_are_skipped() {
+    let redis = MockRedis::default();
+    redis
+        .set_hashes(&[
+            (id(1).to_string(), "0000000000000001".into()),
+            ("not-a-uuid".into(), "0000000000000001".into()),
+            (id(2).to_string(), "zz".into()),
+        ])
+        .await
+        .unwrap();
+
+    assert_eq!(replica(&redis).0.load_from_redis().await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_uploaded_video_hash_is_shared() {
+    let redis = MockRedis::default();
+    let (uploader, _) = replica(&redis);
+    let hash = VideoHash {
+        hash: format!("{:064b}", 0xf0u64),
+    };
+
+    assert!(uploader
+        .add_video_hash("0123456789abcdef0123456789abcdef", &hash)
+        .await
+        .unwrap());
+
+    let (other, index) = replica(&redis);
+    other.load_from_redis().await.unwrap();
+    assert_eq!(
+        index
+            .read()
+            .await
+            .get(&Uuid::parse_str("0123456789abcdef0123456789abcdef").unwrap()),
+        Some(0xf0)
+    );
+}
+
+#[tokio::test]
+async fn test_unindexable_video_hash_is_skipped() {
+    let redis = MockRedis::default();
+    let (uploader, _) = replica(&redis);
+    let hash = VideoHash {
+        hash: format!("{:064b}", 1u64),
+    };
+    let malformed = VideoHash { hash: "101".into() };
+
+    assert!(!uploader.add_video_hash("not-a-uuid", &hash).await.unwrap());
+    assert!(!uploader
+        .add_video_hash("0123456789abcdef0123456789abcdef", &malformed)
+        .await
+        .unwrap());
+    assert!(redis.0.lock().unwrap().is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_keep_in_sync_restores_right_away_then_periodically() {
+    let redis = MockRedis::default();
+    let (first, _) = replica(&redis);
+    first.add(id(1), 1).await.unwrap();
+
+    let (second, index) = replica(&redis);
+    let sync = tokio::spawn(second.keep_in_sync());
+    tokio::task::yield_now().await;
+    assert_eq!(index.read().await.len(), 1);
+
+    first.add(id(2), 2).await.unwrap();
+    tokio::time::advance(REBUILD_INTERVAL / 2).await;
+    tokio::task::yield_now().await;
+    assert_eq!(index.read().await.len(), 1);
+
+    tokio::time::advance(REBUILD_INTERVAL / 2).await;
+    tokio::task::yield_now().await;
+    assert_eq!(index.read().await.get(&id(2)), Some(2));
+
+    sync.abort();
+}
@@ -0,0 +1,478 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Write},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+use uuid::Uuid;
+
+use super::videohash::{VideoHash, HASH_SIZE};
+
+/// Number of substrings each 64 bit hash is split into for multi-index hashing
+pub const MIH_CHUNKS: usize = 4;
+/// Bits per substring
+const MIH_CHUNK_BITS: usize = HASH_SIZE / MIH_CHUNKS;
+/// Largest radius served by the MIH tables, larger radii fall back to a linear scan
+pub const MIH_MAX_RADIUS: u32 = (MIH_CHUNKS as u32) * 4 - 1;
+/// Uuid and hash of an entry, the MIH tables and timestamps are not counted
+pub const HASH_ENTRY_BYTES: usize = 16 + 8;
+
+/// Multi-index hashing tables. By the pigeonhole principle, a hash within Hamming distance `r`
+/// of the query matches the query within `r / MIH_CHUNKS` bits on at least one substring.
+#[derive(Debug, Clone, Default)]
+struct MihIndex {
+    tables: [HashMap<u16, Vec<Uuid>>; MIH_CHUNKS],
+}
+
+impl MihIndex {
+    fn build(hashes: &HashMap<Uuid, u64>) -> Self {
+        let mut index = Self::default();
+        for (id, bits) in hashes {
+            index.insert(*id, *bits);
+        }
+        index
+    }
+
+    fn insert(&mut self, id: Uuid, bits: u64) {
+        for (i, table) in self.tables.iter_mut().enumerate() {
+            table.entry(chunk(bits, i)).or_default().push(id);
+        }
+    }
+
+    fn remove(&mut self, id: Uuid, bits: u64) {
+        for (i, table) in self.tables.iter_mut().enumerate() {
+            let key = chunk(bits, i);
+            if let Some(ids) = table.get_mut(&key) {
+                ids.retain(|existing| *existing != id);
+                if ids.is_empty() {
+                    table.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Ids the tables have room for, removals leave the capacity of their buckets allocated
+    fn allocated_slots(&self) -> usize {
+        self.tables
+            .iter()
+            .flat_map(|table| table.values())
+            .map(Vec::capacity)
+            .sum()
+    }
+
+    fn candidates(&self, bits: u64, radius: u32) -> HashSet<Uuid> {
+        let chunk_radius = radius / MIH_CHUNKS as u32;
+        let mut candidates = HashSet::new();
+
+        for (i, table) in self.tables.iter().enumerate() {
+            let query = chunk(bits, i);
+            for key in chunks_within(query, chunk_radius) {
+                if let Some(ids) = table.get(&key) {
+                    candidates.extend(ids.iter().copied());
+                }
+            }
+        }
+
+        candidates
+    }
+}
+
+/// Stamps a change of an index, unique across indexes so a snapshot never matches an index it
+/// was not taken from
+fn next_generation() -> u64 {
+    static GENERATION: AtomicU64 = AtomicU64::new(1);
+    GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The hashes of an index to rebuild its MIH tables from without holding the index
+pub struct MihSnapshot {
+    hashes: HashMap<Uuid, u64>,
+    generation: u64,
+}
+
+impl MihSnapshot {
+    /// CPU bound, run it off the async workers
+    pub fn build(self) -> RebuiltMih {
+        RebuiltMih {
+            mih: MihIndex::build(&self.hashes),
+            hashes: self.hashes,
+            generation: self.generation,
+        }
+    }
+}
+
+/// MIH tables built from a [`MihSnapshot`], see [`VideoHashIndex::install_mih`]
+pub struct RebuiltMih {
+    hashes: HashMap<Uuid, u64>,
+    generation: u64,
+    mih: MihIndex,
+}
+
+/// Share of matching bits between two hashes `distance` bits apart, 100 for identical hashes
+pub fn similarity_pct(distance: u32) -> f64 {
+    (HASH_SIZE as f64 - distance as f64) / HASH_SIZE as f64 * 100.0
+}
+
+fn chunk(bits: u64, i: usize) -> u16 {
+    (bits >> (i * MIH_CHUNK_BITS)) as u16
+}
+
+/// All 16 bit values within `radius` bit flips of `value`
+fn chunks_within(value: u16, radius: u32) -> Vec<u16> {
+    let mut out = vec![value];
+    let mut frontier = vec![(value, 0usize)];
+
+    for _ in 0..radius {
+        let mut next = Vec::new();
+        for (v, start) in frontier {
+            for bit in start..MIH_CHUNK_BITS {
+                let flipped = v ^ (1 << bit);
+                out.push(flipped);
+                next.push((flipped, bit + 1));
+            }
+        }
+        frontier = next;
+    }
+
+    out
+}
+
+/// Row of the CSV export
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRow {
+    uuid: Uuid,
+    hash_hex: String,
+    created_at: DateTime<Utc>,
+}
+
+fn serialize_elapsed_secs<S: Serializer>(
+    instant: &Option<Instant>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    instant
+        .map(|instant| instant.elapsed().as_secs())
+        .serialize(serializer)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoHashIndexStats {
+    pub total_hashes: usize,
+    /// Whether the index was rebuilt from its full source at least once
+    pub index_built: bool,
+    #[serde(
+        rename = "last_rebuild_secs_ago",
+        serialize_with = "serialize_elapsed_secs"
+    )]
+    pub last_rebuild_at: Option<Instant>,
+    #[serde(
+        rename = "last_explicit_rebuild_secs_ago",
+        serialize_with = "serialize_elapsed_secs"
+    )]
+    pub last_explicit_rebuild: Option<Instant>,
+    pub estimated_memory_bytes: usize,
+}
+
+/// In-memory perceptual hash index keyed by video uuid
+#[derive(Debug, Clone, Default)]
+pub struct VideoHashIndex {
+    hashes: HashMap<Uuid, u64>,
+    created_at: HashMap<Uuid, DateTime<Utc>>,
+    mih: MihIndex,
+    last_rebuild_at: Option<Instant>,
+    /// Set by [`VideoHashIndex::install_mih`], the admin triggered rebuild
+    last_explicit_rebuild: Option<Instant>,
+    /// Changes with every add and remove
+    generation: u64,
+}
+
+impl VideoHashIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<u64> {
+        self.hashes.get(id).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Uuid, &u64)> {
+        self.hashes.iter()
+    }
+
+    pub fn get_stats(&self) -> VideoHashIndexStats {
+        VideoHashIndexStats {
+            total_hashes: self.len(),
+            index_built: self.last_rebuild_at.is_some(),
+            last_rebuild_at: self.last_rebuild_at,
+            last_explicit_rebuild: self.last_explicit_rebuild,
+            estimated_memory_bytes: self.len() * HASH_ENTRY_BYTES,
+        }
+    }
+
+    pub fn mih_allocated_slots(&self) -> usize {
+        self.mih.allocated_slots()
+    }
+
+    /// Rebuilds the MIH tables from the hashes, dropping the capacity left by removals
+    pub fn rebuild_mih(&mut self) {
+        let rebuilt = self.mih_snapshot().build();
+        self.install_mih(rebuilt);
+    }
+
+    /// Copy of the hashes, the index stays usable while the tables are built from it
+    pub fn mih_snapshot(&self) -> MihSnapshot {
+        MihSnapshot {
+            hashes: self.hashes.clone(),
+            generation: self.generation,
+        }
+    }
+
+    /// Swaps in tables rebuilt from a snapshot of the index, replaying the adds and removes made
+    /// since the snapshot was taken
+    pub fn install_mih(&mut self, rebuilt: RebuiltMih) {
+        let RebuiltMih {
+            hashes: snapshot,
+            generation,
+            mut mih,
+        } = rebuilt;
+        if generation != self.generation {
+            for (id, bits) in &snapshot {
+                if self.hashes.get(id) != Some(bits) {
+                    mih.remove(*id, *bits);
+                }
+            }
+            for (id, bits) in &self.hashes {
+                if snapshot.get(id) != Some(bits) {
+                    mih.insert(*id, *bits);
+                }
+            }
+        }
+
+        self.mih = mih;
+        let now = Instant::now();
+        self.last_rebuild_at = Some(now);
+        self.last_explicit_rebuild = Some(now);
+    }
+
+    pub fn created_at(&self, id: &Uuid) -> Option<DateTime<Utc>> {
+        self.created_at.get(id).copied()
+    }
+
+    /// Add a hash, replacing any previous hash for the same id
+    pub fn add(&mut self, id: Uuid, bits: u64) {
+        self.add_with_created_at(id, bits, Utc::now());
+    }
+
+    pub fn add_with_created_at(&mut self, id: Uuid, bits: u64, created_at: DateTime<Utc>) {
+        if let Some(old) = self.hashes.insert(id, bits) {
+            self.mih.remove(id, old);
+        }
+        self.mih.insert(id, bits);
+        self.created_at.insert(id, created_at);
+        self.generation = next_generation();
+    }
+
+    pub fn add_video_hash(&mut self, id: Uuid, hash: &VideoHash) -> bool {
+        match hash.as_u64() {
+            Some(bits) => {
+                self.add(id, bits);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn batch_add(&mut self, entries: impl IntoIterator<Item = (Uuid, u64)>) {
+        for (id, bits) in entries {
+            self.add(id, bits);
+        }
+    }
+
+    pub fn remove(&mut self, id: &Uuid) -> Option<u64> {
+        let bits = self.hashes.remove(id)?;
+        self.mih.remove(*id, bits);
+        self.created_at.remove(id);
+        self.generation = next_generation();
+        Some(bits)
+    }
+
+    /// All entries within `max_distance` bits of `bits`, closest first
+    pub fn find_within_distance(&self, bits: u64, max_distance: u32) -> Vec<(Uuid, u32)> {
+        let mut matches: Vec<(Uuid, u32)> = if max_distance <= MIH_MAX_RADIUS {
+            self.mih
+                .candidates(bits, max_distance)
+                .into_iter()
+                .filter_map(|id| {
+                    let distance = (self.hashes[&id] ^ bits).count_ones();
+                    (distance <= max_distance).then_some((id, distance))
+                })
+                .collect()
+        } else {
+            self.hashes
+                .iter()
+                .filter_map(|(id, other)| {
+                    let distance = (other ^ bits).count_ones();
+                    (distance <= max_distance).then_some((*id, distance))
+                })
+                .collect()
+        };
+
+        matches.sort_by_key(|(id, distance)| (*distance, *id));
+        matches
+    }
+
+    /// The `k` entries closest to `hash` as `(uuid, hamming_distance, similarity_pct)`, closest
+    /// first. The MIH radius grows until `k` entries are within it, every entry within a radius
+    /// is found, so the result is exact and only falls back to a linear scan for far neighbors.
+    pub fn top_k_similar(
+        &self,
+        hash: &VideoHash,
+        k: usize,
+    ) -> Result<Vec<(Uuid, u32, f64)>, anyhow::Error> {
+        let bits = hash
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("hash is not {} bits", HASH_SIZE))?;
+        if k == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut radius = MIH_CHUNKS as u32 - 1;
+        let mut matches = loop {
+            let matches = self.find_within_distance(bits, radius);
+            if matches.len() >= k || matches.len() == self.len() {
+                break matches;
+            }
+            if radius + MIH_CHUNKS as u32 > MIH_MAX_RADIUS {
+                break self.find_within_distance(bits, HASH_SIZE as u32);
+            }
+            radius += MIH_CHUNKS as u32;
+        };
+        matches.truncate(k);
+
+        Ok(matches
+            .into_iter()
+            .map(|(id, distance)| (id, distance, similarity_pct(distance)))
+            .collect())
+    }
+
+    /// Closest entry to `bits` and its Hamming distance
+    pub fn find_nearest_neighbor(&self, bits: u64) -> Option<(Uuid, u32)> {
+        let mut radius = 0;
+        while radius <= MIH_MAX_RADIUS {
+            if let Some(nearest) = self.find_within_distance(bits, radius).into_iter().next() {
+                return Some(nearest);
+            }
+            radius += MIH_CHUNKS as u32;
+        }
+
+        self.hashes
+            .iter()
+            .map(|(id, other)| (*id, (other ^ bits).count_ones()))
+            .min_by_key(|(id, distance)| (*distance, *id))
+    }
+
+    /// Combine two shards. Values from `other` win when both contain the same id.
+    /// Counts as a rebuild of the index.
+    pub fn merge(self, other: VideoHashIndex) -> VideoHashIndex {
+        let mut hashes = self.hashes;
+        hashes.extend(other.hashes);
+        let mut created_at = self.created_at;
+        created_at.extend(other.created_at);
+
+        // the per-shard MIH tables are stale for the combined map, rebuild from scratch
+        let mih = MihIndex::build(&hashes);
+
+        VideoHashIndex {
+            hashes,
+            created_at,
+            mih,
+            last_rebuild_at: Some(Instant::now()),
+            last_explicit_rebuild: self.last_explicit_rebuild.max(other.last_explicit_rebuild),
+            generation: next_generation(),
+        }
+    }
+
+    /// Split into two shards with `n` entries in the first one. Entries are ordered by a hash of
+    /// their uuid, so the same id always lands on the same side for a given `n` and a split at
+    /// `len() / 2` gives balanced shards.
+    pub fn split_at(self, n: usize) -> (VideoHashIndex, VideoHashIndex) {
+        let mut created_at = self.created_at;
+        let mut entries: Vec<(Uuid, u64, DateTime<Utc>)> = self
+            .hashes
+            .into_iter()
+            .map(|(id, bits)| {
+                let ts = created_at.remove(&id).unwrap_or_else(Utc::now);
+                (id, bits, ts)
+            })
+            .collect();
+        entries.sort_by_key(|(id, _, _)| (shard_key(id), *id));
+
+        let right = entries.split_off(n.min(entries.len()));
+
+        let mut left_index = VideoHashIndex::new();
+        for (id, bits, ts) in entries {
+            left_index.add_with_created_at(id, bits, ts);
+        }
+        let mut right_index = VideoHashIndex::new();
+        for (id, bits, ts) in right {
+            right_index.add_with_created_at(id, bits, ts);
+        }
+
+        (left_index, right_index)
+    }
+
+    /// Writes `uuid,hash_hex,created_at` rows, ordered by uuid
+    pub fn export_csv<W: Write>(&self, writer: W) -> Result<(), csv::Error> {
+        let mut writer = csv::Writer::from_writer(writer);
+
+        let mut ids: Vec<&Uuid> = self.hashes.keys().collect();
+        ids.sort();
+        for id in ids {
+            writer.serialize(CsvRow {
+                uuid: *id,
+                hash_hex: format!("{:016x}", self.hashes[id]),
+                created_at: self.created_at.get(id).copied().unwrap_or_else(Utc::now),
+            })?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn import_csv<R: Read>(reader: R) -> Result<Self, csv::Error> {
+        let mut reader = csv::Reader::from_reader(reader);
+        let mut index = VideoHashIndex::new();
+
+        for row in reader.deserialize() {
+            let row: CsvRow = row?;
+            let bits = u64::from_str_radix(&row.hash_hex, 16).map_err(|e| {
+                csv::Error::from(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid hash_hex {}: {}", row.hash_hex, e),
+                ))
+            })?;
+            index.add_with_created_at(row.uuid, bits, row.created_at);
+        }
+
+        Ok(index)
+    }
+}
+
+/// Position of a uuid on the shard ring (splitmix64 finalizer over the folded uuid)
+fn shard_key(id: &Uuid) -> u64 {
+    let (hi, lo) = id.as_u64_pair();
+    let mut z = hi ^ lo.rotate_left(32);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
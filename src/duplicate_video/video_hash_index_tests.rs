@@ -0,0 +1,277 @@
+use uuid::Uuid;
+
+use super::video_hash_index::{similarity_pct, VideoHashIndex, HASH_ENTRY_BYTES};
+use super::videohash::VideoHash;
+
+fn id(n: u128) -> Uuid {
+    Uuid::from_u128(n)
+}
+
+#[test]
+fn test_find_within_distance() {
+    let mut index = VideoHashIndex::new();
+    index.add(id(1), 0);
+    index.add(id(2), 0b1111);
+    index.add(id(3), u64::MAX);
+
+    assert_eq!(index.find_within_distance(0, 0), vec![(id(1), 0)]);
+    assert_eq!(
+        index.find_within_distance(0, 4),
+        vec![(id(1), 0), (id(2), 4)]
+    );
+    assert_eq!(index.find_within_distance(0, 64).len(), 3);
+}
+
+#[test]
+fn test_find_nearest_neighbor() {
+    let mut index = VideoHashIndex::new();
+    assert_eq!(index.find_nearest_neighbor(0), None);
+
+    index.add(id(1), u64::MAX);
+    index.add(id(2), 0b111 << 40);
+
+    assert_eq!(index.find_nearest_neighbor(0), Some((id(2), 3)));
+    assert_eq!(index.find_nearest_neighbor(u64::MAX ^ 1), Some((id(1), 1)));
+}
+
+fn video_hash(bits: u64) -> VideoHash {
+    VideoHash {
+        hash: format!("{:064b}", bits),
+    }
+}
+
+/// Hash with the lowest `n` bits set, `n` bits away from 0
+fn ones(n: u32) -> u64 {
+    if n == 64 {
+        u64::MAX
+    } else {
+        (1 << n) - 1
+    }
+}
+
+#[test]
+fn test_top_k_similar_returns_k_closest_in_order() {
+    let mut index = VideoHashIndex::new();
+    for n in [0, 1, 2, 5, 9, 13, 20, 40, 64] {
+        index.add(id(n as u128 + 1), ones(n));
+    }
+
+    let top = index.top_k_similar(&video_hash(0), 5).unwrap();
+
+    assert_eq!(
+        top.iter()
+            .map(|(id, distance, _)| (*id, *distance))
+            .collect::<Vec<_>>(),
+        vec![(id(1), 0), (id(2), 1), (id(3), 2), (id(6), 5), (id(10), 9)]
+    );
+    assert_eq!(top[0].2, 100.0);
+    assert_eq!(top[4].2, similarity_pct(9));
+}
+
+#[test]
+fn test_top_k_similar_reaches_past_the_mih_radius() {
+    let mut index = VideoHashIndex::new();
+    index.add(id(1), ones(40));
+    index.add(id(2), ones(64));
+    index.add(id(3), ones(20));
+
+    let top = index.top_k_similar(&video_hash(0), 2).unwrap();
+
+    assert_eq!(
+        top,
+        vec![
+            (id(3), 20, similarity_pct(20)),
+            (id(1), 40, similarity_pct(40))
+        ]
+    );
+}
+
+#[test]
+fn test_top_k_similar_with_fewer_entries_than_k() {
+    let mut index = VideoHashIndex::new();
+    assert!(index.top_k_similar(&video_hash(0), 5).unwrap().is_empty());
+
+    index.add(id(1), ones(3));
+    index.add(id(2), ones(1));
+
+    let top = index.top_k_similar(&video_hash(0), 5).unwrap();
+    assert_eq!(
+        top.iter().map(|(id, _, _)| *id).collect::<Vec<_>>(),
+        vec![id(2), id(1)]
+    );
+    assert!(index.top_k_similar(&video_hash(0), 0).unwrap().is_empty());
+}
+
+#[test]
+fn test_top_k_similar_matches_a_linear_scan() {
+    let mut index = VideoHashIndex::new();
+    let mut state = 0x9e3779b97f4a7c15u64;
+    for n in 0..500 {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        index.add(id(n), state);
+    }
+    let query = 0x0123_4567_89ab_cdef;
+
+    let top = index.top_k_similar(&video_hash(query), 10).unwrap();
+
+    let mut expected: Vec<(uuid::Uuid, u32)> = index
+        .iter()
+        .map(|(id, bits)| (*id, (bits ^ query).count_ones()))
+        .collect();
+    expected.sort_by_key(|(id, distance)| (*distance, *id));
+    expected.truncate(10);
+    assert_eq!(
+        top.iter()
+            .map(|(id, distance, _)| (*id, *distance))
+            .collect::<Vec<_>>(),
+        expected
+    );
+}
+
+#[test]
+fn test_top_k_similar_rejects_malformed_hash() {
+    let index = VideoHashIndex::new();
+    let hash = VideoHash {
+        hash: "0101".into(),
+    };
+
+    assert!(index.top_k_similar(&hash, 5).is_err());
+}
+
+#[test]
+fn test_merge_then_find_nearest_neighbor() {
+    let mut shard_a = VideoHashIndex::new();
+    shard_a.add(id(1), 0xFFFF_0000_0000_0000);
+    shard_a.add(id(2), 0x0000_0000_0000_00FF);
+
+    let mut shard_b = VideoHashIndex::new();
+    shard_b.add(id(3), 0x0000_FFFF_0000_0000);
+    // collides with shard_a, shard_b wins
+    shard_b.add(id(2), 0x0000_0000_FFFF_0000);
+
+    let merged = shard_a.merge(shard_b);
+
+    assert_eq!(merged.len(), 3);
+    assert_eq!(merged.get(&id(2)), Some(0x0000_0000_FFFF_0000));
+    assert_eq!(
+        merged.find_nearest_neighbor(0xFFFF_0000_0000_0001),
+        Some((id(1), 1))
+    );
+    assert_eq!(
+        merged.find_nearest_neighbor(0x0000_0000_FFFF_0000),
+        Some((id(2), 0))
+    );
+    // the old shard_a value for id(2) must not be reachable anymore
+    assert_eq!(
+        merged.find_within_distance(0x0000_0000_0000_00FF, 0),
+        vec![]
+    );
+}
+
+#[test]
+fn test_split_at_is_balanced_and_stable() {
+    let mut index = VideoHashIndex::new();
+    index.batch_add((0..100u128).map(|n| (id(n), n as u64)));
+
+    let (left, right) = index.clone().split_at(50);
+    assert_eq!(left.len(), 50);
+    assert_eq!(right.len(), 50);
+    assert!(left.iter().all(|(id, _)| right.get(id).is_none()));
+
+    let (left_again, _) = index.clone().split_at(50);
+    assert!(left.iter().all(|(id, _)| left_again.get(id).is_some()));
+
+    let merged = left.merge(right);
+    assert_eq!(merged.len(), 100);
+    assert_eq!(merged.find_nearest_neighbor(42), Some((id(42), 0)));
+}
+
+#[test]
+fn test_csv_round_trip() {
+    use chrono::{TimeZone, Utc};
+
+    let created_at = Utc.with_ymd_and_hms(2025, 1, 31, 12, 0, 0).unwrap();
+    let mut index = VideoHashIndex::new();
+    index.add_with_created_at(id(2), 0xdead_beef, created_at);
+    index.add_with_created_at(id(1), u64::MAX, created_at);
+
+    let mut csv = Vec::new();
+    index.export_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("uuid,hash_hex,created_at"));
+    assert_eq!(
+        lines.next(),
+        Some("00000000-0000-0000-0000-000000000001,ffffffffffffffff,2025-01-31T12:00:00Z")
+    );
+    assert_eq!(
+        lines.next(),
+        Some("00000000-0000-0000-0000-000000000002,00000000deadbeef,2025-01-31T12:00:00Z")
+    );
+
+    let imported = VideoHashIndex::import_csv(csv.as_bytes()).unwrap();
+    assert_eq!(imported.len(), 2);
+    assert_eq!(imported.get(&id(2)), Some(0xdead_beef));
+    assert_eq!(imported.created_at(&id(1)), Some(created_at));
+    assert_eq!(imported.find_nearest_neighbor(u64::MAX), Some((id(1), 0)));
+}
+
+#[test]
+fn test_import_rejects_invalid_hash() {
+    let csv = "uuid,hash_hex,created_at\n\
+               00000000-0000-0000-0000-000000000001,not-hex,2025-01-31T12:00:00Z\n";
+
+    assert!(VideoHashIndex::import_csv(csv.as_bytes()).is_err());
+}
+
+#[test]
+fn test_import_merges_into_existing_index() {
+    let mut existing = VideoHashIndex::new();
+    existing.add(id(1), 1);
+    existing.add(id(2), 2);
+
+    let mut uploaded = VideoHashIndex::new();
+    uploaded.add(id(2), 20);
+    uploaded.add(id(3), 3);
+    let mut csv = Vec::new();
+    uploaded.export_csv(&mut csv).unwrap();
+
+    let merged = existing.merge(VideoHashIndex::import_csv(&csv[..]).unwrap());
+
+    assert_eq!(merged.len(), 3);
+    assert_eq!(merged.get(&id(1)), Some(1));
+    assert_eq!(merged.get(&id(2)), Some(20));
+    assert_eq!(merged.get(&id(3)), Some(3));
+}
+
+#[test]
+fn test_stats_estimate_memory_per_hash() {
+    let mut index = VideoHashIndex::new();
+    assert_eq!(index.get_stats().total_hashes, 0);
+    assert_eq!(index.get_stats().estimated_memory_bytes, 0);
+
+    for n in 0..10 {
+        index.add(id(n), n as u64);
+    }
+    let stats = index.get_stats();
+    assert_eq!(stats.total_hashes, 10);
+    assert_eq!(stats.estimated_memory_bytes, 10 * HASH_ENTRY_BYTES);
+    assert_eq!(HASH_ENTRY_BYTES, 24);
+}
+
+#[test]
+fn test_stats_report_rebuild_after_merge() {
+    let mut index = VideoHashIndex::new();
+    index.add(id(1), 1);
+    let stats = index.get_stats();
+    assert!(!stats.index_built);
+    assert!(stats.last_rebuild_at.is_none());
+
+    let stats = VideoHashIndex::new().merge(index).get_stats();
+    assert!(stats.index_built);
+    assert!(stats.last_rebuild_at.is_some());
+    assert_eq!(stats.total_hashes, 1);
+}
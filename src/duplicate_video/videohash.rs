@@ -2,6 +2,7 @@ use image::imageops::FilterType;
 use image::DynamicImage;
 use log;
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -83,6 +84,45 @@ fn create_ram_temp_dir(prefix: &str) -> Result<PathBuf, Box<dyn Error + Send + S
     Ok(dir_path)
 }
 
+/// Duration of a video file or URL in seconds, read with ffprobe. Blocking.
+pub fn probe_duration_secs(input: &str) -> Option<f32> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+            input,
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()
+        .filter(|duration: &f32| *duration > 0.0)
+}
+
+/// blake3 of the file's bytes in hex. Blocking.
+pub fn content_digest(path: &Path) -> Result<String, std::io::Error> {
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Details of the video file read while hashing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoFileDetails {
+    /// [`content_digest`] of the file, which unlike the hash only matches identical uploads
+    pub content_digest: String,
+    pub duration_secs: Option<f32>,
+}
+
 /// VideoHash represents a perceptual hash of a video
 #[derive(Debug, Clone)]
 pub struct VideoHash {
@@ -90,25 +130,48 @@ pub struct VideoHash {
     pub hash: String,
 }
 
+impl PartialEq for VideoHash {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl Eq for VideoHash {}
+
 impl VideoHash {
     /// Create a new VideoHash from a video file path
     pub async fn new(video_path: &Path) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self::with_duration(video_path).await?.0)
+    }
+
+    /// The hash along with the video's duration in seconds, probed once for both
+    pub async fn with_duration(
+        video_path: &Path,
+    ) -> Result<(Self, Option<f32>), Box<dyn Error + Send + Sync>> {
         let start = Instant::now();
         let video_path = video_path.to_path_buf();
-        let hash = tokio::task::spawn_blocking(move || Self::fast_hash(&video_path)).await??;
+        let (hash, duration_secs) =
+            tokio::task::spawn_blocking(move || Self::fast_hash(&video_path)).await??;
 
         log::info!("Total processing time: {:?}", start.elapsed());
-        Ok(Self { hash })
+        Ok((Self { hash }, duration_secs))
     }
 
     pub async fn from_url(url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self::from_url_with_details(url).await?.0)
+    }
+
+    /// The hash along with the [`VideoFileDetails`] of the downloaded video
+    pub async fn from_url_with_details(
+        url: &str,
+    ) -> Result<(Self, VideoFileDetails), Box<dyn Error + Send + Sync>> {
         log::info!("Generating video hash from URL: {}", url);
 
         if url.starts_with("file://") {
             if let Some(path_str) = url.strip_prefix("file://") {
                 let path = Path::new(path_str);
                 if path.exists() {
-                    return Self::new(path).await;
+                    return Self::with_details(path).await;
                 }
             }
         }
@@ -139,12 +202,30 @@ impl VideoHash {
             return Err("Failed to download video from URL".into());
         }
 
-        let hash = Self::new(&temp_file).await?.hash;
+        Self::with_details(&temp_file).await
+    }
 
-        Ok(Self { hash })
+    async fn with_details(
+        video_path: &Path,
+    ) -> Result<(Self, VideoFileDetails), Box<dyn Error + Send + Sync>> {
+        let digest_path = video_path.to_path_buf();
+        let content_digest =
+            tokio::task::spawn_blocking(move || content_digest(&digest_path)).await??;
+        let (hash, duration_secs) = Self::with_duration(video_path).await?;
+
+        Ok((
+            hash,
+            VideoFileDetails {
+                content_digest,
+                duration_secs,
+            },
+        ))
     }
 
-    pub fn fast_hash(video_path: &Path) -> Result<String, Box<dyn Error + Send + Sync>> {
+    /// The hash along with the video's duration in seconds read while picking the frame rate
+    pub fn fast_hash(
+        video_path: &Path,
+    ) -> Result<(String, Option<f32>), Box<dyn Error + Send + Sync>> {
         let start = Instant::now();
 
         let temp_dir = TempDir::new("videohash")?;
@@ -161,25 +242,8 @@ impl VideoHash {
         // but since fast_hash is already called from spawn_blocking in VideoHash::new,
         // we'll skip adding another spawn_blocking here to avoid nesting.
 
-        let video_path_str = video_path.to_str().unwrap().to_string();
-        let duration_output = Command::new("ffprobe")
-            .args([
-                "-v",
-                "error",
-                "-show_entries",
-                "format=duration",
-                "-of",
-                "default=noprint_wrappers=1:nokey=1",
-                &video_path_str,
-            ])
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::null())
-            .output()?;
-
-        let duration: f32 = String::from_utf8_lossy(&duration_output.stdout)
-            .trim()
-            .parse()
-            .unwrap_or(0.0);
+        let duration_secs = probe_duration_secs(&video_path.to_string_lossy());
+        let duration = duration_secs.unwrap_or(0.0);
 
         let fps = if duration < 3.0 {
             0.8 // Extract a frame every 1.25 seconds for very short videos
@@ -275,17 +339,22 @@ impl VideoHash {
             return Err("Failed to load any frames".into());
         }
 
-        let (wavelet_hash, color_hash) = rayon::join(
-            || Self::calculate_wavelet_hash(&frames),
-            || Self::calculate_color_hash(&frames),
-        );
-
-        let final_hash = Self::xor_hashes(wavelet_hash?, color_hash?);
+        let final_hash = Self::hash_frames(&frames)?;
         log::info!("Hash calculation took {:?}", hash_start.elapsed());
 
         // temp_dir will be automatically cleaned up when it goes out of scope
 
-        Ok(final_hash)
+        Ok((final_hash, duration_secs))
+    }
+
+    /// Hash of the frames sampled from a video
+    pub fn hash_frames(frames: &[DynamicImage]) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let (wavelet_hash, color_hash) = rayon::join(
+            || Self::calculate_wavelet_hash(frames),
+            || Self::calculate_color_hash(frames),
+        );
+
+        Ok(Self::xor_hashes(wavelet_hash?, color_hash?))
     }
 
     pub fn calculate_wavelet_hash(
@@ -480,9 +549,55 @@ impl VideoHash {
         (max_distance - distance) / max_distance * 100.0
     }
 
+    /// Identical hashes, the uploads are the same video
+    pub fn is_exact_duplicate(&self, other: &VideoHash) -> bool {
+        self.hash.len() == other.hash.len() && self.hamming_distance(other) == 0
+    }
+
     /// Determine if two videos are likely duplicates based on threshold
     pub fn is_duplicate(&self, other: &VideoHash, threshold: Option<f64>) -> bool {
         let threshold = threshold.unwrap_or(85.0);
         self.similarity(other) >= threshold
     }
+
+    /// Pack the binary hash string into a u64, None if the hash is malformed
+    pub fn as_u64(&self) -> Option<u64> {
+        if self.hash.len() != HASH_SIZE {
+            return None;
+        }
+        u64::from_str_radix(&self.hash, 2).ok()
+    }
+
+    /// Cross-compare all pairs of hashes and return the pairs whose similarity
+    /// is at or above the threshold (defaults to 85%, same as `is_duplicate`).
+    /// Each pair is reported once, keyed as (earlier, later) in input order.
+    pub fn similarity_matrix(
+        hashes: &[(Uuid, VideoHash)],
+        threshold: Option<f64>,
+    ) -> HashMap<(Uuid, Uuid), f64> {
+        let threshold = threshold.unwrap_or(85.0);
+
+        let packed: Vec<(Uuid, u64)> = hashes
+            .iter()
+            .filter_map(|(id, hash)| match hash.as_u64() {
+                Some(bits) => Some((*id, bits)),
+                None => {
+                    log::warn!("Skipping malformed videohash for {}", id);
+                    None
+                }
+            })
+            .collect();
+
+        packed
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(i, (id_a, bits_a))| {
+                packed[i + 1..].iter().filter_map(move |(id_b, bits_b)| {
+                    let distance = (bits_a ^ bits_b).count_ones() as f64;
+                    let similarity = (HASH_SIZE as f64 - distance) / HASH_SIZE as f64 * 100.0;
+                    (similarity >= threshold).then_some(((*id_a, *id_b), similarity))
+                })
+            })
+            .collect()
+    }
 }
@@ -1,9 +1,11 @@
+use super::benchmarks::{frame_vectors, VideoHashBenchmark};
 use super::videohash::HASH_SIZE;
 use crate::duplicate_video::videohash::VideoHash;
+use proptest::prelude::*;
 use std::fs;
 use std::io::Write;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 fn create_test_video(
     path: &str,
@@ -189,3 +191,293 @@ async fn test_error_handling_invalid_video() -> Result<(), Box<dyn std::error::E
 
     Ok(())
 }
+
+#[test]
+fn test_similarity_matrix() {
+    let id1 = uuid::Uuid::new_v4();
+    let id2 = uuid::Uuid::new_v4();
+    let id3 = uuid::Uuid::new_v4();
+
+    let hashes = vec![
+        (
+            id1,
+            VideoHash {
+                hash: "0".repeat(64),
+            },
+        ),
+        (
+            id2,
+            VideoHash {
+                hash: "1".repeat(4) + &"0".repeat(60),
+            },
+        ),
+        (
+            id3,
+            VideoHash {
+                hash: "1".repeat(64),
+            },
+        ),
+    ];
+
+    let matrix = VideoHash::similarity_matrix(&hashes, None);
+
+    assert_eq!(matrix.len(), 1);
+    assert_eq!(matrix.get(&(id1, id2)), Some(&93.75));
+    assert!(!matrix.contains_key(&(id1, id3)));
+    assert!(!matrix.contains_key(&(id2, id3)));
+
+    let matrix = VideoHash::similarity_matrix(&hashes, Some(0.0));
+    assert_eq!(matrix.len(), 3);
+    assert_eq!(matrix.get(&(id1, id3)), Some(&0.0));
+    assert_eq!(matrix.get(&(id2, id3)), Some(&6.25));
+}
+
+#[test]
+fn test_similarity_matrix_skips_malformed_hashes() {
+    let id1 = uuid::Uuid::new_v4();
+    let id2 = uuid::Uuid::new_v4();
+
+    let hashes = vec![
+        (
+            id1,
+            VideoHash {
+                hash: "0".repeat(64),
+            },
+        ),
+        (
+            id2,
+            VideoHash {
+                hash: "0".repeat(10),
+            },
+        ),
+    ];
+
+    assert!(VideoHash::similarity_matrix(&hashes, Some(0.0)).is_empty());
+}
+
+#[test]
+fn test_videohash_equality() {
+    let a = VideoHash {
+        hash: "01".repeat(32),
+    };
+    let b = VideoHash {
+        hash: "01".repeat(32),
+    };
+    let c = VideoHash {
+        hash: "10".repeat(32),
+    };
+
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+}
+
+#[test]
+fn test_is_exact_duplicate() {
+    let a = VideoHash {
+        hash: "0".repeat(64),
+    };
+    let mut one_bit_off = "0".repeat(63);
+    one_bit_off.push('1');
+    let b = VideoHash { hash: one_bit_off };
+
+    assert!(a.is_exact_duplicate(&a.clone()));
+    assert!(!a.is_exact_duplicate(&b));
+    // near duplicates are still duplicates, just not exact ones
+    assert!(a.is_duplicate(&b, None));
+}
+
+#[test]
+fn test_truncated_hash_is_not_exact_duplicate() {
+    let a = VideoHash {
+        hash: "0".repeat(64),
+    };
+    let b = VideoHash {
+        hash: "0".repeat(32),
+    };
+
+    assert!(!a.is_exact_duplicate(&b));
+}
+
+/// Most bits two hashes can differ in and still be duplicates at the default 85% threshold
+const MAX_DUPLICATE_DISTANCE: usize = 9;
+
+fn hash_of(bits: u64) -> VideoHash {
+    VideoHash {
+        hash: format!("{:064b}", bits),
+    }
+}
+
+fn flip_bits(bits: u64, positions: &[usize]) -> u64 {
+    positions.iter().fold(bits, |bits, pos| bits ^ (1 << pos))
+}
+
+fn bit_positions(len: std::ops::RangeInclusive<usize>) -> impl Strategy<Value = Vec<usize>> {
+    proptest::sample::subsequence((0..HASH_SIZE).collect::<Vec<_>>(), len)
+}
+
+proptest! {
+    #[test]
+    fn test_hash_is_at_distance_zero_of_itself(bits in any::<u64>()) {
+        let hash = hash_of(bits);
+
+        prop_assert_eq!(hash.hamming_distance(&hash), 0);
+        prop_assert!(hash.is_exact_duplicate(&hash));
+        prop_assert_eq!(hash.as_u64(), Some(bits));
+    }
+
+    #[test]
+    fn test_hamming_distance_is_symmetric(a in any::<u64>(), b in any::<u64>()) {
+        let (a, b) = (hash_of(a), hash_of(b));
+
+        prop_assert_eq!(a.hamming_distance(&b), b.hamming_distance(&a));
+        prop_assert_eq!(a.is_duplicate(&b, None), b.is_duplicate(&a, None));
+    }
+
+    #[test]
+    fn test_hamming_distance_counts_differing_bits(a in any::<u64>(), b in any::<u64>()) {
+        prop_assert_eq!(hash_of(a).hamming_distance(&hash_of(b)), (a ^ b).count_ones());
+    }
+
+    #[test]
+    fn test_hashes_within_threshold_are_duplicates(
+        bits in any::<u64>(),
+        flipped in bit_positions(0..=MAX_DUPLICATE_DISTANCE),
+    ) {
+        let hash = hash_of(bits);
+        let other = hash_of(flip_bits(bits, &flipped));
+
+        prop_assert_eq!(hash.hamming_distance(&other) as usize, flipped.len());
+        prop_assert!(hash.is_duplicate(&other, None));
+    }
+
+    #[test]
+    fn test_hashes_past_threshold_are_not_duplicates(
+        bits in any::<u64>(),
+        flipped in bit_positions(MAX_DUPLICATE_DISTANCE + 1..=HASH_SIZE),
+    ) {
+        let hash = hash_of(bits);
+
+        prop_assert!(!hash.is_duplicate(&hash_of(flip_bits(bits, &flipped)), None));
+    }
+}
+
+fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Encodes 6 seconds of an ffmpeg lavfi source, the vectors need no downloaded fixtures
+fn encode_synthetic_video(
+    path: &Path,
+    source: &str,
+    crf: u32,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    fs::create_dir_all(path.parent().unwrap())?;
+
+    let status = Command::new("ffmpeg")
+        .args(["-f", "lavfi", "-i", source, "-t", "6", "-c:v", "libx264"])
+        .args(["-crf", &crf.to_string(), "-pix_fmt", "yuv420p", "-y"])
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+
+    if !status.success() {
+        return Err(format!("Failed to encode {}", source).into());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_synthetic_video_vectors() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !ffmpeg_available() {
+        println!("ffmpeg not found. Skipping test.");
+        return Ok(());
+    }
+
+    let dir = Path::new("target/test_videos/vectors");
+    let original = dir.join("testsrc2.mp4");
+    let reencoded = dir.join("testsrc2_crf35.mp4");
+    let other = dir.join("mandelbrot.mp4");
+    encode_synthetic_video(&original, "testsrc2=size=320x240:rate=25", 18)?;
+    encode_synthetic_video(&reencoded, "testsrc2=size=320x240:rate=25", 35)?;
+    encode_synthetic_video(&other, "mandelbrot=size=320x240:rate=25", 18)?;
+
+    let original_hash = VideoHash::new(&original).await?;
+    let reencoded_hash = VideoHash::new(&reencoded).await?;
+    let other_hash = VideoHash::new(&other).await?;
+
+    assert_eq!(original_hash, VideoHash::new(&original).await?);
+    assert!(
+        original_hash.is_duplicate(&reencoded_hash, None),
+        "A re-encode should stay a duplicate (similarity {}%)",
+        original_hash.similarity(&reencoded_hash)
+    );
+    assert!(
+        !original_hash.is_duplicate(&other_hash, None),
+        "Unrelated videos should not be duplicates (similarity {}%)",
+        original_hash.similarity(&other_hash)
+    );
+
+    fs::remove_dir_all(dir)?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hash_reports_probed_duration() -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+{
+    if !ffmpeg_available() {
+        println!("ffmpeg not found. Skipping test.");
+        return Ok(());
+    }
+
+    let dir = Path::new("target/test_videos/duration");
+    let video = dir.join("testsrc2.mp4");
+    encode_synthetic_video(&video, "testsrc2=size=320x240:rate=25", 23)?;
+
+    let (hash, duration_secs) = VideoHash::with_duration(&video).await?;
+
+    assert_eq!(hash, VideoHash::new(&video).await?);
+    let duration_secs = duration_secs.expect("duration should be probed");
+    assert!((duration_secs - 6.0).abs() < 0.1, "probed {duration_secs}s");
+
+    fs::remove_dir_all(dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_frame_vectors_keep_their_pinned_hashes() {
+    for vector in frame_vectors() {
+        let hash = VideoHash::hash_frames(&vector.frames).unwrap();
+
+        assert_eq!(hash, vector.expected_hash, "vector {}", vector.name);
+    }
+}
+
+/// Times `fast_hash`, run with `cargo test fast_hash_benchmark -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn test_fast_hash_benchmark() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !ffmpeg_available() {
+        println!("ffmpeg not found. Skipping benchmark.");
+        return Ok(());
+    }
+
+    let dir = Path::new("target/test_videos/benchmark");
+    let video = dir.join("testsrc2.mp4");
+    encode_synthetic_video(&video, "testsrc2=size=720x1280:rate=30", 23)?;
+
+    let timings = VideoHashBenchmark { runs: 10 }.run(&video)?;
+    println!("fast_hash over 6s of 720x1280: {:?}", timings);
+
+    fs::remove_dir_all(dir)?;
+
+    Ok(())
+}
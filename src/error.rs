@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, StatusCode},
     response::{IntoResponse, Response},
 };
 use thiserror::Error;
@@ -12,28 +12,98 @@ pub enum Error {
 
 // pub type Result<T, E = Error> = std::result::Result<T, E>;
 
-// Make our own error that wraps `anyhow::Error`.
-#[derive(Debug)]
-pub struct AppError(anyhow::Error);
+/// Header telling QStash not to retry a failed job, sent with [`non_retryable_status`]
+pub const NON_RETRYABLE_HEADER: &str = "Upstash-NonRetryable-Error";
+
+pub fn non_retryable_status() -> StatusCode {
+    StatusCode::from_u16(489).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("canister call failed: {0}")]
+    CanisterCallFailed(String),
+    #[error("bigquery error: {0}")]
+    BigQueryError(anyhow::Error),
+    #[error("storage error: {0}")]
+    StorageError(#[from] cloud_storage::Error),
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("unauthorized")]
+    Unauthorized,
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+    #[error("rate limited, retry after {retry_after_secs}s")]
+    RateLimited { retry_after_secs: u64 },
+    /// A QStash job that must not run again, e.g. an update call whose reply could not be read
+    #[error("not retryable: {0}")]
+    NonRetryable(String),
+    #[error("{0}")]
+    Internal(#[from] anyhow::Error),
+}
+
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::CanisterCallFailed(_) => StatusCode::BAD_GATEWAY,
+            AppError::BigQueryError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::StorageError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::NonRetryable(_) => non_retryable_status(),
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
 // Tell axum how to convert `AppError` into a response.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Something went wrong: {}", self.0),
-        )
-            .into_response()
+        let status = self.status_code();
+
+        if let AppError::RateLimited { retry_after_secs } = &self {
+            return (
+                status,
+                [(RETRY_AFTER, retry_after_secs.to_string())],
+                self.to_string(),
+            )
+                .into_response();
+        }
+
+        if let AppError::NonRetryable(_) = &self {
+            return (status, [(NON_RETRYABLE_HEADER, "true")], self.to_string()).into_response();
+        }
+
+        let body = match status {
+            StatusCode::INTERNAL_SERVER_ERROR => format!("Something went wrong: {}", self),
+            _ => self.to_string(),
+        };
+
+        (status, body).into_response()
     }
 }
 
-// This enables using `?` on functions that return `Result<_, anyhow::Error>` to turn them into
-// `Result<_, AppError>`. That way you don't need to do that manually.
-impl<E> From<E> for AppError
-where
-    E: Into<anyhow::Error>,
-{
-    fn from(err: E) -> Self {
-        Self(err.into())
-    }
+// Errors without a dedicated variant are treated as internal errors so `?` keeps working on
+// `reqwest`, `serde_json`, etc. results inside handlers.
+macro_rules! impl_internal_app_error {
+    ($($err:ty),* $(,)?) => {
+        $(
+            impl From<$err> for AppError {
+                fn from(err: $err) -> Self {
+                    AppError::Internal(err.into())
+                }
+            }
+        )*
+    };
 }
+
+impl_internal_app_error!(
+    reqwest::Error,
+    serde_json::Error,
+    std::io::Error,
+    tokio::task::JoinError,
+    jsonwebtoken::errors::Error,
+    http::header::ToStrError,
+);
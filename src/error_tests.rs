@@ -0,0 +1,58 @@
+use axum::{http::StatusCode, response::IntoResponse};
+
+use crate::error::{AppError, NON_RETRYABLE_HEADER};
+
+#[test]
+fn test_app_error_status_codes() {
+    let cases = vec![
+        (
+            AppError::CanisterCallFailed("timeout".into()),
+            StatusCode::BAD_GATEWAY,
+        ),
+        (
+            AppError::BigQueryError(anyhow::anyhow!("insert failed")),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+        (AppError::NotFound("post".into()), StatusCode::NOT_FOUND),
+        (AppError::Unauthorized, StatusCode::UNAUTHORIZED),
+        (
+            AppError::InvalidInput("bad principal".into()),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            AppError::RateLimited {
+                retry_after_secs: 30,
+            },
+            StatusCode::TOO_MANY_REQUESTS,
+        ),
+        (
+            AppError::NonRetryable("undecodable reply".into()),
+            StatusCode::from_u16(489).unwrap(),
+        ),
+        (
+            AppError::from(anyhow::anyhow!("boom")),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    ];
+
+    for (err, expected) in cases {
+        assert_eq!(err.into_response().status(), expected);
+    }
+}
+
+#[test]
+fn test_rate_limited_sets_retry_after() {
+    let res = AppError::RateLimited {
+        retry_after_secs: 30,
+    }
+    .into_response();
+
+    assert_eq!(res.headers().get(http::header::RETRY_AFTER).unwrap(), "30");
+}
+
+#[test]
+fn test_non_retryable_tells_qstash_to_stop() {
+    let res = AppError::NonRetryable("undecodable reply".into()).into_response();
+
+    assert_eq!(res.headers().get(NON_RETRYABLE_HEADER).unwrap(), "true");
+}
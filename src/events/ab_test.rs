@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use candid::Principal;
+#[cfg(not(feature = "local-bin"))]
+use google_cloud_bigquery::http::tabledata::insert_all::{InsertAllRequest, Row};
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[cfg(not(feature = "local-bin"))]
+use crate::types::RedisPool;
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_events, AuthBearer},
+    AppError,
+};
+
+/// Redis set of the experiments events can be attributed to
+pub const ACTIVE_EXPERIMENTS_KEY: &str = "experiments:active";
+pub const AB_TEST_EVENTS_TABLE: &str = "ab_test_events";
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct AttributeEventRequest {
+    pub experiment_id: String,
+    pub variant: String,
+    #[schema(value_type = String)]
+    pub user_id: Principal,
+    pub event_name: String,
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub params: Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct AttributeEventResponse {
+    /// Insert id of the BigQuery row
+    pub row_id: String,
+}
+
+/// Row of `yral_ds.ab_test_events`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AbTestEventRow {
+    pub row_id: String,
+    pub experiment_id: String,
+    pub variant: String,
+    pub user_id: String,
+    pub event: String,
+    pub params: String,
+    pub timestamp: String,
+}
+
+pub fn ab_test_event_row(
+    req: AttributeEventRequest,
+    row_id: String,
+    timestamp: String,
+) -> AbTestEventRow {
+    AbTestEventRow {
+        row_id,
+        experiment_id: req.experiment_id,
+        variant: req.variant,
+        user_id: req.user_id.to_text(),
+        event: req.event_name,
+        params: req.params.to_string(),
+        timestamp,
+    }
+}
+
+pub(crate) trait ExperimentWhitelist {
+    async fn is_active(&self, experiment_id: &str) -> Result<bool, anyhow::Error>;
+}
+
+pub(crate) trait AbTestEventSink {
+    async fn insert(&self, row: AbTestEventRow) -> Result<(), anyhow::Error>;
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl ExperimentWhitelist for RedisPool {
+    async fn is_active(&self, experiment_id: &str) -> Result<bool, anyhow::Error> {
+        let mut conn = self.get().await?;
+        Ok(conn
+            .sismember(ACTIVE_EXPERIMENTS_KEY, experiment_id)
+            .await?)
+    }
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl AbTestEventSink for google_cloud_bigquery::client::Client {
+    async fn insert(&self, row: AbTestEventRow) -> Result<(), anyhow::Error> {
+        let request = InsertAllRequest {
+            rows: vec![Row {
+                insert_id: Some(row.row_id.clone()),
+                json: row,
+            }],
+            ..Default::default()
+        };
+
+        let res = self
+            .tabledata()
+            .insert(
+                "hot-or-not-feed-intelligence",
+                "yral_ds",
+                AB_TEST_EVENTS_TABLE,
+                &request,
+            )
+            .await?;
+
+        if let Some(errors) = res.insert_errors {
+            if !errors.is_empty() {
+                log::error!("ab_test_events insert response : {:?}", errors);
+                return Err(anyhow::anyhow!(
+                    "Failed to insert ab_test_events row to bigquery"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Streams the event with its experiment metadata, only for experiments in the whitelist
+pub async fn attribute_event(
+    whitelist: &impl ExperimentWhitelist,
+    sink: &impl AbTestEventSink,
+    req: AttributeEventRequest,
+) -> Result<AttributeEventResponse, AppError> {
+    for (field, value) in [
+        ("experiment_id", &req.experiment_id),
+        ("variant", &req.variant),
+        ("event_name", &req.event_name),
+    ] {
+        if value.trim().is_empty() {
+            return Err(AppError::InvalidInput(format!("{} is required", field)));
+        }
+    }
+
+    if !whitelist.is_active(&req.experiment_id).await? {
+        return Err(AppError::InvalidInput(format!(
+            "experiment {} is not active",
+            req.experiment_id
+        )));
+    }
+
+    let row_id = Uuid::new_v4().to_string();
+    let row = ab_test_event_row(req, row_id.clone(), chrono::Utc::now().to_rfc3339());
+    sink.insert(row).await.map_err(AppError::BigQueryError)?;
+
+    Ok(AttributeEventResponse { row_id })
+}
+
+#[utoipa::path(
+    post,
+    path = "/attribute",
+    request_body = AttributeEventRequest,
+    tag = "events",
+    responses(
+        (status = 200, description = "Event attributed", body = AttributeEventResponse),
+        (status = 400, description = "Missing field or inactive experiment"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state, token))]
+pub async fn attribute_ab_test_event(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Json(req): Json<AttributeEventRequest>,
+) -> Result<Json<AttributeEventResponse>, AppError> {
+    check_auth_events(Some(token)).map_err(|_| AppError::Unauthorized)?;
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let res = attribute_event(
+            &state.canister_backup_redis_pool,
+            &state.bigquery_client,
+            req,
+        )
+        .await?;
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, req);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
@@ -0,0 +1,112 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use candid::Principal;
+use serde_json::json;
+
+use super::ab_test::{
+    ab_test_event_row, attribute_event, AbTestEventRow, AbTestEventSink, AttributeEventRequest,
+    ExperimentWhitelist,
+};
+use crate::AppError;
+
+struct MockWhitelist(HashSet<&'static str>);
+
+impl ExperimentWhitelist for MockWhitelist {
+    async fn is_active(&self, experiment_id: &str) -> Result<bool, anyhow::Error> {
+        Ok(self.0.contains(experiment_id))
+    }
+}
+
+#[derive(Default)]
+struct MockSink(Mutex<Vec<AbTestEventRow>>);
+
+impl AbTestEventSink for MockSink {
+    async fn insert(&self, row: AbTestEventRow) -> Result<(), anyhow::Error> {
+        self.0.lock().unwrap().push(row);
+        Ok(())
+    }
+}
+
+fn whitelist() -> MockWhitelist {
+    MockWhitelist(HashSet::from(["feed-ranking-v2"]))
+}
+
+fn request(experiment_id: &str) -> AttributeEventRequest {
+    AttributeEventRequest {
+        experiment_id: experiment_id.into(),
+        variant: "treatment".into(),
+        user_id: Principal::from_text("2vxsx-fae").unwrap(),
+        event_name: "video_duration_watched".into(),
+        params: json!({ "video_id": "abc", "percentage_watched": 80 }),
+    }
+}
+
+#[tokio::test]
+async fn test_active_experiment_is_attributed() {
+    let sink = MockSink::default();
+
+    let res = attribute_event(&whitelist(), &sink, request("feed-ranking-v2"))
+        .await
+        .unwrap();
+
+    let rows = sink.0.lock().unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].row_id, res.row_id);
+    assert_eq!(rows[0].experiment_id, "feed-ranking-v2");
+    assert_eq!(rows[0].variant, "treatment");
+    assert_eq!(rows[0].event, "video_duration_watched");
+}
+
+#[tokio::test]
+async fn test_inactive_experiment_is_rejected() {
+    let sink = MockSink::default();
+
+    let res = attribute_event(&whitelist(), &sink, request("old-experiment")).await;
+
+    assert!(matches!(res, Err(AppError::InvalidInput(_))));
+    assert!(sink.0.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_empty_fields_are_rejected_before_whitelist() {
+    let sink = MockSink::default();
+    let mut req = request("feed-ranking-v2");
+    req.variant = " ".into();
+
+    let res = attribute_event(&whitelist(), &sink, req).await;
+
+    assert!(matches!(res, Err(AppError::InvalidInput(msg)) if msg.contains("variant")));
+    assert!(sink.0.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_row_includes_experiment_metadata() {
+    let row = ab_test_event_row(
+        request("feed-ranking-v2"),
+        "row-1".into(),
+        "2025-01-01T00:00:00Z".into(),
+    );
+
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&row.params).unwrap(),
+        json!({ "video_id": "abc", "percentage_watched": 80 })
+    );
+    assert_eq!(
+        (
+            row.row_id.as_str(),
+            row.experiment_id.as_str(),
+            row.variant.as_str(),
+            row.user_id.as_str(),
+            row.event.as_str(),
+            row.timestamp.as_str(),
+        ),
+        (
+            "row-1",
+            "feed-ranking-v2",
+            "treatment",
+            "2vxsx-fae",
+            "video_duration_watched",
+            "2025-01-01T00:00:00Z",
+        )
+    );
+}
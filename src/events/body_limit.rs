@@ -0,0 +1,62 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{header::CONTENT_LENGTH, HeaderMap, StatusCode};
+use tonic::Status;
+
+/// Limit of a single event, `params` included
+pub const EVENT_BODY_LIMIT: usize = 64 * 1024;
+pub const BULK_EVENT_BODY_LIMIT: usize = 1024 * 1024;
+
+fn content_length(headers: &HeaderMap) -> Option<usize> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+pub fn payload_too_large(limit: usize) -> Response {
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("Request body exceeds the limit of {} bytes", limit),
+    )
+        .into_response()
+}
+
+/// Rejects bodies over `limit` bytes before any handler or middleware buffers them.
+/// The declared `Content-Length` is checked first, bodies without one are read up to the limit.
+pub async fn limit_request_body(
+    State(limit): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if content_length(request.headers()).is_some_and(|len| len > limit) {
+        return payload_too_large(limit);
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, limit).await else {
+        return payload_too_large(limit);
+    };
+
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
+
+/// gRPC interceptor rejecting events whose declared `content-length` is over [`EVENT_BODY_LIMIT`].
+/// Messages without it are bounded by the server's max decoding message size.
+pub fn check_grpc_content_length(req: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+    let content_length = req
+        .metadata()
+        .get("content-length")
+        .and_then(|len| len.to_str().ok())
+        .and_then(|len| len.parse::<usize>().ok());
+
+    match content_length {
+        Some(len) if len > EVENT_BODY_LIMIT => Err(Status::out_of_range(format!(
+            "Request body exceeds the limit of {} bytes",
+            EVENT_BODY_LIMIT
+        ))),
+        _ => Ok(req),
+    }
+}
@@ -0,0 +1,105 @@
+use axum::{body::Body, middleware, routing::post, Router};
+use http::{header::CONTENT_LENGTH, Request, StatusCode};
+use tonic::{metadata::MetadataValue, Code};
+use tower::ServiceExt;
+
+use super::body_limit::{
+    check_grpc_content_length, limit_request_body, BULK_EVENT_BODY_LIMIT, EVENT_BODY_LIMIT,
+};
+
+/// Echoes the size of the body it received
+fn router(limit: usize) -> Router {
+    Router::new()
+        .route(
+            "/",
+            post(|body: String| async move { body.len().to_string() }),
+        )
+        .layer(middleware::from_fn_with_state(limit, limit_request_body))
+}
+
+async fn send(limit: usize, request: Request<Body>) -> (StatusCode, String) {
+    let response = router(limit).oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+fn request(body: Vec<u8>) -> Request<Body> {
+    Request::post("/")
+        .header(CONTENT_LENGTH, body.len())
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_body_at_limit_is_accepted() {
+    let (status, body) = send(EVENT_BODY_LIMIT, request(vec![b'a'; EVENT_BODY_LIMIT])).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, EVENT_BODY_LIMIT.to_string());
+}
+
+#[tokio::test]
+async fn test_body_over_limit_is_rejected() {
+    let (status, body) = send(EVENT_BODY_LIMIT, request(vec![b'a'; EVENT_BODY_LIMIT + 1])).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body, "Request body exceeds the limit of 65536 bytes");
+}
+
+#[tokio::test]
+async fn test_bulk_limit_allows_larger_bodies() {
+    let body = vec![b'a'; EVENT_BODY_LIMIT * 2];
+
+    let (status, _) = send(BULK_EVENT_BODY_LIMIT, request(body.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(EVENT_BODY_LIMIT, request(body)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_understated_content_length_is_rejected() {
+    // the declared length is checked first, the body itself is still read up to the limit
+    let request = Request::post("/")
+        .header(CONTENT_LENGTH, 10)
+        .body(Body::from(vec![b'a'; EVENT_BODY_LIMIT + 1]))
+        .unwrap();
+
+    let (status, _) = send(EVENT_BODY_LIMIT, request).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_declared_content_length_is_rejected_before_reading() {
+    let request = Request::post("/")
+        .header(CONTENT_LENGTH, 100 * 1024 * 1024)
+        .body(Body::empty())
+        .unwrap();
+
+    let (status, _) = send(EVENT_BODY_LIMIT, request).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+fn grpc_request(content_length: Option<usize>) -> tonic::Request<()> {
+    let mut req = tonic::Request::new(());
+    if let Some(len) = content_length {
+        req.metadata_mut()
+            .insert("content-length", MetadataValue::from(len as u64));
+    }
+    req
+}
+
+#[test]
+fn test_grpc_content_length() {
+    assert!(check_grpc_content_length(grpc_request(None)).is_ok());
+    assert!(check_grpc_content_length(grpc_request(Some(EVENT_BODY_LIMIT))).is_ok());
+
+    let err = check_grpc_content_length(grpc_request(Some(EVENT_BODY_LIMIT + 1))).unwrap_err();
+    assert_eq!(err.code(), Code::OutOfRange);
+}
@@ -0,0 +1,339 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{extract::State, Json};
+use google_cloud_bigquery::http::{
+    job::query::QueryRequest,
+    query::{QueryParameter, QueryParameterType, QueryParameterValue},
+};
+#[cfg(not(feature = "local-bin"))]
+use google_cloud_bigquery::query::row::Row as QueryRow;
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::instrument;
+#[cfg(not(feature = "local-bin"))]
+use yral_ml_feed_cache::consts::{USER_WATCH_HISTORY_CLEAN_SUFFIX, USER_WATCH_HISTORY_NSFW_SUFFIX};
+
+use crate::{app_state::AppState, AppError};
+#[cfg(not(feature = "local-bin"))]
+use crate::{consts::GOOGLE_CHAT_REPORT_SPACE_URL, offchain_service::send_message_gchat};
+
+pub const CONSISTENCY_CHECK_CRON: &str = "0 */6 * * *";
+pub const CONSISTENCY_CHECK_SCHEDULE_ID: &str = "ml-cache-consistency-check";
+/// Days of `video_duration_watched` events compared with the ML cache
+pub const CONSISTENCY_WINDOW_DAYS: u32 = 30;
+pub const CONSISTENCY_SAMPLE_SIZE: u32 = 100;
+/// Relative difference between BigQuery and the ML cache above which the check alerts
+pub const DISCREPANCY_ALERT_THRESHOLD: f64 = 0.10;
+
+/// Users with a `video_duration_watched` event in the last day, picked at random
+pub const CONSISTENCY_SAMPLE_QUERY: &str = "SELECT canister_id FROM (
+        SELECT DISTINCT JSON_EXTRACT_SCALAR(params, '$.canister_id') AS canister_id
+        FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+        WHERE event = 'video_duration_watched'
+            AND timestamp >= TIMESTAMP_SUB(CURRENT_TIMESTAMP(), INTERVAL 1 DAY)
+    )
+    WHERE canister_id IS NOT NULL
+    ORDER BY RAND()
+    LIMIT @sample_size";
+
+pub const WATCH_EVENT_COUNTS_QUERY: &str = "SELECT
+        JSON_EXTRACT_SCALAR(params, '$.canister_id') AS canister_id,
+        COUNT(*) AS watched
+    FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+    WHERE event = 'video_duration_watched'
+        AND timestamp >= TIMESTAMP_SUB(CURRENT_TIMESTAMP(), INTERVAL @window_days DAY)
+        AND JSON_EXTRACT_SCALAR(params, '$.canister_id') IN UNNEST(@canister_ids)
+    GROUP BY canister_id";
+
+fn parameter_type(parameter_type: &str) -> QueryParameterType {
+    QueryParameterType {
+        parameter_type: parameter_type.to_string(),
+        ..Default::default()
+    }
+}
+
+fn parameter_value(value: String) -> QueryParameterValue {
+    QueryParameterValue {
+        value: Some(value),
+        ..Default::default()
+    }
+}
+
+pub fn consistency_sample_request(sample_size: u32) -> QueryRequest {
+    QueryRequest {
+        query: CONSISTENCY_SAMPLE_QUERY.to_string(),
+        parameter_mode: Some("NAMED".to_string()),
+        query_parameters: vec![QueryParameter {
+            name: Some("sample_size".to_string()),
+            parameter_type: parameter_type("INT64"),
+            parameter_value: parameter_value(sample_size.to_string()),
+        }],
+        ..Default::default()
+    }
+}
+
+/// The canister ids are passed as an `ARRAY<STRING>` parameter, never formatted into the query
+pub fn watch_event_counts_request(canister_ids: &[String], window_days: u32) -> QueryRequest {
+    QueryRequest {
+        query: WATCH_EVENT_COUNTS_QUERY.to_string(),
+        parameter_mode: Some("NAMED".to_string()),
+        query_parameters: vec![
+            QueryParameter {
+                name: Some("canister_ids".to_string()),
+                parameter_type: QueryParameterType {
+                    parameter_type: "ARRAY".to_string(),
+                    array_type: Some(Box::new(parameter_type("STRING"))),
+                    ..Default::default()
+                },
+                parameter_value: QueryParameterValue {
+                    array_values: Some(canister_ids.iter().cloned().map(parameter_value).collect()),
+                    ..Default::default()
+                },
+            },
+            QueryParameter {
+                name: Some("window_days".to_string()),
+                parameter_type: parameter_type("INT64"),
+                parameter_value: parameter_value(window_days.to_string()),
+            },
+        ],
+        ..Default::default()
+    }
+}
+
+/// Difference relative to the larger of the two counts, 0 when both are empty
+pub fn discrepancy(bigquery_count: u64, cache_count: u64) -> f64 {
+    let max = bigquery_count.max(cache_count);
+    if max == 0 {
+        return 0.0;
+    }
+
+    bigquery_count.abs_diff(cache_count) as f64 / max as f64
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UserConsistency {
+    pub canister_id: String,
+    pub bigquery_count: u64,
+    pub cache_count: u64,
+    pub discrepancy: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ConsistencyReport {
+    /// Most inconsistent users first
+    pub users: Vec<UserConsistency>,
+    pub bigquery_total: u64,
+    pub cache_total: u64,
+}
+
+impl ConsistencyReport {
+    /// Users missing from either side count as 0 there
+    pub fn new(
+        sample: &[String],
+        bigquery: &HashMap<String, u64>,
+        cache: &HashMap<String, u64>,
+    ) -> Self {
+        let mut users: Vec<UserConsistency> = sample
+            .iter()
+            .map(|canister_id| {
+                let bigquery_count = bigquery.get(canister_id).copied().unwrap_or_default();
+                let cache_count = cache.get(canister_id).copied().unwrap_or_default();
+                UserConsistency {
+                    canister_id: canister_id.clone(),
+                    bigquery_count,
+                    cache_count,
+                    discrepancy: discrepancy(bigquery_count, cache_count),
+                }
+            })
+            .collect();
+        users.sort_by(|a, b| b.discrepancy.total_cmp(&a.discrepancy));
+
+        Self {
+            bigquery_total: users.iter().map(|user| user.bigquery_count).sum(),
+            cache_total: users.iter().map(|user| user.cache_count).sum(),
+            users,
+        }
+    }
+
+    pub fn discrepancy(&self) -> f64 {
+        discrepancy(self.bigquery_total, self.cache_total)
+    }
+
+    pub fn needs_alert(&self) -> bool {
+        self.discrepancy() > DISCREPANCY_ALERT_THRESHOLD
+    }
+
+    pub fn inconsistent_users(&self) -> impl Iterator<Item = &UserConsistency> {
+        self.users
+            .iter()
+            .filter(|user| user.discrepancy > DISCREPANCY_ALERT_THRESHOLD)
+    }
+}
+
+/// Google Chat message listing the totals and the worst users
+pub fn consistency_alert_message(report: &ConsistencyReport, max_users: usize) -> Value {
+    let mut text = format!(
+        "🚨 ML cache consistency check: {:.1}% discrepancy over {} sampled users\nBigQuery video_duration_watched events: {}\nML cache watch history items: {}",
+        report.discrepancy() * 100.0,
+        report.users.len(),
+        report.bigquery_total,
+        report.cache_total,
+    );
+    for user in report.inconsistent_users().take(max_users) {
+        text.push_str(&format!(
+            "\n{}: bigquery {} cache {} ({:.1}%)",
+            user.canister_id,
+            user.bigquery_count,
+            user.cache_count,
+            user.discrepancy * 100.0
+        ));
+    }
+
+    json!({ "text": text })
+}
+
+/// Compares the `video_duration_watched` events in BigQuery of a sample of users with the
+/// size of their watch history in the ML cache
+#[cfg(not(feature = "local-bin"))]
+pub struct ConsistencyChecker {
+    app_state: Arc<AppState>,
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl ConsistencyChecker {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+
+    async fn sample(&self) -> Result<Vec<String>, anyhow::Error> {
+        let mut response = self
+            .app_state
+            .bigquery_client
+            .query::<QueryRow>(
+                "hot-or-not-feed-intelligence",
+                consistency_sample_request(CONSISTENCY_SAMPLE_SIZE),
+            )
+            .await?;
+
+        let mut sample = Vec::new();
+        while let Some(row) = response.next().await? {
+            if let Some(canister_id) = row.column::<Option<String>>(0)? {
+                sample.push(canister_id);
+            }
+        }
+
+        Ok(sample)
+    }
+
+    async fn bigquery_counts(
+        &self,
+        sample: &[String],
+    ) -> Result<HashMap<String, u64>, anyhow::Error> {
+        let mut response = self
+            .app_state
+            .bigquery_client
+            .query::<QueryRow>(
+                "hot-or-not-feed-intelligence",
+                watch_event_counts_request(sample, CONSISTENCY_WINDOW_DAYS),
+            )
+            .await?;
+
+        let mut counts = HashMap::new();
+        while let Some(row) = response.next().await? {
+            let canister_id: Option<String> = row.column(0)?;
+            let watched: i64 = row.column(1)?;
+            if let Some(canister_id) = canister_id {
+                counts.insert(canister_id, watched.max(0) as u64);
+            }
+        }
+
+        Ok(counts)
+    }
+
+    async fn cache_counts(&self, sample: &[String]) -> Result<HashMap<String, u64>, anyhow::Error> {
+        let mut conn = self.app_state.ml_feed_cache.redis_pool.get().await?;
+
+        let mut counts = HashMap::new();
+        for canister_id in sample {
+            let clean: u64 = conn
+                .llen(format!(
+                    "{}{}",
+                    canister_id, USER_WATCH_HISTORY_CLEAN_SUFFIX
+                ))
+                .await?;
+            let nsfw: u64 = conn
+                .llen(format!("{}{}", canister_id, USER_WATCH_HISTORY_NSFW_SUFFIX))
+                .await?;
+            counts.insert(canister_id.clone(), clean + nsfw);
+        }
+
+        Ok(counts)
+    }
+
+    pub async fn check(&self) -> Result<ConsistencyReport, anyhow::Error> {
+        let sample = self.sample().await?;
+        if sample.is_empty() {
+            log::info!("ML cache consistency check: no users to sample");
+            return Ok(ConsistencyReport::new(
+                &[],
+                &HashMap::new(),
+                &HashMap::new(),
+            ));
+        }
+
+        let bigquery = self.bigquery_counts(&sample).await?;
+        let cache = self.cache_counts(&sample).await?;
+        let report = ConsistencyReport::new(&sample, &bigquery, &cache);
+
+        for user in report.inconsistent_users() {
+            log::warn!(
+                "ML cache discrepancy for {}: bigquery {} cache {} ({:.1}%)",
+                user.canister_id,
+                user.bigquery_count,
+                user.cache_count,
+                user.discrepancy * 100.0
+            );
+        }
+        log::info!(
+            "ML cache consistency check: {} users, bigquery {} cache {} ({:.1}%)",
+            report.users.len(),
+            report.bigquery_total,
+            report.cache_total,
+            report.discrepancy() * 100.0
+        );
+
+        if report.needs_alert() {
+            send_message_gchat(
+                GOOGLE_CHAT_REPORT_SPACE_URL,
+                consistency_alert_message(&report, 10),
+            )
+            .await?;
+        }
+
+        Ok(report)
+    }
+}
+
+/// Runs the ML cache consistency check, scheduled every 6 hours so only one replica alerts
+#[instrument(skip(state))]
+pub async fn ml_cache_consistency_check(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ConsistencyReport>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let report = ConsistencyChecker::new(state).check().await?;
+        Ok(Json(report))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Ok(Json(ConsistencyReport::new(
+            &[],
+            &HashMap::new(),
+            &HashMap::new(),
+        )))
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use super::consistency_check::{
+    consistency_alert_message, discrepancy, watch_event_counts_request, ConsistencyReport,
+    CONSISTENCY_WINDOW_DAYS,
+};
+
+fn counts(entries: &[(&str, u64)]) -> HashMap<String, u64> {
+    entries
+        .iter()
+        .map(|(canister_id, count)| (canister_id.to_string(), *count))
+        .collect()
+}
+
+fn sample(canister_ids: &[&str]) -> Vec<String> {
+    canister_ids.iter().map(|id| id.to_string()).collect()
+}
+
+#[test]
+fn test_discrepancy_is_relative_to_larger_count() {
+    assert_eq!(discrepancy(0, 0), 0.0);
+    assert_eq!(discrepancy(100, 100), 0.0);
+    assert_eq!(discrepancy(100, 90), 0.1);
+    assert_eq!(discrepancy(90, 100), 0.1);
+    assert_eq!(discrepancy(10, 0), 1.0);
+}
+
+#[test]
+fn test_report_counts_missing_users_as_zero() {
+    let report = ConsistencyReport::new(
+        &sample(&["a", "b", "c"]),
+        &counts(&[("a", 10), ("b", 5)]),
+        &counts(&[("a", 10), ("c", 3)]),
+    );
+
+    assert_eq!(report.bigquery_total, 15);
+    assert_eq!(report.cache_total, 13);
+    let by_user: Vec<_> = report
+        .users
+        .iter()
+        .map(|user| {
+            (
+                user.canister_id.as_str(),
+                user.bigquery_count,
+                user.cache_count,
+            )
+        })
+        .collect();
+    assert!(by_user.contains(&("b", 5, 0)));
+    assert!(by_user.contains(&("c", 0, 3)));
+    assert_eq!(report.users.last().unwrap().canister_id, "a");
+}
+
+#[test]
+fn test_alert_only_above_threshold() {
+    let at_threshold = ConsistencyReport::new(
+        &sample(&["a"]),
+        &counts(&[("a", 100)]),
+        &counts(&[("a", 90)]),
+    );
+    assert!(!at_threshold.needs_alert());
+
+    let above = ConsistencyReport::new(
+        &sample(&["a"]),
+        &counts(&[("a", 100)]),
+        &counts(&[("a", 89)]),
+    );
+    assert!(above.needs_alert());
+}
+
+#[test]
+fn test_total_discrepancy_drives_alert_not_single_users() {
+    let report = ConsistencyReport::new(
+        &sample(&["a", "b"]),
+        &counts(&[("a", 1000), ("b", 2)]),
+        &counts(&[("a", 1000), ("b", 1)]),
+    );
+
+    assert!(!report.needs_alert());
+    let inconsistent: Vec<_> = report
+        .inconsistent_users()
+        .map(|user| user.canister_id.as_str())
+        .collect();
+    assert_eq!(inconsistent, vec!["b"]);
+}
+
+#[test]
+fn test_alert_message_lists_inconsistent_users() {
+    let report = ConsistencyReport::new(
+        &sample(&["a", "b", "c"]),
+        &counts(&[("a", 10), ("b", 10), ("c", 10)]),
+        &counts(&[("a", 0), ("b", 5), ("c", 10)]),
+    );
+
+    let text = consistency_alert_message(&report, 1)["text"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(text.contains("50.0% discrepancy over 3 sampled users"));
+    assert!(text.contains("a: bigquery 10 cache 0 (100.0%)"));
+    assert!(!text.contains("b: bigquery"));
+    assert!(!text.contains("c: bigquery"));
+}
+
+#[test]
+fn test_counts_request_passes_canister_ids_as_array_parameter() {
+    let request = watch_event_counts_request(&sample(&["a", "b"]), CONSISTENCY_WINDOW_DAYS);
+
+    assert_eq!(request.parameter_mode.as_deref(), Some("NAMED"));
+    assert!(request.query.contains("IN UNNEST(@canister_ids)"));
+    assert!(request.query.contains("INTERVAL @window_days DAY"));
+
+    let parameter = &request.query_parameters[0];
+    assert_eq!(parameter.name.as_deref(), Some("canister_ids"));
+    assert_eq!(parameter.parameter_type.parameter_type, "ARRAY");
+    assert_eq!(
+        parameter
+            .parameter_type
+            .array_type
+            .as_ref()
+            .unwrap()
+            .parameter_type,
+        "STRING"
+    );
+    let values: Vec<_> = parameter
+        .parameter_value
+        .array_values
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|value| value.value.as_deref().unwrap())
+        .collect();
+    assert_eq!(values, vec!["a", "b"]);
+
+    let window = &request.query_parameters[1];
+    assert_eq!(window.name.as_deref(), Some("window_days"));
+    assert_eq!(window.parameter_type.parameter_type, "INT64");
+    assert_eq!(window.parameter_value.value.as_deref(), Some("30"));
+}
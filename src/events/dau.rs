@@ -0,0 +1,94 @@
+use chrono::NaiveDate;
+#[cfg(not(feature = "local-bin"))]
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+#[cfg(not(feature = "local-bin"))]
+use crate::app_state::AppState;
+
+#[cfg(not(feature = "local-bin"))]
+use super::queries::get_dau_query;
+
+pub const DAU_CACHE_TTL_SECS: u64 = 10 * 60;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DauParams {
+    /// UTC day as `YYYY-MM-DD`, defaults to today
+    #[param(value_type = Option<String>, example = "2025-05-01")]
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+pub struct DauResponse {
+    #[schema(value_type = String, example = "2025-05-01")]
+    pub date: NaiveDate,
+    pub dau: u64,
+}
+
+pub fn dau_cache_key(date: NaiveDate) -> String {
+    format!("dau:{}", date)
+}
+
+pub(crate) trait DauSource {
+    async fn cached_dau(&self, key: &str) -> Result<Option<u64>, anyhow::Error>;
+
+    async fn cache_dau(&self, key: &str, dau: u64, ttl_secs: u64) -> Result<(), anyhow::Error>;
+
+    /// Runs [`get_dau_query`](super::queries::get_dau_query)
+    async fn query_dau(&self, date: NaiveDate) -> Result<u64, anyhow::Error>;
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl DauSource for AppState {
+    async fn cached_dau(&self, key: &str) -> Result<Option<u64>, anyhow::Error> {
+        let mut conn = self.canister_backup_redis_pool.get().await?;
+        Ok(conn.get(key).await?)
+    }
+
+    async fn cache_dau(&self, key: &str, dau: u64, ttl_secs: u64) -> Result<(), anyhow::Error> {
+        let mut conn = self.canister_backup_redis_pool.get().await?;
+        conn.set_ex::<_, _, ()>(key, dau, ttl_secs).await?;
+        Ok(())
+    }
+
+    async fn query_dau(&self, date: NaiveDate) -> Result<u64, anyhow::Error> {
+        let request = QueryRequest {
+            query: get_dau_query(date),
+            ..Default::default()
+        };
+        let mut response = self
+            .bigquery_client
+            .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+            .await?;
+
+        let Some(row) = response.next().await? else {
+            return Ok(0);
+        };
+
+        Ok(row.column::<i64>(0)?.max(0) as u64)
+    }
+}
+
+/// DAU of `date`, from the cache when it was counted in the last [`DAU_CACHE_TTL_SECS`].
+/// Cache failures only cost a query.
+pub async fn daily_active_users(
+    source: &impl DauSource,
+    date: NaiveDate,
+) -> Result<DauResponse, anyhow::Error> {
+    let key = dau_cache_key(date);
+    match source.cached_dau(&key).await {
+        Ok(Some(dau)) => return Ok(DauResponse { date, dau }),
+        Ok(None) => {}
+        Err(e) => log::warn!("Failed to read cached DAU {}: {}", key, e),
+    }
+
+    let dau = source.query_dau(date).await?;
+    if let Err(e) = source.cache_dau(&key, dau, DAU_CACHE_TTL_SECS).await {
+        log::warn!("Failed to cache DAU {}: {}", key, e);
+    }
+
+    Ok(DauResponse { date, dau })
+}
@@ -0,0 +1,116 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::NaiveDate;
+
+use super::{
+    dau::{daily_active_users, dau_cache_key, DauResponse, DauSource, DAU_CACHE_TTL_SECS},
+    queries::get_dau_query,
+};
+
+/// BigQuery answering `dau` for every day, with the cache and the queried days recorded
+#[derive(Default)]
+struct MockSource {
+    dau: u64,
+    cache_unavailable: bool,
+    cache: Mutex<HashMap<String, (u64, u64)>>,
+    queried: Mutex<Vec<NaiveDate>>,
+}
+
+impl DauSource for MockSource {
+    async fn cached_dau(&self, key: &str) -> Result<Option<u64>, anyhow::Error> {
+        if self.cache_unavailable {
+            return Err(anyhow::anyhow!("redis unavailable"));
+        }
+        Ok(self.cache.lock().unwrap().get(key).map(|(dau, _)| *dau))
+    }
+
+    async fn cache_dau(&self, key: &str, dau: u64, ttl_secs: u64) -> Result<(), anyhow::Error> {
+        if self.cache_unavailable {
+            return Err(anyhow::anyhow!("redis unavailable"));
+        }
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), (dau, ttl_secs));
+        Ok(())
+    }
+
+    async fn query_dau(&self, date: NaiveDate) -> Result<u64, anyhow::Error> {
+        self.queried.lock().unwrap().push(date);
+        Ok(self.dau)
+    }
+}
+
+fn date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 5, 1).unwrap()
+}
+
+#[test]
+fn test_dau_query_covers_the_utc_day() {
+    let query = get_dau_query(date());
+
+    assert!(query.contains("COUNT(DISTINCT JSON_EXTRACT_SCALAR(params, '$.user_id'))"));
+    assert!(query.contains("event IN ('video_duration_watched', 'like_video', 'login_successful')"));
+    assert!(query.contains("timestamp >= TIMESTAMP('2025-05-01')"));
+    assert!(query.contains("timestamp < TIMESTAMP_ADD(TIMESTAMP('2025-05-01'), INTERVAL 1 DAY)"));
+}
+
+#[test]
+fn test_dau_response_shape() {
+    assert_eq!(
+        serde_json::to_value(DauResponse {
+            date: date(),
+            dau: 12345
+        })
+        .unwrap(),
+        serde_json::json!({ "date": "2025-05-01", "dau": 12345 })
+    );
+}
+
+#[tokio::test]
+async fn test_dau_is_queried_and_cached_for_ten_minutes() {
+    let source = MockSource {
+        dau: 12345,
+        ..Default::default()
+    };
+
+    let res = daily_active_users(&source, date()).await.unwrap();
+    assert_eq!(
+        res,
+        DauResponse {
+            date: date(),
+            dau: 12345
+        }
+    );
+    assert_eq!(
+        source.cache.lock().unwrap()["dau:2025-05-01"],
+        (12345, DAU_CACHE_TTL_SECS)
+    );
+
+    daily_active_users(&source, date()).await.unwrap();
+    assert_eq!(*source.queried.lock().unwrap(), vec![date()]);
+}
+
+#[tokio::test]
+async fn test_cached_dau_is_returned_without_a_query() {
+    let source = MockSource::default();
+    source
+        .cache
+        .lock()
+        .unwrap()
+        .insert(dau_cache_key(date()), (42, DAU_CACHE_TTL_SECS));
+
+    assert_eq!(daily_active_users(&source, date()).await.unwrap().dau, 42);
+    assert!(source.queried.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_cache_failure_falls_back_to_the_query() {
+    let source = MockSource {
+        dau: 7,
+        cache_unavailable: true,
+        ..Default::default()
+    };
+
+    assert_eq!(daily_active_users(&source, date()).await.unwrap().dau, 7);
+}
@@ -0,0 +1,57 @@
+use k256::sha2::{Digest, Sha256};
+
+use crate::utils::claim_store::ClaimStore;
+
+/// Identical events within this window are retries of the same event
+pub const EVENT_DEDUP_TTL_SECS: u64 = 30;
+
+/// Cache key of an event, a SHA-256 digest of its name, params and user so any difference misses
+pub fn event_dedup_key(event: &str, params: &str, user_id: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    // NUL never appears unescaped in event names, JSON or principals
+    for part in [event, params, user_id.unwrap_or_default()] {
+        hasher.update(part.as_bytes());
+        hasher.update(&[0]);
+    }
+
+    format!("event_dedup:{}", hex::encode(hasher.finalize()))
+}
+
+/// `user_id` of the params, the same field the opt-out check reads
+pub fn params_user_id(params: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(params)
+        .ok()?
+        .get("user_id")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Events seen in the last [`EVENT_DEDUP_TTL_SECS`], so client retries are processed once
+pub struct DedupCache<'a, S> {
+    store: &'a S,
+}
+
+impl<'a, S: ClaimStore> DedupCache<'a, S> {
+    pub fn new(store: &'a S) -> Self {
+        Self { store }
+    }
+
+    /// Records the key, returns false if it was already recorded within the window. Events are
+    /// let through when the store fails, a duplicate is better than a lost event.
+    pub async fn check_and_record(&self, key: &str) -> bool {
+        match self.store.set_if_absent(key, EVENT_DEDUP_TTL_SECS).await {
+            Ok(first) => first,
+            Err(e) => {
+                log::warn!("Failed to check event dedup key {}: {}", key, e);
+                true
+            }
+        }
+    }
+
+    /// Frees the key of an event that failed, so the client's retry is processed
+    pub async fn release(&self, key: &str) {
+        if let Err(e) = self.store.remove(key).await {
+            log::warn!("Failed to release event dedup key {}: {}", key, e);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::utils::claim_store::ClaimStore;
+
+use super::dedup::{event_dedup_key, params_user_id, DedupCache, EVENT_DEDUP_TTL_SECS};
+
+/// Keys with their expiry, `now` is advanced by the tests
+#[derive(Default)]
+struct MockRedis {
+    now: Mutex<u64>,
+    keys: Mutex<HashMap<String, u64>>,
+    unavailable: bool,
+}
+
+impl MockRedis {
+    fn advance(&self, secs: u64) {
+        *self.now.lock().unwrap() += secs;
+    }
+}
+
+impl ClaimStore for MockRedis {
+    async fn set_if_absent(&self, key: &str, ttl_secs: u64) -> Result<bool, anyhow::Error> {
+        if self.unavailable {
+            return Err(anyhow::anyhow!("redis unavailable"));
+        }
+
+        let now = *self.now.lock().unwrap();
+        let mut keys = self.keys.lock().unwrap();
+        if keys.get(key).is_some_and(|expiry| *expiry > now) {
+            return Ok(false);
+        }
+        keys.insert(key.to_string(), now + ttl_secs);
+
+        Ok(true)
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), anyhow::Error> {
+        if self.unavailable {
+            return Err(anyhow::anyhow!("redis unavailable"));
+        }
+
+        self.keys.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+const PARAMS: &str = r#"{"user_id":"2vxsx-fae","video_id":"vid1"}"#;
+
+#[test]
+fn test_dedup_key_covers_every_field() {
+    let key = event_dedup_key("like_video", PARAMS, Some("2vxsx-fae"));
+
+    assert!(key.starts_with("event_dedup:"));
+    assert_eq!(
+        key,
+        event_dedup_key("like_video", PARAMS, Some("2vxsx-fae"))
+    );
+    assert_ne!(
+        key,
+        event_dedup_key("video_viewed", PARAMS, Some("2vxsx-fae"))
+    );
+    assert_ne!(
+        key,
+        event_dedup_key(
+            "like_video",
+            r#"{"user_id":"2vxsx-fae","video_id":"vid2"}"#,
+            Some("2vxsx-fae")
+        )
+    );
+    assert_ne!(key, event_dedup_key("like_video", PARAMS, None));
+    // fields are delimited, moving bytes between them changes the key
+    assert_ne!(
+        event_dedup_key("ab", "c", None),
+        event_dedup_key("a", "bc", None)
+    );
+}
+
+#[test]
+fn test_dedup_key_is_sha256_of_the_fields() {
+    assert_eq!(
+        event_dedup_key("like_video", "{}", None),
+        "event_dedup:c0b25c5a7acd8e948c9953fd2edfafc6b1da6b7b0bb783f599bedf94a5a3b812"
+    );
+}
+
+#[test]
+fn test_params_user_id() {
+    assert_eq!(params_user_id(PARAMS).as_deref(), Some("2vxsx-fae"));
+    assert_eq!(params_user_id(r#"{"video_id":"vid1"}"#), None);
+    assert_eq!(params_user_id("not json"), None);
+}
+
+#[tokio::test]
+async fn test_repeat_within_window_is_dropped() {
+    let redis = MockRedis::default();
+    let cache = DedupCache::new(&redis);
+    let key = event_dedup_key("like_video", PARAMS, Some("2vxsx-fae"));
+
+    assert!(cache.check_and_record(&key).await);
+    redis.advance(EVENT_DEDUP_TTL_SECS - 1);
+    assert!(!cache.check_and_record(&key).await);
+}
+
+#[tokio::test]
+async fn test_repeat_after_window_is_processed() {
+    let redis = MockRedis::default();
+    let cache = DedupCache::new(&redis);
+    let key = event_dedup_key("like_video", PARAMS, Some("2vxsx-fae"));
+
+    assert!(cache.check_and_record(&key).await);
+    redis.advance(EVENT_DEDUP_TTL_SECS);
+    assert!(cache.check_and_record(&key).await);
+}
+
+#[tokio::test]
+async fn test_different_params_are_distinct() {
+    let redis = MockRedis::default();
+    let cache = DedupCache::new(&redis);
+
+    assert!(
+        cache
+            .check_and_record(&event_dedup_key("like_video", PARAMS, Some("2vxsx-fae")))
+            .await
+    );
+    assert!(
+        cache
+            .check_and_record(&event_dedup_key(
+                "like_video",
+                r#"{"user_id":"2vxsx-fae","video_id":"vid2"}"#,
+                Some("2vxsx-fae")
+            ))
+            .await
+    );
+}
+
+#[tokio::test]
+async fn test_store_failure_lets_events_through() {
+    let redis = MockRedis {
+        unavailable: true,
+        ..Default::default()
+    };
+    let cache = DedupCache::new(&redis);
+    let key = event_dedup_key("like_video", PARAMS, Some("2vxsx-fae"));
+
+    assert!(cache.check_and_record(&key).await);
+    assert!(cache.check_and_record(&key).await);
+}
+
+#[tokio::test]
+async fn test_released_event_is_processed_again() {
+    let store = MockRedis::default();
+    let cache = DedupCache::new(&store);
+    let key = event_dedup_key("like_video", PARAMS, Some("2vxsx-fae"));
+
+    assert!(cache.check_and_record(&key).await);
+    cache.release(&key).await;
+
+    assert!(cache.check_and_record(&key).await);
+    assert!(!cache.check_and_record(&key).await);
+}
+
+#[tokio::test]
+async fn test_failed_release_is_only_logged() {
+    let store = MockRedis {
+        unavailable: true,
+        ..Default::default()
+    };
+
+    DedupCache::new(&store).release("event_dedup:abc").await;
+}
@@ -1,9 +1,13 @@
-use crate::consts::OFF_CHAIN_AGENT_URL;
 use crate::{
     app_state::AppState,
-    consts::{BIGQUERY_INGESTION_URL, CLOUDFLARE_ACCOUNT_ID},
-    events::warehouse_events::WarehouseEvent,
-    qstash::duplicate::VideoPublisherData,
+    config::{VideoQuality, CLOUDFLARE_CONFIG},
+    consts::CLOUDFLARE_ACCOUNT_ID,
+    events::{
+        dedup::DedupCache, pipeline::spawn_stage_work, types::DeviceType,
+        warehouse_events::WarehouseEvent,
+    },
+    qstash::{duplicate::VideoPublisherData, video_jobs::track_video_job},
+    tokens::embeddings::IndexTokenMetadataRequest,
     utils::cf_images::upload_base64_image,
     AppError,
 };
@@ -12,9 +16,7 @@ use candid::Principal;
 use chrono::{DateTime, Utc};
 use firestore::errors::FirestoreError;
 use google_cloud_bigquery::http::job::query::QueryRequest;
-use http::header::CONTENT_TYPE;
 use log::error;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{collections::HashMap, env, sync::Arc};
@@ -33,11 +35,36 @@ use yral_ml_feed_cache::{
 
 use super::queries::get_icpump_insert_query;
 
+pub mod bigquery_stream;
+pub mod compression_stats;
+pub mod duplicate_video_detected;
 pub mod login_successful;
+pub mod profile_view;
 pub mod storj;
+pub mod token_burn;
+pub mod token_metadata;
+pub mod view_milestone;
+pub mod watch_reward;
+
+#[cfg(test)]
+mod bigquery_stream_tests;
+#[cfg(test)]
+mod compression_stats_tests;
+#[cfg(test)]
+mod duplicate_video_detected_tests;
+#[cfg(test)]
+mod profile_view_tests;
+#[cfg(test)]
+mod token_burn_tests;
+#[cfg(test)]
+mod token_metadata_tests;
+#[cfg(test)]
+mod view_milestone_tests;
+#[cfg(test)]
+mod watch_reward_tests;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
-struct TokenListItem {
+pub(crate) struct TokenListItem {
     user_id: String,
     name: String,
     token_name: String,
@@ -91,19 +118,55 @@ pub struct LoginSuccessfulParams {
 #[derive(Debug)]
 pub struct Event {
     pub event: WarehouseEvent,
+    pub device_type: DeviceType,
+    /// Resolved from the client ip, `None` when the ip is unknown or not in the GeoIP database
+    pub country_code: Option<String>,
+    /// Key recording the event in the dedup cache, freed if the event fails to reach BigQuery
+    pub dedup_key: Option<String>,
 }
 
+/// Events streamed to BigQuery by their handler once verified, never straight from the pipeline
+pub const VERIFIED_BIGQUERY_EVENTS: &[&str] = &["token_burn"];
+
 impl Event {
+    /// Whether the pipeline leaves streaming the event to its handler
+    pub fn is_verified_before_streaming(&self) -> bool {
+        VERIFIED_BIGQUERY_EVENTS.contains(&self.event.event.as_str())
+    }
+
     pub fn new(event: WarehouseEvent) -> Self {
-        Self { event }
+        Self {
+            event,
+            device_type: DeviceType::Unknown,
+            country_code: None,
+            dedup_key: None,
+        }
+    }
+
+    pub fn with_device_type(mut self, device_type: DeviceType) -> Self {
+        self.device_type = device_type;
+        self
+    }
+
+    pub fn with_country_code(mut self, country_code: Option<String>) -> Self {
+        self.country_code = country_code;
+        self
+    }
+
+    pub fn with_dedup_key(mut self, dedup_key: String) -> Self {
+        self.dedup_key = Some(dedup_key);
+        self
     }
 
     pub fn stream_to_bigquery(&self, app_state: &AppState) {
         let event_str = self.event.event.clone();
         let params_str = self.event.params.clone();
+        let device_type = self.device_type.as_str();
+        let country_code = self.country_code.clone();
+        let dedup_key = self.dedup_key.clone();
         let app_state = app_state.clone();
 
-        tokio::spawn(async move {
+        spawn_stage_work(async move {
             let timestamp = chrono::Utc::now().to_rfc3339();
 
             let data = serde_json::json!({
@@ -114,14 +177,27 @@ impl Event {
                             "event": event_str,
                             "params": params_str,
                             "timestamp": timestamp,
+                            "device_type": device_type,
+                            "country_code": country_code,
                         }
                     }
                 ]
             });
 
-            let res = stream_to_bigquery(&app_state, data).await;
-            if res.is_err() {
-                error!("Error sending data to BigQuery: {}", res.err().unwrap());
+            if let Err(e) = bigquery_stream::stream_to_bigquery_with_retry(
+                &app_state,
+                data,
+                bigquery_stream::BIGQUERY_STREAM_MAX_ATTEMPTS,
+            )
+            .await
+            {
+                error!("Error sending data to BigQuery: {}", e);
+                // the client's retry of the event is not a duplicate
+                if let Some(key) = dedup_key {
+                    DedupCache::new(&app_state.canister_backup_redis_pool)
+                        .release(&key)
+                        .await;
+                }
             }
         });
     }
@@ -131,7 +207,7 @@ impl Event {
             let params: Value = serde_json::from_str(&self.event.params).expect("Invalid JSON");
             let app_state = app_state.clone();
 
-            tokio::spawn(async move {
+            spawn_stage_work(async move {
                 let timestamp = chrono::Utc::now().to_rfc3339();
 
                 let data = ICPumpTokenMetadata {
@@ -172,8 +248,9 @@ impl Event {
             };
 
             let qstash_client = app_state.qstash_client.clone();
+            let redis_pool = app_state.canister_backup_redis_pool.clone();
 
-            tokio::spawn(async move {
+            spawn_stage_work(async move {
                 // Extract required fields with error handling
                 let video_id = match params.get("video_id").and_then(|v| v.as_str()) {
                     Some(id) => id,
@@ -209,48 +286,28 @@ impl Event {
                     };
 
                 // Construct video URL
-                let video_url = format!(
-                    "https://customer-2p3jflss4r4hmpnz.cloudflarestream.com/{}/downloads/default.mp4",
-                    video_id
-                );
+                let video_url =
+                    CLOUDFLARE_CONFIG.video_download_url(&video_id, VideoQuality::Default);
 
                 log::info!("Sending video for deduplication check: {}", video_id);
 
-                // Create request for video_deduplication endpoint
-                let off_chain_ep = OFF_CHAIN_AGENT_URL
-                    .join("qstash/video_deduplication")
-                    .unwrap();
-                let url = qstash_client
-                    .base_url
-                    .join(&format!("publish/{}", off_chain_ep))
-                    .unwrap();
-
-                let request_data = serde_json::json!({
-                    "video_id": video_id,
-                    "video_url": video_url,
-                    "publisher_data": {
-                        "canister_id": canister_id,
-                        "publisher_principal": publisher_user_id,
-                        "post_id": post_id
-                    }
-                });
-
-                // Send to the "/video_deduplication" endpoint via QStash
+                let publisher_data = VideoPublisherData {
+                    canister_id: canister_id.to_string(),
+                    publisher_principal: publisher_user_id.to_string(),
+                    post_id,
+                };
                 let result = qstash_client
-                    .client
-                    .post(url)
-                    .json(&request_data)
-                    .header(CONTENT_TYPE, "application/json")
-                    .header("upstash-method", "POST")
-                    .header("upstash-delay", "600s")
-                    .send()
+                    .publish_video_deduplication(video_id, &video_url, &publisher_data)
                     .await;
 
                 match result {
-                    Ok(_) => log::info!(
-                        "Video deduplication check successfully queued for video_id: {}",
-                        video_id
-                    ),
+                    Ok(message_id) => {
+                        track_video_job(&redis_pool, video_id, &message_id).await;
+                        log::info!(
+                            "Video deduplication check successfully queued for video_id: {}",
+                            video_id
+                        )
+                    }
                     Err(e) => error!(
                         "Failed to queue video deduplication check for video_id {}: {:?}",
                         video_id, e
@@ -265,7 +322,7 @@ impl Event {
             let params: Value = serde_json::from_str(&self.event.params).expect("Invalid JSON");
             let app_state = app_state.clone();
 
-            tokio::spawn(async move {
+            spawn_stage_work(async move {
                 let ml_feed_cache = app_state.ml_feed_cache.clone();
 
                 let percent_watched = params["percentage_watched"].as_f64().unwrap();
@@ -291,7 +348,10 @@ impl Event {
                 let user_cache_key = format!(
                     "{}{}",
                     user_canister_id,
-                    if nsfw_probability <= 0.4 {
+                    if !app_state
+                        .nsfw_config
+                        .is_nsfw_probability(nsfw_probability as f32)
+                    {
                         USER_WATCH_HISTORY_CLEAN_SUFFIX
                     } else {
                         USER_WATCH_HISTORY_NSFW_SUFFIX
@@ -370,7 +430,7 @@ impl Event {
 
         let item_type = self.event.event.clone();
 
-        tokio::spawn(async move {
+        spawn_stage_work(async move {
             let ml_feed_cache = app_state.ml_feed_cache.clone();
             let user_canister_id = params["canister_id"].as_str().unwrap();
             let publisher_canister_id = params["publisher_canister_id"].as_str().unwrap();
@@ -392,7 +452,10 @@ impl Event {
             let user_cache_key = format!(
                 "{}{}",
                 user_canister_id,
-                if nsfw_probability <= 0.4 {
+                if !app_state
+                    .nsfw_config
+                    .is_nsfw_probability(nsfw_probability as f32)
+                {
                     USER_SUCCESS_HISTORY_CLEAN_SUFFIX
                 } else {
                     USER_SUCCESS_HISTORY_NSFW_SUFFIX
@@ -465,7 +528,7 @@ impl Event {
             let app_state = app_state.clone();
             let params: Value = serde_json::from_str(&self.event.params).expect("Invalid JSON");
 
-            tokio::spawn(async move {
+            spawn_stage_work(async move {
                 let data = TokenListItem {
                     user_id: params["user_id"].as_str().unwrap().to_string(),
                     name: params["name"].as_str().unwrap().to_string(),
@@ -506,7 +569,7 @@ impl Event {
             let params: LoginSuccessfulParams = serde_json::from_str(&self.event.params)?;
             let bigquery_client = app_state.bigquery_client.clone();
 
-            tokio::spawn(async move {
+            spawn_stage_work(async move {
                 let canister_id = params.canister_id;
                 let user_id = params.user_id;
 
@@ -521,27 +584,103 @@ impl Event {
 
         Ok(())
     }
-}
 
-async fn stream_to_bigquery(
-    app_state: &AppState,
-    data: Value,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let token = app_state
-        .get_access_token(&["https://www.googleapis.com/auth/bigquery.insertdata"])
-        .await;
-    let client = Client::new();
-    let request_url = BIGQUERY_INGESTION_URL.to_string();
-    let response = client
-        .post(request_url)
-        .bearer_auth(token)
-        .json(&data)
-        .send()
-        .await?;
+    #[cfg(not(feature = "local-bin"))]
+    pub fn handle_video_nsfw_appeal(&self, app_state: &AppState) -> Result<(), anyhow::Error> {
+        if self.event.event == "video_nsfw_appeal" {
+            let payload: super::types::VideoNsfwAppealPayload =
+                serde_json::from_str(&self.event.params)?;
+            let app_state = app_state.clone();
+
+            spawn_stage_work(async move {
+                if let Err(e) = super::nsfw_appeal::submit_nsfw_appeal(&app_state, payload).await {
+                    log::error!("Error handling video nsfw appeal: {:?}", e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "local-bin"))]
+    pub fn handle_token_burn(&self, app_state: &AppState) -> Result<(), anyhow::Error> {
+        if self.event.event == "token_burn" {
+            let payload: super::types::TokenBurnPayload = serde_json::from_str(&self.event.params)?;
+            let app_state = app_state.clone();
+            let event = Event {
+                event: self.event.clone(),
+                device_type: self.device_type,
+                country_code: self.country_code.clone(),
+                dedup_key: self.dedup_key.clone(),
+            };
+
+            spawn_stage_work(async move {
+                if let Err(e) = token_burn::record_token_burn(&app_state, payload, event).await {
+                    log::error!("Error handling token burn: {:?}", e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "local-bin"))]
+    pub fn handle_duplicate_video_detected(
+        &self,
+        app_state: &AppState,
+    ) -> Result<(), anyhow::Error> {
+        if self.event.event == "duplicate_video_detected" {
+            let payload: super::types::DuplicateVideoDetectedEvent =
+                serde_json::from_str(&self.event.params)?;
+            let bigquery_client = app_state.bigquery_client.clone();
+
+            spawn_stage_work(async move {
+                if let Err(e) =
+                    duplicate_video_detected::stream_duplicate_video_event(bigquery_client, payload)
+                        .await
+                {
+                    log::error!("Error handling duplicate video detected: {:?}", e);
+                }
+            });
+        }
+
+        Ok(())
+    }
 
-    match response.status().is_success() {
-        true => Ok(()),
-        false => Err(format!("Failed to stream data - {:?}", response.text().await?).into()),
+    #[cfg(not(feature = "local-bin"))]
+    pub fn handle_creator_profile_viewed(&self, app_state: &AppState) -> Result<(), anyhow::Error> {
+        if self.event.event == "creator_profile_viewed" {
+            let payload: super::types::CreatorProfileViewedPayload =
+                serde_json::from_str(&self.event.params)?;
+            let redis_pool = app_state.ml_feed_cache.redis_pool.clone();
+
+            spawn_stage_work(async move {
+                let timestamp = chrono::Utc::now().timestamp();
+                if let Err(e) =
+                    profile_view::record_profile_view(&redis_pool, &payload, timestamp).await
+                {
+                    log::error!("Error recording creator profile view: {:?}", e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "local-bin"))]
+    pub fn check_view_milestones(&self, app_state: &AppState) -> Result<(), anyhow::Error> {
+        if self.event.event == "video_duration_watched" {
+            let params: view_milestone::VideoViewParams = serde_json::from_str(&self.event.params)?;
+            let app_state = app_state.clone();
+
+            spawn_stage_work(async move {
+                if let Err(e) = view_milestone::check_view_milestones(&app_state, params).await {
+                    log::error!("Error checking view milestones: {:?}", e);
+                }
+            });
+        }
+
+        Ok(())
     }
 }
 
@@ -600,16 +739,34 @@ pub async fn stream_to_bigquery_token_metadata_impl_v2(
         ..Default::default()
     };
 
-    match bq_client
+    if let Err(e) = bq_client
         .query::<google_cloud_bigquery::query::row::Row>("hot-or-not-feed-intelligence", request)
         .await
     {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            log::error!("Error streaming to BigQuery: {:?}", e);
-            Err(anyhow::anyhow!("Error streaming to BigQuery"))
+        log::error!("Error streaming to BigQuery: {:?}", e);
+        return Err(anyhow::anyhow!("Error streaming to BigQuery"));
+    }
+
+    // indexing is best effort, the token is already listed
+    match Principal::from_text(root_id) {
+        Ok(token_root) => {
+            let req = IndexTokenMetadataRequest {
+                token_root,
+                description: data.description,
+                token_symbol: data.token_symbol,
+            };
+            if let Err(e) = app_state
+                .qstash_client
+                .publish_index_token_metadata_to_vector_db(&req)
+                .await
+            {
+                log::error!("Failed to enqueue semantic indexing of {}: {}", root_id, e);
+            }
         }
+        Err(e) => log::warn!("Not indexing token with invalid root {}: {}", root_id, e),
     }
+
+    Ok(())
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -620,14 +777,18 @@ pub struct UploadVideoInfo {
     pub timestamp: String,
     pub publisher_user_id: String,
     pub channel_id: Option<String>,
+    /// Probed by the deduplication job which hashed the video before publishing its upload
+    pub duration_secs: Option<f32>,
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state), fields(video_id = tracing::field::Empty))]
 pub async fn upload_video_gcs(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<UploadVideoInfo>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    upload_gcs_impl(
+    crate::qstash::trace::record_video_id(&payload.video_id);
+
+    let video_obj = upload_gcs_impl(
         &payload.video_id,
         &payload.canister_id,
         &payload.publisher_user_id,
@@ -636,10 +797,26 @@ pub async fn upload_video_gcs(
     )
     .await?;
 
+    #[cfg(not(feature = "local-bin"))]
+    compression_stats::emit_video_compression_stats(
+        &state,
+        &payload.video_id,
+        video_obj.size,
+        payload.duration_secs,
+    );
+    #[cfg(feature = "local-bin")]
+    let _ = video_obj;
+
     let qstash_client = state.qstash_client.clone();
-    qstash_client
+    let message_id = qstash_client
         .publish_video_frames(&payload.video_id, &payload)
         .await?;
+    track_video_job(
+        &state.canister_backup_redis_pool,
+        &payload.video_id,
+        &message_id,
+    )
+    .await;
 
     Ok(Json(
         serde_json::json!({ "message": "Video uploaded to GCS" }),
@@ -652,11 +829,8 @@ pub async fn upload_gcs_impl(
     publisher_user_id: &str,
     post_id: u64,
     timestamp_str: &str,
-) -> Result<(), anyhow::Error> {
-    let url = format!(
-        "https://customer-2p3jflss4r4hmpnz.cloudflarestream.com/{}/downloads/default.mp4",
-        uid
-    );
+) -> Result<cloud_storage::Object, anyhow::Error> {
+    let url = CLOUDFLARE_CONFIG.video_download_url(&uid, VideoQuality::Default);
     let name = format!("{}.mp4", uid);
 
     let file = reqwest::Client::new()
@@ -685,5 +859,5 @@ pub async fn upload_gcs_impl(
     // update
     let _ = gcs_client.object().update(&res_obj).await?;
 
-    Ok(())
+    Ok(res_obj)
 }
@@ -0,0 +1,218 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use google_cloud_bigquery::http::job::query::QueryRequest;
+use reqwest::{Client, Url};
+use serde_json::Value;
+
+use crate::{
+    app_state::AppState,
+    consts::{BIGQUERY_DEAD_LETTER_INGESTION_URL, BIGQUERY_INGESTION_URL},
+};
+
+/// Attempts of an events insert before its rows go to the dead letter table
+pub const BIGQUERY_STREAM_MAX_ATTEMPTS: u8 = 4;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Columns `Event::stream_to_bigquery` sets that the events table was created without, safe to
+/// run on every start
+pub const EVENT_CONTEXT_COLUMNS_DDL: &str =
+    "ALTER TABLE `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+    ADD COLUMN IF NOT EXISTS device_type STRING,
+    ADD COLUMN IF NOT EXISTS country_code STRING";
+
+/// Table of [`BIGQUERY_DEAD_LETTER_INGESTION_URL`], the event columns and the failure
+pub const EVENTS_DEAD_LETTER_TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS
+    `hot-or-not-feed-intelligence.analytics_335143420.yral_raw_events_dead_letter` (
+        event STRING,
+        params STRING,
+        timestamp TIMESTAMP,
+        device_type STRING,
+        country_code STRING,
+        failed_at TIMESTAMP,
+        error_message STRING
+    )";
+
+/// Delay before retrying the given failed attempt, doubling from 500ms, jitter not included
+pub fn retry_delay(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt))
+}
+
+/// [`retry_delay`] plus up to half of it, so inserts failing together don't retry together
+fn jittered_retry_delay(attempt: u32) -> Duration {
+    let delay = retry_delay(attempt);
+    let jitter_ms = rand::random::<u64>() % (delay.as_millis() as u64 / 2 + 1);
+    delay + Duration::from_millis(jitter_ms)
+}
+
+/// Gives every row without one an `insertId`. It is set once before the first attempt, so
+/// BigQuery drops the rows a retry sends again after they were inserted.
+pub fn with_insert_ids(mut data: Value) -> Value {
+    for row in data["rows"].as_array_mut().into_iter().flatten() {
+        if let Some(row) = row.as_object_mut() {
+            row.entry("insertId")
+                .or_insert_with(|| uuid::Uuid::new_v4().to_string().into());
+        }
+    }
+
+    data
+}
+
+/// Summary of the rows an `insertAll` response rejected, `insertAll` answers 200 even when
+/// some or all rows failed
+pub fn insert_errors(response: &Value) -> Option<String> {
+    let errors = response["insertErrors"].as_array()?;
+    if errors.is_empty() {
+        return None;
+    }
+
+    let reasons: Vec<String> = errors
+        .iter()
+        .flat_map(|error| error["errors"].as_array().cloned().unwrap_or_default())
+        .filter_map(|error| error["message"].as_str().map(str::to_string))
+        .collect();
+    Some(format!(
+        "{} rows rejected: {}",
+        errors.len(),
+        reasons.join("; ")
+    ))
+}
+
+/// The `insertAll` request for the dead letter table, every row tagged with the failure
+pub fn dead_letter_rows(data: &Value, error_message: &str, failed_at: DateTime<Utc>) -> Value {
+    let rows: Vec<Value> = data["rows"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|row| {
+            let mut json = row["json"].clone();
+            if let Some(json) = json.as_object_mut() {
+                json.insert("failed_at".into(), failed_at.to_rfc3339().into());
+                json.insert("error_message".into(), error_message.into());
+            }
+            match row.get("insertId") {
+                Some(insert_id) => serde_json::json!({ "insertId": insert_id, "json": json }),
+                None => serde_json::json!({ "json": json }),
+            }
+        })
+        .collect();
+
+    serde_json::json!({
+        "kind": "bigquery#tableDataInsertAllRequest",
+        "rows": rows,
+    })
+}
+
+pub async fn add_event_context_columns(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+) -> Result<(), anyhow::Error> {
+    let request = QueryRequest {
+        query: EVENT_CONTEXT_COLUMNS_DDL.to_string(),
+        ..Default::default()
+    };
+
+    bigquery_client
+        .job()
+        .query("hot-or-not-feed-intelligence", &request)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn create_events_dead_letter_table(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+) -> Result<(), anyhow::Error> {
+    let request = QueryRequest {
+        query: EVENTS_DEAD_LETTER_TABLE_DDL.to_string(),
+        ..Default::default()
+    };
+
+    bigquery_client
+        .job()
+        .query("hot-or-not-feed-intelligence", &request)
+        .await?;
+
+    Ok(())
+}
+
+pub(crate) trait BigQueryRows {
+    /// Sends an `insertAll` request to the table of `url`, rejected rows are an error
+    async fn insert_rows(&self, url: &Url, data: &Value) -> Result<(), anyhow::Error>;
+}
+
+impl BigQueryRows for AppState {
+    async fn insert_rows(&self, url: &Url, data: &Value) -> Result<(), anyhow::Error> {
+        let token = self
+            .get_access_token(&["https://www.googleapis.com/auth/bigquery.insertdata"])
+            .await?;
+        let response = Client::new()
+            .post(url.clone())
+            .bearer_auth(token)
+            .json(data)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to stream data - {:?}",
+                response.text().await?
+            ));
+        }
+
+        let body: Value = response.json().await?;
+        match insert_errors(&body) {
+            Some(errors) => Err(anyhow::anyhow!("Failed to stream data - {}", errors)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Inserts `data` into the events table, retrying with exponential backoff. A request with
+/// rejected rows is retried whole, the insert ids keep the accepted rows from being duplicated.
+/// Rows still failing after `max_attempts` are written to the dead letter table and an error is
+/// returned.
+pub async fn stream_to_bigquery_with_retry(
+    bigquery: &impl BigQueryRows,
+    data: Value,
+    max_attempts: u8,
+) -> Result<(), anyhow::Error> {
+    let data = with_insert_ids(data);
+    let mut attempt = 0;
+    let err = loop {
+        let err = match bigquery.insert_rows(&BIGQUERY_INGESTION_URL, &data).await {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+
+        attempt += 1;
+        if attempt >= max_attempts.max(1) {
+            break err;
+        }
+        let delay = jittered_retry_delay(attempt as u32 - 1);
+        log::warn!(
+            "Failed to stream to BigQuery (attempt {}), retrying in {:?}: {}",
+            attempt,
+            delay,
+            err
+        );
+        tokio::time::sleep(delay).await;
+    };
+
+    let dead_letter = dead_letter_rows(&data, &err.to_string(), Utc::now());
+    match bigquery
+        .insert_rows(&BIGQUERY_DEAD_LETTER_INGESTION_URL, &dead_letter)
+        .await
+    {
+        Ok(()) => Err(anyhow::anyhow!(
+            "rows dead lettered after {} attempts: {}",
+            attempt,
+            err
+        )),
+        Err(e) => Err(anyhow::anyhow!(
+            "rows lost after {} attempts: {}, dead letter insert failed: {}",
+            attempt,
+            err,
+            e
+        )),
+    }
+}
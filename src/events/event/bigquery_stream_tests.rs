@@ -0,0 +1,213 @@
+use std::{sync::Mutex, time::Duration};
+
+use chrono::{TimeZone, Utc};
+use reqwest::Url;
+use serde_json::{json, Value};
+
+use super::bigquery_stream::{
+    dead_letter_rows, insert_errors, retry_delay, stream_to_bigquery_with_retry, with_insert_ids,
+    BigQueryRows, EVENTS_DEAD_LETTER_TABLE_DDL, EVENT_CONTEXT_COLUMNS_DDL,
+};
+use crate::consts::{BIGQUERY_DEAD_LETTER_INGESTION_URL, BIGQUERY_INGESTION_URL};
+
+/// Fails the first `failures` events table inserts, records the inserted rows per table and
+/// the insert ids of every events table attempt
+#[derive(Default)]
+struct MockBigQuery {
+    failures: Mutex<usize>,
+    dead_letter_unavailable: bool,
+    inserted: Mutex<Vec<(Url, Value)>>,
+    attempted_ids: Mutex<Vec<Value>>,
+}
+
+impl MockBigQuery {
+    fn failing(failures: usize) -> Self {
+        Self {
+            failures: Mutex::new(failures),
+            ..Default::default()
+        }
+    }
+
+    fn rows_in(&self, url: &Url) -> Vec<Value> {
+        self.inserted
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(table, _)| table == url)
+            .flat_map(|(_, data)| data["rows"].as_array().cloned().unwrap_or_default())
+            .collect()
+    }
+}
+
+impl BigQueryRows for MockBigQuery {
+    async fn insert_rows(&self, url: &Url, data: &Value) -> Result<(), anyhow::Error> {
+        if *url == *BIGQUERY_DEAD_LETTER_INGESTION_URL && self.dead_letter_unavailable {
+            return Err(anyhow::anyhow!("dead letter table unavailable"));
+        }
+        if *url == *BIGQUERY_INGESTION_URL {
+            self.attempted_ids
+                .lock()
+                .unwrap()
+                .push(data["rows"][0]["insertId"].clone());
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(anyhow::anyhow!("503 backend error"));
+            }
+        }
+
+        self.inserted
+            .lock()
+            .unwrap()
+            .push((url.clone(), data.clone()));
+        Ok(())
+    }
+}
+
+fn data() -> Value {
+    json!({
+        "kind": "bigquery#tableDataInsertAllRequest",
+        "rows": [{ "json": { "event": "like_video", "params": "{}" } }]
+    })
+}
+
+#[test]
+fn test_retry_delay_doubles_from_500ms() {
+    assert_eq!(retry_delay(0), Duration::from_millis(500));
+    assert_eq!(retry_delay(1), Duration::from_secs(1));
+    assert_eq!(retry_delay(3), Duration::from_secs(4));
+}
+
+#[test]
+fn test_dead_letter_rows_tag_the_failure() {
+    let failed_at = Utc.with_ymd_and_hms(2026, 10, 15, 12, 0, 0).unwrap();
+
+    assert_eq!(
+        dead_letter_rows(&data(), "503 backend error", failed_at)["rows"],
+        json!([{
+            "json": {
+                "event": "like_video",
+                "params": "{}",
+                "failed_at": "2026-10-15T12:00:00+00:00",
+                "error_message": "503 backend error"
+            }
+        }])
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_insert_succeeds_after_two_failures() {
+    let bigquery = MockBigQuery::failing(2);
+    let start = tokio::time::Instant::now();
+
+    stream_to_bigquery_with_retry(&bigquery, data(), 4)
+        .await
+        .unwrap();
+
+    let rows = bigquery.rows_in(&BIGQUERY_INGESTION_URL);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["json"], data()["rows"][0]["json"]);
+    // every attempt sent the same insert id
+    let attempted_ids = bigquery.attempted_ids.lock().unwrap().clone();
+    assert_eq!(attempted_ids.len(), 3);
+    assert!(attempted_ids[0].is_string());
+    assert!(attempted_ids.iter().all(|id| *id == attempted_ids[0]));
+    assert!(bigquery
+        .rows_in(&BIGQUERY_DEAD_LETTER_INGESTION_URL)
+        .is_empty());
+    // 500ms + 1s of backoff, each with up to half of it in jitter
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(1500));
+    assert!(elapsed <= Duration::from_millis(2250));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rows_are_dead_lettered_after_the_last_attempt() {
+    let bigquery = MockBigQuery::failing(usize::MAX);
+
+    let err = stream_to_bigquery_with_retry(&bigquery, data(), 3)
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "rows dead lettered after 3 attempts: 503 backend error"
+    );
+    assert!(bigquery.rows_in(&BIGQUERY_INGESTION_URL).is_empty());
+    let dead_letter = bigquery.rows_in(&BIGQUERY_DEAD_LETTER_INGESTION_URL);
+    assert_eq!(dead_letter.len(), 1);
+    assert_eq!(dead_letter[0]["json"]["event"], "like_video");
+    assert_eq!(dead_letter[0]["json"]["error_message"], "503 backend error");
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_failed_dead_letter_insert_is_reported() {
+    let bigquery = MockBigQuery {
+        failures: Mutex::new(usize::MAX),
+        dead_letter_unavailable: true,
+        ..Default::default()
+    };
+
+    let err = stream_to_bigquery_with_retry(&bigquery, data(), 2)
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().starts_with("rows lost after 2 attempts"));
+}
+
+#[test]
+fn test_ddl_adds_every_event_context_column() {
+    for column in ["device_type STRING", "country_code STRING"] {
+        assert!(EVENT_CONTEXT_COLUMNS_DDL.contains(&format!("ADD COLUMN IF NOT EXISTS {}", column)));
+    }
+}
+
+#[test]
+fn test_dead_letter_table_has_every_column() {
+    for column in [
+        "event STRING",
+        "params STRING",
+        "device_type STRING",
+        "country_code STRING",
+        "failed_at TIMESTAMP",
+        "error_message STRING",
+    ] {
+        assert!(EVENTS_DEAD_LETTER_TABLE_DDL.contains(column));
+    }
+}
+
+#[test]
+fn test_insert_ids_are_kept_and_filled_in() {
+    let data = json!({
+        "rows": [
+            { "insertId": "kept", "json": {} },
+            { "json": {} }
+        ]
+    });
+
+    let data = with_insert_ids(data);
+
+    assert_eq!(data["rows"][0]["insertId"], "kept");
+    assert!(data["rows"][1]["insertId"].is_string());
+}
+
+#[test]
+fn test_insert_errors_of_a_200_response() {
+    let response = json!({
+        "kind": "bigquery#tableDataInsertAllResponse",
+        "insertErrors": [{
+            "index": 0,
+            "errors": [{ "reason": "invalid", "message": "no such field: device_type." }]
+        }]
+    });
+
+    assert_eq!(
+        insert_errors(&response).as_deref(),
+        Some("1 rows rejected: no such field: device_type.")
+    );
+    assert_eq!(
+        insert_errors(&json!({ "kind": "bigquery#tableDataInsertAllResponse" })),
+        None
+    );
+    assert_eq!(insert_errors(&json!({ "insertErrors": [] })), None);
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{app_state::AppState, events::warehouse_events::WarehouseEvent};
+
+use super::Event;
+
+pub const VIDEO_COMPRESSION_STATS_EVENT: &str = "video_compression_stats";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoCompressionStatsParams {
+    pub video_id: String,
+    pub file_size_bytes: u64,
+    pub duration_secs: Option<f32>,
+    pub bitrate_kbps: Option<u32>,
+}
+
+impl VideoCompressionStatsParams {
+    pub fn new(video_id: String, file_size_bytes: u64, duration_secs: Option<f32>) -> Self {
+        Self {
+            video_id,
+            file_size_bytes,
+            duration_secs,
+            bitrate_kbps: duration_secs.and_then(|d| bitrate_kbps(file_size_bytes, d)),
+        }
+    }
+
+    pub fn into_warehouse_event(self) -> WarehouseEvent {
+        WarehouseEvent {
+            event: VIDEO_COMPRESSION_STATS_EVENT.to_string(),
+            params: serde_json::to_string(&self).unwrap_or_default(),
+        }
+    }
+}
+
+/// Average bitrate over the whole file, container overhead included
+pub fn bitrate_kbps(file_size_bytes: u64, duration_secs: f32) -> Option<u32> {
+    if !duration_secs.is_finite() || duration_secs <= 0.0 {
+        return None;
+    }
+
+    Some((file_size_bytes as f64 * 8.0 / 1000.0 / duration_secs as f64).round() as u32)
+}
+
+/// Streams the uploaded video's size stats to BigQuery, the duration is the one probed while
+/// hashing the video so it is not downloaded again
+pub fn emit_video_compression_stats(
+    app_state: &AppState,
+    video_id: &str,
+    file_size_bytes: u64,
+    duration_secs: Option<f32>,
+) {
+    let params =
+        VideoCompressionStatsParams::new(video_id.to_string(), file_size_bytes, duration_secs);
+    Event::new(params.into_warehouse_event()).stream_to_bigquery(app_state);
+}
@@ -0,0 +1,43 @@
+use super::compression_stats::{
+    bitrate_kbps, VideoCompressionStatsParams, VIDEO_COMPRESSION_STATS_EVENT,
+};
+
+#[test]
+fn test_bitrate_from_size_and_duration() {
+    // 1.25 MB over 10 seconds
+    assert_eq!(bitrate_kbps(1_250_000, 10.0), Some(1000));
+    assert_eq!(bitrate_kbps(0, 10.0), Some(0));
+}
+
+#[test]
+fn test_bitrate_requires_positive_duration() {
+    assert_eq!(bitrate_kbps(1_250_000, 0.0), None);
+    assert_eq!(bitrate_kbps(1_250_000, -1.0), None);
+    assert_eq!(bitrate_kbps(1_250_000, f32::NAN), None);
+}
+
+#[test]
+fn test_params_without_duration_have_no_bitrate() {
+    let params = VideoCompressionStatsParams::new("video".to_string(), 1_000, None);
+
+    assert_eq!(params.duration_secs, None);
+    assert_eq!(params.bitrate_kbps, None);
+}
+
+#[test]
+fn test_warehouse_event() {
+    let event = VideoCompressionStatsParams::new("video".to_string(), 2_500_000, Some(20.0))
+        .into_warehouse_event();
+
+    assert_eq!(event.event, VIDEO_COMPRESSION_STATS_EVENT);
+    let params: serde_json::Value = serde_json::from_str(&event.params).unwrap();
+    assert_eq!(
+        params,
+        serde_json::json!({
+            "video_id": "video",
+            "file_size_bytes": 2_500_000,
+            "duration_secs": 20.0,
+            "bitrate_kbps": 1000,
+        })
+    );
+}
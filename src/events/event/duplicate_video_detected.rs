@@ -0,0 +1,87 @@
+#[cfg(not(feature = "local-bin"))]
+use google_cloud_bigquery::{
+    client::Client,
+    http::tabledata::insert_all::{InsertAllRequest, Row},
+};
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "local-bin"))]
+use tracing::instrument;
+
+use crate::events::types::DuplicateVideoDetectedEvent;
+
+pub const DUPLICATE_VIDEO_EVENTS_TABLE: &str = "duplicate_video_events";
+
+/// Row of `yral_ds.duplicate_video_events`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DuplicateVideoEventRow {
+    pub original_video_id: String,
+    pub parent_video_id: String,
+    pub similarity_percentage: f64,
+    pub exact_duplicate: bool,
+    pub publisher_canister_id: String,
+    pub publisher_principal: String,
+    pub post_id: u64,
+    /// When the duplicate was detected, as sent by the client
+    pub timestamp: String,
+    pub received_at: String,
+}
+
+pub fn duplicate_video_event_row(
+    event: DuplicateVideoDetectedEvent,
+    received_at: String,
+) -> DuplicateVideoEventRow {
+    DuplicateVideoEventRow {
+        original_video_id: event.original_video_id,
+        parent_video_id: event.parent_video_id,
+        similarity_percentage: event.similarity_percentage,
+        exact_duplicate: event.exact_duplicate,
+        publisher_canister_id: event.publisher_canister_id.to_text(),
+        publisher_principal: event.publisher_principal,
+        post_id: event.post_id,
+        timestamp: event.timestamp,
+        received_at,
+    }
+}
+
+#[cfg(not(feature = "local-bin"))]
+#[instrument(skip(bq_client))]
+pub async fn stream_duplicate_video_event(
+    bq_client: Client,
+    event: DuplicateVideoDetectedEvent,
+) -> Result<(), anyhow::Error> {
+    let row = duplicate_video_event_row(event, chrono::Utc::now().to_rfc3339());
+    let request = InsertAllRequest {
+        rows: vec![Row {
+            insert_id: None,
+            json: row,
+        }],
+        ..Default::default()
+    };
+
+    let res = bq_client
+        .tabledata()
+        .insert(
+            "hot-or-not-feed-intelligence",
+            "yral_ds",
+            DUPLICATE_VIDEO_EVENTS_TABLE,
+            &request,
+        )
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to insert duplicate_video_events row to bigquery: {}",
+                e
+            )
+        })?;
+
+    if let Some(errors) = res.insert_errors {
+        if !errors.is_empty() {
+            log::error!("duplicate_video_events insert response : {:?}", errors);
+            return Err(anyhow::anyhow!(
+                "Failed to insert duplicate_video_events row to bigquery"
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use candid::Principal;
+use serde_json::json;
+use yral_metrics::metrics::sealed_metric::SealedMetric;
+
+use super::duplicate_video_detected::duplicate_video_event_row;
+use crate::events::types::AnalyticsEvent;
+
+fn publisher_canister() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+fn bulk_event() -> serde_json::Value {
+    json!({
+        "event": "DuplicateVideoDetected",
+        "original_video_id": "video-new",
+        "parent_video_id": "video-old",
+        "similarity_percentage": 97.5,
+        "exact_duplicate": false,
+        "publisher_canister_id": "rrkah-fqaaa-aaaaa-aaaaq-cai",
+        "publisher_principal": "2vxsx-fae",
+        "post_id": 7,
+        "timestamp": "2025-01-01T00:00:00Z",
+    })
+}
+
+#[test]
+fn test_duplicate_video_detected_event_from_bulk_api() {
+    let event: AnalyticsEvent = serde_json::from_value(bulk_event()).unwrap();
+
+    assert!(matches!(event, AnalyticsEvent::DuplicateVideoDetected(_)));
+    assert_eq!(event.tag(), "duplicate_video_detected");
+    assert_eq!(event.user_id(), Some("2vxsx-fae".to_string()));
+    assert_eq!(event.user_canister(), Some(publisher_canister()));
+    assert_eq!(event.params()["parent_video_id"], "video-old");
+    assert_eq!(event.params()["similarity_percentage"], 97.5);
+}
+
+#[test]
+fn test_duplicate_video_detected_event_requires_video_ids() {
+    let mut value = bulk_event();
+    value.as_object_mut().unwrap().remove("parent_video_id");
+
+    assert!(serde_json::from_value::<AnalyticsEvent>(value).is_err());
+}
+
+#[test]
+fn test_duplicate_video_event_row() {
+    let AnalyticsEvent::DuplicateVideoDetected(event) =
+        serde_json::from_value(bulk_event()).unwrap()
+    else {
+        panic!("expected a duplicate video detected event");
+    };
+
+    let row = duplicate_video_event_row(event, "2025-01-01T00:00:05Z".into());
+
+    assert_eq!(
+        serde_json::to_value(row).unwrap(),
+        json!({
+            "original_video_id": "video-new",
+            "parent_video_id": "video-old",
+            "similarity_percentage": 97.5,
+            "exact_duplicate": false,
+            "publisher_canister_id": "rrkah-fqaaa-aaaaa-aaaaq-cai",
+            "publisher_principal": "2vxsx-fae",
+            "post_id": 7,
+            "timestamp": "2025-01-01T00:00:00Z",
+            "received_at": "2025-01-01T00:00:05Z",
+        })
+    );
+}
@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+use serde_json::json;
+use tracing::instrument;
+
+#[cfg(not(feature = "local-bin"))]
+use crate::events::subscribe;
+use crate::{
+    app_state::AppState,
+    events::{types::CreatorProfileViewedPayload, warehouse_events::WarehouseEvent},
+    types::RedisPool,
+    user::orphaned_keys::KeyStore,
+    AppError,
+};
+
+const PROFILE_VIEWS_PREFIX: &str = "profile_views:";
+/// Visitors older than this are dropped from the sorted sets by the digest job
+pub const PROFILE_VIEWS_RETENTION_SECS: i64 = 30 * 24 * 60 * 60;
+/// Visitors counted by the daily digest
+pub const PROFILE_VIEW_DIGEST_WINDOW_SECS: i64 = 24 * 60 * 60;
+pub const PROFILE_VIEW_DIGEST_CRON: &str = "0 9 * * *";
+pub const PROFILE_VIEW_DIGEST_SCHEDULE_ID: &str = "creator-profile-view-digest";
+
+/// Sorted set of the creator's profile visitors, scored by the time of their last view
+pub fn profile_views_key(creator_id: &str) -> String {
+    format!("{}{}", PROFILE_VIEWS_PREFIX, creator_id)
+}
+
+pub fn profile_views_key_pattern() -> String {
+    format!("{}*", PROFILE_VIEWS_PREFIX)
+}
+
+/// Creators viewing their own profile are not visitors
+pub fn is_self_view(view: &CreatorProfileViewedPayload) -> bool {
+    view.viewer_id == view.creator_id
+}
+
+/// `creator_profile_view_digest` event for the creator, its `user_id` routes it to their SSE
+/// connections
+pub fn profile_view_digest_event(creator_id: &str, unique_visitors: u64) -> WarehouseEvent {
+    WarehouseEvent {
+        event: "creator_profile_view_digest".into(),
+        params: json!({
+            "user_id": creator_id,
+            "unique_visitors": unique_visitors,
+            "message": if unique_visitors == 1 {
+                "1 person viewed your profile today".to_string()
+            } else {
+                format!("{} people viewed your profile today", unique_visitors)
+            },
+        })
+        .to_string(),
+    }
+}
+
+pub(crate) trait ProfileViewStore: KeyStore {
+    /// Adds the viewer to the sorted set or moves their score to `timestamp`
+    async fn record_view(
+        &self,
+        key: &str,
+        viewer: &str,
+        timestamp: i64,
+    ) -> Result<(), anyhow::Error>;
+
+    /// Visitors whose last view is at or after `since`
+    async fn count_views_since(&self, key: &str, since: i64) -> Result<u64, anyhow::Error>;
+
+    /// Drops visitors whose last view is before `before`
+    async fn trim_views_before(&self, key: &str, before: i64) -> Result<(), anyhow::Error>;
+}
+
+impl ProfileViewStore for RedisPool {
+    async fn record_view(
+        &self,
+        key: &str,
+        viewer: &str,
+        timestamp: i64,
+    ) -> Result<(), anyhow::Error> {
+        let mut conn = self.get().await?;
+        redis::cmd("ZADD")
+            .arg(key)
+            .arg(timestamp)
+            .arg(viewer)
+            .query_async::<()>(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn count_views_since(&self, key: &str, since: i64) -> Result<u64, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let count = redis::cmd("ZCOUNT")
+            .arg(key)
+            .arg(since)
+            .arg("+inf")
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn trim_views_before(&self, key: &str, before: i64) -> Result<(), anyhow::Error> {
+        let mut conn = self.get().await?;
+        redis::cmd("ZREMRANGEBYSCORE")
+            .arg(key)
+            .arg("-inf")
+            .arg(format!("({}", before))
+            .query_async::<()>(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Records the viewer in the creator's `profile_views` sorted set, self views are skipped
+pub async fn record_profile_view(
+    store: &impl ProfileViewStore,
+    view: &CreatorProfileViewedPayload,
+    timestamp: i64,
+) -> Result<(), anyhow::Error> {
+    if is_self_view(view) {
+        return Ok(());
+    }
+
+    store
+        .record_view(
+            &profile_views_key(&view.creator_id),
+            &view.viewer_id,
+            timestamp,
+        )
+        .await
+}
+
+/// Unique visitors of every creator over the last [`PROFILE_VIEW_DIGEST_WINDOW_SECS`], creators
+/// without visitors are left out. Visitors past [`PROFILE_VIEWS_RETENTION_SECS`] are dropped.
+pub async fn profile_view_digests(
+    store: &impl ProfileViewStore,
+    now: i64,
+) -> Result<Vec<(String, u64)>, anyhow::Error> {
+    let pattern = profile_views_key_pattern();
+    let mut digests = Vec::new();
+    let mut cursor = 0;
+
+    loop {
+        let (next, keys) = store.scan_keys(cursor, &pattern).await?;
+
+        for key in keys {
+            store
+                .trim_views_before(&key, now - PROFILE_VIEWS_RETENTION_SECS)
+                .await?;
+            let visitors = store
+                .count_views_since(&key, now - PROFILE_VIEW_DIGEST_WINDOW_SECS)
+                .await?;
+            if visitors == 0 {
+                continue;
+            }
+
+            let creator = key.strip_prefix(PROFILE_VIEWS_PREFIX).unwrap_or(&key);
+            digests.push((creator.to_string(), visitors));
+        }
+
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+
+    Ok(digests)
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct ProfileViewDigestResponse {
+    /// Creators with at least one visitor in the last day
+    pub creators: usize,
+}
+
+/// Tells every creator how many people viewed their profile in the last day, scheduled daily.
+/// Creators without an open connection get the digest when they next subscribe.
+#[instrument(skip(state))]
+pub async fn creator_profile_view_digest(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ProfileViewDigestResponse>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let now = chrono::Utc::now().timestamp();
+        let digests = profile_view_digests(&state.ml_feed_cache.redis_pool, now).await?;
+
+        for (creator, visitors) in &digests {
+            let event = profile_view_digest_event(creator, *visitors);
+            if let Err(e) = subscribe::publish_durable_event(
+                &state.event_subscribers,
+                &state.canister_backup_redis_pool,
+                &event.event,
+                &event.params,
+            )
+            .await
+            {
+                log::error!("Failed to keep profile view digest of {}: {}", creator, e);
+            }
+        }
+        log::info!("Sent profile view digests to {} creators", digests.len());
+
+        Ok(Json(ProfileViewDigestResponse {
+            creators: digests.len(),
+        }))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
@@ -0,0 +1,205 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use candid::Principal;
+use serde_json::{json, Value};
+
+use super::profile_view::{
+    profile_view_digest_event, profile_view_digests, profile_views_key, record_profile_view,
+    ProfileViewStore, PROFILE_VIEWS_RETENTION_SECS, PROFILE_VIEW_DIGEST_WINDOW_SECS,
+};
+use crate::{
+    events::types::{AnalyticsEvent, CreatorProfileViewedPayload},
+    user::orphaned_keys::KeyStore,
+};
+
+const NOW: i64 = 1_700_000_000;
+
+/// Sorted sets of visitor scores by key, `SCAN` returns one key per page
+#[derive(Default)]
+struct MockRedis {
+    sets: Mutex<BTreeMap<String, BTreeMap<String, i64>>>,
+}
+
+impl MockRedis {
+    fn visitors(&self, creator: &str) -> BTreeMap<String, i64> {
+        self.sets
+            .lock()
+            .unwrap()
+            .get(&profile_views_key(creator))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl KeyStore for MockRedis {
+    async fn scan_keys(
+        &self,
+        cursor: u64,
+        pattern: &str,
+    ) -> Result<(u64, Vec<String>), anyhow::Error> {
+        let prefix = pattern.strip_suffix('*').unwrap();
+        let matching: Vec<String> = self
+            .sets
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        let Some(key) = matching.get(cursor as usize) else {
+            return Ok((0, vec![]));
+        };
+        let next = if cursor as usize + 1 == matching.len() {
+            0
+        } else {
+            cursor + 1
+        };
+
+        Ok((next, vec![key.clone()]))
+    }
+
+    async fn delete_keys(&self, _keys: &[String]) -> Result<usize, anyhow::Error> {
+        unreachable!("profile views are never deleted")
+    }
+}
+
+impl ProfileViewStore for MockRedis {
+    async fn record_view(
+        &self,
+        key: &str,
+        viewer: &str,
+        timestamp: i64,
+    ) -> Result<(), anyhow::Error> {
+        self.sets
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .insert(viewer.to_string(), timestamp);
+        Ok(())
+    }
+
+    async fn count_views_since(&self, key: &str, since: i64) -> Result<u64, anyhow::Error> {
+        Ok(self.sets.lock().unwrap()[key]
+            .values()
+            .filter(|score| **score >= since)
+            .count() as u64)
+    }
+
+    async fn trim_views_before(&self, key: &str, before: i64) -> Result<(), anyhow::Error> {
+        self.sets
+            .lock()
+            .unwrap()
+            .get_mut(key)
+            .unwrap()
+            .retain(|_, score| *score >= before);
+        Ok(())
+    }
+}
+
+fn view(viewer_id: &str, creator_id: &str) -> CreatorProfileViewedPayload {
+    CreatorProfileViewedPayload {
+        viewer_id: viewer_id.into(),
+        viewer_canister: Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap(),
+        creator_id: creator_id.into(),
+        creator_canister: Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap(),
+        source: "feed".into(),
+    }
+}
+
+#[test]
+fn test_profile_views_key() {
+    assert_eq!(profile_views_key("creator"), "profile_views:creator");
+}
+
+#[tokio::test]
+async fn test_repeat_views_count_one_visitor() {
+    let redis = MockRedis::default();
+
+    record_profile_view(&redis, &view("viewer", "creator"), NOW - 60)
+        .await
+        .unwrap();
+    record_profile_view(&redis, &view("viewer", "creator"), NOW)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        redis.visitors("creator"),
+        BTreeMap::from([("viewer".to_string(), NOW)])
+    );
+}
+
+#[tokio::test]
+async fn test_self_views_are_not_recorded() {
+    let redis = MockRedis::default();
+
+    record_profile_view(&redis, &view("creator", "creator"), NOW)
+        .await
+        .unwrap();
+
+    assert!(redis.visitors("creator").is_empty());
+}
+
+#[tokio::test]
+async fn test_digest_counts_last_day_and_drops_expired_visitors() {
+    let redis = MockRedis::default();
+    for (viewer, creator, timestamp) in [
+        ("a", "creator-1", NOW - 60),
+        ("b", "creator-1", NOW - PROFILE_VIEW_DIGEST_WINDOW_SECS),
+        ("c", "creator-1", NOW - PROFILE_VIEW_DIGEST_WINDOW_SECS - 1),
+        ("d", "creator-1", NOW - PROFILE_VIEWS_RETENTION_SECS - 1),
+        ("a", "creator-2", NOW - 2 * PROFILE_VIEW_DIGEST_WINDOW_SECS),
+        ("b", "creator-3", NOW - 1),
+    ] {
+        record_profile_view(&redis, &view(viewer, creator), timestamp)
+            .await
+            .unwrap();
+    }
+
+    let digests = profile_view_digests(&redis, NOW).await.unwrap();
+
+    assert_eq!(
+        digests,
+        vec![("creator-1".to_string(), 2), ("creator-3".to_string(), 1)]
+    );
+    assert_eq!(
+        redis.visitors("creator-1").keys().collect::<Vec<_>>(),
+        vec!["a", "b", "c"]
+    );
+    assert_eq!(redis.visitors("creator-2").len(), 1);
+}
+
+#[test]
+fn test_digest_notification() {
+    let event = profile_view_digest_event("creator", 3);
+    let params: Value = serde_json::from_str(&event.params).unwrap();
+
+    assert_eq!(event.event, "creator_profile_view_digest");
+    assert_eq!(params["user_id"], "creator");
+    assert_eq!(params["unique_visitors"], 3);
+    assert_eq!(params["message"], "3 people viewed your profile today");
+
+    let params: Value =
+        serde_json::from_str(&profile_view_digest_event("creator", 1).params).unwrap();
+    assert_eq!(params["message"], "1 person viewed your profile today");
+}
+
+#[test]
+fn test_creator_profile_viewed_analytics_event() {
+    let event: AnalyticsEvent = serde_json::from_value(json!({
+        "event": "CreatorProfileViewed",
+        "viewer_id": "viewer",
+        "viewer_canister": "ryjl3-tyaaa-aaaaa-aaaba-cai",
+        "creator_id": "creator",
+        "creator_canister": "rrkah-fqaaa-aaaaa-aaaaq-cai",
+        "source": "search",
+    }))
+    .unwrap();
+
+    assert!(matches!(event, AnalyticsEvent::CreatorProfileViewed(_)));
+    assert_eq!(
+        yral_metrics::metrics::sealed_metric::SealedMetric::tag(&event),
+        "creator_profile_viewed"
+    );
+    assert_eq!(event.params()["source"], "search");
+}
@@ -0,0 +1,240 @@
+#[cfg(not(feature = "local-bin"))]
+use candid::Nat;
+use candid::Principal;
+#[cfg(not(feature = "local-bin"))]
+use serde::Deserialize;
+use serde_json::json;
+#[cfg(not(feature = "local-bin"))]
+use tracing::instrument;
+#[cfg(not(feature = "local-bin"))]
+use yral_canisters_client::{
+    sns_ledger::{GetTransactionsRequest, SnsLedger},
+    sns_root::{GetSnsCanistersSummaryRequest, SnsRoot},
+};
+
+use crate::events::{types::TokenBurnPayload, warehouse_events::WarehouseEvent};
+#[cfg(not(feature = "local-bin"))]
+use crate::{
+    app_state::AppState,
+    events::{event::Event, opt_out, subscribe},
+    utils::claim_store::ClaimStore,
+};
+
+/// A ledger block is recorded once, replays of its burn event within this window are dropped
+pub const TOKEN_BURN_REPLAY_TTL_SECS: u64 = 90 * 24 * 60 * 60;
+
+pub fn token_burns_key(token_root: Principal) -> String {
+    format!("token_burns:{}", token_root)
+}
+
+/// Claimed when the burn of the block is recorded
+pub fn token_burn_block_key(token_root: Principal, block_index: u64) -> String {
+    format!("token_burn_block:{}:{}", token_root, block_index)
+}
+
+/// Burn transaction of a ledger block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerBurn {
+    pub from: Principal,
+    pub amount_e8s: u64,
+}
+
+/// Checks the burn against its ledger block, `None` when the block is not a burn or could not be
+/// read. Tokens are burned by the user or their canister.
+pub fn verify_burn(
+    burn: &TokenBurnPayload,
+    ledger_burn: Option<&LedgerBurn>,
+) -> Result<(), anyhow::Error> {
+    let Some(block_index) = burn.block_index else {
+        return Err(anyhow::anyhow!("burn without ledger block index"));
+    };
+    let Some(ledger_burn) = ledger_burn else {
+        return Err(anyhow::anyhow!("block {} is not a burn", block_index));
+    };
+
+    if burn.amount_burned_e8s == 0 {
+        return Err(anyhow::anyhow!("burn of 0 e8s"));
+    }
+    if ledger_burn.amount_e8s != burn.amount_burned_e8s {
+        return Err(anyhow::anyhow!(
+            "reported burn of {} e8s, block {} burned {} e8s",
+            burn.amount_burned_e8s,
+            block_index,
+            ledger_burn.amount_e8s
+        ));
+    }
+    let burner_is_user = Principal::from_text(&burn.user_id).ok() == Some(ledger_burn.from);
+    if !burner_is_user && ledger_burn.from != burn.canister_id {
+        return Err(anyhow::anyhow!(
+            "block {} was burned by {}, not by the user",
+            block_index,
+            ledger_burn.from
+        ));
+    }
+
+    Ok(())
+}
+
+/// Member of the `token_burns:{token_root}` sorted set, scored by `timestamp`. The block index
+/// keeps burns of the same amount apart.
+pub fn token_burn_member(burn: &TokenBurnPayload, timestamp: i64) -> String {
+    json!({
+        "user_id": burn.user_id,
+        "amount_burned_e8s": burn.amount_burned_e8s,
+        "new_total_supply_e8s": burn.new_total_supply_e8s,
+        "block_index": burn.block_index,
+        "timestamp": timestamp,
+    })
+    .to_string()
+}
+
+/// `token_burned` event for the token creator, its `user_id` routes it to their open SSE connections
+pub fn creator_burn_notification_event(creator: &str, burn: &TokenBurnPayload) -> WarehouseEvent {
+    WarehouseEvent {
+        event: "token_burned".into(),
+        params: json!({
+            "user_id": creator,
+            "token_root": burn.token_root,
+            "amount_burned_e8s": burn.amount_burned_e8s,
+            "new_total_supply_e8s": burn.new_total_supply_e8s,
+            "message": format!(
+                "{} of your tokens were burned",
+                burn.amount_burned_e8s as f64 / 1e8
+            ),
+        })
+        .to_string(),
+    }
+}
+
+#[cfg(not(feature = "local-bin"))]
+#[derive(Debug, Deserialize)]
+struct TokenCreator {
+    user_id: String,
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn ledger_burn(
+    app_state: &AppState,
+    token_root: Principal,
+    block_index: u64,
+) -> Result<Option<LedgerBurn>, anyhow::Error> {
+    let ledger = SnsRoot(token_root, &app_state.agent)
+        .get_sns_canisters_summary(GetSnsCanistersSummaryRequest {
+            update_canister_list: None,
+        })
+        .await?
+        .ledger
+        .and_then(|ledger| ledger.canister_id)
+        .ok_or_else(|| anyhow::anyhow!("no ledger under root {}", token_root))?;
+
+    let res = SnsLedger(ledger, &app_state.agent)
+        .get_transactions(GetTransactionsRequest {
+            start: Nat::from(block_index),
+            length: Nat::from(1u8),
+        })
+        .await?;
+    // archived blocks come back as ranges and the first local block in their place
+    if res.first_index != Nat::from(block_index) {
+        return Ok(None);
+    }
+
+    let Some(burn) = res
+        .transactions
+        .into_iter()
+        .next()
+        .and_then(|transaction| transaction.burn)
+    else {
+        return Ok(None);
+    };
+
+    let amount_e8s = u64::try_from(burn.amount.0)
+        .map_err(|_| anyhow::anyhow!("burn in block {} overflows u64", block_index))?;
+
+    Ok(Some(LedgerBurn {
+        from: burn.from.owner,
+        amount_e8s,
+    }))
+}
+
+/// Verifies the burn against its ledger block, records it once per block, streams the event to
+/// BigQuery and tells the token's creator. Unverified burns are dropped.
+#[cfg(not(feature = "local-bin"))]
+#[instrument(skip(app_state, event))]
+pub async fn record_token_burn(
+    app_state: &AppState,
+    burn: TokenBurnPayload,
+    event: Event,
+) -> Result<(), anyhow::Error> {
+    let Some(block_index) = burn.block_index else {
+        return verify_burn(&burn, None);
+    };
+    let ledger_burn = ledger_burn(app_state, burn.token_root, block_index).await?;
+    verify_burn(&burn, ledger_burn.as_ref())?;
+
+    let redis_pool = &app_state.canister_backup_redis_pool;
+    let block_key = token_burn_block_key(burn.token_root, block_index);
+    if !redis_pool
+        .set_if_absent(&block_key, TOKEN_BURN_REPLAY_TTL_SECS)
+        .await?
+    {
+        log::info!(
+            "Burn in block {} of token {} already recorded",
+            block_index,
+            burn.token_root
+        );
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let recorded = async {
+        let mut conn = redis_pool.get().await?;
+        redis::cmd("ZADD")
+            .arg(token_burns_key(burn.token_root))
+            .arg(timestamp)
+            .arg(token_burn_member(&burn, timestamp))
+            .query_async::<()>(&mut *conn)
+            .await?;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    if let Err(e) = recorded {
+        if let Err(release_err) = redis_pool.remove(&block_key).await {
+            log::error!("Failed to release token burn block claim: {}", release_err);
+        }
+        return Err(e);
+    }
+
+    // the pipeline skipped streaming the event, opted out users stay out of BigQuery here too
+    let opted_out = match Principal::from_text(&burn.user_id) {
+        Ok(user) => opt_out::is_user_opted_out(redis_pool, user).await,
+        Err(_) => false,
+    };
+    if !opted_out {
+        event.stream_to_bigquery(app_state);
+    }
+
+    let creator: Option<TokenCreator> = app_state
+        .firestoredb
+        .fluent()
+        .select()
+        .by_id_in("tokens-list")
+        .obj()
+        .one(burn.token_root.to_text())
+        .await?;
+    match creator {
+        Some(creator) => {
+            let event = creator_burn_notification_event(&creator.user_id, &burn);
+            subscribe::publish_event(&app_state.event_subscribers, &event.event, &event.params);
+        }
+        None => log::warn!("No creator found for token {}", burn.token_root),
+    }
+
+    log::info!(
+        "Recorded burn of {} e8s of token {} in block {}",
+        burn.amount_burned_e8s,
+        burn.token_root,
+        block_index
+    );
+
+    Ok(())
+}
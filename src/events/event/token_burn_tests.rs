@@ -0,0 +1,155 @@
+use candid::Principal;
+use serde_json::{json, Value};
+
+use super::token_burn::{
+    creator_burn_notification_event, token_burn_block_key, token_burn_member, token_burns_key,
+    verify_burn, LedgerBurn,
+};
+use crate::events::{
+    event::Event,
+    types::{AnalyticsEvent, TokenBurnPayload},
+    warehouse_events::WarehouseEvent,
+};
+
+fn token_root() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+fn burn(amount_burned_e8s: u64, new_total_supply_e8s: u64) -> TokenBurnPayload {
+    TokenBurnPayload {
+        user_id: "2vxsx-fae".into(),
+        canister_id: Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap(),
+        token_root: token_root(),
+        amount_burned_e8s,
+        new_total_supply_e8s,
+        block_index: Some(42),
+    }
+}
+
+fn canister() -> Principal {
+    Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap()
+}
+
+fn ledger_burn(from: Principal, amount_e8s: u64) -> LedgerBurn {
+    LedgerBurn { from, amount_e8s }
+}
+
+#[test]
+fn test_burn_matching_ledger_block_is_verified() {
+    assert!(verify_burn(&burn(5_000, 995_000), Some(&ledger_burn(canister(), 5_000))).is_ok());
+
+    let user = Principal::from_text("2vxsx-fae").unwrap();
+    assert!(verify_burn(&burn(5_000, 995_000), Some(&ledger_burn(user, 5_000))).is_ok());
+}
+
+#[test]
+fn test_burn_with_wrong_amount_is_rejected() {
+    let err =
+        verify_burn(&burn(5_000, 995_000), Some(&ledger_burn(canister(), 4_000))).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "reported burn of 5000 e8s, block 42 burned 4000 e8s"
+    );
+}
+
+#[test]
+fn test_burn_by_someone_else_is_rejected() {
+    let other = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+
+    assert!(verify_burn(&burn(5_000, 995_000), Some(&ledger_burn(other, 5_000))).is_err());
+}
+
+#[test]
+fn test_block_that_is_not_a_burn_is_rejected() {
+    assert!(verify_burn(&burn(5_000, 995_000), None).is_err());
+}
+
+#[test]
+fn test_burn_without_block_index_is_rejected() {
+    let mut burn = burn(5_000, 995_000);
+    burn.block_index = None;
+
+    let err = verify_burn(&burn, Some(&ledger_burn(canister(), 5_000))).unwrap_err();
+    assert_eq!(err.to_string(), "burn without ledger block index");
+}
+
+#[test]
+fn test_empty_burn_is_rejected() {
+    assert!(verify_burn(&burn(0, 1_000_000), Some(&ledger_burn(canister(), 0))).is_err());
+}
+
+#[test]
+fn test_burn_block_key() {
+    assert_eq!(
+        token_burn_block_key(token_root(), 42),
+        "token_burn_block:rrkah-fqaaa-aaaaa-aaaaq-cai:42"
+    );
+}
+
+#[test]
+fn test_burn_events_are_streamed_after_verification() {
+    let event = |name: &str| {
+        Event::new(WarehouseEvent {
+            event: name.into(),
+            params: "{}".into(),
+        })
+    };
+
+    assert!(event("token_burn").is_verified_before_streaming());
+    assert!(!event("like_video").is_verified_before_streaming());
+}
+
+#[test]
+fn test_token_burns_key() {
+    assert_eq!(
+        token_burns_key(token_root()),
+        "token_burns:rrkah-fqaaa-aaaaa-aaaaq-cai"
+    );
+}
+
+#[test]
+fn test_token_burn_member_is_unique_per_burn() {
+    let member: Value =
+        serde_json::from_str(&token_burn_member(&burn(5_000, 995_000), 1_700_000_000)).unwrap();
+
+    assert_eq!(
+        member,
+        json!({
+            "user_id": "2vxsx-fae",
+            "amount_burned_e8s": 5_000,
+            "new_total_supply_e8s": 995_000,
+            "block_index": 42,
+            "timestamp": 1_700_000_000,
+        })
+    );
+}
+
+#[test]
+fn test_creator_notification() {
+    let event = creator_burn_notification_event("creator-principal", &burn(250_000_000, 0));
+    let params: Value = serde_json::from_str(&event.params).unwrap();
+
+    assert_eq!(event.event, "token_burned");
+    assert_eq!(params["user_id"], "creator-principal");
+    assert_eq!(params["message"], "2.5 of your tokens were burned");
+}
+
+#[test]
+fn test_token_burn_analytics_event() {
+    let event: AnalyticsEvent = serde_json::from_value(json!({
+        "event": "TokenBurn",
+        "user_id": "2vxsx-fae",
+        "canister_id": "ryjl3-tyaaa-aaaaa-aaaba-cai",
+        "token_root": "rrkah-fqaaa-aaaaa-aaaaq-cai",
+        "amount_burned_e8s": 5_000,
+        "new_total_supply_e8s": 995_000,
+    }))
+    .unwrap();
+
+    assert!(matches!(event, AnalyticsEvent::TokenBurn(_)));
+    assert_eq!(
+        yral_metrics::metrics::sealed_metric::SealedMetric::tag(&event),
+        "token_burn"
+    );
+}
@@ -0,0 +1,217 @@
+use std::{env, sync::Arc};
+
+use axum::{extract::State, Json};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use candid::Principal;
+use google_cloud_bigquery::http::job::query::QueryRequest;
+use serde::{Deserialize, Serialize};
+use tonic::{
+    metadata::MetadataValue,
+    transport::{Channel, ClientTlsConfig},
+    Request,
+};
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    config::NsfwConfig,
+    consts::NSFW_SERVER_URL,
+    events::nsfw::{nsfw_detector, NSFWInfo},
+    AppError,
+};
+
+use super::TokenListItem;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateTokenMetadataRequest {
+    pub token_root: Principal,
+    pub logo_url: String,
+}
+
+#[instrument(skip(config))]
+pub async fn get_image_nsfw_info(
+    image: &[u8],
+    config: &NsfwConfig,
+) -> Result<NSFWInfo, anyhow::Error> {
+    // create a new connection everytime and depend on fly proxy to load balance
+    let tls_config = ClientTlsConfig::new().with_webpki_roots();
+    let channel = Channel::from_static(NSFW_SERVER_URL)
+        .tls_config(tls_config)?
+        .connect()
+        .await?;
+
+    let nsfw_grpc_auth_token = env::var("NSFW_GRPC_TOKEN").expect("NSFW_GRPC_TOKEN");
+    let token: MetadataValue<_> = format!("Bearer {}", nsfw_grpc_auth_token).parse()?;
+
+    let mut client = nsfw_detector::nsfw_detector_client::NsfwDetectorClient::with_interceptor(
+        channel,
+        move |mut req: Request<()>| {
+            req.metadata_mut().insert("authorization", token.clone());
+            Ok(req)
+        },
+    );
+
+    let req = tonic::Request::new(nsfw_detector::NsfwDetectorRequestImg {
+        image: STANDARD.encode(image),
+    });
+    let res = client.detect_nsfw_img(req).await?;
+
+    Ok(NSFWInfo::from_response(res.into_inner(), config))
+}
+
+/// BigQuery update of the token's `is_nsfw`, token links are `/token/info/ROOT_ID/USER_PRINCIPAL`
+pub fn token_metadata_nsfw_query(token_root: Principal, is_nsfw: bool) -> String {
+    format!(
+        "UPDATE `hot-or-not-feed-intelligence.icpumpfun.token_metadata_v1`
+         SET is_nsfw = {}
+         WHERE SPLIT(link, '/')[SAFE_OFFSET(3)] = '{}'",
+        is_nsfw,
+        token_root.to_text()
+    )
+}
+
+/// Side effects of refreshing a token logo's classification
+pub(crate) trait TokenMetadataStore {
+    async fn fetch_logo(&self, logo_url: &str) -> Result<Vec<u8>, anyhow::Error>;
+
+    async fn classify_logo(&self, image: &[u8]) -> Result<NSFWInfo, anyhow::Error>;
+
+    async fn get_token(
+        &self,
+        token_root: Principal,
+    ) -> Result<Option<TokenListItem>, anyhow::Error>;
+
+    async fn put_token(
+        &self,
+        token_root: Principal,
+        token: &TokenListItem,
+    ) -> Result<(), anyhow::Error>;
+
+    async fn set_token_metadata_nsfw(
+        &self,
+        token_root: Principal,
+        is_nsfw: bool,
+    ) -> Result<(), anyhow::Error>;
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl TokenMetadataStore for AppState {
+    async fn fetch_logo(&self, logo_url: &str) -> Result<Vec<u8>, anyhow::Error> {
+        let image = reqwest::get(logo_url)
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        Ok(image.to_vec())
+    }
+
+    async fn classify_logo(&self, image: &[u8]) -> Result<NSFWInfo, anyhow::Error> {
+        get_image_nsfw_info(image, &self.nsfw_config).await
+    }
+
+    async fn get_token(
+        &self,
+        token_root: Principal,
+    ) -> Result<Option<TokenListItem>, anyhow::Error> {
+        let token = self
+            .firestoredb
+            .fluent()
+            .select()
+            .by_id_in("tokens-list")
+            .obj()
+            .one(&token_root.to_text())
+            .await?;
+
+        Ok(token)
+    }
+
+    async fn put_token(
+        &self,
+        token_root: Principal,
+        token: &TokenListItem,
+    ) -> Result<(), anyhow::Error> {
+        let _: TokenListItem = self
+            .firestoredb
+            .fluent()
+            .update()
+            .in_col("tokens-list")
+            .document_id(token_root.to_text())
+            .object(token)
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_token_metadata_nsfw(
+        &self,
+        token_root: Principal,
+        is_nsfw: bool,
+    ) -> Result<(), anyhow::Error> {
+        let request = QueryRequest {
+            query: token_metadata_nsfw_query(token_root, is_nsfw),
+            ..Default::default()
+        };
+        self.bigquery_client
+            .job()
+            .query("hot-or-not-feed-intelligence", &request)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Classifies the logo again and stores the result in the token's `tokens-list` document and
+/// BigQuery metadata row
+pub async fn update_token_metadata_impl(
+    store: &impl TokenMetadataStore,
+    req: &UpdateTokenMetadataRequest,
+) -> Result<NSFWInfo, AppError> {
+    let Some(mut token) = store.get_token(req.token_root).await? else {
+        return Err(AppError::NotFound(format!(
+            "token {} not found in tokens-list",
+            req.token_root
+        )));
+    };
+
+    let image = store.fetch_logo(&req.logo_url).await?;
+    let nsfw_info = store.classify_logo(&image).await?;
+
+    token.is_nsfw = nsfw_info.is_nsfw;
+    token.nsfw_ec = nsfw_info.nsfw_ec.clone();
+    token.nsfw_gore = nsfw_info.nsfw_gore.clone();
+    store.put_token(req.token_root, &token).await?;
+
+    store
+        .set_token_metadata_nsfw(req.token_root, nsfw_info.is_nsfw)
+        .await
+        .map_err(AppError::BigQueryError)?;
+
+    log::info!(
+        "Updated token {} nsfw classification: is_nsfw={}",
+        req.token_root,
+        nsfw_info.is_nsfw
+    );
+
+    Ok(nsfw_info)
+}
+
+/// Re-runs NSFW classification for a token logo and updates the stored classification
+#[instrument(skip(state))]
+pub async fn update_token_metadata(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UpdateTokenMetadataRequest>,
+) -> Result<Json<NSFWInfo>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let nsfw_info = update_token_metadata_impl(state.as_ref(), &req).await?;
+        Ok(Json(nsfw_info))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, req);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
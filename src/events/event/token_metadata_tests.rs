@@ -0,0 +1,191 @@
+use std::sync::Mutex;
+
+use candid::Principal;
+use chrono::{TimeZone, Utc};
+
+use super::{
+    token_metadata::{
+        token_metadata_nsfw_query, update_token_metadata_impl, TokenMetadataStore,
+        UpdateTokenMetadataRequest,
+    },
+    TokenListItem,
+};
+use crate::{events::nsfw::NSFWInfo, AppError};
+
+fn token_root() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+fn request() -> UpdateTokenMetadataRequest {
+    UpdateTokenMetadataRequest {
+        token_root: token_root(),
+        logo_url: "https://example.com/logo.png".into(),
+    }
+}
+
+fn token() -> TokenListItem {
+    TokenListItem {
+        user_id: "2vxsx-fae".into(),
+        name: "Token".into(),
+        token_name: "Token".into(),
+        token_symbol: "TKN".into(),
+        logo: "https://example.com/logo.png".into(),
+        description: "a token".into(),
+        created_at: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+        link: format!("/token/info/{}/2vxsx-fae", token_root()),
+        is_nsfw: false,
+        nsfw_ec: "neutral".into(),
+        nsfw_gore: "UNKNOWN".into(),
+    }
+}
+
+fn nsfw_logo() -> NSFWInfo {
+    NSFWInfo {
+        is_nsfw: true,
+        nsfw_ec: "explicit".into(),
+        nsfw_gore: "UNLIKELY".into(),
+        csam_detected: false,
+    }
+}
+
+#[derive(Default)]
+struct MockStore {
+    token: Mutex<Option<TokenListItem>>,
+    classification: NSFWInfo,
+    fail_fetch: bool,
+    fail_bigquery: bool,
+    fetched: Mutex<Vec<String>>,
+    bigquery_updates: Mutex<Vec<(Principal, bool)>>,
+}
+
+impl TokenMetadataStore for MockStore {
+    async fn fetch_logo(&self, logo_url: &str) -> Result<Vec<u8>, anyhow::Error> {
+        if self.fail_fetch {
+            return Err(anyhow::anyhow!("404 Not Found"));
+        }
+        self.fetched.lock().unwrap().push(logo_url.to_string());
+        Ok(vec![0x89, 0x50, 0x4e, 0x47])
+    }
+
+    async fn classify_logo(&self, _image: &[u8]) -> Result<NSFWInfo, anyhow::Error> {
+        Ok(self.classification.clone())
+    }
+
+    async fn get_token(
+        &self,
+        _token_root: Principal,
+    ) -> Result<Option<TokenListItem>, anyhow::Error> {
+        Ok(self.token.lock().unwrap().clone())
+    }
+
+    async fn put_token(
+        &self,
+        _token_root: Principal,
+        token: &TokenListItem,
+    ) -> Result<(), anyhow::Error> {
+        *self.token.lock().unwrap() = Some(token.clone());
+        Ok(())
+    }
+
+    async fn set_token_metadata_nsfw(
+        &self,
+        token_root: Principal,
+        is_nsfw: bool,
+    ) -> Result<(), anyhow::Error> {
+        if self.fail_bigquery {
+            return Err(anyhow::anyhow!("quota exceeded"));
+        }
+        self.bigquery_updates
+            .lock()
+            .unwrap()
+            .push((token_root, is_nsfw));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_classification_is_stored() {
+    let store = MockStore {
+        token: Mutex::new(Some(token())),
+        classification: nsfw_logo(),
+        ..Default::default()
+    };
+
+    let nsfw_info = update_token_metadata_impl(&store, &request())
+        .await
+        .unwrap();
+
+    assert!(nsfw_info.is_nsfw);
+    assert_eq!(
+        *store.fetched.lock().unwrap(),
+        vec!["https://example.com/logo.png".to_string()]
+    );
+
+    let stored = store.token.lock().unwrap().clone().unwrap();
+    assert!(stored.is_nsfw);
+    assert_eq!(stored.nsfw_ec, "explicit");
+    assert_eq!(stored.nsfw_gore, "UNLIKELY");
+    assert_eq!(stored.token_symbol, "TKN");
+
+    assert_eq!(
+        *store.bigquery_updates.lock().unwrap(),
+        vec![(token_root(), true)]
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_token_is_not_found() {
+    let store = MockStore {
+        classification: nsfw_logo(),
+        ..Default::default()
+    };
+
+    let err = update_token_metadata_impl(&store, &request())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, AppError::NotFound(_)));
+    assert!(store.fetched.lock().unwrap().is_empty());
+    assert!(store.bigquery_updates.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_failed_logo_download_changes_nothing() {
+    let store = MockStore {
+        token: Mutex::new(Some(token())),
+        classification: nsfw_logo(),
+        fail_fetch: true,
+        ..Default::default()
+    };
+
+    assert!(update_token_metadata_impl(&store, &request())
+        .await
+        .is_err());
+
+    assert!(!store.token.lock().unwrap().as_ref().unwrap().is_nsfw);
+    assert!(store.bigquery_updates.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_failed_bigquery_update_is_a_bigquery_error() {
+    let store = MockStore {
+        token: Mutex::new(Some(token())),
+        classification: nsfw_logo(),
+        fail_bigquery: true,
+        ..Default::default()
+    };
+
+    let err = update_token_metadata_impl(&store, &request())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, AppError::BigQueryError(_)));
+}
+
+#[test]
+fn test_nsfw_query_matches_token_link() {
+    let query = token_metadata_nsfw_query(token_root(), true);
+
+    assert!(query.contains("SET is_nsfw = true"));
+    assert!(query.contains("SPLIT(link, '/')[SAFE_OFFSET(3)] = 'rrkah-fqaaa-aaaaa-aaaaq-cai'"));
+}
@@ -0,0 +1,159 @@
+use candid::Principal;
+use serde::Deserialize;
+use serde_json::json;
+#[cfg(not(feature = "local-bin"))]
+use tracing::instrument;
+
+use crate::events::warehouse_events::WarehouseEvent;
+#[cfg(not(feature = "local-bin"))]
+use crate::{
+    app_state::AppState,
+    events::{event::Event, subscribe},
+    types::RedisPool,
+};
+
+/// View counts announced to the publisher, ascending
+pub const VIEW_MILESTONES: [u64; 3] = [1_000, 10_000, 100_000];
+/// A post's view count is read from its canister at most once per interval
+pub const VIEW_MILESTONE_CHECK_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct VideoViewParams {
+    pub publisher_canister_id: Principal,
+    pub post_id: u64,
+}
+
+pub fn view_milestone_key(
+    publisher_canister_id: Principal,
+    post_id: u64,
+    milestone: u64,
+) -> String {
+    format!(
+        "view_milestone:{}:{}:{}",
+        publisher_canister_id, post_id, milestone
+    )
+}
+
+pub fn view_milestone_check_key(publisher_canister_id: Principal, post_id: u64) -> String {
+    format!("view_milestone_check:{}:{}", publisher_canister_id, post_id)
+}
+
+/// Milestones at or below the view count, lowest first
+pub fn reached_milestones(view_count: u64) -> Vec<u64> {
+    VIEW_MILESTONES
+        .into_iter()
+        .filter(|milestone| view_count >= *milestone)
+        .collect()
+}
+
+/// 1000 as `1K`, 100000 as `100K`
+pub fn format_milestone(milestone: u64) -> String {
+    if milestone >= 1_000 && milestone % 1_000 == 0 {
+        format!("{}K", milestone / 1_000)
+    } else {
+        milestone.to_string()
+    }
+}
+
+pub fn milestone_message(milestone: u64) -> String {
+    format!("Your video just hit {} views!", format_milestone(milestone))
+}
+
+/// `post_viewed_threshold` event, its `user_id` is the publisher so it reaches their open SSE connections
+pub fn post_viewed_threshold_event(
+    publisher: Principal,
+    params: &VideoViewParams,
+    milestone: u64,
+    view_count: u64,
+) -> WarehouseEvent {
+    WarehouseEvent {
+        event: "post_viewed_threshold".into(),
+        params: json!({
+            "user_id": publisher,
+            "publisher_canister_id": params.publisher_canister_id,
+            "post_id": params.post_id,
+            "milestone": milestone,
+            "view_count": view_count,
+            "message": milestone_message(milestone),
+        })
+        .to_string(),
+    }
+}
+
+/// Sets the key if it does not exist yet, returns whether it was set
+#[cfg(not(feature = "local-bin"))]
+async fn claim_key(
+    redis_pool: &RedisPool,
+    key: &str,
+    ttl_secs: Option<u64>,
+) -> Result<bool, anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    let mut cmd = redis::cmd("SET");
+    cmd.arg(key).arg(1).arg("NX");
+    if let Some(ttl_secs) = ttl_secs {
+        cmd.arg("EX").arg(ttl_secs);
+    }
+    let res: Option<String> = cmd.query_async(&mut *conn).await?;
+
+    Ok(res.is_some())
+}
+
+/// Reads the post's view count and announces the highest milestone crossed since the last check.
+/// Lower milestones crossed at the same time are only recorded, so old posts announce once.
+#[cfg(not(feature = "local-bin"))]
+#[instrument(skip(app_state))]
+pub async fn check_view_milestones(
+    app_state: &AppState,
+    params: VideoViewParams,
+) -> Result<(), anyhow::Error> {
+    let redis_pool = &app_state.canister_backup_redis_pool;
+
+    let check_key = view_milestone_check_key(params.publisher_canister_id, params.post_id);
+    if !claim_key(
+        redis_pool,
+        &check_key,
+        Some(VIEW_MILESTONE_CHECK_INTERVAL_SECS),
+    )
+    .await?
+    {
+        return Ok(());
+    }
+
+    let post = app_state
+        .individual_user(params.publisher_canister_id)
+        .get_individual_post_details_by_id(params.post_id)
+        .await?;
+
+    let mut announce = None;
+    for milestone in reached_milestones(post.total_view_count) {
+        let key = view_milestone_key(params.publisher_canister_id, params.post_id, milestone);
+        if claim_key(redis_pool, &key, None).await? {
+            announce = Some(milestone);
+        }
+    }
+    let Some(milestone) = announce else {
+        return Ok(());
+    };
+
+    let event = Event::new(post_viewed_threshold_event(
+        post.created_by_user_principal_id,
+        &params,
+        milestone,
+        post.total_view_count,
+    ));
+    subscribe::publish_event(
+        &app_state.event_subscribers,
+        &event.event.event,
+        &event.event.params,
+    );
+    event.stream_to_bigquery(app_state);
+
+    log::info!(
+        "Post {}/{} crossed {} views",
+        params.publisher_canister_id,
+        params.post_id,
+        milestone
+    );
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use candid::Principal;
+use serde_json::Value;
+
+use super::view_milestone::{
+    format_milestone, milestone_message, post_viewed_threshold_event, reached_milestones,
+    view_milestone_key, VideoViewParams,
+};
+
+fn canister() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+#[test]
+fn test_reached_milestones() {
+    assert!(reached_milestones(0).is_empty());
+    assert!(reached_milestones(999).is_empty());
+    assert_eq!(reached_milestones(1_000), vec![1_000]);
+    assert_eq!(reached_milestones(9_999), vec![1_000]);
+    assert_eq!(reached_milestones(10_000), vec![1_000, 10_000]);
+    assert_eq!(reached_milestones(5_000_000), vec![1_000, 10_000, 100_000]);
+}
+
+#[test]
+fn test_view_milestone_key_is_per_post_and_milestone() {
+    assert_eq!(
+        view_milestone_key(canister(), 7, 1_000),
+        "view_milestone:rrkah-fqaaa-aaaaa-aaaaq-cai:7:1000"
+    );
+    assert_ne!(
+        view_milestone_key(canister(), 7, 1_000),
+        view_milestone_key(canister(), 7, 10_000)
+    );
+}
+
+#[test]
+fn test_milestone_message() {
+    assert_eq!(format_milestone(1_000), "1K");
+    assert_eq!(format_milestone(100_000), "100K");
+    assert_eq!(format_milestone(1_500), "1500");
+    assert_eq!(milestone_message(10_000), "Your video just hit 10K views!");
+}
+
+#[test]
+fn test_post_viewed_threshold_event_targets_publisher() {
+    let publisher = Principal::anonymous();
+    let params = VideoViewParams {
+        publisher_canister_id: canister(),
+        post_id: 7,
+    };
+
+    let event = post_viewed_threshold_event(publisher, &params, 1_000, 1_234);
+    let event_params: Value = serde_json::from_str(&event.params).unwrap();
+
+    assert_eq!(event.event, "post_viewed_threshold");
+    assert_eq!(event_params["user_id"], publisher.to_text());
+    assert_eq!(event_params["publisher_canister_id"], canister().to_text());
+    assert_eq!(event_params["milestone"], 1_000);
+    assert_eq!(event_params["view_count"], 1_234);
+}
@@ -0,0 +1,281 @@
+#[cfg(not(feature = "local-bin"))]
+use candid::Nat;
+use candid::Principal;
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+#[cfg(not(feature = "local-bin"))]
+use tracing::instrument;
+#[cfg(not(feature = "local-bin"))]
+use yral_canisters_client::sns_ledger::{
+    Account as LedgerAccount, SnsLedger, TransferArg, TransferError, TransferResult,
+};
+use yral_metrics::metrics::sealed_metric::SealedMetric;
+
+#[cfg(not(feature = "local-bin"))]
+use crate::{
+    app_state::AppState,
+    consts::{CCAT_LEDGER_CANISTER_ID, WATCH_REWARD_E8S},
+    events::{event::Event, subscribe},
+    types::RedisPool,
+    AppError,
+};
+use crate::{
+    events::{
+        types::{AnalyticsEvent, WatchVideoRewardPayload},
+        warehouse_events::WarehouseEvent,
+    },
+    utils::rate_limit::RateLimit,
+};
+
+/// One reward per user per video per day
+pub const WATCH_REWARD_TTL_SECS: u64 = 24 * 60 * 60;
+/// A watch counts as completed from this share of the video
+pub const WATCH_REWARD_MIN_PERCENTAGE: f64 = 95.0;
+/// Rewards a user can be paid per day, whatever the videos
+pub const WATCH_REWARD_DAILY_LIMIT: RateLimit = RateLimit {
+    name: "watch_reward",
+    max_requests: 50,
+    window_secs: WATCH_REWARD_TTL_SECS,
+};
+/// Rewards above this are announced to the user's open SSE connections
+pub const WATCH_REWARD_NOTIFY_ABOVE_E8S: u64 = 1_000;
+
+/// A completed watch reported by a verified caller, see `verify_event_bulk_request`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletedWatch {
+    pub user_id: Principal,
+    pub canister_id: Principal,
+    pub video_id: String,
+    pub publisher_canister_id: Principal,
+    pub post_id: u64,
+}
+
+impl CompletedWatch {
+    /// The completed watch of a `VideoDurationWatched` event, the reward is never taken from
+    /// the client
+    pub fn from_event(event: &AnalyticsEvent) -> Option<Self> {
+        let AnalyticsEvent::VideoDurationWatched(_) = event else {
+            return None;
+        };
+
+        Self::from_params(&event.params(), event.user_id(), event.user_canister())
+    }
+
+    pub fn from_params(
+        params: &Value,
+        user_id: Option<String>,
+        canister_id: Option<Principal>,
+    ) -> Option<Self> {
+        if params["percentage_watched"].as_f64()? < WATCH_REWARD_MIN_PERCENTAGE {
+            return None;
+        }
+
+        Some(Self {
+            user_id: Principal::from_text(user_id?).ok()?,
+            canister_id: canister_id?,
+            video_id: params["video_id"].as_str()?.to_string(),
+            publisher_canister_id: Principal::from_text(params["publisher_canister_id"].as_str()?)
+                .ok()?,
+            post_id: params["post_id"].as_u64()?,
+        })
+    }
+}
+
+/// Checks the watched post is the video's and was not published by the watcher, the event is
+/// only taken as a pointer to the post in the publisher canister
+pub fn check_watched_post(
+    watch: &CompletedWatch,
+    post_video_uid: &str,
+    post_creator: Principal,
+) -> Result<(), String> {
+    if post_video_uid != watch.video_id {
+        return Err(format!(
+            "post {} of {} is not video {}",
+            watch.post_id, watch.publisher_canister_id, watch.video_id
+        ));
+    }
+    if post_creator == watch.user_id {
+        return Err(format!(
+            "video {} was published by its watcher {}",
+            watch.video_id, watch.user_id
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn should_notify_watch_reward(reward_e8s: u64) -> bool {
+    reward_e8s > WATCH_REWARD_NOTIFY_ABOVE_E8S
+}
+
+/// `watch_video_reward` event of a paid reward, its `user_id` routes it to the user's open SSE
+/// connections
+pub fn watch_video_reward_event(watch: &CompletedWatch, reward_e8s: u64) -> WarehouseEvent {
+    let payload = WatchVideoRewardPayload {
+        user_id: watch.user_id.to_text(),
+        canister_id: watch.canister_id,
+        video_id: watch.video_id.clone(),
+        post_id: watch.post_id,
+        reward_e8s,
+    };
+
+    WarehouseEvent {
+        event: payload.tag(),
+        params: json!(payload).to_string(),
+    }
+}
+
+pub fn watch_reward_key(user_id: &str, video_id: &str) -> String {
+    format!("watch_reward:{}:{}", user_id, video_id)
+}
+
+/// Memo of the day's reward transfer, the ledger rejects a second transfer with the same memo
+/// and `created_at_time` as a duplicate
+pub fn watch_reward_memo(user_id: &str, video_id: &str, day: NaiveDate) -> Vec<u8> {
+    blake3::hash(format!("watch_reward:{}:{}:{}", user_id, video_id, day).as_bytes())
+        .as_bytes()
+        .to_vec()
+}
+
+/// Start of the day in nanoseconds, always inside the ledger's 24h deduplication window
+pub fn watch_reward_created_at_time(day: NaiveDate) -> u64 {
+    day.and_hms_opt(0, 0, 0)
+        .and_then(|start| start.and_utc().timestamp_nanos_opt())
+        .unwrap_or_default() as u64
+}
+
+/// Claims the reward slot, returns false if the user was already rewarded for the video today
+#[cfg(not(feature = "local-bin"))]
+async fn claim_watch_reward(
+    redis_pool: &RedisPool,
+    user_id: &str,
+    video_id: &str,
+) -> Result<bool, anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    let res: Option<String> = redis::cmd("SET")
+        .arg(watch_reward_key(user_id, video_id))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(WATCH_REWARD_TTL_SECS)
+        .query_async(&mut *conn)
+        .await?;
+
+    Ok(res.is_some())
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn release_watch_reward(
+    redis_pool: &RedisPool,
+    user_id: &str,
+    video_id: &str,
+) -> Result<(), anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    redis::cmd("DEL")
+        .arg(watch_reward_key(user_id, video_id))
+        .query_async::<()>(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Transfers [`WATCH_REWARD_E8S`] from the platform treasury to the user canister once the
+/// watched post is found in its publisher canister, at most [`WATCH_REWARD_DAILY_LIMIT`] times
+/// a day per user
+#[cfg(not(feature = "local-bin"))]
+#[instrument(skip(app_state))]
+pub async fn reward_watch_video(
+    app_state: &AppState,
+    watch: CompletedWatch,
+) -> Result<(), anyhow::Error> {
+    let redis_pool = &app_state.canister_backup_redis_pool;
+    let user_id = watch.user_id.to_text();
+    if !claim_watch_reward(redis_pool, &user_id, &watch.video_id).await? {
+        log::info!(
+            "User {} already rewarded for video {} today",
+            user_id,
+            watch.video_id
+        );
+        return Ok(());
+    }
+
+    // counted before the post lookup, so made up videos also use up the user's rewards
+    let now_secs = chrono::Utc::now().timestamp() as u64;
+    if let Err(AppError::RateLimited { .. }) = WATCH_REWARD_DAILY_LIMIT
+        .check(redis_pool, &user_id, now_secs)
+        .await
+    {
+        log::warn!("User {} reached the daily watch reward limit", user_id);
+        return Ok(());
+    }
+
+    let post = app_state
+        .individual_user(watch.publisher_canister_id)
+        .get_individual_post_details_by_id(watch.post_id)
+        .await?;
+    check_watched_post(&watch, &post.video_uid, post.created_by_user_principal_id)
+        .map_err(anyhow::Error::msg)?;
+
+    let reward_e8s = *WATCH_REWARD_E8S;
+    let today = chrono::Utc::now().date_naive();
+    let ledger = SnsLedger(*CCAT_LEDGER_CANISTER_ID, &app_state.agent);
+    let transfer_res = ledger
+        .icrc_1_transfer(TransferArg {
+            to: LedgerAccount {
+                owner: watch.canister_id,
+                subaccount: None,
+            },
+            fee: None,
+            memo: Some(watch_reward_memo(&user_id, &watch.video_id, today).into()),
+            from_subaccount: None,
+            amount: Nat::from(reward_e8s),
+            created_at_time: Some(watch_reward_created_at_time(today)),
+        })
+        .await;
+
+    let error = match transfer_res {
+        Ok(TransferResult::Ok(_)) => None,
+        Ok(TransferResult::Err(TransferError::Duplicate { .. })) => {
+            log::info!(
+                "Watch reward of {} for video {} was already transferred today",
+                user_id,
+                watch.video_id
+            );
+            return Ok(());
+        }
+        Ok(TransferResult::Err(e)) => Some(format!("{e:?}")),
+        Err(e) => Some(e.to_string()),
+    };
+
+    if let Some(e) = error {
+        // the ledger deduplicates a retry of a transfer that did go through
+        if let Err(release_err) = release_watch_reward(redis_pool, &user_id, &watch.video_id).await
+        {
+            log::error!("Failed to release watch reward claim: {}", release_err);
+        }
+        return Err(anyhow::anyhow!(
+            "Failed to transfer watch reward to {}: {}",
+            watch.canister_id,
+            e
+        ));
+    }
+
+    log::info!(
+        "Rewarded {} e8s to {} for watching video {}",
+        reward_e8s,
+        watch.canister_id,
+        watch.video_id
+    );
+
+    let event = Event::new(watch_video_reward_event(&watch, reward_e8s));
+    if should_notify_watch_reward(reward_e8s) {
+        subscribe::publish_event(
+            &app_state.event_subscribers,
+            &event.event.event,
+            &event.event.params,
+        );
+    }
+    event.stream_to_bigquery(app_state);
+
+    Ok(())
+}
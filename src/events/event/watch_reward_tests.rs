@@ -0,0 +1,144 @@
+use candid::Principal;
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+
+use super::watch_reward::{
+    check_watched_post, should_notify_watch_reward, watch_reward_created_at_time, watch_reward_key,
+    watch_reward_memo, watch_video_reward_event, CompletedWatch, WATCH_REWARD_DAILY_LIMIT,
+};
+
+fn canister() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+fn publisher_canister() -> Principal {
+    Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap()
+}
+
+fn publisher() -> Principal {
+    Principal::from_text("aaaaa-aa").unwrap()
+}
+
+fn watch() -> CompletedWatch {
+    CompletedWatch {
+        user_id: Principal::anonymous(),
+        canister_id: canister(),
+        video_id: "vid1".into(),
+        publisher_canister_id: publisher_canister(),
+        post_id: 7,
+    }
+}
+
+#[test]
+fn test_watch_reward_key_is_per_user_and_video() {
+    assert_eq!(watch_reward_key("user1", "vid1"), "watch_reward:user1:vid1");
+    assert_ne!(
+        watch_reward_key("user1", "vid1"),
+        watch_reward_key("user1", "vid2")
+    );
+}
+
+#[test]
+fn test_only_completed_watches_are_rewarded() {
+    let params = |percentage: f64| {
+        json!({
+            "video_id": "vid1",
+            "publisher_canister_id": publisher_canister(),
+            "post_id": 7,
+            "percentage_watched": percentage,
+        })
+    };
+
+    assert_eq!(
+        CompletedWatch::from_params(&params(100.0), Some("2vxsx-fae".into()), Some(canister())),
+        Some(watch())
+    );
+    assert!(
+        CompletedWatch::from_params(&params(95.0), Some("2vxsx-fae".into()), Some(canister()))
+            .is_some()
+    );
+    assert!(
+        CompletedWatch::from_params(&params(94.9), Some("2vxsx-fae".into()), Some(canister()))
+            .is_none()
+    );
+    assert!(CompletedWatch::from_params(
+        &json!({ "video_id": "vid1" }),
+        Some("2vxsx-fae".into()),
+        Some(canister())
+    )
+    .is_none());
+    assert!(CompletedWatch::from_params(&params(100.0), None, Some(canister())).is_none());
+}
+
+#[test]
+fn test_reward_transfer_is_deduplicated_per_day() {
+    let day = NaiveDate::from_ymd_opt(2025, 1, 31).unwrap();
+    let next_day = day.succ_opt().unwrap();
+
+    let memo = watch_reward_memo("2vxsx-fae", "vid1", day);
+    assert_eq!(memo.len(), 32);
+    assert_eq!(memo, watch_reward_memo("2vxsx-fae", "vid1", day));
+    assert_ne!(memo, watch_reward_memo("2vxsx-fae", "vid2", day));
+    assert_ne!(memo, watch_reward_memo("2vxsx-fae", "vid1", next_day));
+
+    assert_eq!(watch_reward_created_at_time(day), 1_738_281_600_000_000_000);
+    assert_eq!(
+        watch_reward_created_at_time(next_day) - watch_reward_created_at_time(day),
+        24 * 60 * 60 * 1_000_000_000
+    );
+}
+
+#[test]
+fn test_watches_without_a_post_are_not_rewarded() {
+    assert!(CompletedWatch::from_params(
+        &json!({ "video_id": "vid1", "percentage_watched": 100.0 }),
+        Some("2vxsx-fae".into()),
+        Some(canister())
+    )
+    .is_none());
+}
+
+#[test]
+fn test_watched_post_must_be_the_video_of_another_user() {
+    assert!(check_watched_post(&watch(), "vid1", publisher()).is_ok());
+    assert!(check_watched_post(&watch(), "vid2", publisher()).is_err());
+    assert!(check_watched_post(&watch(), "vid1", Principal::anonymous()).is_err());
+}
+
+#[test]
+fn test_daily_limit_is_per_user_and_day() {
+    let day_secs = 1_738_281_600;
+
+    assert_eq!(
+        WATCH_REWARD_DAILY_LIMIT.key("2vxsx-fae", day_secs),
+        WATCH_REWARD_DAILY_LIMIT.key("2vxsx-fae", day_secs + 60 * 60)
+    );
+    assert_ne!(
+        WATCH_REWARD_DAILY_LIMIT.key("2vxsx-fae", day_secs),
+        WATCH_REWARD_DAILY_LIMIT.key("2vxsx-fae", day_secs + 24 * 60 * 60)
+    );
+    assert_ne!(
+        WATCH_REWARD_DAILY_LIMIT.key("2vxsx-fae", day_secs),
+        WATCH_REWARD_DAILY_LIMIT.key("aaaaa-aa", day_secs)
+    );
+}
+
+#[test]
+fn test_only_large_rewards_are_notified() {
+    assert!(!should_notify_watch_reward(500));
+    assert!(!should_notify_watch_reward(1_000));
+    assert!(should_notify_watch_reward(1_001));
+}
+
+#[test]
+fn test_watch_video_reward_event() {
+    let event = watch_video_reward_event(&watch(), 5_000);
+    let params: Value = serde_json::from_str(&event.params).unwrap();
+
+    assert_eq!(event.event, "watch_video_reward");
+    assert_eq!(params["user_id"], "2vxsx-fae");
+    assert_eq!(params["canister_id"], canister().to_text());
+    assert_eq!(params["video_id"], "vid1");
+    assert_eq!(params["post_id"], 7);
+    assert_eq!(params["reward_e8s"], 5_000);
+}
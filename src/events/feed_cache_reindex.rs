@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use axum::{extract::State, response::Response, Json};
+use candid::Principal;
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use yral_ml_feed_cache::{
+    consts::{
+        USER_SUCCESS_HISTORY_CLEAN_SUFFIX, USER_SUCCESS_HISTORY_NSFW_SUFFIX,
+        USER_WATCH_HISTORY_CLEAN_SUFFIX, USER_WATCH_HISTORY_NSFW_SUFFIX,
+    },
+    types::MLFeedCacheHistoryItem,
+};
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    config::NsfwConfig,
+};
+
+pub const FEED_CACHE_REINDEX_DAYS: u32 = 7;
+/// Same threshold as the live event handlers
+const SUCCESS_MIN_PERCENT_WATCHED: f64 = 30.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedCacheReindexRequest {
+    pub canister_id: Principal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEventRow {
+    pub event: String,
+    pub publisher_canister_id: String,
+    pub post_id: u64,
+    pub video_id: String,
+    pub nsfw_probability: f64,
+    pub percent_watched: f64,
+    pub timestamp_ms: u64,
+}
+
+/// History items grouped by cache key
+#[derive(Default)]
+pub struct ReindexedHistory {
+    pub watch: HashMap<String, Vec<MLFeedCacheHistoryItem>>,
+    pub success: HashMap<String, Vec<MLFeedCacheHistoryItem>>,
+}
+
+pub fn reindex_history_query(canister_id: Principal) -> String {
+    format!(
+        "SELECT
+            event,
+            JSON_EXTRACT_SCALAR(params, '$.publisher_canister_id') AS publisher_canister_id,
+            CAST(JSON_EXTRACT_SCALAR(params, '$.post_id') AS INT64) AS post_id,
+            JSON_EXTRACT_SCALAR(params, '$.video_id') AS video_id,
+            IFNULL(CAST(JSON_EXTRACT_SCALAR(params, '$.nsfw_probability') AS FLOAT64), 0) AS nsfw_probability,
+            IFNULL(CAST(JSON_EXTRACT_SCALAR(params, '$.percentage_watched') AS FLOAT64), 0) AS percent_watched,
+            UNIX_MILLIS(timestamp) AS timestamp_ms
+        FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+        WHERE event IN ('video_duration_watched', 'like_video')
+            AND JSON_EXTRACT_SCALAR(params, '$.canister_id') = '{}'
+            AND timestamp >= TIMESTAMP_SUB(CURRENT_TIMESTAMP(), INTERVAL {} DAY)
+        ORDER BY timestamp ASC",
+        canister_id.to_text(),
+        FEED_CACHE_REINDEX_DAYS
+    )
+}
+
+/// Rebuilds the cache items the live handlers would have written for these events
+pub fn build_history_items(
+    canister_id: Principal,
+    rows: Vec<HistoryEventRow>,
+    nsfw_config: &NsfwConfig,
+) -> ReindexedHistory {
+    let mut history = ReindexedHistory::default();
+
+    for row in rows {
+        let is_watch = row.event == "video_duration_watched";
+        let is_success = row.event == "like_video"
+            || (is_watch && row.percent_watched >= SUCCESS_MIN_PERCENT_WATCHED);
+        let is_clean = !nsfw_config.is_nsfw_probability(row.nsfw_probability as f32);
+
+        let item = MLFeedCacheHistoryItem {
+            canister_id: row.publisher_canister_id,
+            item_type: row.event,
+            nsfw_probability: row.nsfw_probability as f32,
+            post_id: row.post_id,
+            video_id: row.video_id,
+            timestamp: UNIX_EPOCH + Duration::from_millis(row.timestamp_ms),
+            percent_watched: row.percent_watched as f32,
+        };
+
+        if is_success {
+            let suffix = if is_clean {
+                USER_SUCCESS_HISTORY_CLEAN_SUFFIX
+            } else {
+                USER_SUCCESS_HISTORY_NSFW_SUFFIX
+            };
+            history
+                .success
+                .entry(format!("{}{}", canister_id, suffix))
+                .or_default()
+                .push(item.clone());
+        }
+
+        if is_watch {
+            let suffix = if is_clean {
+                USER_WATCH_HISTORY_CLEAN_SUFFIX
+            } else {
+                USER_WATCH_HISTORY_NSFW_SUFFIX
+            };
+            history
+                .watch
+                .entry(format!("{}{}", canister_id, suffix))
+                .or_default()
+                .push(item);
+        }
+    }
+
+    history
+}
+
+async fn fetch_history_events(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+    canister_id: Principal,
+) -> Result<Vec<HistoryEventRow>, anyhow::Error> {
+    let request = QueryRequest {
+        query: reindex_history_query(canister_id),
+        ..Default::default()
+    };
+    let mut response = bigquery_client
+        .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+        .await?;
+
+    let mut rows = Vec::new();
+    while let Some(row) = response.next().await? {
+        let (Some(publisher_canister_id), Some(post_id), Some(video_id)) = (
+            row.column::<Option<String>>(1)?,
+            row.column::<Option<i64>>(2)?,
+            row.column::<Option<String>>(3)?,
+        ) else {
+            continue;
+        };
+
+        rows.push(HistoryEventRow {
+            event: row.column::<String>(0)?,
+            publisher_canister_id,
+            post_id: post_id as u64,
+            video_id,
+            nsfw_probability: row.column::<f64>(4)?,
+            percent_watched: row.column::<f64>(5)?,
+            timestamp_ms: row.column::<i64>(6)? as u64,
+        });
+    }
+
+    Ok(rows)
+}
+
+/// Regenerates a user's watch and success history in the ML feed cache from BigQuery
+#[instrument(skip(state))]
+pub async fn reindex_user_feed_cache(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<FeedCacheReindexRequest>,
+) -> Result<Response, StatusCode> {
+    let rows = fetch_history_events(&state.bigquery_client, req.canister_id)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Failed to fetch feed history of {} from BigQuery: {}",
+                req.canister_id,
+                e
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let events = rows.len();
+    let history = build_history_items(req.canister_id, rows, &state.nsfw_config);
+
+    for (key, items) in history.watch {
+        state
+            .ml_feed_cache
+            .add_user_watch_history_items(&key, items)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to write watch history {}: {:?}", key, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    }
+    for (key, items) in history.success {
+        state
+            .ml_feed_cache
+            .add_user_success_history_items(&key, items)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to write success history {}: {:?}", key, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    }
+
+    log::info!(
+        "Reindexed feed cache of {} from {} events",
+        req.canister_id,
+        events
+    );
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(format!("Reindexed {} events", events).into())
+        .unwrap())
+}
+
+#[instrument(skip(state, token))]
+pub async fn feed_cache_reindex_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Json(req): Json<FeedCacheReindexRequest>,
+) -> Result<Response, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    state
+        .qstash_client
+        .publish_reindex_user_feed_cache(req.canister_id)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Failed to enqueue feed cache reindex for {}: {}",
+                req.canister_id,
+                e
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Response::builder()
+        .status(StatusCode::ACCEPTED)
+        .body("Feed cache reindex enqueued".into())
+        .unwrap())
+}
@@ -0,0 +1,138 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use candid::Principal;
+use yral_ml_feed_cache::consts::{
+    USER_SUCCESS_HISTORY_CLEAN_SUFFIX, USER_SUCCESS_HISTORY_NSFW_SUFFIX,
+    USER_WATCH_HISTORY_CLEAN_SUFFIX, USER_WATCH_HISTORY_NSFW_SUFFIX,
+};
+
+use super::feed_cache_reindex::{build_history_items, reindex_history_query, HistoryEventRow};
+use crate::config::NsfwConfig;
+
+fn canister() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+fn row(event: &str, nsfw_probability: f64, percent_watched: f64) -> HistoryEventRow {
+    HistoryEventRow {
+        event: event.to_string(),
+        publisher_canister_id: "ryjl3-tyaaa-aaaaa-aaaba-cai".to_string(),
+        post_id: 7,
+        video_id: "video".to_string(),
+        nsfw_probability,
+        percent_watched,
+        timestamp_ms: 1_700_000_000_000,
+    }
+}
+
+fn key(suffix: &str) -> String {
+    format!("{}{}", canister(), suffix)
+}
+
+#[test]
+fn test_query_filters_user_and_window() {
+    let query = reindex_history_query(canister());
+
+    assert!(query.contains("'rrkah-fqaaa-aaaaa-aaaaq-cai'"));
+    assert!(query.contains("'video_duration_watched', 'like_video'"));
+    assert!(query.contains("INTERVAL 7 DAY"));
+}
+
+#[test]
+fn test_watch_event_goes_to_watch_history() {
+    let history = build_history_items(
+        canister(),
+        vec![row("video_duration_watched", 0.1, 10.0)],
+        &NsfwConfig::default(),
+    );
+
+    let items = &history.watch[&key(USER_WATCH_HISTORY_CLEAN_SUFFIX)];
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].canister_id, "ryjl3-tyaaa-aaaaa-aaaba-cai");
+    assert_eq!(items[0].item_type, "video_duration_watched");
+    assert_eq!(items[0].percent_watched, 10.0);
+    assert_eq!(
+        items[0].timestamp,
+        UNIX_EPOCH + Duration::from_millis(1_700_000_000_000)
+    );
+    // short watches are not a success
+    assert!(history.success.is_empty());
+}
+
+#[test]
+fn test_long_watch_is_also_success() {
+    let history = build_history_items(
+        canister(),
+        vec![row("video_duration_watched", 0.1, 30.0)],
+        &NsfwConfig::default(),
+    );
+
+    assert_eq!(
+        history.watch[&key(USER_WATCH_HISTORY_CLEAN_SUFFIX)].len(),
+        1
+    );
+    assert_eq!(
+        history.success[&key(USER_SUCCESS_HISTORY_CLEAN_SUFFIX)].len(),
+        1
+    );
+}
+
+#[test]
+fn test_like_is_success_only() {
+    let history = build_history_items(
+        canister(),
+        vec![row("like_video", 0.1, 0.0)],
+        &NsfwConfig::default(),
+    );
+
+    assert!(history.watch.is_empty());
+    assert_eq!(
+        history.success[&key(USER_SUCCESS_HISTORY_CLEAN_SUFFIX)][0].item_type,
+        "like_video"
+    );
+}
+
+#[test]
+fn test_nsfw_items_use_nsfw_keys() {
+    let history = build_history_items(
+        canister(),
+        vec![
+            row("video_duration_watched", 0.9, 80.0),
+            row("like_video", 0.4, 0.0),
+            row("like_video", 0.39, 0.0),
+        ],
+        &NsfwConfig::default(),
+    );
+
+    assert_eq!(history.watch[&key(USER_WATCH_HISTORY_NSFW_SUFFIX)].len(), 1);
+    assert_eq!(
+        history.success[&key(USER_SUCCESS_HISTORY_NSFW_SUFFIX)].len(),
+        2
+    );
+    assert_eq!(
+        history.success[&key(USER_SUCCESS_HISTORY_CLEAN_SUFFIX)].len(),
+        1
+    );
+}
+
+#[test]
+fn test_configured_threshold_splits_items() {
+    let config = NsfwConfig {
+        probability_threshold: 0.8,
+        ..Default::default()
+    };
+    let history = build_history_items(
+        canister(),
+        vec![
+            row("video_duration_watched", 0.5, 10.0),
+            row("video_duration_watched", 0.8, 10.0),
+        ],
+        &config,
+    );
+
+    assert_eq!(
+        history.watch[&key(USER_WATCH_HISTORY_CLEAN_SUFFIX)].len(),
+        1
+    );
+    assert_eq!(history.watch[&key(USER_WATCH_HISTORY_NSFW_SUFFIX)].len(), 1);
+}
@@ -0,0 +1,150 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use yral_ml_feed_cache::consts::USER_WATCH_HISTORY_CLEAN_SUFFIX;
+
+use crate::{app_state::AppState, types::RedisPool, user::orphaned_keys::KeyStore, AppError};
+
+/// Longest watch history kept per user, longer lists make `LLEN` and the feed reads slow
+pub const MAX_WATCH_HISTORY_LEN: usize = 2000;
+/// Users checked per job run, the job enqueues itself until the scan completes
+pub const REBALANCE_USERS_PER_RUN: usize = 1000;
+pub const REBALANCE_FEED_HISTORY_CRON: &str = "0 5 * * 3";
+pub const REBALANCE_FEED_HISTORY_SCHEDULE_ID: &str = "rebalance-user-feed-history";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RebalanceFeedHistoryRequest {
+    /// `SCAN` cursor to resume from, 0 starts a new scan
+    #[serde(default)]
+    pub cursor: u64,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct RebalanceFeedHistoryResponse {
+    pub checked: usize,
+    /// Items removed per user canister id
+    pub trimmed: BTreeMap<String, usize>,
+    /// Cursor the next run resumes from, `None` once the scan is done
+    pub next_cursor: Option<u64>,
+}
+
+pub fn watch_history_key_pattern() -> String {
+    format!("*{}", USER_WATCH_HISTORY_CLEAN_SUFFIX)
+}
+
+pub(crate) trait HistoryListStore: KeyStore {
+    async fn list_len(&self, key: &str) -> Result<usize, anyhow::Error>;
+
+    /// Keeps the first `len` items of the list
+    async fn trim_list(&self, key: &str, len: usize) -> Result<(), anyhow::Error>;
+}
+
+impl HistoryListStore for RedisPool {
+    async fn list_len(&self, key: &str) -> Result<usize, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let len = redis::cmd("LLEN").arg(key).query_async(&mut *conn).await?;
+
+        Ok(len)
+    }
+
+    async fn trim_list(&self, key: &str, len: usize) -> Result<(), anyhow::Error> {
+        let mut conn = self.get().await?;
+        let _: () = redis::cmd("LTRIM")
+            .arg(key)
+            .arg(0)
+            .arg(len as i64 - 1)
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Trims the watch histories over [`MAX_WATCH_HISTORY_LEN`] from `cursor` on. Stops at the
+/// first `SCAN` page boundary after `max_users` histories, so a run can check a few more.
+pub async fn rebalance_feed_history_batch(
+    store: &impl HistoryListStore,
+    cursor: u64,
+    max_users: usize,
+) -> Result<RebalanceFeedHistoryResponse, anyhow::Error> {
+    let pattern = watch_history_key_pattern();
+    let mut res = RebalanceFeedHistoryResponse::default();
+    let mut cursor = cursor;
+
+    loop {
+        let (next, keys) = store.scan_keys(cursor, &pattern).await?;
+
+        for key in keys {
+            res.checked += 1;
+            let len = store.list_len(&key).await?;
+            if len <= MAX_WATCH_HISTORY_LEN {
+                continue;
+            }
+
+            store.trim_list(&key, MAX_WATCH_HISTORY_LEN).await?;
+            let user = key
+                .strip_suffix(USER_WATCH_HISTORY_CLEAN_SUFFIX)
+                .unwrap_or(&key)
+                .to_string();
+            log::info!(
+                "Trimmed {} watch history items of {}",
+                len - MAX_WATCH_HISTORY_LEN,
+                user
+            );
+            res.trimmed.insert(user, len - MAX_WATCH_HISTORY_LEN);
+        }
+
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+        if res.checked >= max_users {
+            res.next_cursor = Some(cursor);
+            break;
+        }
+    }
+
+    Ok(res)
+}
+
+/// Caps the ML feed watch history of every user, scheduled weekly. The schedule posts
+/// without a body, which starts a new scan.
+#[instrument(skip(state))]
+pub async fn rebalance_user_feed_history(
+    State(state): State<Arc<AppState>>,
+    req: Option<Json<RebalanceFeedHistoryRequest>>,
+) -> Result<Json<RebalanceFeedHistoryResponse>, AppError> {
+    let cursor = req.map(|Json(req)| req.cursor).unwrap_or_default();
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let res = rebalance_feed_history_batch(
+            &state.ml_feed_cache.redis_pool,
+            cursor,
+            REBALANCE_USERS_PER_RUN,
+        )
+        .await?;
+
+        if let Some(cursor) = res.next_cursor {
+            state
+                .qstash_client
+                .publish_rebalance_user_feed_history(&RebalanceFeedHistoryRequest { cursor })
+                .await?;
+        }
+        log::info!(
+            "Rebalanced feed history: {} users checked, {} trimmed",
+            res.checked,
+            res.trimmed.len()
+        );
+
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, cursor);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
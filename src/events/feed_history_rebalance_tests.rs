@@ -0,0 +1,143 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use yral_ml_feed_cache::consts::USER_WATCH_HISTORY_CLEAN_SUFFIX;
+
+use super::feed_history_rebalance::{
+    rebalance_feed_history_batch, watch_history_key_pattern, HistoryListStore,
+    MAX_WATCH_HISTORY_LEN,
+};
+use crate::user::orphaned_keys::KeyStore;
+
+/// Redis holding list lengths, `SCAN` returns `page_size` matching keys at a time with the
+/// index of the next key as cursor
+struct MockRedis {
+    lists: Mutex<BTreeMap<String, usize>>,
+    page_size: usize,
+}
+
+impl MockRedis {
+    fn with_lists(lists: &[(&str, usize)], page_size: usize) -> Self {
+        Self {
+            lists: Mutex::new(
+                lists
+                    .iter()
+                    .map(|(key, len)| (key.to_string(), *len))
+                    .collect(),
+            ),
+            page_size,
+        }
+    }
+
+    fn len(&self, key: &str) -> usize {
+        self.lists.lock().unwrap()[key]
+    }
+}
+
+impl KeyStore for MockRedis {
+    async fn scan_keys(
+        &self,
+        cursor: u64,
+        pattern: &str,
+    ) -> Result<(u64, Vec<String>), anyhow::Error> {
+        let suffix = pattern.strip_prefix('*').unwrap();
+        let matching: Vec<String> = self
+            .lists
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.ends_with(suffix))
+            .cloned()
+            .collect();
+        let start = cursor as usize;
+        let end = (start + self.page_size).min(matching.len());
+        let next = if end == matching.len() { 0 } else { end as u64 };
+
+        Ok((next, matching[start..end].to_vec()))
+    }
+
+    async fn delete_keys(&self, _keys: &[String]) -> Result<usize, anyhow::Error> {
+        unreachable!("rebalancing never deletes keys")
+    }
+}
+
+impl HistoryListStore for MockRedis {
+    async fn list_len(&self, key: &str) -> Result<usize, anyhow::Error> {
+        Ok(self.len(key))
+    }
+
+    async fn trim_list(&self, key: &str, len: usize) -> Result<(), anyhow::Error> {
+        let mut lists = self.lists.lock().unwrap();
+        let current = lists.get_mut(key).unwrap();
+        *current = (*current).min(len);
+        Ok(())
+    }
+}
+
+fn history_key(user: &str) -> String {
+    format!("{}{}", user, USER_WATCH_HISTORY_CLEAN_SUFFIX)
+}
+
+#[test]
+fn test_pattern_matches_clean_watch_history_keys() {
+    assert_eq!(
+        watch_history_key_pattern(),
+        format!("*{}", USER_WATCH_HISTORY_CLEAN_SUFFIX)
+    );
+}
+
+#[tokio::test]
+async fn test_only_oversized_histories_are_trimmed() {
+    let small = history_key("user-a");
+    let exact = history_key("user-b");
+    let large = history_key("user-c");
+    let mock = MockRedis::with_lists(
+        &[
+            (&small, 10),
+            (&exact, MAX_WATCH_HISTORY_LEN),
+            (&large, 25_000),
+            ("user-c:other", 50_000),
+        ],
+        10,
+    );
+
+    let res = rebalance_feed_history_batch(&mock, 0, 1000).await.unwrap();
+
+    assert_eq!(res.checked, 3);
+    assert_eq!(res.next_cursor, None);
+    assert_eq!(
+        res.trimmed,
+        BTreeMap::from([("user-c".to_string(), 25_000 - MAX_WATCH_HISTORY_LEN)])
+    );
+    assert_eq!(mock.len(&small), 10);
+    assert_eq!(mock.len(&exact), MAX_WATCH_HISTORY_LEN);
+    assert_eq!(mock.len(&large), MAX_WATCH_HISTORY_LEN);
+    assert_eq!(mock.len("user-c:other"), 50_000);
+}
+
+#[tokio::test]
+async fn test_batch_stops_after_max_users_and_resumes() {
+    let users: Vec<String> = (0..25)
+        .map(|n| history_key(&format!("user-{:02}", n)))
+        .collect();
+    let lists: Vec<(&str, usize)> = users
+        .iter()
+        .map(|key| (key.as_str(), MAX_WATCH_HISTORY_LEN + 1))
+        .collect();
+    let mock = MockRedis::with_lists(&lists, 4);
+
+    let first = rebalance_feed_history_batch(&mock, 0, 10).await.unwrap();
+    assert_eq!(first.checked, 12);
+    assert_eq!(first.next_cursor, Some(12));
+
+    let second = rebalance_feed_history_batch(&mock, 12, 10).await.unwrap();
+    assert_eq!(second.checked, 12);
+    assert_eq!(second.next_cursor, Some(24));
+
+    let last = rebalance_feed_history_batch(&mock, 24, 10).await.unwrap();
+    assert_eq!(last.checked, 1);
+    assert_eq!(last.next_cursor, None);
+
+    assert!(users
+        .iter()
+        .all(|key| mock.len(key) == MAX_WATCH_HISTORY_LEN));
+}
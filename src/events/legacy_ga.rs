@@ -0,0 +1,162 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{extract::State, Json};
+use http::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+use crate::{
+    app_state::AppState,
+    auth::check_auth_events,
+    events::{event::Event, warehouse_events::WarehouseEvent},
+};
+
+use super::{device_type_from_headers, process_event_impl};
+
+/// GA event names mapped to warehouse event names when `ga_event_mapping` is not configured.
+/// Most clients sent GA the same events they send the warehouse.
+pub const DEFAULT_GA_EVENT_MAPPING: [(&str, &str); 6] = [
+    ("login", "login_successful"),
+    ("video_upload", "video_upload_successful"),
+    ("video_watched", "video_watched"),
+    ("video_duration_watched", "video_duration_watched"),
+    ("like_video", "like_video"),
+    ("token_creation_completed", "token_creation_completed"),
+];
+
+/// GA bookkeeping params with no meaning in the warehouse
+const GA_INTERNAL_PARAMS: [&str; 4] = [
+    "engagement_time_msec",
+    "debug_mode",
+    "ga_session_id",
+    "ga_session_number",
+];
+
+pub fn default_ga_event_mapping() -> HashMap<String, String> {
+    DEFAULT_GA_EVENT_MAPPING
+        .iter()
+        .map(|(ga, warehouse)| (ga.to_string(), warehouse.to_string()))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct GaEvent {
+    pub name: String,
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub params: HashMap<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct LegacyGaRequest {
+    pub client_id: String,
+    pub events: Vec<GaEvent>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum GaEventResult {
+    Processed {
+        name: String,
+        event: String,
+    },
+    /// No warehouse event is mapped to the GA event name
+    Unmapped {
+        name: String,
+    },
+    Failed {
+        name: String,
+        error: String,
+    },
+}
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct LegacyGaResponse {
+    pub results: Vec<GaEventResult>,
+}
+
+/// Warehouse params of a GA event, GA bookkeeping is dropped and the GA `client_id` kept
+/// unless the event carries its own
+pub fn ga_params_to_warehouse(client_id: &str, params: &HashMap<String, Value>) -> Value {
+    let mut warehouse_params: Map<String, Value> = params
+        .iter()
+        .filter(|(key, _)| !GA_INTERNAL_PARAMS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    warehouse_params
+        .entry("client_id")
+        .or_insert_with(|| Value::String(client_id.to_string()));
+
+    Value::Object(warehouse_params)
+}
+
+/// `None` for GA events with no mapped warehouse event
+pub fn convert_ga_event(
+    mapping: &HashMap<String, String>,
+    client_id: &str,
+    ga_event: &GaEvent,
+) -> Option<WarehouseEvent> {
+    let event = mapping.get(&ga_event.name)?;
+
+    Some(WarehouseEvent {
+        event: event.clone(),
+        params: ga_params_to_warehouse(client_id, &ga_event.params).to_string(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/ingest-legacy-ga",
+    request_body = LegacyGaRequest,
+    tag = "events",
+    responses(
+        (status = 200, description = "Result of every event", body = LegacyGaResponse),
+        (status = 401, description = "Unauthorized"),
+    )
+)]
+pub async fn ingest_legacy_ga_events(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<LegacyGaRequest>,
+) -> Result<Json<LegacyGaResponse>, (StatusCode, String)> {
+    let auth_token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim_start_matches("Bearer ").to_string());
+
+    check_auth_events(auth_token).map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+
+    let device_type = device_type_from_headers(&headers);
+    let mut results = Vec::with_capacity(request.events.len());
+    for ga_event in request.events {
+        let Some(warehouse_event) =
+            convert_ga_event(&state.ga_event_mapping, &request.client_id, &ga_event)
+        else {
+            log::warn!("Unmapped legacy GA event name: {}", ga_event.name);
+            results.push(GaEventResult::Unmapped {
+                name: ga_event.name,
+            });
+            continue;
+        };
+
+        let event_name = warehouse_event.event.clone();
+        let event = Event::new(warehouse_event).with_device_type(device_type);
+        let result = match process_event_impl(event, state.clone(), None).await {
+            Ok(()) => GaEventResult::Processed {
+                name: ga_event.name,
+                event: event_name,
+            },
+            Err(e) => {
+                log::error!("Failed to process legacy GA event {}: {}", ga_event.name, e);
+                GaEventResult::Failed {
+                    name: ga_event.name,
+                    error: e.to_string(),
+                }
+            }
+        };
+        results.push(result);
+    }
+
+    Ok(Json(LegacyGaResponse { results }))
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use super::legacy_ga::{
+    convert_ga_event, default_ga_event_mapping, ga_params_to_warehouse, GaEvent,
+};
+
+fn ga_event(name: &str, params: Value) -> GaEvent {
+    GaEvent {
+        name: name.into(),
+        params: serde_json::from_value(params).unwrap(),
+    }
+}
+
+#[test]
+fn test_default_mapping_renames_login() {
+    let mapping = default_ga_event_mapping();
+
+    let event = convert_ga_event(&mapping, "client-1", &ga_event("login", json!({}))).unwrap();
+
+    assert_eq!(event.event, "login_successful");
+}
+
+#[test]
+fn test_unmapped_event_is_not_converted() {
+    let mapping = default_ga_event_mapping();
+
+    assert!(convert_ga_event(&mapping, "client-1", &ga_event("page_view", json!({}))).is_none());
+}
+
+#[test]
+fn test_configured_mapping_replaces_default() {
+    let mapping = HashMap::from([("page_view".to_string(), "home_page_viewed".to_string())]);
+
+    let event = convert_ga_event(&mapping, "client-1", &ga_event("page_view", json!({}))).unwrap();
+
+    assert_eq!(event.event, "home_page_viewed");
+    assert!(convert_ga_event(&mapping, "client-1", &ga_event("login", json!({}))).is_none());
+}
+
+#[test]
+fn test_params_drop_ga_bookkeeping() {
+    let params: HashMap<String, Value> = serde_json::from_value(json!({
+        "video_id": "abc",
+        "percentage_watched": 87.5,
+        "engagement_time_msec": 1200,
+        "ga_session_id": "1700000000",
+        "debug_mode": true,
+    }))
+    .unwrap();
+
+    let warehouse_params = ga_params_to_warehouse("client-1", &params);
+
+    assert_eq!(
+        warehouse_params,
+        json!({
+            "video_id": "abc",
+            "percentage_watched": 87.5,
+            "client_id": "client-1",
+        })
+    );
+}
+
+#[test]
+fn test_event_client_id_is_kept() {
+    let params: HashMap<String, Value> =
+        serde_json::from_value(json!({ "client_id": "from-event" })).unwrap();
+
+    assert_eq!(
+        ga_params_to_warehouse("from-request", &params),
+        json!({ "client_id": "from-event" })
+    );
+}
+
+#[test]
+fn test_converted_params_are_json_string() {
+    let mapping = default_ga_event_mapping();
+
+    let event = convert_ga_event(
+        &mapping,
+        "client-1",
+        &ga_event("like_video", json!({ "post_id": 4 })),
+    )
+    .unwrap();
+    let params: Value = serde_json::from_str(&event.params).unwrap();
+
+    assert_eq!(params, json!({ "post_id": 4, "client_id": "client-1" }));
+}
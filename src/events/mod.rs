@@ -1,6 +1,7 @@
-use axum::extract::State;
+use axum::extract::{Query, State};
 use axum::response::IntoResponse;
 use axum::{middleware, Json};
+use body_limit::{limit_request_body, BULK_EVENT_BODY_LIMIT, EVENT_BODY_LIMIT};
 use candid::Principal;
 use event::Event;
 use http::{header, StatusCode};
@@ -8,7 +9,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::error::Error;
 use std::sync::Arc;
-use types::AnalyticsEvent;
+use types::{AnalyticsEvent, DeviceType};
 use utoipa::ToSchema;
 use utoipa_axum::router::{OpenApiRouter, UtoipaMethodRouterExt};
 use utoipa_axum::routes;
@@ -28,12 +29,70 @@ pub mod warehouse_events {
         tonic::include_file_descriptor_set!("warehouse_events_descriptor");
 }
 
+pub mod ab_test;
+pub mod body_limit;
+pub mod consistency_check;
+pub mod dau;
+pub mod dedup;
 pub mod event;
+pub mod feed_cache_reindex;
+pub mod feed_history_rebalance;
+pub mod legacy_ga;
 pub mod nsfw;
+pub mod nsfw_appeal;
+pub mod nsfw_cache;
+pub mod nsfw_replay;
+pub mod opt_out;
+pub mod parquet_export;
+pub mod pipeline;
+pub mod purge_test_data;
 pub mod queries;
+pub mod session_replay;
+pub mod subscribe;
 pub mod types;
 pub mod verify;
 
+#[cfg(test)]
+mod ab_test_tests;
+#[cfg(test)]
+mod body_limit_tests;
+#[cfg(test)]
+mod consistency_check_tests;
+#[cfg(test)]
+mod dau_tests;
+#[cfg(test)]
+mod dedup_tests;
+#[cfg(test)]
+mod feed_cache_reindex_tests;
+#[cfg(test)]
+mod feed_history_rebalance_tests;
+#[cfg(test)]
+mod legacy_ga_tests;
+#[cfg(test)]
+mod nsfw_appeal_tests;
+#[cfg(test)]
+mod nsfw_cache_tests;
+#[cfg(test)]
+mod nsfw_replay_tests;
+#[cfg(test)]
+mod nsfw_tests;
+#[cfg(test)]
+mod opt_out_tests;
+#[cfg(test)]
+mod parquet_export_tests;
+#[cfg(test)]
+mod pipeline_tests;
+#[cfg(test)]
+mod purge_test_data_tests;
+#[cfg(test)]
+mod session_replay_tests;
+#[cfg(test)]
+mod subscribe_tests;
+#[cfg(test)]
+mod types_tests;
+#[cfg(test)]
+mod verify_tests;
+
 pub struct WarehouseEventsService {
     pub shared_state: Arc<AppState>,
 }
@@ -49,10 +108,12 @@ impl WarehouseEvents for WarehouseEventsService {
         let request = request.into_inner();
         let event = event::Event::new(request);
 
-        process_event_impl(event, shared_state).await.map_err(|e| {
-            log::error!("Failed to process event grpc: {}", e);
-            tonic::Status::internal("Failed to process event")
-        })?;
+        process_event_impl(event, shared_state, None)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to process event grpc: {}", e);
+                tonic::Status::internal("Failed to process event")
+            })?;
 
         Ok(tonic::Response::new(Empty {}))
     }
@@ -110,20 +171,106 @@ impl VideoUploadSuccessful {
 
 pub fn events_router(state: Arc<AppState>) -> OpenApiRouter {
     OpenApiRouter::new()
-        .routes(routes!(post_event))
+        .routes(routes!(post_event).layer(middleware::from_fn_with_state(
+            EVENT_BODY_LIMIT,
+            limit_request_body,
+        )))
+        .routes(routes!(session_replay::get_session_replay))
+        .routes(routes!(nsfw_replay::replay_nsfw_pipeline))
+        .routes(routes!(subscribe::subscribe_events))
+        .routes(routes!(legacy_ga::ingest_legacy_ga_events))
+        .routes(routes!(ab_test::attribute_ab_test_event))
+        .routes(routes!(
+            opt_out::opt_out_of_analytics,
+            opt_out::opt_in_to_analytics
+        ))
         .routes(
-            routes!(handle_bulk_events).layer(middleware::from_fn_with_state(
-                state.clone(),
-                verify_event_bulk_request,
-            )),
+            routes!(handle_bulk_events)
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    verify_event_bulk_request,
+                ))
+                .layer(middleware::from_fn_with_state(
+                    BULK_EVENT_BODY_LIMIT,
+                    limit_request_body,
+                )),
         )
         .with_state(state)
 }
 
+pub fn analytics_router(state: Arc<AppState>) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(get_dau_handler))
+        .with_state(state)
+}
+
+#[utoipa::path(
+    get,
+    path = "/dau",
+    params(dau::DauParams),
+    tag = "analytics",
+    responses(
+        (status = 200, description = "Distinct users watching, liking or logging in on the day", body = dau::DauResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+async fn get_dau_handler(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Query(params): Query<dau::DauParams>,
+) -> Result<Json<dau::DauResponse>, (StatusCode, String)> {
+    let auth_token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim_start_matches("Bearer ").to_string());
+
+    check_auth_events(auth_token).map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+
+    let date = params
+        .date
+        .unwrap_or_else(|| chrono::Utc::now().date_naive());
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let res = dau::daily_active_users(state.as_ref(), date)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to count DAU of {}: {}", date, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to count daily active users".to_string(),
+                )
+            })?;
+
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Ok(Json(dau::DauResponse { date, dau: 0 }))
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
 pub struct EventRequest {
     event: String,
     params: String,
+    /// Per session uuid set by the frontend, used for session replay
+    #[serde(default)]
+    session_id: Option<String>,
+    /// Overrides the device type derived from the `User-Agent` header
+    #[serde(default)]
+    device_type: Option<DeviceType>,
+}
+
+fn device_type_from_headers(headers: &axum::http::HeaderMap) -> DeviceType {
+    DeviceType::from_user_agent(
+        headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok()),
+    )
 }
 
 #[utoipa::path(
@@ -134,6 +281,7 @@ pub struct EventRequest {
     responses(
         (status = 200, description = "Event sent successfully"),
         (status = 401, description = "Unauthorized"),
+        (status = 413, description = "Request body over 64KB"),
         (status = 500, description = "Internal server error"),
     )
 )]
@@ -149,14 +297,29 @@ async fn post_event(
 
     check_auth_events(auth_token).map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
 
+    let user_id = serde_json::from_str::<serde_json::Value>(&payload.params)
+        .ok()
+        .and_then(|params| params.get("user_id")?.as_str().map(str::to_string));
+    let opted_out = is_opted_out(&state, user_id.as_deref()).await;
+    if let Some(session_id) = &payload.session_id {
+        if !opted_out {
+            record_session_event(&state, session_id, &payload.event, &payload.params).await;
+        }
+    }
+
     let warehouse_event = WarehouseEvent {
         event: payload.event,
         params: payload.params,
     };
 
-    let event = Event::new(warehouse_event);
+    let device_type = payload
+        .device_type
+        .unwrap_or_else(|| device_type_from_headers(&headers));
+    let event = Event::new(warehouse_event)
+        .with_device_type(device_type)
+        .with_country_code(state.geoip.country_code_from_headers(&headers));
 
-    process_event_impl(event, state.clone())
+    process_event_impl(event, state.clone(), Some(opted_out))
         .await
         .map_err(|e| {
             log::error!("Failed to process event rest: {}", e);
@@ -169,40 +332,115 @@ async fn post_event(
     Ok((StatusCode::OK, "Event processed".to_string()))
 }
 
+/// Session replay is a debugging aid, failures are logged and never fail the event
+async fn record_session_event(
+    shared_state: &AppState,
+    session_id: &str,
+    event: &str,
+    params: &str,
+) {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let session_event = session_replay::SessionEvent::new(
+            event.to_string(),
+            params,
+            session_replay::now_timestamp(),
+        );
+        if let Err(e) = session_replay::store_session_event(
+            &shared_state.canister_backup_redis_pool,
+            session_id,
+            &session_event,
+        )
+        .await
+        {
+            log::error!("Failed to store session event for {}: {}", session_id, e);
+        }
+    }
+
+    #[cfg(feature = "local-bin")]
+    let _ = (shared_state, session_id, event, params);
+}
+
+/// Runs the event through every stage of [`pipeline::EVENT_PIPELINE`], stage failures are logged
+/// by the pipeline and never fail the event. Repeats of the event within
+/// [`dedup::EVENT_DEDUP_TTL_SECS`] are dropped, unless it failed to reach BigQuery. `opted_out` is
+/// the analytics opt out of the event's user when the caller already resolved it, see
+/// [`opt_out::process_respecting_opt_out`].
 async fn process_event_impl(
     event: Event,
     shared_state: Arc<AppState>,
+    opted_out: Option<bool>,
 ) -> Result<(), anyhow::Error> {
     #[cfg(not(feature = "local-bin"))]
-    event.stream_to_bigquery(&shared_state.clone());
+    {
+        let key = dedup::event_dedup_key(
+            &event.event.event,
+            &event.event.params,
+            dedup::params_user_id(&event.event.params).as_deref(),
+        );
+        let dedup_cache = dedup::DedupCache::new(&shared_state.canister_backup_redis_pool);
+        if !dedup_cache.check_and_record(&key).await {
+            log::debug!("Dropping duplicate {} event", event.event.event);
+            return Ok(());
+        }
 
-    event.check_video_deduplication(&shared_state.clone());
+        opt_out::process_respecting_opt_out(
+            &pipeline::EVENT_PIPELINE,
+            &shared_state.canister_backup_redis_pool,
+            &event.with_dedup_key(key),
+            opted_out,
+            &shared_state,
+        )
+        .await;
+    }
 
-    event.update_watch_history(&shared_state.clone());
-    event.update_success_history(&shared_state.clone());
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = opted_out;
+        pipeline::EVENT_PIPELINE.run(&event, &shared_state).await;
+    }
 
-    #[cfg(not(feature = "local-bin"))]
-    event.stream_to_firestore(&shared_state.clone());
+    Ok(())
+}
 
-    #[cfg(not(feature = "local-bin"))]
-    event.stream_to_bigquery_token_metadata(&shared_state.clone());
+/// Whether the user opted out of analytics, never for users without a valid principal
+async fn is_opted_out(shared_state: &AppState, user_id: Option<&str>) -> bool {
+    let Some(user) = user_id.and_then(|user_id| Principal::from_text(user_id).ok()) else {
+        return false;
+    };
 
-    if let Err(e) = event.handle_login_successful(&shared_state.clone()) {
-        log::error!("Error handling login successful: {:?}", e);
+    #[cfg(not(feature = "local-bin"))]
+    {
+        opt_out::is_user_opted_out(&shared_state.canister_backup_redis_pool, user).await
     }
 
-    Ok(())
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (shared_state, user);
+        false
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct EventBulkRequest {
     pub delegated_identity_wire: DelegatedIdentityWire,
     pub events: Vec<AnalyticsEvent>,
+    /// Session of all events in the batch, the metric payloads come from yral-metrics and
+    /// cannot carry it themselves
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Overrides the device type derived from the `User-Agent` header
+    #[serde(default)]
+    pub device_type: Option<DeviceType>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct VerifiedEventBulkRequest {
     pub events: Vec<AnalyticsEvent>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub device_type: Option<DeviceType>,
 }
 
 #[utoipa::path(
@@ -213,24 +451,58 @@ pub struct VerifiedEventBulkRequest {
     responses(
         (status = 200, description = "Bulk event success"),
         (status = 400, description = "Bulk event failed"),
+        (status = 413, description = "Request body over 1MB"),
         (status = 500, description = "Internal server error"),
         (status = 403, description = "Forbidden"),
     )
 )]
 async fn handle_bulk_events(
     State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
     Json(request): Json<VerifiedEventBulkRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let device_type = request
+        .device_type
+        .unwrap_or_else(|| device_type_from_headers(&headers));
+    let country_code = state.geoip.country_code_from_headers(&headers);
+    // every event of a bulk request is from the verified caller
+    let user_id = request.events.iter().find_map(|event| event.user_id());
+    let opted_out = is_opted_out(&state, user_id.as_deref()).await;
     let mut metric_events = Vec::new();
     for req_event in request.events {
+        #[cfg(feature = "local-bin")]
+        if req_event.is_test_event() {
+            types::TEST_EVENTS_RECEIVED.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            continue;
+        }
+
         let event = Event::new(WarehouseEvent {
             event: req_event.tag(),
             params: req_event.params().to_string(),
-        });
+        })
+        .with_device_type(device_type)
+        .with_country_code(country_code.clone());
+
+        #[cfg(not(feature = "local-bin"))]
+        if let Some(watch) = event::watch_reward::CompletedWatch::from_event(&req_event) {
+            let state = state.clone();
+
+            tokio::spawn(async move {
+                if let Err(e) = event::watch_reward::reward_watch_video(&state, watch).await {
+                    log::error!("Error handling watch video reward: {:?}", e);
+                }
+            });
+        }
 
-        metric_events.push(req_event);
+        if !opted_out {
+            if let Some(session_id) = &request.session_id {
+                record_session_event(&state, session_id, &event.event.event, &event.event.params)
+                    .await;
+            }
+            metric_events.push(req_event);
+        }
 
-        if let Err(e) = process_event_impl(event, state.clone()).await {
+        if let Err(e) = process_event_impl(event, state.clone(), Some(opted_out)).await {
             log::error!("Failed to process event rest: {}", e); // not sending any error to the client as it is a bulk request
         }
     }
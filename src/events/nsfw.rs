@@ -6,11 +6,13 @@ use std::{
 };
 
 use crate::{
-    consts::{NSFW_SERVER_URL, NSFW_THRESHOLD, STORJ_INTERFACE_TOKEN, STORJ_INTERFACE_URL},
-    qstash::client::QStashClient,
+    config::{NsfwConfig, VideoQuality, CLOUDFLARE_CONFIG},
+    consts::{NSFW_SERVER_URL, STORJ_INTERFACE_TOKEN, STORJ_INTERFACE_URL},
+    qstash::{client::QStashClient, video_jobs::track_video_job},
 };
 use anyhow::Error;
 use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
 use google_cloud_bigquery::http::{
     job::query::QueryRequest,
     tabledata::{
@@ -20,12 +22,20 @@ use google_cloud_bigquery::http::{
 };
 use serde::{Deserialize, Serialize};
 use tonic::transport::{Channel, ClientTlsConfig};
-use tonic::{metadata::MetadataValue, Request};
+use tonic::{
+    metadata::{MetadataMap, MetadataValue},
+    Request,
+};
 use tracing::instrument;
+#[cfg(not(feature = "local-bin"))]
+use yral_canisters_client::individual_user_template::PostStatus;
 
 use crate::{app_state::AppState, AppError};
 
-use super::event::UploadVideoInfo;
+use super::{
+    event::UploadVideoInfo,
+    nsfw_cache::{lookup_nsfw_result_for_video, set_cached_nsfw_result},
+};
 
 pub mod nsfw_detector {
     tonic::include_proto!("nsfw_detector");
@@ -123,16 +133,15 @@ pub struct VideoRequest {
 }
 
 // extract_frames_and_upload API handler which takes video_id as queryparam in axum
-#[instrument(skip(state))]
+#[instrument(skip(state), fields(video_id = tracing::field::Empty))]
 pub async fn extract_frames_and_upload(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<VideoRequest>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    crate::qstash::trace::record_video_id(&payload.video_id);
+
     let video_id = payload.video_id;
-    let video_path = format!(
-        "https://customer-2p3jflss4r4hmpnz.cloudflarestream.com/{}/downloads/default.mp4",
-        video_id
-    );
+    let video_path = CLOUDFLARE_CONFIG.video_download_url(&video_id, VideoQuality::P480);
     let output_dir = create_output_directory(&video_id)?;
     let frames = extract_frames(&video_path, output_dir.clone()).await?;
     #[cfg(not(feature = "local-bin"))]
@@ -142,9 +151,10 @@ pub async fn extract_frames_and_upload(
 
     // enqueue qstash job to detect nsfw
     let qstash_client = state.qstash_client.clone();
-    qstash_client
+    let message_id = qstash_client
         .publish_video_nsfw_detection(&video_id, &payload.video_info)
         .await?;
+    track_video_job(&state.canister_backup_redis_pool, &video_id, &message_id).await;
 
     Ok(Json(
         serde_json::json!({ "message": "Frames extracted and uploaded to GCS" }),
@@ -159,8 +169,8 @@ pub struct NSFWInfo {
     pub csam_detected: bool,
 }
 
-#[instrument]
-pub async fn get_video_nsfw_info(video_id: String) -> Result<NSFWInfo, Error> {
+#[instrument(skip(config))]
+pub async fn get_video_nsfw_info(video_id: String, config: &NsfwConfig) -> Result<NSFWInfo, Error> {
     // create a new connection everytime and depend on fly proxy to load balance
     let tls_config = ClientTlsConfig::new().with_webpki_roots();
     let channel = Channel::from_static(NSFW_SERVER_URL)
@@ -186,9 +196,7 @@ pub async fn get_video_nsfw_info(video_id: String) -> Result<NSFWInfo, Error> {
     });
     let res = client.detect_nsfw_video_id(req).await?;
 
-    let mut nsfw_info = NSFWInfo::from(res.into_inner());
-
-    Ok(nsfw_info)
+    Ok(NSFWInfo::from_response(res.into_inner(), config))
 }
 
 #[derive(Serialize)]
@@ -208,26 +216,60 @@ pub async fn nsfw_job(
 }
 
 #[cfg(not(feature = "local-bin"))]
-#[instrument(skip(state))]
+#[instrument(skip(state), fields(video_id = tracing::field::Empty))]
 pub async fn nsfw_job(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<VideoRequest>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    crate::qstash::trace::record_video_id(&payload.video_id);
+
     let video_id = payload.video_id;
     let video_info = payload.video_info;
 
-    let nsfw_info = get_video_nsfw_info(video_id.clone()).await?;
+    let redis_pool = &state.canister_backup_redis_pool;
+    let (content_digest, mut cached) = lookup_nsfw_result_for_video(redis_pool, &video_id).await;
+
+    let nsfw_info = match cached.nsfw_info.clone() {
+        Some(nsfw_info) => nsfw_info,
+        None => {
+            let nsfw_info = get_video_nsfw_info(video_id.clone(), &state.nsfw_config).await?;
+            if let Some(content_digest) = content_digest {
+                cached.nsfw_info = Some(nsfw_info.clone());
+                if let Err(e) = set_cached_nsfw_result(redis_pool, &content_digest, &cached).await {
+                    log::warn!("Failed to cache NSFW result for {}: {}", video_id, e);
+                }
+            }
+            nsfw_info
+        }
+    };
+
+    let csam_responder = AppCsamResponder {
+        state: &state,
+        http: reqwest::Client::new(),
+    };
+    let csam_detected =
+        detect_csam_and_alert(&csam_responder, &nsfw_info, &video_info, Utc::now()).await?;
 
     // push nsfw info to bigquery table using google-cloud-bigquery
     let bigquery_client = state.bigquery_client.clone();
 
-    push_nsfw_data_bigquery(bigquery_client, nsfw_info, video_id.clone()).await?;
+    push_nsfw_data_bigquery(bigquery_client, nsfw_info, video_id.clone())
+        .await
+        .map_err(AppError::BigQueryError)?;
+
+    // flagged videos are never classified again nor duplicated to storj
+    if csam_detected {
+        return Ok(Json(
+            serde_json::json!({ "message": "NSFW job completed, CSAM reported" }),
+        ));
+    }
 
     // enqueue qstash job to detect nsfw v2
     let qstash_client = state.qstash_client.clone();
-    qstash_client
+    let message_id = qstash_client
         .publish_video_nsfw_detection_v2(&video_id, video_info)
         .await?;
+    track_video_job(&state.canister_backup_redis_pool, &video_id, &message_id).await;
 
     Ok(Json(serde_json::json!({ "message": "NSFW job completed" })))
 }
@@ -292,17 +334,10 @@ pub async fn push_nsfw_data_bigquery(
     Ok(())
 }
 
-impl From<nsfw_detector::NsfwDetectorResponse> for NSFWInfo {
-    fn from(item: nsfw_detector::NsfwDetectorResponse) -> Self {
-        let is_nsfw = item.csam_detected
-            || matches!(
-                item.nsfw_gore.as_str(),
-                "POSSIBLE" | "LIKELY" | "VERY_LIKELY"
-            )
-            || matches!(item.nsfw_ec.as_str(), "nudity" | "provocative" | "explicit");
-
+impl NSFWInfo {
+    pub fn from_response(item: nsfw_detector::NsfwDetectorResponse, config: &NsfwConfig) -> Self {
         Self {
-            is_nsfw,
+            is_nsfw: config.is_nsfw(&item.nsfw_ec, &item.nsfw_gore, item.csam_detected),
             nsfw_ec: item.nsfw_ec,
             nsfw_gore: item.nsfw_gore,
             csam_detected: item.csam_detected,
@@ -319,19 +354,39 @@ pub async fn nsfw_job_v2(
 }
 
 #[cfg(not(feature = "local-bin"))]
-#[instrument(skip(state))]
+#[instrument(skip(state), fields(video_id = tracing::field::Empty))]
 pub async fn nsfw_job_v2(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<VideoRequest>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    crate::qstash::trace::record_video_id(&payload.video_id);
+
     let video_id = payload.video_id;
 
-    let nsfw_prob = get_video_nsfw_info_v2(video_id.clone()).await?;
-    let is_nsfw = nsfw_prob >= NSFW_THRESHOLD;
+    let redis_pool = &state.canister_backup_redis_pool;
+    let (content_digest, mut cached) = lookup_nsfw_result_for_video(redis_pool, &video_id).await;
+
+    // cached results only keep the probability, their detection details are already stored
+    let (nsfw_prob, detection) = match cached.probability {
+        Some(nsfw_prob) => (nsfw_prob, None),
+        None => {
+            let detection = get_video_nsfw_info_v2(video_id.clone(), &state.nsfw_config).await?;
+            if let Some(content_digest) = content_digest {
+                cached.probability = Some(detection.probability);
+                if let Err(e) = set_cached_nsfw_result(redis_pool, &content_digest, &cached).await {
+                    log::warn!("Failed to cache NSFW v2 result for {}: {}", video_id, e);
+                }
+            }
+            (detection.probability, Some(detection))
+        }
+    };
+    let is_nsfw = state.nsfw_config.is_nsfw_probability(nsfw_prob);
 
     // push nsfw info to bigquery table using google-cloud-bigquery
     let bigquery_client = state.bigquery_client.clone();
-    push_nsfw_data_bigquery_v2(bigquery_client, nsfw_prob, video_id.clone()).await?;
+    push_nsfw_data_bigquery_v2(bigquery_client, nsfw_prob, detection, video_id.clone())
+        .await
+        .map_err(AppError::BigQueryError)?;
 
     duplicate_to_storj(&state.qstash_client, payload.video_info, is_nsfw).await?;
 
@@ -340,8 +395,85 @@ pub async fn nsfw_job_v2(
     ))
 }
 
-#[instrument]
-pub async fn get_video_nsfw_info_v2(video_id: String) -> Result<f32, Error> {
+/// Response metadata set by the detector, bounds missing when the model does not report them
+pub const NSFW_MODEL_VERSION_HEADER: &str = "x-model-version";
+pub const NSFW_CONFIDENCE_LOWER_HEADER: &str = "x-confidence-lower";
+pub const NSFW_CONFIDENCE_UPPER_HEADER: &str = "x-confidence-upper";
+pub const UNKNOWN_NSFW_MODEL_VERSION: &str = "unknown";
+
+/// Columns of [`NSFWDetectionResult`] missing from `video_nsfw_agg`, safe to run on every start
+pub const NSFW_DETECTION_COLUMNS_DDL: &str =
+    "ALTER TABLE `hot-or-not-feed-intelligence.yral_ds.video_nsfw_agg`
+    ADD COLUMN IF NOT EXISTS model_version STRING,
+    ADD COLUMN IF NOT EXISTS confidence_lower FLOAT64,
+    ADD COLUMN IF NOT EXISTS confidence_upper FLOAT64,
+    ADD COLUMN IF NOT EXISTS detected_at TIMESTAMP";
+
+/// Embedding based classification with what is needed to audit it after model updates
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct NSFWDetectionResult {
+    pub is_nsfw: bool,
+    pub probability: f32,
+    pub model_version: String,
+    pub confidence_lower: f32,
+    pub confidence_upper: f32,
+    pub detected_at: DateTime<Utc>,
+}
+
+fn metadata_str<'a>(metadata: &'a MetadataMap, key: &str) -> Option<&'a str> {
+    metadata.get(key).and_then(|value| value.to_str().ok())
+}
+
+fn metadata_f32(metadata: &MetadataMap, key: &str) -> Option<f32> {
+    metadata_str(metadata, key).and_then(|value| value.parse().ok())
+}
+
+impl NSFWDetectionResult {
+    pub fn from_response(
+        res: tonic::Response<nsfw_detector::EmbeddingNsfwDetectorResponse>,
+        config: &NsfwConfig,
+    ) -> Self {
+        let metadata = res.metadata();
+        let model_version = metadata_str(metadata, NSFW_MODEL_VERSION_HEADER)
+            .unwrap_or(UNKNOWN_NSFW_MODEL_VERSION)
+            .to_string();
+        let confidence_lower = metadata_f32(metadata, NSFW_CONFIDENCE_LOWER_HEADER);
+        let confidence_upper = metadata_f32(metadata, NSFW_CONFIDENCE_UPPER_HEADER);
+        let probability = res.into_inner().probability;
+
+        Self {
+            is_nsfw: config.is_nsfw_probability(probability),
+            probability,
+            model_version,
+            confidence_lower: confidence_lower.unwrap_or(probability),
+            confidence_upper: confidence_upper.unwrap_or(probability),
+            detected_at: Utc::now(),
+        }
+    }
+}
+
+#[instrument(skip(bigquery_client))]
+pub async fn add_nsfw_detection_columns(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+) -> Result<(), Error> {
+    let request = QueryRequest {
+        query: NSFW_DETECTION_COLUMNS_DDL.to_string(),
+        ..Default::default()
+    };
+
+    bigquery_client
+        .job()
+        .query("hot-or-not-feed-intelligence", &request)
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(config))]
+pub async fn get_video_nsfw_info_v2(
+    video_id: String,
+    config: &NsfwConfig,
+) -> Result<NSFWDetectionResult, Error> {
     // create a new connection everytime and depend on fly proxy to load balance
     let tls_config = ClientTlsConfig::new().with_webpki_roots();
     let channel = Channel::from_static(NSFW_SERVER_URL)
@@ -368,7 +500,7 @@ pub async fn get_video_nsfw_info_v2(video_id: String) -> Result<f32, Error> {
     });
     let embedding_res = client.detect_nsfw_embedding(embedding_req).await?;
 
-    Ok(embedding_res.into_inner().probability)
+    Ok(NSFWDetectionResult::from_response(embedding_res, config))
 }
 
 #[derive(Serialize)]
@@ -379,6 +511,10 @@ struct VideoNSFWDataV2 {
     nsfw_ec: String,
     nsfw_gore: String,
     probability: f32,
+    model_version: Option<String>,
+    confidence_lower: Option<f32>,
+    confidence_upper: Option<f32>,
+    detected_at: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -411,6 +547,7 @@ struct VideoEmbeddingAgg {
 pub async fn push_nsfw_data_bigquery_v2(
     bigquery_client: google_cloud_bigquery::client::Client,
     nsfw_prob: f32,
+    detection: Option<NSFWDetectionResult>,
     video_id: String,
 ) -> Result<(), Error> {
     // First query to get existing NSFW data
@@ -466,6 +603,10 @@ pub async fn push_nsfw_data_bigquery_v2(
         nsfw_ec: nsfw_ec.clone(),
         nsfw_gore: nsfw_gore.clone(),
         probability: nsfw_prob,
+        model_version: detection.as_ref().map(|d| d.model_version.clone()),
+        confidence_lower: detection.as_ref().map(|d| d.confidence_lower),
+        confidence_upper: detection.as_ref().map(|d| d.confidence_upper),
+        detected_at: detection.as_ref().map(|d| d.detected_at.to_rfc3339()),
     };
 
     let row = Row {
@@ -620,3 +761,208 @@ pub async fn push_nsfw_data_bigquery_v2(
 
     Ok(())
 }
+
+/// Law enforcement endpoint receiving CSAM reports
+pub const CSAM_ALERT_WEBHOOK_URL_ENV: &str = "CSAM_ALERT_WEBHOOK_URL";
+/// Restricted Google Chat space for CSAM alerts, kept apart from the moderation space
+pub const CSAM_ALERT_CHAT_SPACE_URL_ENV: &str = "CSAM_ALERT_CHAT_SPACE_URL";
+/// Steps of the CSAM response, each one recorded in the video's flag once done so a retried
+/// job never repeats it
+pub const CSAM_WEBHOOK_STEP: &str = "webhook";
+pub const CSAM_CHAT_ALERT_STEP: &str = "chat alert";
+pub const CSAM_POST_BAN_STEP: &str = "post ban";
+
+/// Hash of the flagged video, `detected_at` and the time each completed step was done
+pub fn csam_flagged_key(video_id: &str) -> String {
+    format!("csam_flagged:{}", video_id)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CsamAlert {
+    pub video_id: String,
+    pub publisher_canister_id: String,
+    pub publisher_user_id: String,
+    pub post_id: u64,
+    pub detected_at: DateTime<Utc>,
+}
+
+impl CsamAlert {
+    pub fn new(video_info: &UploadVideoInfo, detected_at: DateTime<Utc>) -> Self {
+        Self {
+            video_id: video_info.video_id.clone(),
+            publisher_canister_id: video_info.canister_id.clone(),
+            publisher_user_id: video_info.publisher_user_id.clone(),
+            post_id: video_info.post_id,
+            detected_at,
+        }
+    }
+}
+
+/// Google Chat message for the restricted CSAM space, without a link to the video
+pub fn csam_chat_message(alert: &CsamAlert) -> serde_json::Value {
+    serde_json::json!({
+        "text": format!(
+            "CSAM detected \n video_id: {} \n publisher_id: {} \n publisher_canister_id: {} \n post_id: {} \n detected_at: {}",
+            alert.video_id,
+            alert.publisher_user_id,
+            alert.publisher_canister_id,
+            alert.post_id,
+            alert.detected_at.to_rfc3339(),
+        )
+    })
+}
+
+pub(crate) trait CsamResponder {
+    async fn report_to_webhook(&self, alert: &CsamAlert) -> Result<(), Error>;
+
+    async fn send_chat_alert(&self, message: serde_json::Value) -> Result<(), Error>;
+
+    /// Flags the video permanently, keeping the first detection time. The flag is never expired.
+    async fn flag_video(&self, video_id: &str, detected_at: DateTime<Utc>) -> Result<(), Error>;
+
+    /// Steps recorded in the video's flag
+    async fn completed_steps(&self, video_id: &str) -> Result<Vec<String>, Error>;
+
+    async fn record_step(&self, video_id: &str, step: &str) -> Result<(), Error>;
+
+    async fn ban_post(
+        &self,
+        publisher_canister: candid::Principal,
+        post_id: u64,
+    ) -> Result<(), Error>;
+}
+
+#[cfg(not(feature = "local-bin"))]
+pub struct AppCsamResponder<'a> {
+    pub state: &'a AppState,
+    pub http: reqwest::Client,
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl CsamResponder for AppCsamResponder<'_> {
+    async fn report_to_webhook(&self, alert: &CsamAlert) -> Result<(), Error> {
+        let url = env::var(CSAM_ALERT_WEBHOOK_URL_ENV)
+            .map_err(|_| anyhow::anyhow!("{} is not set", CSAM_ALERT_WEBHOOK_URL_ENV))?;
+        self.http
+            .post(url)
+            .json(alert)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn send_chat_alert(&self, message: serde_json::Value) -> Result<(), Error> {
+        let url = env::var(CSAM_ALERT_CHAT_SPACE_URL_ENV)
+            .map_err(|_| anyhow::anyhow!("{} is not set", CSAM_ALERT_CHAT_SPACE_URL_ENV))?;
+        crate::offchain_service::send_message_gchat(&url, message).await
+    }
+
+    async fn flag_video(&self, video_id: &str, detected_at: DateTime<Utc>) -> Result<(), Error> {
+        use redis::AsyncCommands;
+
+        let mut conn = self.state.canister_backup_redis_pool.get().await?;
+        let _: bool = conn
+            .hset_nx(
+                csam_flagged_key(video_id),
+                "detected_at",
+                detected_at.to_rfc3339(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn completed_steps(&self, video_id: &str) -> Result<Vec<String>, Error> {
+        use redis::AsyncCommands;
+
+        let mut conn = self.state.canister_backup_redis_pool.get().await?;
+        Ok(conn.hkeys(csam_flagged_key(video_id)).await?)
+    }
+
+    async fn record_step(&self, video_id: &str, step: &str) -> Result<(), Error> {
+        use redis::AsyncCommands;
+
+        let mut conn = self.state.canister_backup_redis_pool.get().await?;
+        let _: () = conn
+            .hset(csam_flagged_key(video_id), step, Utc::now().to_rfc3339())
+            .await?;
+        Ok(())
+    }
+
+    async fn ban_post(
+        &self,
+        publisher_canister: candid::Principal,
+        post_id: u64,
+    ) -> Result<(), Error> {
+        self.state
+            .individual_user(publisher_canister)
+            .update_post_status(post_id, PostStatus::BannedDueToUserReporting)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Reports a video the detector found CSAM in: permanent redis flag, law enforcement webhook,
+/// restricted chat space and ban of the post. Returns whether CSAM was detected.
+///
+/// The flag is written before anything is sent and each step is recorded in it once done, a
+/// retry of the job only runs the steps that failed. Every step is attempted even when an
+/// earlier one fails and an error lists the failed ones.
+#[instrument(skip(responder, nsfw_info))]
+pub async fn detect_csam_and_alert(
+    responder: &impl CsamResponder,
+    nsfw_info: &NSFWInfo,
+    video_info: &UploadVideoInfo,
+    detected_at: DateTime<Utc>,
+) -> Result<bool, Error> {
+    if !nsfw_info.csam_detected {
+        return Ok(false);
+    }
+
+    let alert = CsamAlert::new(video_info, detected_at);
+    log::error!(
+        "CSAM detected in video {} of {}",
+        alert.video_id,
+        alert.publisher_canister_id
+    );
+
+    responder.flag_video(&alert.video_id, detected_at).await?;
+    let completed = responder.completed_steps(&alert.video_id).await?;
+
+    let mut failures: Vec<&str> = Vec::new();
+    for step in [CSAM_WEBHOOK_STEP, CSAM_CHAT_ALERT_STEP, CSAM_POST_BAN_STEP] {
+        if completed.iter().any(|done| done == step) {
+            continue;
+        }
+
+        let res = match step {
+            CSAM_WEBHOOK_STEP => responder.report_to_webhook(&alert).await,
+            CSAM_CHAT_ALERT_STEP => responder.send_chat_alert(csam_chat_message(&alert)).await,
+            _ => match candid::Principal::from_text(&alert.publisher_canister_id) {
+                Ok(publisher_canister) => {
+                    responder.ban_post(publisher_canister, alert.post_id).await
+                }
+                Err(e) => Err(anyhow::anyhow!("invalid publisher canister: {}", e)),
+            },
+        };
+        let res = match res {
+            Ok(()) => responder.record_step(&alert.video_id, step).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = res {
+            log::error!("CSAM {} failed for {}: {}", step, alert.video_id, e);
+            failures.push(step);
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(true)
+    } else {
+        Err(anyhow::anyhow!(
+            "CSAM response for {} incomplete, failed: {}",
+            alert.video_id,
+            failures.join(", ")
+        ))
+    }
+}
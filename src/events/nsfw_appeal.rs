@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use candid::Principal;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState, consts::GOOGLE_CHAT_REPORT_SPACE_URL, events::event::UploadVideoInfo,
+    events::types::VideoNsfwAppealPayload, offchain_service::send_message_gchat, AppError,
+};
+
+/// Firestore collection of appeals, one document per video
+pub const NSFW_APPEALS_COLLECTION: &str = "nsfw-appeals";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NsfwAppealStatus {
+    Pending,
+    /// The video was wrongly flagged
+    Accepted,
+    Rejected,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NsfwAppeal {
+    pub video_id: String,
+    pub canister_id: String,
+    pub post_id: u64,
+    pub publisher_user_id: String,
+    pub reason: String,
+    pub status: NsfwAppealStatus,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub resolved_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub resolution_note: Option<String>,
+}
+
+impl NsfwAppeal {
+    pub fn new(payload: VideoNsfwAppealPayload, now: DateTime<Utc>) -> Self {
+        Self {
+            video_id: payload.video_id,
+            canister_id: payload.canister_id.to_text(),
+            post_id: payload.post_id,
+            publisher_user_id: payload.publisher_user_id,
+            reason: payload.reason,
+            status: NsfwAppealStatus::Pending,
+            created_at: now,
+            resolved_at: None,
+            resolution_note: None,
+        }
+    }
+
+    /// Marks a pending appeal resolved, resolved appeals stay as they are
+    pub fn resolve(
+        mut self,
+        status: NsfwAppealStatus,
+        note: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<Self, String> {
+        if status == NsfwAppealStatus::Pending {
+            return Err("an appeal can only be resolved as accepted or rejected".into());
+        }
+        if self.status != NsfwAppealStatus::Pending {
+            return Err(format!(
+                "appeal of video {} is already {:?}",
+                self.video_id, self.status
+            ));
+        }
+
+        self.status = status;
+        self.resolved_at = Some(now);
+        self.resolution_note = note;
+        Ok(self)
+    }
+
+    pub fn upload_video_info(&self) -> UploadVideoInfo {
+        UploadVideoInfo {
+            video_id: self.video_id.clone(),
+            canister_id: self.canister_id.clone(),
+            post_id: self.post_id,
+            timestamp: self.created_at.to_rfc3339(),
+            publisher_user_id: self.publisher_user_id.clone(),
+            channel_id: None,
+            duration_secs: None,
+        }
+    }
+}
+
+/// Checks the appealed post is the video's and was created by the appellant, the payload is
+/// only taken as a pointer to the post in the publisher canister
+pub fn check_appeal_post(
+    payload: &VideoNsfwAppealPayload,
+    post_video_uid: &str,
+    post_creator: Principal,
+) -> Result<(), String> {
+    if post_video_uid != payload.video_id {
+        return Err(format!(
+            "post {} of {} is not video {}",
+            payload.post_id, payload.canister_id, payload.video_id
+        ));
+    }
+    if post_creator.to_text() != payload.publisher_user_id {
+        return Err(format!(
+            "video {} was not published by {}",
+            payload.video_id, payload.publisher_user_id
+        ));
+    }
+
+    Ok(())
+}
+
+/// Google Chat card for the moderation space
+pub fn appeal_chat_message(appeal: &NsfwAppeal) -> Value {
+    let video_url = format!(
+        "https://yral.com/hot-or-not/{}/{}",
+        appeal.canister_id, appeal.post_id
+    );
+    let text = format!(
+        "publisher_id: {} \n publisher_canister_id: {} \n post_id: {} \n video_id: {} \n reason: {} \n video_url: {}",
+        appeal.publisher_user_id,
+        appeal.canister_id,
+        appeal.post_id,
+        appeal.video_id,
+        appeal.reason,
+        video_url
+    );
+
+    json!({
+        "cardsV2": [
+            {
+                "cardId": format!("nsfw-appeal-{}", appeal.video_id),
+                "card": {
+                    "sections": [
+                        {
+                            "header": "NSFW Appeal",
+                            "widgets": [
+                                { "textParagraph": { "text": text } },
+                                {
+                                    "buttonList": {
+                                        "buttons": [
+                                            {
+                                                "text": "View video",
+                                                "onClick": { "openLink": { "url": video_url } }
+                                            }
+                                        ]
+                                    }
+                                }
+                            ]
+                        }
+                    ]
+                }
+            }
+        ]
+    })
+}
+
+/// Records the appeal, tells the moderators and runs the NSFW detection on the video again.
+/// A video is appealed at most once and only by its publisher.
+#[cfg(not(feature = "local-bin"))]
+#[instrument(skip(state))]
+pub async fn submit_nsfw_appeal(
+    state: &AppState,
+    payload: VideoNsfwAppealPayload,
+) -> Result<(), anyhow::Error> {
+    let post = state
+        .individual_user(payload.canister_id)
+        .get_individual_post_details_by_id(payload.post_id)
+        .await?;
+    check_appeal_post(&payload, &post.video_uid, post.created_by_user_principal_id)
+        .map_err(anyhow::Error::msg)?;
+
+    let appeal = NsfwAppeal::new(payload, Utc::now());
+
+    // creating the document fails if the video was already appealed
+    let res: Result<NsfwAppeal, firestore::errors::FirestoreError> = state
+        .firestoredb
+        .fluent()
+        .insert()
+        .into(NSFW_APPEALS_COLLECTION)
+        .document_id(&appeal.video_id)
+        .object(&appeal)
+        .execute()
+        .await;
+    match res {
+        Ok(_) => {}
+        Err(firestore::errors::FirestoreError::DataConflictError(_)) => {
+            log::info!("Video {} was already appealed", appeal.video_id);
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    if let Err(e) =
+        send_message_gchat(GOOGLE_CHAT_REPORT_SPACE_URL, appeal_chat_message(&appeal)).await
+    {
+        log::error!(
+            "Failed to send nsfw appeal of {} to Google Chat: {}",
+            appeal.video_id,
+            e
+        );
+    }
+
+    state
+        .qstash_client
+        .publish_video_nsfw_detection_v2(&appeal.video_id, appeal.upload_video_info())
+        .await?;
+
+    log::info!("NSFW appeal submitted for video {}", appeal.video_id);
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ResolveNsfwAppealRequest {
+    pub video_id: String,
+    pub status: NsfwAppealStatus,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Marks a pending appeal accepted or rejected
+#[instrument(skip(state))]
+pub async fn resolve_nsfw_appeal(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ResolveNsfwAppealRequest>,
+) -> Result<Json<NsfwAppeal>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let db = &state.firestoredb;
+
+        let appeal: Option<NsfwAppeal> = db
+            .fluent()
+            .select()
+            .by_id_in(NSFW_APPEALS_COLLECTION)
+            .obj()
+            .one(&req.video_id)
+            .await
+            .map_err(anyhow::Error::from)?;
+        let Some(appeal) = appeal else {
+            return Err(AppError::NotFound(format!(
+                "no nsfw appeal for video {}",
+                req.video_id
+            )));
+        };
+
+        let appeal = appeal
+            .resolve(req.status, req.note, Utc::now())
+            .map_err(AppError::InvalidInput)?;
+
+        let appeal: NsfwAppeal = db
+            .fluent()
+            .update()
+            .in_col(NSFW_APPEALS_COLLECTION)
+            .document_id(&req.video_id)
+            .object(&appeal)
+            .execute()
+            .await
+            .map_err(anyhow::Error::from)?;
+
+        log::info!(
+            "NSFW appeal of video {} resolved as {:?}",
+            appeal.video_id,
+            appeal.status
+        );
+
+        Ok(Json(appeal))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, req);
+        Err(AppError::NotFound("nsfw appeals need firestore".into()))
+    }
+}
@@ -0,0 +1,126 @@
+use candid::Principal;
+use chrono::{TimeZone, Utc};
+use serde_json::json;
+use yral_metrics::metrics::sealed_metric::SealedMetric;
+
+use super::nsfw_appeal::{appeal_chat_message, check_appeal_post, NsfwAppeal, NsfwAppealStatus};
+use super::types::{AnalyticsEvent, VideoNsfwAppealPayload};
+
+fn payload() -> VideoNsfwAppealPayload {
+    VideoNsfwAppealPayload {
+        video_id: "vid1".into(),
+        canister_id: Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap(),
+        post_id: 7,
+        publisher_user_id: "2vxsx-fae".into(),
+        reason: "it is a cooking video".into(),
+    }
+}
+
+fn appeal() -> NsfwAppeal {
+    NsfwAppeal::new(payload(), Utc.timestamp_opt(1_700_000_000, 0).unwrap())
+}
+
+#[test]
+fn test_video_nsfw_appeal_event() {
+    let event: AnalyticsEvent = serde_json::from_value(json!({
+        "event": "VideoNsfwAppeal",
+        "video_id": "vid1",
+        "canister_id": "rrkah-fqaaa-aaaaa-aaaaq-cai",
+        "post_id": 7,
+        "publisher_user_id": "2vxsx-fae",
+        "reason": "it is a cooking video",
+    }))
+    .unwrap();
+
+    assert_eq!(event.tag(), "video_nsfw_appeal");
+    // the bulk endpoint only accepts appeals of the caller's own videos
+    assert_eq!(event.user_id().as_deref(), Some("2vxsx-fae"));
+    assert_eq!(
+        event.user_canister().map(|p| p.to_text()).as_deref(),
+        Some("rrkah-fqaaa-aaaaa-aaaaq-cai")
+    );
+    assert_eq!(event.params()["reason"], "it is a cooking video");
+}
+
+#[test]
+fn test_new_appeal_is_pending() {
+    let appeal = appeal();
+
+    assert_eq!(appeal.status, NsfwAppealStatus::Pending);
+    assert_eq!(appeal.canister_id, "rrkah-fqaaa-aaaaa-aaaaq-cai");
+    assert_eq!(appeal.resolved_at, None);
+
+    let info = appeal.upload_video_info();
+    assert_eq!(info.video_id, "vid1");
+    assert_eq!(info.post_id, 7);
+    assert_eq!(info.publisher_user_id, "2vxsx-fae");
+}
+
+#[test]
+fn test_resolve_pending_appeal() {
+    let now = Utc.timestamp_opt(1_700_100_000, 0).unwrap();
+
+    let resolved = appeal()
+        .resolve(NsfwAppealStatus::Accepted, Some("not nsfw".into()), now)
+        .unwrap();
+
+    assert_eq!(resolved.status, NsfwAppealStatus::Accepted);
+    assert_eq!(resolved.resolved_at, Some(now));
+    assert_eq!(resolved.resolution_note.as_deref(), Some("not nsfw"));
+}
+
+#[test]
+fn test_resolved_appeal_cannot_be_resolved_again() {
+    let now = Utc::now();
+    let rejected = appeal()
+        .resolve(NsfwAppealStatus::Rejected, None, now)
+        .unwrap();
+
+    let err = rejected
+        .resolve(NsfwAppealStatus::Accepted, None, now)
+        .unwrap_err();
+    assert!(err.contains("already Rejected"));
+}
+
+#[test]
+fn test_appeal_cannot_be_resolved_as_pending() {
+    assert!(appeal()
+        .resolve(NsfwAppealStatus::Pending, None, Utc::now())
+        .is_err());
+}
+
+#[test]
+fn test_status_is_stored_in_snake_case() {
+    let stored = serde_json::to_value(appeal()).unwrap();
+    assert_eq!(stored["status"], "pending");
+}
+
+#[test]
+fn test_chat_message_links_the_video() {
+    let msg = appeal_chat_message(&appeal()).to_string();
+
+    assert!(msg.contains("NSFW Appeal"));
+    assert!(msg.contains("it is a cooking video"));
+    assert!(msg.contains("https://yral.com/hot-or-not/rrkah-fqaaa-aaaaa-aaaaq-cai/7"));
+}
+
+#[test]
+fn test_appeal_of_own_post_is_accepted() {
+    let publisher = Principal::from_text("2vxsx-fae").unwrap();
+
+    assert_eq!(check_appeal_post(&payload(), "vid1", publisher), Ok(()));
+}
+
+#[test]
+fn test_appeal_of_another_video_is_rejected() {
+    let publisher = Principal::from_text("2vxsx-fae").unwrap();
+
+    assert!(check_appeal_post(&payload(), "vid2", publisher).is_err());
+}
+
+#[test]
+fn test_appeal_of_someone_elses_post_is_rejected() {
+    let other = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+
+    assert!(check_appeal_post(&payload(), "vid1", other).is_err());
+}
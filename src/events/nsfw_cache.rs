@@ -0,0 +1,137 @@
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::types::RedisPool;
+
+use super::nsfw::NSFWInfo;
+
+/// NSFW results are cached per exact video content, so identical re-uploads skip detection.
+/// Perceptually similar videos never share a result, an edited video is classified again.
+pub const NSFW_RESULT_CACHE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CachedNsfwResult {
+    pub nsfw_info: Option<NSFWInfo>,
+    pub probability: Option<f32>,
+}
+
+/// Cache entry of a [`content_digest`](crate::duplicate_video::videohash::content_digest)
+pub fn nsfw_result_key(content_digest: &str) -> String {
+    format!("nsfw_result:{}", content_digest)
+}
+
+pub fn video_content_digest_key(video_id: &str) -> String {
+    format!("video_content_digest:{}", video_id)
+}
+
+/// Remember the content digest of a video so the NSFW jobs further down the
+/// pipeline can find its cache entry by video_id
+#[instrument(skip(redis_pool))]
+pub async fn set_video_content_digest(
+    redis_pool: &RedisPool,
+    video_id: &str,
+    content_digest: &str,
+) -> Result<(), anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    conn.set_ex::<_, _, ()>(
+        video_content_digest_key(video_id),
+        content_digest,
+        NSFW_RESULT_CACHE_TTL_SECS,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(redis_pool))]
+pub async fn get_video_content_digest(
+    redis_pool: &RedisPool,
+    video_id: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    let content_digest: Option<String> = conn.get(video_content_digest_key(video_id)).await?;
+
+    Ok(content_digest)
+}
+
+#[instrument(skip(redis_pool))]
+pub async fn get_cached_nsfw_result(
+    redis_pool: &RedisPool,
+    content_digest: &str,
+) -> Result<Option<CachedNsfwResult>, anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    let raw: Option<String> = conn.get(nsfw_result_key(content_digest)).await?;
+
+    match raw {
+        Some(raw) => Ok(Some(serde_json::from_str(&raw)?)),
+        None => Ok(None),
+    }
+}
+
+#[instrument(skip(redis_pool))]
+pub async fn set_cached_nsfw_result(
+    redis_pool: &RedisPool,
+    content_digest: &str,
+    result: &CachedNsfwResult,
+) -> Result<(), anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    conn.set_ex::<_, _, ()>(
+        nsfw_result_key(content_digest),
+        serde_json::to_string(result)?,
+        NSFW_RESULT_CACHE_TTL_SECS,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Looks up the cached NSFW result for a video via its content digest.
+/// Cache errors are logged and treated as a miss.
+pub async fn lookup_nsfw_result_for_video(
+    redis_pool: &RedisPool,
+    video_id: &str,
+) -> (Option<String>, CachedNsfwResult) {
+    let content_digest = match get_video_content_digest(redis_pool, video_id).await {
+        Ok(Some(content_digest)) => content_digest,
+        Ok(None) => return (None, CachedNsfwResult::default()),
+        Err(e) => {
+            log::warn!("Failed to get content digest for {}: {}", video_id, e);
+            return (None, CachedNsfwResult::default());
+        }
+    };
+
+    let cached = match get_cached_nsfw_result(redis_pool, &content_digest).await {
+        Ok(Some(cached)) => {
+            log::info!(
+                "NSFW cache hit for video {} (content {})",
+                video_id,
+                content_digest
+            );
+            cached
+        }
+        Ok(None) => CachedNsfwResult::default(),
+        Err(e) => {
+            log::warn!("Failed to read NSFW cache for {}: {}", video_id, e);
+            CachedNsfwResult::default()
+        }
+    };
+
+    (Some(content_digest), cached)
+}
+
+/// Drops the cached NSFW result for a video so the next detection run hits the model
+#[instrument(skip(redis_pool))]
+pub async fn clear_cached_nsfw_result_for_video(
+    redis_pool: &RedisPool,
+    video_id: &str,
+) -> Result<(), anyhow::Error> {
+    let Some(content_digest) = get_video_content_digest(redis_pool, video_id).await? else {
+        return Ok(());
+    };
+
+    let mut conn = redis_pool.get().await?;
+    conn.del::<_, ()>(nsfw_result_key(&content_digest)).await?;
+
+    Ok(())
+}
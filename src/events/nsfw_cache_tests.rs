@@ -0,0 +1,69 @@
+use std::{fs, path::PathBuf};
+
+use crate::duplicate_video::videohash::content_digest;
+
+use super::nsfw_cache::{nsfw_result_key, video_content_digest_key};
+
+fn write_video(name: &str, bytes: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join("nsfw_cache_tests");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+#[test]
+fn test_identical_uploads_share_a_content_digest() {
+    let bytes = vec![7u8; 64 * 1024];
+    let first = write_video("identical_a.mp4", &bytes);
+    let second = write_video("identical_b.mp4", &bytes);
+
+    assert_eq!(
+        content_digest(&first).unwrap(),
+        content_digest(&second).unwrap()
+    );
+}
+
+#[test]
+fn test_edited_upload_does_not_reuse_the_cached_result() {
+    let bytes = vec![7u8; 64 * 1024];
+    let mut edited = bytes.clone();
+    edited[32 * 1024] ^= 1;
+    let original = write_video("edited_original.mp4", &bytes);
+    let edited = write_video("edited_copy.mp4", &edited);
+
+    let original_digest = content_digest(&original).unwrap();
+    let edited_digest = content_digest(&edited).unwrap();
+
+    assert_ne!(original_digest, edited_digest);
+    assert_ne!(
+        nsfw_result_key(&original_digest),
+        nsfw_result_key(&edited_digest)
+    );
+}
+
+#[test]
+fn test_content_digest_is_blake3_of_the_file() {
+    let path = write_video("blake3.mp4", b"video bytes");
+
+    assert_eq!(
+        content_digest(&path).unwrap(),
+        blake3::hash(b"video bytes").to_hex().to_string()
+    );
+}
+
+#[test]
+fn test_content_digest_of_missing_file_is_an_error() {
+    let path = std::env::temp_dir().join("nsfw_cache_tests/missing.mp4");
+
+    assert!(content_digest(&path).is_err());
+}
+
+#[test]
+fn test_cache_keys() {
+    assert_eq!(nsfw_result_key("abc"), "nsfw_result:abc");
+    assert_eq!(
+        video_content_digest_key("video-1"),
+        "video_content_digest:video-1"
+    );
+}
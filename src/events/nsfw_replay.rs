@@ -0,0 +1,235 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    AppError,
+};
+
+use super::event::UploadVideoInfo;
+
+/// Tables holding NSFW results of a video, cleared before a replay
+pub const NSFW_RESULT_TABLES: [&str; 2] = ["video_nsfw", "video_nsfw_agg"];
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ReplayNsfwPipelineParams {
+    /// Skip the GCS existence check
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReplayNsfwPipelineResponse {
+    pub video_id: String,
+    /// Frames were already in GCS so only NSFW detection was enqueued
+    pub frames_reused: bool,
+}
+
+/// Video ids are interpolated into BigQuery statements
+pub fn is_valid_video_id(video_id: &str) -> bool {
+    !video_id.is_empty()
+        && video_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+pub fn delete_nsfw_rows_query(table: &str, video_id: &str) -> String {
+    format!(
+        "DELETE FROM `hot-or-not-feed-intelligence.yral_ds.{}` WHERE video_id = '{}'",
+        table, video_id
+    )
+}
+
+pub fn upload_info_query(video_id: &str) -> String {
+    format!(
+        "SELECT
+            JSON_EXTRACT_SCALAR(params, '$.canister_id') AS canister_id,
+            CAST(JSON_EXTRACT_SCALAR(params, '$.post_id') AS INT64) AS post_id,
+            FORMAT_TIMESTAMP('%Y-%m-%dT%H:%M:%SZ', timestamp) AS timestamp,
+            JSON_EXTRACT_SCALAR(params, '$.user_id') AS publisher_user_id
+        FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+        WHERE event = 'video_upload_successful'
+            AND JSON_EXTRACT_SCALAR(params, '$.video_id') = '{}'
+        ORDER BY timestamp DESC
+        LIMIT 1",
+        video_id
+    )
+}
+
+async fn get_upload_video_info(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+    video_id: &str,
+) -> Result<Option<UploadVideoInfo>, anyhow::Error> {
+    let request = QueryRequest {
+        query: upload_info_query(video_id),
+        ..Default::default()
+    };
+    let mut response = bigquery_client
+        .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+        .await?;
+
+    let Some(row) = response.next().await? else {
+        return Ok(None);
+    };
+
+    Ok(Some(UploadVideoInfo {
+        video_id: video_id.to_string(),
+        canister_id: row.column::<String>(0)?,
+        post_id: row.column::<i64>(1)? as u64,
+        timestamp: row.column::<String>(2)?,
+        publisher_user_id: row.column::<String>(3)?,
+        channel_id: None,
+        duration_secs: None,
+    }))
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn replay_nsfw_pipeline_impl(
+    state: &AppState,
+    video_id: &str,
+    force: bool,
+) -> Result<ReplayNsfwPipelineResponse, AppError> {
+    use cloud_storage::ListRequest;
+    use futures::StreamExt;
+
+    use crate::qstash::gcs_gc::{
+        frames_prefix, video_object_name, VIDEOS_BUCKET, VIDEO_FRAMES_BUCKET,
+    };
+
+    if !force {
+        state
+            .gcs_client
+            .object()
+            .read(VIDEOS_BUCKET, &video_object_name(video_id))
+            .await
+            .map_err(|_| AppError::NotFound(format!("video {} not found in GCS", video_id)))?;
+    }
+
+    let video_info = get_upload_video_info(&state.bigquery_client, video_id)
+        .await
+        .map_err(AppError::BigQueryError)?
+        .ok_or_else(|| AppError::NotFound(format!("upload event for {} not found", video_id)))?;
+
+    for table in NSFW_RESULT_TABLES {
+        let request = QueryRequest {
+            query: delete_nsfw_rows_query(table, video_id),
+            ..Default::default()
+        };
+        state
+            .bigquery_client
+            .job()
+            .query("hot-or-not-feed-intelligence", &request)
+            .await
+            .map_err(|e| AppError::BigQueryError(e.into()))?;
+    }
+
+    if let Err(e) = super::nsfw_cache::clear_cached_nsfw_result_for_video(
+        &state.canister_backup_redis_pool,
+        video_id,
+    )
+    .await
+    {
+        log::warn!("Failed to clear NSFW cache for {}: {}", video_id, e);
+    }
+
+    let list_request = ListRequest {
+        prefix: Some(frames_prefix(video_id)),
+        max_results: Some(1),
+        ..Default::default()
+    };
+    let frames_reused = match state
+        .gcs_client
+        .object()
+        .list(VIDEO_FRAMES_BUCKET, list_request)
+        .await
+    {
+        Ok(pages) => matches!(
+            Box::pin(pages).next().await,
+            Some(Ok(page)) if !page.items.is_empty()
+        ),
+        Err(_) => false,
+    };
+
+    // the frames job enqueues NSFW detection itself once frames are uploaded
+    let message_id = if frames_reused {
+        state
+            .qstash_client
+            .publish_video_nsfw_detection(video_id, &video_info)
+            .await?
+    } else {
+        state
+            .qstash_client
+            .publish_video_frames(video_id, &video_info)
+            .await?
+    };
+    crate::qstash::video_jobs::track_video_job(
+        &state.canister_backup_redis_pool,
+        video_id,
+        &message_id,
+    )
+    .await;
+
+    Ok(ReplayNsfwPipelineResponse {
+        video_id: video_id.to_string(),
+        frames_reused,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/replay-nsfw-pipeline/{video_id}",
+    params(
+        ("video_id" = String, Path, description = "Video to re-run NSFW analysis for"),
+        ReplayNsfwPipelineParams,
+    ),
+    tag = "events",
+    responses(
+        (status = 200, description = "NSFW pipeline enqueued", body = ReplayNsfwPipelineResponse),
+        (status = 400, description = "Invalid video id"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Video not found"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state, token))]
+pub async fn replay_nsfw_pipeline(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Path(video_id): Path<String>,
+    Query(params): Query<ReplayNsfwPipelineParams>,
+) -> Result<Json<ReplayNsfwPipelineResponse>, AppError> {
+    check_auth_admin(&token).map_err(|_| AppError::Unauthorized)?;
+
+    if !is_valid_video_id(&video_id) {
+        return Err(AppError::InvalidInput(format!(
+            "invalid video id {}",
+            video_id
+        )));
+    }
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let res = replay_nsfw_pipeline_impl(&state, &video_id, params.force).await?;
+        log::info!(
+            "Replaying NSFW pipeline for {} (frames reused: {})",
+            video_id,
+            res.frames_reused
+        );
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, params);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
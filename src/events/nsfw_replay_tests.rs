@@ -0,0 +1,35 @@
+use super::nsfw_replay::{
+    delete_nsfw_rows_query, is_valid_video_id, upload_info_query, NSFW_RESULT_TABLES,
+};
+
+#[test]
+fn test_video_id_validation() {
+    assert!(is_valid_video_id("4f0a7c1e9b2d4e6f8a0b1c2d3e4f5a6b"));
+    assert!(is_valid_video_id("abc-DEF_123"));
+
+    assert!(!is_valid_video_id(""));
+    assert!(!is_valid_video_id("abc' OR '1'='1"));
+    assert!(!is_valid_video_id("abc/def"));
+}
+
+#[test]
+fn test_delete_queries_cover_all_result_tables() {
+    let queries: Vec<String> = NSFW_RESULT_TABLES
+        .iter()
+        .map(|table| delete_nsfw_rows_query(table, "vid1"))
+        .collect();
+
+    assert_eq!(
+        queries[0],
+        "DELETE FROM `hot-or-not-feed-intelligence.yral_ds.video_nsfw` WHERE video_id = 'vid1'"
+    );
+    assert!(queries[1].contains("yral_ds.video_nsfw_agg"));
+}
+
+#[test]
+fn test_upload_info_query_filters_by_video() {
+    let query = upload_info_query("vid1");
+
+    assert!(query.contains("event = 'video_upload_successful'"));
+    assert!(query.contains("JSON_EXTRACT_SCALAR(params, '$.video_id') = 'vid1'"));
+}
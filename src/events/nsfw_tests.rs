@@ -0,0 +1,383 @@
+use std::sync::Mutex;
+
+use candid::Principal;
+use chrono::{DateTime, TimeZone, Utc};
+use tonic::metadata::MetadataValue;
+
+use super::event::UploadVideoInfo;
+use super::nsfw::{
+    csam_chat_message, csam_flagged_key, detect_csam_and_alert,
+    nsfw_detector::{EmbeddingNsfwDetectorResponse, NsfwDetectorResponse},
+    CsamAlert, CsamResponder, NSFWDetectionResult, NSFWInfo, CSAM_CHAT_ALERT_STEP,
+    CSAM_POST_BAN_STEP, CSAM_WEBHOOK_STEP, NSFW_CONFIDENCE_LOWER_HEADER,
+    NSFW_CONFIDENCE_UPPER_HEADER, NSFW_DETECTION_COLUMNS_DDL, NSFW_MODEL_VERSION_HEADER,
+    UNKNOWN_NSFW_MODEL_VERSION,
+};
+use crate::config::NsfwConfig;
+
+fn response(probability: f32) -> tonic::Response<EmbeddingNsfwDetectorResponse> {
+    tonic::Response::new(EmbeddingNsfwDetectorResponse {
+        probability,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_detection_result_from_response_metadata() {
+    let mut res = response(0.72);
+    let metadata = res.metadata_mut();
+    metadata.insert(
+        NSFW_MODEL_VERSION_HEADER,
+        MetadataValue::from_static("embedding-nsfw-2025-03"),
+    );
+    metadata.insert(
+        NSFW_CONFIDENCE_LOWER_HEADER,
+        MetadataValue::from_static("0.65"),
+    );
+    metadata.insert(
+        NSFW_CONFIDENCE_UPPER_HEADER,
+        MetadataValue::from_static("0.8"),
+    );
+
+    let result = NSFWDetectionResult::from_response(res, &NsfwConfig::default());
+
+    assert!(result.is_nsfw);
+    assert_eq!(result.probability, 0.72);
+    assert_eq!(result.model_version, "embedding-nsfw-2025-03");
+    assert_eq!(result.confidence_lower, 0.65);
+    assert_eq!(result.confidence_upper, 0.8);
+}
+
+#[test]
+fn test_detection_result_without_metadata() {
+    let result = NSFWDetectionResult::from_response(response(0.1), &NsfwConfig::default());
+
+    assert!(!result.is_nsfw);
+    assert_eq!(result.model_version, UNKNOWN_NSFW_MODEL_VERSION);
+    assert_eq!(result.confidence_lower, 0.1);
+    assert_eq!(result.confidence_upper, 0.1);
+}
+
+#[test]
+fn test_unparsable_bounds_fall_back_to_probability() {
+    let mut res = response(0.4);
+    res.metadata_mut().insert(
+        NSFW_CONFIDENCE_LOWER_HEADER,
+        MetadataValue::from_static("n/a"),
+    );
+
+    let result = NSFWDetectionResult::from_response(res, &NsfwConfig::default());
+
+    assert!(result.is_nsfw);
+    assert_eq!(result.confidence_lower, 0.4);
+}
+
+fn custom_config() -> NsfwConfig {
+    NsfwConfig {
+        probability_threshold: 0.7,
+        ec_categories: vec!["explicit".into()],
+        gore_categories: vec!["VERY_LIKELY".into()],
+    }
+}
+
+#[test]
+fn test_default_config_matches_the_previous_labels() {
+    let config = NsfwConfig::default();
+
+    for (ec, gore) in [
+        ("nudity", "UNLIKELY"),
+        ("provocative", ""),
+        ("", "POSSIBLE"),
+    ] {
+        assert!(config.is_nsfw(ec, gore, false));
+    }
+    assert!(!config.is_nsfw("neutral", "UNLIKELY", false));
+    assert!(config.is_nsfw("neutral", "UNLIKELY", true));
+}
+
+#[test]
+fn test_custom_categories_flip_is_nsfw() {
+    let config = custom_config();
+    let info = |nsfw_ec: &str, nsfw_gore: &str| {
+        NSFWInfo::from_response(
+            NsfwDetectorResponse {
+                nsfw_ec: nsfw_ec.into(),
+                nsfw_gore: nsfw_gore.into(),
+                ..Default::default()
+            },
+            &config,
+        )
+    };
+
+    assert!(!info("nudity", "LIKELY").is_nsfw);
+    assert!(info("explicit", "UNLIKELY").is_nsfw);
+    assert!(info("neutral", "VERY_LIKELY").is_nsfw);
+    assert!(
+        NSFWInfo::from_response(
+            NsfwDetectorResponse {
+                nsfw_ec: "nudity".into(),
+                ..Default::default()
+            },
+            &NsfwConfig::default()
+        )
+        .is_nsfw
+    );
+}
+
+#[test]
+fn test_custom_threshold_flips_detection_result() {
+    assert!(NSFWDetectionResult::from_response(response(0.5), &NsfwConfig::default()).is_nsfw);
+    assert!(!NSFWDetectionResult::from_response(response(0.5), &custom_config()).is_nsfw);
+    assert!(NSFWDetectionResult::from_response(response(0.7), &custom_config()).is_nsfw);
+}
+
+#[test]
+fn test_ddl_adds_every_detection_column() {
+    for column in [
+        "model_version STRING",
+        "confidence_lower FLOAT64",
+        "confidence_upper FLOAT64",
+        "detected_at TIMESTAMP",
+    ] {
+        assert!(
+            NSFW_DETECTION_COLUMNS_DDL.contains(&format!("ADD COLUMN IF NOT EXISTS {}", column))
+        );
+    }
+}
+
+/// Records what each step was called with, the steps named in `failing` return an error.
+/// `steps` is the flag's hash of completed steps.
+#[derive(Default)]
+struct MockResponder {
+    failing: Vec<&'static str>,
+    webhook: Mutex<Vec<CsamAlert>>,
+    chat: Mutex<Vec<serde_json::Value>>,
+    flagged: Mutex<Vec<(String, DateTime<Utc>)>>,
+    steps: Mutex<Vec<String>>,
+    banned: Mutex<Vec<(Principal, u64)>>,
+}
+
+impl MockResponder {
+    fn failing(failing: &[&'static str]) -> Self {
+        Self {
+            failing: failing.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn result(&self, step: &str) -> Result<(), anyhow::Error> {
+        if self.failing.contains(&step) {
+            Err(anyhow::anyhow!("{} unavailable", step))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl CsamResponder for MockResponder {
+    async fn report_to_webhook(&self, alert: &CsamAlert) -> Result<(), anyhow::Error> {
+        self.webhook.lock().unwrap().push(alert.clone());
+        self.result(CSAM_WEBHOOK_STEP)
+    }
+
+    async fn send_chat_alert(&self, message: serde_json::Value) -> Result<(), anyhow::Error> {
+        self.chat.lock().unwrap().push(message);
+        self.result(CSAM_CHAT_ALERT_STEP)
+    }
+
+    async fn flag_video(
+        &self,
+        video_id: &str,
+        detected_at: DateTime<Utc>,
+    ) -> Result<(), anyhow::Error> {
+        self.result("flag")?;
+        self.flagged
+            .lock()
+            .unwrap()
+            .push((video_id.to_string(), detected_at));
+        Ok(())
+    }
+
+    async fn completed_steps(&self, _video_id: &str) -> Result<Vec<String>, anyhow::Error> {
+        Ok(self.steps.lock().unwrap().clone())
+    }
+
+    async fn record_step(&self, _video_id: &str, step: &str) -> Result<(), anyhow::Error> {
+        self.steps.lock().unwrap().push(step.to_string());
+        Ok(())
+    }
+
+    async fn ban_post(
+        &self,
+        publisher_canister: Principal,
+        post_id: u64,
+    ) -> Result<(), anyhow::Error> {
+        self.banned
+            .lock()
+            .unwrap()
+            .push((publisher_canister, post_id));
+        self.result(CSAM_POST_BAN_STEP)
+    }
+}
+
+const PUBLISHER_CANISTER: &str = "rrkah-fqaaa-aaaaa-aaaaq-cai";
+
+fn video_info() -> UploadVideoInfo {
+    UploadVideoInfo {
+        video_id: "vid1".into(),
+        canister_id: PUBLISHER_CANISTER.into(),
+        post_id: 7,
+        timestamp: "2025-01-01T00:00:00Z".into(),
+        publisher_user_id: "2vxsx-fae".into(),
+        channel_id: None,
+        duration_secs: None,
+    }
+}
+
+fn csam_info() -> NSFWInfo {
+    NSFWInfo {
+        is_nsfw: true,
+        csam_detected: true,
+        ..Default::default()
+    }
+}
+
+fn detected_at() -> DateTime<Utc> {
+    Utc.timestamp_opt(1_700_000_000, 0).unwrap()
+}
+
+#[tokio::test]
+async fn test_no_alert_without_csam() {
+    let responder = MockResponder::default();
+    let nsfw_info = NSFWInfo {
+        is_nsfw: true,
+        ..Default::default()
+    };
+
+    let detected = detect_csam_and_alert(&responder, &nsfw_info, &video_info(), detected_at())
+        .await
+        .unwrap();
+
+    assert!(!detected);
+    assert!(responder.webhook.lock().unwrap().is_empty());
+    assert!(responder.chat.lock().unwrap().is_empty());
+    assert!(responder.flagged.lock().unwrap().is_empty());
+    assert!(responder.banned.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_csam_runs_every_step() {
+    let responder = MockResponder::default();
+
+    let detected = detect_csam_and_alert(&responder, &csam_info(), &video_info(), detected_at())
+        .await
+        .unwrap();
+
+    assert!(detected);
+    let alert = CsamAlert::new(&video_info(), detected_at());
+    assert_eq!(*responder.webhook.lock().unwrap(), vec![alert.clone()]);
+    assert_eq!(
+        *responder.chat.lock().unwrap(),
+        vec![csam_chat_message(&alert)]
+    );
+    assert_eq!(
+        *responder.flagged.lock().unwrap(),
+        vec![("vid1".to_string(), detected_at())]
+    );
+    assert_eq!(
+        *responder.banned.lock().unwrap(),
+        vec![(Principal::from_text(PUBLISHER_CANISTER).unwrap(), 7)]
+    );
+    assert_eq!(
+        *responder.steps.lock().unwrap(),
+        vec![CSAM_WEBHOOK_STEP, CSAM_CHAT_ALERT_STEP, CSAM_POST_BAN_STEP]
+    );
+}
+
+#[tokio::test]
+async fn test_failed_webhook_still_flags_and_bans() {
+    let responder = MockResponder::failing(&[CSAM_WEBHOOK_STEP, CSAM_CHAT_ALERT_STEP]);
+
+    let err = detect_csam_and_alert(&responder, &csam_info(), &video_info(), detected_at())
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().ends_with("failed: webhook, chat alert"));
+    assert_eq!(responder.flagged.lock().unwrap().len(), 1);
+    assert_eq!(responder.banned.lock().unwrap().len(), 1);
+    assert_eq!(*responder.steps.lock().unwrap(), vec![CSAM_POST_BAN_STEP]);
+}
+
+#[tokio::test]
+async fn test_retry_only_runs_the_failed_steps() {
+    let responder = MockResponder::default();
+    responder
+        .steps
+        .lock()
+        .unwrap()
+        .extend(["detected_at".to_string(), CSAM_WEBHOOK_STEP.to_string()]);
+
+    detect_csam_and_alert(&responder, &csam_info(), &video_info(), detected_at())
+        .await
+        .unwrap();
+
+    assert!(responder.webhook.lock().unwrap().is_empty());
+    assert_eq!(responder.chat.lock().unwrap().len(), 1);
+    assert_eq!(responder.banned.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_nothing_is_sent_without_the_flag() {
+    let responder = MockResponder::failing(&["flag"]);
+
+    assert!(
+        detect_csam_and_alert(&responder, &csam_info(), &video_info(), detected_at())
+            .await
+            .is_err()
+    );
+    assert!(responder.webhook.lock().unwrap().is_empty());
+    assert!(responder.chat.lock().unwrap().is_empty());
+    assert!(responder.banned.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_invalid_publisher_canister_fails_the_ban_only() {
+    let responder = MockResponder::default();
+    let mut video_info = video_info();
+    video_info.canister_id = "not-a-principal".into();
+
+    let err = detect_csam_and_alert(&responder, &csam_info(), &video_info, detected_at())
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().ends_with("failed: post ban"));
+    assert_eq!(responder.webhook.lock().unwrap().len(), 1);
+    assert!(responder.banned.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_csam_alert_payload() {
+    let alert = CsamAlert::new(&video_info(), detected_at());
+
+    assert_eq!(
+        serde_json::to_value(&alert).unwrap(),
+        serde_json::json!({
+            "video_id": "vid1",
+            "publisher_canister_id": PUBLISHER_CANISTER,
+            "publisher_user_id": "2vxsx-fae",
+            "post_id": 7,
+            "detected_at": "2023-11-14T22:13:20Z",
+        })
+    );
+    let text = csam_chat_message(&alert)["text"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(text.contains("video_id: vid1"));
+    assert!(!text.contains("yral.com"));
+}
+
+#[test]
+fn test_csam_flagged_key() {
+    assert_eq!(csam_flagged_key("vid1"), "csam_flagged:vid1");
+}
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use candid::Principal;
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::instrument;
+use utoipa::ToSchema;
+
+#[cfg(not(feature = "local-bin"))]
+use crate::types::RedisPool;
+use crate::{app_state::AppState, types::DelegatedIdentityWire};
+
+use super::{
+    event::Event,
+    pipeline::{EventPipeline, StageFailure},
+    verify::get_user_principal,
+};
+
+/// Redis set of the principals opted out of analytics, kept until they opt back in
+pub const ANALYTICS_OPTED_OUT_KEY: &str = "analytics:opted_out";
+
+pub(crate) trait OptOutStore {
+    async fn is_opted_out(&self, user: Principal) -> Result<bool, anyhow::Error>;
+
+    async fn opt_out(&self, user: Principal) -> Result<(), anyhow::Error>;
+
+    async fn opt_in(&self, user: Principal) -> Result<(), anyhow::Error>;
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl OptOutStore for RedisPool {
+    async fn is_opted_out(&self, user: Principal) -> Result<bool, anyhow::Error> {
+        let mut conn = self.get().await?;
+        Ok(conn
+            .sismember(ANALYTICS_OPTED_OUT_KEY, user.to_text())
+            .await?)
+    }
+
+    async fn opt_out(&self, user: Principal) -> Result<(), anyhow::Error> {
+        let mut conn = self.get().await?;
+        conn.sadd::<_, _, ()>(ANALYTICS_OPTED_OUT_KEY, user.to_text())
+            .await?;
+        Ok(())
+    }
+
+    async fn opt_in(&self, user: Principal) -> Result<(), anyhow::Error> {
+        let mut conn = self.get().await?;
+        conn.srem::<_, _, ()>(ANALYTICS_OPTED_OUT_KEY, user.to_text())
+            .await?;
+        Ok(())
+    }
+}
+
+/// The user whose activity the event records, its `user_id` param or the viewer of a profile
+/// view
+pub fn event_user(event: &Event) -> Option<Principal> {
+    let params: Value = serde_json::from_str(&event.event.params).ok()?;
+    let user_id = params.get("user_id").or_else(|| params.get("viewer_id"))?;
+    Principal::from_text(user_id.as_str()?).ok()
+}
+
+/// Whether the user opted out. A failed lookup keeps tracking the user so a Redis outage does
+/// not drop every event's analytics.
+pub async fn is_user_opted_out(store: &impl OptOutStore, user: Principal) -> bool {
+    store.is_opted_out(user).await.unwrap_or_else(|e| {
+        log::error!("Failed to read analytics opt out of {}: {}", user, e);
+        false
+    })
+}
+
+/// Runs the pipeline, without its tracking stages when the event's user opted out. `opted_out`
+/// is the opt out of a caller that already knows the user, e.g. the verified sender of a bulk
+/// request whose events name their user in other params. Otherwise the `user_id` param decides.
+pub async fn process_respecting_opt_out<S: Sync>(
+    pipeline: &EventPipeline<S>,
+    store: &impl OptOutStore,
+    event: &Event,
+    opted_out: Option<bool>,
+    state: &S,
+) -> Vec<StageFailure> {
+    let opted_out = match (opted_out, event_user(event)) {
+        (Some(opted_out), _) => opted_out,
+        (None, Some(user)) => is_user_opted_out(store, user).await,
+        (None, None) => false,
+    };
+
+    if opted_out {
+        pipeline.run_untracked(event, state).await
+    } else {
+        pipeline.run(event, state).await
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct AnalyticsOptOutRequest {
+    pub delegated_identity_wire: DelegatedIdentityWire,
+}
+
+async fn update_opt_out(
+    state: &AppState,
+    req: AnalyticsOptOutRequest,
+    opt_out: bool,
+) -> Result<(StatusCode, String), (StatusCode, String)> {
+    let user = get_user_principal(state, req.delegated_identity_wire)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::UNAUTHORIZED,
+                format!("Failed to get user info: {}", e),
+            )
+        })?;
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let store = &state.canister_backup_redis_pool;
+        let res = if opt_out {
+            store.opt_out(user).await
+        } else {
+            store.opt_in(user).await
+        };
+        res.map_err(|e| {
+            log::error!("Failed to update analytics opt out of {}: {}", user, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update analytics opt out".to_string(),
+            )
+        })?;
+
+        log::info!("User {} analytics opt out set to {}", user, opt_out);
+
+        Ok((StatusCode::OK, "Analytics opt out updated".to_string()))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (user, opt_out);
+        Err((
+            StatusCode::NOT_IMPLEMENTED,
+            "not implemented for local binary".to_string(),
+        ))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/opt-out",
+    request_body = AnalyticsOptOutRequest,
+    tag = "events",
+    responses(
+        (status = 200, description = "User opted out of analytics"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state, req))]
+pub async fn opt_out_of_analytics(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<AnalyticsOptOutRequest>,
+) -> Result<(StatusCode, String), (StatusCode, String)> {
+    update_opt_out(&state, req, true).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/opt-out",
+    request_body = AnalyticsOptOutRequest,
+    tag = "events",
+    responses(
+        (status = 200, description = "User tracked again"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state, req))]
+pub async fn opt_in_to_analytics(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<AnalyticsOptOutRequest>,
+) -> Result<(StatusCode, String), (StatusCode, String)> {
+    update_opt_out(&state, req, false).await
+}
@@ -0,0 +1,215 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use candid::Principal;
+use serde_json::json;
+
+use super::event::Event;
+use super::opt_out::{event_user, is_user_opted_out, process_respecting_opt_out, OptOutStore};
+use super::pipeline::{EventPipeline, EventStage, StageError};
+use super::warehouse_events::WarehouseEvent;
+
+/// Names of the stages that ran, in order
+type Ran = Mutex<Vec<&'static str>>;
+
+#[derive(Default)]
+struct MockStore {
+    opted_out: Mutex<HashSet<Principal>>,
+    unavailable: bool,
+}
+
+impl OptOutStore for MockStore {
+    async fn is_opted_out(&self, user: Principal) -> Result<bool, anyhow::Error> {
+        if self.unavailable {
+            return Err(anyhow::anyhow!("redis unavailable"));
+        }
+        Ok(self.opted_out.lock().unwrap().contains(&user))
+    }
+
+    async fn opt_out(&self, user: Principal) -> Result<(), anyhow::Error> {
+        self.opted_out.lock().unwrap().insert(user);
+        Ok(())
+    }
+
+    async fn opt_in(&self, user: Principal) -> Result<(), anyhow::Error> {
+        self.opted_out.lock().unwrap().remove(&user);
+        Ok(())
+    }
+}
+
+struct MockStage {
+    name: &'static str,
+    tracks_user: bool,
+}
+
+#[tonic::async_trait]
+impl EventStage<Ran> for MockStage {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn tracks_user(&self) -> bool {
+        self.tracks_user
+    }
+
+    async fn process(&self, _event: &Event, state: &Ran) -> Result<(), StageError> {
+        state.lock().unwrap().push(self.name);
+        Ok(())
+    }
+}
+
+fn pipeline() -> EventPipeline<Ran> {
+    EventPipeline::new(
+        [
+            ("stream_to_bigquery", true),
+            ("handle_token_burn", false),
+            ("update_watch_history", true),
+        ]
+        .into_iter()
+        .map(|(name, tracks_user)| {
+            Box::new(MockStage { name, tracks_user }) as Box<dyn EventStage<Ran> + Send + Sync>
+        })
+        .collect(),
+    )
+}
+
+fn user() -> Principal {
+    Principal::from_text("2vxsx-fae").unwrap()
+}
+
+fn event_of(user_id: &str) -> Event {
+    Event::new(WarehouseEvent {
+        event: "video_duration_watched".into(),
+        params: json!({ "user_id": user_id, "video_id": "vid1" }).to_string(),
+    })
+}
+
+#[test]
+fn test_event_user_reads_user_id_param() {
+    assert_eq!(event_user(&event_of("2vxsx-fae")), Some(user()));
+    assert_eq!(event_user(&event_of("not a principal")), None);
+
+    let without_user = Event::new(WarehouseEvent {
+        event: "video_upload_successful".into(),
+        params: json!({ "video_id": "vid1" }).to_string(),
+    });
+    assert_eq!(event_user(&without_user), None);
+}
+
+#[test]
+fn test_event_user_of_profile_view_is_the_viewer() {
+    let profile_view = Event::new(WarehouseEvent {
+        event: "creator_profile_viewed".into(),
+        params: json!({ "viewer_id": "2vxsx-fae", "creator_id": "rrkah-fqaaa-aaaaa-aaaaq-cai" })
+            .to_string(),
+    });
+
+    assert_eq!(event_user(&profile_view), Some(user()));
+}
+
+#[tokio::test]
+async fn test_tracked_user_runs_every_stage() {
+    let ran = Ran::default();
+
+    process_respecting_opt_out(
+        &pipeline(),
+        &MockStore::default(),
+        &event_of("2vxsx-fae"),
+        None,
+        &ran,
+    )
+    .await;
+
+    assert_eq!(
+        *ran.lock().unwrap(),
+        vec![
+            "stream_to_bigquery",
+            "handle_token_burn",
+            "update_watch_history"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_opted_out_user_is_not_tracked() {
+    let store = MockStore::default();
+    store.opt_out(user()).await.unwrap();
+    let ran = Ran::default();
+
+    process_respecting_opt_out(&pipeline(), &store, &event_of("2vxsx-fae"), None, &ran).await;
+
+    assert_eq!(*ran.lock().unwrap(), vec!["handle_token_burn"]);
+}
+
+#[tokio::test]
+async fn test_opting_back_in_resumes_tracking() {
+    let store = MockStore::default();
+    store.opt_out(user()).await.unwrap();
+    store.opt_in(user()).await.unwrap();
+    let ran = Ran::default();
+
+    process_respecting_opt_out(&pipeline(), &store, &event_of("2vxsx-fae"), None, &ran).await;
+
+    assert_eq!(ran.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_opt_out_only_applies_to_that_user() {
+    let store = MockStore::default();
+    store.opt_out(user()).await.unwrap();
+    let ran = Ran::default();
+
+    process_respecting_opt_out(
+        &pipeline(),
+        &store,
+        &event_of("rrkah-fqaaa-aaaaa-aaaaq-cai"),
+        None,
+        &ran,
+    )
+    .await;
+
+    assert_eq!(ran.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_resolved_opt_out_applies_to_events_without_user_id() {
+    let event = Event::new(WarehouseEvent {
+        event: "like_video".into(),
+        params: json!({ "publisher_user_id": "rrkah-fqaaa-aaaaa-aaaaq-cai", "video_id": "vid1" })
+            .to_string(),
+    });
+    let ran = Ran::default();
+
+    process_respecting_opt_out(&pipeline(), &MockStore::default(), &event, Some(true), &ran).await;
+
+    assert_eq!(*ran.lock().unwrap(), vec!["handle_token_burn"]);
+}
+
+#[tokio::test]
+async fn test_resolved_opt_out_is_not_looked_up_again() {
+    let store = MockStore {
+        unavailable: true,
+        ..Default::default()
+    };
+    let ran = Ran::default();
+
+    process_respecting_opt_out(
+        &pipeline(),
+        &store,
+        &event_of("2vxsx-fae"),
+        Some(true),
+        &ran,
+    )
+    .await;
+
+    assert_eq!(*ran.lock().unwrap(), vec!["handle_token_burn"]);
+}
+
+#[tokio::test]
+async fn test_unavailable_store_keeps_tracking() {
+    let store = MockStore {
+        unavailable: true,
+        ..Default::default()
+    };
+
+    assert!(!is_user_opted_out(&store, user()).await);
+}
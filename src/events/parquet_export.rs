@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::NaiveDate;
+#[cfg(not(feature = "local-bin"))]
+use google_cloud_bigquery::http::job::{get::GetJobRequest, query::QueryRequest, JobState};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    AppError,
+};
+
+const EVENTS_TABLE: &str = "hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics";
+#[cfg(not(feature = "local-bin"))]
+const EXPORT_PROJECT: &str = "hot-or-not-feed-intelligence";
+pub const EXPORT_BUCKET: &str = "yral-exports";
+
+#[derive(Debug, Deserialize)]
+pub struct ExportToParquetRequest {
+    /// `YYYY-MM-DD`, the UTC day to export
+    pub date: String,
+    pub event_types: Vec<String>,
+}
+
+/// A started export, its progress is read from `GET /events/export-to-parquet/{job_id}`
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ParquetExportJob {
+    pub event_type: String,
+    pub uri: String,
+    pub job_id: String,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportToParquetResponse {
+    pub jobs: Vec<ParquetExportJob>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportJobStatusQuery {
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ExportJobStatus {
+    Running,
+    Done,
+    /// The job's `errorResult`
+    Failed {
+        error: String,
+    },
+}
+
+impl ExportJobStatus {
+    /// A job with an `errorResult` failed, even once it is done
+    pub fn new(done: bool, error_result: Option<String>) -> Self {
+        match error_result {
+            Some(error) => Self::Failed { error },
+            None if done => Self::Done,
+            None => Self::Running,
+        }
+    }
+}
+
+/// Event names end up in the query and the object path
+pub fn is_valid_event_type(event_type: &str) -> bool {
+    !event_type.is_empty()
+        && event_type
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// BigQuery shards exports over files, the URI needs a single `*` wildcard for the shard number
+pub fn export_uri(date: NaiveDate, event_type: &str) -> String {
+    format!(
+        "gs://{}/events/{}/{}-*.parquet",
+        EXPORT_BUCKET,
+        date.format("%Y-%m-%d"),
+        event_type
+    )
+}
+
+pub fn export_query(date: NaiveDate, event_type: &str) -> String {
+    format!(
+        "EXPORT DATA OPTIONS(
+            uri = '{}',
+            format = 'PARQUET',
+            compression = 'SNAPPY',
+            overwrite = true
+        ) AS
+        SELECT *
+        FROM `{}`
+        WHERE event = '{}'
+            AND DATE(timestamp) = '{}'",
+        export_uri(date, event_type),
+        EVENTS_TABLE,
+        event_type,
+        date.format("%Y-%m-%d")
+    )
+}
+
+/// Starts the export job without waiting for it, an export that fails right away is an error
+#[cfg(not(feature = "local-bin"))]
+async fn start_export(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+    date: NaiveDate,
+    event_type: &str,
+) -> Result<ParquetExportJob, anyhow::Error> {
+    let request = QueryRequest {
+        query: export_query(date, event_type),
+        timeout_ms: Some(0),
+        ..Default::default()
+    };
+    let response = bigquery_client
+        .job()
+        .query(EXPORT_PROJECT, &request)
+        .await?;
+
+    if let Some(error) = response.errors.as_ref().and_then(|errors| errors.first()) {
+        return Err(anyhow::anyhow!(
+            "export of {} failed: {}",
+            event_type,
+            error.message
+        ));
+    }
+
+    Ok(ParquetExportJob {
+        event_type: event_type.to_string(),
+        uri: export_uri(date, event_type),
+        job_id: response.job_reference.job_id,
+        location: response.job_reference.location,
+    })
+}
+
+/// Starts exporting a day of events to GCS as Parquet, one job and set of files per event type
+#[instrument(skip(state, token))]
+pub async fn export_to_parquet_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Json(req): Json<ExportToParquetRequest>,
+) -> Result<Json<ExportToParquetResponse>, AppError> {
+    check_auth_admin(&token).map_err(|_| AppError::Unauthorized)?;
+
+    let date = NaiveDate::parse_from_str(&req.date, "%Y-%m-%d")
+        .map_err(|e| AppError::InvalidInput(format!("invalid date: {}", e)))?;
+
+    if req.event_types.is_empty() {
+        return Err(AppError::InvalidInput("no event types given".to_string()));
+    }
+    if let Some(invalid) = req.event_types.iter().find(|e| !is_valid_event_type(e)) {
+        return Err(AppError::InvalidInput(format!(
+            "invalid event type: {}",
+            invalid
+        )));
+    }
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let mut jobs = Vec::with_capacity(req.event_types.len());
+        for event_type in &req.event_types {
+            let job = start_export(&state.bigquery_client, date, event_type)
+                .await
+                .map_err(AppError::BigQueryError)?;
+            log::info!(
+                "Started export of {} events of {} to {} as job {}",
+                event_type,
+                date,
+                job.uri,
+                job.job_id
+            );
+            jobs.push(job);
+        }
+
+        Ok(Json(ExportToParquetResponse { jobs }))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, date);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
+
+/// State of an export job started by [`export_to_parquet_handler`]
+#[instrument(skip(state, token))]
+pub async fn export_job_status_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Path(job_id): Path<String>,
+    Query(query): Query<ExportJobStatusQuery>,
+) -> Result<Json<ExportJobStatus>, AppError> {
+    check_auth_admin(&token).map_err(|_| AppError::Unauthorized)?;
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let job = state
+            .bigquery_client
+            .job()
+            .get(
+                EXPORT_PROJECT,
+                &job_id,
+                &GetJobRequest {
+                    location: query.location,
+                },
+            )
+            .await
+            .map_err(|e| AppError::BigQueryError(e.into()))?;
+
+        Ok(Json(ExportJobStatus::new(
+            matches!(job.status.state, JobState::Done),
+            job.status.error_result.map(|error| error.message),
+        )))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, job_id, query);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
@@ -0,0 +1,59 @@
+use chrono::NaiveDate;
+use serde_json::json;
+
+use super::parquet_export::{export_query, export_uri, is_valid_event_type, ExportJobStatus};
+
+fn date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 3, 14).unwrap()
+}
+
+#[test]
+fn test_export_uri_is_sharded_per_event_type() {
+    assert_eq!(
+        export_uri(date(), "video_viewed"),
+        "gs://yral-exports/events/2025-03-14/video_viewed-*.parquet"
+    );
+}
+
+#[test]
+fn test_export_query_filters_event_and_day() {
+    let query = export_query(date(), "like_video");
+
+    assert!(query.starts_with("EXPORT DATA OPTIONS("));
+    assert!(query.contains("uri = 'gs://yral-exports/events/2025-03-14/like_video-*.parquet'"));
+    assert!(query.contains("format = 'PARQUET'"));
+    assert!(query.contains("WHERE event = 'like_video'"));
+    assert!(query.contains("DATE(timestamp) = '2025-03-14'"));
+}
+
+#[test]
+fn test_event_type_validation() {
+    assert!(is_valid_event_type("video_duration_watched"));
+    assert!(!is_valid_event_type(""));
+    assert!(!is_valid_event_type("like_video' OR '1'='1"));
+    assert!(!is_valid_event_type("../other"));
+}
+
+#[test]
+fn test_export_job_status() {
+    assert_eq!(ExportJobStatus::new(false, None), ExportJobStatus::Running);
+    assert_eq!(ExportJobStatus::new(true, None), ExportJobStatus::Done);
+    assert_eq!(
+        ExportJobStatus::new(true, Some("Access Denied: bucket yral-exports".into())),
+        ExportJobStatus::Failed {
+            error: "Access Denied: bucket yral-exports".into()
+        }
+    );
+}
+
+#[test]
+fn test_failed_export_job_status_shows_error() {
+    assert_eq!(
+        serde_json::to_value(ExportJobStatus::new(true, Some("quota exceeded".into()))).unwrap(),
+        json!({ "state": "failed", "error": "quota exceeded" })
+    );
+    assert_eq!(
+        serde_json::to_value(ExportJobStatus::Running).unwrap(),
+        json!({ "state": "running" })
+    );
+}
@@ -0,0 +1,280 @@
+use std::{cell::RefCell, future::Future, panic::AssertUnwindSafe};
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+use super::{event::Event, subscribe};
+use crate::app_state::AppState;
+
+#[derive(Error, Debug)]
+pub enum StageError {
+    #[error("{0}")]
+    Failed(#[from] anyhow::Error),
+    #[error("panicked: {0}")]
+    Panicked(String),
+}
+
+/// One step of processing an incoming event. Stages run in order and a failing stage never
+/// stops the ones after it.
+#[tonic::async_trait]
+pub trait EventStage<S: Sync = AppState> {
+    fn name(&self) -> &'static str;
+
+    /// Stages recording what the user does, skipped for users opted out of analytics
+    fn tracks_user(&self) -> bool {
+        false
+    }
+
+    async fn process(&self, event: &Event, state: &S) -> Result<(), StageError>;
+}
+
+tokio::task_local! {
+    /// Background work spawned by the stage running on this task
+    static STAGE_WORK: RefCell<Vec<JoinHandle<()>>>;
+}
+
+/// Spawns background work of an event stage. Work spawned while a [`FnStage`] runs is watched
+/// so its panics are logged with the stage, elsewhere it is detached like `tokio::spawn`.
+pub fn spawn_stage_work<F>(work: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = tokio::spawn(work);
+    let _ = STAGE_WORK.try_with(|stage_work| stage_work.borrow_mut().push(handle));
+}
+
+/// Runs `f`, returning the handles of the work it spawned through [`spawn_stage_work`]
+pub fn collect_stage_work<T>(f: impl FnOnce() -> T) -> (T, Vec<JoinHandle<()>>) {
+    STAGE_WORK.sync_scope(RefCell::new(Vec::new()), || {
+        let res = f();
+        (res, STAGE_WORK.with(RefCell::take))
+    })
+}
+
+/// Waits for the work, returning the messages of the tasks that panicked
+pub async fn stage_work_panics(work: Vec<JoinHandle<()>>) -> Vec<String> {
+    let mut panics = Vec::new();
+    for handle in work {
+        if let Err(e) = handle.await {
+            if e.is_panic() {
+                panics.push(panic_message(&*e.into_panic()));
+            }
+        }
+    }
+
+    panics
+}
+
+fn watch_stage_work(stage: &'static str, event: &str, work: Vec<JoinHandle<()>>) {
+    if work.is_empty() {
+        return;
+    }
+
+    let event = event.to_string();
+    tokio::spawn(async move {
+        for panic in stage_work_panics(work).await {
+            log::error!(
+                "Event stage {} panicked in the background for {}: {}",
+                stage,
+                event,
+                panic
+            );
+        }
+    });
+}
+
+/// Stage running one of the synchronous `Event` methods, which spawn their own work
+pub struct FnStage<S = AppState> {
+    name: &'static str,
+    run: fn(&Event, &S) -> Result<(), anyhow::Error>,
+    tracks_user: bool,
+}
+
+impl<S> FnStage<S> {
+    pub fn new(name: &'static str, run: fn(&Event, &S) -> Result<(), anyhow::Error>) -> Self {
+        Self {
+            name,
+            run,
+            tracks_user: false,
+        }
+    }
+
+    pub fn tracking(mut self) -> Self {
+        self.tracks_user = true;
+        self
+    }
+}
+
+#[tonic::async_trait]
+impl<S: Sync> EventStage<S> for FnStage<S> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn tracks_user(&self) -> bool {
+        self.tracks_user
+    }
+
+    async fn process(&self, event: &Event, state: &S) -> Result<(), StageError> {
+        let (res, work) = collect_stage_work(|| (self.run)(event, state));
+        watch_stage_work(self.name, &event.event.event, work);
+
+        res.map_err(StageError::from)
+    }
+}
+
+#[derive(Debug)]
+pub struct StageFailure {
+    pub stage: &'static str,
+    pub error: StageError,
+}
+
+pub struct EventPipeline<S: Sync = AppState> {
+    pub stages: Vec<Box<dyn EventStage<S> + Send + Sync>>,
+}
+
+impl<S: Sync> EventPipeline<S> {
+    pub fn new(stages: Vec<Box<dyn EventStage<S> + Send + Sync>>) -> Self {
+        Self { stages }
+    }
+
+    /// Runs every stage, returning the failures. Panics are caught and reported as failures,
+    /// panics of the work stages spawn are logged once the work finishes.
+    pub async fn run(&self, event: &Event, state: &S) -> Vec<StageFailure> {
+        self.run_stages(event, state, true).await
+    }
+
+    /// Runs the stages that do not track the user, for events of opted out users
+    pub async fn run_untracked(&self, event: &Event, state: &S) -> Vec<StageFailure> {
+        self.run_stages(event, state, false).await
+    }
+
+    async fn run_stages(&self, event: &Event, state: &S, track_user: bool) -> Vec<StageFailure> {
+        let mut failures = Vec::new();
+
+        for stage in &self.stages {
+            if stage.tracks_user() && !track_user {
+                continue;
+            }
+
+            let res = AssertUnwindSafe(stage.process(event, state))
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|panic| Err(StageError::Panicked(panic_message(&*panic))));
+
+            if let Err(error) = res {
+                log::error!(
+                    "Event stage {} failed for {}: {}",
+                    stage.name(),
+                    event.event.event,
+                    error
+                );
+                failures.push(StageFailure {
+                    stage: stage.name(),
+                    error,
+                });
+            }
+        }
+
+        failures
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+fn stage(
+    name: &'static str,
+    run: fn(&Event, &AppState) -> Result<(), anyhow::Error>,
+) -> Box<dyn EventStage + Send + Sync> {
+    Box::new(FnStage::new(name, run))
+}
+
+fn tracking_stage(
+    name: &'static str,
+    run: fn(&Event, &AppState) -> Result<(), anyhow::Error>,
+) -> Box<dyn EventStage + Send + Sync> {
+    Box::new(FnStage::new(name, run).tracking())
+}
+
+/// Stages every incoming event goes through, in order. Tracking stages record the user's
+/// activity: analytics, push notifications and the ML feed caches.
+pub static EVENT_PIPELINE: Lazy<EventPipeline> = Lazy::new(|| {
+    let mut stages = vec![tracking_stage("publish_to_subscribers", |event, state| {
+        subscribe::publish_event(
+            &state.event_subscribers,
+            &event.event.event,
+            &event.event.params,
+        );
+        Ok(())
+    })];
+
+    #[cfg(not(feature = "local-bin"))]
+    stages.push(tracking_stage("stream_to_bigquery", |event, state| {
+        if !event.is_verified_before_streaming() {
+            event.stream_to_bigquery(state);
+        }
+        Ok(())
+    }));
+
+    stages.push(stage("check_video_deduplication", |event, state| {
+        event.check_video_deduplication(state);
+        Ok(())
+    }));
+    stages.push(tracking_stage("update_watch_history", |event, state| {
+        event.update_watch_history(state);
+        Ok(())
+    }));
+    stages.push(tracking_stage("update_success_history", |event, state| {
+        event.update_success_history(state);
+        Ok(())
+    }));
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        stages.push(tracking_stage("stream_to_firestore", |event, state| {
+            event.stream_to_firestore(state);
+            Ok(())
+        }));
+        stages.push(stage(
+            "stream_to_bigquery_token_metadata",
+            |event, state| {
+                event.stream_to_bigquery_token_metadata(state);
+                Ok(())
+            },
+        ));
+    }
+
+    stages.push(stage("handle_login_successful", |event, state| {
+        event.handle_login_successful(state)
+    }));
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        stages.push(tracking_stage("check_view_milestones", |event, state| {
+            event.check_view_milestones(state)
+        }));
+        stages.push(stage("handle_video_nsfw_appeal", |event, state| {
+            event.handle_video_nsfw_appeal(state)
+        }));
+        stages.push(stage("handle_token_burn", |event, state| {
+            event.handle_token_burn(state)
+        }));
+        stages.push(stage("handle_duplicate_video_detected", |event, state| {
+            event.handle_duplicate_video_detected(state)
+        }));
+        stages.push(tracking_stage(
+            "handle_creator_profile_viewed",
+            |event, state| event.handle_creator_profile_viewed(state),
+        ));
+    }
+
+    EventPipeline::new(stages)
+});
@@ -0,0 +1,166 @@
+use std::sync::Mutex;
+
+use super::event::Event;
+use super::pipeline::{
+    collect_stage_work, spawn_stage_work, stage_work_panics, EventPipeline, EventStage, FnStage,
+    StageError,
+};
+use super::warehouse_events::WarehouseEvent;
+
+/// Names of the stages that ran, in order
+type Ran = Mutex<Vec<&'static str>>;
+
+enum Outcome {
+    Ok,
+    Fail,
+    Panic,
+}
+
+struct MockStage {
+    name: &'static str,
+    outcome: Outcome,
+}
+
+impl MockStage {
+    fn boxed(name: &'static str, outcome: Outcome) -> Box<dyn EventStage<Ran> + Send + Sync> {
+        Box::new(Self { name, outcome })
+    }
+}
+
+#[tonic::async_trait]
+impl EventStage<Ran> for MockStage {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn process(&self, _event: &Event, state: &Ran) -> Result<(), StageError> {
+        state.lock().unwrap().push(self.name);
+        match self.outcome {
+            Outcome::Ok => Ok(()),
+            Outcome::Fail => Err(anyhow::anyhow!("{} failed", self.name).into()),
+            Outcome::Panic => panic!("{} panicked", self.name),
+        }
+    }
+}
+
+fn event() -> Event {
+    Event::new(WarehouseEvent {
+        event: "video_duration_watched".into(),
+        params: "{}".into(),
+    })
+}
+
+#[tokio::test]
+async fn test_failed_stage_does_not_stop_later_stages() {
+    let pipeline = EventPipeline::new(vec![
+        MockStage::boxed("first", Outcome::Fail),
+        MockStage::boxed("second", Outcome::Ok),
+        MockStage::boxed("third", Outcome::Fail),
+    ]);
+    let ran = Ran::default();
+
+    let failures = pipeline.run(&event(), &ran).await;
+
+    assert_eq!(*ran.lock().unwrap(), vec!["first", "second", "third"]);
+    assert_eq!(
+        failures.iter().map(|f| f.stage).collect::<Vec<_>>(),
+        vec!["first", "third"]
+    );
+    assert_eq!(failures[0].error.to_string(), "first failed");
+}
+
+#[tokio::test]
+async fn test_panicking_stage_is_reported_as_failure() {
+    let pipeline = EventPipeline::new(vec![
+        MockStage::boxed("panics", Outcome::Panic),
+        MockStage::boxed("after", Outcome::Ok),
+    ]);
+    let ran = Ran::default();
+
+    let failures = pipeline.run(&event(), &ran).await;
+
+    assert_eq!(*ran.lock().unwrap(), vec!["panics", "after"]);
+    assert_eq!(failures.len(), 1);
+    assert!(matches!(
+        &failures[0].error,
+        StageError::Panicked(msg) if msg == "panics panicked"
+    ));
+}
+
+#[tokio::test]
+async fn test_fn_stage_maps_errors() {
+    let pipeline = EventPipeline::new(vec![
+        Box::new(FnStage::<Ran>::new("ok", |_, _| Ok(()))),
+        Box::new(FnStage::<Ran>::new("err", |event, _| {
+            Err(anyhow::anyhow!("bad params: {}", event.event.params))
+        })),
+    ]);
+
+    let failures = pipeline.run(&event(), &Ran::default()).await;
+
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].stage, "err");
+    assert_eq!(failures[0].error.to_string(), "bad params: {}");
+}
+
+#[tokio::test]
+async fn test_untracked_run_skips_tracking_stages() {
+    let pipeline = EventPipeline::new(vec![
+        Box::new(
+            FnStage::<Ran>::new("tracking", |_, ran| {
+                ran.lock().unwrap().push("tracking");
+                Ok(())
+            })
+            .tracking(),
+        ),
+        Box::new(FnStage::<Ran>::new("functional", |_, ran| {
+            ran.lock().unwrap().push("functional");
+            Ok(())
+        })),
+    ]);
+    let ran = Ran::default();
+
+    assert!(pipeline.run_untracked(&event(), &ran).await.is_empty());
+    assert_eq!(*ran.lock().unwrap(), vec!["functional"]);
+
+    pipeline.run(&event(), &ran).await;
+    assert_eq!(
+        *ran.lock().unwrap(),
+        vec!["functional", "tracking", "functional"]
+    );
+}
+
+#[tokio::test]
+async fn test_panics_of_spawned_stage_work_are_reported() {
+    let ((), work) = collect_stage_work(|| {
+        spawn_stage_work(async {});
+        spawn_stage_work(async { panic!("background boom") });
+    });
+
+    assert_eq!(work.len(), 2);
+    assert_eq!(stage_work_panics(work).await, vec!["background boom"]);
+}
+
+#[tokio::test]
+async fn test_fn_stage_with_panicking_work_still_returns() {
+    let pipeline = EventPipeline::new(vec![Box::new(FnStage::<Ran>::new("spawns", |_, ran| {
+        ran.lock().unwrap().push("spawns");
+        spawn_stage_work(async { panic!("background boom") });
+        Ok(())
+    }))]);
+    let ran = Ran::default();
+
+    assert!(pipeline.run(&event(), &ran).await.is_empty());
+    assert_eq!(*ran.lock().unwrap(), vec!["spawns"]);
+}
+
+#[tokio::test]
+async fn test_stage_work_outside_a_stage_is_detached() {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    spawn_stage_work(async move {
+        tx.send(()).unwrap();
+    });
+
+    rx.await.unwrap();
+}
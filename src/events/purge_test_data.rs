@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use candid::Principal;
+use chrono::{DateTime, Utc};
+use google_cloud_bigquery::http::{job::query::QueryRequest, tabledata::list::Value};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    AppError,
+};
+
+const EVENTS_TABLE: &str = "hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics";
+
+#[derive(Debug, Deserialize)]
+pub struct PurgeTestDataRequest {
+    pub dry_run: bool,
+    /// RFC 3339 timestamp, only events before it are purged
+    pub before_timestamp: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PurgeTestDataResponse {
+    pub dry_run: bool,
+    pub rows: i64,
+}
+
+fn test_events_filter(test_principals: &[Principal], before: DateTime<Utc>) -> String {
+    let principals = test_principals
+        .iter()
+        .map(|p| format!("'{}'", p.to_text()))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "WHERE JSON_EXTRACT_SCALAR(params, '$.user_id') IN ({})
+            AND timestamp < TIMESTAMP('{}')",
+        principals,
+        before.to_rfc3339()
+    )
+}
+
+pub fn purge_test_data_query(
+    test_principals: &[Principal],
+    before: DateTime<Utc>,
+    dry_run: bool,
+) -> String {
+    let filter = test_events_filter(test_principals, before);
+    if dry_run {
+        format!("SELECT COUNT(*) FROM `{}` {}", EVENTS_TABLE, filter)
+    } else {
+        format!("DELETE FROM `{}` {}", EVENTS_TABLE, filter)
+    }
+}
+
+/// Removes events sent by internal test accounts from the analytics table
+#[instrument(skip(state, token))]
+pub async fn purge_test_data_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Json(req): Json<PurgeTestDataRequest>,
+) -> Result<Json<PurgeTestDataResponse>, AppError> {
+    check_auth_admin(&token).map_err(|_| AppError::Unauthorized)?;
+
+    let before = DateTime::parse_from_rfc3339(&req.before_timestamp)
+        .map_err(|e| AppError::InvalidInput(format!("invalid before_timestamp: {}", e)))?
+        .with_timezone(&Utc);
+
+    if state.test_principals.is_empty() {
+        return Err(AppError::InvalidInput(
+            "no test principals configured".to_string(),
+        ));
+    }
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let request = QueryRequest {
+            query: purge_test_data_query(&state.test_principals, before, req.dry_run),
+            ..Default::default()
+        };
+        let response = state
+            .bigquery_client
+            .job()
+            .query("hot-or-not-feed-intelligence", &request)
+            .await
+            .map_err(|e| AppError::BigQueryError(e.into()))?;
+
+        let rows = if req.dry_run {
+            response
+                .rows
+                .and_then(|rows| rows.into_iter().next())
+                .and_then(|row| match row.f.first().map(|cell| &cell.v) {
+                    Some(Value::String(count)) => count.parse().ok(),
+                    _ => None,
+                })
+                .unwrap_or_default()
+        } else {
+            response.num_dml_affected_rows.unwrap_or_default()
+        };
+
+        log::info!(
+            "Purge of test data before {} (dry run: {}) matched {} rows",
+            before,
+            req.dry_run,
+            rows
+        );
+
+        Ok(Json(PurgeTestDataResponse {
+            dry_run: req.dry_run,
+            rows,
+        }))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = before;
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
@@ -0,0 +1,40 @@
+use candid::Principal;
+use chrono::{TimeZone, Utc};
+
+use super::purge_test_data::purge_test_data_query;
+
+fn principals() -> Vec<Principal> {
+    vec![
+        Principal::from_text("2vxsx-fae").unwrap(),
+        Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap(),
+    ]
+}
+
+#[test]
+fn test_delete_query_filters_test_principals_and_time() {
+    let before = Utc.with_ymd_and_hms(2025, 1, 31, 0, 0, 0).unwrap();
+
+    let query = purge_test_data_query(&principals(), before, false);
+
+    assert!(query.starts_with(
+        "DELETE FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`"
+    ));
+    assert!(query.contains("IN ('2vxsx-fae', 'rrkah-fqaaa-aaaaa-aaaaq-cai')"));
+    assert!(query.contains("timestamp < TIMESTAMP('2025-01-31T00:00:00+00:00')"));
+}
+
+#[test]
+fn test_dry_run_counts_instead_of_deleting() {
+    let before = Utc.with_ymd_and_hms(2025, 1, 31, 0, 0, 0).unwrap();
+
+    let query = purge_test_data_query(&principals(), before, true);
+
+    assert!(query.starts_with("SELECT COUNT(*) FROM"));
+    assert!(!query.contains("DELETE"));
+    assert_eq!(
+        query.split("WHERE").nth(1),
+        purge_test_data_query(&principals(), before, false)
+            .split("WHERE")
+            .nth(1)
+    );
+}
@@ -1,3 +1,5 @@
+use chrono::NaiveDate;
+
 pub fn get_icpump_insert_query(
     canister_id: String,
     description: String,
@@ -119,3 +121,14 @@ pub fn get_icpump_insert_query_created_at(
     FROM `token_name_embedding`, `token_description_embedding`;
     ", description, token_name, canister_id, description, host, link, logo, token_name, token_symbol, user_id, created_at)
 }
+
+/// Distinct users watching, liking or logging in on the UTC day `date`
+pub fn get_dau_query(date: NaiveDate) -> String {
+    format!(
+        "SELECT COUNT(DISTINCT JSON_EXTRACT_SCALAR(params, '$.user_id')) AS dau
+        FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+        WHERE event IN ('video_duration_watched', 'like_video', 'login_successful')
+            AND timestamp >= TIMESTAMP('{date}')
+            AND timestamp < TIMESTAMP_ADD(TIMESTAMP('{date}'), INTERVAL 1 DAY)"
+    )
+}
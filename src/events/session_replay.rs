@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use http::StatusCode;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    types::RedisPool,
+};
+
+pub const SESSION_REPLAY_TTL_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct SessionEvent {
+    pub event: String,
+    #[schema(value_type = Object)]
+    pub params: Value,
+    pub timestamp: f64,
+}
+
+impl SessionEvent {
+    pub fn new(event: String, params: &str, timestamp: f64) -> Self {
+        // params are sent as a json string, keep the raw string if it is not valid json
+        let params =
+            serde_json::from_str(params).unwrap_or_else(|_| Value::String(params.to_string()));
+
+        Self {
+            event,
+            params,
+            timestamp,
+        }
+    }
+}
+
+pub fn session_key(session_id: &str) -> String {
+    format!("session:{}", session_id)
+}
+
+pub fn now_timestamp() -> f64 {
+    chrono::Utc::now().timestamp_micros() as f64 / 1_000_000.0
+}
+
+/// Parses sorted set members, members that fail to parse are skipped
+pub fn parse_session_events(members: Vec<String>) -> Vec<SessionEvent> {
+    members
+        .into_iter()
+        .filter_map(|member| match serde_json::from_str(&member) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                log::warn!("Skipping invalid session event: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[instrument(skip(redis_pool, event))]
+pub async fn store_session_event(
+    redis_pool: &RedisPool,
+    session_id: &str,
+    event: &SessionEvent,
+) -> Result<(), anyhow::Error> {
+    let key = session_key(session_id);
+    let member = serde_json::to_string(event)?;
+
+    let mut conn = redis_pool.get().await?;
+    redis::pipe()
+        .atomic()
+        .zadd(&key, member, event.timestamp)
+        .expire(&key, SESSION_REPLAY_TTL_SECS)
+        .query_async::<()>(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(redis_pool))]
+pub async fn get_session_events(
+    redis_pool: &RedisPool,
+    session_id: &str,
+) -> Result<Vec<SessionEvent>, anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    let members: Vec<String> = conn.zrange(session_key(session_id), 0, -1).await?;
+
+    Ok(parse_session_events(members))
+}
+
+#[utoipa::path(
+    get,
+    path = "/session-replay/{session_id}",
+    params(
+        ("session_id" = String, Path, description = "Session id set by the frontend"),
+    ),
+    tag = "events",
+    responses(
+        (status = 200, description = "Events of the session in order", body = Vec<SessionEvent>),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state, token))]
+pub async fn get_session_replay(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<SessionEvent>>, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let events = get_session_events(&state.canister_backup_redis_pool, &session_id)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to read session {}: {}", session_id, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        Ok(Json(events))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, session_id);
+        Ok(Json(vec![]))
+    }
+}
@@ -0,0 +1,54 @@
+use serde_json::json;
+
+use super::session_replay::{parse_session_events, session_key, SessionEvent};
+
+#[test]
+fn test_session_key() {
+    assert_eq!(session_key("abc-123"), "session:abc-123");
+}
+
+#[test]
+fn test_session_event_params_are_parsed() {
+    let event = SessionEvent::new(
+        "video_watched".into(),
+        r#"{"video_id":"v1","percentage_watched":50}"#,
+        1.5,
+    );
+    assert_eq!(
+        event.params,
+        json!({"video_id": "v1", "percentage_watched": 50})
+    );
+
+    let event = SessionEvent::new("video_watched".into(), "not json", 1.5);
+    assert_eq!(event.params, json!("not json"));
+}
+
+#[test]
+fn test_stored_members_round_trip_in_order() {
+    let events = vec![
+        SessionEvent::new("login".into(), "{}", 1_700_000_000.1),
+        SessionEvent::new(
+            "video_watched".into(),
+            r#"{"video_id":"v1"}"#,
+            1_700_000_000.2,
+        ),
+    ];
+    // members are returned by ZRANGE in score order
+    let members = events
+        .iter()
+        .map(|e| serde_json::to_string(e).unwrap())
+        .collect();
+
+    assert_eq!(parse_session_events(members), events);
+}
+
+#[test]
+fn test_invalid_members_are_skipped() {
+    let valid = SessionEvent::new("login".into(), "{}", 1.0);
+    let members = vec![
+        "garbage".to_string(),
+        serde_json::to_string(&valid).unwrap(),
+    ];
+
+    assert_eq!(parse_session_events(members), vec![valid]);
+}
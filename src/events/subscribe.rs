@@ -0,0 +1,303 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::State,
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+    Json,
+};
+use candid::Principal;
+use dashmap::DashMap;
+use futures::{Stream, StreamExt};
+use http::StatusCode;
+use ic_agent::{identity::DelegatedIdentity, Identity};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::{
+    app_state::AppState,
+    types::{DelegatedIdentityWire, RedisPool},
+};
+
+pub const MAX_SSE_CONNECTIONS_PER_USER: usize = 3;
+/// Slow clients lagging further behind skip the missed notifications
+const NOTIFICATION_CHANNEL_CAPACITY: usize = 64;
+/// Durable notifications of users without an open connection are kept this long
+pub const PENDING_NOTIFICATIONS_TTL_SECS: u64 = 24 * 60 * 60;
+/// Only the latest pending notifications of a user are kept
+pub const MAX_PENDING_NOTIFICATIONS: isize = 50;
+
+pub type EventSubscribers = DashMap<Principal, broadcast::Sender<EventNotification>>;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct EventNotification {
+    pub event: String,
+    pub params: serde_json::Value,
+    pub timestamp: f64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConnectionLimitReached;
+
+/// Receiver of a user's notifications, the user's channel is dropped with its last subscription
+pub struct Subscription {
+    receiver: broadcast::Receiver<EventNotification>,
+    user: Principal,
+    subscribers: Arc<EventSubscribers>,
+}
+
+impl Subscription {
+    pub async fn recv(&mut self) -> Option<EventNotification> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(notification) => return Some(notification),
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("SSE subscriber of {} skipped {} events", self.user, skipped)
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        // our own receiver is still alive here
+        self.subscribers
+            .remove_if(&self.user, |_, sender| sender.receiver_count() <= 1);
+    }
+}
+
+pub fn subscribe(
+    subscribers: &Arc<EventSubscribers>,
+    user: Principal,
+) -> Result<Subscription, ConnectionLimitReached> {
+    let sender = subscribers
+        .entry(user)
+        .or_insert_with(|| broadcast::channel(NOTIFICATION_CHANNEL_CAPACITY).0);
+    // the entry guard is held so concurrent connects of the same user cannot both pass the check
+    if sender.receiver_count() >= MAX_SSE_CONNECTIONS_PER_USER {
+        return Err(ConnectionLimitReached);
+    }
+
+    Ok(Subscription {
+        receiver: sender.subscribe(),
+        user,
+        subscribers: subscribers.clone(),
+    })
+}
+
+/// Notification for the user in the event's `user_id` param, if any
+pub fn event_notification(
+    event: &str,
+    params: &str,
+    timestamp: f64,
+) -> Option<(Principal, EventNotification)> {
+    let params: serde_json::Value = serde_json::from_str(params).ok()?;
+    let user = params
+        .get("user_id")
+        .and_then(|user_id| user_id.as_str())
+        .and_then(|user_id| Principal::from_text(user_id).ok())?;
+
+    Some((
+        user,
+        EventNotification {
+            event: event.to_string(),
+            params,
+            timestamp,
+        },
+    ))
+}
+
+/// Sends the notification to the user's open SSE connections on this replica, false if there
+/// are none
+fn deliver(
+    subscribers: &EventSubscribers,
+    user: Principal,
+    notification: EventNotification,
+) -> bool {
+    match subscribers.get(&user) {
+        Some(sender) => sender.send(notification).is_ok(),
+        None => false,
+    }
+}
+
+fn now_timestamp() -> f64 {
+    chrono::Utc::now().timestamp_millis() as f64 / 1000.0
+}
+
+/// Forwards the event to the user's open SSE connections, returns whether any got it
+pub fn publish_event(subscribers: &EventSubscribers, event: &str, params: &str) -> bool {
+    if subscribers.is_empty() {
+        return false;
+    }
+
+    let Some((user, notification)) = event_notification(event, params, now_timestamp()) else {
+        return false;
+    };
+
+    deliver(subscribers, user, notification)
+}
+
+pub fn pending_notifications_key(user: Principal) -> String {
+    format!("pending_notifications:{}", user)
+}
+
+/// Notifications waiting for the next subscription of their user, on any replica
+pub(crate) trait PendingNotificationStore {
+    async fn push_pending(
+        &self,
+        user: Principal,
+        notification: &EventNotification,
+    ) -> Result<(), anyhow::Error>;
+
+    /// Removes and returns the user's pending notifications, oldest first
+    async fn take_pending(&self, user: Principal) -> Result<Vec<EventNotification>, anyhow::Error>;
+}
+
+impl PendingNotificationStore for RedisPool {
+    async fn push_pending(
+        &self,
+        user: Principal,
+        notification: &EventNotification,
+    ) -> Result<(), anyhow::Error> {
+        let key = pending_notifications_key(user);
+        let mut conn = self.get().await?;
+        redis::pipe()
+            .atomic()
+            .cmd("RPUSH")
+            .arg(&key)
+            .arg(serde_json::to_string(notification)?)
+            .ignore()
+            .cmd("LTRIM")
+            .arg(&key)
+            .arg(-MAX_PENDING_NOTIFICATIONS)
+            .arg(-1)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg(PENDING_NOTIFICATIONS_TTL_SECS)
+            .ignore()
+            .query_async::<()>(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn take_pending(&self, user: Principal) -> Result<Vec<EventNotification>, anyhow::Error> {
+        let key = pending_notifications_key(user);
+        let mut conn = self.get().await?;
+        let (pending,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .cmd("LRANGE")
+            .arg(&key)
+            .arg(0)
+            .arg(-1)
+            .cmd("DEL")
+            .arg(&key)
+            .ignore()
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(pending
+            .iter()
+            .filter_map(|notification| serde_json::from_str(notification).ok())
+            .collect())
+    }
+}
+
+/// Forwards the event to the user's open SSE connections, or keeps it for their next
+/// subscription when this replica has none. For notifications sent once, like digests, that
+/// must not be lost to a closed app.
+pub async fn publish_durable_event(
+    subscribers: &EventSubscribers,
+    store: &impl PendingNotificationStore,
+    event: &str,
+    params: &str,
+) -> Result<(), anyhow::Error> {
+    let Some((user, notification)) = event_notification(event, params, now_timestamp()) else {
+        return Ok(());
+    };
+
+    if deliver(subscribers, user, notification.clone()) {
+        return Ok(());
+    }
+
+    store.push_pending(user, &notification).await
+}
+
+/// SSE events of the pending notifications, then of the live ones
+pub fn notification_stream(
+    subscription: Subscription,
+    pending: Vec<EventNotification>,
+) -> impl Stream<Item = Result<SseEvent, Infallible>> {
+    let live = futures::stream::unfold(subscription, |mut subscription| async move {
+        let notification = subscription.recv().await?;
+        Some((notification, subscription))
+    });
+
+    futures::stream::iter(pending)
+        .chain(live)
+        .map(|notification| {
+            Ok(SseEvent::default()
+                .event(notification.event.clone())
+                .json_data(&notification)
+                .unwrap_or_default())
+        })
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct SubscribeRequest {
+    pub delegated_identity_wire: DelegatedIdentityWire,
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscribe",
+    request_body = SubscribeRequest,
+    tag = "events",
+    responses(
+        (status = 200, description = "Stream of the user's events", content_type = "text/event-stream", body = EventNotification),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many open connections"),
+    )
+)]
+#[instrument(skip(state, request))]
+pub async fn subscribe_events(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SubscribeRequest>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, (StatusCode, String)> {
+    let user = DelegatedIdentity::try_from(request.delegated_identity_wire)
+        .and_then(|identity| identity.sender())
+        .map_err(|e| {
+            (
+                StatusCode::UNAUTHORIZED,
+                format!("Invalid delegated identity: {}", e),
+            )
+        })?;
+
+    let subscription = subscribe(&state.event_subscribers, user).map_err(|_| {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            format!(
+                "At most {} event streams per user",
+                MAX_SSE_CONNECTIONS_PER_USER
+            ),
+        )
+    })?;
+
+    #[cfg(not(feature = "local-bin"))]
+    let pending = state
+        .canister_backup_redis_pool
+        .take_pending(user)
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("Failed to read pending notifications of {}: {}", user, e);
+            vec![]
+        });
+    #[cfg(feature = "local-bin")]
+    let pending = vec![];
+
+    Ok(Sse::new(notification_stream(subscription, pending)).keep_alive(KeepAlive::default()))
+}
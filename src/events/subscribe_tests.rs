@@ -0,0 +1,191 @@
+use std::{collections::HashMap, sync::Arc, sync::Mutex};
+
+use candid::Principal;
+use futures::StreamExt;
+use serde_json::json;
+
+use super::subscribe::{
+    event_notification, notification_stream, publish_durable_event, publish_event, subscribe,
+    ConnectionLimitReached, EventNotification, EventSubscribers, PendingNotificationStore,
+    MAX_SSE_CONNECTIONS_PER_USER,
+};
+
+fn user() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+fn params_for(user: Principal) -> String {
+    json!({ "user_id": user.to_text(), "video_id": "abc" }).to_string()
+}
+
+#[test]
+fn test_notification_targets_user_id() {
+    let (target, notification) =
+        event_notification("like_video", &params_for(user()), 1.5).unwrap();
+
+    assert_eq!(target, user());
+    assert_eq!(notification.event, "like_video");
+    assert_eq!(notification.params["video_id"], "abc");
+    assert_eq!(notification.timestamp, 1.5);
+}
+
+#[test]
+fn test_notification_requires_valid_user_id() {
+    assert!(event_notification("like_video", r#"{"video_id":"abc"}"#, 0.0).is_none());
+    assert!(event_notification("like_video", r#"{"user_id":"not a principal"}"#, 0.0).is_none());
+    assert!(event_notification("like_video", "not json", 0.0).is_none());
+}
+
+#[test]
+fn test_connection_limit_per_user() {
+    let subscribers = Arc::new(EventSubscribers::new());
+
+    let subscriptions = (0..MAX_SSE_CONNECTIONS_PER_USER)
+        .map(|_| subscribe(&subscribers, user()).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        subscribe(&subscribers, user()).err(),
+        Some(ConnectionLimitReached)
+    );
+
+    // other users are not affected
+    assert!(subscribe(&subscribers, Principal::anonymous()).is_ok());
+
+    drop(subscriptions);
+    assert!(subscribe(&subscribers, user()).is_ok());
+}
+
+#[test]
+fn test_channel_removed_with_last_subscription() {
+    let subscribers = Arc::new(EventSubscribers::new());
+
+    let first = subscribe(&subscribers, user()).unwrap();
+    let second = subscribe(&subscribers, user()).unwrap();
+    drop(first);
+    assert!(subscribers.contains_key(&user()));
+
+    drop(second);
+    assert!(!subscribers.contains_key(&user()));
+}
+
+#[tokio::test]
+async fn test_published_event_reaches_all_user_streams() {
+    let subscribers = Arc::new(EventSubscribers::new());
+    let mut first = Box::pin(notification_stream(
+        subscribe(&subscribers, user()).unwrap(),
+        vec![],
+    ));
+    let mut second = Box::pin(notification_stream(
+        subscribe(&subscribers, user()).unwrap(),
+        vec![],
+    ));
+
+    publish_event(&subscribers, "video_viewed", &params_for(user()));
+    // events of other users are not delivered
+    publish_event(
+        &subscribers,
+        "video_viewed",
+        &params_for(Principal::management_canister()),
+    );
+    publish_event(&subscribers, "like_video", &params_for(user()));
+
+    for stream in [&mut first, &mut second] {
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(stream.next().await.unwrap().is_ok());
+    }
+
+    let mut subscription = subscribe(&subscribers, user()).unwrap();
+    publish_event(&subscribers, "like_video", &params_for(user()));
+    assert_eq!(subscription.recv().await.unwrap().event, "like_video");
+}
+
+#[test]
+fn test_publish_without_subscribers_is_noop() {
+    let subscribers = EventSubscribers::new();
+
+    assert!(!publish_event(
+        &subscribers,
+        "like_video",
+        &params_for(user())
+    ));
+
+    assert!(subscribers.is_empty());
+}
+
+/// Pending notifications by user
+#[derive(Default)]
+struct MockRedis {
+    pending: Mutex<HashMap<Principal, Vec<EventNotification>>>,
+}
+
+impl PendingNotificationStore for MockRedis {
+    async fn push_pending(
+        &self,
+        user: Principal,
+        notification: &EventNotification,
+    ) -> Result<(), anyhow::Error> {
+        self.pending
+            .lock()
+            .unwrap()
+            .entry(user)
+            .or_default()
+            .push(notification.clone());
+        Ok(())
+    }
+
+    async fn take_pending(&self, user: Principal) -> Result<Vec<EventNotification>, anyhow::Error> {
+        Ok(self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(&user)
+            .unwrap_or_default())
+    }
+}
+
+#[tokio::test]
+async fn test_durable_event_is_kept_without_open_connection() {
+    let subscribers = Arc::new(EventSubscribers::new());
+    let store = MockRedis::default();
+
+    publish_durable_event(&subscribers, &store, "digest", &params_for(user()))
+        .await
+        .unwrap();
+
+    let pending = store.take_pending(user()).await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].event, "digest");
+    assert_eq!(pending[0].params["video_id"], "abc");
+}
+
+#[tokio::test]
+async fn test_durable_event_is_not_kept_when_delivered() {
+    let subscribers = Arc::new(EventSubscribers::new());
+    let store = MockRedis::default();
+    let mut subscription = subscribe(&subscribers, user()).unwrap();
+
+    publish_durable_event(&subscribers, &store, "digest", &params_for(user()))
+        .await
+        .unwrap();
+
+    assert_eq!(subscription.recv().await.unwrap().event, "digest");
+    assert!(store.take_pending(user()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_pending_notifications_are_streamed_first() {
+    let subscribers = Arc::new(EventSubscribers::new());
+    let (_, pending) = event_notification("digest", &params_for(user()), 1.0).unwrap();
+    let mut stream = Box::pin(notification_stream(
+        subscribe(&subscribers, user()).unwrap(),
+        vec![pending],
+    ));
+
+    publish_event(&subscribers, "like_video", &params_for(user()));
+
+    assert!(stream.next().await.unwrap().is_ok());
+    assert!(stream.next().await.unwrap().is_ok());
+    let mut subscription = subscribe(&subscribers, user()).unwrap();
+    publish_event(&subscribers, "like_video", &params_for(user()));
+    assert_eq!(subscription.recv().await.unwrap().event, "like_video");
+}
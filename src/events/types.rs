@@ -1,6 +1,6 @@
 use candid::Principal;
 use serde::{Deserialize, Deserializer, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use utoipa::ToSchema;
 use yral_metrics::metrics::{
     like_video::LikeVideo, sealed_metric::SealedMetric,
@@ -13,6 +13,348 @@ pub enum AnalyticsEvent {
     VideoWatched(VideoWatched),
     VideoDurationWatched(VideoDurationWatched),
     LikeVideo(LikeVideo),
+    ShareVideo(ShareVideoPayload),
+    CommentVideo(CommentVideoPayload),
+    VideoNsfwAppeal(VideoNsfwAppealPayload),
+    TokenBurn(TokenBurnPayload),
+    DuplicateVideoDetected(DuplicateVideoDetectedEvent),
+    CreatorProfileViewed(CreatorProfileViewedPayload),
+    #[cfg(feature = "local-bin")]
+    TestEvent(TestEventPayload),
+}
+
+/// Platform the event was sent from, stored with every BigQuery row
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceType {
+    Ios,
+    Android,
+    Web,
+    #[default]
+    Unknown,
+}
+
+impl DeviceType {
+    /// Classifies a `User-Agent`, native HTTP clients are matched by their platform tokens
+    pub fn from_user_agent(user_agent: Option<&str>) -> Self {
+        let Some(user_agent) = user_agent.map(str::to_ascii_lowercase) else {
+            return Self::Unknown;
+        };
+
+        if ["iphone", "ipad", "ipod", "cfnetwork"]
+            .iter()
+            .any(|token| user_agent.contains(token))
+        {
+            Self::Ios
+        } else if ["android", "dalvik"]
+            .iter()
+            .any(|token| user_agent.contains(token))
+        {
+            Self::Android
+        } else if user_agent.contains("mozilla") {
+            Self::Web
+        } else {
+            Self::Unknown
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ios => "ios",
+            Self::Android => "android",
+            Self::Web => "web",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Sent by the frontend when a user shares a video
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct ShareVideoPayload {
+    pub user_id: String,
+    #[schema(value_type = String)]
+    pub canister_id: Principal,
+    pub video_id: String,
+    pub post_id: u64,
+    #[schema(value_type = String)]
+    pub publisher_canister_id: Principal,
+}
+
+impl ShareVideoPayload {
+    fn tag(&self) -> String {
+        "share_video".into()
+    }
+
+    fn user_id(&self) -> Option<String> {
+        Some(self.user_id.clone())
+    }
+
+    fn user_canister(&self) -> Option<Principal> {
+        Some(self.canister_id)
+    }
+}
+
+/// Sent by the frontend when a user comments on a video, the comment text is not part of it
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct CommentVideoPayload {
+    pub user_id: String,
+    #[schema(value_type = String)]
+    pub canister_id: Principal,
+    pub video_id: String,
+    pub post_id: u64,
+    #[schema(value_type = String)]
+    pub publisher_canister_id: Principal,
+    pub comment_id: String,
+}
+
+impl CommentVideoPayload {
+    fn tag(&self) -> String {
+        "comment_video".into()
+    }
+
+    fn user_id(&self) -> Option<String> {
+        Some(self.user_id.clone())
+    }
+
+    fn user_canister(&self) -> Option<Principal> {
+        Some(self.canister_id)
+    }
+}
+
+/// Sent by a creator contesting the NSFW classification of their video
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct VideoNsfwAppealPayload {
+    pub video_id: String,
+    #[schema(value_type = String)]
+    pub canister_id: Principal,
+    pub post_id: u64,
+    pub publisher_user_id: String,
+    pub reason: String,
+}
+
+impl VideoNsfwAppealPayload {
+    fn tag(&self) -> String {
+        "video_nsfw_appeal".into()
+    }
+
+    fn user_id(&self) -> Option<String> {
+        Some(self.publisher_user_id.clone())
+    }
+
+    fn user_canister(&self) -> Option<Principal> {
+        Some(self.canister_id)
+    }
+}
+
+/// Sent by the frontend after a user burns creator tokens
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct TokenBurnPayload {
+    pub user_id: String,
+    #[schema(value_type = String)]
+    pub canister_id: Principal,
+    #[schema(value_type = String)]
+    pub token_root: Principal,
+    pub amount_burned_e8s: u64,
+    /// Supply of the token after the burn
+    pub new_total_supply_e8s: u64,
+    /// Ledger block of the burn, burns without it cannot be verified and are dropped
+    #[serde(default)]
+    pub block_index: Option<u64>,
+}
+
+impl TokenBurnPayload {
+    fn tag(&self) -> String {
+        "token_burn".into()
+    }
+
+    fn user_id(&self) -> Option<String> {
+        Some(self.user_id.clone())
+    }
+
+    fn user_canister(&self) -> Option<Principal> {
+        Some(self.canister_id)
+    }
+}
+
+/// Output of the deduplication pipeline, sent by services collecting ML training data
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct DuplicateVideoDetectedEvent {
+    /// The newly uploaded video
+    pub original_video_id: String,
+    /// The video it duplicates
+    pub parent_video_id: String,
+    pub similarity_percentage: f64,
+    pub exact_duplicate: bool,
+    #[schema(value_type = String)]
+    pub publisher_canister_id: Principal,
+    pub publisher_principal: String,
+    pub post_id: u64,
+    pub timestamp: String,
+}
+
+impl DuplicateVideoDetectedEvent {
+    fn tag(&self) -> String {
+        "duplicate_video_detected".into()
+    }
+
+    fn user_id(&self) -> Option<String> {
+        Some(self.publisher_principal.clone())
+    }
+
+    fn user_canister(&self) -> Option<Principal> {
+        Some(self.publisher_canister_id)
+    }
+}
+
+/// Sent by the frontend when a user opens a creator's profile page
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct CreatorProfileViewedPayload {
+    pub viewer_id: String,
+    #[schema(value_type = String)]
+    pub viewer_canister: Principal,
+    pub creator_id: String,
+    #[schema(value_type = String)]
+    pub creator_canister: Principal,
+    /// Where the profile was opened from: `feed`, `search` or `direct`
+    pub source: String,
+}
+
+impl CreatorProfileViewedPayload {
+    fn tag(&self) -> String {
+        "creator_profile_viewed".into()
+    }
+
+    fn user_id(&self) -> Option<String> {
+        Some(self.viewer_id.clone())
+    }
+
+    fn user_canister(&self) -> Option<Principal> {
+        Some(self.viewer_canister)
+    }
+}
+
+/// Recorded by the server after paying a watch reward, clients cannot send it
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct WatchVideoRewardPayload {
+    pub user_id: String,
+    #[schema(value_type = String)]
+    pub canister_id: Principal,
+    pub video_id: String,
+    pub post_id: u64,
+    pub reward_e8s: u64,
+}
+
+impl WatchVideoRewardPayload {
+    pub fn tag(&self) -> String {
+        "watch_video_reward".into()
+    }
+}
+
+/// Dummy event for frontend development, never forwarded to BigQuery, Firestore or notifications
+#[cfg(feature = "local-bin")]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct TestEventPayload {
+    pub test_id: String,
+    #[schema(value_type = Object)]
+    pub arbitrary_data: Value,
+}
+
+/// Longest `test_id` accepted
+pub const TEST_EVENT_MAX_ID_LEN: usize = 128;
+/// Largest serialized `arbitrary_data` accepted
+pub const TEST_EVENT_MAX_DATA_BYTES: usize = 16 * 1024;
+
+#[cfg(feature = "local-bin")]
+pub static TEST_EVENTS_RECEIVED: std::sync::atomic::AtomicU64 =
+    std::sync::atomic::AtomicU64::new(0);
+
+#[cfg(feature = "local-bin")]
+impl TestEventPayload {
+    fn tag(&self) -> String {
+        "TestEvent".into()
+    }
+
+    fn user_id(&self) -> Option<String> {
+        None
+    }
+
+    fn user_canister(&self) -> Option<Principal> {
+        None
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.test_id.trim().is_empty() || self.test_id.len() > TEST_EVENT_MAX_ID_LEN {
+            return Err(format!(
+                "test_id must be 1 to {} characters",
+                TEST_EVENT_MAX_ID_LEN
+            ));
+        }
+        if !self.arbitrary_data.is_object() {
+            return Err("arbitrary_data must be an object".into());
+        }
+        if self.arbitrary_data.to_string().len() > TEST_EVENT_MAX_DATA_BYTES {
+            return Err(format!(
+                "arbitrary_data is over {} bytes",
+                TEST_EVENT_MAX_DATA_BYTES
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Version of the event payloads sent by current clients
+pub const CURRENT_SCHEMA_VERSION: u8 = 2;
+
+/// Payloads without `schema_version` predate versioning and count as version 1
+pub fn schema_version(value: &Value) -> u8 {
+    value
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .map(|version| version.min(u8::MAX as u64) as u8)
+        .unwrap_or(1)
+}
+
+/// Fields added in version 2 with the value they implicitly had before
+fn v2_defaults(event: &str) -> Vec<(&'static str, Value)> {
+    match event {
+        "VideoWatched" | "VideoDurationWatched" | "LikeVideo" => {
+            vec![("nsfw_probability", json!(0.0))]
+        }
+        _ => vec![],
+    }
+}
+
+/// Fills in the fields version 1 clients did not send, fields already present are kept
+pub fn migrate_v1_to_v2(mut value: Value) -> Value {
+    let event = value
+        .get("event")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+
+    if let Some(fields) = value.as_object_mut() {
+        for (field, default) in v2_defaults(&event) {
+            fields.entry(field).or_insert(default);
+        }
+        fields.insert("schema_version".into(), json!(2));
+    }
+
+    value
+}
+
+/// Brings the payload up to the current version, newer versions sent during a rolling
+/// deploy are passed through and their unknown fields ignored
+fn migrate_to_current(mut value: Value) -> Value {
+    if schema_version(&value) < 2 {
+        value = migrate_v1_to_v2(value);
+    }
+
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("schema_version");
+    }
+
+    value
 }
 
 // open issues for tagged and untagged enums - https://github.com/serde-rs/json/issues/1046 and https://github.com/serde-rs/json/issues/1108
@@ -22,7 +364,7 @@ impl<'de> Deserialize<'de> for AnalyticsEvent {
         D: Deserializer<'de>,
     {
         // First deserialize to a generic Value to handle arbitrary_precision issues
-        let value = Value::deserialize(deserializer)?;
+        let value = migrate_to_current(Value::deserialize(deserializer)?);
 
         // Then try to deserialize from the Value to our enum
         match value.get("event").and_then(|v| v.as_str()) {
@@ -41,6 +383,48 @@ impl<'de> Deserialize<'de> for AnalyticsEvent {
                     serde_json::from_value(value).map_err(serde::de::Error::custom)?;
                 Ok(AnalyticsEvent::LikeVideo(like_video))
             }
+            Some("ShareVideo") => {
+                let share_video: ShareVideoPayload =
+                    serde_json::from_value(value).map_err(serde::de::Error::custom)?;
+                Ok(AnalyticsEvent::ShareVideo(share_video))
+            }
+            Some("CommentVideo") => {
+                let comment_video: CommentVideoPayload =
+                    serde_json::from_value(value).map_err(serde::de::Error::custom)?;
+                Ok(AnalyticsEvent::CommentVideo(comment_video))
+            }
+            Some("VideoNsfwAppeal") => {
+                let video_nsfw_appeal: VideoNsfwAppealPayload =
+                    serde_json::from_value(value).map_err(serde::de::Error::custom)?;
+                Ok(AnalyticsEvent::VideoNsfwAppeal(video_nsfw_appeal))
+            }
+            Some("TokenBurn") => {
+                let token_burn: TokenBurnPayload =
+                    serde_json::from_value(value).map_err(serde::de::Error::custom)?;
+                Ok(AnalyticsEvent::TokenBurn(token_burn))
+            }
+            Some("DuplicateVideoDetected") => {
+                let duplicate_video_detected: DuplicateVideoDetectedEvent =
+                    serde_json::from_value(value).map_err(serde::de::Error::custom)?;
+                Ok(AnalyticsEvent::DuplicateVideoDetected(
+                    duplicate_video_detected,
+                ))
+            }
+            Some("CreatorProfileViewed") => {
+                let creator_profile_viewed: CreatorProfileViewedPayload =
+                    serde_json::from_value(value).map_err(serde::de::Error::custom)?;
+                Ok(AnalyticsEvent::CreatorProfileViewed(creator_profile_viewed))
+            }
+            #[cfg(feature = "local-bin")]
+            Some("TestEvent") => {
+                let test_event: TestEventPayload =
+                    serde_json::from_value(value).map_err(serde::de::Error::custom)?;
+                Ok(AnalyticsEvent::TestEvent(test_event))
+            }
+            #[cfg(not(feature = "local-bin"))]
+            Some("TestEvent") => Err(serde::de::Error::custom(
+                "TestEvent is only accepted by local builds",
+            )),
             Some(event_type) => Err(serde::de::Error::custom(format!(
                 "Unknown event type: {}",
                 event_type
@@ -56,6 +440,14 @@ macro_rules! delegate_metric_method {
             AnalyticsEvent::VideoWatched(event) => event.$method(),
             AnalyticsEvent::VideoDurationWatched(event) => event.$method(),
             AnalyticsEvent::LikeVideo(event) => event.$method(),
+            AnalyticsEvent::ShareVideo(event) => event.$method(),
+            AnalyticsEvent::CommentVideo(event) => event.$method(),
+            AnalyticsEvent::VideoNsfwAppeal(event) => event.$method(),
+            AnalyticsEvent::TokenBurn(event) => event.$method(),
+            AnalyticsEvent::DuplicateVideoDetected(event) => event.$method(),
+            AnalyticsEvent::CreatorProfileViewed(event) => event.$method(),
+            #[cfg(feature = "local-bin")]
+            AnalyticsEvent::TestEvent(event) => event.$method(),
         }
     };
     // Overload for methods that need serde_json::to_value
@@ -64,6 +456,14 @@ macro_rules! delegate_metric_method {
             AnalyticsEvent::VideoWatched(event) => serde_json::to_value(event).unwrap(),
             AnalyticsEvent::VideoDurationWatched(event) => serde_json::to_value(event).unwrap(),
             AnalyticsEvent::LikeVideo(event) => serde_json::to_value(event).unwrap(),
+            AnalyticsEvent::ShareVideo(event) => serde_json::to_value(event).unwrap(),
+            AnalyticsEvent::CommentVideo(event) => serde_json::to_value(event).unwrap(),
+            AnalyticsEvent::VideoNsfwAppeal(event) => serde_json::to_value(event).unwrap(),
+            AnalyticsEvent::TokenBurn(event) => serde_json::to_value(event).unwrap(),
+            AnalyticsEvent::DuplicateVideoDetected(event) => serde_json::to_value(event).unwrap(),
+            AnalyticsEvent::CreatorProfileViewed(event) => serde_json::to_value(event).unwrap(),
+            #[cfg(feature = "local-bin")]
+            AnalyticsEvent::TestEvent(event) => serde_json::to_value(event).unwrap(),
         }
     };
 }
@@ -87,4 +487,23 @@ impl AnalyticsEvent {
         // Use the overloaded macro variant for to_value
         delegate_metric_method!(self, params, to_value)
     }
+
+    /// Test events carry no user, the payload is all there is to check before skipping them
+    pub fn validate_test_event(&self) -> Result<(), String> {
+        #[cfg(feature = "local-bin")]
+        if let AnalyticsEvent::TestEvent(payload) = self {
+            return payload.validate();
+        }
+
+        Ok(())
+    }
+
+    pub fn is_test_event(&self) -> bool {
+        #[cfg(feature = "local-bin")]
+        if matches!(self, AnalyticsEvent::TestEvent(_)) {
+            return true;
+        }
+
+        false
+    }
 }
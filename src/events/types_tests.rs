@@ -0,0 +1,247 @@
+use serde_json::json;
+
+use super::types::{migrate_v1_to_v2, schema_version, AnalyticsEvent, CURRENT_SCHEMA_VERSION};
+
+#[cfg(feature = "local-bin")]
+#[test]
+fn test_test_event_is_accepted_in_local_builds() {
+    use yral_metrics::metrics::sealed_metric::SealedMetric;
+
+    let event: AnalyticsEvent = serde_json::from_value(json!({
+        "event": "TestEvent",
+        "test_id": "frontend-smoke-1",
+        "arbitrary_data": { "foo": "bar" },
+    }))
+    .unwrap();
+
+    assert!(event.is_test_event());
+    assert_eq!(event.tag(), "TestEvent");
+    assert_eq!(event.user_id(), None);
+    assert_eq!(event.params()["arbitrary_data"]["foo"], "bar");
+}
+
+#[cfg(feature = "local-bin")]
+#[test]
+fn test_invalid_test_event_payload_is_rejected() {
+    use super::types::TEST_EVENT_MAX_DATA_BYTES;
+
+    let test_event = |test_id: &str, arbitrary_data| -> AnalyticsEvent {
+        serde_json::from_value(json!({
+            "event": "TestEvent",
+            "test_id": test_id,
+            "arbitrary_data": arbitrary_data,
+        }))
+        .unwrap()
+    };
+
+    assert!(test_event("frontend-smoke-1", json!({ "foo": "bar" }))
+        .validate_test_event()
+        .is_ok());
+    assert!(test_event(" ", json!({})).validate_test_event().is_err());
+    assert!(test_event(&"a".repeat(129), json!({}))
+        .validate_test_event()
+        .is_err());
+    assert!(test_event("frontend-smoke-1", json!("not an object"))
+        .validate_test_event()
+        .is_err());
+    assert!(test_event(
+        "frontend-smoke-1",
+        json!({ "blob": "a".repeat(TEST_EVENT_MAX_DATA_BYTES) })
+    )
+    .validate_test_event()
+    .is_err());
+}
+
+#[cfg(not(feature = "local-bin"))]
+#[test]
+fn test_test_event_is_rejected_in_non_local_builds() {
+    let res = serde_json::from_value::<AnalyticsEvent>(json!({
+        "event": "TestEvent",
+        "test_id": "frontend-smoke-1",
+        "arbitrary_data": { "foo": "bar" },
+    }));
+
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_watch_reward_is_not_a_client_event() {
+    let res = serde_json::from_value::<AnalyticsEvent>(json!({
+        "event": "WatchVideoReward",
+        "user_id": "2vxsx-fae",
+        "canister_id": "rrkah-fqaaa-aaaaa-aaaaq-cai",
+        "video_id": "vid1",
+        "post_id": 7,
+        "reward_e8s": 500,
+    }));
+
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_share_and_comment_events_round_trip() {
+    use yral_metrics::metrics::sealed_metric::SealedMetric;
+
+    for (payload, tag) in [
+        (
+            json!({
+                "event": "ShareVideo",
+                "user_id": "2vxsx-fae",
+                "canister_id": "rrkah-fqaaa-aaaaa-aaaaq-cai",
+                "video_id": "vid1",
+                "post_id": 7,
+                "publisher_canister_id": "ryjl3-tyaaa-aaaaa-aaaba-cai",
+            }),
+            "share_video",
+        ),
+        (
+            json!({
+                "event": "CommentVideo",
+                "user_id": "2vxsx-fae",
+                "canister_id": "rrkah-fqaaa-aaaaa-aaaaq-cai",
+                "video_id": "vid1",
+                "post_id": 7,
+                "publisher_canister_id": "ryjl3-tyaaa-aaaaa-aaaba-cai",
+                "comment_id": "c1",
+            }),
+            "comment_video",
+        ),
+    ] {
+        let event: AnalyticsEvent = serde_json::from_value(payload.clone()).unwrap();
+        let round_tripped: AnalyticsEvent =
+            serde_json::from_value(serde_json::to_value(&event).unwrap()).unwrap();
+
+        assert_eq!(serde_json::to_value(&round_tripped).unwrap(), payload);
+        assert_eq!(round_tripped.tag(), tag);
+        assert_eq!(round_tripped.user_id().as_deref(), Some("2vxsx-fae"));
+        assert_eq!(
+            round_tripped.params()["publisher_canister_id"],
+            "ryjl3-tyaaa-aaaaa-aaaba-cai"
+        );
+    }
+}
+
+#[test]
+fn test_device_type_from_user_agent() {
+    use super::types::DeviceType;
+
+    let cases = [
+        (
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15",
+            DeviceType::Ios,
+        ),
+        (
+            "Mozilla/5.0 (iPad; CPU OS 16_6 like Mac OS X) AppleWebKit/605.1.15",
+            DeviceType::Ios,
+        ),
+        (
+            "Yral/1.4.0 CFNetwork/1494.0.7 Darwin/23.4.0",
+            DeviceType::Ios,
+        ),
+        (
+            "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 Chrome/124.0 Mobile",
+            DeviceType::Android,
+        ),
+        (
+            "Dalvik/2.1.0 (Linux; U; Android 13; SM-A536E)",
+            DeviceType::Android,
+        ),
+        (
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 Chrome/124.0",
+            DeviceType::Web,
+        ),
+        (
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_4) AppleWebKit/605.1.15 Safari/605.1.15",
+            DeviceType::Web,
+        ),
+        ("curl/8.5.0", DeviceType::Unknown),
+        ("", DeviceType::Unknown),
+    ];
+
+    for (user_agent, expected) in cases {
+        assert_eq!(
+            DeviceType::from_user_agent(Some(user_agent)),
+            expected,
+            "{user_agent}"
+        );
+    }
+    assert_eq!(DeviceType::from_user_agent(None), DeviceType::Unknown);
+}
+
+#[test]
+fn test_device_type_serializes_as_bigquery_value() {
+    use super::types::DeviceType;
+
+    for device_type in [
+        DeviceType::Ios,
+        DeviceType::Android,
+        DeviceType::Web,
+        DeviceType::Unknown,
+    ] {
+        assert_eq!(
+            serde_json::to_value(device_type).unwrap(),
+            json!(device_type.as_str())
+        );
+    }
+}
+
+#[test]
+fn test_schema_version_defaults_to_v1() {
+    assert_eq!(schema_version(&json!({ "event": "LikeVideo" })), 1);
+    assert_eq!(
+        schema_version(&json!({ "event": "LikeVideo", "schema_version": 2 })),
+        2
+    );
+    assert_eq!(
+        schema_version(&json!({ "event": "LikeVideo", "schema_version": 1000 })),
+        u8::MAX
+    );
+}
+
+#[test]
+fn test_migrate_v1_to_v2_fills_missing_fields_only() {
+    let migrated = migrate_v1_to_v2(json!({
+        "event": "VideoDurationWatched",
+        "percentage_watched": 80.0,
+    }));
+    assert_eq!(migrated["nsfw_probability"], 0.0);
+    assert_eq!(migrated["percentage_watched"], 80.0);
+    assert_eq!(schema_version(&migrated), CURRENT_SCHEMA_VERSION);
+
+    let migrated = migrate_v1_to_v2(json!({
+        "event": "VideoDurationWatched",
+        "nsfw_probability": 0.9,
+    }));
+    assert_eq!(migrated["nsfw_probability"], 0.9);
+
+    let migrated = migrate_v1_to_v2(json!({ "event": "TokenBurn" }));
+    assert_eq!(
+        migrated,
+        json!({ "event": "TokenBurn", "schema_version": 2 })
+    );
+}
+
+#[test]
+fn test_every_schema_version_deserializes() {
+    use yral_metrics::metrics::sealed_metric::SealedMetric;
+
+    for version in [None, Some(1), Some(2), Some(3)] {
+        let mut payload = json!({
+            "event": "ShareVideo",
+            "user_id": "2vxsx-fae",
+            "canister_id": "rrkah-fqaaa-aaaaa-aaaaq-cai",
+            "video_id": "vid1",
+            "post_id": 7,
+            "publisher_canister_id": "ryjl3-tyaaa-aaaaa-aaaba-cai",
+            "added_in_v3": true,
+        });
+        if let Some(version) = version {
+            payload["schema_version"] = json!(version);
+        }
+
+        let event: AnalyticsEvent = serde_json::from_value(payload).unwrap();
+
+        assert_eq!(event.tag(), "share_video");
+        assert!(event.params().get("schema_version").is_none());
+    }
+}
@@ -9,15 +9,136 @@ use axum::{
 };
 use candid::Principal;
 use ic_agent::{identity::DelegatedIdentity, Identity};
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
 use yral_metrics::metrics::sealed_metric::SealedMetric;
 
-use crate::{
-    app_state::AppState, utils::delegated_identity::get_user_info_from_delegated_identity_wire,
-};
+use crate::{app_state::AppState, types::DelegatedIdentityWire};
 
 use super::{types::AnalyticsEvent, EventBulkRequest, VerifiedEventBulkRequest};
 
+pub const IDENTITY_CACHE_TTL_SECS: u64 = 5 * 60;
+
+/// Cache key of a verified identity, derived from the whole wire so any change misses the cache
+pub fn identity_cache_key(
+    delegated_identity_wire: &DelegatedIdentityWire,
+) -> Result<String, serde_json::Error> {
+    let bytes = serde_json::to_vec(delegated_identity_wire)?;
+    Ok(format!("identity_cache:{}", blake3::hash(&bytes).to_hex()))
+}
+
+/// Cache TTL in seconds, never outliving the earliest delegation in the chain
+pub fn identity_cache_ttl(delegated_identity_wire: &DelegatedIdentityWire, now_ns: u64) -> u64 {
+    let remaining_secs = delegated_identity_wire
+        .delegation_chain
+        .iter()
+        .map(|signed| signed.delegation.expiration.saturating_sub(now_ns) / 1_000_000_000)
+        .min()
+        .unwrap_or(IDENTITY_CACHE_TTL_SECS);
+
+    remaining_secs.min(IDENTITY_CACHE_TTL_SECS)
+}
+
+/// The cached principal if there is one, otherwise the result of `verify`
+pub fn cached_or_verified_principal<F>(
+    cached: Option<Principal>,
+    verify: F,
+) -> Result<(Principal, bool), String>
+where
+    F: FnOnce() -> Result<Principal, String>,
+{
+    match cached {
+        Some(principal) => Ok((principal, true)),
+        None => verify().map(|principal| (principal, false)),
+    }
+}
+
+fn verify_delegated_identity(
+    delegated_identity_wire: DelegatedIdentityWire,
+) -> Result<Principal, String> {
+    let identity = DelegatedIdentity::try_from(delegated_identity_wire)
+        .map_err(|e| format!("Failed to parse delegated identity wire: {}", e))?;
+    identity
+        .sender()
+        .map_err(|e| format!("Failed to get user principal: {}", e))
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn get_cached_principal(state: &AppState, key: &str) -> Option<Principal> {
+    let mut conn = match state.canister_backup_redis_pool.get().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            log::warn!("Failed to get redis connection for identity cache: {}", e);
+            return None;
+        }
+    };
+
+    match conn.get::<_, Option<String>>(key).await {
+        Ok(principal) => principal.and_then(|p| Principal::from_text(p).ok()),
+        Err(e) => {
+            log::warn!("Failed to read identity cache: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn cache_principal(state: &AppState, key: &str, principal: Principal, ttl_secs: u64) {
+    if ttl_secs == 0 {
+        return;
+    }
+
+    let res = async {
+        let mut conn = state.canister_backup_redis_pool.get().await?;
+        conn.set_ex::<_, _, ()>(key, principal.to_text(), ttl_secs)
+            .await?;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    if let Err(e) = res {
+        log::warn!("Failed to write identity cache: {}", e);
+    }
+}
+
+/// Verifies the delegated identity, reusing verifications of the same wire from the last few minutes
+pub(crate) async fn get_user_principal(
+    state: &AppState,
+    delegated_identity_wire: DelegatedIdentityWire,
+) -> Result<Principal, String> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let key = match identity_cache_key(&delegated_identity_wire) {
+            Ok(key) => key,
+            Err(e) => {
+                log::warn!("Failed to hash delegated identity: {}", e);
+                return verify_delegated_identity(delegated_identity_wire);
+            }
+        };
+        let ttl_secs = identity_cache_ttl(
+            &delegated_identity_wire,
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64,
+        );
+
+        let cached = get_cached_principal(state, &key).await;
+        let (principal, hit) = cached_or_verified_principal(cached, || {
+            verify_delegated_identity(delegated_identity_wire)
+        })?;
+        if !hit {
+            cache_principal(state, &key, principal, ttl_secs).await;
+        }
+
+        Ok(principal)
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        verify_delegated_identity(delegated_identity_wire)
+    }
+}
+
 pub async fn verify_event_bulk_request(
     State(state): State<Arc<AppState>>,
     request: Request,
@@ -46,22 +167,39 @@ pub async fn verify_event_bulk_request(
         }
     };
 
-    let user_info = get_user_info_from_delegated_identity_wire(
-        &state,
-        event_bulk_request.delegated_identity_wire.clone(),
-    )
-    .await
-    .map_err(|e| {
-        (
-            StatusCode::UNAUTHORIZED,
-            format!("Failed to get user info: {}", e),
-        )
-    })?;
-    let user_principal = user_info.user_principal;
-    let user_canister = user_info.user_canister;
+    let user_principal =
+        get_user_principal(&state, event_bulk_request.delegated_identity_wire.clone())
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    format!("Failed to get user info: {}", e),
+                )
+            })?;
+    let user_canister = state
+        .get_individual_canister_by_user_principal(user_principal)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::UNAUTHORIZED,
+                format!(
+                    "Failed to get user info: Failed to get user canister: {}",
+                    e
+                ),
+            )
+        })?;
 
     // verify all events are valid
     for event in event_bulk_request.events.clone() {
+        if event.is_test_event() {
+            event.validate_test_event().map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid test event: {}", e),
+                )
+            })?;
+            continue;
+        }
         if event.user_canister().unwrap_or(Principal::anonymous()) != user_canister {
             return Err((StatusCode::BAD_REQUEST, "Invalid user canister".to_string()));
         }
@@ -72,6 +210,8 @@ pub async fn verify_event_bulk_request(
 
     let verified_request = VerifiedEventBulkRequest {
         events: event_bulk_request.events,
+        session_id: event_bulk_request.session_id,
+        device_type: event_bulk_request.device_type,
     };
 
     let request_body = serde_json::to_string(&verified_request).unwrap();
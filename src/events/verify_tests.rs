@@ -0,0 +1,93 @@
+use std::cell::Cell;
+
+use candid::Principal;
+use ic_agent::identity::{Delegation, SignedDelegation};
+use k256::SecretKey;
+
+use crate::types::DelegatedIdentityWire;
+
+use super::verify::{
+    cached_or_verified_principal, identity_cache_key, identity_cache_ttl, IDENTITY_CACHE_TTL_SECS,
+};
+
+const SECOND_NS: u64 = 1_000_000_000;
+
+fn wire(expirations: &[u64]) -> DelegatedIdentityWire {
+    DelegatedIdentityWire {
+        from_key: vec![1, 2, 3],
+        to_secret: SecretKey::from_slice(&[7u8; 32]).unwrap().to_jwk(),
+        delegation_chain: expirations
+            .iter()
+            .map(|&expiration| SignedDelegation {
+                delegation: Delegation {
+                    pubkey: vec![4, 5, 6],
+                    expiration,
+                    targets: None,
+                },
+                signature: vec![8, 9],
+            })
+            .collect(),
+    }
+}
+
+fn user() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+#[test]
+fn test_cache_hit_skips_verification() {
+    let verified = Cell::new(false);
+
+    let res = cached_or_verified_principal(Some(user()), || {
+        verified.set(true);
+        Err("should not verify".to_string())
+    });
+
+    assert_eq!(res, Ok((user(), true)));
+    assert!(!verified.get());
+}
+
+#[test]
+fn test_cache_miss_verifies() {
+    let verified = Cell::new(false);
+
+    let res = cached_or_verified_principal(None, || {
+        verified.set(true);
+        Ok(user())
+    });
+
+    assert_eq!(res, Ok((user(), false)));
+    assert!(verified.get());
+}
+
+#[test]
+fn test_cache_miss_propagates_verification_error() {
+    let res = cached_or_verified_principal(None, || Err("invalid signature".to_string()));
+
+    assert_eq!(res, Err("invalid signature".to_string()));
+}
+
+#[test]
+fn test_cache_key_depends_on_the_whole_wire() {
+    let key = identity_cache_key(&wire(&[100 * SECOND_NS])).unwrap();
+
+    assert!(key.starts_with("identity_cache:"));
+    assert_eq!(key.len(), "identity_cache:".len() + 64);
+    assert_eq!(key, identity_cache_key(&wire(&[100 * SECOND_NS])).unwrap());
+    assert_ne!(key, identity_cache_key(&wire(&[101 * SECOND_NS])).unwrap());
+}
+
+#[test]
+fn test_cache_ttl_is_capped_by_delegation_expiry() {
+    let now = 1_000 * SECOND_NS;
+
+    assert_eq!(
+        identity_cache_ttl(&wire(&[now + 3_600 * SECOND_NS]), now),
+        IDENTITY_CACHE_TTL_SECS
+    );
+    assert_eq!(
+        identity_cache_ttl(&wire(&[now + 3_600 * SECOND_NS, now + 42 * SECOND_NS]), now),
+        42
+    );
+    assert_eq!(identity_cache_ttl(&wire(&[now - SECOND_NS]), now), 0);
+}
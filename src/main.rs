@@ -3,19 +3,30 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use axum::http::StatusCode;
+use axum::middleware;
 use axum::routing::post;
 use axum::{routing::get, Router};
+use canister::bulk_claim_tokens::bulk_claim_tokens_handler;
+use canister::canisters_list::canisters_list_handler;
+use canister::neuron_health::neuron_health_handler;
+use canister::snapshot::coverage::backup_coverage_handler;
 use canister::upgrade_user_token_sns_canister::{
     upgrade_user_token_sns_canister_for_entire_network, upgrade_user_token_sns_canister_handler,
 };
-use canister::upload_user_video::upload_user_video_handler;
+use canister::upload_user_video::{storage_quota_check, upload_user_video_handler};
 use config::AppConfig;
 use events::event::storj::enqueue_storj_backfill_item;
+use events::feed_cache_reindex::feed_cache_reindex_handler;
+use events::parquet_export::{export_job_status_handler, export_to_parquet_handler};
+use events::purge_test_data::purge_test_data_handler;
 use http::header::CONTENT_TYPE;
 use offchain_service::report_approved_handler;
+use qstash::dead_letter::{dead_letter_count_handler, dead_letter_replay_handler};
 use qstash::qstash_router;
+use qstash::queue_depths::queue_depths_handler;
+use qstash::stuck_videos::stuck_videos_handler;
 use sentry_tower::{NewSentryLayer, SentryHttpLayer};
-use tonic::service::Routes;
+use tonic::service::{interceptor::InterceptedService, Routes};
 use tower::make::Shared;
 use tower::steer::Steer;
 use tower::ServiceBuilder;
@@ -23,16 +34,32 @@ use tower_http::cors::CorsLayer;
 use tracing::instrument;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
+use user::orphaned_keys::cleanup_orphaned_redis_keys_handler;
 use utoipa::OpenApi;
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_swagger_ui::SwaggerUi;
 
 use crate::auth::check_auth_grpc;
 use crate::duplicate_video::backfill::trigger_videohash_backfill;
+use crate::duplicate_video::cluster::videohash_cluster_handler;
+use crate::duplicate_video::index_csv::{
+    videohash_export_csv_handler, videohash_import_csv_handler, videohash_index_stats_handler,
+    videohash_rebuild_mih_index_handler,
+};
+#[cfg(not(feature = "local-bin"))]
+use crate::events::body_limit::{check_grpc_content_length, EVENT_BODY_LIMIT};
+#[cfg(not(feature = "local-bin"))]
+use crate::events::event::bigquery_stream::{
+    add_event_context_columns, create_events_dead_letter_table,
+};
+#[cfg(not(feature = "local-bin"))]
+use crate::events::nsfw::add_nsfw_detection_columns;
 use crate::events::warehouse_events::warehouse_events_server::WarehouseEventsServer;
 use crate::events::{warehouse_events, WarehouseEventsService};
 use crate::offchain_service::off_chain::off_chain_server::OffChainServer;
 use crate::offchain_service::{off_chain, OffChainService};
+#[cfg(not(feature = "local-bin"))]
+use crate::tokens::embeddings::create_token_embeddings_table;
 use error::*;
 
 mod app_state;
@@ -40,14 +67,20 @@ pub(crate) mod async_dedup_index;
 mod auth;
 pub mod canister;
 mod config;
+#[cfg(test)]
+mod config_tests;
 mod consts;
+mod creators;
 mod duplicate_video;
 mod error;
+#[cfg(test)]
+mod error_tests;
 mod events;
 pub mod metrics;
 mod offchain_service;
 mod posts;
 mod qstash;
+mod tokens;
 mod types;
 pub mod user;
 pub mod utils;
@@ -67,6 +100,82 @@ async fn main_impl() -> Result<()> {
 
     let shared_state = Arc::new(AppState::new(conf.clone()).await);
 
+    #[cfg(not(feature = "local-bin"))]
+    tokio::spawn(
+        shared_state
+            .geoip
+            .clone()
+            .run_refresh(shared_state.gcs_client.clone()),
+    );
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let bigquery_client = shared_state.bigquery_client.clone();
+        tokio::spawn(async move {
+            if let Err(e) = add_nsfw_detection_columns(&bigquery_client).await {
+                log::error!("Failed to add nsfw detection columns: {}", e);
+            }
+            if let Err(e) = add_event_context_columns(&bigquery_client).await {
+                log::error!("Failed to add event context columns: {}", e);
+            }
+            if let Err(e) = create_events_dead_letter_table(&bigquery_client).await {
+                log::error!("Failed to create events dead letter table: {}", e);
+            }
+            if let Err(e) = create_token_embeddings_table(&bigquery_client).await {
+                log::error!("Failed to create token embeddings table: {}", e);
+            }
+        });
+    }
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let qstash_client = shared_state.qstash_client.clone();
+        tokio::spawn(async move {
+            if let Err(e) = qstash_client.upsert_refresh_hot_videos_schedule().await {
+                log::error!("Failed to schedule hot videos refresh: {}", e);
+            }
+            if let Err(e) = qstash_client.upsert_archive_old_events_schedule().await {
+                log::error!("Failed to schedule archival of old events: {}", e);
+            }
+            if let Err(e) = qstash_client.upsert_compute_creator_scores_schedule().await {
+                log::error!("Failed to schedule creator score computation: {}", e);
+            }
+            if let Err(e) = qstash_client.upsert_verify_sns_wasm_hashes_schedule().await {
+                log::error!("Failed to schedule sns wasm hash verification: {}", e);
+            }
+            if let Err(e) = qstash_client
+                .upsert_sns_governance_health_check_schedule()
+                .await
+            {
+                log::error!("Failed to schedule sns governance health check: {}", e);
+            }
+            if let Err(e) = qstash_client
+                .upsert_sync_sns_neuron_followees_schedule()
+                .await
+            {
+                log::error!("Failed to schedule sns neuron followee sync: {}", e);
+            }
+            if let Err(e) = qstash_client
+                .upsert_rebalance_user_feed_history_schedule()
+                .await
+            {
+                log::error!("Failed to schedule feed history rebalancing: {}", e);
+            }
+            if let Err(e) = qstash_client
+                .upsert_creator_profile_view_digest_schedule()
+                .await
+            {
+                log::error!("Failed to schedule creator profile view digest: {}", e);
+            }
+            if let Err(e) = qstash_client
+                .upsert_ml_cache_consistency_check_schedule()
+                .await
+            {
+                log::error!("Failed to schedule ml cache consistency check: {}", e);
+            }
+        });
+    }
+
     let sentry_tower_layer = ServiceBuilder::new()
         .layer(NewSentryLayer::new_from_top())
         .layer(SentryHttpLayer::with_transaction());
@@ -78,6 +187,22 @@ async fn main_impl() -> Result<()> {
             events::events_router(shared_state.clone()),
         )
         .nest("/api/v1/user", user::user_router(shared_state.clone()))
+        .nest(
+            "/api/v1/creators",
+            creators::creators_router(shared_state.clone()),
+        )
+        .nest(
+            "/api/v1/tokens",
+            tokens::tokens_router(shared_state.clone()),
+        )
+        .nest(
+            "/api/v1/analytics",
+            events::analytics_router(shared_state.clone()),
+        )
+        .nest(
+            "/api/v1/videos",
+            duplicate_video::videos_router(shared_state.clone()),
+        )
         .split_for_parts();
 
     let router =
@@ -88,12 +213,45 @@ async fn main_impl() -> Result<()> {
 
     let admin_routes = Router::new()
         .route("/backfill/videohash", post(trigger_videohash_backfill))
+        .route("/canisters-list", get(canisters_list_handler))
+        .route("/canister-backup/coverage", get(backup_coverage_handler))
+        .route("/videohash/cluster", post(videohash_cluster_handler))
+        .route("/videohash/export.csv", get(videohash_export_csv_handler))
+        .route("/videohash/import", post(videohash_import_csv_handler))
+        .route("/videohash/index-stats", get(videohash_index_stats_handler))
+        .route(
+            "/videohash/rebuild-mih-index",
+            post(videohash_rebuild_mih_index_handler),
+        )
+        .route("/qstash/queue-depths", get(queue_depths_handler))
+        .route("/dead_letter/count", get(dead_letter_count_handler))
+        .route("/dead_letter/replay", post(dead_letter_replay_handler))
+        .route("/video-pipeline/stuck", get(stuck_videos_handler))
+        .route("/sns/neuron-health", get(neuron_health_handler))
+        .route("/sns/bulk-claim-tokens", post(bulk_claim_tokens_handler))
+        .route("/purge-test-data", post(purge_test_data_handler))
+        .route("/feed-cache/reindex", post(feed_cache_reindex_handler))
+        .route("/events/export-to-parquet", post(export_to_parquet_handler))
+        .route(
+            "/events/export-to-parquet/{job_id}",
+            get(export_job_status_handler),
+        )
+        .route(
+            "/cleanup-orphaned-redis-keys",
+            post(cleanup_orphaned_redis_keys_handler),
+        )
         .with_state(shared_state.clone());
 
     let http = Router::new()
         .route("/healthz", get(health_handler))
         .route("/report-approved", post(report_approved_handler))
-        .route("/import-video", post(upload_user_video_handler))
+        .route(
+            "/import-video",
+            post(upload_user_video_handler).layer(middleware::from_fn_with_state(
+                shared_state.clone(),
+                storage_quota_check,
+            )),
+        )
         .route(
             "/upgrade_user_token_sns_canister/{individual_user_canister_id}",
             post(upgrade_user_token_sns_canister_handler),
@@ -121,11 +279,12 @@ async fn main_impl() -> Result<()> {
 
     let grpc_axum = Routes::builder()
         .routes()
-        .add_service(WarehouseEventsServer::with_interceptor(
-            WarehouseEventsService {
+        .add_service(InterceptedService::new(
+            WarehouseEventsServer::new(WarehouseEventsService {
                 shared_state: shared_state.clone(),
-            },
-            check_auth_grpc,
+            })
+            .max_decoding_message_size(EVENT_BODY_LIMIT),
+            |req| check_grpc_content_length(check_auth_grpc(req)?),
         ))
         .add_service(OffChainServer::with_interceptor(
             OffChainService {
@@ -4,7 +4,7 @@ use crate::{
     app_state::AppState, consts::GOOGLE_CHAT_REPORT_SPACE_URL,
     posts::report_post::repost_post_common_impl, AppError,
 };
-use anyhow::{Context, Result};
+use anyhow::Result;
 use axum::extract::State;
 use candid::Principal;
 use http::HeaderMap;
@@ -160,16 +160,12 @@ pub async fn report_approved_handler(
 
     // authenticate the request
 
-    let bearer = headers
-        .get("Authorization")
-        .context("Missing Authorization header")?;
-    let bearer_str = bearer
-        .to_str()
-        .context("Failed to parse Authorization header")?;
+    let bearer = headers.get("Authorization").ok_or(AppError::Unauthorized)?;
+    let bearer_str = bearer.to_str().map_err(|_| AppError::Unauthorized)?;
     let auth_token = bearer_str
         .split("Bearer ")
         .last()
-        .context("Failed to parse Bearer token")?;
+        .ok_or(AppError::Unauthorized)?;
 
     // get PUBLIC_CERTS from GET https://www.googleapis.com/service_accounts/v1/metadata/x509/chat@system.gserviceaccount.com
 
@@ -203,23 +199,39 @@ pub async fn report_approved_handler(
         }
     }
     if !valid {
-        return Err(anyhow::anyhow!("Invalid JWT").into());
+        return Err(AppError::Unauthorized);
     }
 
     // Get the data from the body
-    let payload: GChatPayload = serde_json::from_str(&body)?;
-    let view_type = payload.action.parameters[0].value.clone();
+    let payload: GChatPayload =
+        serde_json::from_str(&body).map_err(|e| AppError::InvalidInput(e.to_string()))?;
+    let view_type = payload
+        .action
+        .parameters
+        .first()
+        .ok_or_else(|| AppError::InvalidInput("Missing action parameters".into()))?
+        .value
+        .clone();
 
     // view_type format : "canister_id post_id(int)"
     let view_type: Vec<&str> = view_type.split(" ").collect();
-    let canister_id = view_type[0];
-    let canister_principal = Principal::from_text(canister_id)?;
-    let post_id = view_type[1].parse::<u64>()?;
+    let [canister_id, post_id, ..] = view_type[..] else {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid view_type: {}",
+            view_type.join(" ")
+        )));
+    };
+    let canister_principal =
+        Principal::from_text(canister_id).map_err(|e| AppError::InvalidInput(e.to_string()))?;
+    let post_id = post_id
+        .parse::<u64>()
+        .map_err(|e| AppError::InvalidInput(e.to_string()))?;
 
     let user = state.individual_user(canister_principal);
 
     user.update_post_status(post_id, PostStatus::BannedDueToUserReporting)
-        .await?;
+        .await
+        .map_err(|e| AppError::CanisterCallFailed(e.to_string()))?;
 
     // send confirmation to Google Chat
     let confirmation_msg = json!({
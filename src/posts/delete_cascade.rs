@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use candid::Principal;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use uuid::Uuid;
+
+#[cfg(not(feature = "local-bin"))]
+use google_cloud_bigquery::http::job::query::QueryRequest;
+
+#[cfg(not(feature = "local-bin"))]
+use crate::user::orphaned_keys::KeyStore;
+use crate::{
+    app_state::AppState,
+    events::{
+        event::view_milestone::{view_milestone_check_key, view_milestone_key, VIEW_MILESTONES},
+        nsfw_replay::{delete_nsfw_rows_query, is_valid_video_id, NSFW_RESULT_TABLES},
+    },
+    posts::engagement::post_engagement_cache_key,
+    qstash::gcs_gc::{video_object_name, VIDEOS_BUCKET},
+    AppError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PostDeleteCascadeRequest {
+    pub post_id: u64,
+    pub canister_id: Principal,
+    pub video_id: String,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct PostDeleteCascadeResponse {
+    pub bigquery_rows_deleted: u64,
+    pub redis_keys_deleted: usize,
+    pub gcs_cleanup_enqueued: bool,
+    pub video_hash_removed: bool,
+    /// Steps that failed, the job is retried when any did
+    pub failed: Vec<String>,
+}
+
+/// BigQuery rows derived from the video. `video_unique` is handled by
+/// `handle_duplicate_post_on_delete`, and `video_deleted` is the tombstone itself.
+pub fn post_delete_queries(video_id: &str) -> Vec<String> {
+    let mut queries: Vec<String> = NSFW_RESULT_TABLES
+        .iter()
+        .map(|table| delete_nsfw_rows_query(table, video_id))
+        .collect();
+    queries.push(format!(
+        "DELETE FROM `hot-or-not-feed-intelligence.yral_ds.video_embeddings_agg` WHERE uri = 'gs://{}/{}'",
+        VIDEOS_BUCKET,
+        video_object_name(video_id)
+    ));
+
+    queries
+}
+
+/// Redis keys of the post: engagement cache and view milestones. Its video hash is removed
+/// with [`PostCleanup::remove_video_hash`].
+pub fn post_redis_keys(canister_id: Principal, post_id: u64) -> Vec<String> {
+    let mut keys = vec![
+        post_engagement_cache_key(canister_id, post_id),
+        view_milestone_check_key(canister_id, post_id),
+    ];
+    keys.extend(
+        VIEW_MILESTONES
+            .iter()
+            .map(|milestone| view_milestone_key(canister_id, post_id, *milestone)),
+    );
+
+    keys
+}
+
+pub(crate) trait PostCleanup {
+    /// Runs a DML statement, returns the number of rows it deleted
+    async fn delete_bigquery_rows(&self, query: &str) -> Result<u64, anyhow::Error>;
+
+    /// Returns the number of keys that existed
+    async fn delete_redis_keys(&self, keys: &[String]) -> Result<usize, anyhow::Error>;
+
+    async fn enqueue_gcs_cleanup(&self, video_id: &str) -> Result<(), anyhow::Error>;
+
+    /// Drops the hash from Redis and this replica's index, returns whether either had it
+    async fn remove_video_hash(&self, id: &Uuid) -> Result<bool, anyhow::Error>;
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl PostCleanup for AppState {
+    async fn delete_bigquery_rows(&self, query: &str) -> Result<u64, anyhow::Error> {
+        let request = QueryRequest {
+            query: query.to_string(),
+            ..Default::default()
+        };
+        let response = self
+            .bigquery_client
+            .job()
+            .query("hot-or-not-feed-intelligence", &request)
+            .await?;
+
+        Ok(response.num_dml_affected_rows.unwrap_or_default().max(0) as u64)
+    }
+
+    async fn delete_redis_keys(&self, keys: &[String]) -> Result<usize, anyhow::Error> {
+        self.canister_backup_redis_pool.delete_keys(keys).await
+    }
+
+    async fn enqueue_gcs_cleanup(&self, video_id: &str) -> Result<(), anyhow::Error> {
+        self.qstash_client
+            .publish_gc_orphaned_gcs_objects(video_id)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_video_hash(&self, id: &Uuid) -> Result<bool, anyhow::Error> {
+        self.redis_video_hash_index().remove(id).await
+    }
+}
+
+/// Runs every cleanup step even when an earlier one fails, each step is idempotent so the
+/// whole cascade can be retried
+pub async fn post_delete_cascade_impl(
+    cleanup: &impl PostCleanup,
+    req: &PostDeleteCascadeRequest,
+) -> PostDeleteCascadeResponse {
+    let mut res = PostDeleteCascadeResponse::default();
+
+    for query in post_delete_queries(&req.video_id) {
+        match cleanup.delete_bigquery_rows(&query).await {
+            Ok(rows) => res.bigquery_rows_deleted += rows,
+            Err(e) => {
+                log::error!("Failed to delete rows of video {}: {}", req.video_id, e);
+                res.failed.push("bigquery".into());
+            }
+        }
+    }
+
+    let keys = post_redis_keys(req.canister_id, req.post_id);
+    match cleanup.delete_redis_keys(&keys).await {
+        Ok(deleted) => res.redis_keys_deleted = deleted,
+        Err(e) => {
+            log::error!("Failed to delete Redis keys of post {}: {}", req.post_id, e);
+            res.failed.push("redis".into());
+        }
+    }
+
+    match cleanup.enqueue_gcs_cleanup(&req.video_id).await {
+        Ok(()) => res.gcs_cleanup_enqueued = true,
+        Err(e) => {
+            log::error!(
+                "Failed to enqueue GCS cleanup of video {}: {}",
+                req.video_id,
+                e
+            );
+            res.failed.push("gcs".into());
+        }
+    }
+
+    // the hash index is keyed by the Cloudflare video uid
+    if let Ok(id) = Uuid::parse_str(&req.video_id) {
+        match cleanup.remove_video_hash(&id).await {
+            Ok(removed) => res.video_hash_removed = removed,
+            Err(e) => {
+                log::error!("Failed to remove video hash of {}: {}", req.video_id, e);
+                res.failed.push("video_hash".into());
+            }
+        }
+    }
+
+    res.failed.dedup();
+    res
+}
+
+/// Cleans up the data derived from a deleted post, enqueued by `handle_delete_post`.
+/// Other replicas drop the video hash at their next load from Redis.
+#[instrument(skip(state))]
+pub async fn post_delete_cascade(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<PostDeleteCascadeRequest>,
+) -> Result<Json<PostDeleteCascadeResponse>, AppError> {
+    if !is_valid_video_id(&req.video_id) {
+        return Err(AppError::InvalidInput(format!(
+            "invalid video id {}",
+            req.video_id
+        )));
+    }
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let res = post_delete_cascade_impl(state.as_ref(), &req).await;
+        log::info!(
+            "Post delete cascade of {}/{}: {:?}",
+            req.canister_id,
+            req.post_id,
+            res
+        );
+
+        if !res.failed.is_empty() {
+            return Err(anyhow::anyhow!(
+                "post delete cascade of video {} failed at {}",
+                req.video_id,
+                res.failed.join(", ")
+            )
+            .into());
+        }
+
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        log::info!("Skipping post delete cascade of {} on local", req.video_id);
+        Ok(Json(PostDeleteCascadeResponse::default()))
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::Mutex;
+
+use candid::Principal;
+use uuid::Uuid;
+
+use super::delete_cascade::{
+    post_delete_cascade_impl, post_delete_queries, post_redis_keys, PostCleanup,
+    PostDeleteCascadeRequest, PostDeleteCascadeResponse,
+};
+
+const VIDEO_ID: &str = "0123456789abcdef0123456789abcdef";
+
+/// Records every cleanup call, `failing` steps return an error
+#[derive(Default)]
+struct MockCleanup {
+    failing: Vec<&'static str>,
+    queries: Mutex<Vec<String>>,
+    deleted_keys: Mutex<Vec<String>>,
+    gcs_cleanups: Mutex<Vec<String>>,
+    removed_hashes: Mutex<Vec<Uuid>>,
+}
+
+impl MockCleanup {
+    fn check(&self, step: &str) -> Result<(), anyhow::Error> {
+        if self.failing.contains(&step) {
+            return Err(anyhow::anyhow!("{} unavailable", step));
+        }
+        Ok(())
+    }
+}
+
+impl PostCleanup for MockCleanup {
+    async fn delete_bigquery_rows(&self, query: &str) -> Result<u64, anyhow::Error> {
+        self.check("bigquery")?;
+        self.queries.lock().unwrap().push(query.to_string());
+        Ok(1)
+    }
+
+    async fn delete_redis_keys(&self, keys: &[String]) -> Result<usize, anyhow::Error> {
+        self.check("redis")?;
+        self.deleted_keys.lock().unwrap().extend_from_slice(keys);
+        Ok(2)
+    }
+
+    async fn enqueue_gcs_cleanup(&self, video_id: &str) -> Result<(), anyhow::Error> {
+        self.check("gcs")?;
+        self.gcs_cleanups.lock().unwrap().push(video_id.to_string());
+        Ok(())
+    }
+
+    async fn remove_video_hash(&self, id: &Uuid) -> Result<bool, anyhow::Error> {
+        self.check("video_hash")?;
+        self.removed_hashes.lock().unwrap().push(*id);
+        Ok(true)
+    }
+}
+
+fn request(video_id: &str) -> PostDeleteCascadeRequest {
+    PostDeleteCascadeRequest {
+        post_id: 7,
+        canister_id: Principal::anonymous(),
+        video_id: video_id.to_string(),
+    }
+}
+
+#[test]
+fn test_post_delete_queries() {
+    assert_eq!(
+        post_delete_queries("vid1"),
+        vec![
+            "DELETE FROM `hot-or-not-feed-intelligence.yral_ds.video_nsfw` WHERE video_id = 'vid1'",
+            "DELETE FROM `hot-or-not-feed-intelligence.yral_ds.video_nsfw_agg` WHERE video_id = 'vid1'",
+            "DELETE FROM `hot-or-not-feed-intelligence.yral_ds.video_embeddings_agg` WHERE uri = 'gs://yral-videos/vid1.mp4'",
+        ]
+    );
+}
+
+#[test]
+fn test_post_redis_keys() {
+    assert_eq!(
+        post_redis_keys(Principal::anonymous(), 7),
+        vec![
+            "post_engagement:2vxsx-fae:7".to_string(),
+            "view_milestone_check:2vxsx-fae:7".to_string(),
+            "view_milestone:2vxsx-fae:7:1000".to_string(),
+            "view_milestone:2vxsx-fae:7:10000".to_string(),
+            "view_milestone:2vxsx-fae:7:100000".to_string(),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_cascade_runs_every_cleanup_step() {
+    let cleanup = MockCleanup::default();
+
+    let res = post_delete_cascade_impl(&cleanup, &request(VIDEO_ID)).await;
+
+    assert_eq!(
+        res,
+        PostDeleteCascadeResponse {
+            bigquery_rows_deleted: 3,
+            redis_keys_deleted: 2,
+            gcs_cleanup_enqueued: true,
+            video_hash_removed: true,
+            failed: vec![],
+        }
+    );
+    assert_eq!(
+        *cleanup.queries.lock().unwrap(),
+        post_delete_queries(VIDEO_ID)
+    );
+    assert_eq!(*cleanup.gcs_cleanups.lock().unwrap(), vec![VIDEO_ID]);
+    assert_eq!(
+        *cleanup.removed_hashes.lock().unwrap(),
+        vec![Uuid::parse_str(VIDEO_ID).unwrap()]
+    );
+}
+
+#[tokio::test]
+async fn test_video_without_hash_id_skips_the_index() {
+    let cleanup = MockCleanup::default();
+
+    let res = post_delete_cascade_impl(&cleanup, &request("not-a-uuid")).await;
+
+    assert!(!res.video_hash_removed);
+    assert!(res.failed.is_empty());
+    assert!(cleanup.removed_hashes.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_failed_steps_do_not_stop_the_others() {
+    let cleanup = MockCleanup {
+        failing: vec!["bigquery", "gcs"],
+        ..Default::default()
+    };
+
+    let res = post_delete_cascade_impl(&cleanup, &request(VIDEO_ID)).await;
+
+    assert_eq!(res.failed, vec!["bigquery", "gcs"]);
+    assert_eq!(res.bigquery_rows_deleted, 0);
+    assert!(!res.gcs_cleanup_enqueued);
+    assert_eq!(res.redis_keys_deleted, 2);
+    assert!(res.video_hash_removed);
+}
+
+#[tokio::test]
+async fn test_failed_hash_removal_is_retried() {
+    let cleanup = MockCleanup {
+        failing: vec!["video_hash"],
+        ..Default::default()
+    };
+
+    let res = post_delete_cascade_impl(&cleanup, &request(VIDEO_ID)).await;
+
+    assert_eq!(res.failed, vec!["video_hash"]);
+    assert!(!res.video_hash_removed);
+    assert!(res.gcs_cleanup_enqueued);
+}
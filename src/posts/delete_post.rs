@@ -18,8 +18,9 @@ use verify::VerifiedPostRequest;
 use yral_canisters_client::individual_user_template::{IndividualUserTemplate, Result_};
 
 use crate::{
-    app_state::AppState, posts::queries::get_duplicate_children_query,
-    user::utils::get_agent_from_delegated_identity_wire,
+    app_state::AppState,
+    posts::{delete_cascade::PostDeleteCascadeRequest, queries::get_duplicate_children_query},
+    qstash::video_jobs::cancel_video_jobs,
 };
 
 use super::{types, utils, verify, DeletePostRequest};
@@ -52,10 +53,9 @@ pub async fn handle_delete_post(
     let post_id = request_body.post_id;
     let video_id = request_body.video_id;
 
-    let agent =
-        get_agent_from_delegated_identity_wire(&verified_request.request.delegated_identity_wire)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let agent = state
+        .get_pooled_agent(&verified_request.request.delegated_identity_wire)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     let individual_user_template = IndividualUserTemplate(verified_request.user_canister, &agent);
 
     // Call the canister to delete the post
@@ -87,6 +87,36 @@ pub async fn handle_delete_post(
             )
         })?;
 
+    match cancel_video_jobs(
+        &state.canister_backup_redis_pool,
+        &state.qstash_client,
+        &video_id,
+    )
+    .await
+    {
+        Ok(cancelled) if cancelled > 0 => {
+            log::info!("Cancelled {} pending jobs of video {}", cancelled, video_id)
+        }
+        Ok(_) => (),
+        Err(e) => log::error!("Failed to cancel pending jobs of video {}: {}", video_id, e),
+    }
+
+    if let Err(e) = state
+        .qstash_client
+        .publish_post_delete_cascade(&PostDeleteCascadeRequest {
+            post_id,
+            canister_id: request_body.canister_id,
+            video_id: video_id.clone(),
+        })
+        .await
+    {
+        log::error!(
+            "Failed to enqueue delete cascade for video {}: {}",
+            video_id,
+            e
+        );
+    }
+
     // spawn to not block the request since as far as user is concerned, the post is deleted
     let bigquery_client = state.bigquery_client.clone();
     let video_id_clone = video_id.clone();
@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+#[cfg(not(feature = "local-bin"))]
+use crate::utils::geoip::client_ip;
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    Json,
+};
+use candid::Principal;
+use google_cloud_bigquery::{
+    http::{
+        job::query::QueryRequest,
+        query::{QueryParameter, QueryParameterType, QueryParameterValue},
+    },
+    query::row::Row as QueryRow,
+};
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_events, AuthBearer},
+    utils::rate_limit::RateLimit,
+    AppError,
+};
+
+pub const POST_ENGAGEMENT_CACHE_TTL_SECS: u64 = 60;
+/// Engagement is counted over this many days, so the query scans a bounded set of partitions
+pub const POST_ENGAGEMENT_WINDOW_DAYS: u64 = 90;
+/// Lookups per client ip, cached or not
+pub const POST_ENGAGEMENT_RATE_LIMIT: RateLimit = RateLimit {
+    name: "post_engagement",
+    max_requests: 60,
+    window_secs: 60,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ToSchema)]
+pub struct PostEngagement {
+    pub likes: u64,
+    pub views: u64,
+    pub shares: u64,
+}
+
+pub fn post_engagement_cache_key(canister_id: Principal, post_id: u64) -> String {
+    format!("post_engagement:{}:{}", canister_id, post_id)
+}
+
+pub const POST_ENGAGEMENT_QUERY: &str = "SELECT
+        COUNTIF(event = 'like_video') AS likes,
+        COUNTIF(event = 'video_duration_watched') AS views,
+        COUNTIF(event = 'share_video') AS shares
+    FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+    WHERE event IN ('like_video', 'video_duration_watched', 'share_video')
+        AND timestamp >= TIMESTAMP_SUB(CURRENT_TIMESTAMP(), INTERVAL @window_days DAY)
+        AND JSON_EXTRACT_SCALAR(params, '$.publisher_canister_id') = @publisher_canister_id
+        AND SAFE_CAST(JSON_EXTRACT_SCALAR(params, '$.post_id') AS INT64) = @post_id";
+
+pub(crate) fn named_parameter(name: &str, parameter_type: &str, value: String) -> QueryParameter {
+    QueryParameter {
+        name: Some(name.to_string()),
+        parameter_type: QueryParameterType {
+            parameter_type: parameter_type.to_string(),
+            ..Default::default()
+        },
+        parameter_value: QueryParameterValue {
+            value: Some(value),
+            ..Default::default()
+        },
+    }
+}
+
+/// The ids are passed as named parameters, never formatted into the query
+pub fn post_engagement_request(canister_id: Principal, post_id: u64) -> QueryRequest {
+    QueryRequest {
+        query: POST_ENGAGEMENT_QUERY.to_string(),
+        parameter_mode: Some("NAMED".to_string()),
+        query_parameters: vec![
+            named_parameter("publisher_canister_id", "STRING", canister_id.to_text()),
+            named_parameter("post_id", "INT64", post_id.to_string()),
+            named_parameter(
+                "window_days",
+                "INT64",
+                POST_ENGAGEMENT_WINDOW_DAYS.to_string(),
+            ),
+        ],
+        ..Default::default()
+    }
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn query_post_engagement(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+    canister_id: Principal,
+    post_id: u64,
+) -> Result<PostEngagement, anyhow::Error> {
+    let mut response = bigquery_client
+        .query::<QueryRow>(
+            "hot-or-not-feed-intelligence",
+            post_engagement_request(canister_id, post_id),
+        )
+        .await?;
+
+    let Some(row) = response.next().await? else {
+        return Ok(PostEngagement::default());
+    };
+
+    Ok(PostEngagement {
+        likes: row.column::<i64>(0)? as u64,
+        views: row.column::<i64>(1)? as u64,
+        shares: row.column::<i64>(2)? as u64,
+    })
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn get_cached_engagement(state: &AppState, key: &str) -> Option<PostEngagement> {
+    let mut conn = state.canister_backup_redis_pool.get().await.ok()?;
+    let cached: Option<String> = conn.get(key).await.ok()?;
+
+    cached.and_then(|cached| serde_json::from_str(&cached).ok())
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn cache_engagement(state: &AppState, key: &str, engagement: PostEngagement) {
+    let res = async {
+        let mut conn = state.canister_backup_redis_pool.get().await?;
+        conn.set_ex::<_, _, ()>(
+            key,
+            serde_json::to_string(&engagement)?,
+            POST_ENGAGEMENT_CACHE_TTL_SECS,
+        )
+        .await?;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    if let Err(e) = res {
+        log::warn!("Failed to cache post engagement {}: {}", key, e);
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{canister_id}/{post_id}/engagement",
+    params(
+        ("canister_id" = String, Path, description = "Canister id of the post's publisher"),
+        ("post_id" = u64, Path, description = "Post id"),
+    ),
+    tag = "posts",
+    responses(
+        (status = 200, description = "Like, view and share counts of the post over the last 90 days", body = PostEngagement),
+        (status = 400, description = "Invalid canister id"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many lookups from the client"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state, token, headers))]
+pub async fn handle_post_engagement(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    headers: HeaderMap,
+    Path((canister_id, post_id)): Path<(String, u64)>,
+) -> Result<Json<PostEngagement>, AppError> {
+    check_auth_events(Some(token)).map_err(|_| AppError::Unauthorized)?;
+
+    let canister_id = Principal::from_text(&canister_id)
+        .map_err(|_| AppError::InvalidInput("Invalid canister id".to_string()))?;
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let caller = client_ip(&headers)
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        POST_ENGAGEMENT_RATE_LIMIT
+            .check(
+                &state.canister_backup_redis_pool,
+                &caller,
+                chrono::Utc::now().timestamp() as u64,
+            )
+            .await?;
+
+        let key = post_engagement_cache_key(canister_id, post_id);
+        if let Some(engagement) = get_cached_engagement(&state, &key).await {
+            return Ok(Json(engagement));
+        }
+
+        let engagement = query_post_engagement(&state.bigquery_client, canister_id, post_id)
+            .await
+            .map_err(|e| {
+                log::error!(
+                    "Failed to query engagement of post {}/{}: {}",
+                    canister_id,
+                    post_id,
+                    e
+                );
+                AppError::BigQueryError(e)
+            })?;
+        cache_engagement(&state, &key, engagement).await;
+
+        Ok(Json(engagement))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, headers, canister_id, post_id);
+        Ok(Json(PostEngagement::default()))
+    }
+}
@@ -0,0 +1,90 @@
+use candid::Principal;
+
+use super::engagement::{post_engagement_cache_key, post_engagement_request, PostEngagement};
+
+fn canister() -> Principal {
+    Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()
+}
+
+#[test]
+fn test_engagement_request_uses_named_parameters() {
+    let request = post_engagement_request(canister(), 42);
+
+    assert_eq!(request.parameter_mode.as_deref(), Some("NAMED"));
+    assert!(request.query.contains("= @publisher_canister_id"));
+    assert!(request.query.contains("= @post_id"));
+    assert!(!request.query.contains("rrkah-fqaaa-aaaaa-aaaaq-cai"));
+
+    let params: Vec<_> = request
+        .query_parameters
+        .iter()
+        .map(|p| {
+            (
+                p.name.as_deref().unwrap(),
+                p.parameter_type.parameter_type.as_str(),
+                p.parameter_value.value.as_deref().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        params,
+        vec![
+            (
+                "publisher_canister_id",
+                "STRING",
+                "rrkah-fqaaa-aaaaa-aaaaq-cai"
+            ),
+            ("post_id", "INT64", "42"),
+            ("window_days", "INT64", "90"),
+        ]
+    );
+}
+
+#[test]
+fn test_engagement_query_counts_each_event() {
+    let request = post_engagement_request(canister(), 42);
+
+    assert!(request
+        .query
+        .contains("COUNTIF(event = 'like_video') AS likes"));
+    assert!(request
+        .query
+        .contains("COUNTIF(event = 'video_duration_watched') AS views"));
+    assert!(request
+        .query
+        .contains("COUNTIF(event = 'share_video') AS shares"));
+}
+
+#[test]
+fn test_engagement_query_is_bounded_in_time() {
+    let request = post_engagement_request(canister(), 42);
+
+    assert!(request
+        .query
+        .contains("timestamp >= TIMESTAMP_SUB(CURRENT_TIMESTAMP(), INTERVAL @window_days DAY)"));
+}
+
+#[test]
+fn test_engagement_cache_key() {
+    assert_eq!(
+        post_engagement_cache_key(canister(), 7),
+        "post_engagement:rrkah-fqaaa-aaaaa-aaaaq-cai:7"
+    );
+}
+
+#[test]
+fn test_engagement_cache_round_trip() {
+    let engagement = PostEngagement {
+        likes: 3,
+        views: 120,
+        shares: 1,
+    };
+
+    let cached = serde_json::to_string(&engagement).unwrap();
+
+    assert_eq!(cached, r#"{"likes":3,"views":120,"shares":1}"#);
+    assert_eq!(
+        serde_json::from_str::<PostEngagement>(&cached).unwrap(),
+        engagement
+    );
+}
@@ -21,12 +21,22 @@ use crate::app_state::AppState;
 use crate::posts::delete_post::__path_handle_delete_post;
 use crate::posts::report_post::{__path_handle_report_post, __path_handle_report_post_v2};
 
+pub mod delete_cascade;
 pub mod delete_post;
+pub mod engagement;
 mod queries;
 pub mod report_post;
 pub mod types;
 mod utils;
 mod verify;
+pub mod watch_history;
+
+#[cfg(test)]
+mod delete_cascade_tests;
+#[cfg(test)]
+mod engagement_tests;
+#[cfg(test)]
+mod watch_history_tests;
 
 /// Macro to create a route with verification middleware
 macro_rules! verified_route {
@@ -45,6 +55,8 @@ pub fn posts_router(state: Arc<AppState>) -> OpenApiRouter {
     router = verified_route!(router, handle_delete_post, DeletePostRequest, state);
     router = verified_route!(router, handle_report_post, ReportPostRequest, state);
     router = verified_route!(router, handle_report_post_v2, ReportPostRequestV2, state);
+    router = router.routes(routes!(watch_history::handle_watch_history));
+    router = router.routes(routes!(engagement::handle_post_engagement));
 
     router.with_state(state)
 }
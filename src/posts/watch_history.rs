@@ -0,0 +1,159 @@
+use std::{sync::Arc, time::UNIX_EPOCH};
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use candid::Principal;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+use yral_ml_feed_cache::{
+    consts::{USER_WATCH_HISTORY_CLEAN_SUFFIX, USER_WATCH_HISTORY_NSFW_SUFFIX},
+    types::MLFeedCacheHistoryItem,
+};
+
+use crate::{
+    app_state::AppState, types::DelegatedIdentityWire,
+    utils::delegated_identity::get_user_info_from_delegated_identity_wire,
+};
+
+/// Header carrying the json encoded `DelegatedIdentityWire`, GET requests have no body
+pub const DELEGATED_IDENTITY_HEADER: &str = "x-delegated-identity";
+
+/// Most recent items read from each history list
+pub const WATCH_HISTORY_LIMIT: isize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct WatchHistoryResponse {
+    pub publisher_canister_id: String,
+    pub post_id: u64,
+    pub video_id: String,
+    pub item_type: String,
+    pub nsfw_probability: f32,
+    pub percent_watched: f32,
+    /// Unix timestamp in milliseconds
+    pub timestamp: u64,
+}
+
+impl From<MLFeedCacheHistoryItem> for WatchHistoryResponse {
+    fn from(item: MLFeedCacheHistoryItem) -> Self {
+        let timestamp = item
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            publisher_canister_id: item.canister_id,
+            post_id: item.post_id,
+            video_id: item.video_id,
+            item_type: item.item_type,
+            nsfw_probability: item.nsfw_probability,
+            percent_watched: item.percent_watched,
+            timestamp,
+        }
+    }
+}
+
+/// Merges the clean and nsfw histories, most recent first
+pub fn merge_watch_history(
+    clean: Vec<MLFeedCacheHistoryItem>,
+    nsfw: Vec<MLFeedCacheHistoryItem>,
+) -> Vec<WatchHistoryResponse> {
+    let mut items: Vec<WatchHistoryResponse> = clean
+        .into_iter()
+        .chain(nsfw)
+        .map(WatchHistoryResponse::from)
+        .collect();
+    items.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+    items
+}
+
+async fn get_recent_history_items(
+    state: &AppState,
+    key: &str,
+) -> Result<Vec<MLFeedCacheHistoryItem>, anyhow::Error> {
+    let mut conn = state.ml_feed_cache.redis_pool.get().await?;
+    let raw: Vec<String> = conn.lrange(key, 0, WATCH_HISTORY_LIMIT - 1).await?;
+
+    Ok(raw
+        .iter()
+        .filter_map(|item| match serde_json::from_str(item) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                log::warn!("Skipping invalid watch history item in {}: {}", key, e);
+                None
+            }
+        })
+        .collect())
+}
+
+#[utoipa::path(
+    get,
+    path = "/watch-history/{canister_id}",
+    params(
+        ("canister_id" = String, Path, description = "User canister id"),
+        ("x-delegated-identity" = String, Header, description = "JSON encoded DelegatedIdentityWire of the canister owner"),
+    ),
+    tag = "posts",
+    responses(
+        (status = 200, description = "Recent watch history", body = Vec<WatchHistoryResponse>),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state, headers))]
+pub async fn handle_watch_history(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(canister_id): Path<String>,
+) -> Result<Json<Vec<WatchHistoryResponse>>, (StatusCode, String)> {
+    let canister_id = Principal::from_text(&canister_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid canister id".to_string()))?;
+
+    let delegated_identity_wire: DelegatedIdentityWire = headers
+        .get(DELEGATED_IDENTITY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| serde_json::from_str(value).ok())
+        .ok_or((
+            StatusCode::UNAUTHORIZED,
+            "Missing delegated identity".to_string(),
+        ))?;
+
+    let user_info = get_user_info_from_delegated_identity_wire(&state, delegated_identity_wire)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::UNAUTHORIZED,
+                format!("Failed to get user info: {}", e),
+            )
+        })?;
+    if user_info.user_canister != canister_id {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Canister does not belong to user".to_string(),
+        ));
+    }
+
+    let clean_key = format!("{}{}", canister_id, USER_WATCH_HISTORY_CLEAN_SUFFIX);
+    let nsfw_key = format!("{}{}", canister_id, USER_WATCH_HISTORY_NSFW_SUFFIX);
+
+    let (clean, nsfw) = tokio::try_join!(
+        get_recent_history_items(&state, &clean_key),
+        get_recent_history_items(&state, &nsfw_key),
+    )
+    .map_err(|e| {
+        log::error!("Failed to read watch history for {}: {}", canister_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to read watch history".to_string(),
+        )
+    })?;
+
+    Ok(Json(merge_watch_history(clean, nsfw)))
+}
@@ -0,0 +1,43 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use yral_ml_feed_cache::types::MLFeedCacheHistoryItem;
+
+use super::watch_history::merge_watch_history;
+
+fn history_item(video_id: &str, secs: u64) -> MLFeedCacheHistoryItem {
+    MLFeedCacheHistoryItem {
+        canister_id: "publisher".to_string(),
+        item_type: "video_duration_watched".to_string(),
+        nsfw_probability: 0.1,
+        post_id: secs,
+        video_id: video_id.to_string(),
+        timestamp: UNIX_EPOCH + Duration::from_secs(secs),
+        percent_watched: 50.0,
+    }
+}
+
+#[test]
+fn test_merge_sorts_most_recent_first() {
+    let clean = vec![history_item("c2", 20), history_item("c1", 10)];
+    let nsfw = vec![history_item("n3", 30), history_item("n0", 5)];
+
+    let merged = merge_watch_history(clean, nsfw);
+    let ids: Vec<&str> = merged.iter().map(|i| i.video_id.as_str()).collect();
+
+    assert_eq!(ids, vec!["n3", "c2", "c1", "n0"]);
+    assert_eq!(merged[0].timestamp, 30_000);
+}
+
+#[test]
+fn test_merge_empty_histories() {
+    assert!(merge_watch_history(vec![], vec![]).is_empty());
+}
+
+#[test]
+fn test_response_keeps_item_fields() {
+    let merged = merge_watch_history(vec![history_item("c1", 10)], vec![]);
+
+    assert_eq!(merged[0].publisher_canister_id, "publisher");
+    assert_eq!(merged[0].post_id, 10);
+    assert_eq!(merged[0].percent_watched, 50.0);
+}
@@ -0,0 +1,188 @@
+use std::{future::Future, sync::Arc};
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, NaiveDate, Utc};
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::{app_state::AppState, qstash::trace::job_skipped, types::RedisPool, AppError};
+
+const HOT_EVENTS_TABLE: &str =
+    "hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics";
+/// Day partitioned, untouched partitions are billed at the long-term storage rate after 90 days
+const COLD_EVENTS_TABLE: &str =
+    "hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics_archive";
+
+pub const ARCHIVE_AFTER_DAYS: i64 = 90;
+/// Days archived per job run, the job enqueues itself until it catches up
+pub const ARCHIVE_DAYS_PER_RUN: usize = 30;
+/// Last day moved to the cold table, as `YYYY-MM-DD`
+pub const ARCHIVED_THROUGH_KEY: &str = "events_archive:archived_through";
+pub const ARCHIVE_OLD_EVENTS_CRON: &str = "0 3 1 * *";
+pub const ARCHIVE_OLD_EVENTS_SCHEDULE_ID: &str = "archive-old-events";
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct ArchiveProgress {
+    pub archived_days: usize,
+    pub archived_through: Option<NaiveDate>,
+    /// All days before the cutoff are archived
+    pub done: bool,
+}
+
+/// Events before this day are moved to the cold table
+pub fn archive_cutoff(today: NaiveDate) -> NaiveDate {
+    today - Duration::days(ARCHIVE_AFTER_DAYS)
+}
+
+/// Moves one day of events in a transaction, so a failed delete never leaves them in both tables
+pub fn archive_day_statement(day: NaiveDate) -> String {
+    let filter = format!(
+        "WHERE timestamp >= TIMESTAMP('{}') AND timestamp < TIMESTAMP('{}')",
+        day.format("%Y-%m-%d"),
+        (day + Duration::days(1)).format("%Y-%m-%d")
+    );
+
+    format!(
+        "BEGIN TRANSACTION;
+        INSERT INTO `{cold}` SELECT * FROM `{hot}` {filter};
+        DELETE FROM `{hot}` {filter};
+        COMMIT TRANSACTION;",
+        cold = COLD_EVENTS_TABLE,
+        hot = HOT_EVENTS_TABLE,
+        filter = filter
+    )
+}
+
+/// Archives days from `start` one at a time until `cutoff` or `max_days`.
+/// `archive_day` must record its day as done, days archived before a failure stay recorded.
+pub async fn archive_days<F, Fut>(
+    start: NaiveDate,
+    cutoff: NaiveDate,
+    max_days: usize,
+    mut archive_day: F,
+) -> Result<ArchiveProgress, anyhow::Error>
+where
+    F: FnMut(NaiveDate) -> Fut,
+    Fut: Future<Output = Result<(), anyhow::Error>>,
+{
+    let mut progress = ArchiveProgress::default();
+    let mut day = start;
+
+    while day < cutoff && progress.archived_days < max_days {
+        archive_day(day).await?;
+        progress.archived_days += 1;
+        progress.archived_through = Some(day);
+        day += Duration::days(1);
+    }
+    progress.done = day >= cutoff;
+
+    Ok(progress)
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn get_archived_through(redis_pool: &RedisPool) -> Result<Option<NaiveDate>, anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    let day: Option<String> = conn.get(ARCHIVED_THROUGH_KEY).await?;
+
+    Ok(day.and_then(|day| NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()))
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn set_archived_through(redis_pool: &RedisPool, day: NaiveDate) -> Result<(), anyhow::Error> {
+    let mut conn = redis_pool.get().await?;
+    conn.set::<_, _, ()>(ARCHIVED_THROUGH_KEY, day.format("%Y-%m-%d").to_string())
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn first_event_day(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+) -> Result<Option<NaiveDate>, anyhow::Error> {
+    let request = QueryRequest {
+        query: format!(
+            "SELECT CAST(DATE(MIN(timestamp)) AS STRING) FROM `{}`",
+            HOT_EVENTS_TABLE
+        ),
+        ..Default::default()
+    };
+    let mut response = bigquery_client
+        .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+        .await?;
+
+    let Some(row) = response.next().await? else {
+        return Ok(None);
+    };
+
+    Ok(row
+        .column::<Option<String>>(0)?
+        .and_then(|day| NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()))
+}
+
+/// Moves events older than 90 days from the events table to the cold table, scheduled monthly
+#[instrument(skip(state))]
+pub async fn archive_old_events(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let redis_pool = &state.canister_backup_redis_pool;
+        let bigquery_client = &state.bigquery_client;
+        let cutoff = archive_cutoff(Utc::now().date_naive());
+
+        let start = match get_archived_through(redis_pool).await? {
+            Some(day) => Some(day + Duration::days(1)),
+            None => first_event_day(bigquery_client)
+                .await
+                .map_err(AppError::BigQueryError)?,
+        };
+        let Some(start) = start else {
+            log::info!("No events to archive");
+            return Ok((
+                job_skipped("no events to archive"),
+                Json(ArchiveProgress {
+                    done: true,
+                    ..Default::default()
+                }),
+            )
+                .into_response());
+        };
+
+        let progress = archive_days(start, cutoff, ARCHIVE_DAYS_PER_RUN, |day| async move {
+            let request = QueryRequest {
+                query: archive_day_statement(day),
+                ..Default::default()
+            };
+            bigquery_client
+                .job()
+                .query("hot-or-not-feed-intelligence", &request)
+                .await?;
+            set_archived_through(redis_pool, day).await?;
+            log::info!("Archived events of {}", day);
+            Ok(())
+        })
+        .await?;
+
+        if !progress.done {
+            state.qstash_client.publish_archive_old_events().await?;
+        }
+
+        Ok(Json(progress).into_response())
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Ok((
+            job_skipped("archiving needs bigquery"),
+            Json(ArchiveProgress::default()),
+        )
+            .into_response())
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Mutex;
+
+use chrono::NaiveDate;
+
+use super::archive_events::{archive_cutoff, archive_day_statement, archive_days};
+
+fn day(d: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap()
+}
+
+#[test]
+fn test_cutoff_is_ninety_days_back() {
+    assert_eq!(archive_cutoff(day("2025-04-01")), day("2025-01-01"));
+}
+
+#[test]
+fn test_archive_statement_moves_one_day_in_a_transaction() {
+    let statement = archive_day_statement(day("2024-12-31"));
+
+    assert!(statement.starts_with("BEGIN TRANSACTION;"));
+    assert!(statement.contains(
+        "INSERT INTO `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics_archive` SELECT * FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`"
+    ));
+    assert!(statement.contains(
+        "DELETE FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`"
+    ));
+    assert_eq!(
+        statement
+            .matches("WHERE timestamp >= TIMESTAMP('2024-12-31') AND timestamp < TIMESTAMP('2025-01-01')")
+            .count(),
+        2
+    );
+    assert!(statement.trim_end().ends_with("COMMIT TRANSACTION;"));
+}
+
+#[tokio::test]
+async fn test_archive_days_stops_at_cutoff() {
+    let executed = Mutex::new(Vec::new());
+
+    let progress = archive_days(day("2025-01-01"), day("2025-01-04"), 30, |d| {
+        executed.lock().unwrap().push(archive_day_statement(d));
+        async { Ok(()) }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(progress.archived_days, 3);
+    assert_eq!(progress.archived_through, Some(day("2025-01-03")));
+    assert!(progress.done);
+    assert_eq!(executed.lock().unwrap().len(), 3);
+    assert!(executed.lock().unwrap()[2].contains("TIMESTAMP('2025-01-03')"));
+}
+
+#[tokio::test]
+async fn test_archive_days_respects_run_budget() {
+    let progress = archive_days(day("2025-01-01"), day("2025-03-01"), 10, |_| async {
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(progress.archived_days, 10);
+    assert_eq!(progress.archived_through, Some(day("2025-01-10")));
+    assert!(!progress.done);
+}
+
+#[tokio::test]
+async fn test_archive_days_nothing_before_cutoff() {
+    let executed = Mutex::new(0);
+
+    let progress = archive_days(day("2025-03-01"), day("2025-03-01"), 10, |_| {
+        *executed.lock().unwrap() += 1;
+        async { Ok(()) }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(progress.archived_days, 0);
+    assert!(progress.done);
+    assert_eq!(*executed.lock().unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_archive_days_stops_at_first_failure() {
+    let archived = Mutex::new(Vec::new());
+
+    let res = archive_days(day("2025-01-01"), day("2025-02-01"), 30, |d| {
+        let fail = d == day("2025-01-03");
+        if !fail {
+            archived.lock().unwrap().push(d);
+        }
+        async move {
+            if fail {
+                Err(anyhow::anyhow!("transaction aborted"))
+            } else {
+                Ok(())
+            }
+        }
+    })
+    .await;
+
+    assert!(res.is_err());
+    assert_eq!(
+        *archived.lock().unwrap(),
+        vec![day("2025-01-01"), day("2025-01-02")]
+    );
+}
@@ -5,23 +5,133 @@ use chrono::Timelike;
 use futures::StreamExt;
 use http::{
     header::{AUTHORIZATION, CONTENT_TYPE},
-    HeaderMap, HeaderValue,
+    HeaderMap, HeaderValue, StatusCode,
 };
 use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tracing::instrument;
 
 use crate::{
     canister::{
-        snapshot::snapshot_v2::BackupUserCanisterPayload,
+        bulk_claim_tokens::AdminClaimTokensRequest,
+        governance_health::{
+            SNS_GOVERNANCE_HEALTH_CHECK_CRON, SNS_GOVERNANCE_HEALTH_CHECK_SCHEDULE_ID,
+        },
+        neuron_followees::{
+            SyncNeuronFolloweesRequest, SYNC_NEURON_FOLLOWEES_CRON,
+            SYNC_NEURON_FOLLOWEES_SCHEDULE_ID,
+        },
+        snapshot::{
+            sample_verify::{VerifyBackupSampleRequest, VERIFY_BACKUP_SAMPLE_DELAY_SECS},
+            snapshot_v2::BackupUserCanisterPayload,
+        },
+        sns_wasm_hashes::{VERIFY_SNS_WASM_HASHES_CRON, VERIFY_SNS_WASM_HASHES_SCHEDULE_ID},
         upgrade_user_token_sns_canister::{SnsCanisters, VerifyUpgradeProposalRequest},
     },
     consts::OFF_CHAIN_AGENT_URL,
-    events::event::UploadVideoInfo,
-    posts::report_post::ReportPostRequestV2,
-    qstash::duplicate::{DuplicateVideoEvent, VideoHashDuplication, VideoPublisherData},
+    creators::score::{
+        ComputeCreatorScoreRequest, COMPUTE_CREATOR_SCORES_CRON, COMPUTE_CREATOR_SCORES_SCHEDULE_ID,
+    },
+    events::{
+        consistency_check::{CONSISTENCY_CHECK_CRON, CONSISTENCY_CHECK_SCHEDULE_ID},
+        event::{
+            profile_view::{PROFILE_VIEW_DIGEST_CRON, PROFILE_VIEW_DIGEST_SCHEDULE_ID},
+            UploadVideoInfo,
+        },
+        feed_cache_reindex::FeedCacheReindexRequest,
+        feed_history_rebalance::{
+            RebalanceFeedHistoryRequest, REBALANCE_FEED_HISTORY_CRON,
+            REBALANCE_FEED_HISTORY_SCHEDULE_ID,
+        },
+    },
+    posts::{delete_cascade::PostDeleteCascadeRequest, report_post::ReportPostRequestV2},
+    qstash::{
+        archive_events::{ARCHIVE_OLD_EVENTS_CRON, ARCHIVE_OLD_EVENTS_SCHEDULE_ID},
+        duplicate::{DuplicateVideoEvent, VideoHashDuplication, VideoPublisherData},
+        hot_videos::{REFRESH_HOT_VIDEOS_CRON, REFRESH_HOT_VIDEOS_SCHEDULE_ID},
+        token_airdrop::TokenAirdropRequest,
+        videohash_migration::MigrateVideohashRequest,
+    },
+    tokens::embeddings::IndexTokenMetadataRequest,
 };
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueInfo {
+    pub name: String,
+    #[serde(default)]
+    pub lag: u64,
+    #[serde(default)]
+    pub parallelism: u64,
+    #[serde(default)]
+    pub paused: bool,
+    pub created_at: Option<i64>,
+    pub updated_at: Option<i64>,
+}
+
+/// Most messages QStash accepts in one batch request
+const QSTASH_BATCH_LIMIT: usize = 100;
+
+/// QStash batch entries posting each request as the json body to `destination_url`
+pub fn json_batch_body<T: Serialize>(
+    destination_url: &str,
+    requests: &[T],
+) -> Vec<serde_json::Value> {
+    requests
+        .iter()
+        .map(|req| {
+            json!({
+                "destination": destination_url,
+                "headers": {
+                    "Upstash-Forward-Content-Type": "application/json",
+                    "Upstash-Forward-Method": "POST",
+                    "Upstash-Retries": "2",
+                },
+                "body": serde_json::to_string(req).unwrap_or_else(|_| "{}".to_string()),
+            })
+        })
+        .collect()
+}
+
+/// QStash batch entries for the admin token claim job, each with its own nonce for the job's
+/// replay check. QStash retries a message with the same one.
+pub fn claim_tokens_batch_body(
+    destination_url: &str,
+    requests: &[AdminClaimTokensRequest],
+) -> Vec<serde_json::Value> {
+    let mut body = json_batch_body(destination_url, requests);
+    for entry in &mut body {
+        entry["headers"]["Upstash-Forward-X-Nonce"] = uuid::Uuid::new_v4().to_string().into();
+    }
+
+    body
+}
+
+/// Id QStash assigns to a published message, needed to cancel it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct QStashMessageId(pub String);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PublishResponse {
+    pub message_id: QStashMessageId,
+}
+
+pub fn cancel_message_url(base_url: &Url, message_id: &str) -> Result<Url, anyhow::Error> {
+    Ok(base_url.join(&format!("messages/{}", message_id))?)
+}
+
+async fn message_id(response: reqwest::Response) -> Result<QStashMessageId, anyhow::Error> {
+    let response = response
+        .error_for_status()?
+        .json::<PublishResponse>()
+        .await?;
+
+    Ok(response.message_id)
+}
+
 #[derive(Clone, Debug)]
 pub struct QStashClient {
     pub client: Client,
@@ -90,7 +200,8 @@ impl QStashClient {
         post_id: u64,
         timestamp_str: String,
         publisher_user_id: &str,
-    ) -> Result<(), anyhow::Error> {
+        duration_secs: Option<f32>,
+    ) -> Result<QStashMessageId, anyhow::Error> {
         let off_chain_ep = OFF_CHAIN_AGENT_URL.join("qstash/upload_video_gcs").unwrap();
 
         let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
@@ -99,10 +210,12 @@ impl QStashClient {
             "canister_id": canister_id,
             "post_id": post_id,
             "timestamp": timestamp_str,
-            "publisher_user_id": publisher_user_id
+            "publisher_user_id": publisher_user_id,
+            "duration_secs": duration_secs
         });
 
-        self.client
+        let response = self
+            .client
             .post(url)
             .json(&req)
             .header(CONTENT_TYPE, "application/json")
@@ -110,7 +223,38 @@ impl QStashClient {
             .send()
             .await?;
 
-        Ok(())
+        message_id(response).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_video_deduplication(
+        &self,
+        video_id: &str,
+        video_url: &str,
+        publisher_data: &VideoPublisherData,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/video_deduplication")
+            .unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+        let req = serde_json::json!({
+            "video_id": video_id,
+            "video_url": video_url,
+            "publisher_data": publisher_data,
+        });
+
+        let response = self
+            .client
+            .post(url)
+            .json(&req)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .header("upstash-delay", "600s")
+            .send()
+            .await?;
+
+        message_id(response).await
     }
 
     #[instrument(skip(self))]
@@ -118,7 +262,7 @@ impl QStashClient {
         &self,
         video_id: &str,
         video_info: &UploadVideoInfo,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<QStashMessageId, anyhow::Error> {
         let off_chain_ep = OFF_CHAIN_AGENT_URL
             .join("qstash/enqueue_video_frames")
             .unwrap();
@@ -129,7 +273,8 @@ impl QStashClient {
             "video_info": video_info,
         });
 
-        self.client
+        let response = self
+            .client
             .post(url)
             .json(&req)
             .header(CONTENT_TYPE, "application/json")
@@ -137,7 +282,7 @@ impl QStashClient {
             .send()
             .await?;
 
-        Ok(())
+        message_id(response).await
     }
 
     #[instrument(skip(self))]
@@ -145,7 +290,7 @@ impl QStashClient {
         &self,
         video_id: &str,
         video_info: &UploadVideoInfo,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<QStashMessageId, anyhow::Error> {
         let off_chain_ep = OFF_CHAIN_AGENT_URL
             .join("qstash/enqueue_video_nsfw_detection")
             .unwrap();
@@ -156,7 +301,8 @@ impl QStashClient {
             "video_info": video_info,
         });
 
-        self.client
+        let response = self
+            .client
             .post(url)
             .json(&req)
             .header(CONTENT_TYPE, "application/json")
@@ -164,7 +310,7 @@ impl QStashClient {
             .send()
             .await?;
 
-        Ok(())
+        message_id(response).await
     }
 
     #[instrument(skip(self))]
@@ -172,7 +318,7 @@ impl QStashClient {
         &self,
         video_id: &str,
         video_info: UploadVideoInfo,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<QStashMessageId, anyhow::Error> {
         let off_chain_ep = OFF_CHAIN_AGENT_URL
             .join("qstash/enqueue_video_nsfw_detection_v2")
             .unwrap();
@@ -195,7 +341,8 @@ impl QStashClient {
         let jitter = (now.nanosecond() % 601) as u32;
         let delay_seconds = minutes_until_20 * 60 + jitter + 3600;
 
-        self.client
+        let response = self
+            .client
             .post(url)
             .json(&req)
             .header(CONTENT_TYPE, "application/json")
@@ -204,7 +351,7 @@ impl QStashClient {
             .send()
             .await?;
 
-        Ok(())
+        message_id(response).await
     }
 
     pub async fn upgrade_sns_creator_dao_canister(
@@ -304,13 +451,63 @@ impl QStashClient {
     pub async fn publish_report_post(
         &self,
         report_request: ReportPostRequestV2,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<QStashMessageId, anyhow::Error> {
         let off_chain_ep = OFF_CHAIN_AGENT_URL.join("qstash/report_post").unwrap();
 
         let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
         let req = serde_json::json!(report_request);
 
-        self.client
+        let response = self
+            .client
+            .post(url)
+            .json(&req)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_post_delete_cascade(
+        &self,
+        req: &PostDeleteCascadeRequest,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/post-delete-cascade")
+            .unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+
+        let response = self
+            .client
+            .post(url)
+            .json(req)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_gc_orphaned_gcs_objects(
+        &self,
+        video_id: &str,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/gc-orphaned-gcs-objects")
+            .unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+        let req = serde_json::json!({
+            "video_id": video_id,
+        });
+
+        let response = self
+            .client
             .post(url)
             .json(&req)
             .header(CONTENT_TYPE, "application/json")
@@ -318,9 +515,250 @@ impl QStashClient {
             .send()
             .await?;
 
+        message_id(response).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_token_airdrop(
+        &self,
+        airdrop_request: &TokenAirdropRequest,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL.join("qstash/token-airdrop").unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+        let req = serde_json::json!(airdrop_request);
+
+        let response = self
+            .client
+            .post(url)
+            .json(&req)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .header("upstash-retries", "0")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_reindex_user_feed_cache(
+        &self,
+        canister_id: Principal,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/reindex-user-feed-cache")
+            .unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+        let req = FeedCacheReindexRequest { canister_id };
+
+        let response = self
+            .client
+            .post(url)
+            .json(&req)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_sync_sns_neuron_followees(
+        &self,
+        req: &SyncNeuronFolloweesRequest,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/sync-sns-neuron-followees")
+            .unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+
+        let response = self
+            .client
+            .post(url)
+            .json(req)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_rebalance_user_feed_history(
+        &self,
+        req: &RebalanceFeedHistoryRequest,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/rebalance-user-feed-history")
+            .unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+
+        let response = self
+            .client
+            .post(url)
+            .json(req)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_migrate_videohash_to_spacetimedb(
+        &self,
+        req: &MigrateVideohashRequest,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/migrate-videohash-to-spacetimedb")
+            .unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+
+        let response = self
+            .client
+            .post(url)
+            .json(req)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_index_token_metadata_to_vector_db(
+        &self,
+        req: &IndexTokenMetadataRequest,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/index-token-metadata-to-vector-db")
+            .unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+
+        let response = self
+            .client
+            .post(url)
+            .json(req)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
+    #[instrument(skip(self, requests))]
+    pub async fn claim_tokens_batch(
+        &self,
+        requests: &[AdminClaimTokensRequest],
+    ) -> anyhow::Result<()> {
+        let destination_url = OFF_CHAIN_AGENT_URL
+            .join("qstash/claim_tokens_admin")?
+            .to_string();
+        let qstash_batch_url = self.base_url.join("batch")?;
+
+        for chunk in requests.chunks(QSTASH_BATCH_LIMIT) {
+            self.client
+                .post(qstash_batch_url.clone())
+                .json(&claim_tokens_batch_body(&destination_url, chunk))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
         Ok(())
     }
 
+    /// Cancels a queued message, messages already delivered or cancelled are ignored
+    #[instrument(skip(self))]
+    pub async fn cancel_message(&self, message_id: &str) -> Result<(), anyhow::Error> {
+        let url = cancel_message_url(&self.base_url, message_id)?;
+
+        let response = self.client.delete(url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        response.error_for_status()?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn list_queues(&self) -> Result<Vec<QueueInfo>, anyhow::Error> {
+        let url = self.base_url.join("queues")?;
+
+        let queues = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<QueueInfo>>()
+            .await?;
+
+        Ok(queues)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_backup_user_canister(
+        &self,
+        payload: &BackupUserCanisterPayload,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/backup_user_canister")
+            .unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+
+        let response = self
+            .client
+            .post(url)
+            .json(payload)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_verify_backup_sample(
+        &self,
+        date_str: String,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/verify-backup-sample")
+            .unwrap();
+
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+
+        let response = self
+            .client
+            .post(url)
+            .json(&VerifyBackupSampleRequest { date_str })
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .header(
+                "upstash-delay",
+                format!("{}s", VERIFY_BACKUP_SAMPLE_DELAY_SECS),
+            )
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
     #[instrument(skip(self, canister_ids))]
     pub async fn backup_canister_batch(
         &self,
@@ -404,4 +842,177 @@ impl QStashClient {
 
         Ok(())
     }
+
+    /// Creates or updates a cron schedule, the fixed id keeps restarts from adding duplicates
+    async fn upsert_schedule(
+        &self,
+        endpoint: &str,
+        schedule_id: &str,
+        cron: &str,
+    ) -> Result<(), anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL.join(endpoint)?;
+        let url = self.base_url.join(&format!("schedules/{}", off_chain_ep))?;
+
+        self.client
+            .post(url)
+            .header("upstash-method", "POST")
+            .header("Upstash-Cron", cron)
+            .header("Upstash-Schedule-Id", schedule_id)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upsert_refresh_hot_videos_schedule(&self) -> Result<(), anyhow::Error> {
+        self.upsert_schedule(
+            "qstash/refresh-hot-videos",
+            REFRESH_HOT_VIDEOS_SCHEDULE_ID,
+            REFRESH_HOT_VIDEOS_CRON,
+        )
+        .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upsert_archive_old_events_schedule(&self) -> Result<(), anyhow::Error> {
+        self.upsert_schedule(
+            "qstash/archive-old-events",
+            ARCHIVE_OLD_EVENTS_SCHEDULE_ID,
+            ARCHIVE_OLD_EVENTS_CRON,
+        )
+        .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upsert_compute_creator_scores_schedule(&self) -> Result<(), anyhow::Error> {
+        self.upsert_schedule(
+            "qstash/compute-creator-scores",
+            COMPUTE_CREATOR_SCORES_SCHEDULE_ID,
+            COMPUTE_CREATOR_SCORES_CRON,
+        )
+        .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upsert_verify_sns_wasm_hashes_schedule(&self) -> Result<(), anyhow::Error> {
+        self.upsert_schedule(
+            "qstash/verify-sns-wasm-hashes",
+            VERIFY_SNS_WASM_HASHES_SCHEDULE_ID,
+            VERIFY_SNS_WASM_HASHES_CRON,
+        )
+        .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upsert_sync_sns_neuron_followees_schedule(&self) -> Result<(), anyhow::Error> {
+        self.upsert_schedule(
+            "qstash/sync-sns-neuron-followees",
+            SYNC_NEURON_FOLLOWEES_SCHEDULE_ID,
+            SYNC_NEURON_FOLLOWEES_CRON,
+        )
+        .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upsert_sns_governance_health_check_schedule(&self) -> Result<(), anyhow::Error> {
+        self.upsert_schedule(
+            "qstash/sns-governance-health-check",
+            SNS_GOVERNANCE_HEALTH_CHECK_SCHEDULE_ID,
+            SNS_GOVERNANCE_HEALTH_CHECK_CRON,
+        )
+        .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upsert_rebalance_user_feed_history_schedule(&self) -> Result<(), anyhow::Error> {
+        self.upsert_schedule(
+            "qstash/rebalance-user-feed-history",
+            REBALANCE_FEED_HISTORY_SCHEDULE_ID,
+            REBALANCE_FEED_HISTORY_CRON,
+        )
+        .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upsert_creator_profile_view_digest_schedule(&self) -> Result<(), anyhow::Error> {
+        self.upsert_schedule(
+            "qstash/creator-profile-view-digest",
+            PROFILE_VIEW_DIGEST_SCHEDULE_ID,
+            PROFILE_VIEW_DIGEST_CRON,
+        )
+        .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn upsert_ml_cache_consistency_check_schedule(&self) -> Result<(), anyhow::Error> {
+        self.upsert_schedule(
+            "qstash/ml-cache-consistency-check",
+            CONSISTENCY_CHECK_SCHEDULE_ID,
+            CONSISTENCY_CHECK_CRON,
+        )
+        .await
+    }
+
+    #[instrument(skip(self, requests))]
+    pub async fn publish_compute_creator_scores(
+        &self,
+        requests: &[ComputeCreatorScoreRequest],
+    ) -> Result<(), anyhow::Error> {
+        let destination_url = OFF_CHAIN_AGENT_URL
+            .join("qstash/compute-creator-score")?
+            .to_string();
+        let qstash_batch_url = self.base_url.join("batch")?;
+
+        for chunk in requests.chunks(QSTASH_BATCH_LIMIT) {
+            self.client
+                .post(qstash_batch_url.clone())
+                .json(&json_batch_body(&destination_url, chunk))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish_archive_old_events(&self) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL
+            .join("qstash/archive-old-events")
+            .unwrap();
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+
+        let response = self
+            .client
+            .post(url)
+            .header("upstash-method", "POST")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
+
+    /// Publishes a stored job again, `path` is relative to the agent's url
+    #[instrument(skip(self, body))]
+    pub async fn publish_dead_letter(
+        &self,
+        path: &str,
+        body: String,
+    ) -> Result<QStashMessageId, anyhow::Error> {
+        let off_chain_ep = OFF_CHAIN_AGENT_URL.join(path.trim_start_matches('/'))?;
+        let url = self.base_url.join(&format!("publish/{}", off_chain_ep))?;
+
+        let response = self
+            .client
+            .post(url)
+            .body(body)
+            .header(CONTENT_TYPE, "application/json")
+            .header("upstash-method", "POST")
+            .send()
+            .await?;
+
+        message_id(response).await
+    }
 }
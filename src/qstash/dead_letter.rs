@@ -0,0 +1,366 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{OriginalUri, Query, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use http::StatusCode;
+use http_body_util::BodyExt;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    error::NON_RETRYABLE_HEADER,
+    types::RedisPool,
+};
+
+use super::client::QStashClient;
+
+/// Sorted set of failed jobs scored by their failure time in ms
+pub const DEAD_LETTER_KEY: &str = "qstash_dead_letter";
+pub const DEFAULT_REPLAY_LIMIT: usize = 100;
+pub const MAX_REPLAY_LIMIT: usize = 1000;
+/// Entries older than this are dropped whenever a job is dead lettered
+pub const DEAD_LETTER_RETENTION_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+/// Retries QStash makes for jobs published without `Upstash-Retries`
+pub const DEFAULT_JOB_RETRIES: u32 = 3;
+/// Retries of the jobs published with their own `Upstash-Retries`, see `QStashClient`
+pub const JOB_RETRIES: &[(&str, u32)] = &[
+    ("/qstash/upgrade_sns_creator_dao_canister", 0),
+    ("/qstash/upgrade_all_sns_canisters_for_a_user_canister", 0),
+    (
+        "/qstash/upgrade_user_token_sns_canister_for_entire_network",
+        0,
+    ),
+    ("/qstash/token-airdrop", 0),
+    ("/qstash/backup_user_canister", 2),
+    ("/qstash/claim_tokens_admin", 2),
+    ("/qstash/compute-creator-score", 2),
+];
+/// Jobs whose bodies carry delegated identities, they are never stored
+pub const NEVER_DEAD_LETTERED: &[&str] = &["/qstash/claim_tokens"];
+
+fn route_matches(path: &str, route: &str) -> bool {
+    let path = path.split('?').next().unwrap_or_default();
+    path == route
+        || path
+            .strip_prefix(route)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Retries QStash makes for the job at `path`
+pub fn job_retry_budget(path: &str) -> u32 {
+    JOB_RETRIES
+        .iter()
+        .find(|(route, _)| route_matches(path, route))
+        .map(|(_, retries)| *retries)
+        .unwrap_or(DEFAULT_JOB_RETRIES)
+}
+
+/// A failed job is dead lettered once QStash has no retries left for it, `retried` is the
+/// `Upstash-Retried` header of the delivery
+pub fn should_dead_letter(path: &str, retried: u32) -> bool {
+    !NEVER_DEAD_LETTERED
+        .iter()
+        .any(|route| route_matches(path, route))
+        && retried >= job_retry_budget(path)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetterEntry {
+    /// Path and query of the job, e.g. `/qstash/claim_tokens`
+    pub path: String,
+    pub body: String,
+    /// Status the job responded with
+    pub status: u16,
+    pub message_id: Option<String>,
+    pub failed_at_ms: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeadLetterReplayParams {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct DeadLetterReplayResponse {
+    pub replayed: usize,
+    /// Entries that could not be republished, they stay in the set
+    pub failed: usize,
+    pub remaining: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeadLetterCountResponse {
+    pub count: u64,
+}
+
+pub(crate) trait DeadLetterStore {
+    async fn push(&self, entry: &DeadLetterEntry) -> Result<(), anyhow::Error>;
+
+    /// Up to `limit` entries, oldest first, as stored
+    async fn oldest(&self, limit: usize) -> Result<Vec<String>, anyhow::Error>;
+
+    async fn remove(&self, member: &str) -> Result<(), anyhow::Error>;
+
+    async fn count(&self) -> Result<u64, anyhow::Error>;
+}
+
+impl DeadLetterStore for RedisPool {
+    async fn push(&self, entry: &DeadLetterEntry) -> Result<(), anyhow::Error> {
+        let mut conn = self.get().await?;
+        redis::pipe()
+            .cmd("ZADD")
+            .arg(DEAD_LETTER_KEY)
+            .arg(entry.failed_at_ms)
+            .arg(serde_json::to_string(entry)?)
+            .ignore()
+            .cmd("ZREMRANGEBYSCORE")
+            .arg(DEAD_LETTER_KEY)
+            .arg("-inf")
+            .arg(format!(
+                "({}",
+                entry.failed_at_ms - DEAD_LETTER_RETENTION_MS
+            ))
+            .ignore()
+            .query_async::<()>(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn oldest(&self, limit: usize) -> Result<Vec<String>, anyhow::Error> {
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut conn = self.get().await?;
+        Ok(redis::cmd("ZRANGE")
+            .arg(DEAD_LETTER_KEY)
+            .arg(0)
+            .arg(limit - 1)
+            .query_async(&mut *conn)
+            .await?)
+    }
+
+    async fn remove(&self, member: &str) -> Result<(), anyhow::Error> {
+        let mut conn = self.get().await?;
+        redis::cmd("ZREM")
+            .arg(DEAD_LETTER_KEY)
+            .arg(member)
+            .query_async::<()>(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn count(&self) -> Result<u64, anyhow::Error> {
+        let mut conn = self.get().await?;
+        Ok(redis::cmd("ZCARD")
+            .arg(DEAD_LETTER_KEY)
+            .query_async(&mut *conn)
+            .await?)
+    }
+}
+
+pub(crate) trait JobPublisher {
+    async fn republish(&self, entry: &DeadLetterEntry) -> Result<(), anyhow::Error>;
+}
+
+impl JobPublisher for QStashClient {
+    async fn republish(&self, entry: &DeadLetterEntry) -> Result<(), anyhow::Error> {
+        self.publish_dead_letter(&entry.path, entry.body.clone())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Republishes up to `limit` of the oldest failed jobs, removing the ones QStash accepted.
+/// Entries that don't parse are dropped, they can never be replayed.
+pub async fn replay_dead_letters(
+    store: &impl DeadLetterStore,
+    publisher: &impl JobPublisher,
+    limit: usize,
+) -> Result<DeadLetterReplayResponse, anyhow::Error> {
+    let mut res = DeadLetterReplayResponse::default();
+
+    for member in store.oldest(limit).await? {
+        let entry: DeadLetterEntry = match serde_json::from_str(&member) {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!("Dropping invalid dead letter entry {}: {}", member, e);
+                store.remove(&member).await?;
+                continue;
+            }
+        };
+
+        match publisher.republish(&entry).await {
+            Ok(()) => {
+                store.remove(&member).await?;
+                res.replayed += 1;
+            }
+            Err(e) => {
+                log::error!("Failed to replay job {}: {}", entry.path, e);
+                res.failed += 1;
+            }
+        }
+    }
+    res.remaining = store.count().await?;
+
+    Ok(res)
+}
+
+/// Stores verified jobs that failed their last attempt in the dead letter set and
+/// acknowledges them. Earlier attempts keep their status so QStash retries them, and jobs are
+/// only acknowledged once stored.
+pub async fn dead_letter_failed_jobs(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.0.clone())
+        .unwrap_or_else(|| request.uri().clone());
+    let path = path
+        .path_and_query()
+        .map(|path| path.to_string())
+        .unwrap_or_else(|| path.path().to_string());
+    let message_id = request
+        .headers()
+        .get("Upstash-Message-Id")
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_string);
+    let retried = request
+        .headers()
+        .get("Upstash-Retried")
+        .and_then(|retried| retried.to_str().ok())
+        .and_then(|retried| retried.parse().ok())
+        .unwrap_or(0);
+
+    let (parts, body) = request.into_parts();
+    let body = match body.collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let response = next
+        .run(Request::from_parts(parts, Body::from(body.clone())))
+        .await;
+    // QStash won't retry a job that told it not to
+    let retried = if response.headers().contains_key(NON_RETRYABLE_HEADER) {
+        u32::MAX
+    } else {
+        retried
+    };
+    if response.status().is_success() || !should_dead_letter(&path, retried) {
+        return response;
+    }
+
+    let entry = DeadLetterEntry {
+        path,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        status: response.status().as_u16(),
+        message_id,
+        failed_at_ms: chrono::Utc::now().timestamp_millis(),
+    };
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        match state.canister_backup_redis_pool.push(&entry).await {
+            Ok(()) => {
+                log::warn!(
+                    "Job {} failed with status {}, moved to the dead letter set",
+                    entry.path,
+                    entry.status
+                );
+                StatusCode::OK.into_response()
+            }
+            Err(e) => {
+                log::error!("Failed to dead letter job {}: {}", entry.path, e);
+                response
+            }
+        }
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, entry);
+        response
+    }
+}
+
+#[instrument(skip(state, token))]
+pub async fn dead_letter_replay_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Query(params): Query<DeadLetterReplayParams>,
+) -> Result<Json<DeadLetterReplayResponse>, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_REPLAY_LIMIT)
+        .min(MAX_REPLAY_LIMIT);
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let res = replay_dead_letters(
+            &state.canister_backup_redis_pool,
+            &state.qstash_client,
+            limit,
+        )
+        .await
+        .map_err(|e| {
+            log::error!("Failed to replay dead letter jobs: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        log::info!(
+            "Replayed {} dead letter jobs, {} failed, {} remaining",
+            res.replayed,
+            res.failed,
+            res.remaining
+        );
+
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, limit);
+        Ok(Json(DeadLetterReplayResponse::default()))
+    }
+}
+
+#[instrument(skip(state, token))]
+pub async fn dead_letter_count_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<DeadLetterCountResponse>, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let count = state
+            .canister_backup_redis_pool
+            .count()
+            .await
+            .map_err(|e| {
+                log::error!("Failed to count dead letter jobs: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        Ok(Json(DeadLetterCountResponse { count }))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Ok(Json(DeadLetterCountResponse { count: 0 }))
+    }
+}
@@ -0,0 +1,215 @@
+use std::sync::Mutex;
+
+use super::dead_letter::{
+    job_retry_budget, replay_dead_letters, should_dead_letter, DeadLetterEntry,
+    DeadLetterReplayResponse, DeadLetterStore, JobPublisher, DEFAULT_JOB_RETRIES,
+};
+
+/// Members ordered by score like the sorted set
+#[derive(Default)]
+struct MockStore {
+    members: Mutex<Vec<(i64, String)>>,
+}
+
+impl MockStore {
+    fn with(entries: &[DeadLetterEntry]) -> Self {
+        let store = Self::default();
+        for entry in entries {
+            store
+                .members
+                .lock()
+                .unwrap()
+                .push((entry.failed_at_ms, serde_json::to_string(entry).unwrap()));
+        }
+        store
+    }
+
+    fn paths(&self) -> Vec<String> {
+        self.members
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(_, member)| serde_json::from_str::<DeadLetterEntry>(member).ok())
+            .map(|entry| entry.path)
+            .collect()
+    }
+}
+
+impl DeadLetterStore for MockStore {
+    async fn push(&self, entry: &DeadLetterEntry) -> Result<(), anyhow::Error> {
+        let mut members = self.members.lock().unwrap();
+        members.push((entry.failed_at_ms, serde_json::to_string(entry)?));
+        members.sort();
+        Ok(())
+    }
+
+    async fn oldest(&self, limit: usize) -> Result<Vec<String>, anyhow::Error> {
+        let mut members = self.members.lock().unwrap().clone();
+        members.sort();
+        Ok(members
+            .into_iter()
+            .take(limit)
+            .map(|(_, member)| member)
+            .collect())
+    }
+
+    async fn remove(&self, member: &str) -> Result<(), anyhow::Error> {
+        self.members
+            .lock()
+            .unwrap()
+            .retain(|(_, existing)| existing != member);
+        Ok(())
+    }
+
+    async fn count(&self) -> Result<u64, anyhow::Error> {
+        Ok(self.members.lock().unwrap().len() as u64)
+    }
+}
+
+/// Records republished jobs, rejects the `failing` paths
+#[derive(Default)]
+struct MockPublisher {
+    failing: Vec<&'static str>,
+    published: Mutex<Vec<(String, String)>>,
+}
+
+impl JobPublisher for MockPublisher {
+    async fn republish(&self, entry: &DeadLetterEntry) -> Result<(), anyhow::Error> {
+        if self.failing.contains(&entry.path.as_str()) {
+            return Err(anyhow::anyhow!("qstash unavailable"));
+        }
+        self.published
+            .lock()
+            .unwrap()
+            .push((entry.path.clone(), entry.body.clone()));
+        Ok(())
+    }
+}
+
+fn entry(path: &str, failed_at_ms: i64) -> DeadLetterEntry {
+    DeadLetterEntry {
+        path: path.to_string(),
+        body: format!(r#"{{"failed_at":{}}}"#, failed_at_ms),
+        status: 500,
+        message_id: Some(format!("msg_{}", failed_at_ms)),
+        failed_at_ms,
+    }
+}
+
+#[tokio::test]
+async fn test_replay_republishes_the_oldest_entries() {
+    let store = MockStore::with(&[
+        entry("/qstash/storj_ingest", 3),
+        entry("/qstash/report_post", 1),
+        entry("/qstash/upload_video_gcs", 2),
+    ]);
+    let publisher = MockPublisher::default();
+
+    let res = replay_dead_letters(&store, &publisher, 2).await.unwrap();
+
+    assert_eq!(
+        res,
+        DeadLetterReplayResponse {
+            replayed: 2,
+            failed: 0,
+            remaining: 1,
+        }
+    );
+    assert_eq!(
+        *publisher.published.lock().unwrap(),
+        vec![
+            (
+                "/qstash/report_post".to_string(),
+                r#"{"failed_at":1}"#.to_string()
+            ),
+            (
+                "/qstash/upload_video_gcs".to_string(),
+                r#"{"failed_at":2}"#.to_string()
+            ),
+        ]
+    );
+    assert_eq!(store.paths(), vec!["/qstash/storj_ingest"]);
+}
+
+#[tokio::test]
+async fn test_failed_republish_stays_in_the_set() {
+    let store = MockStore::with(&[
+        entry("/qstash/report_post", 1),
+        entry("/qstash/storj_ingest", 2),
+    ]);
+    let publisher = MockPublisher {
+        failing: vec!["/qstash/report_post"],
+        ..Default::default()
+    };
+
+    let res = replay_dead_letters(&store, &publisher, 100).await.unwrap();
+
+    assert_eq!(res.replayed, 1);
+    assert_eq!(res.failed, 1);
+    assert_eq!(res.remaining, 1);
+    assert_eq!(store.paths(), vec!["/qstash/report_post"]);
+}
+
+#[tokio::test]
+async fn test_invalid_entries_are_dropped() {
+    let store = MockStore::default();
+    store
+        .members
+        .lock()
+        .unwrap()
+        .push((1, "not json".to_string()));
+    store.push(&entry("/qstash/report_post", 2)).await.unwrap();
+
+    let res = replay_dead_letters(&store, &MockPublisher::default(), 100)
+        .await
+        .unwrap();
+
+    assert_eq!(res.replayed, 1);
+    assert_eq!(res.failed, 0);
+    assert_eq!(res.remaining, 0);
+}
+
+#[tokio::test]
+async fn test_replay_of_an_empty_set() {
+    let res = replay_dead_letters(&MockStore::default(), &MockPublisher::default(), 100)
+        .await
+        .unwrap();
+
+    assert_eq!(res, DeadLetterReplayResponse::default());
+}
+
+#[test]
+fn test_job_retry_budget() {
+    assert_eq!(job_retry_budget("/qstash/report_post"), DEFAULT_JOB_RETRIES);
+    assert_eq!(job_retry_budget("/qstash/token-airdrop"), 0);
+    assert_eq!(
+        job_retry_budget("/qstash/upgrade_all_sns_canisters_for_a_user_canister/2vxsx-fae"),
+        0
+    );
+    assert_eq!(job_retry_budget("/qstash/compute-creator-score"), 2);
+    assert_eq!(
+        job_retry_budget("/qstash/compute-creator-scores"),
+        DEFAULT_JOB_RETRIES
+    );
+    assert_eq!(job_retry_budget("/qstash/backup_user_canister?x=1"), 2);
+}
+
+#[test]
+fn test_only_the_last_attempt_is_dead_lettered() {
+    assert!(!should_dead_letter("/qstash/report_post", 0));
+    assert!(!should_dead_letter(
+        "/qstash/report_post",
+        DEFAULT_JOB_RETRIES - 1
+    ));
+    assert!(should_dead_letter(
+        "/qstash/report_post",
+        DEFAULT_JOB_RETRIES
+    ));
+    assert!(should_dead_letter("/qstash/token-airdrop", 0));
+}
+
+#[test]
+fn test_jobs_carrying_identities_are_never_dead_lettered() {
+    assert!(!should_dead_letter("/qstash/claim_tokens", 10));
+    assert!(should_dead_letter("/qstash/claim_tokens_admin", 2));
+}
@@ -1,14 +1,41 @@
-use std::time::SystemTime;
-
 use crate::{
-    app_state, async_dedup_index, consts::OFF_CHAIN_AGENT_URL,
-    duplicate_video::videohash::VideoHash,
+    app_state, async_dedup_index,
+    consts::OFF_CHAIN_AGENT_URL,
+    duplicate_video::{redis_hash_index::RedisBackedVideoHashIndex, videohash::VideoHash},
+    events::nsfw_cache::{get_cached_nsfw_result, set_video_content_digest},
+    posts::engagement::named_parameter,
+    types::RedisPool,
 };
-use anyhow::{anyhow, Context};
-use google_cloud_bigquery::http::job::query::QueryRequest;
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
 use http::header::CONTENT_TYPE;
 use serde::{Deserialize, Serialize};
 
+pub const VIDEOHASH_ORIGINAL_TABLE: &str =
+    "hot-or-not-feed-intelligence.yral_ds.videohash_original";
+pub const VIDEO_UNIQUE_TABLE: &str = "hot-or-not-feed-intelligence.yral_ds.video_unique";
+
+pub const ORIGINAL_VIDEOHASH_QUERY: &str = "SELECT videohash
+    FROM `hot-or-not-feed-intelligence.yral_ds.videohash_original`
+    WHERE video_id = @video_id
+    LIMIT 1";
+
+/// Column of `duplicate_videos` holding the id of the duplicate upload, despite its name. The
+/// video it duplicates is in `parent_video_id`.
+pub const DUPLICATE_VIDEO_ID_COLUMN: &str = "original_video_id";
+
+pub const DUPLICATE_VIDEO_INSERT: &str =
+    "INSERT INTO `hot-or-not-feed-intelligence.yral_ds.duplicate_videos` (
+        publisher_canister_id, publisher_principal, post_id,
+        original_video_id, parent_video_id, parent_canister_id,
+        parent_principal, parent_post_id, exact_duplicate,
+        duplication_score
+    ) VALUES (
+        @publisher_canister_id, @publisher_principal, @post_id,
+        @video_id, @parent_video_id, NULL,
+        NULL, NULL, @exact_duplicate,
+        @duplication_score
+    )";
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct VideoPublisherData {
     pub canister_id: String,
@@ -121,8 +148,10 @@ impl<'a> VideoHashDuplication<'a> {
 
     pub async fn process_video_deduplication(
         &self,
-        dedup_index_ctx: &async_dedup_index::WrappedContext,
+        dedup_index_ctx: &async_dedup_index::AsyncDedupIndex,
         bigquery_client: &google_cloud_bigquery::client::Client,
+        redis_pool: &RedisPool,
+        hash_index: &RedisBackedVideoHashIndex,
         video_id: &str,
         video_url: &str,
         publisher_data: VideoPublisherData,
@@ -132,14 +161,42 @@ impl<'a> VideoHashDuplication<'a> {
             u64,
             String,
             &str,
+            Option<f32>,
         )
             -> futures::future::BoxFuture<'a, Result<(), anyhow::Error>>,
     ) -> Result<(), anyhow::Error> {
         log::info!("Calculating videohash for video URL: {}", video_url);
-        let video_hash = VideoHash::from_url(video_url)
+        let (video_hash, details) = VideoHash::from_url_with_details(video_url)
             .await
             .map_err(|e| anyhow::anyhow!("Failed to generate videohash: {}", e))?;
 
+        // Link the video to its exact content so NSFW detection can reuse results of identical
+        // uploads
+        let content_digest = &details.content_digest;
+        if let Err(e) = set_video_content_digest(redis_pool, video_id, content_digest).await {
+            log::warn!(
+                "Failed to store content digest in redis for {}: {}",
+                video_id,
+                e
+            );
+        } else if let Ok(Some(_)) = get_cached_nsfw_result(redis_pool, content_digest).await {
+            log::info!(
+                "NSFW result already cached for video_id [{}] (content {})",
+                video_id,
+                content_digest
+            );
+        }
+
+        // Compared against the indexer's answer below until stdb replaces it
+        match dedup_index_ctx.is_duplicate(&video_hash.hash).await {
+            Ok(indexed) => log::info!(
+                "stdb dedup index has hash of video_id [{}] already: {}",
+                video_id,
+                indexed
+            ),
+            Err(err) => log::warn!("error while checking stdb dedup index: {err:#?}"),
+        }
+
         // Store the original hash regardless of duplication status
         let res = self
             .store_videohash_to_spacetime(dedup_index_ctx, video_id, &video_hash.hash)
@@ -151,6 +208,13 @@ impl<'a> VideoHashDuplication<'a> {
         self.store_videohash_original(bigquery_client, video_id, &video_hash.hash)
             .await?;
 
+        // Shared through redis so every replica finds the upload in its similarity index
+        match hash_index.add_video_hash(video_id, &video_hash).await {
+            Ok(true) => {}
+            Ok(false) => log::warn!("Video hash of [{}] can't be indexed", video_id),
+            Err(e) => log::warn!("Failed to share video hash of [{}]: {}", video_id, e),
+        }
+
         // TODO: the following call will be replaced with spacetimedb in
         // https://github.com/dolr-ai/product-roadmap/issues/569
 
@@ -187,22 +251,37 @@ impl<'a> VideoHashDuplication<'a> {
         if is_duplicate {
             // A similar video was found - record as duplicate
             if let Some(match_details) = indexer_response.match_details {
-                self.store_duplicate_video(
-                    video_id,
-                    &video_hash.hash,
-                    &match_details,
-                    &publisher_data,
-                )
-                .await?;
+                // an identical hash means the parent video was uploaded again, it is flagged in
+                // its duplicate row and published like any other duplicate
+                let identical = match_details.video_id != video_id
+                    && match self
+                        .get_original_videohash(bigquery_client, &match_details.video_id)
+                        .await
+                    {
+                        Ok(Some(parent_hash)) => video_hash.is_exact_duplicate(&parent_hash),
+                        Ok(None) => false,
+                        Err(e) => {
+                            log::warn!(
+                                "Failed to read videohash of parent video [{}]: {}",
+                                match_details.video_id,
+                                e
+                            );
+                            false
+                        }
+                    };
+
+                self.store_duplicate_video(video_id, &match_details, &publisher_data, identical)
+                    .await?;
 
                 log::info!(
-                    "Duplicate video detected: video_id [{}] is similar to parent_video_id [{}] (score: {})",
+                    "Duplicate video detected: video_id [{}] is similar to parent_video_id [{}] (score: {}, identical: {})",
                     video_id,
                     match_details.video_id,
-                    match_details.similarity_percentage
+                    match_details.similarity_percentage,
+                    identical
                 );
 
-                let exact_duplicate = match_details.similarity_percentage > 98.0;
+                let exact_duplicate = identical || match_details.similarity_percentage > 98.0;
                 let _duplicate_event = DuplicateVideoEvent {
                     original_video_id: video_id.to_string(),
                     parent_video_id: match_details.video_id.clone(),
@@ -227,6 +306,7 @@ impl<'a> VideoHashDuplication<'a> {
             publisher_data.post_id,
             timestamp,
             &publisher_data.publisher_principal,
+            details.duration_secs,
         )
         .await?;
 
@@ -239,17 +319,7 @@ impl<'a> VideoHashDuplication<'a> {
         video_id: &str,
         hash: &str,
     ) -> Result<(), anyhow::Error> {
-        let query = format!(
-            "INSERT INTO `hot-or-not-feed-intelligence.yral_ds.videohash_original` 
-             (video_id, videohash, created_at) 
-             VALUES ('{}', '{}', CURRENT_TIMESTAMP())",
-            video_id, hash
-        );
-
-        let request = QueryRequest {
-            query,
-            ..Default::default()
-        };
+        let request = videohash_insert_request(VIDEOHASH_ORIGINAL_TABLE, video_id, hash);
 
         log::info!(
             "Storing hash in videohash_original for video_id [{}]",
@@ -264,35 +334,39 @@ impl<'a> VideoHashDuplication<'a> {
         Ok(())
     }
 
-    async fn store_videohash_to_spacetime(
+    async fn get_original_videohash(
+        &self,
+        bigquery_client: &google_cloud_bigquery::client::Client,
+        video_id: &str,
+    ) -> Result<Option<VideoHash>, anyhow::Error> {
+        let mut response = bigquery_client
+            .query::<QueryRow>(
+                "hot-or-not-feed-intelligence",
+                original_videohash_request(video_id),
+            )
+            .await?;
+
+        let Some(row) = response.next().await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(VideoHash {
+            hash: row.column::<String>(0)?,
+        }))
+    }
+
+    pub(crate) async fn store_videohash_to_spacetime(
         &self,
-        ctx: &async_dedup_index::WrappedContext,
+        ctx: &async_dedup_index::AsyncDedupIndex,
         video_id: &str,
         hash: &str,
     ) -> anyhow::Result<()> {
-        ctx.add(video_id, hash, SystemTime::now())
-            .await
-            .context("Couldn't send request to stdb")?
-            .map_err(|err| anyhow!("{err}"))
-            .context("Module returned error")?;
-
-        Ok(())
+        ctx.add_hash(hash, video_id).await
     }
 
     async fn store_unique_video(&self, video_id: &str, hash: &str) -> Result<(), anyhow::Error> {
         let bigquery_client = app_state::init_bigquery_client().await;
-
-        let query = format!(
-            "INSERT INTO `hot-or-not-feed-intelligence.yral_ds.video_unique` 
-             (video_id, videohash, created_at) 
-             VALUES ('{}', '{}', CURRENT_TIMESTAMP())",
-            video_id, hash
-        );
-
-        let request = QueryRequest {
-            query,
-            ..Default::default()
-        };
+        let request = videohash_insert_request(VIDEO_UNIQUE_TABLE, video_id, hash);
 
         log::info!(
             "Storing unique video in video_unique for video_id [{}]",
@@ -310,38 +384,20 @@ impl<'a> VideoHashDuplication<'a> {
     async fn store_duplicate_video(
         &self,
         video_id: &str,
-        _hash: &str,
         match_details: &MatchDetails,
         publisher_data: &VideoPublisherData,
+        identical: bool,
     ) -> Result<(), anyhow::Error> {
         let bigquery_client = app_state::init_bigquery_client().await;
-        let exact_duplicate = match_details.similarity_percentage > 99.0;
-        let query = format!(
-            "INSERT INTO `hot-or-not-feed-intelligence.yral_ds.duplicate_videos` (
-                publisher_canister_id, publisher_principal, post_id,
-                original_video_id, parent_video_id, parent_canister_id,
-                parent_principal, parent_post_id, exact_duplicate,
-                duplication_score
-            ) VALUES (
-                '{}', '{}', {},
-                '{}', '{}', NULL,
-                NULL, NULL, {},
-                {}
-            )",
-            publisher_data.canister_id,
-            publisher_data.publisher_principal,
-            publisher_data.post_id,
+        let exact_duplicate = identical || match_details.similarity_percentage > 99.0;
+        let request = duplicate_video_request(
+            publisher_data,
             video_id,
-            match_details.video_id,
+            &match_details.video_id,
             exact_duplicate,
-            match_details.similarity_percentage
+            match_details.similarity_percentage,
         );
 
-        let request = QueryRequest {
-            query,
-            ..Default::default()
-        };
-
         log::info!(
             "Storing duplicate video in duplicate_video: video_id [{}], parent_video_id [{}], score={}",
             video_id,
@@ -357,3 +413,64 @@ impl<'a> VideoHashDuplication<'a> {
         Ok(())
     }
 }
+
+/// Row of `videohash_original` or `video_unique`, the values are passed as named parameters
+pub fn videohash_insert_request(table: &str, video_id: &str, hash: &str) -> QueryRequest {
+    QueryRequest {
+        query: format!(
+            "INSERT INTO `{}` (video_id, videohash, created_at)
+             VALUES (@video_id, @videohash, CURRENT_TIMESTAMP())",
+            table
+        ),
+        parameter_mode: Some("NAMED".to_string()),
+        query_parameters: vec![
+            named_parameter("video_id", "STRING", video_id.to_string()),
+            named_parameter("videohash", "STRING", hash.to_string()),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn original_videohash_request(video_id: &str) -> QueryRequest {
+    QueryRequest {
+        query: ORIGINAL_VIDEOHASH_QUERY.to_string(),
+        parameter_mode: Some("NAMED".to_string()),
+        query_parameters: vec![named_parameter("video_id", "STRING", video_id.to_string())],
+        ..Default::default()
+    }
+}
+
+pub fn duplicate_video_request(
+    publisher_data: &VideoPublisherData,
+    video_id: &str,
+    parent_video_id: &str,
+    exact_duplicate: bool,
+    duplication_score: f64,
+) -> QueryRequest {
+    QueryRequest {
+        query: DUPLICATE_VIDEO_INSERT.to_string(),
+        parameter_mode: Some("NAMED".to_string()),
+        query_parameters: vec![
+            named_parameter(
+                "publisher_canister_id",
+                "STRING",
+                publisher_data.canister_id.clone(),
+            ),
+            named_parameter(
+                "publisher_principal",
+                "STRING",
+                publisher_data.publisher_principal.clone(),
+            ),
+            named_parameter("post_id", "INT64", publisher_data.post_id.to_string()),
+            named_parameter("video_id", "STRING", video_id.to_string()),
+            named_parameter("parent_video_id", "STRING", parent_video_id.to_string()),
+            named_parameter("exact_duplicate", "BOOL", exact_duplicate.to_string()),
+            named_parameter(
+                "duplication_score",
+                "FLOAT64",
+                duplication_score.to_string(),
+            ),
+        ],
+        ..Default::default()
+    }
+}
@@ -0,0 +1,97 @@
+use google_cloud_bigquery::http::job::query::QueryRequest;
+
+use super::duplicate::{
+    duplicate_video_request, original_videohash_request, videohash_insert_request,
+    VideoPublisherData, DUPLICATE_VIDEO_ID_COLUMN, DUPLICATE_VIDEO_INSERT, VIDEO_UNIQUE_TABLE,
+};
+
+/// Quote of a caller controlled id, it must never reach the query text
+const VIDEO_ID: &str = "v1' OR '1'='1";
+
+fn params(request: &QueryRequest) -> Vec<(&str, &str, &str)> {
+    request
+        .query_parameters
+        .iter()
+        .map(|p| {
+            (
+                p.name.as_deref().unwrap(),
+                p.parameter_type.parameter_type.as_str(),
+                p.parameter_value.value.as_deref().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_original_videohash_request_uses_named_parameters() {
+    let request = original_videohash_request(VIDEO_ID);
+
+    assert_eq!(request.parameter_mode.as_deref(), Some("NAMED"));
+    assert!(request.query.contains("video_id = @video_id"));
+    assert!(!request.query.contains(VIDEO_ID));
+    assert_eq!(params(&request), vec![("video_id", "STRING", VIDEO_ID)]);
+}
+
+#[test]
+fn test_videohash_insert_request_uses_named_parameters() {
+    let request = videohash_insert_request(VIDEO_UNIQUE_TABLE, VIDEO_ID, "0101");
+
+    assert!(request.query.contains(VIDEO_UNIQUE_TABLE));
+    assert!(!request.query.contains(VIDEO_ID));
+    assert_eq!(
+        params(&request),
+        vec![
+            ("video_id", "STRING", VIDEO_ID),
+            ("videohash", "STRING", "0101")
+        ]
+    );
+}
+
+#[test]
+fn test_duplicate_row_flags_exact_duplicates() {
+    let publisher = VideoPublisherData {
+        canister_id: "canister".to_string(),
+        publisher_principal: "principal".to_string(),
+        post_id: 7,
+    };
+
+    let request = duplicate_video_request(&publisher, VIDEO_ID, "parent", true, 100.0);
+
+    assert!(!request.query.contains(VIDEO_ID));
+    assert_eq!(
+        params(&request),
+        vec![
+            ("publisher_canister_id", "STRING", "canister"),
+            ("publisher_principal", "STRING", "principal"),
+            ("post_id", "INT64", "7"),
+            ("video_id", "STRING", VIDEO_ID),
+            ("parent_video_id", "STRING", "parent"),
+            ("exact_duplicate", "BOOL", "true"),
+            ("duplication_score", "FLOAT64", "100"),
+        ]
+    );
+}
+
+#[test]
+fn test_duplicate_id_column_holds_the_uploaded_video() {
+    let (columns, values) = DUPLICATE_VIDEO_INSERT
+        .split_once(") VALUES (")
+        .expect("insert with a column and a value list");
+    let columns: Vec<&str> = columns
+        .rsplit_once('(')
+        .unwrap()
+        .1
+        .split(',')
+        .map(str::trim)
+        .collect();
+    let values: Vec<&str> = values
+        .trim_end()
+        .trim_end_matches(')')
+        .split(',')
+        .map(str::trim)
+        .collect();
+
+    let position = |column: &str| columns.iter().position(|c| *c == column).unwrap();
+    assert_eq!(values[position(DUPLICATE_VIDEO_ID_COLUMN)], "@video_id");
+    assert_eq!(values[position("parent_video_id")], "@parent_video_id");
+}
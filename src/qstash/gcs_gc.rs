@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use cloud_storage::ListRequest;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{app_state::AppState, AppError};
+
+pub const VIDEOS_BUCKET: &str = "yral-videos";
+pub const VIDEO_FRAMES_BUCKET: &str = "yral-video-frames";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GcOrphanedGcsObjectsRequest {
+    pub video_id: String,
+}
+
+#[derive(Debug, Serialize, Default, PartialEq)]
+pub struct GcOrphanedGcsObjectsResponse {
+    pub video_deleted: bool,
+    pub frames_deleted: usize,
+    pub frames_failed: usize,
+}
+
+/// GCS object operations of the cleanup
+pub(crate) trait GcsObjects {
+    async fn delete_object(&self, bucket: &str, name: &str) -> Result<(), anyhow::Error>;
+
+    /// Names of every object of the bucket under `prefix`
+    async fn list_object_names(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<String>, anyhow::Error>;
+}
+
+impl GcsObjects for cloud_storage::Client {
+    async fn delete_object(&self, bucket: &str, name: &str) -> Result<(), anyhow::Error> {
+        self.object().delete(bucket, name).await?;
+        Ok(())
+    }
+
+    async fn list_object_names(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let list_request = ListRequest {
+            prefix: Some(prefix.to_string()),
+            ..Default::default()
+        };
+        let mut pages = Box::pin(self.object().list(bucket, list_request).await?);
+
+        let mut names = Vec::new();
+        while let Some(page) = pages.next().await {
+            names.extend(page?.items.into_iter().map(|object| object.name));
+        }
+
+        Ok(names)
+    }
+}
+
+/// Removes the GCS video object and extracted frames of a deleted post
+#[instrument(skip(state))]
+pub async fn gc_orphaned_gcs_objects(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<GcOrphanedGcsObjectsRequest>,
+) -> Result<Json<GcOrphanedGcsObjectsResponse>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let res = gc_orphaned_gcs_objects_impl(state.gcs_client.as_ref(), &req.video_id).await?;
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        log::info!("Skipping GCS cleanup for video {} on local", req.video_id);
+        Ok(Json(GcOrphanedGcsObjectsResponse::default()))
+    }
+}
+
+pub async fn gc_orphaned_gcs_objects_impl(
+    gcs: &impl GcsObjects,
+    video_id: &str,
+) -> Result<GcOrphanedGcsObjectsResponse, anyhow::Error> {
+    let mut res = GcOrphanedGcsObjectsResponse::default();
+
+    let video_object = video_object_name(video_id);
+    match gcs.delete_object(VIDEOS_BUCKET, &video_object).await {
+        Ok(()) => res.video_deleted = true,
+        Err(e) => log::warn!("Failed to delete gs://{VIDEOS_BUCKET}/{video_object}: {e}"),
+    }
+
+    let frames = gcs
+        .list_object_names(VIDEO_FRAMES_BUCKET, &frames_prefix(video_id))
+        .await?;
+    for name in frames {
+        match gcs.delete_object(VIDEO_FRAMES_BUCKET, &name).await {
+            Ok(()) => res.frames_deleted += 1,
+            Err(e) => {
+                res.frames_failed += 1;
+                log::warn!("Failed to delete gs://{VIDEO_FRAMES_BUCKET}/{name}: {e}");
+            }
+        }
+    }
+
+    log::info!(
+        "GCS cleanup for video {video_id}: video_deleted={}, frames_deleted={}, frames_failed={}",
+        res.video_deleted,
+        res.frames_deleted,
+        res.frames_failed
+    );
+
+    Ok(res)
+}
+
+pub fn video_object_name(video_id: &str) -> String {
+    format!("{video_id}.mp4")
+}
+
+pub fn frames_prefix(video_id: &str) -> String {
+    format!("{video_id}/")
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::Mutex,
+};
+
+use super::gcs_gc::{
+    frames_prefix, gc_orphaned_gcs_objects_impl, video_object_name, GcOrphanedGcsObjectsResponse,
+    GcsObjects, VIDEOS_BUCKET, VIDEO_FRAMES_BUCKET,
+};
+
+/// Buckets holding `(bucket, name)` objects
+#[derive(Default)]
+struct MockGcs {
+    objects: Mutex<BTreeSet<(String, String)>>,
+    undeletable: HashSet<String>,
+    fail_list: bool,
+}
+
+impl MockGcs {
+    fn with_objects(objects: &[(&str, &str)]) -> Self {
+        Self {
+            objects: Mutex::new(
+                objects
+                    .iter()
+                    .map(|(bucket, name)| (bucket.to_string(), name.to_string()))
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn remaining(&self) -> Vec<(String, String)> {
+        self.objects.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl GcsObjects for MockGcs {
+    async fn delete_object(&self, bucket: &str, name: &str) -> Result<(), anyhow::Error> {
+        if self.undeletable.contains(name) {
+            return Err(anyhow::anyhow!("permission denied"));
+        }
+        if !self
+            .objects
+            .lock()
+            .unwrap()
+            .remove(&(bucket.to_string(), name.to_string()))
+        {
+            return Err(anyhow::anyhow!("not found"));
+        }
+        Ok(())
+    }
+
+    async fn list_object_names(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        if self.fail_list {
+            return Err(anyhow::anyhow!("backend error"));
+        }
+        Ok(self
+            .objects
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(b, name)| b == bucket && name.starts_with(prefix))
+            .map(|(_, name)| name.clone())
+            .collect())
+    }
+}
+
+#[test]
+fn test_object_names() {
+    assert_eq!(video_object_name("abc"), "abc.mp4");
+    assert_eq!(frames_prefix("abc"), "abc/");
+}
+
+#[tokio::test]
+async fn test_deletes_video_and_its_frames_only() {
+    let gcs = MockGcs::with_objects(&[
+        (VIDEOS_BUCKET, "abc.mp4"),
+        (VIDEOS_BUCKET, "other.mp4"),
+        (VIDEO_FRAMES_BUCKET, "abc/frame-0.jpg"),
+        (VIDEO_FRAMES_BUCKET, "abc/frame-1.jpg"),
+        (VIDEO_FRAMES_BUCKET, "abcd/frame-0.jpg"),
+    ]);
+
+    let res = gc_orphaned_gcs_objects_impl(&gcs, "abc").await.unwrap();
+
+    assert_eq!(
+        res,
+        GcOrphanedGcsObjectsResponse {
+            video_deleted: true,
+            frames_deleted: 2,
+            frames_failed: 0,
+        }
+    );
+    assert_eq!(
+        gcs.remaining(),
+        vec![
+            (
+                VIDEO_FRAMES_BUCKET.to_string(),
+                "abcd/frame-0.jpg".to_string()
+            ),
+            (VIDEOS_BUCKET.to_string(), "other.mp4".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_missing_video_still_deletes_frames() {
+    let gcs = MockGcs::with_objects(&[(VIDEO_FRAMES_BUCKET, "abc/frame-0.jpg")]);
+
+    let res = gc_orphaned_gcs_objects_impl(&gcs, "abc").await.unwrap();
+
+    assert!(!res.video_deleted);
+    assert_eq!(res.frames_deleted, 1);
+    assert!(gcs.remaining().is_empty());
+}
+
+#[tokio::test]
+async fn test_failed_frame_deletes_are_counted() {
+    let mut gcs = MockGcs::with_objects(&[
+        (VIDEOS_BUCKET, "abc.mp4"),
+        (VIDEO_FRAMES_BUCKET, "abc/frame-0.jpg"),
+        (VIDEO_FRAMES_BUCKET, "abc/frame-1.jpg"),
+    ]);
+    gcs.undeletable.insert("abc/frame-1.jpg".to_string());
+
+    let res = gc_orphaned_gcs_objects_impl(&gcs, "abc").await.unwrap();
+
+    assert_eq!(res.frames_deleted, 1);
+    assert_eq!(res.frames_failed, 1);
+}
+
+#[tokio::test]
+async fn test_failed_listing_is_an_error() {
+    let gcs = MockGcs {
+        fail_list: true,
+        ..MockGcs::with_objects(&[(VIDEOS_BUCKET, "abc.mp4")])
+    };
+
+    assert!(gc_orphaned_gcs_objects_impl(&gcs, "abc").await.is_err());
+}
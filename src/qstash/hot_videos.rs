@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use futures::StreamExt;
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::{app_state::AppState, utils::cf_stream::stream_manifest_url, AppError};
+
+pub const HOT_VIDEOS_LIMIT: u32 = 20;
+pub const HOT_VIDEOS_WINDOW_HOURS: u32 = 2;
+pub const REFRESH_HOT_VIDEOS_CRON: &str = "*/15 * * * *";
+pub const REFRESH_HOT_VIDEOS_SCHEDULE_ID: &str = "refresh-hot-videos";
+const WARM_CONCURRENCY: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    Expired,
+    Other,
+}
+
+impl CacheStatus {
+    /// Parses the `CF-Cache-Status` header, a missing header counts as a miss
+    pub fn from_header(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_ascii_uppercase()).as_deref() {
+            Some("HIT") | Some("REVALIDATED") | Some("UPDATING") => Self::Hit,
+            Some("MISS") | None => Self::Miss,
+            Some("EXPIRED") | Some("STALE") => Self::Expired,
+            Some(_) => Self::Other,
+        }
+    }
+
+    /// The edge didn't have a fresh copy, the request just cached it
+    pub fn is_miss(self) -> bool {
+        matches!(self, Self::Miss | Self::Expired)
+    }
+}
+
+/// Edge cache requests used to warm the hot videos
+pub(crate) trait CdnCache {
+    /// `HEAD`s the url, returning its `CF-Cache-Status` header
+    async fn head(&self, url: &str) -> Result<Option<String>, anyhow::Error>;
+}
+
+#[derive(Default)]
+pub struct CloudflareCdnCache {
+    client: reqwest::Client,
+}
+
+impl CdnCache for CloudflareCdnCache {
+    async fn head(&self, url: &str) -> Result<Option<String>, anyhow::Error> {
+        let response = self.client.head(url).send().await?.error_for_status()?;
+
+        Ok(response
+            .headers()
+            .get("CF-Cache-Status")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string()))
+    }
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct RefreshHotVideosResponse {
+    pub hits: usize,
+    /// Videos the edge didn't have, cached by this refresh
+    pub misses: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+pub fn hot_videos_query() -> String {
+    format!(
+        "SELECT JSON_EXTRACT_SCALAR(params, '$.video_id') AS video_id, COUNT(*) AS views
+        FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+        WHERE event = 'video_viewed'
+            AND timestamp >= TIMESTAMP_SUB(CURRENT_TIMESTAMP(), INTERVAL {} HOUR)
+            AND JSON_EXTRACT_SCALAR(params, '$.video_id') IS NOT NULL
+        GROUP BY video_id
+        ORDER BY views DESC
+        LIMIT {}",
+        HOT_VIDEOS_WINDOW_HOURS, HOT_VIDEOS_LIMIT
+    )
+}
+
+/// Requests every video's stream once so the edge caches it
+pub(crate) async fn warm_videos<C: CdnCache>(
+    cdn: &C,
+    video_ids: Vec<String>,
+) -> RefreshHotVideosResponse {
+    let results = futures::stream::iter(video_ids)
+        .map(|video_id| async move {
+            let status = cdn.head(&stream_manifest_url(&video_id)).await;
+            (video_id, status)
+        })
+        .buffer_unordered(WARM_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut res = RefreshHotVideosResponse::default();
+    for (video_id, status) in results {
+        match status {
+            Ok(header) => {
+                let status = CacheStatus::from_header(header.as_deref());
+                log::info!("Warmed hot video {}: {:?}", video_id, status);
+                if status.is_miss() {
+                    res.misses.push(video_id);
+                } else {
+                    res.hits += 1;
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to warm hot video {}: {}", video_id, e);
+                res.failed.push(video_id);
+            }
+        }
+    }
+
+    res
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn fetch_hot_videos(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+) -> Result<Vec<String>, anyhow::Error> {
+    let request = QueryRequest {
+        query: hot_videos_query(),
+        ..Default::default()
+    };
+    let mut response = bigquery_client
+        .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+        .await?;
+
+    let mut video_ids = Vec::new();
+    while let Some(row) = response.next().await? {
+        video_ids.push(row.column::<String>(0)?);
+    }
+
+    Ok(video_ids)
+}
+
+/// Pre-warms the CDN for the most viewed videos of the last hours, scheduled every 15 minutes
+#[instrument(skip(state))]
+pub async fn refresh_hot_videos(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<RefreshHotVideosResponse>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let video_ids = fetch_hot_videos(&state.bigquery_client)
+            .await
+            .map_err(AppError::BigQueryError)?;
+        let cdn = CloudflareCdnCache::default();
+
+        let res = warm_videos(&cdn, video_ids).await;
+        log::info!(
+            "Refreshed hot videos: {} hits, {} misses, {} failed",
+            res.hits,
+            res.misses.len(),
+            res.failed.len()
+        );
+
+        Ok(Json(res))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = state;
+        Ok(Json(RefreshHotVideosResponse::default()))
+    }
+}
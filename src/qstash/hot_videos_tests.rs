@@ -0,0 +1,103 @@
+use std::sync::Mutex;
+
+use super::hot_videos::{hot_videos_query, warm_videos, CacheStatus, CdnCache};
+
+/// Cloudflare stand-in answering `HEAD`s from a fixed list of cache statuses
+#[derive(Default)]
+struct MockCdn {
+    statuses: Vec<(&'static str, Result<Option<&'static str>, ()>)>,
+    requested: Mutex<Vec<String>>,
+}
+
+impl CdnCache for MockCdn {
+    async fn head(&self, url: &str) -> Result<Option<String>, anyhow::Error> {
+        self.requested.lock().unwrap().push(url.to_string());
+        let (_, status) = self
+            .statuses
+            .iter()
+            .find(|(video_id, _)| url.contains(video_id))
+            .expect("unexpected url");
+
+        (*status)
+            .map(|header| header.map(|h| h.to_string()))
+            .map_err(|_| anyhow::anyhow!("connection reset"))
+    }
+}
+
+fn video_ids(cdn: &MockCdn) -> Vec<String> {
+    cdn.statuses.iter().map(|(id, _)| id.to_string()).collect()
+}
+
+#[test]
+fn test_cache_status_from_header() {
+    assert_eq!(CacheStatus::from_header(Some("HIT")), CacheStatus::Hit);
+    assert_eq!(CacheStatus::from_header(Some("hit")), CacheStatus::Hit);
+    assert_eq!(CacheStatus::from_header(Some("MISS")), CacheStatus::Miss);
+    assert_eq!(CacheStatus::from_header(None), CacheStatus::Miss);
+    assert_eq!(
+        CacheStatus::from_header(Some("EXPIRED")),
+        CacheStatus::Expired
+    );
+    assert_eq!(
+        CacheStatus::from_header(Some("DYNAMIC")),
+        CacheStatus::Other
+    );
+    assert!(CacheStatus::Miss.is_miss());
+    assert!(CacheStatus::Expired.is_miss());
+    assert!(!CacheStatus::Hit.is_miss());
+}
+
+#[test]
+fn test_hot_videos_query_ranks_recent_views() {
+    let query = hot_videos_query();
+
+    assert!(query.contains("event = 'video_viewed'"));
+    assert!(query.contains("INTERVAL 2 HOUR"));
+    assert!(query.contains("ORDER BY views DESC"));
+    assert!(query.contains("LIMIT 20"));
+}
+
+#[tokio::test]
+async fn test_warm_videos_counts_misses_without_purging() {
+    let cdn = MockCdn {
+        statuses: vec![
+            ("video_hit", Ok(Some("HIT"))),
+            ("video_miss", Ok(Some("MISS"))),
+            ("video_down", Err(())),
+        ],
+        ..Default::default()
+    };
+
+    let res = warm_videos(&cdn, video_ids(&cdn)).await;
+
+    assert_eq!(res.hits, 1);
+    assert_eq!(res.misses, vec!["video_miss".to_string()]);
+    assert_eq!(res.failed, vec!["video_down".to_string()]);
+    // a purge would drop the copy the miss just cached, each url is requested once
+    let mut requested = cdn.requested.lock().unwrap().clone();
+    requested.sort();
+    assert_eq!(
+        requested,
+        vec![
+            "https://customer-2p3jflss4r4hmpnz.cloudflarestream.com/video_down/manifest/video.m3u8"
+                .to_string(),
+            "https://customer-2p3jflss4r4hmpnz.cloudflarestream.com/video_hit/manifest/video.m3u8"
+                .to_string(),
+            "https://customer-2p3jflss4r4hmpnz.cloudflarestream.com/video_miss/manifest/video.m3u8"
+                .to_string(),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_warm_videos_counts_hits() {
+    let cdn = MockCdn {
+        statuses: vec![("video_a", Ok(Some("HIT"))), ("video_b", Ok(Some("HIT")))],
+        ..Default::default()
+    };
+
+    let res = warm_videos(&cdn, video_ids(&cdn)).await;
+
+    assert_eq!(res.hits, 2);
+    assert!(res.misses.is_empty());
+}
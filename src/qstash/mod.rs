@@ -2,6 +2,7 @@ mod verify;
 
 use std::{str::FromStr, sync::Arc, time::Duration};
 
+use archive_events::archive_old_events;
 use axum::{
     extract::{Path, State},
     middleware::{self},
@@ -10,35 +11,52 @@ use axum::{
     Json, Router,
 };
 use candid::{Decode, Encode, Nat, Principal};
+use dashmap::DashMap;
+use gcs_gc::gc_orphaned_gcs_objects;
+use hot_videos::refresh_hot_videos;
 use hotornot_job::start_hotornot_job;
-use http::StatusCode;
+use http::{HeaderMap, StatusCode};
 use ic_agent::{identity::DelegatedIdentity, Identity};
-use jsonwebtoken::{Algorithm, DecodingKey, Validation};
-use serde::Deserialize;
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, TokenData, Validation};
+use serde::{de::DeserializeOwned, Deserialize};
 use serde_bytes::ByteBuf;
+use token_airdrop::token_airdrop_handler;
 use tower::ServiceBuilder;
+use trace::trace_qstash_job;
 use tracing::instrument;
 use verify::verify_qstash_message;
+use videohash_migration::migrate_videohash_to_spacetimedb;
 use yral_canisters_client::{
     individual_user_template::{DeployedCdaoCanisters, IndividualUserTemplate},
     sns_governance::{
         Account, Amount, Command, Command1, Disburse, DissolveState, ListNeurons, ManageNeuron,
-        SnsGovernance,
+        NeuronId, SnsGovernance,
     },
     sns_ledger::{Account as LedgerAccount, SnsLedger, TransferArg, TransferResult},
     sns_swap::{self, NewSaleTicketRequest, RefreshBuyerTokensRequest, SnsSwap},
 };
 use yral_qstash_types::{ClaimTokensRequest, ParticipateInSwapRequest};
 
+#[cfg(not(feature = "local-bin"))]
+use crate::auth::nonce::NonceCache;
 use crate::qstash::duplicate::VideoPublisherData;
 use crate::{
     app_state::AppState,
+    auth::nonce::{nonce_required, optional_nonce_from_headers},
     canister::{
+        bulk_claim_tokens::AdminClaimTokensRequest,
+        canister_metrics::export_canister_metrics,
+        governance_health::sns_governance_health_check,
+        neuron_followees::sync_sns_neuron_followees,
+        neuron_health::track_governed_canister,
         snapshot::{
             // alert::snapshot_alert_job,
             alert::snapshot_alert_job,
+            integrity::verify_backup_integrity,
+            sample_verify::verify_backup_sample,
             snapshot_v2::{backup_canisters_job_v2, backup_user_canister},
         },
+        sns_wasm_hashes::verify_sns_wasm_hashes,
         upgrade_user_token_sns_canister::{
             setup_sns_canisters_of_a_user_canister_for_upgrade,
             upgrade_user_token_sns_canister_for_entire_network_impl,
@@ -47,33 +65,115 @@ use crate::{
         },
     },
     consts::ICP_LEDGER_CANISTER_ID,
+    creators::score::{compute_creator_score, compute_creator_scores},
     events::{
-        event::{storj::storj_ingest, upload_video_gcs},
+        consistency_check::ml_cache_consistency_check,
+        event::{
+            profile_view::creator_profile_view_digest, storj::storj_ingest,
+            token_metadata::update_token_metadata, upload_video_gcs,
+        },
+        feed_cache_reindex::reindex_user_feed_cache,
+        feed_history_rebalance::rebalance_user_feed_history,
         nsfw::{extract_frames_and_upload, nsfw_job, nsfw_job_v2},
+        nsfw_appeal::resolve_nsfw_appeal,
     },
-    posts::report_post::qstash_report_post,
+    posts::{delete_cascade::post_delete_cascade, report_post::qstash_report_post},
+    tokens::embeddings::index_token_metadata_to_vector_db,
 };
 
+pub mod archive_events;
 pub mod client;
+pub mod dead_letter;
 pub mod duplicate;
+pub mod gcs_gc;
+pub mod hot_videos;
 pub mod hotornot_job;
+pub mod queue_depths;
+pub mod stuck_videos;
+pub mod token_airdrop;
+pub mod trace;
+pub mod video_jobs;
+pub mod videohash_migration;
+
+#[cfg(test)]
+mod archive_events_tests;
+#[cfg(test)]
+mod dead_letter_tests;
+#[cfg(test)]
+mod duplicate_tests;
+#[cfg(test)]
+mod gcs_gc_tests;
+#[cfg(test)]
+mod hot_videos_tests;
+#[cfg(test)]
+mod qstash_tests;
+#[cfg(test)]
+mod queue_depths_tests;
+#[cfg(test)]
+mod stuck_videos_tests;
+#[cfg(test)]
+mod token_airdrop_tests;
+#[cfg(test)]
+mod trace_tests;
+#[cfg(test)]
+mod video_jobs_tests;
+#[cfg(test)]
+mod videohash_migration_tests;
+
+pub const QSTASH_ISSUER: &str = "Upstash";
 
 #[derive(Clone)]
 pub struct QStashState {
-    decoding_key: Arc<DecodingKey>,
-    validation: Arc<Validation>,
+    /// (issuer, key) pairs tried in order, e.g. staging and production environments
+    keys: Arc<Vec<(String, DecodingKey)>>,
+    validations: Arc<DashMap<String, Arc<Validation>>>,
 }
 
 impl QStashState {
     pub fn init(verification_key: String) -> Self {
-        let decoding_key = DecodingKey::from_secret(verification_key.as_bytes());
-        let mut validation = Validation::new(Algorithm::HS256);
-        validation.set_issuer(&["Upstash"]);
-        validation.set_audience(&[""]);
+        Self::with_multiple_issuers(vec![(
+            QSTASH_ISSUER.to_string(),
+            DecodingKey::from_secret(verification_key.as_bytes()),
+        )])
+    }
+
+    pub fn with_multiple_issuers(keys: Vec<(String, DecodingKey)>) -> Self {
         Self {
-            decoding_key: Arc::new(decoding_key),
-            validation: Arc::new(validation),
+            keys: Arc::new(keys),
+            validations: Arc::new(DashMap::new()),
+        }
+    }
+
+    fn validation(&self, issuer: &str) -> Arc<Validation> {
+        if let Some(validation) = self.validations.get(issuer) {
+            return validation.clone();
+        }
+
+        self.validations
+            .entry(issuer.to_string())
+            .or_insert_with(|| {
+                let mut validation = Validation::new(Algorithm::HS256);
+                validation.set_issuer(&[issuer]);
+                validation.set_audience(&[""]);
+                Arc::new(validation)
+            })
+            .clone()
+    }
+
+    /// Decodes a signature JWT with the first registered key-issuer pair that accepts it
+    pub fn decode_signature<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<TokenData<T>, jsonwebtoken::errors::Error> {
+        let mut last_err = jsonwebtoken::errors::Error::from(ErrorKind::InvalidToken);
+        for (issuer, key) in self.keys.iter() {
+            match jsonwebtoken::decode::<T>(token, key, &self.validation(issuer)) {
+                Ok(data) => return Ok(data),
+                Err(e) => last_err = e,
+            }
         }
+
+        Err(last_err)
     }
 }
 
@@ -107,18 +207,60 @@ async fn get_user_canister(
     Ok(meta.user_canister_id)
 }
 
-fn principal_to_subaccount(principal: Principal) -> ByteBuf {
+/// ICP principals are at most 29 bytes
+pub(crate) const MAX_PRINCIPAL_LEN: usize = 29;
+
+pub(crate) fn principal_to_subaccount(principal: Principal) -> Result<ByteBuf, &'static str> {
+    principal_bytes_to_subaccount(principal.as_slice())
+}
+
+/// Subaccount layout is the principal length followed by the principal bytes, zero padded
+pub(crate) fn principal_bytes_to_subaccount(principal: &[u8]) -> Result<ByteBuf, &'static str> {
+    if principal.len() > MAX_PRINCIPAL_LEN {
+        return Err("principal too long for subaccount");
+    }
+
     let mut subaccount = [0u8; 32];
-    let principal = principal.as_slice();
-    subaccount[0] = principal.len().try_into().unwrap();
+    subaccount[0] = principal.len() as u8;
     subaccount[1..1 + principal.len()].copy_from_slice(principal);
 
-    subaccount.to_vec().into()
+    Ok(subaccount.to_vec().into())
+}
+
+/// Inverse of `principal_to_subaccount`, non zero padding means the subaccount was not derived from a principal
+pub fn subaccount_to_principal(subaccount: &[u8; 32]) -> Result<Principal, &'static str> {
+    let len = subaccount[0] as usize;
+    if len > MAX_PRINCIPAL_LEN {
+        return Err("subaccount length byte exceeds principal length");
+    }
+    if subaccount[1 + len..].iter().any(|b| *b != 0) {
+        return Err("subaccount has non zero padding");
+    }
+
+    Ok(Principal::from_slice(&subaccount[1..1 + len]))
 }
 
+/// Takes an unused `X-Nonce` per user, see [`claim_request_nonce`]
 async fn participate_in_swap(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<ParticipateInSwapRequest>,
+) -> Result<Response, StatusCode> {
+    let user_principal = req.user_principal;
+    let nonce = claim_request_nonce(&state, &headers, user_principal).await?;
+
+    let res = participate_in_swap_for_user(&state, req).await;
+    // an unavailable swap is retried too
+    if !res.as_ref().is_ok_and(|res| res.status().is_success()) {
+        release_request_nonce(&state, user_principal, nonce.as_deref()).await;
+    }
+
+    res
+}
+
+async fn participate_in_swap_for_user(
+    state: &AppState,
+    req: ParticipateInSwapRequest,
 ) -> Result<Response, StatusCode> {
     let user_canister = get_user_canister(&state.yral_metadata_client, req.user_principal).await?;
     let cdao_cans = verify_token_root(&state.agent, user_canister, req.token_root).await?;
@@ -148,7 +290,10 @@ async fn participate_in_swap(
 
     // transfer icp
     let admin_principal = agent.get_principal().unwrap();
-    let subaccount = principal_to_subaccount(admin_principal);
+    let subaccount = principal_to_subaccount(admin_principal).map_err(|e| {
+        log::error!("Failed to derive swap subaccount: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
     let transfer_args = TransferArg {
         memo: Some(vec![0].into()),
         amount: Nat::from(1000000_u64),
@@ -188,8 +333,43 @@ async fn participate_in_swap(
     Ok(res)
 }
 
+/// Marks the request's `X-Nonce` as used by the principal, see [`optional_nonce_from_headers`].
+/// QStash forwards it from `Upstash-Forward-X-Nonce`.
+async fn claim_request_nonce(
+    state: &AppState,
+    headers: &HeaderMap,
+    principal: Principal,
+) -> Result<Option<String>, StatusCode> {
+    let nonce = optional_nonce_from_headers(headers, nonce_required())?;
+
+    #[cfg(not(feature = "local-bin"))]
+    if let Some(nonce) = &nonce {
+        NonceCache::new(&state.canister_backup_redis_pool)
+            .claim(principal, nonce)
+            .await?;
+    }
+    #[cfg(feature = "local-bin")]
+    let _ = (state, principal);
+
+    Ok(nonce)
+}
+
+/// Frees the nonce of a failed request, QStash retries it with the same nonce
+async fn release_request_nonce(state: &AppState, principal: Principal, nonce: Option<&str>) {
+    #[cfg(not(feature = "local-bin"))]
+    if let Some(nonce) = nonce {
+        NonceCache::new(&state.canister_backup_redis_pool)
+            .release(principal, nonce)
+            .await;
+    }
+    #[cfg(feature = "local-bin")]
+    let _ = (state, principal, nonce);
+}
+
+/// Takes an unused `X-Nonce` per user, see [`claim_request_nonce`]
 async fn claim_tokens_from_first_neuron(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<ClaimTokensRequest>,
 ) -> Result<Response, StatusCode> {
     let identity: DelegatedIdentity = req
@@ -199,17 +379,42 @@ async fn claim_tokens_from_first_neuron(
     let user_principal = identity
         .sender()
         .expect("Delegated identity without principal?!");
+    let nonce = claim_request_nonce(&state, &headers, user_principal).await?;
 
     let mut agent = state.agent.clone();
     // we need to set identity for disburse and icrc-1 transfer
     agent.set_identity(identity);
 
-    let user_canister = get_user_canister(&state.yral_metadata_client, user_principal).await?;
-    let cdao_cans = verify_token_root(&agent, user_canister, req.token_root).await?;
-    let governance_principal = cdao_cans.governance;
-    let ledger_principal = cdao_cans.ledger;
+    let claimed = async {
+        let user_canister = get_user_canister(&state.yral_metadata_client, user_principal).await?;
+        let cdao_cans = verify_token_root(&agent, user_canister, req.token_root).await?;
+        claim_tokens_for_user(&agent, user_principal, user_canister, cdao_cans).await
+    }
+    .await;
+
+    if claimed.is_err() {
+        release_request_nonce(&state, user_principal, nonce.as_deref()).await;
+    }
+    let claimed = claimed?;
+
+    let res = Response::builder()
+        .status(StatusCode::OK)
+        .body(claimed.into())
+        .unwrap();
+    Ok(res)
+}
+
+/// Share of a claim that goes to the user canister. The user has 50% of the overall amount,
+/// 20% of this 50% is the 10% of the overall amount reserved for the canister.
+pub fn canister_share(amount: u64) -> u64 {
+    (amount as u128 * 20 / 100) as u64
+}
 
-    let governance = SnsGovernance(governance_principal, &agent);
+/// Id and stake of the user's unlocked neuron, `None` if there is nothing to claim
+async fn claimable_neuron(
+    governance: &SnsGovernance<'_>,
+    user_principal: Principal,
+) -> Result<Option<(NeuronId, u64)>, StatusCode> {
     let neurons = governance
         .list_neurons(ListNeurons {
             of_principal: Some(user_principal),
@@ -221,11 +426,7 @@ async fn claim_tokens_from_first_neuron(
         .neurons;
 
     if neurons.len() < 2 || neurons[1].cached_neuron_stake_e8s == 0 {
-        let res = Response::builder()
-            .status(StatusCode::OK)
-            .body("Claiming not required".into())
-            .unwrap();
-        return Ok(res);
+        return Ok(None);
     }
     let ix = if matches!(
         neurons[1].dissolve_state.as_ref(),
@@ -238,14 +439,21 @@ async fn claim_tokens_from_first_neuron(
 
     let amount = neurons[ix].cached_neuron_stake_e8s;
     if amount == 0 {
-        let res = Response::builder()
-            .status(StatusCode::OK)
-            .body("Claiming not required".into())
-            .unwrap();
-        return Ok(res);
+        return Ok(None);
     }
-    let neuron_id = &neurons[ix].id.as_ref().ok_or(StatusCode::BAD_REQUEST)?.id;
+    let neuron_id = neurons[ix].id.clone().ok_or(StatusCode::BAD_REQUEST)?;
 
+    Ok(Some((neuron_id, amount)))
+}
+
+/// Disburses `amount` of the neuron to `owner`, all of its stake if `None`. Retries while the
+/// SNS swap is initialising.
+async fn disburse_neuron(
+    governance: &SnsGovernance<'_>,
+    neuron_id: &NeuronId,
+    owner: Principal,
+    amount: Option<u64>,
+) -> Result<(), StatusCode> {
     let mut tries = 0;
     loop {
         if tries > 10 {
@@ -254,13 +462,13 @@ async fn claim_tokens_from_first_neuron(
         tries += 1;
 
         let manage_neuron_arg = ManageNeuron {
-            subaccount: neuron_id.clone(),
+            subaccount: neuron_id.id.clone(),
             command: Some(Command::Disburse(Disburse {
                 to_account: Some(Account {
-                    owner: Some(user_principal),
+                    owner: Some(owner),
                     subaccount: None,
                 }),
-                amount: Some(Amount { e8s: amount }),
+                amount: amount.map(|e8s| Amount { e8s }),
             })),
         };
         let manage_neuron = governance
@@ -268,10 +476,10 @@ async fn claim_tokens_from_first_neuron(
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         match manage_neuron.command {
-            Some(Command1::Disburse(_)) => break,
+            Some(Command1::Disburse(_)) => return Ok(()),
             Some(Command1::Error(e)) => {
                 if e.error_message.contains("PreInitializationSwap") {
-                    log::debug!("Governance {governance_principal} is not ready. Retrying...");
+                    log::debug!("Governance {} is not ready. Retrying...", governance.0);
                     tokio::time::sleep(Duration::from_secs(8)).await;
                     continue;
                 }
@@ -280,13 +488,27 @@ async fn claim_tokens_from_first_neuron(
             _ => return Err(StatusCode::INTERNAL_SERVER_ERROR),
         }
     }
+}
+
+/// Disburses the user's unlocked neuron to the user and sends the canister its share from the
+/// user's account, `agent` must have the user's identity
+async fn claim_tokens_for_user(
+    agent: &ic_agent::Agent,
+    user_principal: Principal,
+    user_canister: Principal,
+    cdao_cans: DeployedCdaoCanisters,
+) -> Result<&'static str, StatusCode> {
+    let governance_principal = cdao_cans.governance;
+    let ledger_principal = cdao_cans.ledger;
+
+    let governance = SnsGovernance(governance_principal, agent);
+    let Some((neuron_id, amount)) = claimable_neuron(&governance, user_principal).await? else {
+        return Ok("Claiming not required");
+    };
+    disburse_neuron(&governance, &neuron_id, user_principal, Some(amount)).await?;
 
     // Transfer to canister
-    let ledger = SnsLedger(ledger_principal, &agent);
-    // User has 50% of the overall amount
-    // 20% of this 50% is 10% of the overall amount
-    // 10% of the overall amount is reserveed for the canister
-    let distribution_amt = Nat::from(amount) * 20u32 / 100u32;
+    let ledger = SnsLedger(ledger_principal, agent);
     let transfer_resp = ledger
         .icrc_1_transfer(TransferArg {
             to: LedgerAccount {
@@ -296,7 +518,7 @@ async fn claim_tokens_from_first_neuron(
             fee: None,
             memo: None,
             from_subaccount: None,
-            amount: distribution_amt,
+            amount: Nat::from(canister_share(amount)),
             created_at_time: None,
         })
         .await;
@@ -313,18 +535,98 @@ async fn claim_tokens_from_first_neuron(
         _ => (),
     }
 
-    let res = Response::builder()
+    Ok("Tokens claimed")
+}
+
+/// Claims the user's unlocked neuron with the admin identity. The admin holds none of the
+/// claimed tokens, so the canister's share is disbursed to it straight from the neuron and the
+/// rest to the user, nothing is ever transferred from the admin's account.
+async fn claim_tokens_for_user_as_admin(
+    agent: &ic_agent::Agent,
+    user_principal: Principal,
+    user_canister: Principal,
+    cdao_cans: DeployedCdaoCanisters,
+) -> Result<&'static str, StatusCode> {
+    let governance = SnsGovernance(cdao_cans.governance, agent);
+    let Some((neuron_id, amount)) = claimable_neuron(&governance, user_principal).await? else {
+        return Ok("Claiming not required");
+    };
+
+    disburse_neuron(
+        &governance,
+        &neuron_id,
+        user_canister,
+        Some(canister_share(amount)),
+    )
+    .await?;
+    disburse_neuron(&governance, &neuron_id, user_principal, None).await?;
+
+    Ok("Tokens claimed")
+}
+
+/// Claims every token of a user with the admin identity, enqueued by the bulk claim endpoint.
+/// Takes an unused `X-Nonce` per user, set by [`client::QStashClient::claim_tokens_batch`]
+#[instrument(skip(state))]
+async fn claim_tokens_as_admin(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<AdminClaimTokensRequest>,
+) -> Result<Response, StatusCode> {
+    let nonce = claim_request_nonce(&state, &headers, req.user_principal).await?;
+
+    let Ok(tokens) = IndividualUserTemplate(req.user_canister, &state.agent)
+        .deployed_cdao_canisters()
+        .await
+    else {
+        release_request_nonce(&state, req.user_principal, nonce.as_deref()).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    let mut failed = 0;
+    for cdao_cans in tokens {
+        let root = cdao_cans.root;
+        if let Err(status) = claim_tokens_for_user_as_admin(
+            &state.agent,
+            req.user_principal,
+            req.user_canister,
+            cdao_cans,
+        )
+        .await
+        {
+            log::error!(
+                "Failed to claim token {} for {}: {}",
+                root,
+                req.user_principal,
+                status
+            );
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        release_request_nonce(&state, req.user_principal, nonce.as_deref()).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(Response::builder()
         .status(StatusCode::OK)
         .body("Tokens claimed".into())
-        .unwrap();
-
-    Ok(res)
+        .unwrap())
 }
 
 async fn upgrade_sns_creator_dao_canister(
     State(state): State<Arc<AppState>>,
     Json(req): Json<SnsCanisters>,
 ) -> Result<Response, StatusCode> {
+    if let Err(e) = track_governed_canister(&state.canister_backup_redis_pool, req.governance).await
+    {
+        log::warn!(
+            "Failed to track governance canister {}: {}",
+            req.governance,
+            e
+        );
+    }
+
     let result =
         upgrade_user_token_sns_canister_impl(&state.agent, &state.qstash_client, req).await;
 
@@ -359,6 +661,21 @@ async fn verify_sns_canister_upgrade_proposal(
     )
     .await;
 
+    #[cfg(not(feature = "local-bin"))]
+    if matches!(result, Ok(true)) {
+        if let Err(e) = crate::canister::cdao_milestone::record_first_upgrade_milestone(
+            &state,
+            verify_sns_canister_proposal_request
+                .sns_canisters
+                .governance,
+            verify_sns_canister_proposal_request.proposal_id,
+        )
+        .await
+        {
+            log::error!("Failed to record first upgrade milestone: {}", e);
+        }
+    }
+
     match result {
         Ok(executed) if executed => Ok(Response::builder()
             .status(StatusCode::OK)
@@ -424,11 +741,13 @@ struct VideoHashIndexingRequest {
     publisher_data: VideoPublisherData,
 }
 
-#[instrument(skip(state))]
+#[instrument(skip(state), fields(video_id = tracing::field::Empty))]
 async fn video_deduplication_handler(
     State(state): State<Arc<AppState>>,
     Json(req): Json<VideoHashIndexingRequest>,
 ) -> Result<Response, StatusCode> {
+    trace::record_video_id(&req.video_id);
+
     log::info!(
         "Processing video deduplication for video ID: {}",
         req.video_id
@@ -451,10 +770,12 @@ async fn video_deduplication_handler(
         .process_video_deduplication(
             &state.dedup_index_ctx,
             &state.bigquery_client,
+            &state.canister_backup_redis_pool,
+            &state.redis_video_hash_index(),
             &req.video_id,
             &req.video_url,
             publisher_data,
-            move |vid_id, canister_id, post_id, timestamp, publisher_user_id| {
+            move |vid_id, canister_id, post_id, timestamp, publisher_user_id, duration_secs| {
                 // Clone the values to ensure they have 'static lifetime
                 let vid_id = vid_id.to_string();
                 let canister_id = canister_id.to_string();
@@ -471,8 +792,10 @@ async fn video_deduplication_handler(
                             post_id,
                             timestamp,
                             &publisher_user_id,
+                            duration_secs,
                         )
                         .await
+                        .map(|_| ())
                 })
             },
         )
@@ -495,6 +818,7 @@ async fn video_deduplication_handler(
 pub fn qstash_router<S>(app_state: Arc<AppState>) -> Router<S> {
     Router::new()
         .route("/claim_tokens", post(claim_tokens_from_first_neuron))
+        .route("/claim_tokens_admin", post(claim_tokens_as_admin))
         .route("/participate_in_swap", post(participate_in_swap))
         .route(
             "/upgrade_sns_creator_dao_canister",
@@ -524,8 +848,55 @@ pub fn qstash_router<S>(app_state: Arc<AppState>) -> Router<S> {
             post(backup_canisters_job_v2),
         )
         .route("/backup_user_canister", post(backup_user_canister))
+        .route("/verify-backup-integrity", post(verify_backup_integrity))
+        .route("/verify-backup-sample", post(verify_backup_sample))
         .route("/snapshot_alert_job", post(snapshot_alert_job))
         .route("/start_hotornot_job", post(start_hotornot_job))
+        .route("/gc-orphaned-gcs-objects", post(gc_orphaned_gcs_objects))
+        .route("/post-delete-cascade", post(post_delete_cascade))
+        .route("/token-airdrop", post(token_airdrop_handler))
+        .route("/update_token_metadata", post(update_token_metadata))
+        .route(
+            "/index-token-metadata-to-vector-db",
+            post(index_token_metadata_to_vector_db),
+        )
+        .route("/export_canister_metrics", post(export_canister_metrics))
+        .route("/reindex-user-feed-cache", post(reindex_user_feed_cache))
+        .route(
+            "/rebalance-user-feed-history",
+            post(rebalance_user_feed_history),
+        )
+        .route(
+            "/creator-profile-view-digest",
+            post(creator_profile_view_digest),
+        )
+        .route("/refresh-hot-videos", post(refresh_hot_videos))
+        .route("/archive-old-events", post(archive_old_events))
+        .route(
+            "/ml-cache-consistency-check",
+            post(ml_cache_consistency_check),
+        )
+        .route("/compute-creator-score", post(compute_creator_score))
+        .route("/compute-creator-scores", post(compute_creator_scores))
+        .route("/verify-sns-wasm-hashes", post(verify_sns_wasm_hashes))
+        .route(
+            "/sns-governance-health-check",
+            post(sns_governance_health_check),
+        )
+        .route("/resolve-nsfw-appeal", post(resolve_nsfw_appeal))
+        .route(
+            "/sync-sns-neuron-followees",
+            post(sync_sns_neuron_followees),
+        )
+        .route(
+            "/migrate-videohash-to-spacetimedb",
+            post(migrate_videohash_to_spacetimedb),
+        )
+        .layer(middleware::from_fn(trace_qstash_job))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            dead_letter::dead_letter_failed_jobs,
+        ))
         .layer(ServiceBuilder::new().layer(middleware::from_fn_with_state(
             app_state.qstash.clone(),
             verify_qstash_message,
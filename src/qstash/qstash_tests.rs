@@ -0,0 +1,175 @@
+use candid::Principal;
+use jsonwebtoken::{encode, DecodingKey, EncodingKey, Header};
+use proptest::prelude::*;
+use serde_json::{json, Value};
+
+use super::{
+    canister_share, principal_bytes_to_subaccount, principal_to_subaccount,
+    subaccount_to_principal, QStashState, MAX_PRINCIPAL_LEN, QSTASH_ISSUER,
+};
+
+fn subaccount_array(principal: Principal) -> [u8; 32] {
+    principal_to_subaccount(principal)
+        .unwrap()
+        .as_slice()
+        .try_into()
+        .unwrap()
+}
+
+proptest! {
+    #[test]
+    fn test_subaccount_round_trip(bytes in proptest::collection::vec(any::<u8>(), 0..=MAX_PRINCIPAL_LEN)) {
+        let principal = Principal::from_slice(&bytes);
+
+        prop_assert_eq!(subaccount_to_principal(&subaccount_array(principal)), Ok(principal));
+    }
+
+    #[test]
+    fn test_subaccount_rejects_length_over_max(len in (MAX_PRINCIPAL_LEN as u8 + 1)..=u8::MAX) {
+        let mut subaccount = [0u8; 32];
+        subaccount[0] = len;
+
+        prop_assert!(subaccount_to_principal(&subaccount).is_err());
+    }
+}
+
+#[test]
+fn test_subaccount_to_principal_of_known_principals() {
+    for principal in [
+        Principal::anonymous(),
+        Principal::management_canister(),
+        Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap(),
+    ] {
+        assert_eq!(
+            subaccount_to_principal(&subaccount_array(principal)),
+            Ok(principal)
+        );
+    }
+}
+
+#[test]
+fn test_subaccount_with_non_zero_padding_is_rejected() {
+    let mut subaccount =
+        subaccount_array(Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap());
+    subaccount[31] = 1;
+
+    assert!(subaccount_to_principal(&subaccount).is_err());
+}
+
+#[test]
+fn test_empty_principal_subaccount() {
+    let subaccount = principal_bytes_to_subaccount(&[]).unwrap();
+
+    assert_eq!(subaccount.len(), 32);
+    assert_eq!(subaccount[0], 0);
+    assert!(subaccount[1..].iter().all(|b| *b == 0));
+}
+
+#[test]
+fn test_max_length_principal_subaccount() {
+    let principal: Vec<u8> = (1..=MAX_PRINCIPAL_LEN as u8).collect();
+    let subaccount = principal_bytes_to_subaccount(&principal).unwrap();
+
+    assert_eq!(subaccount.len(), 32);
+    assert_eq!(subaccount[0], 29);
+    assert_eq!(&subaccount[1..30], principal.as_slice());
+    assert!(subaccount[30..].iter().all(|b| *b == 0));
+}
+
+#[test]
+fn test_too_long_principal_is_rejected() {
+    let principal = [7u8; MAX_PRINCIPAL_LEN + 1];
+
+    assert!(principal_bytes_to_subaccount(&principal).is_err());
+}
+
+#[test]
+fn test_principal_to_subaccount_matches_slice() {
+    let principal = Principal::from_text("2vxsx-fae").unwrap();
+    let subaccount = principal_to_subaccount(principal).unwrap();
+
+    assert_eq!(subaccount[0] as usize, principal.as_slice().len());
+    assert_eq!(
+        &subaccount[1..1 + principal.as_slice().len()],
+        principal.as_slice()
+    );
+}
+
+#[test]
+fn test_canister_share_of_a_claim() {
+    assert_eq!(canister_share(1_000), 200);
+    assert_eq!(canister_share(7), 1);
+    assert_eq!(canister_share(0), 0);
+    assert_eq!(canister_share(u64::MAX), u64::MAX / 5);
+}
+
+fn sign(issuer: &str, secret: &str) -> String {
+    let exp = chrono::Utc::now().timestamp() + 300;
+    encode(
+        &Header::default(),
+        &json!({ "iss": issuer, "aud": "", "sub": "test", "exp": exp, "body": "" }),
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_single_issuer_signature() {
+    let state = QStashState::init("prod-key".into());
+
+    assert!(state
+        .decode_signature::<Value>(&sign(QSTASH_ISSUER, "prod-key"))
+        .is_ok());
+    assert!(state
+        .decode_signature::<Value>(&sign(QSTASH_ISSUER, "other-key"))
+        .is_err());
+    assert!(state
+        .decode_signature::<Value>(&sign("Someone", "prod-key"))
+        .is_err());
+}
+
+#[test]
+fn test_multiple_issuers_try_every_key() {
+    let state = QStashState::with_multiple_issuers(vec![
+        ("Staging".into(), DecodingKey::from_secret(b"staging-key")),
+        (QSTASH_ISSUER.into(), DecodingKey::from_secret(b"prod-key")),
+    ]);
+
+    let staging = state
+        .decode_signature::<Value>(&sign("Staging", "staging-key"))
+        .unwrap();
+    assert_eq!(staging.claims["iss"], "Staging");
+    assert!(state
+        .decode_signature::<Value>(&sign(QSTASH_ISSUER, "prod-key"))
+        .is_ok());
+
+    // a key only verifies tokens of its own issuer
+    assert!(state
+        .decode_signature::<Value>(&sign(QSTASH_ISSUER, "staging-key"))
+        .is_err());
+}
+
+#[test]
+fn test_concurrent_signature_verification() {
+    let state = QStashState::with_multiple_issuers(vec![
+        ("Staging".into(), DecodingKey::from_secret(b"staging-key")),
+        (QSTASH_ISSUER.into(), DecodingKey::from_secret(b"prod-key")),
+    ]);
+    let staging = sign("Staging", "staging-key");
+    let prod = sign(QSTASH_ISSUER, "prod-key");
+    let forged = sign(QSTASH_ISSUER, "forged-key");
+
+    std::thread::scope(|s| {
+        for i in 0..32 {
+            let state = state.clone();
+            let (staging, prod, forged) = (&staging, &prod, &forged);
+            s.spawn(move || {
+                for _ in 0..200 {
+                    let token = if i % 2 == 0 { staging } else { prod };
+                    assert!(state.decode_signature::<Value>(token).is_ok());
+                    assert!(state.decode_signature::<Value>(forged).is_err());
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,84 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{extract::State, Json};
+use http::StatusCode;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::RwLock;
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+};
+
+use super::client::{QStashClient, QueueInfo};
+
+pub const QUEUE_DEPTHS_CACHE_TTL: Duration = Duration::from_secs(30);
+
+static QUEUE_DEPTHS_CACHE: Lazy<RwLock<Option<(Instant, Vec<QueueInfo>)>>> =
+    Lazy::new(|| RwLock::new(None));
+
+#[derive(Debug, Serialize)]
+pub struct QueueDepth {
+    #[serde(flatten)]
+    pub queue: QueueInfo,
+    pub route: Option<&'static str>,
+}
+
+/// Maps queue / flow control keys to the qstash route that consumes them
+pub fn queue_route(queue_name: &str) -> Option<&'static str> {
+    match queue_name {
+        "BACKUP_CANISTER" => Some("/qstash/backup_user_canister"),
+        "STORJ_INGESTION" => Some("/qstash/storj_ingest"),
+        "VIDEOHASH_BACKFILL" => Some("/qstash/process_single_video"),
+        _ => None,
+    }
+}
+
+#[instrument(skip(state, token))]
+pub async fn queue_depths_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<QueueDepth>>, StatusCode> {
+    check_auth_admin(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let queues = cached_queues(&state.qstash_client, &QUEUE_DEPTHS_CACHE)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to list qstash queues: {}", e);
+            StatusCode::BAD_GATEWAY
+        })?;
+
+    Ok(Json(with_routes(queues)))
+}
+
+/// Queues listed in the last [`QUEUE_DEPTHS_CACHE_TTL`], listed again from QStash otherwise
+pub async fn cached_queues(
+    qstash_client: &QStashClient,
+    cache: &RwLock<Option<(Instant, Vec<QueueInfo>)>>,
+) -> Result<Vec<QueueInfo>, anyhow::Error> {
+    if let Some((fetched_at, queues)) = cache.read().await.as_ref() {
+        if fetched_at.elapsed() < QUEUE_DEPTHS_CACHE_TTL {
+            return Ok(queues.clone());
+        }
+    }
+
+    let queues = qstash_client.list_queues().await?;
+    *cache.write().await = Some((Instant::now(), queues.clone()));
+
+    Ok(queues)
+}
+
+pub fn with_routes(queues: Vec<QueueInfo>) -> Vec<QueueDepth> {
+    queues
+        .into_iter()
+        .map(|queue| QueueDepth {
+            route: queue_route(&queue.name),
+            queue,
+        })
+        .collect()
+}
@@ -0,0 +1,126 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use axum::{extract::State, routing::get, Json, Router};
+use http::StatusCode;
+use reqwest::Url;
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+
+use super::{
+    client::{QStashClient, QueueInfo},
+    queue_depths::{cached_queues, queue_route, with_routes, QUEUE_DEPTHS_CACHE_TTL},
+};
+
+/// QStash API serving `GET /v2/queues`, counting the requests it gets
+async fn mock_qstash(status: StatusCode, queues: Value) -> (QStashClient, Arc<AtomicUsize>) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let app = Router::new()
+        .route(
+            "/v2/queues",
+            get(|State(requests): State<Arc<AtomicUsize>>| async move {
+                requests.fetch_add(1, Ordering::SeqCst);
+                (status, Json(queues))
+            }),
+        )
+        .with_state(requests.clone());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let client = QStashClient {
+        client: reqwest::Client::new(),
+        base_url: Arc::new(Url::parse(&format!("http://{addr}/v2/")).unwrap()),
+    };
+
+    (client, requests)
+}
+
+fn queues_response() -> Value {
+    json!([
+        {
+            "name": "BACKUP_CANISTER",
+            "lag": 12,
+            "parallelism": 5,
+            "paused": false,
+            "createdAt": 1700000000000i64,
+            "updatedAt": 1700000100000i64
+        },
+        { "name": "adhoc" }
+    ])
+}
+
+#[test]
+fn test_queue_route() {
+    assert_eq!(
+        queue_route("BACKUP_CANISTER"),
+        Some("/qstash/backup_user_canister")
+    );
+    assert_eq!(queue_route("STORJ_INGESTION"), Some("/qstash/storj_ingest"));
+    assert_eq!(queue_route("unknown"), None);
+}
+
+#[tokio::test]
+async fn test_list_queues_from_qstash() {
+    let (client, _) = mock_qstash(StatusCode::OK, queues_response()).await;
+
+    let queues = client.list_queues().await.unwrap();
+
+    assert_eq!(queues.len(), 2);
+    assert_eq!(queues[0].name, "BACKUP_CANISTER");
+    assert_eq!(queues[0].lag, 12);
+    assert_eq!(queues[0].parallelism, 5);
+    assert_eq!(queues[0].created_at, Some(1700000000000));
+    assert_eq!(queues[1].lag, 0);
+    assert_eq!(queues[1].created_at, None);
+}
+
+#[tokio::test]
+async fn test_qstash_error_is_returned() {
+    let (client, _) = mock_qstash(StatusCode::UNAUTHORIZED, json!({ "error": "bad token" })).await;
+
+    assert!(client.list_queues().await.is_err());
+}
+
+#[tokio::test]
+async fn test_queues_are_cached() {
+    let (client, requests) = mock_qstash(StatusCode::OK, queues_response()).await;
+    let cache = RwLock::new(None);
+
+    let first = cached_queues(&client, &cache).await.unwrap();
+    let second = cached_queues(&client, &cache).await.unwrap();
+
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert_eq!(first.len(), second.len());
+}
+
+#[tokio::test]
+async fn test_stale_cache_is_refreshed() {
+    let (client, requests) = mock_qstash(StatusCode::OK, queues_response()).await;
+    let stale = Instant::now() - QUEUE_DEPTHS_CACHE_TTL;
+    let cache = RwLock::new(Some((stale, Vec::<QueueInfo>::new())));
+
+    let queues = cached_queues(&client, &cache).await.unwrap();
+
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert_eq!(queues.len(), 2);
+}
+
+#[tokio::test]
+async fn test_depths_are_mapped_to_routes() {
+    let (client, _) = mock_qstash(StatusCode::OK, queues_response()).await;
+
+    let depths = with_routes(client.list_queues().await.unwrap());
+    let depths = serde_json::to_value(depths).unwrap();
+
+    assert_eq!(depths[0]["name"], "BACKUP_CANISTER");
+    assert_eq!(depths[0]["lag"], 12);
+    assert_eq!(depths[0]["route"], "/qstash/backup_user_canister");
+    assert_eq!(depths[1]["route"], Value::Null);
+}
@@ -0,0 +1,277 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+#[cfg(not(feature = "local-bin"))]
+use futures::StreamExt;
+#[cfg(not(feature = "local-bin"))]
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
+#[cfg(not(feature = "local-bin"))]
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    events::event::UploadVideoInfo,
+    qstash::duplicate::DUPLICATE_VIDEO_ID_COLUMN,
+    AppError,
+};
+
+/// A video without an NSFW result this long after its upload is stuck
+pub const STUCK_AFTER_HOURS: u32 = 2;
+/// Uploads older than this are not looked at, their jobs are long gone
+pub const STUCK_LOOKBACK_DAYS: u32 = 7;
+pub const STUCK_VIDEOS_LIMIT: u32 = 1000;
+#[cfg(not(feature = "local-bin"))]
+const STUCK_VIDEOS_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+#[cfg(not(feature = "local-bin"))]
+static STUCK_VIDEOS_CACHE: Lazy<StuckVideosCache> =
+    Lazy::new(|| StuckVideosCache::new(STUCK_VIDEOS_CACHE_TTL));
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct StuckVideo {
+    pub video_id: String,
+    pub canister_id: String,
+    pub post_id: u64,
+    pub publisher_user_id: String,
+    pub uploaded_at: String,
+}
+
+impl StuckVideo {
+    /// Payload of the NSFW detection job the video never got
+    pub fn upload_video_info(&self) -> UploadVideoInfo {
+        UploadVideoInfo {
+            video_id: self.video_id.clone(),
+            canister_id: self.canister_id.clone(),
+            post_id: self.post_id,
+            timestamp: self.uploaded_at.clone(),
+            publisher_user_id: self.publisher_user_id.clone(),
+            channel_id: None,
+            duration_secs: None,
+        }
+    }
+}
+
+/// Videos deduplicated, so in `video_unique` or uploaded as a duplicate, without a row in
+/// `video_nsfw_agg`. The upload event carries the publisher data needed to requeue them.
+pub fn stuck_videos_query() -> String {
+    format!(
+        "WITH deduplicated AS (
+            SELECT video_id FROM `hot-or-not-feed-intelligence.yral_ds.video_unique`
+            UNION DISTINCT
+            SELECT {duplicate_id} AS video_id FROM `hot-or-not-feed-intelligence.yral_ds.duplicate_videos`
+        ),
+        uploads AS (
+            SELECT
+                JSON_EXTRACT_SCALAR(params, '$.video_id') AS video_id,
+                ANY_VALUE(JSON_EXTRACT_SCALAR(params, '$.canister_id')) AS canister_id,
+                ANY_VALUE(JSON_EXTRACT_SCALAR(params, '$.post_id')) AS post_id,
+                ANY_VALUE(JSON_EXTRACT_SCALAR(params, '$.publisher_user_id')) AS publisher_user_id,
+                MIN(timestamp) AS uploaded_at
+            FROM `hot-or-not-feed-intelligence.analytics_335143420.test_events_analytics`
+            WHERE event = 'video_upload_successful'
+                AND timestamp >= TIMESTAMP_SUB(CURRENT_TIMESTAMP(), INTERVAL {lookback} DAY)
+            GROUP BY video_id
+        )
+        SELECT u.video_id, u.canister_id, u.post_id, u.publisher_user_id, FORMAT_TIMESTAMP('%Y-%m-%dT%H:%M:%SZ', u.uploaded_at)
+        FROM deduplicated d
+        JOIN uploads u ON u.video_id = d.video_id
+        LEFT JOIN `hot-or-not-feed-intelligence.yral_ds.video_nsfw_agg` n ON n.video_id = d.video_id
+        WHERE n.video_id IS NULL
+            AND u.uploaded_at < TIMESTAMP_SUB(CURRENT_TIMESTAMP(), INTERVAL {stuck_after} HOUR)
+        ORDER BY u.uploaded_at
+        LIMIT {limit}",
+        duplicate_id = DUPLICATE_VIDEO_ID_COLUMN,
+        lookback = STUCK_LOOKBACK_DAYS,
+        stuck_after = STUCK_AFTER_HOURS,
+        limit = STUCK_VIDEOS_LIMIT,
+    )
+}
+
+/// `None` for rows missing publisher data, those videos cannot be requeued
+pub fn stuck_video_from_columns(
+    video_id: Option<String>,
+    canister_id: Option<String>,
+    post_id: Option<String>,
+    publisher_user_id: Option<String>,
+    uploaded_at: Option<String>,
+) -> Option<StuckVideo> {
+    Some(StuckVideo {
+        video_id: video_id?,
+        canister_id: canister_id?,
+        post_id: post_id?.parse().ok()?,
+        publisher_user_id: publisher_user_id?,
+        uploaded_at: uploaded_at.unwrap_or_default(),
+    })
+}
+
+pub(crate) trait StuckVideoSource {
+    async fn stuck_videos(&self) -> Result<Vec<StuckVideo>, anyhow::Error>;
+}
+
+#[cfg(not(feature = "local-bin"))]
+impl StuckVideoSource for google_cloud_bigquery::client::Client {
+    async fn stuck_videos(&self) -> Result<Vec<StuckVideo>, anyhow::Error> {
+        let request = QueryRequest {
+            query: stuck_videos_query(),
+            ..Default::default()
+        };
+        let mut response = self
+            .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+            .await?;
+
+        let mut videos = Vec::new();
+        while let Some(row) = response.next().await? {
+            let video = stuck_video_from_columns(
+                row.column(0)?,
+                row.column(1)?,
+                row.column(2)?,
+                row.column(3)?,
+                row.column(4)?,
+            );
+            match video {
+                Some(video) => videos.push(video),
+                None => log::warn!("Skipping stuck video row without publisher data"),
+            }
+        }
+
+        Ok(videos)
+    }
+}
+
+/// Stuck videos of the last query, requeued videos are dropped from it
+pub(crate) struct StuckVideosCache {
+    ttl: Duration,
+    entry: RwLock<Option<(Instant, Vec<StuckVideo>)>>,
+}
+
+impl StuckVideosCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: RwLock::new(None),
+        }
+    }
+
+    pub async fn get_or_fetch(
+        &self,
+        source: &impl StuckVideoSource,
+    ) -> Result<Vec<StuckVideo>, anyhow::Error> {
+        if let Some((fetched_at, videos)) = self.entry.read().await.as_ref() {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(videos.clone());
+            }
+        }
+
+        let videos = source.stuck_videos().await?;
+        *self.entry.write().await = Some((Instant::now(), videos.clone()));
+
+        Ok(videos)
+    }
+
+    pub async fn remove(&self, video_ids: &[String]) {
+        if let Some((_, videos)) = self.entry.write().await.as_mut() {
+            videos.retain(|video| !video_ids.contains(&video.video_id));
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct RequeueSummary {
+    pub requeued: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// Requeues every video through `publish`, one at a time to go easy on qstash
+pub async fn requeue_stuck_videos<F, Fut>(videos: &[StuckVideo], publish: F) -> RequeueSummary
+where
+    F: Fn(StuckVideo) -> Fut,
+    Fut: Future<Output = Result<(), anyhow::Error>>,
+{
+    let mut summary = RequeueSummary::default();
+    for video in videos {
+        match publish(video.clone()).await {
+            Ok(()) => summary.requeued.push(video.video_id.clone()),
+            Err(e) => {
+                log::error!("Failed to requeue stuck video {}: {}", video.video_id, e);
+                summary.failed.push(video.video_id.clone());
+            }
+        }
+    }
+
+    summary
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StuckVideosParams {
+    #[serde(default)]
+    pub requeue: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StuckVideosResponse {
+    pub video_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requeue: Option<RequeueSummary>,
+}
+
+/// Lists videos stuck before the NSFW stage, `?requeue=true` enqueues their NSFW detection again.
+/// Upload and deduplication already ran for them, so they are not sent through those again.
+#[instrument(skip(state, token))]
+pub async fn stuck_videos_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Query(params): Query<StuckVideosParams>,
+) -> Result<Json<StuckVideosResponse>, AppError> {
+    check_auth_admin(&token).map_err(|_| AppError::Unauthorized)?;
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let videos = STUCK_VIDEOS_CACHE
+            .get_or_fetch(&state.bigquery_client)
+            .await
+            .map_err(AppError::BigQueryError)?;
+        let video_ids = videos.iter().map(|video| video.video_id.clone()).collect();
+
+        let requeue = if params.requeue {
+            let qstash_client = &state.qstash_client;
+            let summary = requeue_stuck_videos(&videos, |video| async move {
+                qstash_client
+                    .publish_video_nsfw_detection_v2(&video.video_id, video.upload_video_info())
+                    .await
+                    .map(|_| ())
+            })
+            .await;
+            STUCK_VIDEOS_CACHE.remove(&summary.requeued).await;
+            log::info!(
+                "Requeued {} stuck videos, {} failed",
+                summary.requeued.len(),
+                summary.failed.len()
+            );
+            Some(summary)
+        } else {
+            None
+        };
+
+        Ok(Json(StuckVideosResponse { video_ids, requeue }))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, params);
+        Ok(Json(StuckVideosResponse {
+            video_ids: vec![],
+            requeue: None,
+        }))
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use super::stuck_videos::{
+    requeue_stuck_videos, stuck_video_from_columns, stuck_videos_query, StuckVideo,
+    StuckVideoSource, StuckVideosCache,
+};
+
+/// BigQuery returning a fixed result, counting the queries
+struct MockBigQuery {
+    videos: Vec<StuckVideo>,
+    queries: AtomicUsize,
+}
+
+impl MockBigQuery {
+    fn new(video_ids: &[&str]) -> Self {
+        Self {
+            videos: video_ids.iter().map(|id| video(id)).collect(),
+            queries: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl StuckVideoSource for MockBigQuery {
+    async fn stuck_videos(&self) -> Result<Vec<StuckVideo>, anyhow::Error> {
+        self.queries.fetch_add(1, Ordering::SeqCst);
+        Ok(self.videos.clone())
+    }
+}
+
+struct FailingBigQuery;
+
+impl StuckVideoSource for FailingBigQuery {
+    async fn stuck_videos(&self) -> Result<Vec<StuckVideo>, anyhow::Error> {
+        Err(anyhow::anyhow!("quota exceeded"))
+    }
+}
+
+fn video(video_id: &str) -> StuckVideo {
+    StuckVideo {
+        video_id: video_id.into(),
+        canister_id: "ryjl3-tyaaa-aaaaa-aaaba-cai".into(),
+        post_id: 7,
+        publisher_user_id: "2vxsx-fae".into(),
+        uploaded_at: "2026-10-15T08:00:00Z".into(),
+    }
+}
+
+fn ids(videos: &[StuckVideo]) -> Vec<&str> {
+    videos.iter().map(|video| video.video_id.as_str()).collect()
+}
+
+#[test]
+fn test_stuck_videos_query() {
+    let query = stuck_videos_query();
+
+    assert!(query.contains("yral_ds.video_unique"));
+    assert!(query.contains(
+        "SELECT original_video_id AS video_id FROM `hot-or-not-feed-intelligence.yral_ds.duplicate_videos`"
+    ));
+    assert!(!query.contains("parent_video_id"));
+    assert!(query.contains("LEFT JOIN `hot-or-not-feed-intelligence.yral_ds.video_nsfw_agg`"));
+    assert!(query.contains("WHERE n.video_id IS NULL"));
+    assert!(query.contains("INTERVAL 2 HOUR"));
+}
+
+#[test]
+fn test_requeue_payload_carries_the_upload() {
+    let info = video("video-1").upload_video_info();
+
+    assert_eq!(info.video_id, "video-1");
+    assert_eq!(info.canister_id, "ryjl3-tyaaa-aaaaa-aaaba-cai");
+    assert_eq!(info.post_id, 7);
+    assert_eq!(info.timestamp, "2026-10-15T08:00:00Z");
+    assert_eq!(info.publisher_user_id, "2vxsx-fae");
+    assert_eq!(info.channel_id, None);
+}
+
+#[test]
+fn test_stuck_video_from_columns() {
+    let row = stuck_video_from_columns(
+        Some("video-1".into()),
+        Some("ryjl3-tyaaa-aaaaa-aaaba-cai".into()),
+        Some("7".into()),
+        Some("2vxsx-fae".into()),
+        Some("2026-10-15T08:00:00Z".into()),
+    );
+    assert_eq!(row, Some(video("video-1")));
+
+    let missing_post = stuck_video_from_columns(
+        Some("video-1".into()),
+        Some("ryjl3-tyaaa-aaaaa-aaaba-cai".into()),
+        None,
+        Some("2vxsx-fae".into()),
+        None,
+    );
+    assert_eq!(missing_post, None);
+
+    let invalid_post = stuck_video_from_columns(
+        Some("video-1".into()),
+        Some("ryjl3-tyaaa-aaaaa-aaaba-cai".into()),
+        Some("seven".into()),
+        Some("2vxsx-fae".into()),
+        None,
+    );
+    assert_eq!(invalid_post, None);
+}
+
+#[tokio::test]
+async fn test_cache_serves_repeated_requests() {
+    let bigquery = MockBigQuery::new(&["video-1", "video-2"]);
+    let cache = StuckVideosCache::new(Duration::from_secs(600));
+
+    let first = cache.get_or_fetch(&bigquery).await.unwrap();
+    let second = cache.get_or_fetch(&bigquery).await.unwrap();
+
+    assert_eq!(ids(&first), vec!["video-1", "video-2"]);
+    assert_eq!(first, second);
+    assert_eq!(bigquery.queries.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_expired_cache_queries_again() {
+    let bigquery = MockBigQuery::new(&["video-1"]);
+    let cache = StuckVideosCache::new(Duration::ZERO);
+
+    cache.get_or_fetch(&bigquery).await.unwrap();
+    cache.get_or_fetch(&bigquery).await.unwrap();
+
+    assert_eq!(bigquery.queries.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_failed_query_is_not_cached() {
+    let cache = StuckVideosCache::new(Duration::from_secs(600));
+    assert!(cache.get_or_fetch(&FailingBigQuery).await.is_err());
+
+    let bigquery = MockBigQuery::new(&["video-1"]);
+    let videos = cache.get_or_fetch(&bigquery).await.unwrap();
+
+    assert_eq!(ids(&videos), vec!["video-1"]);
+}
+
+#[tokio::test]
+async fn test_requeued_videos_leave_the_cache() {
+    let bigquery = MockBigQuery::new(&["video-1", "video-2", "video-3"]);
+    let cache = StuckVideosCache::new(Duration::from_secs(600));
+    let videos = cache.get_or_fetch(&bigquery).await.unwrap();
+
+    let summary = requeue_stuck_videos(&videos, |video| async move {
+        if video.video_id == "video-2" {
+            Err(anyhow::anyhow!("qstash unavailable"))
+        } else {
+            Ok(())
+        }
+    })
+    .await;
+    cache.remove(&summary.requeued).await;
+
+    assert_eq!(summary.requeued, vec!["video-1", "video-3"]);
+    assert_eq!(summary.failed, vec!["video-2"]);
+    let cached = cache.get_or_fetch(&bigquery).await.unwrap();
+    assert_eq!(ids(&cached), vec!["video-2"]);
+    assert_eq!(bigquery.queries.load(Ordering::SeqCst), 1);
+}
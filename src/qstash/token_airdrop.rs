@@ -0,0 +1,394 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{extract::State, response::Response, Json};
+use candid::{Nat, Principal};
+use google_cloud_bigquery::http::tabledata::insert_all::{InsertAllRequest, Row};
+use http::StatusCode;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use yral_canisters_client::sns_ledger::{
+    Account as LedgerAccount, SnsLedger, TransferArg, TransferResult,
+};
+use yral_ml_feed_cache::{consts::USER_WATCH_HISTORY_PLAIN_POST_ITEM_SUFFIX, types::PlainPostItem};
+
+use crate::{app_state::AppState, types::RedisPool, user::orphaned_keys::KeyStore};
+
+use super::verify_token_root;
+
+/// Users airdropped to per job run, the job enqueues itself until the scan completes
+pub const AIRDROP_BATCH_SIZE: usize = 500;
+/// Watch histories read per job run when few of them qualify
+pub const AIRDROP_HISTORIES_PER_RUN: usize = 5000;
+const AIRDROP_KEY_PREFIX: &str = "token_airdrop:";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TokenAirdropRequest {
+    pub token_root: Principal,
+    pub creator_canister: Principal,
+    pub min_watches: u64,
+    pub amount_per_user: u64,
+    /// `SCAN` cursor over the watch histories to resume from, 0 starts a new scan
+    #[serde(default)]
+    pub cursor: u64,
+}
+
+/// Where a user's airdrop of a token got to, a user with any status is never airdropped again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AirdropStatus {
+    Attempted,
+    Done,
+    Failed,
+}
+
+impl AirdropStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AirdropStatus::Attempted => "attempted",
+            AirdropStatus::Done => "done",
+            AirdropStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct AirdropBatch {
+    pub airdropped: Vec<Principal>,
+    pub failed: Vec<(Principal, String)>,
+    /// Cursor the next run resumes from, `None` once the scan is done
+    pub next_cursor: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct TokenAirdropRow {
+    token_root: String,
+    creator_canister: String,
+    user_canister: String,
+    amount: u64,
+    success: bool,
+    error: Option<String>,
+    created_at: String,
+}
+
+/// Hash of the users airdropped a token, user canister id to [`AirdropStatus`]
+pub fn airdrop_key(token_root: Principal) -> String {
+    format!("{}{}", AIRDROP_KEY_PREFIX, token_root)
+}
+
+/// Memo of a user's airdrop transfer, the ledger rejects a second transfer with the same memo
+/// and `created_at_time` as a duplicate
+pub fn airdrop_memo(token_root: Principal, user_canister: Principal) -> Vec<u8> {
+    blake3::hash(format!("token_airdrop:{}:{}", token_root, user_canister).as_bytes())
+        .as_bytes()
+        .to_vec()
+}
+
+/// Plain watch histories, they hold the posts of both the clean and the NSFW feed
+pub fn plain_watch_history_key_pattern() -> String {
+    format!("*{}", USER_WATCH_HISTORY_PLAIN_POST_ITEM_SUFFIX)
+}
+
+/// Distinct posts of the creator in a watch history
+pub fn creator_watches(history: &[PlainPostItem], creator_canister: Principal) -> u64 {
+    let creator = creator_canister.to_text();
+    history
+        .iter()
+        .filter(|item| item.canister_id == creator)
+        .map(|item| item.post_id)
+        .collect::<HashSet<_>>()
+        .len() as u64
+}
+
+pub(crate) trait WatchHistorySource: KeyStore {
+    async fn watch_history(&self, key: &str) -> Result<Vec<PlainPostItem>, anyhow::Error>;
+}
+
+impl WatchHistorySource for RedisPool {
+    async fn watch_history(&self, key: &str) -> Result<Vec<PlainPostItem>, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let raw: Vec<String> = conn.smembers(key).await?;
+
+        Ok(raw
+            .iter()
+            .filter_map(|item| serde_json::from_str(item).ok())
+            .collect())
+    }
+}
+
+pub(crate) trait AirdropBook {
+    /// Marks the user as attempted, returns false if they already have a status
+    async fn claim_airdrop(
+        &self,
+        token_root: Principal,
+        user_canister: Principal,
+    ) -> Result<bool, anyhow::Error>;
+
+    async fn record_airdrop(
+        &self,
+        token_root: Principal,
+        user_canister: Principal,
+        status: AirdropStatus,
+    ) -> Result<(), anyhow::Error>;
+}
+
+impl AirdropBook for RedisPool {
+    async fn claim_airdrop(
+        &self,
+        token_root: Principal,
+        user_canister: Principal,
+    ) -> Result<bool, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let claimed = conn
+            .hset_nx(
+                airdrop_key(token_root),
+                user_canister.to_text(),
+                AirdropStatus::Attempted.as_str(),
+            )
+            .await?;
+
+        Ok(claimed)
+    }
+
+    async fn record_airdrop(
+        &self,
+        token_root: Principal,
+        user_canister: Principal,
+        status: AirdropStatus,
+    ) -> Result<(), anyhow::Error> {
+        let mut conn = self.get().await?;
+        let _: () = conn
+            .hset(
+                airdrop_key(token_root),
+                user_canister.to_text(),
+                status.as_str(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub(crate) trait AirdropTransfer {
+    async fn transfer(
+        &self,
+        to: Principal,
+        amount: u64,
+        memo: Vec<u8>,
+        created_at_time: u64,
+    ) -> Result<(), String>;
+}
+
+impl AirdropTransfer for SnsLedger<'_> {
+    async fn transfer(
+        &self,
+        to: Principal,
+        amount: u64,
+        memo: Vec<u8>,
+        created_at_time: u64,
+    ) -> Result<(), String> {
+        let transfer_res = self
+            .icrc_1_transfer(TransferArg {
+                to: LedgerAccount {
+                    owner: to,
+                    subaccount: None,
+                },
+                fee: None,
+                memo: Some(memo.into()),
+                from_subaccount: None,
+                amount: Nat::from(amount),
+                created_at_time: Some(created_at_time),
+            })
+            .await;
+
+        match transfer_res {
+            Ok(TransferResult::Ok(_)) => Ok(()),
+            Ok(TransferResult::Err(e)) => Err(format!("{e:?}")),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Airdrops to the users whose watch history holds more than `min_watches` posts of the
+/// creator, from `req.cursor` on. Each user is claimed before their transfer and keeps their
+/// status, so neither a retry nor a later run airdrops to them again, even when it failed.
+pub async fn airdrop_batch(
+    history: &impl WatchHistorySource,
+    book: &impl AirdropBook,
+    ledger: &impl AirdropTransfer,
+    req: &TokenAirdropRequest,
+    created_at_time: u64,
+) -> Result<AirdropBatch, anyhow::Error> {
+    let pattern = plain_watch_history_key_pattern();
+    let mut batch = AirdropBatch::default();
+    let mut cursor = req.cursor;
+    let mut checked = 0;
+
+    loop {
+        let (next, keys) = history.scan_keys(cursor, &pattern).await?;
+
+        for key in keys {
+            let Some(user_canister) = key
+                .strip_suffix(USER_WATCH_HISTORY_PLAIN_POST_ITEM_SUFFIX)
+                .and_then(|user| Principal::from_text(user).ok())
+            else {
+                continue;
+            };
+            if user_canister == req.creator_canister {
+                continue;
+            }
+            checked += 1;
+            let watches =
+                creator_watches(&history.watch_history(&key).await?, req.creator_canister);
+            if watches <= req.min_watches {
+                continue;
+            }
+
+            if batch.airdropped.len() + batch.failed.len() >= AIRDROP_BATCH_SIZE {
+                // users of this page claimed so far are skipped when it is scanned again
+                batch.next_cursor = Some(cursor);
+                return Ok(batch);
+            }
+            if !book.claim_airdrop(req.token_root, user_canister).await? {
+                continue;
+            }
+
+            let memo = airdrop_memo(req.token_root, user_canister);
+            let status = match ledger
+                .transfer(user_canister, req.amount_per_user, memo, created_at_time)
+                .await
+            {
+                Ok(()) => {
+                    batch.airdropped.push(user_canister);
+                    AirdropStatus::Done
+                }
+                Err(e) => {
+                    log::error!(
+                        "Airdrop of token {} to {} failed: {}",
+                        req.token_root,
+                        user_canister,
+                        e
+                    );
+                    batch.failed.push((user_canister, e));
+                    AirdropStatus::Failed
+                }
+            };
+            // the user stays attempted, which also keeps them out of later runs
+            if let Err(e) = book
+                .record_airdrop(req.token_root, user_canister, status)
+                .await
+            {
+                log::error!(
+                    "Failed to record airdrop of token {} to {} as {}: {}",
+                    req.token_root,
+                    user_canister,
+                    status.as_str(),
+                    e
+                );
+            }
+        }
+
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+        if checked >= AIRDROP_HISTORIES_PER_RUN {
+            batch.next_cursor = Some(cursor);
+            break;
+        }
+    }
+
+    Ok(batch)
+}
+
+fn airdrop_rows(req: &TokenAirdropRequest, batch: &AirdropBatch) -> Vec<Row<TokenAirdropRow>> {
+    let created_at = chrono::Utc::now().to_rfc3339();
+    let airdropped = batch.airdropped.iter().map(|user| (user, None));
+    let failed = batch.failed.iter().map(|(user, e)| (user, Some(e.clone())));
+
+    airdropped
+        .chain(failed)
+        .map(|(user_canister, error)| Row {
+            insert_id: None,
+            json: TokenAirdropRow {
+                token_root: req.token_root.to_text(),
+                creator_canister: req.creator_canister.to_text(),
+                user_canister: user_canister.to_text(),
+                amount: req.amount_per_user,
+                success: error.is_none(),
+                error,
+                created_at: created_at.clone(),
+            },
+        })
+        .collect()
+}
+
+/// Airdrops `amount_per_user` of a creator's token to every user canister that watched more than
+/// `min_watches` of the creator's posts. Users are tracked in Redis under [`airdrop_key`], so the
+/// job re-enqueues itself until the watch histories are scanned and tries each user once.
+#[instrument(skip(state))]
+pub async fn token_airdrop_handler(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<TokenAirdropRequest>,
+) -> Result<Response, StatusCode> {
+    let cdao_cans = verify_token_root(&state.agent, req.creator_canister, req.token_root).await?;
+
+    let ledger = SnsLedger(cdao_cans.ledger, &state.agent);
+    let created_at_time = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+    let batch = airdrop_batch(
+        &state.ml_feed_cache.redis_pool,
+        &state.canister_backup_redis_pool,
+        &ledger,
+        &req,
+        created_at_time,
+    )
+    .await
+    .map_err(|e| {
+        log::error!("Failed to airdrop token {}: {}", req.token_root, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let rows = airdrop_rows(&req, &batch);
+    if !rows.is_empty() {
+        let request = InsertAllRequest {
+            rows,
+            ..Default::default()
+        };
+        if let Err(e) = state
+            .bigquery_client
+            .tabledata()
+            .insert(
+                "hot-or-not-feed-intelligence",
+                "yral_ds",
+                "token_airdrops",
+                &request,
+            )
+            .await
+        {
+            log::error!("Failed to log token airdrops to bigquery: {}", e);
+        }
+    }
+
+    if let Some(cursor) = batch.next_cursor {
+        let next = TokenAirdropRequest {
+            cursor,
+            ..req.clone()
+        };
+        if let Err(e) = state.qstash_client.publish_token_airdrop(&next).await {
+            log::error!("Failed to re-enqueue token airdrop: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    log::info!(
+        "Airdropped token {} to {} users, {} failed, more remaining: {}",
+        req.token_root,
+        batch.airdropped.len(),
+        batch.failed.len(),
+        batch.next_cursor.is_some()
+    );
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(format!("Airdropped to {} users", batch.airdropped.len()).into())
+        .unwrap())
+}
@@ -0,0 +1,261 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use candid::Principal;
+use yral_ml_feed_cache::{consts::USER_WATCH_HISTORY_PLAIN_POST_ITEM_SUFFIX, types::PlainPostItem};
+
+use super::token_airdrop::{
+    airdrop_batch, airdrop_memo, creator_watches, plain_watch_history_key_pattern, AirdropBook,
+    AirdropStatus, AirdropTransfer, TokenAirdropRequest, WatchHistorySource, AIRDROP_BATCH_SIZE,
+};
+use crate::user::orphaned_keys::KeyStore;
+
+fn principal(n: u32) -> Principal {
+    Principal::from_slice(&n.to_be_bytes())
+}
+
+fn creator() -> Principal {
+    principal(1_000_000)
+}
+
+fn history_item(publisher: Principal, post_id: u64) -> PlainPostItem {
+    PlainPostItem {
+        canister_id: publisher.to_text(),
+        post_id,
+    }
+}
+
+fn history_key(user: Principal) -> String {
+    format!("{}{}", user, USER_WATCH_HISTORY_PLAIN_POST_ITEM_SUFFIX)
+}
+
+fn request(min_watches: u64) -> TokenAirdropRequest {
+    TokenAirdropRequest {
+        token_root: principal(2_000_000),
+        creator_canister: creator(),
+        min_watches,
+        amount_per_user: 100,
+        cursor: 0,
+    }
+}
+
+/// Redis holding watch histories and airdrop statuses, `SCAN` returns `page_size` histories at
+/// a time with the index of the next one as cursor
+struct MockRedis {
+    /// (publisher, post id) of the watched posts
+    histories: BTreeMap<String, Vec<(Principal, u64)>>,
+    statuses: Mutex<BTreeMap<Principal, AirdropStatus>>,
+    page_size: usize,
+}
+
+impl MockRedis {
+    fn with_histories(histories: Vec<(Principal, Vec<(Principal, u64)>)>) -> Self {
+        Self {
+            histories: histories
+                .into_iter()
+                .map(|(user, items)| (history_key(user), items))
+                .collect(),
+            statuses: Mutex::default(),
+            page_size: 100,
+        }
+    }
+
+    fn status(&self, user: Principal) -> Option<AirdropStatus> {
+        self.statuses.lock().unwrap().get(&user).copied()
+    }
+}
+
+impl KeyStore for MockRedis {
+    async fn scan_keys(
+        &self,
+        cursor: u64,
+        pattern: &str,
+    ) -> Result<(u64, Vec<String>), anyhow::Error> {
+        assert_eq!(pattern, plain_watch_history_key_pattern());
+        let keys: Vec<String> = self.histories.keys().cloned().collect();
+        let start = cursor as usize;
+        let end = (start + self.page_size).min(keys.len());
+        let next = if end == keys.len() { 0 } else { end as u64 };
+
+        Ok((next, keys[start..end].to_vec()))
+    }
+
+    async fn delete_keys(&self, _keys: &[String]) -> Result<usize, anyhow::Error> {
+        unreachable!("airdrops never delete keys")
+    }
+}
+
+impl WatchHistorySource for MockRedis {
+    async fn watch_history(&self, key: &str) -> Result<Vec<PlainPostItem>, anyhow::Error> {
+        Ok(self.histories[key]
+            .iter()
+            .map(|(publisher, post_id)| history_item(*publisher, *post_id))
+            .collect())
+    }
+}
+
+impl AirdropBook for MockRedis {
+    async fn claim_airdrop(
+        &self,
+        _token_root: Principal,
+        user_canister: Principal,
+    ) -> Result<bool, anyhow::Error> {
+        let mut statuses = self.statuses.lock().unwrap();
+        if statuses.contains_key(&user_canister) {
+            return Ok(false);
+        }
+        statuses.insert(user_canister, AirdropStatus::Attempted);
+        Ok(true)
+    }
+
+    async fn record_airdrop(
+        &self,
+        _token_root: Principal,
+        user_canister: Principal,
+        status: AirdropStatus,
+    ) -> Result<(), anyhow::Error> {
+        self.statuses.lock().unwrap().insert(user_canister, status);
+        Ok(())
+    }
+}
+
+/// Ledger failing the transfers to `failing` users
+#[derive(Default)]
+struct MockLedger {
+    failing: Vec<Principal>,
+    transfers: Mutex<Vec<(Principal, u64, Vec<u8>)>>,
+}
+
+impl AirdropTransfer for MockLedger {
+    async fn transfer(
+        &self,
+        to: Principal,
+        amount: u64,
+        memo: Vec<u8>,
+        _created_at_time: u64,
+    ) -> Result<(), String> {
+        self.transfers.lock().unwrap().push((to, amount, memo));
+        if self.failing.contains(&to) {
+            return Err("InsufficientFunds".into());
+        }
+        Ok(())
+    }
+}
+
+fn watched(count: u64) -> Vec<(Principal, u64)> {
+    (0..count).map(|post| (creator(), post)).collect()
+}
+
+#[test]
+fn test_creator_watches_counts_distinct_posts_of_the_creator() {
+    let history = vec![
+        history_item(creator(), 1),
+        history_item(creator(), 1),
+        history_item(creator(), 2),
+        history_item(principal(7), 3),
+    ];
+
+    assert_eq!(creator_watches(&history, creator()), 2);
+}
+
+#[tokio::test]
+async fn test_airdrops_only_users_over_min_watches() {
+    let redis = MockRedis::with_histories(vec![
+        (principal(1), watched(3)),
+        (principal(2), watched(2)),
+        (creator(), watched(10)),
+    ]);
+    let ledger = MockLedger::default();
+
+    let batch = airdrop_batch(&redis, &redis, &ledger, &request(2), 0)
+        .await
+        .unwrap();
+
+    assert_eq!(batch.airdropped, vec![principal(1)]);
+    assert_eq!(batch.next_cursor, None);
+    assert_eq!(
+        *ledger.transfers.lock().unwrap(),
+        vec![(
+            principal(1),
+            100,
+            airdrop_memo(request(2).token_root, principal(1))
+        )]
+    );
+    assert_eq!(redis.status(principal(1)), Some(AirdropStatus::Done));
+    assert_eq!(redis.status(principal(2)), None);
+}
+
+#[tokio::test]
+async fn test_failed_users_are_not_picked_again() {
+    let redis =
+        MockRedis::with_histories(vec![(principal(1), watched(3)), (principal(2), watched(3))]);
+    let ledger = MockLedger {
+        failing: vec![principal(2)],
+        ..Default::default()
+    };
+
+    let first = airdrop_batch(&redis, &redis, &ledger, &request(0), 0)
+        .await
+        .unwrap();
+    let second = airdrop_batch(&redis, &redis, &ledger, &request(0), 0)
+        .await
+        .unwrap();
+
+    assert_eq!(first.airdropped, vec![principal(1)]);
+    assert_eq!(
+        first.failed,
+        vec![(principal(2), "InsufficientFunds".to_string())]
+    );
+    assert_eq!(redis.status(principal(2)), Some(AirdropStatus::Failed));
+    assert_eq!(second, Default::default());
+    assert_eq!(ledger.transfers.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_attempted_users_are_skipped() {
+    let redis = MockRedis::with_histories(vec![(principal(1), watched(3))]);
+    redis
+        .statuses
+        .lock()
+        .unwrap()
+        .insert(principal(1), AirdropStatus::Attempted);
+    let ledger = MockLedger::default();
+
+    let batch = airdrop_batch(&redis, &redis, &ledger, &request(0), 0)
+        .await
+        .unwrap();
+
+    assert!(batch.airdropped.is_empty());
+    assert!(ledger.transfers.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_full_batch_resumes_from_the_current_page() {
+    let users = AIRDROP_BATCH_SIZE as u32 + 5;
+    let mut redis =
+        MockRedis::with_histories((1..=users).map(|n| (principal(n), watched(1))).collect());
+    redis.page_size = 200;
+    let ledger = MockLedger::default();
+
+    let first = airdrop_batch(&redis, &redis, &ledger, &request(0), 0)
+        .await
+        .unwrap();
+    let cursor = first.next_cursor.unwrap();
+    let second = airdrop_batch(
+        &redis,
+        &redis,
+        &ledger,
+        &TokenAirdropRequest {
+            cursor,
+            ..request(0)
+        },
+        0,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(first.airdropped.len(), AIRDROP_BATCH_SIZE);
+    assert_eq!(cursor, 400);
+    assert_eq!(second.airdropped.len(), 5);
+    assert_eq!(second.next_cursor, None);
+    assert_eq!(ledger.transfers.lock().unwrap().len(), users as usize);
+}
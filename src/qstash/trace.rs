@@ -0,0 +1,137 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use http::header::CONTENT_LENGTH;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobOutcome {
+    Success,
+    Failure(String),
+    /// The job had nothing to do
+    Skipped(String),
+}
+
+impl JobOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobOutcome::Success => "success",
+            JobOutcome::Failure(_) => "failure",
+            JobOutcome::Skipped(_) => "skipped",
+        }
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            JobOutcome::Success => None,
+            JobOutcome::Failure(reason) | JobOutcome::Skipped(reason) => Some(reason),
+        }
+    }
+
+    /// Outcome set by the handler through [`job_skipped`], otherwise derived from the status
+    pub fn from_response(response: &Response) -> Self {
+        if let Some(outcome) = response.extensions().get::<JobOutcome>() {
+            return outcome.clone();
+        }
+
+        let status = response.status();
+        if status.is_success() {
+            JobOutcome::Success
+        } else {
+            JobOutcome::Failure(format!("responded with status {}", status))
+        }
+    }
+}
+
+/// Response part marking the job as skipped in its trace
+pub fn job_skipped(reason: impl Into<String>) -> Extension<JobOutcome> {
+    Extension(JobOutcome::Skipped(reason.into()))
+}
+
+/// Records the job's video on the current span, which must declare an empty `video_id` field
+/// to filter traces by video
+pub fn record_video_id(video_id: &str) {
+    tracing::Span::current().record("video_id", video_id);
+}
+
+/// Structured log entry of a qstash job, emitted when dropped.
+/// A trace dropped before recording an outcome, as when the handler panicked or
+/// qstash gave up on the request, is logged as a failure.
+#[derive(Debug)]
+pub struct QStashJobTrace {
+    pub job_type: String,
+    pub started_at: Instant,
+    pub payload_size_bytes: usize,
+    pub outcome: JobOutcome,
+}
+
+impl QStashJobTrace {
+    pub fn start(job_type: impl Into<String>, payload_size_bytes: usize) -> Self {
+        Self {
+            job_type: job_type.into(),
+            started_at: Instant::now(),
+            payload_size_bytes,
+            outcome: JobOutcome::Failure("job did not complete".into()),
+        }
+    }
+
+    pub fn finish(&mut self, outcome: JobOutcome) {
+        self.outcome = outcome;
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+}
+
+impl Drop for QStashJobTrace {
+    fn drop(&mut self) {
+        let duration_ms = self.duration().as_millis() as u64;
+        let outcome = self.outcome.as_str();
+        let reason = self.outcome.reason().unwrap_or_default();
+
+        match self.outcome {
+            JobOutcome::Failure(_) => tracing::error!(
+                job_type = %self.job_type,
+                duration_ms,
+                payload_size_bytes = self.payload_size_bytes,
+                outcome,
+                reason,
+                "qstash job failed"
+            ),
+            _ => tracing::info!(
+                job_type = %self.job_type,
+                duration_ms,
+                payload_size_bytes = self.payload_size_bytes,
+                outcome,
+                reason,
+                "qstash job finished"
+            ),
+        }
+    }
+}
+
+/// Traces every qstash job, keyed by its route
+pub async fn trace_qstash_job(request: Request, next: Next) -> Response {
+    let job_type = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().trim_start_matches('/').to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let payload_size_bytes = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok())
+        .and_then(|len| len.parse().ok())
+        .unwrap_or_default();
+
+    let mut trace = QStashJobTrace::start(job_type, payload_size_bytes);
+    let response = next.run(request).await;
+    trace.finish(JobOutcome::from_response(&response));
+
+    response
+}
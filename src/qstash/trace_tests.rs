@@ -0,0 +1,115 @@
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use http::StatusCode;
+
+use super::trace::{job_skipped, record_video_id, JobOutcome, QStashJobTrace};
+
+/// Log lines written while a trace is dropped
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn logged(f: impl FnOnce()) -> String {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .finish();
+
+    tracing::subscriber::with_default(subscriber, f);
+
+    let out = captured.0.lock().unwrap();
+    String::from_utf8(out.clone()).unwrap()
+}
+
+#[test]
+fn test_outcome_from_status() {
+    let ok = StatusCode::OK.into_response();
+    let err = StatusCode::INTERNAL_SERVER_ERROR.into_response();
+
+    assert_eq!(JobOutcome::from_response(&ok), JobOutcome::Success);
+    assert_eq!(
+        JobOutcome::from_response(&err),
+        JobOutcome::Failure("responded with status 500 Internal Server Error".into())
+    );
+}
+
+#[test]
+fn test_outcome_set_by_handler() {
+    let response: Response = (job_skipped("nothing to do"), Json(())).into_response();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        JobOutcome::from_response(&response),
+        JobOutcome::Skipped("nothing to do".into())
+    );
+}
+
+#[test]
+fn test_success_is_logged_on_drop() {
+    let out = logged(|| {
+        let mut trace = QStashJobTrace::start("refresh-hot-videos", 42);
+        trace.finish(JobOutcome::Success);
+    });
+
+    assert!(out.contains("INFO"));
+    assert!(out.contains("qstash job finished"));
+    assert!(out.contains("job_type=refresh-hot-videos"));
+    assert!(out.contains("payload_size_bytes=42"));
+    assert!(out.contains("outcome=\"success\""));
+    assert!(out.contains("duration_ms="));
+}
+
+#[test]
+fn test_failure_is_logged_as_error() {
+    let out = logged(|| {
+        let mut trace = QStashJobTrace::start("archive-old-events", 0);
+        trace.finish(JobOutcome::Failure("bigquery unavailable".into()));
+    });
+
+    assert!(out.contains("ERROR"));
+    assert!(out.contains("qstash job failed"));
+    assert!(out.contains("outcome=\"failure\""));
+    assert!(out.contains("reason=\"bigquery unavailable\""));
+}
+
+#[test]
+fn test_unfinished_job_is_logged_as_failure() {
+    let out = logged(|| {
+        let _trace = QStashJobTrace::start("backup_user_canister", 7);
+    });
+
+    assert!(out.contains("ERROR"));
+    assert!(out.contains("reason=\"job did not complete\""));
+}
+
+#[test]
+fn test_video_id_is_recorded_on_span() {
+    #[tracing::instrument(fields(video_id = tracing::field::Empty))]
+    fn video_job(payload_size: usize, video_id: &str) {
+        record_video_id(video_id);
+        tracing::info!("processing");
+    }
+
+    let out = logged(|| video_job(42, "abc123"));
+
+    assert!(out.contains("video_job{payload_size=42 video_id=\"abc123\"}"));
+}
@@ -34,7 +34,8 @@ pub async fn verify_qstash_message(
         .ok_or(StatusCode::UNAUTHORIZED)?;
     let sig_str = sig.to_str().map_err(|_| StatusCode::UNAUTHORIZED)?;
 
-    let jwt = jsonwebtoken::decode::<Claims>(sig_str, &state.decoding_key, &state.validation)
+    let jwt = state
+        .decode_signature::<Claims>(sig_str)
         .map_err(|_| StatusCode::UNAUTHORIZED)?;
 
     let (parts, body) = request.into_parts();
@@ -0,0 +1,69 @@
+use crate::types::RedisPool;
+
+use super::client::{QStashClient, QStashMessageId};
+
+/// Video jobs run within hours of the upload, ids are kept a little longer
+pub const VIDEO_JOBS_TTL_SECS: i64 = 24 * 60 * 60;
+
+pub fn video_jobs_key(video_id: &str) -> String {
+    format!("qstash:video_jobs:{}", video_id)
+}
+
+/// Remembers a pending frames, NSFW or deduplication job so deleting the video can cancel it
+pub async fn track_video_job(redis_pool: &RedisPool, video_id: &str, message_id: &QStashMessageId) {
+    let res: Result<(), anyhow::Error> = async {
+        let key = video_jobs_key(video_id);
+        let mut conn = redis_pool.get().await?;
+        redis::pipe()
+            .atomic()
+            .sadd(&key, &message_id.0)
+            .expire(&key, VIDEO_JOBS_TTL_SECS)
+            .query_async::<()>(&mut *conn)
+            .await?;
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = res {
+        log::warn!(
+            "Failed to track QStash message {} of video {}: {}",
+            message_id.0,
+            video_id,
+            e
+        );
+    }
+}
+
+/// Cancels the video's tracked jobs, returns how many were cancelled
+pub async fn cancel_video_jobs(
+    redis_pool: &RedisPool,
+    qstash_client: &QStashClient,
+    video_id: &str,
+) -> Result<usize, anyhow::Error> {
+    let key = video_jobs_key(video_id);
+    let mut conn = redis_pool.get().await?;
+    let message_ids: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(&key)
+        .query_async(&mut *conn)
+        .await?;
+
+    let mut cancelled = 0;
+    for message_id in &message_ids {
+        match qstash_client.cancel_message(message_id).await {
+            Ok(()) => cancelled += 1,
+            Err(e) => log::error!(
+                "Failed to cancel QStash message {} of video {}: {}",
+                message_id,
+                video_id,
+                e
+            ),
+        }
+    }
+
+    redis::cmd("DEL")
+        .arg(&key)
+        .query_async::<()>(&mut *conn)
+        .await?;
+
+    Ok(cancelled)
+}
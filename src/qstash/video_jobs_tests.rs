@@ -0,0 +1,34 @@
+use reqwest::Url;
+
+use super::client::{cancel_message_url, PublishResponse, QStashMessageId};
+use super::video_jobs::video_jobs_key;
+
+#[test]
+fn test_publish_response_message_id() {
+    let response: PublishResponse =
+        serde_json::from_str(r#"{"messageId":"msd_1234","url":"https://example.com"}"#).unwrap();
+
+    assert_eq!(response.message_id, QStashMessageId("msd_1234".to_string()));
+}
+
+#[test]
+fn test_message_id_serializes_as_string() {
+    let id = QStashMessageId("msd_1234".to_string());
+
+    assert_eq!(serde_json::to_string(&id).unwrap(), r#""msd_1234""#);
+}
+
+#[test]
+fn test_cancel_message_url() {
+    let base_url = Url::parse("https://qstash.upstash.io/v2/").unwrap();
+
+    assert_eq!(
+        cancel_message_url(&base_url, "msd_1234").unwrap().as_str(),
+        "https://qstash.upstash.io/v2/messages/msd_1234"
+    );
+}
+
+#[test]
+fn test_video_jobs_key() {
+    assert_eq!(video_jobs_key("abc"), "qstash:video_jobs:abc");
+}
@@ -0,0 +1,238 @@
+use std::sync::Arc;
+
+use axum::{extract::State, response::Response, Json};
+use futures::StreamExt;
+use google_cloud_bigquery::{http::job::query::QueryRequest, query::row::Row as QueryRow};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    qstash::duplicate::{VideoHashDuplication, DUPLICATE_VIDEO_ID_COLUMN},
+    types::RedisPool,
+};
+
+pub const MIGRATION_COMPLETED_KEY: &str = "migration:completed";
+pub const MIGRATION_BATCH_SIZE: u64 = 1000;
+const MIGRATION_PROGRESS_LOG_INTERVAL: u64 = 100;
+const MIGRATION_CONCURRENCY: usize = 50;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrateVideohashRequest {
+    #[serde(default)]
+    pub offset: u64,
+    #[serde(default)]
+    pub batches_done: u64,
+}
+
+impl MigrateVideohashRequest {
+    /// Request for the following batch, None once a short batch signals the end of the tables
+    pub fn next(&self, fetched: usize) -> Option<Self> {
+        if (fetched as u64) < MIGRATION_BATCH_SIZE {
+            return None;
+        }
+
+        Some(Self {
+            offset: self.offset + MIGRATION_BATCH_SIZE,
+            batches_done: self.batches_done + 1,
+        })
+    }
+}
+
+/// Unique videos carry their hash, duplicate uploads get theirs from videohash_original
+pub fn migration_batch_query(offset: u64) -> String {
+    format!(
+        "SELECT video_id, videohash FROM (
+            SELECT video_id, videohash
+            FROM `hot-or-not-feed-intelligence.yral_ds.video_unique`
+            UNION DISTINCT
+            SELECT d.{duplicate_id} AS video_id, o.videohash
+            FROM `hot-or-not-feed-intelligence.yral_ds.duplicate_videos` d
+            JOIN `hot-or-not-feed-intelligence.yral_ds.videohash_original` o
+                ON o.video_id = d.{duplicate_id}
+        )
+        ORDER BY video_id
+        LIMIT {limit} OFFSET {offset}",
+        duplicate_id = DUPLICATE_VIDEO_ID_COLUMN,
+        limit = MIGRATION_BATCH_SIZE,
+        offset = offset,
+    )
+}
+
+/// Ids of the stored and of the failed video hashes
+pub fn partition_migration_results<E: std::fmt::Debug>(
+    results: Vec<(String, Result<(), E>)>,
+) -> (Vec<String>, Vec<String>) {
+    let mut migrated = Vec::with_capacity(results.len());
+    let mut failed = Vec::new();
+    for (video_id, res) in results {
+        match res {
+            Ok(()) => migrated.push(video_id),
+            Err(e) => {
+                log::error!("Failed to migrate video hash of {}: {:?}", video_id, e);
+                failed.push(video_id);
+            }
+        }
+    }
+
+    (migrated, failed)
+}
+
+pub fn should_log_progress(batches_done: u64) -> bool {
+    batches_done % MIGRATION_PROGRESS_LOG_INTERVAL == 0
+}
+
+async fn fetch_migration_batch(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+    offset: u64,
+) -> Result<Vec<(String, String)>, anyhow::Error> {
+    let request = QueryRequest {
+        query: migration_batch_query(offset),
+        ..Default::default()
+    };
+    let mut response = bigquery_client
+        .query::<QueryRow>("hot-or-not-feed-intelligence", request)
+        .await?;
+
+    let mut rows = Vec::new();
+    while let Some(row) = response.next().await? {
+        rows.push((row.column::<String>(0)?, row.column::<String>(1)?));
+    }
+
+    Ok(rows)
+}
+
+async fn filter_migrated(
+    redis_pool: &RedisPool,
+    rows: Vec<(String, String)>,
+) -> Result<Vec<(String, String)>, anyhow::Error> {
+    if rows.is_empty() {
+        return Ok(rows);
+    }
+
+    let mut conn = redis_pool.get().await?;
+    let migrated: Vec<bool> = redis::cmd("SMISMEMBER")
+        .arg(MIGRATION_COMPLETED_KEY)
+        .arg(
+            rows.iter()
+                .map(|(video_id, _)| video_id)
+                .collect::<Vec<_>>(),
+        )
+        .query_async(&mut *conn)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .zip(migrated)
+        .filter_map(|(row, done)| (!done).then_some(row))
+        .collect())
+}
+
+async fn mark_migrated(redis_pool: &RedisPool, video_ids: &[String]) -> Result<(), anyhow::Error> {
+    if video_ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut conn = redis_pool.get().await?;
+    redis::cmd("SADD")
+        .arg(MIGRATION_COMPLETED_KEY)
+        .arg(video_ids)
+        .query_async::<()>(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Copies one batch of video hashes from BigQuery into SpacetimeDB and enqueues the next batch.
+/// A batch with failed stores fails the job instead, QStash retries the same offset and only the
+/// hashes not yet migrated are stored again.
+#[instrument(skip(state))]
+pub async fn migrate_videohash_to_spacetimedb(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<MigrateVideohashRequest>,
+) -> Result<Response, StatusCode> {
+    let rows = fetch_migration_batch(&state.bigquery_client, req.offset)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Failed to fetch video hashes at offset {}: {}",
+                req.offset,
+                e
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let fetched = rows.len();
+
+    let pending = filter_migrated(&state.canister_backup_redis_pool, rows)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to read migration progress: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let duplication =
+        VideoHashDuplication::new(&state.qstash_client.client, &state.qstash_client.base_url);
+    let results = futures::stream::iter(pending)
+        .map(|(video_id, hash)| {
+            let duplication = &duplication;
+            let ctx = &state.dedup_index_ctx;
+            async move {
+                let res = duplication
+                    .store_videohash_to_spacetime(ctx, &video_id, &hash)
+                    .await;
+                (video_id, res)
+            }
+        })
+        .buffer_unordered(MIGRATION_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let (migrated, failed) = partition_migration_results(results);
+
+    mark_migrated(&state.canister_backup_redis_pool, &migrated)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to record migration progress: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if !failed.is_empty() {
+        log::error!(
+            "Videohash migration stopped at offset {}, {} hashes failed: {:?}",
+            req.offset,
+            failed.len(),
+            failed
+        );
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let next = req.next(fetched);
+    let batches_done = req.batches_done + 1;
+    if should_log_progress(batches_done) || next.is_none() {
+        log::info!(
+            "Videohash migration: {} batches done, offset {}",
+            batches_done,
+            req.offset + fetched as u64
+        );
+    }
+
+    match next {
+        Some(next) => {
+            state
+                .qstash_client
+                .publish_migrate_videohash_to_spacetimedb(&next)
+                .await
+                .map_err(|e| {
+                    log::error!("Failed to enqueue next migration batch: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+        }
+        None => log::info!("Videohash migration to SpacetimeDB completed"),
+    }
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(format!("Migrated {} of {} video hashes", migrated.len(), fetched).into())
+        .unwrap())
+}
@@ -0,0 +1,68 @@
+use super::videohash_migration::{
+    migration_batch_query, partition_migration_results, should_log_progress,
+    MigrateVideohashRequest, MIGRATION_BATCH_SIZE,
+};
+
+#[test]
+fn test_full_batch_enqueues_next_offset() {
+    let req = MigrateVideohashRequest {
+        offset: 2000,
+        batches_done: 2,
+    };
+
+    assert_eq!(
+        req.next(MIGRATION_BATCH_SIZE as usize),
+        Some(MigrateVideohashRequest {
+            offset: 3000,
+            batches_done: 3,
+        })
+    );
+}
+
+#[test]
+fn test_short_batch_finishes_migration() {
+    let req = MigrateVideohashRequest::default();
+
+    assert_eq!(req.next(999), None);
+    assert_eq!(req.next(0), None);
+}
+
+#[test]
+fn test_empty_body_starts_from_beginning() {
+    let req: MigrateVideohashRequest = serde_json::from_str("{}").unwrap();
+
+    assert_eq!(req, MigrateVideohashRequest::default());
+}
+
+#[test]
+fn test_batch_query_reads_both_tables() {
+    let query = migration_batch_query(5000);
+
+    assert!(query.contains("yral_ds.video_unique"));
+    assert!(query.contains("yral_ds.duplicate_videos"));
+    assert!(query.contains("SELECT d.original_video_id AS video_id, o.videohash"));
+    assert!(query.contains("ON o.video_id = d.original_video_id"));
+    assert!(!query.contains("parent_video_id"));
+    assert!(query.contains("ORDER BY video_id"));
+    assert!(query.contains("LIMIT 1000 OFFSET 5000"));
+}
+
+#[test]
+fn test_progress_logged_every_hundred_batches() {
+    assert!(should_log_progress(100));
+    assert!(should_log_progress(300));
+    assert!(!should_log_progress(1));
+    assert!(!should_log_progress(199));
+}
+
+#[test]
+fn test_failed_stores_are_reported() {
+    let (migrated, failed) = partition_migration_results(vec![
+        ("video-1".to_string(), Ok(())),
+        ("video-2".to_string(), Err("stdb unavailable")),
+        ("video-3".to_string(), Ok(())),
+    ]);
+
+    assert_eq!(migrated, vec!["video-1", "video-3"]);
+    assert_eq!(failed, vec!["video-2"]);
+}
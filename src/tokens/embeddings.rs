@@ -0,0 +1,384 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    Json,
+};
+use candid::Principal;
+use google_cloud_bigquery::http::{
+    job::query::QueryRequest,
+    query::{QueryParameter, QueryParameterType, QueryParameterValue},
+};
+#[cfg(not(feature = "local-bin"))]
+use google_cloud_bigquery::query::row::Row as QueryRow;
+#[cfg(not(feature = "local-bin"))]
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+#[cfg(not(feature = "local-bin"))]
+use crate::utils::geoip::client_ip;
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_events, AuthBearer},
+    posts::engagement::named_parameter,
+    utils::rate_limit::RateLimit,
+    AppError,
+};
+
+pub const TOKEN_EMBEDDINGS_TABLE: &str = "hot-or-not-feed-intelligence.icpumpfun.token_embeddings";
+/// Remote Vertex AI text embedding model, the one token_metadata_v1 embeddings come from
+pub const TEXT_EMBED_MODEL: &str = "hot-or-not-feed-intelligence.icpumpfun.text_embed";
+pub const TOKEN_EMBEDDING_DIMENSIONS: u32 = 256;
+pub const DEFAULT_TOKEN_SEARCH_LIMIT: u32 = 10;
+pub const MAX_TOKEN_SEARCH_LIMIT: u32 = 50;
+/// Embeddings of search queries are reused for this long, popular queries are embedded once
+pub const QUERY_EMBEDDING_CACHE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+/// Searches per client ip, each uncached query is a Vertex AI call
+pub const TOKEN_SEARCH_RATE_LIMIT: RateLimit = RateLimit {
+    name: "token_search",
+    max_requests: 30,
+    window_secs: 60,
+};
+
+/// Safe to run on every start
+pub fn token_embeddings_table_ddl() -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS `{}` (
+            token_root STRING,
+            embedding ARRAY<FLOAT64>,
+            created_at TIMESTAMP
+        )",
+        TOKEN_EMBEDDINGS_TABLE
+    )
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IndexTokenMetadataRequest {
+    pub token_root: Principal,
+    pub description: String,
+    pub token_symbol: String,
+}
+
+/// `ML.GENERATE_EMBEDDING` of `content` as a 256 dimension `embedding` column
+fn generate_embedding_sql(content: &str, task_type: &str) -> String {
+    format!(
+        "SELECT
+            ARRAY(
+                SELECT CAST(JSON_VALUE(value, '$') AS FLOAT64)
+                FROM UNNEST(JSON_EXTRACT_ARRAY(ml_generate_embedding_result.predictions[0].embeddings.values)) AS value
+            ) AS embedding
+        FROM ML.GENERATE_EMBEDDING(
+            MODEL `{}`,
+            (SELECT {} AS content),
+            STRUCT(FALSE AS flatten_json_output, '{}' AS task_type, {} AS output_dimensionality)
+        )",
+        TEXT_EMBED_MODEL, content, task_type, TOKEN_EMBEDDING_DIMENSIONS
+    )
+}
+
+/// Embeds the symbol and description and upserts the token's row, reindexing a token replaces
+/// its embedding. Values are passed as named parameters, never formatted into the query.
+pub fn index_token_embedding_request(req: &IndexTokenMetadataRequest) -> QueryRequest {
+    let query = format!(
+        "MERGE `{}` AS target
+        USING (
+            SELECT @token_root AS token_root, embedding
+            FROM ({})
+        ) AS source
+        ON target.token_root = source.token_root
+        WHEN MATCHED THEN
+            UPDATE SET embedding = source.embedding, created_at = CURRENT_TIMESTAMP()
+        WHEN NOT MATCHED THEN
+            INSERT (token_root, embedding, created_at)
+            VALUES (source.token_root, source.embedding, CURRENT_TIMESTAMP())",
+        TOKEN_EMBEDDINGS_TABLE,
+        generate_embedding_sql(
+            "CONCAT(@token_symbol, ': ', @description)",
+            "RETRIEVAL_DOCUMENT"
+        ),
+    );
+
+    QueryRequest {
+        query,
+        parameter_mode: Some("NAMED".to_string()),
+        query_parameters: vec![
+            named_parameter("token_root", "STRING", req.token_root.to_text()),
+            named_parameter("token_symbol", "STRING", req.token_symbol.clone()),
+            named_parameter("description", "STRING", req.description.clone()),
+        ],
+        ..Default::default()
+    }
+}
+
+pub fn query_embedding_cache_key(q: &str) -> String {
+    format!(
+        "token_search_embedding:{}",
+        blake3::hash(q.as_bytes()).to_hex()
+    )
+}
+
+/// Embedding of the search query `q`
+pub fn query_embedding_request(q: &str) -> QueryRequest {
+    QueryRequest {
+        query: generate_embedding_sql("@q", "RETRIEVAL_QUERY"),
+        parameter_mode: Some("NAMED".to_string()),
+        query_parameters: vec![named_parameter("q", "STRING", q.to_string())],
+        ..Default::default()
+    }
+}
+
+fn float_array_parameter(name: &str, values: &[f64]) -> QueryParameter {
+    QueryParameter {
+        name: Some(name.to_string()),
+        parameter_type: QueryParameterType {
+            parameter_type: "ARRAY".to_string(),
+            array_type: Some(Box::new(QueryParameterType {
+                parameter_type: "FLOAT64".to_string(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        },
+        parameter_value: QueryParameterValue {
+            array_values: Some(
+                values
+                    .iter()
+                    .map(|value| QueryParameterValue {
+                        value: Some(value.to_string()),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        },
+    }
+}
+
+/// Nearest tokens to the query embedding by cosine distance, `top_k` only takes a literal
+pub fn token_search_request(embedding: &[f64], limit: u32) -> QueryRequest {
+    let query = format!(
+        "SELECT base.token_root, distance
+        FROM VECTOR_SEARCH(
+            TABLE `{}`,
+            'embedding',
+            (SELECT @embedding AS embedding),
+            'embedding',
+            top_k => {},
+            distance_type => 'COSINE'
+        )
+        ORDER BY distance",
+        TOKEN_EMBEDDINGS_TABLE, limit,
+    );
+
+    QueryRequest {
+        query,
+        parameter_mode: Some("NAMED".to_string()),
+        query_parameters: vec![float_array_parameter("embedding", embedding)],
+        ..Default::default()
+    }
+}
+
+#[cfg(not(feature = "local-bin"))]
+#[instrument(skip(bigquery_client))]
+pub async fn create_token_embeddings_table(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+) -> Result<(), anyhow::Error> {
+    let request = QueryRequest {
+        query: token_embeddings_table_ddl(),
+        ..Default::default()
+    };
+    bigquery_client
+        .job()
+        .query("hot-or-not-feed-intelligence", &request)
+        .await?;
+
+    Ok(())
+}
+
+/// Embeds a token's description for semantic search
+#[instrument(skip(state))]
+pub async fn index_token_metadata_to_vector_db(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<IndexTokenMetadataRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    #[cfg(not(feature = "local-bin"))]
+    {
+        state
+            .bigquery_client
+            .job()
+            .query(
+                "hot-or-not-feed-intelligence",
+                &index_token_embedding_request(&req),
+            )
+            .await
+            .map_err(|e| AppError::BigQueryError(e.into()))?;
+
+        log::info!("Indexed token {} for semantic search", req.token_root);
+
+        Ok(Json(serde_json::json!({ "message": "Token indexed" })))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, req);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TokenSearchParams {
+    /// What the token is about, in natural language
+    pub q: String,
+    /// At most 50, defaults to 10
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct TokenSearchResult {
+    pub token_root: String,
+    /// Cosine distance to the query, lower is closer
+    pub distance: f64,
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn embed_query(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+    q: &str,
+) -> Result<Vec<f64>, anyhow::Error> {
+    let mut response = bigquery_client
+        .query::<QueryRow>("hot-or-not-feed-intelligence", query_embedding_request(q))
+        .await?;
+
+    let row = response
+        .next()
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No embedding generated for the query"))?;
+
+    Ok(row.column::<Vec<f64>>(0)?)
+}
+
+/// Embedding of `q` from the cache, generated and cached on a miss. Cache errors are logged and
+/// treated as a miss.
+#[cfg(not(feature = "local-bin"))]
+async fn query_embedding(state: &AppState, q: &str) -> Result<Vec<f64>, anyhow::Error> {
+    let key = query_embedding_cache_key(q);
+    let cached: Option<Vec<f64>> = async {
+        let mut conn = state.canister_backup_redis_pool.get().await.ok()?;
+        let cached: Option<String> = conn.get(&key).await.ok()?;
+        cached.and_then(|cached| serde_json::from_str(&cached).ok())
+    }
+    .await;
+    if let Some(embedding) = cached {
+        return Ok(embedding);
+    }
+
+    let embedding = embed_query(&state.bigquery_client, q).await?;
+
+    let res = async {
+        let mut conn = state.canister_backup_redis_pool.get().await?;
+        conn.set_ex::<_, _, ()>(
+            &key,
+            serde_json::to_string(&embedding)?,
+            QUERY_EMBEDDING_CACHE_TTL_SECS,
+        )
+        .await?;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    if let Err(e) = res {
+        log::warn!("Failed to cache query embedding {}: {}", key, e);
+    }
+
+    Ok(embedding)
+}
+
+#[cfg(not(feature = "local-bin"))]
+async fn search_tokens(
+    bigquery_client: &google_cloud_bigquery::client::Client,
+    embedding: &[f64],
+    limit: u32,
+) -> Result<Vec<TokenSearchResult>, anyhow::Error> {
+    let mut response = bigquery_client
+        .query::<QueryRow>(
+            "hot-or-not-feed-intelligence",
+            token_search_request(embedding, limit),
+        )
+        .await?;
+
+    let mut results = Vec::new();
+    while let Some(row) = response.next().await? {
+        results.push(TokenSearchResult {
+            token_root: row.column::<String>(0)?,
+            distance: row.column::<f64>(1)?,
+        });
+    }
+
+    Ok(results)
+}
+
+#[utoipa::path(
+    get,
+    path = "/search",
+    params(TokenSearchParams),
+    tag = "tokens",
+    responses(
+        (status = 200, description = "Tokens with descriptions closest in meaning to the query", body = Vec<TokenSearchResult>),
+        (status = 400, description = "Empty query"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many searches from the client"),
+        (status = 500, description = "Internal server error"),
+    )
+)]
+#[instrument(skip(state, token, headers))]
+pub async fn handle_token_search(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    headers: HeaderMap,
+    Query(params): Query<TokenSearchParams>,
+) -> Result<Json<Vec<TokenSearchResult>>, AppError> {
+    check_auth_events(Some(token)).map_err(|_| AppError::Unauthorized)?;
+
+    let q = params.q.trim();
+    if q.is_empty() {
+        return Err(AppError::InvalidInput("Empty query".to_string()));
+    }
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_TOKEN_SEARCH_LIMIT)
+        .clamp(1, MAX_TOKEN_SEARCH_LIMIT);
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        let caller = client_ip(&headers)
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        TOKEN_SEARCH_RATE_LIMIT
+            .check(
+                &state.canister_backup_redis_pool,
+                &caller,
+                chrono::Utc::now().timestamp() as u64,
+            )
+            .await?;
+
+        let embedding = query_embedding(&state, q).await.map_err(|e| {
+            log::error!("Failed to embed search query {:?}: {}", q, e);
+            AppError::BigQueryError(e)
+        })?;
+        let results = search_tokens(&state.bigquery_client, &embedding, limit)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to search tokens for {:?}: {}", q, e);
+                AppError::BigQueryError(e)
+            })?;
+
+        Ok(Json(results))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, headers, limit);
+        Ok(Json(vec![]))
+    }
+}
@@ -0,0 +1,156 @@
+use candid::Principal;
+use google_cloud_bigquery::http::job::query::QueryRequest;
+
+use super::embeddings::{
+    index_token_embedding_request, query_embedding_cache_key, query_embedding_request,
+    token_embeddings_table_ddl, token_search_request, IndexTokenMetadataRequest, TEXT_EMBED_MODEL,
+    TOKEN_EMBEDDINGS_TABLE,
+};
+
+fn index_request() -> IndexTokenMetadataRequest {
+    IndexTokenMetadataRequest {
+        token_root: Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap(),
+        description: "a \"community\" token for cat lovers'); DROP TABLE x; --".into(),
+        token_symbol: "MEOW".into(),
+    }
+}
+
+fn parameters(request: &QueryRequest) -> Vec<(&str, &str, &str)> {
+    request
+        .query_parameters
+        .iter()
+        .map(|p| {
+            (
+                p.name.as_deref().unwrap(),
+                p.parameter_type.parameter_type.as_str(),
+                p.parameter_value.value.as_deref().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_table_ddl_matches_schema() {
+    let ddl = token_embeddings_table_ddl();
+
+    assert!(ddl.starts_with(&format!(
+        "CREATE TABLE IF NOT EXISTS `{}`",
+        TOKEN_EMBEDDINGS_TABLE
+    )));
+    for column in [
+        "token_root STRING",
+        "embedding ARRAY<FLOAT64>",
+        "created_at TIMESTAMP",
+    ] {
+        assert!(ddl.contains(column));
+    }
+}
+
+#[test]
+fn test_index_request_upserts_token_embedding() {
+    let request = index_token_embedding_request(&index_request());
+
+    assert!(request
+        .query
+        .starts_with(&format!("MERGE `{}`", TOKEN_EMBEDDINGS_TABLE)));
+    assert!(request
+        .query
+        .contains("ON target.token_root = source.token_root"));
+    assert!(request.query.contains("WHEN MATCHED THEN"));
+    assert!(request.query.contains("WHEN NOT MATCHED THEN"));
+    assert!(request
+        .query
+        .contains(&format!("MODEL `{}`", TEXT_EMBED_MODEL)));
+    assert!(request.query.contains("'RETRIEVAL_DOCUMENT' AS task_type"));
+    assert!(request.query.contains("256 AS output_dimensionality"));
+}
+
+#[test]
+fn test_index_request_passes_values_as_parameters() {
+    let req = index_request();
+    let request = index_token_embedding_request(&req);
+
+    assert_eq!(request.parameter_mode.as_deref(), Some("NAMED"));
+    assert!(request
+        .query
+        .contains("CONCAT(@token_symbol, ': ', @description)"));
+    assert!(!request.query.contains("MEOW"));
+    assert!(!request.query.contains("DROP TABLE"));
+    assert_eq!(
+        parameters(&request),
+        vec![
+            ("token_root", "STRING", "rrkah-fqaaa-aaaaa-aaaaq-cai"),
+            ("token_symbol", "STRING", "MEOW"),
+            ("description", "STRING", req.description.as_str()),
+        ]
+    );
+}
+
+#[test]
+fn test_query_embedding_request_embeds_query_text() {
+    let request = query_embedding_request("cats");
+
+    assert!(request.query.contains("(SELECT @q AS content)"));
+    assert!(request.query.contains("'RETRIEVAL_QUERY' AS task_type"));
+    assert!(request.query.contains("256 AS output_dimensionality"));
+    assert_eq!(parameters(&request), vec![("q", "STRING", "cats")]);
+}
+
+#[test]
+fn test_search_request_passes_embedding_as_parameter() {
+    let request = token_search_request(&[0.5, -0.25], 5);
+
+    assert!(request
+        .query
+        .contains(&format!("TABLE `{}`", TOKEN_EMBEDDINGS_TABLE)));
+    assert!(request.query.contains("(SELECT @embedding AS embedding)"));
+    assert!(!request.query.contains("ML.GENERATE_EMBEDDING"));
+    assert!(request.query.contains("top_k => 5"));
+    assert!(request.query.contains("distance_type => 'COSINE'"));
+
+    let [param] = &request.query_parameters[..] else {
+        panic!("expected one parameter");
+    };
+    assert_eq!(param.name.as_deref(), Some("embedding"));
+    assert_eq!(param.parameter_type.parameter_type, "ARRAY");
+    assert_eq!(
+        param
+            .parameter_type
+            .array_type
+            .as_ref()
+            .map(|t| t.parameter_type.as_str()),
+        Some("FLOAT64")
+    );
+    let values: Vec<_> = param
+        .parameter_value
+        .array_values
+        .iter()
+        .flatten()
+        .map(|v| v.value.as_deref().unwrap())
+        .collect();
+    assert_eq!(values, vec!["0.5", "-0.25"]);
+}
+
+#[test]
+fn test_query_embedding_cache_key_hashes_the_query() {
+    assert_eq!(
+        query_embedding_cache_key("cats"),
+        format!("token_search_embedding:{}", blake3::hash(b"cats").to_hex())
+    );
+    assert_ne!(
+        query_embedding_cache_key("cats"),
+        query_embedding_cache_key("dogs")
+    );
+}
+
+#[test]
+fn test_index_request_round_trips_as_json() {
+    let req = index_request();
+    let json = serde_json::to_value(&req).unwrap();
+
+    assert_eq!(json["token_symbol"], "MEOW");
+    assert_eq!(
+        serde_json::from_value::<IndexTokenMetadataRequest>(json).unwrap(),
+        req
+    );
+}
@@ -0,0 +1,16 @@
+pub mod embeddings;
+
+#[cfg(test)]
+mod embeddings_tests;
+
+use std::sync::Arc;
+
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::app_state::AppState;
+
+pub fn tokens_router(state: Arc<AppState>) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(embeddings::handle_token_search))
+        .with_state(state)
+}
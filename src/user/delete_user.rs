@@ -17,8 +17,6 @@ use crate::{
     utils::delegated_identity::get_user_info_from_delegated_identity_wire,
 };
 
-use super::utils::get_agent_from_delegated_identity_wire;
-
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct DeleteUserRequest {
     pub delegated_identity_wire: DelegatedIdentityWire,
@@ -54,8 +52,8 @@ pub async fn handle_delete_user(
     let user_principal = user_info.user_principal;
     let user_canister = user_info.user_canister;
 
-    let agent = get_agent_from_delegated_identity_wire(&request.delegated_identity_wire)
-        .await
+    let agent = state
+        .get_pooled_agent(&request.delegated_identity_wire)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     // 1. Get all posts for the user from canister
@@ -1,6 +1,10 @@
 pub mod delete_user;
+pub mod orphaned_keys;
 pub mod utils;
 
+#[cfg(test)]
+mod orphaned_keys_tests;
+
 use std::sync::Arc;
 
 use utoipa_axum::{router::OpenApiRouter, routes};
@@ -0,0 +1,152 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{extract::State, Json};
+use candid::Principal;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    app_state::AppState,
+    auth::{check_auth_admin, AuthBearer},
+    types::RedisPool,
+    AppError,
+};
+
+pub const MAX_CLEANUP_CANISTERS: usize = 10;
+const SCAN_COUNT: usize = 1000;
+
+/// Keys of a user's ML feed cache, `{canister_id}:...` as well as the crate's
+/// `{canister_id}{SUFFIX}` keys whose suffixes start with `_`.
+/// Principal texts never contain glob characters.
+pub fn canister_key_pattern(canister_id: Principal) -> String {
+    format!("{}[:_]*", canister_id)
+}
+
+/// Same keys as [`canister_key_pattern`]
+pub fn is_canister_key(canister_id: Principal, key: &str) -> bool {
+    key.strip_prefix(&canister_id.to_text())
+        .is_some_and(|rest| rest.starts_with([':', '_']))
+}
+
+pub(crate) trait KeyStore {
+    /// One `SCAN` page, `(next_cursor, keys)` with cursor 0 once the scan is done
+    async fn scan_keys(
+        &self,
+        cursor: u64,
+        pattern: &str,
+    ) -> Result<(u64, Vec<String>), anyhow::Error>;
+
+    /// Returns the number of keys that existed
+    async fn delete_keys(&self, keys: &[String]) -> Result<usize, anyhow::Error>;
+}
+
+impl KeyStore for RedisPool {
+    async fn scan_keys(
+        &self,
+        cursor: u64,
+        pattern: &str,
+    ) -> Result<(u64, Vec<String>), anyhow::Error> {
+        let mut conn = self.get().await?;
+        let page = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(SCAN_COUNT)
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(page)
+    }
+
+    async fn delete_keys(&self, keys: &[String]) -> Result<usize, anyhow::Error> {
+        if keys.is_empty() {
+            return Ok(0);
+        }
+
+        let mut conn = self.get().await?;
+        let mut pipe = redis::pipe();
+        for key in keys {
+            pipe.del(key);
+        }
+        let deleted: Vec<usize> = pipe.query_async(&mut *conn).await?;
+
+        Ok(deleted.into_iter().sum())
+    }
+}
+
+/// Scans every key of the canister and deletes them page by page, returns the number deleted
+pub async fn cleanup_canister_keys(
+    store: &impl KeyStore,
+    canister_id: Principal,
+) -> Result<usize, anyhow::Error> {
+    let pattern = canister_key_pattern(canister_id);
+    let mut deleted = 0;
+    let mut cursor = 0;
+    loop {
+        let (next, keys) = store.scan_keys(cursor, &pattern).await?;
+        deleted += store.delete_keys(&keys).await?;
+
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+
+    Ok(deleted)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CleanupOrphanedKeysRequest {
+    pub canister_ids: Vec<Principal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CleanupOrphanedKeysResponse {
+    /// Keys deleted per canister id
+    pub deleted: BTreeMap<String, usize>,
+}
+
+pub async fn cleanup_orphaned_keys(
+    store: &impl KeyStore,
+    canister_ids: &[Principal],
+) -> Result<CleanupOrphanedKeysResponse, AppError> {
+    if canister_ids.len() > MAX_CLEANUP_CANISTERS {
+        return Err(AppError::InvalidInput(format!(
+            "At most {} canisters per request",
+            MAX_CLEANUP_CANISTERS
+        )));
+    }
+
+    let mut deleted = BTreeMap::new();
+    for canister_id in canister_ids {
+        let count = cleanup_canister_keys(store, *canister_id).await?;
+        log::info!("Deleted {} orphaned redis keys of {}", count, canister_id);
+        deleted.insert(canister_id.to_text(), count);
+    }
+
+    Ok(CleanupOrphanedKeysResponse { deleted })
+}
+
+/// Removes the ML feed cache keys of deleted users whose cleanup failed
+#[instrument(skip(state, token))]
+pub async fn cleanup_orphaned_redis_keys_handler(
+    State(state): State<Arc<AppState>>,
+    AuthBearer(token): AuthBearer,
+    Json(req): Json<CleanupOrphanedKeysRequest>,
+) -> Result<Json<CleanupOrphanedKeysResponse>, AppError> {
+    check_auth_admin(&token).map_err(|_| AppError::Unauthorized)?;
+
+    #[cfg(not(feature = "local-bin"))]
+    {
+        Ok(Json(
+            cleanup_orphaned_keys(&state.ml_feed_cache.redis_pool, &req.canister_ids).await?,
+        ))
+    }
+
+    #[cfg(feature = "local-bin")]
+    {
+        let _ = (state, req);
+        Err(anyhow::anyhow!("not implemented for local binary").into())
+    }
+}
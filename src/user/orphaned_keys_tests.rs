@@ -0,0 +1,150 @@
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+};
+
+use candid::Principal;
+
+use super::orphaned_keys::{
+    canister_key_pattern, cleanup_canister_keys, cleanup_orphaned_keys, is_canister_key, KeyStore,
+    MAX_CLEANUP_CANISTERS,
+};
+use crate::AppError;
+
+/// Redis whose `SCAN` returns `page_size` keys at a time, the cursor being the index of the
+/// next seeded key so deletes during the scan do not move it
+#[derive(Clone)]
+struct MockRedis {
+    seeded: Vec<String>,
+    deleted: Arc<Mutex<BTreeSet<String>>>,
+    page_size: usize,
+    scans: Arc<Mutex<usize>>,
+}
+
+impl MockRedis {
+    fn seeded(keys: &[String], page_size: usize) -> Self {
+        let seeded: BTreeSet<String> = keys.iter().cloned().collect();
+        Self {
+            seeded: seeded.into_iter().collect(),
+            deleted: Arc::default(),
+            page_size,
+            scans: Arc::default(),
+        }
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let deleted = self.deleted.lock().unwrap();
+        self.seeded
+            .iter()
+            .filter(|key| !deleted.contains(*key))
+            .cloned()
+            .collect()
+    }
+}
+
+impl KeyStore for MockRedis {
+    async fn scan_keys(
+        &self,
+        cursor: u64,
+        pattern: &str,
+    ) -> Result<(u64, Vec<String>), anyhow::Error> {
+        *self.scans.lock().unwrap() += 1;
+        let prefix = pattern.strip_suffix("[:_]*").unwrap();
+        let deleted = self.deleted.lock().unwrap();
+        let start = cursor as usize;
+        let end = (start + self.page_size).min(self.seeded.len());
+        let next = if end == self.seeded.len() {
+            0
+        } else {
+            end as u64
+        };
+        let page = self.seeded[start..end]
+            .iter()
+            .filter(|key| !deleted.contains(*key))
+            .filter(|key| {
+                key.strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with([':', '_']))
+            })
+            .cloned()
+            .collect();
+
+        Ok((next, page))
+    }
+
+    async fn delete_keys(&self, keys: &[String]) -> Result<usize, anyhow::Error> {
+        let mut deleted = self.deleted.lock().unwrap();
+        Ok(keys
+            .iter()
+            .filter(|key| self.seeded.contains(*key) && deleted.insert((*key).clone()))
+            .count())
+    }
+}
+
+fn canister(n: u64) -> Principal {
+    Principal::from_slice(&n.to_be_bytes())
+}
+
+fn keys_of(canister_id: Principal, suffixes: &[&str]) -> Vec<String> {
+    suffixes
+        .iter()
+        .map(|suffix| format!("{}{}", canister_id, suffix))
+        .collect()
+}
+
+#[test]
+fn test_pattern_matches_colon_and_suffix_keys() {
+    let c = canister(1);
+
+    assert_eq!(canister_key_pattern(c), format!("{}[:_]*", c));
+    assert!(is_canister_key(c, &format!("{}:feed", c)));
+    assert!(is_canister_key(c, &format!("{}_watch_clean_v2", c)));
+    assert!(!is_canister_key(c, &c.to_text()));
+    assert!(!is_canister_key(c, &format!("{}x:feed", c)));
+    assert!(!is_canister_key(c, &format!("{}:feed", canister(2))));
+}
+
+#[tokio::test]
+async fn test_cleanup_follows_cursor_across_pages() {
+    let c = canister(1);
+    let other = canister(2);
+    let mut seeded = keys_of(c, &[":a", ":b", ":c", "_watch_clean_v2", "_watch_nsfw_v2"]);
+    seeded.extend(keys_of(other, &[":a", "_watch_clean_v2"]));
+    seeded.push("videohash_index".to_string());
+    let redis = MockRedis::seeded(&seeded, 2);
+
+    let deleted = cleanup_canister_keys(&redis, c).await.unwrap();
+
+    assert_eq!(deleted, 5);
+    assert!(*redis.scans.lock().unwrap() > 1);
+    let mut expected = keys_of(other, &[":a", "_watch_clean_v2"]);
+    expected.push("videohash_index".to_string());
+    expected.sort();
+    assert_eq!(redis.keys(), expected);
+}
+
+#[tokio::test]
+async fn test_cleanup_returns_counts_per_canister() {
+    let (a, b, c) = (canister(1), canister(2), canister(3));
+    let mut seeded = keys_of(a, &[":x", ":y"]);
+    seeded.extend(keys_of(b, &["_watch_clean_v2"]));
+    let redis = MockRedis::seeded(&seeded, 10);
+
+    let res = cleanup_orphaned_keys(&redis, &[a, b, c]).await.unwrap();
+
+    assert_eq!(res.deleted.get(&a.to_text()), Some(&2));
+    assert_eq!(res.deleted.get(&b.to_text()), Some(&1));
+    assert_eq!(res.deleted.get(&c.to_text()), Some(&0));
+    assert!(redis.keys().is_empty());
+}
+
+#[tokio::test]
+async fn test_cleanup_rejects_more_than_max_canisters() {
+    let seeded = keys_of(canister(1), &[":x"]);
+    let redis = MockRedis::seeded(&seeded, 10);
+    let canister_ids: Vec<_> = (0..=MAX_CLEANUP_CANISTERS as u64).map(canister).collect();
+
+    let res = cleanup_orphaned_keys(&redis, &canister_ids).await;
+
+    assert!(matches!(res, Err(AppError::InvalidInput(_))));
+    assert_eq!(redis.keys().len(), seeded.len());
+}
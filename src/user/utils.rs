@@ -2,7 +2,8 @@ use ic_agent::{identity::DelegatedIdentity, Agent};
 
 use crate::types::DelegatedIdentityWire;
 
-pub async fn get_agent_from_delegated_identity_wire(
+/// Builds a new agent for the identity, handlers get theirs from `AppState::get_pooled_agent`
+pub fn get_agent_from_delegated_identity_wire(
     identity_wire: &DelegatedIdentityWire,
 ) -> Result<Agent, anyhow::Error> {
     let identity: DelegatedIdentity = DelegatedIdentity::try_from(identity_wire.clone())
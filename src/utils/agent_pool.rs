@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+use ic_agent::Agent;
+
+use crate::types::DelegatedIdentityWire;
+
+/// Pooled agents older than this are rebuilt
+pub const POOLED_AGENT_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Agents built from delegated identities, reused across requests of the same identity
+#[derive(Default)]
+pub struct DelegatedIdentityPool {
+    agents: DashMap<String, (Agent, Instant)>,
+}
+
+impl DelegatedIdentityPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pool_key(identity_wire: &DelegatedIdentityWire) -> Result<String, anyhow::Error> {
+        let bytes = serde_json::to_vec(identity_wire)?;
+        Ok(blake3::hash(&bytes).to_hex().to_string())
+    }
+
+    pub fn len(&self) -> usize {
+        self.agents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+
+    /// Returns the pooled agent for `key`, or one built by `build` if missing or expired.
+    /// The shard stays locked while building, so concurrent callers with the same key build once.
+    pub fn get_or_insert_with<F>(&self, key: String, build: F) -> Result<Agent, anyhow::Error>
+    where
+        F: FnOnce() -> Result<Agent, anyhow::Error>,
+    {
+        let now = Instant::now();
+        let agent = match self.agents.entry(key) {
+            Entry::Occupied(entry) if now.duration_since(entry.get().1) < POOLED_AGENT_TTL => {
+                return Ok(entry.get().0.clone());
+            }
+            Entry::Occupied(mut entry) => {
+                let agent = build()?;
+                entry.insert((agent.clone(), now));
+                agent
+            }
+            Entry::Vacant(entry) => {
+                let agent = build()?;
+                entry.insert((agent.clone(), now));
+                agent
+            }
+        };
+
+        // misses are the rare path, a good time to drop identities that stopped calling
+        self.evict_expired(now);
+
+        Ok(agent)
+    }
+
+    pub fn evict_expired(&self, now: Instant) {
+        self.agents
+            .retain(|_, (_, created_at)| now.duration_since(*created_at) < POOLED_AGENT_TTL);
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use ic_agent::Agent;
+
+use super::agent_pool::{DelegatedIdentityPool, POOLED_AGENT_TTL};
+
+fn build_agent(builds: &AtomicUsize) -> Result<Agent, anyhow::Error> {
+    builds.fetch_add(1, Ordering::SeqCst);
+    Ok(Agent::builder().with_url("https://ic0.app").build()?)
+}
+
+#[test]
+fn test_concurrent_requests_of_one_identity_share_an_agent() {
+    let pool = DelegatedIdentityPool::new();
+    let builds = AtomicUsize::new(0);
+
+    std::thread::scope(|s| {
+        for _ in 0..16 {
+            s.spawn(|| {
+                for _ in 0..10 {
+                    pool.get_or_insert_with("identity".to_string(), || build_agent(&builds))
+                        .unwrap();
+                }
+            });
+        }
+    });
+
+    assert_eq!(builds.load(Ordering::SeqCst), 1);
+    assert_eq!(pool.len(), 1);
+}
+
+#[test]
+fn test_identities_get_their_own_agents() {
+    let pool = DelegatedIdentityPool::new();
+    let builds = AtomicUsize::new(0);
+
+    for key in ["a", "b", "a", "b"] {
+        pool.get_or_insert_with(key.to_string(), || build_agent(&builds))
+            .unwrap();
+    }
+
+    assert_eq!(builds.load(Ordering::SeqCst), 2);
+    assert_eq!(pool.len(), 2);
+}
+
+#[test]
+fn test_failed_build_is_not_pooled() {
+    let pool = DelegatedIdentityPool::new();
+
+    let res = pool.get_or_insert_with("identity".to_string(), || {
+        Err(anyhow::anyhow!("invalid identity"))
+    });
+
+    assert!(res.is_err());
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn test_expired_agents_are_evicted() {
+    let pool = DelegatedIdentityPool::new();
+    let builds = AtomicUsize::new(0);
+    pool.get_or_insert_with("identity".to_string(), || build_agent(&builds))
+        .unwrap();
+
+    pool.evict_expired(Instant::now() + POOLED_AGENT_TTL);
+
+    assert!(pool.is_empty());
+}
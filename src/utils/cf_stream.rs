@@ -0,0 +1,8 @@
+use crate::config::CLOUDFLARE_CONFIG;
+
+pub fn stream_manifest_url(video_id: &str) -> String {
+    format!(
+        "https://{}/{}/manifest/video.m3u8",
+        CLOUDFLARE_CONFIG.stream_customer_domain, video_id
+    )
+}
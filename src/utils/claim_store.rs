@@ -0,0 +1,37 @@
+use crate::types::RedisPool;
+
+/// Keys claimed for a while, so an operation runs once per key. Shared by the nonce and the
+/// event dedup caches.
+pub(crate) trait ClaimStore {
+    /// Stores the key for `ttl_secs` unless it exists, returns false if it did
+    async fn set_if_absent(&self, key: &str, ttl_secs: u64) -> Result<bool, anyhow::Error>;
+
+    /// Frees the key before it expires, for operations that failed and may run again
+    async fn remove(&self, key: &str) -> Result<(), anyhow::Error>;
+}
+
+impl ClaimStore for RedisPool {
+    async fn set_if_absent(&self, key: &str, ttl_secs: u64) -> Result<bool, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let set: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs)
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(set.is_some())
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), anyhow::Error> {
+        let mut conn = self.get().await?;
+        redis::cmd("DEL")
+            .arg(key)
+            .query_async::<()>(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    net::IpAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use http::HeaderMap;
+use maxminddb::{geoip2, Reader};
+
+pub const GEOIP_DB_BUCKET: &str = "yral-geoip";
+pub const GEOIP_DB_OBJECT: &str = "GeoLite2-Country.mmdb";
+/// GeoLite2 databases are released twice a week, a weekly refresh is enough for countries
+pub const GEOIP_REFRESH_INTERVAL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+pub trait CountryLookup: Send + Sync {
+    /// ISO 3166-1 alpha-2 code of the country the ip is registered in
+    fn lookup_country(&self, ip: IpAddr) -> Option<String>;
+}
+
+impl CountryLookup for Reader<Vec<u8>> {
+    fn lookup_country(&self, ip: IpAddr) -> Option<String> {
+        let country: geoip2::Country = self.lookup(ip).ok()?;
+        country.country?.iso_code.map(str::to_string)
+    }
+}
+
+/// The client as seen by the Fly proxy, `Fly-Client-IP` or else the hop the proxy appended to
+/// `X-Forwarded-For`. Earlier hops come from the client and are not trusted.
+pub fn client_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    if let Some(ip) = headers.get("fly-client-ip") {
+        return ip.to_str().ok()?.trim().parse().ok();
+    }
+
+    headers
+        .get("x-forwarded-for")?
+        .to_str()
+        .ok()?
+        .rsplit(',')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Resolves ips to countries with the database loaded last, resolves nothing until one is loaded
+#[derive(Clone, Default)]
+pub struct GeoIpResolver {
+    db: Arc<RwLock<Option<Arc<dyn CountryLookup>>>>,
+}
+
+impl GeoIpResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_lookup(lookup: impl CountryLookup + 'static) -> Self {
+        let resolver = Self::new();
+        resolver.set_lookup(lookup);
+        resolver
+    }
+
+    pub fn set_lookup(&self, lookup: impl CountryLookup + 'static) {
+        *self.db.write().unwrap() = Some(Arc::new(lookup));
+    }
+
+    pub fn load_mmdb(&self, bytes: Vec<u8>) -> Result<(), anyhow::Error> {
+        self.set_lookup(Reader::from_source(bytes)?);
+        Ok(())
+    }
+
+    pub fn country_code(&self, ip: IpAddr) -> Option<String> {
+        let db = self.db.read().unwrap().clone()?;
+        db.lookup_country(ip)
+            .filter(|code| code.len() == 2)
+            .map(|code| code.to_ascii_uppercase())
+    }
+
+    pub fn country_code_from_headers(&self, headers: &HeaderMap) -> Option<String> {
+        self.country_code(client_ip(headers)?)
+    }
+
+    pub async fn refresh_from_gcs(
+        &self,
+        gcs_client: &cloud_storage::Client,
+    ) -> Result<(), anyhow::Error> {
+        let bytes = gcs_client
+            .object()
+            .download(GEOIP_DB_BUCKET, GEOIP_DB_OBJECT)
+            .await?;
+        self.load_mmdb(bytes)
+    }
+
+    /// Loads the database right away and then every [`GEOIP_REFRESH_INTERVAL`], a failed
+    /// refresh keeps the previous database
+    pub async fn run_refresh(self, gcs_client: Arc<cloud_storage::Client>) {
+        let mut interval = tokio::time::interval(GEOIP_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            match self.refresh_from_gcs(&gcs_client).await {
+                Ok(()) => log::info!("Loaded GeoIP database {}", GEOIP_DB_OBJECT),
+                Err(e) => log::error!("Failed to load GeoIP database: {}", e),
+            }
+        }
+    }
+}
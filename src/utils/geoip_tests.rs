@@ -0,0 +1,93 @@
+use std::{collections::HashMap, net::IpAddr};
+
+use http::{HeaderMap, HeaderValue};
+
+use super::geoip::{client_ip, CountryLookup, GeoIpResolver};
+
+/// Known ip and country pairs, as in the GeoLite2 country database
+struct MockDb(HashMap<IpAddr, &'static str>);
+
+impl CountryLookup for MockDb {
+    fn lookup_country(&self, ip: IpAddr) -> Option<String> {
+        self.0.get(&ip).map(|code| code.to_string())
+    }
+}
+
+fn resolver() -> GeoIpResolver {
+    GeoIpResolver::with_lookup(MockDb(HashMap::from([
+        ("8.8.8.8".parse().unwrap(), "US"),
+        ("81.2.69.142".parse().unwrap(), "GB"),
+        ("2001:218::1".parse().unwrap(), "jp"),
+        ("49.207.0.1".parse().unwrap(), "IN"),
+        ("10.0.0.1".parse().unwrap(), "EU-private"),
+    ])))
+}
+
+fn forwarded_for(value: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", HeaderValue::from_static(value));
+    headers
+}
+
+#[test]
+fn test_client_ip_is_proxy_appended_hop() {
+    assert_eq!(
+        client_ip(&forwarded_for("10.0.0.1, 172.16.0.2, 81.2.69.142")),
+        Some("81.2.69.142".parse().unwrap())
+    );
+    assert_eq!(
+        client_ip(&forwarded_for(" 2001:218::1 ")),
+        Some("2001:218::1".parse().unwrap())
+    );
+    assert_eq!(client_ip(&forwarded_for("81.2.69.142, unknown")), None);
+    assert_eq!(client_ip(&HeaderMap::new()), None);
+}
+
+#[test]
+fn test_fly_client_ip_is_preferred() {
+    let mut headers = forwarded_for("8.8.8.8, 66.241.124.1");
+    headers.insert("fly-client-ip", HeaderValue::from_static("49.207.0.1"));
+
+    assert_eq!(client_ip(&headers), Some("49.207.0.1".parse().unwrap()));
+}
+
+#[test]
+fn test_known_ips_resolve_to_country_codes() {
+    let resolver = resolver();
+
+    for (ip, country) in [
+        ("8.8.8.8", "US"),
+        ("81.2.69.142", "GB"),
+        ("2001:218::1", "JP"),
+        ("49.207.0.1", "IN"),
+    ] {
+        assert_eq!(
+            resolver.country_code(ip.parse().unwrap()).as_deref(),
+            Some(country)
+        );
+    }
+    assert_eq!(resolver.country_code("1.1.1.1".parse().unwrap()), None);
+    assert_eq!(resolver.country_code("10.0.0.1".parse().unwrap()), None);
+}
+
+#[test]
+fn test_country_code_from_headers() {
+    let resolver = resolver();
+
+    assert_eq!(
+        resolver
+            .country_code_from_headers(&forwarded_for("8.8.8.8, 49.207.0.1"))
+            .as_deref(),
+        Some("IN")
+    );
+    assert_eq!(resolver.country_code_from_headers(&HeaderMap::new()), None);
+}
+
+#[test]
+fn test_nothing_resolves_before_a_database_is_loaded() {
+    let resolver = GeoIpResolver::new();
+    assert_eq!(resolver.country_code("8.8.8.8".parse().unwrap()), None);
+
+    assert!(resolver.load_mmdb(b"not an mmdb".to_vec()).is_err());
+    assert_eq!(resolver.country_code("8.8.8.8".parse().unwrap()), None);
+}
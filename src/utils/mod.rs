@@ -1,5 +1,23 @@
+pub mod agent_pool;
 pub mod api_response;
 pub mod cf_images;
+pub mod cf_stream;
+pub mod claim_store;
 pub mod delegated_identity;
+pub mod geoip;
 pub mod grpc_clients;
+pub mod notifications;
+pub mod rate_limit;
 pub mod time;
+pub mod token_cache;
+
+#[cfg(test)]
+mod agent_pool_tests;
+#[cfg(test)]
+mod geoip_tests;
+#[cfg(test)]
+mod notifications_tests;
+#[cfg(test)]
+mod rate_limit_tests;
+#[cfg(test)]
+mod token_cache_tests;
@@ -0,0 +1,160 @@
+use std::env;
+
+use serde_json::{json, Value};
+
+const SLACK_POST_MESSAGE_URL: &str = "https://slack.com/api/chat.postMessage";
+
+/// A destination for plain text alerts
+#[tonic::async_trait]
+pub trait NotificationBackend {
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, message: &str) -> Result<(), anyhow::Error>;
+}
+
+pub type NotificationBackends = Vec<Box<dyn NotificationBackend + Send + Sync>>;
+
+pub fn google_chat_body(message: &str) -> Value {
+    json!({ "text": message })
+}
+
+pub fn slack_body(channel: &str, message: &str) -> Value {
+    json!({
+        "channel": channel,
+        "text": message,
+        "mrkdwn": true,
+    })
+}
+
+/// Slack answers API errors with a 200 and `ok: false`
+pub fn check_slack_response(response: &Value) -> Result<(), anyhow::Error> {
+    if response.get("ok").and_then(Value::as_bool) == Some(true) {
+        return Ok(());
+    }
+
+    let error = response
+        .get("error")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown error");
+    Err(anyhow::anyhow!("Slack chat.postMessage failed: {}", error))
+}
+
+pub struct GoogleChatBackend {
+    client: reqwest::Client,
+    webhook_url: String,
+}
+
+impl GoogleChatBackend {
+    pub fn new(webhook_url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            webhook_url,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl NotificationBackend for GoogleChatBackend {
+    fn name(&self) -> &'static str {
+        "google_chat"
+    }
+
+    async fn send(&self, message: &str) -> Result<(), anyhow::Error> {
+        self.client
+            .post(&self.webhook_url)
+            .json(&google_chat_body(message))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+pub struct SlackBackend {
+    client: reqwest::Client,
+    bot_token: String,
+    channel: String,
+}
+
+impl SlackBackend {
+    pub fn new(bot_token: String, channel: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            bot_token,
+            channel,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl NotificationBackend for SlackBackend {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn send(&self, message: &str) -> Result<(), anyhow::Error> {
+        let response: Value = self
+            .client
+            .post(SLACK_POST_MESSAGE_URL)
+            .bearer_auth(&self.bot_token)
+            .json(&slack_body(&self.channel, message))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        check_slack_response(&response)
+    }
+}
+
+/// Backends with their environment configured, Google Chat keeps the existing webhook variable
+pub fn init_notification_backends() -> NotificationBackends {
+    let mut backends: NotificationBackends = Vec::new();
+
+    if let Ok(webhook_url) = env::var("CANISTER_BACKUP_ALERT_GOOGLE_CHAT_WEBHOOK_URL") {
+        backends.push(Box::new(GoogleChatBackend::new(webhook_url)));
+    }
+    if let (Ok(bot_token), Ok(channel)) = (
+        env::var("SLACK_ALERT_BOT_TOKEN"),
+        env::var("SLACK_ALERT_CHANNEL"),
+    ) {
+        backends.push(Box::new(SlackBackend::new(bot_token, channel)));
+    }
+
+    if backends.is_empty() {
+        log::warn!("No notification backend configured, alerts will only be logged");
+    }
+
+    backends
+}
+
+/// Sends the message to every backend, failing only if none of them got it
+pub async fn notify_all(
+    backends: &[Box<dyn NotificationBackend + Send + Sync>],
+    message: &str,
+) -> Result<(), anyhow::Error> {
+    if backends.is_empty() {
+        log::warn!("Alert not delivered, no notification backend: {}", message);
+        return Ok(());
+    }
+
+    let results = futures::future::join_all(backends.iter().map(|backend| async move {
+        let res = backend.send(message).await;
+        if let Err(e) = &res {
+            log::error!("Failed to send alert to {}: {}", backend.name(), e);
+        }
+        res
+    }))
+    .await;
+
+    if results.iter().all(Result::is_err) {
+        return Err(anyhow::anyhow!(
+            "alert could not be sent to any of {} backends",
+            backends.len()
+        ));
+    }
+
+    Ok(())
+}
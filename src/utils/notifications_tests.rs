@@ -0,0 +1,101 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use serde_json::json;
+
+use super::notifications::{
+    check_slack_response, google_chat_body, notify_all, slack_body, NotificationBackend,
+    NotificationBackends,
+};
+
+struct MockBackend {
+    fail: bool,
+    sent: Arc<AtomicUsize>,
+}
+
+#[tonic::async_trait]
+impl NotificationBackend for MockBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn send(&self, _message: &str) -> Result<(), anyhow::Error> {
+        if self.fail {
+            return Err(anyhow::anyhow!("webhook down"));
+        }
+        self.sent.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+fn backends(fails: &[bool], sent: &Arc<AtomicUsize>) -> NotificationBackends {
+    fails
+        .iter()
+        .map(|&fail| {
+            Box::new(MockBackend {
+                fail,
+                sent: sent.clone(),
+            }) as Box<dyn NotificationBackend + Send + Sync>
+        })
+        .collect()
+}
+
+#[test]
+fn test_google_chat_body() {
+    assert_eq!(
+        google_chat_body("backup failed"),
+        json!({ "text": "backup failed" })
+    );
+}
+
+#[test]
+fn test_slack_body_targets_channel() {
+    assert_eq!(
+        slack_body("#alerts", "backup failed"),
+        json!({ "channel": "#alerts", "text": "backup failed", "mrkdwn": true })
+    );
+}
+
+#[test]
+fn test_slack_response_errors_are_surfaced() {
+    assert!(check_slack_response(&json!({ "ok": true, "ts": "1700000000.000100" })).is_ok());
+
+    let err =
+        check_slack_response(&json!({ "ok": false, "error": "channel_not_found" })).unwrap_err();
+    assert!(err.to_string().contains("channel_not_found"));
+
+    assert!(check_slack_response(&json!({})).is_err());
+}
+
+#[tokio::test]
+async fn test_notify_all_sends_to_every_backend() {
+    let sent = Arc::new(AtomicUsize::new(0));
+
+    notify_all(&backends(&[false, false], &sent), "alert")
+        .await
+        .unwrap();
+
+    assert_eq!(sent.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_notify_all_tolerates_a_failing_backend() {
+    let sent = Arc::new(AtomicUsize::new(0));
+
+    notify_all(&backends(&[true, false], &sent), "alert")
+        .await
+        .unwrap();
+
+    assert_eq!(sent.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_notify_all_fails_when_no_backend_delivers() {
+    let sent = Arc::new(AtomicUsize::new(0));
+
+    assert!(notify_all(&backends(&[true, true], &sent), "alert")
+        .await
+        .is_err());
+}
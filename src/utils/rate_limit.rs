@@ -0,0 +1,70 @@
+use crate::{types::RedisPool, AppError};
+
+pub(crate) trait RateLimitStore {
+    /// Increments the counter, expiring it `ttl_secs` after its first increment, and returns
+    /// the new count
+    async fn increment(&self, key: &str, ttl_secs: u64) -> Result<u64, anyhow::Error>;
+}
+
+impl RateLimitStore for RedisPool {
+    async fn increment(&self, key: &str, ttl_secs: u64) -> Result<u64, anyhow::Error> {
+        let mut conn = self.get().await?;
+        let count: u64 = redis::cmd("INCR").arg(key).query_async(&mut *conn).await?;
+        if count == 1 {
+            redis::cmd("EXPIRE")
+                .arg(key)
+                .arg(ttl_secs)
+                .query_async::<()>(&mut *conn)
+                .await?;
+        }
+
+        Ok(count)
+    }
+}
+
+/// Fixed window limit of `max_requests` per caller every `window_secs`
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub name: &'static str,
+    pub max_requests: u64,
+    pub window_secs: u64,
+}
+
+impl RateLimit {
+    pub fn key(&self, caller: &str, now_secs: u64) -> String {
+        format!(
+            "rate_limit:{}:{}:{}",
+            self.name,
+            caller,
+            now_secs / self.window_secs
+        )
+    }
+
+    /// Counts the request against the caller's current window. The limit is not enforced
+    /// while the store is unavailable.
+    pub async fn check(
+        &self,
+        store: &impl RateLimitStore,
+        caller: &str,
+        now_secs: u64,
+    ) -> Result<(), AppError> {
+        let count = match store
+            .increment(&self.key(caller, now_secs), self.window_secs)
+            .await
+        {
+            Ok(count) => count,
+            Err(e) => {
+                log::warn!("Failed to rate limit {} for {}: {}", self.name, caller, e);
+                return Ok(());
+            }
+        };
+
+        if count > self.max_requests {
+            return Err(AppError::RateLimited {
+                retry_after_secs: self.window_secs - now_secs % self.window_secs,
+            });
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::AppError;
+
+use super::rate_limit::{RateLimit, RateLimitStore};
+
+const LIMIT: RateLimit = RateLimit {
+    name: "test",
+    max_requests: 2,
+    window_secs: 60,
+};
+
+#[derive(Default)]
+struct MockRedis {
+    counts: Mutex<HashMap<String, u64>>,
+    unavailable: bool,
+}
+
+impl RateLimitStore for MockRedis {
+    async fn increment(&self, key: &str, _ttl_secs: u64) -> Result<u64, anyhow::Error> {
+        if self.unavailable {
+            return Err(anyhow::anyhow!("connection refused"));
+        }
+
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(key.to_string()).or_default();
+        *count += 1;
+        Ok(*count)
+    }
+}
+
+#[tokio::test]
+async fn test_requests_past_the_limit_are_rejected_until_the_window_ends() {
+    let store = MockRedis::default();
+
+    assert!(LIMIT.check(&store, "1.2.3.4", 120).await.is_ok());
+    assert!(LIMIT.check(&store, "1.2.3.4", 130).await.is_ok());
+    match LIMIT.check(&store, "1.2.3.4", 150).await {
+        Err(AppError::RateLimited { retry_after_secs }) => assert_eq!(retry_after_secs, 30),
+        other => panic!("expected rate limit, got {:?}", other),
+    }
+
+    assert!(LIMIT.check(&store, "1.2.3.4", 180).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_callers_are_limited_separately() {
+    let store = MockRedis::default();
+
+    for _ in 0..2 {
+        assert!(LIMIT.check(&store, "1.2.3.4", 0).await.is_ok());
+    }
+    assert!(LIMIT.check(&store, "1.2.3.4", 0).await.is_err());
+    assert!(LIMIT.check(&store, "5.6.7.8", 0).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_unavailable_store_does_not_reject() {
+    let store = MockRedis {
+        unavailable: true,
+        ..Default::default()
+    };
+
+    for _ in 0..5 {
+        assert!(LIMIT.check(&store, "1.2.3.4", 0).await.is_ok());
+    }
+}
+
+#[test]
+fn test_key_names_the_window() {
+    assert_eq!(LIMIT.key("1.2.3.4", 59), "rate_limit:test:1.2.3.4:0");
+    assert_eq!(LIMIT.key("1.2.3.4", 60), "rate_limit:test:1.2.3.4:1");
+}
@@ -0,0 +1,95 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+
+/// Tokens closer than this to expiry are refreshed
+pub const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+/// Lifetime assumed for tokens without an expiration, shorter than Google's one hour
+pub const UNKNOWN_EXPIRY_TOKEN_LIFETIME: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Clone)]
+struct CachedToken {
+    token: String,
+    expires_at: Instant,
+}
+
+impl CachedToken {
+    fn is_valid(&self) -> bool {
+        self.expires_at > Instant::now() + TOKEN_REFRESH_MARGIN
+    }
+}
+
+type TokenSlot = Arc<RwLock<Option<CachedToken>>>;
+
+/// When a token expiring at `expiration_unix` expires on the monotonic clock, tokens without an
+/// expiration last [`UNKNOWN_EXPIRY_TOKEN_LIFETIME`]
+pub fn token_expires_at(expiration_unix: Option<i64>, now_unix: i64, now: Instant) -> Instant {
+    match expiration_unix {
+        Some(expiration) => {
+            now + Duration::from_secs(expiration.saturating_sub(now_unix).max(0) as u64)
+        }
+        None => now + UNKNOWN_EXPIRY_TOKEN_LIFETIME,
+    }
+}
+
+/// Caches the Google service account access tokens between calls, one per scope set
+#[derive(Debug, Default)]
+pub struct GoogleTokenCache {
+    tokens: Mutex<HashMap<Vec<String>, TokenSlot>>,
+}
+
+impl GoogleTokenCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Slot of the scope set, the order of the scopes doesn't matter
+    fn slot(&self, scopes: &[&str]) -> TokenSlot {
+        let mut key: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+        key.sort();
+        key.dedup();
+
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key)
+            .or_default()
+            .clone()
+    }
+
+    /// Returns the cached token for `scopes`, or calls `refresh` for a new `(token, expires_at)`.
+    /// Concurrent callers with a stale cache wait for a single refresh.
+    pub async fn get_or_refresh<F, Fut, E>(&self, scopes: &[&str], refresh: F) -> Result<String, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(String, Instant), E>>,
+    {
+        let slot = self.slot(scopes);
+        if let Some(cached) = slot.read().await.as_ref() {
+            if cached.is_valid() {
+                return Ok(cached.token.clone());
+            }
+        }
+
+        let mut guard = slot.write().await;
+        // another caller may have refreshed while we waited for the write lock
+        if let Some(cached) = guard.as_ref() {
+            if cached.is_valid() {
+                return Ok(cached.token.clone());
+            }
+        }
+
+        let (token, expires_at) = refresh().await?;
+        *guard = Some(CachedToken {
+            token: token.clone(),
+            expires_at,
+        });
+
+        Ok(token)
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use super::token_cache::{token_expires_at, GoogleTokenCache, UNKNOWN_EXPIRY_TOKEN_LIFETIME};
+
+const SCOPES: &[&str] = &["https://www.googleapis.com/auth/bigquery.insertdata"];
+
+async fn refresh_counting(
+    calls: &AtomicUsize,
+    valid_for: Duration,
+) -> Result<(String, Instant), ()> {
+    let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+    // widen the race window for concurrent callers
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    Ok((format!("token-{n}"), Instant::now() + valid_for))
+}
+
+#[tokio::test]
+async fn test_valid_token_is_reused() {
+    let cache = GoogleTokenCache::new();
+    let calls = AtomicUsize::new(0);
+
+    for _ in 0..3 {
+        let token = cache
+            .get_or_refresh(SCOPES, || {
+                refresh_counting(&calls, Duration::from_secs(3600))
+            })
+            .await
+            .unwrap();
+        assert_eq!(token, "token-1");
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_token_near_expiry_is_refreshed() {
+    let cache = GoogleTokenCache::new();
+    let calls = AtomicUsize::new(0);
+
+    // valid for less than the refresh margin
+    cache
+        .get_or_refresh(SCOPES, || refresh_counting(&calls, Duration::from_secs(30)))
+        .await
+        .unwrap();
+    let token = cache
+        .get_or_refresh(SCOPES, || {
+            refresh_counting(&calls, Duration::from_secs(3600))
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(token, "token-2");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_different_scopes_are_not_shared() {
+    let cache = GoogleTokenCache::new();
+    let calls = AtomicUsize::new(0);
+
+    cache
+        .get_or_refresh(SCOPES, || {
+            refresh_counting(&calls, Duration::from_secs(3600))
+        })
+        .await
+        .unwrap();
+    let token = cache
+        .get_or_refresh(&["https://www.googleapis.com/auth/datastore"], || {
+            refresh_counting(&calls, Duration::from_secs(3600))
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(token, "token-2");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn test_concurrent_callers_refresh_once() {
+    let cache = Arc::new(GoogleTokenCache::new());
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let handles: Vec<_> = (0..64)
+        .map(|_| {
+            let cache = cache.clone();
+            let calls = calls.clone();
+            tokio::spawn(async move {
+                cache
+                    .get_or_refresh(SCOPES, || {
+                        refresh_counting(&calls, Duration::from_secs(3600))
+                    })
+                    .await
+                    .unwrap()
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        assert_eq!(handle.await.unwrap(), "token-1");
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_scope_sets_do_not_evict_each_other() {
+    let cache = GoogleTokenCache::new();
+    let calls = AtomicUsize::new(0);
+    let monitoring = &["https://www.googleapis.com/auth/monitoring.read"];
+
+    for _ in 0..2 {
+        cache
+            .get_or_refresh(SCOPES, || {
+                refresh_counting(&calls, Duration::from_secs(3600))
+            })
+            .await
+            .unwrap();
+        cache
+            .get_or_refresh(monitoring, || {
+                refresh_counting(&calls, Duration::from_secs(3600))
+            })
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_scope_order_does_not_matter() {
+    let cache = GoogleTokenCache::new();
+    let calls = AtomicUsize::new(0);
+
+    cache
+        .get_or_refresh(&["a", "b"], || {
+            refresh_counting(&calls, Duration::from_secs(3600))
+        })
+        .await
+        .unwrap();
+    let token = cache
+        .get_or_refresh(&["b", "a"], || {
+            refresh_counting(&calls, Duration::from_secs(3600))
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(token, "token-1");
+}
+
+#[tokio::test]
+async fn test_refresh_error_is_returned() {
+    let cache = GoogleTokenCache::new();
+
+    let res = cache
+        .get_or_refresh(SCOPES, || async { Err::<(String, Instant), _>("no token") })
+        .await;
+
+    assert_eq!(res, Err("no token"));
+}
+
+#[test]
+fn test_token_expiry() {
+    let now = Instant::now();
+
+    assert_eq!(
+        token_expires_at(Some(1_700_003_600), 1_700_000_000, now),
+        now + Duration::from_secs(3600)
+    );
+    // already expired
+    assert_eq!(token_expires_at(Some(10), 20, now), now);
+    assert_eq!(
+        token_expires_at(None, 20, now),
+        now + UNKNOWN_EXPIRY_TOKEN_LIFETIME
+    );
+}